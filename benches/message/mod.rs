@@ -4,6 +4,7 @@ use std::io::Read;
 use test::{black_box, Bencher};
 
 use pgp::composed::{Deserializable, Message, SignedSecretKey};
+use pgp::crypto::sym::SymmetricKeyAlgorithm;
 
 #[cfg(feature = "profile")]
 use gperftools::profiler::PROFILER;
@@ -94,3 +95,30 @@ fn bench_message_parse_decrypt_x25519(b: &mut Bencher) {
     });
     stop_profile();
 }
+
+/// The RSA encryption subkey of the GnuPG test key, used for 100 recipients.
+fn rsa_recipient() -> SignedSecretKey {
+    let mut key_file =
+        File::open("./tests/opengpg-interop/testcases/messages/gnupg-v1-001-decrypt.asc").unwrap();
+    let (key, _headers) = SignedSecretKey::from_armor_single(&mut key_file).unwrap();
+    key
+}
+
+/// With the `rayon` feature, the session key is encrypted to the recipients in parallel.
+#[bench]
+fn bench_message_encrypt_100_recipients_rsa(b: &mut Bencher) {
+    let key = rsa_recipient();
+    let recipients = vec![&key.secret_subkeys[0]; 100];
+    let message = Message::new_literal("hello.txt", "hello world\n");
+    let mut rng = rand::thread_rng();
+
+    start_profile("message_encrypt_100_recipients_rsa");
+    b.iter(|| {
+        black_box(
+            message
+                .encrypt_to_keys(&mut rng, SymmetricKeyAlgorithm::AES128, &recipients)
+                .unwrap(),
+        )
+    });
+    stop_profile();
+}
@@ -0,0 +1,65 @@
+/*
+ * C API of rPGP, available when building the `pgp` crate with the `ffi` feature, e.g.
+ *
+ *     cargo rustc --release --features ffi --crate-type cdylib
+ *
+ * Handles must be released with the matching `rpgp_*_free` function. Functions returning a
+ * pointer return NULL on failure, functions returning an int return 0 on success and -1 on
+ * failure. `rpgp_last_error` returns the message of the last failure on the calling thread.
+ *
+ * Keys, messages and signatures are accepted both armored and binary.
+ */
+
+#ifndef RPGP_H
+#define RPGP_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct rpgp_buffer rpgp_buffer;
+typedef struct rpgp_public_key rpgp_public_key;
+typedef struct rpgp_secret_key rpgp_secret_key;
+
+/* Errors and strings */
+
+char *rpgp_last_error(void);
+void rpgp_string_free(char *s);
+
+/* Buffers */
+
+const uint8_t *rpgp_buffer_data(const rpgp_buffer *buffer);
+size_t rpgp_buffer_len(const rpgp_buffer *buffer);
+void rpgp_buffer_free(rpgp_buffer *buffer);
+
+/* Keys */
+
+rpgp_public_key *rpgp_public_key_parse(const uint8_t *data, size_t len);
+char *rpgp_public_key_fingerprint(const rpgp_public_key *key);
+rpgp_buffer *rpgp_public_key_serialize(const rpgp_public_key *key, bool armor);
+void rpgp_public_key_free(rpgp_public_key *key);
+
+rpgp_secret_key *rpgp_secret_key_parse(const uint8_t *data, size_t len);
+rpgp_public_key *rpgp_secret_key_public_key(const rpgp_secret_key *key);
+void rpgp_secret_key_free(rpgp_secret_key *key);
+
+/* Operations, `password` may be NULL for unprotected keys */
+
+rpgp_buffer *rpgp_encrypt(const uint8_t *data, size_t len,
+                          const rpgp_public_key *const *keys, size_t keys_len);
+rpgp_buffer *rpgp_decrypt(const uint8_t *data, size_t len, const rpgp_secret_key *key,
+                          const char *password);
+rpgp_buffer *rpgp_sign_detached(const uint8_t *data, size_t len, const rpgp_secret_key *key,
+                                const char *password);
+int rpgp_verify_detached(const uint8_t *data, size_t len, const uint8_t *sig, size_t sig_len,
+                         const rpgp_public_key *key);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RPGP_H */
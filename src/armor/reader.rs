@@ -4,22 +4,17 @@ use std::io::prelude::*;
 use std::{fmt, io, str};
 
 use base64::engine::{general_purpose::STANDARD, Engine as _};
-use buffer_redux::BufReader;
 use byteorder::{BigEndian, ByteOrder};
 
 use nom::branch::alt;
+use nom::bytes::complete::take_while1;
 use nom::bytes::streaming::tag;
-use nom::bytes::streaming::{take, take_until};
 use nom::character::streaming::{digit1, line_ending, not_line_ending, space0};
-use nom::combinator::{complete, map, map_res, opt, success, value};
-use nom::multi::many0;
-use nom::sequence::{delimited, pair, preceded, terminated};
+use nom::combinator::{map, map_res, opt, value};
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
 use nom::{IResult, InputIter, InputLength, Slice};
 
-use crate::base64_decoder::Base64Decoder;
-use crate::base64_reader::Base64Reader;
 use crate::errors::Result;
-use crate::line_reader::LineReader;
 use crate::ser::Serialize;
 
 /// Armor block types.
@@ -179,7 +174,7 @@ fn armor_header_line(i: &[u8]) -> IResult<&[u8], BlockType> {
     delimited(
         pair(armor_header_sep, tag(b"BEGIN ")),
         armor_header_type,
-        pair(armor_header_sep, line_ending),
+        tuple((armor_header_sep, space0, line_ending)),
     )(i)
 }
 
@@ -221,26 +216,6 @@ fn key_value_pair(i: &[u8]) -> IResult<&[u8], (&str, &str)> {
     )(i)
 }
 
-/// Parses a list of key value pairs.
-fn key_value_pairs(i: &[u8]) -> IResult<&[u8], Vec<(&str, &str)>> {
-    many0(complete(key_value_pair))(i)
-}
-
-/// Parses the full armor header.
-fn armor_headers(i: &[u8]) -> IResult<&[u8], BTreeMap<String, String>> {
-    map(key_value_pairs, |pairs| {
-        pairs
-            .iter()
-            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
-            .collect()
-    })(i)
-}
-
-/// Armor Header
-fn armor_header(i: &[u8]) -> IResult<&[u8], (BlockType, BTreeMap<String, String>)> {
-    pair(armor_header_line, armor_headers)(i)
-}
-
 /// Read the checksum from an base64 encoded buffer.
 fn read_checksum(input: &[u8]) -> ::std::io::Result<u64> {
     let checksum = STANDARD
@@ -257,43 +232,20 @@ fn read_checksum(input: &[u8]) -> ::std::io::Result<u64> {
     Ok(u64::from(BigEndian::read_u32(&buf)))
 }
 
-fn header_parser(i: &[u8]) -> IResult<&[u8], (BlockType, BTreeMap<String, String>)> {
-    delimited(
-        take_until(b"-----".as_slice()),
-        armor_header,
-        many0(pair(space0, line_ending)),
-    )(i)
-}
-
-fn footer_parser(i: &[u8]) -> IResult<&[u8], (Option<&[u8]>, BlockType)> {
-    pair(
-        alt((
-            delimited(
-                tag(b"="),
-                map(take(4u8), Some),
-                pair(many0(line_ending), tag(b"--")),
-            ),
-            delimited(
-                many0(tag(b"=")),
-                success(None),
-                pair(many0(line_ending), tag(b"--")),
-            ),
-        )),
-        armor_footer_line,
-    )(i)
-}
-
-/// Parses a single armor footer line
+/// Parses a single armor footer line, without the line ending.
+///
+/// The number of trailing dashes is not checked, as some tools get it wrong.
 fn armor_footer_line(i: &[u8]) -> IResult<&[u8], BlockType> {
-    // Only 3, because we parsed two already in the `footer_parser`.
     delimited(
-        tag(b"---END "),
+        tag(b"-----END "),
         armor_header_type,
-        pair(armor_header_sep, opt(complete(line_ending))),
+        take_while1(|c| c == b'-'),
     )(i)
 }
 
 /// Streaming based ascii armor parsing.
+///
+/// Reads the header on first use, and decodes the body with an [`ArmorReader`].
 pub struct Dearmor<R> {
     /// The ascii armor parsed block type.
     pub typ: Option<BlockType>,
@@ -301,213 +253,431 @@ pub struct Dearmor<R> {
     pub headers: BTreeMap<String, String>,
     /// Optional crc checksum
     pub checksum: Option<u64>,
-    /// track what we are currently parsing
-    current_part: Part,
-    /// the underlying data source, wrapped in a BufferedReader
-    inner: Option<BufReader<R>>,
-    /// base64 decoder
-    base_decoder: Option<
-        Base64Decoder<base64::engine::GeneralPurpose, Base64Reader<LineReader<BufReader<R>>>>,
-    >,
-    /// Are we done?
-    done: bool,
-    crc: crc24::Crc24Hasher,
-}
-
-/// Internal indicator, where in the parsing phase we are
-#[derive(Debug, PartialEq, Eq)]
-enum Part {
-    Header,
-    Body,
-    Footer,
+    /// the underlying data source, until the header was read
+    input: Option<R>,
+    reader: Option<ArmorReader<io::BufReader<R>>>,
 }
 
 const CAPACITY: usize = 1024 * 32;
 
-impl<R: Read + Seek> Dearmor<R> {
+impl<R: Read> Dearmor<R> {
     pub fn new(input: R) -> Self {
         Dearmor {
             typ: None,
             headers: BTreeMap::new(),
             checksum: None,
-            current_part: Part::Header,
-            base_decoder: None,
-            inner: Some(BufReader::with_capacity(CAPACITY, input)),
-            done: false,
-            crc: Default::default(),
+            input: Some(input),
+            reader: None,
         }
     }
 
     pub fn read_header(&mut self) -> io::Result<()> {
-        if let Some(ref mut b) = self.inner {
-            b.read_into_buf()?;
-
-            // no data available currently
-            if b.buf_len() == 0 {
-                return Err(io::Error::new(io::ErrorKind::Interrupted, "empty buffer"));
-            }
+        let input = self.input.take().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "ascii armor header was already read")
+        })?;
+        let reader = ArmorReader::new(io::BufReader::with_capacity(CAPACITY, input))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        self.typ = Some(reader.typ());
+        self.headers = reader.headers().clone();
+        self.reader = Some(reader);
 
-            let consumed = match header_parser(b.buffer()) {
-                Ok((remaining, (typ, header))) => {
-                    self.typ = Some(typ);
-                    self.headers = header;
-                    self.current_part = Part::Body;
+        Ok(())
+    }
+}
 
-                    b.buf_len() - remaining.len()
-                }
-                Err(nom::Err::Incomplete(_)) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Interrupted,
-                        "incomplete parse",
-                    ));
-                }
-                Err(err) => {
-                    self.done = true;
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("invalid ascii armor header: {err:?}"),
-                    ));
-                }
-            };
+impl<R: Read> Read for Dearmor<R> {
+    fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
+        if self.input.is_some() {
+            self.read_header()?;
+        }
+        let Some(ref mut reader) = self.reader else {
+            return Ok(0);
+        };
 
-            b.consume(consumed);
-        } else {
-            panic!("invalid state");
+        let size = reader.read(into)?;
+        if size == 0 && !into.is_empty() {
+            self.checksum = reader.checksum();
         }
 
-        Ok(())
+        Ok(size)
     }
+}
 
-    fn read_body(&mut self, into: &mut [u8]) -> io::Result<usize> {
-        if self.base_decoder.is_none() {
-            let b = self.inner.take().ok_or_else(|| {
-                self.done = true;
-                io::Error::new(io::ErrorKind::UnexpectedEof, "bad parser state")
-            })?;
-            self.base_decoder = Some(Base64Decoder::new(Base64Reader::new(LineReader::new(b))));
-        }
+/// The maximum length of a single line of armored input accepted by [`ArmorReader`].
+const MAX_LINE_LEN: usize = 64 * 1024;
 
-        // "allow" as workaround for https://github.com/rust-lang/rust-clippy/issues/12208
-        #[allow(clippy::unused_io_amount)]
-        let size = if let Some(ref mut base_decoder) = self.base_decoder {
-            base_decoder.read(into)?
-        } else {
-            unreachable!();
-        };
+/// Lenient base64 engine for the armor body, which accepts missing padding.
+const BODY_ENGINE: base64::engine::GeneralPurpose = base64::engine::GeneralPurpose::new(
+    &base64::alphabet::STANDARD,
+    base64::engine::GeneralPurposeConfig::new()
+        .with_decode_padding_mode(base64::engine::DecodePaddingMode::Indifferent),
+);
 
-        if size == 0 && !into.is_empty() {
-            // we are done with the body
-            self.current_part = Part::Footer;
-            self.read_footer()
-        } else if size == 0 {
-            Err(io::Error::new(
-                io::ErrorKind::UnexpectedEof,
-                "missing footer",
-            ))
-        } else {
-            // update the hash
-            self.crc.write(&into[0..size]);
+/// Ascii armor decoding, that does not require the input to be seekable.
+///
+/// The body is decoded line by line, as it is read, so memory use is bounded by the length of
+/// a single line, independent of the size of the input. The checksum, if present, is verified
+/// when the footer is reached.
+///
+/// Input as found in mails and on the web is accepted: text before the header line is skipped,
+/// and byte order marks, `\r\n` line endings, surrounding whitespace and quoted-printable
+/// `=3D` are ignored.
+///
+/// ```rust
+/// # fn main() -> pgp::errors::Result<()> {
+/// use std::io::Read;
+///
+/// use pgp::armor::{ArmorReader, BlockType};
+/// use pgp::{Deserializable, Message};
+///
+/// let armored = Message::new_literal_bytes("hello.txt", &[0u8; 100_000]).to_armored_string(None)?;
+///
+/// // `&[u8]` is not seekable, like stdin or a socket
+/// let mut reader = ArmorReader::new(armored.as_bytes())?;
+/// assert_eq!(reader.typ(), BlockType::Message);
+/// let mut bytes = Vec::new();
+/// reader.read_to_end(&mut bytes)?;
+///
+/// let msg = Message::from_bytes(&bytes[..])?;
+/// assert_eq!(msg.get_content()?.unwrap().len(), 100_000);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ArmorReader<R> {
+    inner: R,
+    typ: BlockType,
+    headers: BTreeMap<String, String>,
+    /// The current line of input.
+    line: Vec<u8>,
+    /// Base64 characters that do not yet form a full group of 4.
+    pending: Vec<u8>,
+    /// Decoded data that was not read yet.
+    decoded: Vec<u8>,
+    pos: usize,
+    checksum: Option<u64>,
+    crc: crc24::Crc24Hasher,
+    tolerate_checksum_mismatch: bool,
+    checksum_mismatch: bool,
+    done: bool,
+}
 
-            Ok(size)
+impl<R: BufRead> ArmorReader<R> {
+    /// Reads the armor header line and headers from `inner`, skipping any text before them.
+    pub fn new(inner: R) -> Result<Self> {
+        Self::try_new(inner)?.ok_or_else(|| format_err!("missing ascii armor header"))
+    }
+
+    /// Like [`ArmorReader::new`], but returns `None` if there is no armor header in the input.
+    fn try_new(mut inner: R) -> Result<Option<Self>> {
+        let mut line = Vec::new();
+        let typ = loop {
+            if !read_line(&mut inner, &mut line)? {
+                return Ok(None);
+            }
+            let trimmed = trim(&line);
+            let trimmed = trimmed.strip_prefix(BOM).unwrap_or(trimmed);
+            if trimmed.starts_with(b"-----BEGIN ") {
+                let mut header_line = trimmed.to_vec();
+                header_line.push(b'\n');
+                // anything else, e.g. "-----BEGIN FOO-----", is skipped like any other text
+                if let Ok((_, typ)) = armor_header_line(&header_line) {
+                    break typ;
+                }
+            }
+        };
+
+        let mut headers = BTreeMap::new();
+        let mut pending = Vec::new();
+        loop {
+            ensure!(
+                read_line(&mut inner, &mut line)?,
+                "missing ascii armor body"
+            );
+            let trimmed = trim(&line);
+            if trimmed.is_empty() {
+                break;
+            }
+            if !trimmed.contains(&b':') {
+                // no blank line after the headers, this is already part of the body
+                extend_unquoted(&mut pending, trimmed);
+                break;
+            }
+            let mut header = trimmed.to_vec();
+            header.push(b'\n');
+            let (_, (key, value)) = key_value_pair(&header)
+                .map_err(|err| format_err!("invalid ascii armor header: {:?}", err))?;
+            headers.insert(key.to_string(), value.to_string());
         }
+
+        Ok(Some(ArmorReader {
+            inner,
+            typ,
+            headers,
+            line,
+            pending,
+            decoded: Vec::new(),
+            pos: 0,
+            checksum: None,
+            crc: Default::default(),
+            tolerate_checksum_mismatch: false,
+            checksum_mismatch: false,
+            done: false,
+        }))
     }
 
-    fn read_footer(&mut self) -> io::Result<usize> {
-        if self.base_decoder.is_some() {
-            let decoder = self
-                .base_decoder
-                .take()
-                .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "bad parser state"))?;
-            let (base_reader, buffer_outer) = decoder.into_inner_with_buffer();
+    pub fn typ(&self) -> BlockType {
+        self.typ
+    }
 
-            let line_reader: LineReader<_> = base_reader.into_inner();
-            let mut b = BufReader::with_buffer(buffer_outer, line_reader.into_inner());
-            b.make_room();
+    pub fn headers(&self) -> &BTreeMap<String, String> {
+        &self.headers
+    }
 
-            b.read_into_buf()?;
-            if b.buf_len() == 0 {
-                // we are done here
-                return Ok(0);
-            }
+    /// The checksum from the footer, available once all data was read.
+    pub fn checksum(&self) -> Option<u64> {
+        self.checksum
+    }
 
-            let consumed = match footer_parser(b.buffer()) {
-                Ok((remaining, (checksum, footer_typ))) => {
-                    if let Some(ref header_typ) = self.typ {
-                        if header_typ != &footer_typ {
-                            self.done = true;
-                            return Err(io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                format!(
-                                    "armor ascii footer does not match header: {:?} != {:?}",
-                                    self.typ, footer_typ
-                                ),
-                            ));
-                        }
-                    }
+    /// Accept data with a checksum that does not match, logging a warning instead of failing.
+    ///
+    /// The checksum is not part of the signed or encrypted data, and is often broken by
+    /// tools that rewrap armored text.
+    pub fn tolerate_checksum_mismatch(mut self, tolerate: bool) -> Self {
+        self.tolerate_checksum_mismatch = tolerate;
+        self
+    }
 
-                    if let Some(raw) = checksum {
-                        self.checksum = Some(read_checksum(raw)?);
-                    }
+    /// Whether the checksum did not match the data, with
+    /// [`ArmorReader::tolerate_checksum_mismatch`] set.
+    pub fn checksum_mismatch(&self) -> bool {
+        self.checksum_mismatch
+    }
 
-                    b.buf_len() - remaining.len()
-                }
-                Err(nom::Err::Incomplete(_)) => {
-                    self.done = true;
-                    return Err(io::Error::new(
-                        io::ErrorKind::Interrupted,
-                        "incomplete parse",
-                    ));
-                }
-                Err(err) => {
-                    warn!(
-                        "invalid ascii armor footer: `{:?}`",
-                        ::std::str::from_utf8(b.buffer())
-                    );
-                    self.done = true;
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("invalid ascii armor footer: {err:?}"),
-                    ));
-                }
-            };
+    /// Returns the underlying reader, positioned after the footer once all data was read.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
 
-            b.consume(consumed);
-            self.done = true;
+    #[cfg(feature = "async")]
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
 
-            // check checksum if there is one
-            if let Some(expected) = self.checksum {
-                let actual = self.crc.finish();
+    /// Decodes the next line of the body, until there is data or the footer is reached.
+    fn fill(&mut self) -> Result<()> {
+        self.decoded.clear();
+        self.pos = 0;
+
+        while self.decoded.is_empty() && !self.done {
+            ensure!(
+                read_line(&mut self.inner, &mut self.line)?,
+                "missing ascii armor footer"
+            );
+            let mut line = trim(&self.line);
+            // the checksum directly followed by the footer, e.g. `=4JBj-----END PGP MESSAGE-----`
+            if line.first() == Some(&b'=') {
+                if let Some(i) = line.windows(5).position(|w| w == b"-----") {
+                    if i == 5 {
+                        self.checksum = Some(read_checksum(&line[1..i])?);
+                    }
+                    line = &line[i..];
+                }
+            }
 
-                if expected != actual {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "invalid crc24 checksum",
-                    ));
+            if line.starts_with(b"-----") {
+                let (_, typ) = armor_footer_line(line)
+                    .map_err(|err| format_err!("invalid ascii armor footer: {:?}", err))?;
+                ensure!(
+                    self.typ == typ,
+                    "armor ascii footer does not match header: {:?} != {:?}",
+                    self.typ,
+                    typ
+                );
+                self.done = true;
+                self.decode(true)?;
+
+                if let Some(expected) = self.checksum {
+                    let actual = self.crc.finish();
+                    if expected != actual {
+                        ensure!(self.tolerate_checksum_mismatch, "invalid crc24 checksum");
+                        warn!("invalid crc24 checksum: {:06x} != {:06x}", expected, actual);
+                        self.checksum_mismatch = true;
+                    }
                 }
+            } else if line.iter().all(|b| *b == b'=') {
+                // blank lines, and checksum lines without a checksum
+            } else if line.len() == 5 && line[0] == b'=' {
+                self.checksum = Some(read_checksum(&line[1..])?);
+            } else if line.len() == 7 && line.starts_with(QUOTED_EQUALS) {
+                self.checksum = Some(read_checksum(&line[3..])?);
+            } else {
+                ensure!(
+                    self.checksum.is_none(),
+                    "unexpected data after the ascii armor checksum"
+                );
+                extend_unquoted(&mut self.pending, line);
+                self.decode(false)?;
             }
-        } else {
-            panic!("invalid state");
         }
 
-        // We always return zero, as we do not write to the `into` buffer.
-        Ok(0)
+        Ok(())
     }
+
+    /// Decodes the pending full groups of base64 characters, or all of them when `last` is set.
+    fn decode(&mut self, last: bool) -> Result<()> {
+        let len = if last {
+            self.pending.len()
+        } else {
+            self.pending.len() / 4 * 4
+        };
+        let start = self.decoded.len();
+        BODY_ENGINE
+            .decode_vec(&self.pending[..len], &mut self.decoded)
+            .map_err(|err| format_err!("invalid ascii armor body: {}", err))?;
+        self.pending.drain(..len);
+        self.crc.write(&self.decoded[start..]);
+
+        Ok(())
+    }
+}
+
+/// The UTF-8 byte order mark.
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// `=` in quoted-printable encoding, as left behind by some mail clients.
+const QUOTED_EQUALS: &[u8] = b"=3D";
+
+/// Appends the base64 characters of `line` to `out`, undoing quoted-printable `=3D`, which can
+/// not occur in valid base64.
+fn extend_unquoted(out: &mut Vec<u8>, mut line: &[u8]) {
+    while let Some(i) = line
+        .windows(QUOTED_EQUALS.len())
+        .position(|w| w == QUOTED_EQUALS)
+    {
+        out.extend_from_slice(&line[..=i]);
+        line = &line[i + QUOTED_EQUALS.len()..];
+    }
+    out.extend_from_slice(line);
+}
+
+/// Strips leading and trailing ascii whitespace.
+fn trim(line: &[u8]) -> &[u8] {
+    let start = line
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(line.len());
+    let end = line
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |i| i + 1);
+    &line[start..end]
+}
+
+/// Reads a line, including its line ending, of at most [`MAX_LINE_LEN`] bytes into `line`.
+/// Returns `false` at the end of the input.
+fn read_line<R: BufRead>(inner: &mut R, line: &mut Vec<u8>) -> Result<bool> {
+    line.clear();
+    let read = inner
+        .by_ref()
+        .take(MAX_LINE_LEN as u64 + 1)
+        .read_until(b'\n', line)?;
+    ensure!(
+        line.len() <= MAX_LINE_LEN,
+        "ascii armor line exceeds {} bytes",
+        MAX_LINE_LEN
+    );
+
+    Ok(read > 0)
 }
 
-impl<R: Read + Seek> Read for Dearmor<R> {
+impl<R: BufRead> Read for ArmorReader<R> {
     fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
-        if self.done {
-            return Ok(0);
+        if self.pos == self.decoded.len() {
+            self.fill()
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
         }
 
-        match self.current_part {
-            Part::Header => {
-                self.read_header()?;
-                self.read_body(into)
-            }
-            Part::Body => self.read_body(into),
-            Part::Footer => self.read_footer(),
+        let size = into.len().min(self.decoded.len() - self.pos);
+        into[..size].copy_from_slice(&self.decoded[self.pos..self.pos + size]);
+        self.pos += size;
+
+        Ok(size)
+    }
+}
+
+/// A single block of ascii armor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArmorBlock {
+    pub typ: BlockType,
+    pub headers: BTreeMap<String, String>,
+    /// The decoded body.
+    pub data: Vec<u8>,
+}
+
+/// Reads all ascii armor blocks from `input`, e.g. a public key followed by a signature.
+///
+/// Any text between the blocks is skipped.
+///
+/// ```rust
+/// # fn main() -> pgp::errors::Result<()> {
+/// use pgp::armor::{self, BlockType};
+/// use pgp::{Deserializable, SignedPublicKey};
+///
+/// let key = std::fs::read_to_string("./tests/autocrypt/alice@autocrypt.example.pub.asc")?;
+/// let input = format!("{key}\nsome text\n{key}");
+///
+/// let mut keys = Vec::new();
+/// for block in armor::from_armor_many(input.as_bytes()) {
+///     let block = block?;
+///     assert_eq!(block.typ, BlockType::PublicKey);
+///     keys.push(SignedPublicKey::from_bytes(&block.data[..])?);
+/// }
+/// assert_eq!(keys.len(), 2);
+/// # Ok(())
+/// # }
+/// ```
+pub fn from_armor_many<R: BufRead>(input: R) -> ArmorBlocks<R> {
+    ArmorBlocks {
+        inner: input,
+        done: false,
+    }
+}
+
+/// Iterator over the ascii armor blocks in an input, created by [`from_armor_many`].
+pub struct ArmorBlocks<R> {
+    inner: R,
+    done: bool,
+}
+
+impl<R: BufRead> ArmorBlocks<R> {
+    fn read_block(&mut self) -> Result<Option<ArmorBlock>> {
+        let Some(mut reader) = ArmorReader::try_new(&mut self.inner)? else {
+            return Ok(None);
+        };
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        Ok(Some(ArmorBlock {
+            typ: reader.typ,
+            headers: reader.headers,
+            data,
+        }))
+    }
+}
+
+impl<R: BufRead> Iterator for ArmorBlocks<R> {
+    type Item = Result<ArmorBlock>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
         }
+
+        let res = self.read_block().transpose();
+        // stop at the end of the input, and after errors, as the position in the input is unknown
+        self.done = !matches!(res, Some(Ok(_)));
+
+        res
     }
 }
 
@@ -558,13 +728,11 @@ mod tests {
         map.insert("special-stuff".to_string(), "cool12.0".to_string());
         map.insert("some:colon".to_string(), "with:me".to_string());
 
-        assert_eq!(
-            armor_headers(
-                &b"Version: 12\r\nspecial-stuff: cool12.0\r\nsome:colon: with:me\r\n"[..],
-            )
-            .unwrap(),
-            (&b""[..], map)
-        );
+        let reader = ArmorReader::new(
+            &b"-----BEGIN PGP MESSAGE-----\r\nVersion: 12\r\nspecial-stuff: cool12.0\r\nsome:colon: with:me\r\n\r\naGVsbG8=\r\n-----END PGP MESSAGE-----\r\n"[..],
+        )
+        .unwrap();
+        assert_eq!(reader.headers(), &map);
     }
 
     #[test]
@@ -573,19 +741,22 @@ mod tests {
         map.insert("Version".to_string(), "1.0".to_string());
         map.insert("Mode".to_string(), "Test".to_string());
 
-        assert_eq!(
-            armor_header(&b"-----BEGIN PGP MESSAGE-----\nVersion: 1.0\nMode: Test\n"[..],).unwrap(),
-            (&b""[..], (BlockType::Message, map))
-        );
+        let reader = ArmorReader::new(
+            &b"-----BEGIN PGP MESSAGE-----\nVersion: 1.0\nMode: Test\n\naGVsbG8=\n-----END PGP MESSAGE-----\n"[..],
+        )
+        .unwrap();
+        assert_eq!(reader.typ(), BlockType::Message);
+        assert_eq!(reader.headers(), &map);
 
         let mut map = BTreeMap::new();
         map.insert("Version".to_string(), "GnuPG v1".to_string());
 
-        assert_eq!(
-            armor_header(&b"-----BEGIN PGP PUBLIC KEY BLOCK-----\nVersion: GnuPG v1\n"[..],)
-                .unwrap(),
-            (&b""[..], (BlockType::PublicKey, map))
-        );
+        let reader = ArmorReader::new(
+            &b"-----BEGIN PGP PUBLIC KEY BLOCK-----\nVersion: GnuPG v1\n\naGVsbG8=\n-----END PGP PUBLIC KEY BLOCK-----\n"[..],
+        )
+        .unwrap();
+        assert_eq!(reader.typ(), BlockType::PublicKey);
+        assert_eq!(reader.headers(), &map);
     }
 
     #[test]
@@ -824,72 +995,192 @@ y5Zgv9TWZlmW9FDTp4XVgn5zQTEN1LdL7vNXWV9aOvfrqPk5ClBkxhndgq7j6MFs
     }
 
     #[test]
-    fn test_key_value_pairs() {
-        assert_eq!(
-            key_value_pairs(&b"hello: world\ncool: stuff\n"[..]).unwrap(),
-            (&b""[..], vec![("hello", "world"), ("cool", "stuff")]),
-            "single"
-        );
+    fn test_footers() {
+        let read = |footer: &str| -> Result<(Vec<u8>, Option<u64>)> {
+            let input =
+                format!("-----BEGIN PGP PUBLIC KEY BLOCK-----\n\naGVsbG8gd29ybGQ=\n{footer}");
+            let mut reader = ArmorReader::new(input.as_bytes())?;
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes)?;
+            Ok((bytes, reader.checksum()))
+        };
 
-        assert_eq!(
-            key_value_pairs(&b"hello:\ncool: stuff\n"[..]).unwrap(),
-            (&b""[..], vec![("hello", ""), ("cool", "stuff")]),
-            "empty"
-        );
+        for footer in [
+            "-----END PGP PUBLIC KEY BLOCK-----",
+            "-----END PGP PUBLIC KEY BLOCK-----\n",
+            "=-----END PGP PUBLIC KEY BLOCK-----\n",
+            "=\n-----END PGP PUBLIC KEY BLOCK-----\n",
+            "\r\n-----END PGP PUBLIC KEY BLOCK-----\r\n",
+        ] {
+            assert_eq!(
+                read(footer).unwrap(),
+                (b"hello world".to_vec(), None),
+                "{footer:?}"
+            );
+        }
 
-        assert_eq!(
-            key_value_pairs(&b"hello: world\ncool: stuff\nother content"[..]).unwrap(),
-            (
-                &b"other content"[..],
-                vec![("hello", "world"), ("cool", "stuff")]
-            ),
-            "with rest"
-        );
-    }
+        for footer in [
+            "=sDy3-----END PGP PUBLIC KEY BLOCK-----\r\n",
+            "=sDy3\r\n-----END PGP PUBLIC KEY BLOCK-----\r\n",
+            "=sDy3\n\n-----END PGP PUBLIC KEY BLOCK-----\n",
+        ] {
+            assert_eq!(
+                read(footer).unwrap(),
+                (b"hello world".to_vec(), Some(0xb03cb7)),
+                "{footer:?}"
+            );
+        }
 
-    #[test]
-    fn test_footer_parser() {
-        assert_eq!(
-            footer_parser(b"-----END PGP PUBLIC KEY BLOCK-----"),
-            Ok((&b""[..], (None, BlockType::PublicKey)))
-        );
+        assert!(read("=AAAA-----END PGP PUBLIC KEY BLOCK-----\n").is_err());
 
+        // the footer ends the block, the next one is left in the input
+        let mut input = &b"-----BEGIN PGP MESSAGE-----\n\naGVsbG8gd29ybGQ=\n-----END PGP MESSAGE-----\n-----BEGIN PGP MESSAGE-----\n\naGVsbG8gd29ybGQ=\n-----END PGP MESSAGE-----\n"[..];
+        let mut reader = ArmorReader::new(&mut input).unwrap();
+        reader.read_to_end(&mut Vec::new()).unwrap();
         assert_eq!(
-            footer_parser(b"-----END PGP PUBLIC KEY BLOCK-----\n"),
-            Ok((&b""[..], (None, BlockType::PublicKey)))
+            input,
+            &b"-----BEGIN PGP MESSAGE-----\n\naGVsbG8gd29ybGQ=\n-----END PGP MESSAGE-----\n"[..]
         );
+    }
 
-        assert_eq!(
-            footer_parser(b"=-----END PGP PUBLIC KEY BLOCK-----\n"),
-            Ok((&b""[..], (None, BlockType::PublicKey)))
-        );
+    #[test]
+    fn test_armor_reader() {
+        use crate::composed::{Deserializable, Message};
+
+        let data: Vec<u8> = (0..1_000_000u32).map(|i| (i % 251) as u8).collect();
+        let msg = Message::new_literal_bytes("data.bin", &data);
+        let mut headers = BTreeMap::new();
+        headers.insert("Comment".to_string(), "streaming".to_string());
+        let armored = msg.to_armored_bytes(Some(&headers)).unwrap();
+
+        let mut reader = ArmorReader::new(&armored[..]).unwrap();
+        assert_eq!(reader.typ(), BlockType::Message);
+        assert_eq!(reader.headers(), &headers);
+        let mut bytes = Vec::new();
+        // small reads, so that the decoded lines are split
+        let mut buf = [0u8; 7];
+        loop {
+            let size = reader.read(&mut buf).unwrap();
+            if size == 0 {
+                break;
+            }
+            bytes.extend_from_slice(&buf[..size]);
+        }
+        assert!(reader.checksum().is_some());
+
+        let (_, _, expected) = parse(Cursor::new(&armored)).unwrap();
+        assert_eq!(bytes, expected);
+        assert_eq!(Message::from_bytes(&bytes[..]).unwrap(), msg);
+
+        // without a blank line after the header, and with leading text
+        let (typ, _, expected) = parse(Cursor::new(
+            b"-----BEGIN PGP MESSAGE-----\n\naGVsbG8gd29ybGQ=\n-----END PGP MESSAGE-----\n",
+        ))
+        .unwrap();
+        let mut reader = ArmorReader::new(
+            &b"some text\n-----BEGIN PGP MESSAGE-----\naGVsbG8g\nd29ybGQ\n-----END PGP MESSAGE-----\n"[..],
+        )
+        .unwrap();
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).unwrap();
+        assert_eq!(reader.typ(), typ);
+        assert_eq!(bytes, expected);
+    }
 
-        assert_eq!(
-            footer_parser(b"=4JBj-----END PGP PUBLIC KEY BLOCK-----\r\n"),
-            Ok((&b""[..], (Some(&b"4JBj"[..]), BlockType::PublicKey)))
-        );
+    #[test]
+    fn test_armor_reader_invalid() {
+        let read = |input: &[u8]| -> Result<Vec<u8>> {
+            let mut bytes = Vec::new();
+            ArmorReader::new(input)?.read_to_end(&mut bytes)?;
+            Ok(bytes)
+        };
 
         assert_eq!(
-            footer_parser(b"=4JBj\r\n-----END PGP PUBLIC KEY BLOCK-----\r\n"),
-            Ok((&b""[..], (Some(&b"4JBj"[..]), BlockType::PublicKey)))
+            read(b"-----BEGIN PGP MESSAGE-----\n\naGVsbG8gd29ybGQ=\n=sDy3\n-----END PGP MESSAGE-----\n").unwrap(),
+            b"hello world"
         );
+        // bad checksum
+        assert!(read(
+            b"-----BEGIN PGP MESSAGE-----\n\naGVsbG8gd29ybGQ=\n=AAAA\n-----END PGP MESSAGE-----\n"
+        )
+        .is_err());
+        // mismatched footer
+        assert!(read(
+            b"-----BEGIN PGP MESSAGE-----\n\naGVsbG8gd29ybGQ=\n-----END PGP SIGNATURE-----\n"
+        )
+        .is_err());
+        // missing footer
+        assert!(read(b"-----BEGIN PGP MESSAGE-----\n\naGVsbG8gd29ybGQ=\n").is_err());
+        // missing header
+        assert!(read(b"aGVsbG8gd29ybGQ=\n").is_err());
+        // invalid base64
+        assert!(read(
+            b"-----BEGIN PGP MESSAGE-----\n\naGVsb*8gd29ybGQ=\n-----END PGP MESSAGE-----\n"
+        )
+        .is_err());
+        // overlong lines
+        let mut long = b"-----BEGIN PGP MESSAGE-----\n\n".to_vec();
+        long.extend(std::iter::repeat(b'A').take(MAX_LINE_LEN * 2));
+        assert!(read(&long).is_err());
+    }
 
-        assert_eq!(
-            footer_parser(b"\r\n-----END PGP PUBLIC KEY BLOCK-----\r\n"),
-            Ok((&b""[..], (None, BlockType::PublicKey)))
-        );
+    #[test]
+    fn test_armor_reader_tolerant() {
+        let read = |input: &[u8], tolerate: bool| -> Result<(Vec<u8>, bool)> {
+            let mut reader = ArmorReader::new(input)?.tolerate_checksum_mismatch(tolerate);
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes)?;
+            Ok((bytes, reader.checksum_mismatch()))
+        };
 
+        let input = "\u{feff}-----Original Message-----\r\n> quoted\r\n-----BEGIN PGP MESSAGE-----  \r\nComment: mangled  \r\n \t\r\n  aGVsbG8g\t\r\nd29ybGQ=3D\r\n=3DsDy3\r\n-----END PGP MESSAGE----- \r\n";
         assert_eq!(
-            footer_parser(&b"=XyBX\n-----END PGP PUBLIC KEY BLOCK-----\n"[..]),
-            Ok((&b""[..], (Some(&b"XyBX"[..]), BlockType::PublicKey)))
+            read(input.as_bytes(), false).unwrap(),
+            (b"hello world".to_vec(), false)
         );
+        let mut reader = ArmorReader::new(input.as_bytes()).unwrap();
+        assert_eq!(reader.headers().get("Comment").unwrap(), "mangled");
+        reader.read_to_end(&mut Vec::new()).unwrap();
+        assert_eq!(reader.checksum(), Some(0xb03cb7));
+
+        // broken checksums can be tolerated
+        let input =
+            b"-----BEGIN PGP MESSAGE-----\n\naGVsbG8gd29ybGQ=\n=AAAA\n-----END PGP MESSAGE-----\n";
+        assert!(read(input, false).is_err());
+        assert_eq!(read(input, true).unwrap(), (b"hello world".to_vec(), true));
+
+        // trailing whitespace after the header line
+        let (typ, _, bytes) = parse(Cursor::new(
+            "-----BEGIN PGP MESSAGE----- \r\n\r\naGVsbG8gd29ybGQ=\r\n-----END PGP MESSAGE-----\r\n",
+        ))
+        .unwrap();
+        assert_eq!(typ, BlockType::Message);
+        assert_eq!(bytes, b"hello world");
+    }
 
-        assert_eq!(
-            footer_parser(&b"-----END PGP MESSAGE-----\n-----BEGIN PGP MESSAGE-----\n\naGVsbG8gd29ybGQ=\n-----END PGP MESSAGE-----\n"[..]),
-            Ok((
-                &b"-----BEGIN PGP MESSAGE-----\n\naGVsbG8gd29ybGQ=\n-----END PGP MESSAGE-----\n"[..],
-                (None, BlockType::Message)
-            )),
-        );
+    #[test]
+    fn test_from_armor_many() {
+        let message = "-----BEGIN PGP MESSAGE-----\nComment: first\n\naGVsbG8gd29ybGQ=\n=sDy3\n-----END PGP MESSAGE-----\n";
+        let signature = "-----BEGIN PGP SIGNATURE-----\n\naGVsbG8=\n-----END PGP SIGNATURE-----";
+        let input = format!("leading text\n{message}\nin between\n\n{signature}\ntrailing text\n");
+
+        let blocks: Vec<_> = from_armor_many(input.as_bytes())
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].typ, BlockType::Message);
+        assert_eq!(blocks[0].headers.get("Comment").unwrap(), "first");
+        assert_eq!(blocks[0].data, b"hello world");
+        assert_eq!(blocks[1].typ, BlockType::Signature);
+        assert!(blocks[1].headers.is_empty());
+        assert_eq!(blocks[1].data, b"hello");
+
+        assert_eq!(from_armor_many(&b""[..]).count(), 0);
+
+        // iteration stops after an invalid block
+        let input = format!("{}\n{message}", message.replace("=sDy3", "=AAAA"));
+        let blocks: Vec<_> = from_armor_many(input.as_bytes()).collect();
+        assert_eq!(blocks.len(), 1);
+        assert!(blocks[0].is_err());
     }
 }
@@ -1,72 +1,233 @@
 use std::collections::BTreeMap;
 use std::hash::Hasher;
-use std::io::Write;
+use std::io::{self, Write};
 
 use base64::engine::{general_purpose, Engine as _};
 use crc24::Crc24Hasher;
-use generic_array::typenum::U64;
 
 use crate::armor::BlockType;
 use crate::errors::Result;
-use crate::line_writer::{LineBreak, LineWriter};
 use crate::ser::Serialize;
-use crate::util::TeeWriter;
 
-pub fn write(
+/// The default line width of the base64 body.
+pub const DEFAULT_LINE_WIDTH: usize = 64;
+
+/// The maximum line width of the base64 body, as allowed by RFC 9580.
+pub const MAX_LINE_WIDTH: usize = 76;
+
+/// Options for writing ascii armor.
+///
+/// `None` and `Some(&headers)` convert into the default options, with the given headers, so
+/// they can be passed directly to the `to_armored_*` functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArmorOptions<'a> {
+    /// Armor headers, like `Comment`, `Version` or `Charset`.
+    pub headers: Option<&'a BTreeMap<String, String>>,
+    /// The number of base64 characters per line, at most [`MAX_LINE_WIDTH`].
+    pub line_width: usize,
+    /// Whether to write the CRC24 checksum, which RFC 9580 makes optional.
+    pub include_checksum: bool,
+}
+
+impl Default for ArmorOptions<'_> {
+    fn default() -> Self {
+        ArmorOptions {
+            headers: None,
+            line_width: DEFAULT_LINE_WIDTH,
+            include_checksum: true,
+        }
+    }
+}
+
+impl<'a> From<Option<&'a BTreeMap<String, String>>> for ArmorOptions<'a> {
+    fn from(headers: Option<&'a BTreeMap<String, String>>) -> Self {
+        ArmorOptions {
+            headers,
+            ..Default::default()
+        }
+    }
+}
+
+/// Writes `source` as ascii armor of type `typ`.
+pub fn write<'a>(
     source: &impl Serialize,
     typ: BlockType,
     writer: &mut impl Write,
-    headers: Option<&BTreeMap<String, String>>,
+    opts: impl Into<ArmorOptions<'a>>,
 ) -> Result<()> {
-    // write armor header
-    writer.write_all(&b"-----BEGIN "[..])?;
-    typ.to_writer(writer)?;
-    writer.write_all(&b"-----\n"[..])?;
-
-    // write armor headers
-    if let Some(headers) = headers {
-        for (key, value) in headers.iter() {
-            writer.write_all(key.as_bytes())?;
-            writer.write_all(&b": "[..])?;
-            writer.write_all(value.as_bytes())?;
-            writer.write_all(&b"\n"[..])?;
+    let mut armor = ArmorWriter::new(writer, typ, opts)?;
+    source.to_writer(&mut armor)?;
+    armor.finish()?;
+
+    Ok(())
+}
+
+/// Encodes data into ascii armor as it is written.
+///
+/// [`ArmorWriter::finish`] must be called to write the checksum and footer.
+///
+/// ```rust
+/// # fn main() -> pgp::errors::Result<()> {
+/// use std::collections::BTreeMap;
+/// use std::io::Write;
+///
+/// use pgp::armor::{ArmorOptions, ArmorWriter, BlockType};
+///
+/// let mut headers = BTreeMap::new();
+/// headers.insert("Comment".to_string(), "hello".to_string());
+/// let opts = ArmorOptions {
+///     headers: Some(&headers),
+///     line_width: 76,
+///     include_checksum: false,
+/// };
+///
+/// let mut writer = ArmorWriter::new(Vec::new(), BlockType::Message, opts)?;
+/// writer.write_all(b"hello world")?;
+/// let armored = writer.finish()?;
+///
+/// assert_eq!(
+///     std::str::from_utf8(&armored)?,
+///     "-----BEGIN PGP MESSAGE-----\nComment: hello\n\naGVsbG8gd29ybGQ=\n-----END PGP MESSAGE-----\n"
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub struct ArmorWriter<W: Write> {
+    inner: W,
+    typ: BlockType,
+    line_width: usize,
+    include_checksum: bool,
+    crc: Crc24Hasher,
+    /// Input that does not yet form a full group of 3 bytes.
+    pending: Vec<u8>,
+    /// Reused buffer for the encoded characters.
+    encoded: String,
+    /// The number of characters on the current line.
+    line_len: usize,
+}
+
+impl<W: Write> ArmorWriter<W> {
+    /// Writes the armor header line and headers to `inner`.
+    pub fn new<'a>(
+        mut inner: W,
+        typ: BlockType,
+        opts: impl Into<ArmorOptions<'a>>,
+    ) -> Result<Self> {
+        let opts = opts.into();
+        ensure!(
+            opts.line_width > 0 && opts.line_width <= MAX_LINE_WIDTH,
+            "invalid armor line width {}",
+            opts.line_width
+        );
+
+        inner.write_all(&b"-----BEGIN "[..])?;
+        typ.to_writer(&mut inner)?;
+        inner.write_all(&b"-----\n"[..])?;
+
+        if let Some(headers) = opts.headers {
+            for (key, value) in headers.iter() {
+                ensure!(
+                    !key.contains([':', '\r', '\n']) && !value.contains(['\r', '\n']),
+                    "invalid armor header {:?}",
+                    key
+                );
+                inner.write_all(key.as_bytes())?;
+                inner.write_all(&b": "[..])?;
+                inner.write_all(value.as_bytes())?;
+                inner.write_all(&b"\n"[..])?;
+            }
         }
+
+        inner.write_all(&b"\n"[..])?;
+
+        Ok(ArmorWriter {
+            inner,
+            typ,
+            line_width: opts.line_width,
+            include_checksum: opts.include_checksum,
+            crc: Crc24Hasher::init(0x00B7_04CE),
+            pending: Vec::with_capacity(3),
+            encoded: String::new(),
+            line_len: 0,
+        })
     }
 
-    writer.write_all(&b"\n"[..])?;
+    /// Writes the encoded characters, breaking the lines at the line width.
+    fn write_encoded(&mut self) -> io::Result<()> {
+        let mut chars = self.encoded.as_bytes();
+        while !chars.is_empty() {
+            let size = chars.len().min(self.line_width - self.line_len);
+            self.inner.write_all(&chars[..size])?;
+            chars = &chars[size..];
+            self.line_len += size;
 
-    // write body
-    let mut crc_hasher = Crc24Hasher::init(0x00B7_04CE);
-    {
-        let mut line_wrapper = LineWriter::<_, U64>::new(writer.by_ref(), LineBreak::Lf);
-        let mut enc =
-            base64::write::EncoderWriter::new(&mut line_wrapper, &general_purpose::STANDARD);
+            if self.line_len == self.line_width {
+                self.inner.write_all(b"\n")?;
+                self.line_len = 0;
+            }
+        }
+        self.encoded.clear();
 
-        let mut tee = TeeWriter::new(&mut crc_hasher, &mut enc);
-        source.to_writer(&mut tee)?;
+        Ok(())
     }
 
-    let crc = crc_hasher.finish() as u32;
+    #[cfg(feature = "async")]
+    pub(crate) fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
 
-    // write crc
-    writer.write_all(b"=")?;
+    /// Writes the remaining data, the checksum and the footer, returning the inner writer.
+    pub fn finish(mut self) -> Result<W> {
+        general_purpose::STANDARD.encode_string(&self.pending, &mut self.encoded);
+        self.write_encoded()?;
+        if self.line_len > 0 {
+            self.inner.write_all(b"\n")?;
+        }
 
-    let crc_buf = [
-        // (crc >> 24) as u8,
-        (crc >> 16) as u8,
-        (crc >> 8) as u8,
-        crc as u8,
-    ];
-    let crc_enc = general_purpose::STANDARD.encode(crc_buf);
+        if self.include_checksum {
+            let crc = self.crc.finish() as u32;
+            let crc_buf = [(crc >> 16) as u8, (crc >> 8) as u8, crc as u8];
+            self.inner.write_all(b"=")?;
+            self.inner
+                .write_all(general_purpose::STANDARD.encode(crc_buf).as_bytes())?;
+            self.inner.write_all(b"\n")?;
+        }
 
-    writer.write_all(crc_enc.as_bytes())?;
+        self.inner.write_all(&b"-----END "[..])?;
+        self.typ.to_writer(&mut self.inner)?;
+        self.inner.write_all(&b"-----\n"[..])?;
 
-    // write footer
-    writer.write_all(&b"\n-----END "[..])?;
-    typ.to_writer(writer)?;
-    writer.write_all(&b"-----\n"[..])?;
+        Ok(self.inner)
+    }
+}
 
-    Ok(())
+impl<W: Write> Write for ArmorWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.crc.write(buf);
+
+        let mut data = buf;
+        if !self.pending.is_empty() {
+            let size = data.len().min(3 - self.pending.len());
+            self.pending.extend_from_slice(&data[..size]);
+            data = &data[size..];
+            if self.pending.len() < 3 {
+                return Ok(buf.len());
+            }
+            general_purpose::STANDARD.encode_string(&self.pending, &mut self.encoded);
+            self.pending.clear();
+        }
+
+        let full = data.len() / 3 * 3;
+        general_purpose::STANDARD.encode_string(&data[..full], &mut self.encoded);
+        self.pending.extend_from_slice(&data[full..]);
+        self.write_encoded()?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
 }
 
 #[cfg(test)]
@@ -126,4 +287,77 @@ mod tests {
             assert_eq!(lines[lines.len() - 1], "-----END PGP MESSAGE-----");
         }
     }
+
+    #[test]
+    fn test_armor_writer() {
+        use crate::armor::{ArmorReader, Dearmor};
+        use std::io::{Cursor, Read};
+
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 253) as u8).collect();
+        let mut headers = BTreeMap::new();
+        headers.insert("Charset".to_string(), "UTF-8".to_string());
+        headers.insert("Comment".to_string(), "streaming".to_string());
+
+        for line_width in [1, 63, 64, 76] {
+            for include_checksum in [true, false] {
+                let opts = ArmorOptions {
+                    headers: Some(&headers),
+                    line_width,
+                    include_checksum,
+                };
+
+                let mut expected = Vec::new();
+                write(
+                    &TestSource::new(data.clone()),
+                    BlockType::File,
+                    &mut expected,
+                    opts,
+                )
+                .unwrap();
+
+                // the output does not depend on how the data is split
+                let mut writer = ArmorWriter::new(Vec::new(), BlockType::File, opts).unwrap();
+                for chunk in data.chunks(7) {
+                    writer.write_all(chunk).unwrap();
+                }
+                let armored = writer.finish().unwrap();
+                assert_eq!(armored, expected);
+
+                let text = std::str::from_utf8(&armored).unwrap();
+                let lines: Vec<_> = text.lines().collect();
+                assert_eq!(lines[1], "Charset: UTF-8");
+                assert_eq!(lines[2], "Comment: streaming");
+                assert!(lines[4..lines.len() - 1]
+                    .iter()
+                    .all(|l| !l.is_empty() && l.len() <= line_width.max(5)));
+                assert_eq!(
+                    lines.iter().any(|l| l.starts_with('=') && l.len() == 5),
+                    include_checksum
+                );
+
+                let mut reader = ArmorReader::new(&armored[..]).unwrap();
+                let mut bytes = Vec::new();
+                reader.read_to_end(&mut bytes).unwrap();
+                assert_eq!(bytes, data);
+                assert_eq!(reader.headers(), &headers);
+
+                let mut dearmor = Dearmor::new(Cursor::new(&armored));
+                let mut bytes = Vec::new();
+                dearmor.read_to_end(&mut bytes).unwrap();
+                assert_eq!(bytes, data);
+            }
+        }
+
+        for line_width in [0, 77] {
+            let opts = ArmorOptions {
+                line_width,
+                ..Default::default()
+            };
+            assert!(ArmorWriter::new(Vec::new(), BlockType::File, opts).is_err());
+        }
+
+        let mut headers = BTreeMap::new();
+        headers.insert("Comment".to_string(), "two\nlines".to_string());
+        assert!(ArmorWriter::new(Vec::new(), BlockType::File, Some(&headers)).is_err());
+    }
 }
@@ -0,0 +1,501 @@
+//! # Async IO
+//!
+//! [tokio](https://tokio.rs) `AsyncRead` and `AsyncWrite` adapters for the streaming armor
+//! reader and writer, the streaming decrypter and the signing writer, enabled with the
+//! `async` feature.
+//!
+//! The adapters drive the blocking implementations on buffered data, so they never block the
+//! executor. Readers keep up to [`READ_AHEAD`] bytes of input buffered, writers buffer the
+//! output of each write until the next one.
+//!
+//! ```rust
+//! # fn main() -> pgp::errors::Result<()> {
+//! # pollster::block_on(async {
+//! use pgp::armor::BlockType;
+//! use pgp::async_io::{AsyncArmorReader, AsyncArmorWriter};
+//! use tokio::io::{AsyncReadExt, AsyncWriteExt};
+//!
+//! let mut writer = AsyncArmorWriter::new(Vec::new(), BlockType::Message, None)?;
+//! writer.write_all(b"hello world").await?;
+//! let armored = writer.finish().await?;
+//!
+//! let mut reader = AsyncArmorReader::new(&armored[..]).await?;
+//! let mut data = Vec::new();
+//! reader.read_to_end(&mut data).await?;
+//! assert_eq!(data, b"hello world");
+//! # Ok(())
+//! # })
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::future::poll_fn;
+use std::io::{self, BufRead, Read, Write};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use bstr::BStr;
+use chrono::{DateTime, Utc};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::armor::{ArmorOptions, ArmorReader, ArmorWriter, BlockType};
+use crate::composed::message::{
+    decrypt_session_key_for_async_keys, DecryptedReader, Esk, SessionKey, SignerWriter,
+    StreamDecrypter,
+};
+use crate::composed::SignedSecretKey;
+use crate::crypto::hash::HashAlgorithm;
+use crate::errors::{Error, Result};
+use crate::packet::DataMode;
+use crate::types::{AsyncSecretKeyTrait, KeyId, PassphraseProvider, SecretKeyTrait};
+
+/// The amount of input the readers keep buffered. A single packet header, armor line or
+/// decrypted chunk needing more input than this fails to read.
+pub const READ_AHEAD: usize = 64 * 1024;
+
+/// The most data that is read before the start of the armor or the encrypted data.
+const MAX_PREFIX_LEN: usize = 16 * 1024 * 1024;
+
+/// The amount of data a blocking reader is asked for at once.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Input of the blocking readers, which is filled from an async reader.
+///
+/// Reading more than is buffered fails with [`io::ErrorKind::WouldBlock`], until the end of
+/// the input was reached.
+#[derive(Debug, Clone, Default)]
+struct Buffered {
+    buf: Vec<u8>,
+    pos: usize,
+    eof: bool,
+}
+
+impl Buffered {
+    fn available(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    /// Reads from `inner` until at least `target` bytes are buffered, or the input ended.
+    fn poll_fill<R: AsyncRead + Unpin>(
+        &mut self,
+        cx: &mut Context<'_>,
+        inner: &mut R,
+        target: usize,
+    ) -> Poll<io::Result<()>> {
+        while !self.eof && self.available() < target {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+
+            let start = self.buf.len();
+            self.buf.resize(start.max(target), 0);
+            let mut read_buf = ReadBuf::new(&mut self.buf[start..]);
+            let res = Pin::new(&mut *inner).poll_read(cx, &mut read_buf);
+            let read = read_buf.filled().len();
+            self.buf.truncate(start + read);
+
+            ready!(res)?;
+            self.eof = read == 0;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Read for Buffered {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = buf.len().min(available.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+
+        Ok(len)
+    }
+}
+
+impl BufRead for Buffered {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.available() == 0 && !self.eof {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buf.len());
+    }
+}
+
+/// Creates a blocking reader over the start of `inner`, buffering more of the input and
+/// starting over while `f` runs out of it.
+async fn start<R, T, F>(inner: &mut R, f: F) -> Result<T>
+where
+    R: AsyncRead + Unpin,
+    F: Fn(Buffered) -> Result<T>,
+{
+    let mut source = Buffered::default();
+    let mut target = CHUNK_SIZE;
+    loop {
+        poll_fn(|cx| source.poll_fill(cx, inner, target)).await?;
+        match f(source.clone()) {
+            Err(Error::IOError(err)) if err.kind() == io::ErrorKind::WouldBlock => {
+                ensure!(
+                    target < MAX_PREFIX_LEN,
+                    "more than {} bytes before the start of the data",
+                    MAX_PREFIX_LEN
+                );
+                target *= 2;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Reports a blocking reader running out of buffered input as invalid data, as it can not be
+/// resumed.
+fn check_io(err: io::Error) -> io::Error {
+    if err.kind() == io::ErrorKind::WouldBlock {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("more than {} bytes of input needed at once", READ_AHEAD),
+        )
+    } else {
+        err
+    }
+}
+
+fn check(err: Error) -> Error {
+    match err {
+        Error::IOError(err) => Error::IOError(check_io(err)),
+        err => err,
+    }
+}
+
+/// Reads from a blocking `reader` into `buf`, asking for at most [`CHUNK_SIZE`] bytes.
+fn read_into(
+    buf: &mut ReadBuf<'_>,
+    reader: impl FnOnce(&mut [u8]) -> io::Result<usize>,
+) -> io::Result<()> {
+    let unfilled = buf.initialize_unfilled();
+    let len = unfilled.len().min(CHUNK_SIZE);
+    let read = reader(&mut unfilled[..len]).map_err(check_io)?;
+    buf.advance(read);
+
+    Ok(())
+}
+
+/// Writes all of `buffer` to `inner`.
+fn poll_drain<W: AsyncWrite + Unpin>(
+    cx: &mut Context<'_>,
+    inner: &mut W,
+    buffer: &mut Vec<u8>,
+) -> Poll<io::Result<()>> {
+    while !buffer.is_empty() {
+        let written = ready!(Pin::new(&mut *inner).poll_write(cx, buffer))?;
+        if written == 0 {
+            return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+        }
+        buffer.drain(..written);
+    }
+
+    Poll::Ready(Ok(()))
+}
+
+/// Async variant of [`ArmorReader`], decoding ascii armor as it is read.
+pub struct AsyncArmorReader<R> {
+    reader: ArmorReader<Buffered>,
+    inner: R,
+}
+
+impl<R: AsyncRead + Unpin> AsyncArmorReader<R> {
+    /// Reads the armor header line and headers from `inner`, skipping any text before them.
+    pub async fn new(mut inner: R) -> Result<Self> {
+        let reader = start(&mut inner, ArmorReader::new).await?;
+
+        Ok(AsyncArmorReader { reader, inner })
+    }
+
+    pub fn typ(&self) -> BlockType {
+        self.reader.typ()
+    }
+
+    pub fn headers(&self) -> &BTreeMap<String, String> {
+        self.reader.headers()
+    }
+
+    /// The checksum from the footer, available once all data was read.
+    pub fn checksum(&self) -> Option<u64> {
+        self.reader.checksum()
+    }
+
+    /// See [`ArmorReader::tolerate_checksum_mismatch`].
+    pub fn tolerate_checksum_mismatch(mut self, tolerate: bool) -> Self {
+        self.reader = self.reader.tolerate_checksum_mismatch(tolerate);
+        self
+    }
+
+    /// See [`ArmorReader::checksum_mismatch`].
+    pub fn checksum_mismatch(&self) -> bool {
+        self.reader.checksum_mismatch()
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncArmorReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let reader = &mut this.reader;
+        ready!(reader.get_mut().poll_fill(cx, &mut this.inner, READ_AHEAD))?;
+
+        Poll::Ready(read_into(buf, |buf| reader.read(buf)))
+    }
+}
+
+/// Async variant of [`StreamDecrypter`], decrypting a message while it is read.
+pub struct AsyncStreamDecrypter<R> {
+    decrypter: StreamDecrypter<Buffered>,
+    inner: R,
+}
+
+impl<R: AsyncRead + Unpin> AsyncStreamDecrypter<R> {
+    /// Reads the encrypted session key packets from `inner`, up to the start of the
+    /// encrypted data.
+    pub async fn new(mut inner: R) -> Result<Self> {
+        let decrypter = start(&mut inner, StreamDecrypter::new).await?;
+
+        Ok(AsyncStreamDecrypter { decrypter, inner })
+    }
+
+    /// The encrypted session key packets of the message.
+    pub fn esk(&self) -> &[Esk] {
+        self.decrypter.esk()
+    }
+
+    /// Decrypts the message using the given keys, see [`StreamDecrypter::decrypt`].
+    pub async fn decrypt<G>(
+        self,
+        key_pw: G,
+        keys: &[&SignedSecretKey],
+    ) -> Result<(AsyncDecryptedReader<R>, Vec<KeyId>)>
+    where
+        G: PassphraseProvider + Clone,
+    {
+        let (session_key, ids) =
+            crate::composed::message::decrypt_session_key_for_keys(self.esk(), key_pw, keys)?;
+
+        Ok((self.decrypt_with_session_key(&session_key).await?, ids))
+    }
+
+    /// Decrypts the message using keys whose secret operations are delegated, see
+    /// [`Message::decrypt_async`](crate::Message::decrypt_async).
+    pub async fn decrypt_async<G, K>(
+        self,
+        key_pw: G,
+        keys: &[&K],
+    ) -> Result<(AsyncDecryptedReader<R>, Vec<KeyId>)>
+    where
+        G: PassphraseProvider + Clone + Send,
+        K: AsyncSecretKeyTrait,
+    {
+        let (session_key, ids) =
+            decrypt_session_key_for_async_keys(self.esk(), key_pw, keys).await?;
+
+        Ok((self.decrypt_with_session_key(&session_key).await?, ids))
+    }
+
+    /// Decrypts the message using the given password.
+    pub async fn decrypt_with_password<F>(self, msg_pw: F) -> Result<AsyncDecryptedReader<R>>
+    where
+        F: FnOnce() -> String,
+    {
+        let session_key =
+            crate::composed::message::decrypt_session_key_for_password(self.esk(), msg_pw)?;
+
+        self.decrypt_with_session_key(&session_key).await
+    }
+
+    /// Decrypts the message using a session key that was obtained by other means.
+    pub async fn decrypt_with_session_key(
+        mut self,
+        session_key: &SessionKey,
+    ) -> Result<AsyncDecryptedReader<R>> {
+        let source = self.decrypter.get_mut();
+        poll_fn(|cx| source.poll_fill(cx, &mut self.inner, READ_AHEAD)).await?;
+
+        let reader = self
+            .decrypter
+            .decrypt_with_session_key(session_key)
+            .map_err(check)?;
+
+        Ok(AsyncDecryptedReader {
+            reader,
+            inner: self.inner,
+        })
+    }
+}
+
+/// The plaintext of a message decrypted by [`AsyncStreamDecrypter`], see [`DecryptedReader`].
+pub struct AsyncDecryptedReader<R> {
+    reader: DecryptedReader<Buffered>,
+    inner: R,
+}
+
+impl<R> AsyncDecryptedReader<R> {
+    pub fn is_binary(&self) -> bool {
+        self.reader.is_binary()
+    }
+
+    /// The file name of the literal data, which may contain non utf-8 bytes.
+    pub fn file_name(&self) -> &BStr {
+        self.reader.file_name()
+    }
+
+    pub fn created(&self) -> &DateTime<Utc> {
+        self.reader.created()
+    }
+
+    pub fn mode(&self) -> DataMode {
+        self.reader.mode()
+    }
+
+    /// Whether the data is "for your eyes only", see
+    /// [`CONSOLE_FILE_NAME`](crate::packet::CONSOLE_FILE_NAME).
+    pub fn is_for_your_eyes_only(&self) -> bool {
+        self.reader.is_for_your_eyes_only()
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncDecryptedReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let reader = &mut this.reader;
+        ready!(reader.get_mut().poll_fill(cx, &mut this.inner, READ_AHEAD))?;
+
+        Poll::Ready(read_into(buf, |buf| reader.read(buf)))
+    }
+}
+
+/// Async variant of [`ArmorWriter`], encoding data into ascii armor as it is written.
+///
+/// [`AsyncArmorWriter::finish`] must be called to write the checksum and footer.
+pub struct AsyncArmorWriter<W> {
+    writer: ArmorWriter<Vec<u8>>,
+    inner: W,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncArmorWriter<W> {
+    /// Creates a writer, which writes the armor header line and headers with the first data.
+    pub fn new<'a>(inner: W, typ: BlockType, opts: impl Into<ArmorOptions<'a>>) -> Result<Self> {
+        let writer = ArmorWriter::new(Vec::new(), typ, opts)?;
+
+        Ok(AsyncArmorWriter { writer, inner })
+    }
+
+    /// Writes the remaining data, the checksum and the footer, returning the inner writer.
+    pub async fn finish(mut self) -> Result<W> {
+        let rest = self.writer.finish()?;
+        self.inner.write_all(&rest).await?;
+        self.inner.flush().await?;
+
+        Ok(self.inner)
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncArmorWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(poll_drain(cx, &mut this.inner, this.writer.get_mut()))?;
+
+        Poll::Ready(this.writer.write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(poll_drain(cx, &mut this.inner, this.writer.get_mut()))?;
+
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    /// Flushes and shuts down the inner writer, without writing the footer.
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(poll_drain(cx, &mut this.inner, this.writer.get_mut()))?;
+
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+/// Async variant of [`SignerWriter`], writing a one-pass signed message.
+///
+/// [`AsyncSignerWriter::finish`] must be called to write the signature.
+pub struct AsyncSignerWriter<'a, K, W> {
+    writer: SignerWriter<'a, K, Vec<u8>>,
+    inner: W,
+}
+
+impl<'a, K: SecretKeyTrait, W: AsyncWrite + Unpin> AsyncSignerWriter<'a, K, W> {
+    /// Creates a writer, which writes the one-pass signature and the literal data header for
+    /// `file_name` with the first data, see [`SignerWriter::new`].
+    pub fn new(
+        inner: W,
+        key: &'a K,
+        hash_algorithm: HashAlgorithm,
+        file_name: &BStr,
+        mode: DataMode,
+    ) -> Result<Self> {
+        let writer = SignerWriter::new(Vec::new(), key, hash_algorithm, file_name, mode)?;
+
+        Ok(AsyncSignerWriter { writer, inner })
+    }
+
+    /// Writes the remaining literal data and the signature, returning the inner writer.
+    pub async fn finish<F>(mut self, key_pw: F) -> Result<W>
+    where
+        F: PassphraseProvider,
+    {
+        let rest = self.writer.finish(key_pw)?;
+        self.inner.write_all(&rest).await?;
+        self.inner.flush().await?;
+
+        Ok(self.inner)
+    }
+}
+
+impl<'a, K: SecretKeyTrait, W: AsyncWrite + Unpin> AsyncWrite for AsyncSignerWriter<'a, K, W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(poll_drain(cx, &mut this.inner, this.writer.get_mut()))?;
+
+        Poll::Ready(this.writer.write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(poll_drain(cx, &mut this.inner, this.writer.get_mut()))?;
+
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    /// Flushes and shuts down the inner writer, without writing the signature.
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(poll_drain(cx, &mut this.inner, this.writer.get_mut()))?;
+
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
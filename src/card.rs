@@ -0,0 +1,1174 @@
+//! # OpenPGP card
+//!
+//! A client for the [OpenPGP card application](https://gnupg.org/ftp/specs/OpenPGP-smart-card-application-3.4.1.pdf),
+//! as implemented by smartcards and tokens like the YubiKey or Nitrokey.
+//!
+//! This module does not talk to the card reader itself. [`OpenPgpCard`] sends its command
+//! APDUs through a [`CardTransport`], which is usually backed by PC/SC, e.g. with the
+//! [pcsc](https://docs.rs/pcsc) crate:
+//!
+//! ```rust,ignore
+//! struct Pcsc(pcsc::Card);
+//!
+//! impl CardTransport for Pcsc {
+//!     fn transmit(&self, apdu: &[u8]) -> Result<Vec<u8>> {
+//!         let mut buf = [0u8; pcsc::MAX_BUFFER_SIZE_EXTENDED];
+//!         let response = self
+//!             .0
+//!             .transmit(apdu, &mut buf)
+//!             .map_err(|err| format_err!("pcsc: {}", err))?;
+//!
+//!         Ok(response.to_vec())
+//!     }
+//! }
+//! ```
+//!
+//! A [`CardKey`] pairs a public key with the card slot holding its secret key, and implements
+//! [`Signer`] and [`Decryptor`]. The PIN has to be verified with [`OpenPgpCard::verify_pin`]
+//! before using it.
+
+use std::fmt;
+use std::io;
+
+use chrono::{DateTime, TimeZone, Utc};
+use rand::{CryptoRng, Rng};
+use rsa::traits::{PrivateKeyParts, PublicKeyParts};
+use zeroize::Zeroizing;
+
+use crate::crypto::ecc_curve::{ecc_curve_from_oid, ECCCurve};
+use crate::crypto::hash::HashAlgorithm;
+use crate::crypto::public_key::PublicKeyAlgorithm;
+use crate::crypto::{ecdh, rsa as pgp_rsa};
+use crate::errors::Result;
+use crate::packet::{SecretKey, SecretSubkey};
+use crate::types::{
+    Decryptor, ECDSASecretKey, Fingerprint, KeyId, KeyTrait, KeyVersion, Mpi, PassphraseProvider,
+    PublicKeyTrait, PublicParams, SecretKeyRepr, SecretKeyTrait, Signer,
+};
+
+/// The application identifier of the OpenPGP card application.
+const OPENPGP_AID: [u8; 6] = [0xD2, 0x76, 0x00, 0x01, 0x24, 0x01];
+
+/// The maximum length of the data of a short command APDU. Longer data is sent using command
+/// chaining.
+const MAX_COMMAND_DATA: usize = 255;
+
+/// Sends command APDUs to a card, and returns its response APDUs, including the status word.
+pub trait CardTransport {
+    fn transmit(&self, apdu: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// The key slots of the card.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeySlot {
+    Signing,
+    Decryption,
+    Authentication,
+}
+
+impl KeySlot {
+    fn index(self) -> u8 {
+        match self {
+            KeySlot::Signing => 0,
+            KeySlot::Decryption => 1,
+            KeySlot::Authentication => 2,
+        }
+    }
+
+    /// The control reference template, that selects the slot when importing a key.
+    fn crt(self) -> u8 {
+        match self {
+            KeySlot::Signing => 0xB6,
+            KeySlot::Decryption => 0xB8,
+            KeySlot::Authentication => 0xA4,
+        }
+    }
+}
+
+/// The PINs of the card, named after their use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pin {
+    /// PW1, verified for signing with the signing key.
+    Signing,
+    /// PW1, verified for decryption and authentication.
+    User,
+    /// PW3, verified for administrative operations, like importing keys.
+    Admin,
+}
+
+impl Pin {
+    fn reference(self) -> u8 {
+        match self {
+            Pin::Signing => 0x81,
+            Pin::User => 0x82,
+            Pin::Admin => 0x83,
+        }
+    }
+}
+
+/// The algorithm of a key slot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlgorithmAttributes {
+    Rsa {
+        modulus_bits: u16,
+        exponent_bits: u16,
+        import_format: u8,
+    },
+    Ecc {
+        algorithm: PublicKeyAlgorithm,
+        curve: ECCCurve,
+    },
+    Unknown(Vec<u8>),
+}
+
+impl AlgorithmAttributes {
+    pub fn from_slice(data: &[u8]) -> Self {
+        match data.split_first() {
+            Some((0x01, rest)) if rest.len() >= 5 => AlgorithmAttributes::Rsa {
+                modulus_bits: u16::from_be_bytes([rest[0], rest[1]]),
+                exponent_bits: u16::from_be_bytes([rest[2], rest[3]]),
+                import_format: rest[4],
+            },
+            Some((&algorithm @ (0x12 | 0x13 | 0x16), oid)) => {
+                // the OID may be followed by the import format
+                let curve = ecc_curve_from_oid(oid)
+                    .or_else(|| ecc_curve_from_oid(&oid[..oid.len().saturating_sub(1)]));
+                match curve {
+                    Some(curve) => AlgorithmAttributes::Ecc {
+                        algorithm: PublicKeyAlgorithm::from(algorithm),
+                        curve,
+                    },
+                    None => AlgorithmAttributes::Unknown(data.to_vec()),
+                }
+            }
+            _ => AlgorithmAttributes::Unknown(data.to_vec()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            AlgorithmAttributes::Rsa {
+                modulus_bits,
+                exponent_bits,
+                import_format,
+            } => {
+                let mut data = vec![0x01];
+                data.extend_from_slice(&modulus_bits.to_be_bytes());
+                data.extend_from_slice(&exponent_bits.to_be_bytes());
+                data.push(*import_format);
+                data
+            }
+            AlgorithmAttributes::Ecc { algorithm, curve } => {
+                let mut data = vec![u8::from(*algorithm)];
+                data.extend_from_slice(&curve.oid());
+                data
+            }
+            AlgorithmAttributes::Unknown(data) => data.clone(),
+        }
+    }
+}
+
+/// The key held in a slot, as described by the card.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyInfo {
+    pub attributes: AlgorithmAttributes,
+    pub fingerprint: Option<Fingerprint>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// The retry counters and flags of the PINs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinStatus {
+    /// Whether a verified signing PIN is valid for more than one signature.
+    pub signing_pin_valid_for_multiple: bool,
+    pub user_retries: u8,
+    pub reset_code_retries: u8,
+    pub admin_retries: u8,
+}
+
+/// The application related data of the card.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApplicationData {
+    pub aid: Vec<u8>,
+    pub keys: [KeyInfo; 3],
+    pub pin_status: PinStatus,
+}
+
+impl ApplicationData {
+    /// Parses the application related data object, tag `6E`.
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        let aid = find_tlv(data, 0x4F)?.ok_or_else(|| format_err!("missing AID"))?;
+        ensure!(aid.len() >= 14, "invalid AID");
+
+        let pin_status = find_tlv(data, 0xC4)?.ok_or_else(|| format_err!("missing PW status"))?;
+        ensure!(pin_status.len() >= 7, "invalid PW status");
+
+        let fingerprints =
+            find_tlv(data, 0xC5)?.ok_or_else(|| format_err!("missing fingerprints"))?;
+        ensure_eq!(fingerprints.len(), 60, "invalid fingerprints");
+        let times = find_tlv(data, 0xCD)?.unwrap_or_default();
+
+        let key = |slot: usize| -> Result<KeyInfo> {
+            let attributes = find_tlv(data, 0xC1 + slot as u16)?.unwrap_or_default();
+            let fingerprint = &fingerprints[slot * 20..(slot + 1) * 20];
+            let created_at = times
+                .get(slot * 4..(slot + 1) * 4)
+                .map(|t| u32::from_be_bytes([t[0], t[1], t[2], t[3]]))
+                .filter(|&t| t != 0)
+                .and_then(|t| Utc.timestamp_opt(i64::from(t), 0).single());
+
+            Ok(KeyInfo {
+                attributes: AlgorithmAttributes::from_slice(attributes),
+                fingerprint: if fingerprint.iter().all(|&b| b == 0) {
+                    None
+                } else {
+                    Some(Fingerprint::new(KeyVersion::V4, fingerprint)?)
+                },
+                created_at,
+            })
+        };
+
+        Ok(ApplicationData {
+            aid: aid.to_vec(),
+            keys: [key(0)?, key(1)?, key(2)?],
+            pin_status: PinStatus {
+                signing_pin_valid_for_multiple: pin_status[0] != 0,
+                user_retries: pin_status[4],
+                reset_code_retries: pin_status[5],
+                admin_retries: pin_status[6],
+            },
+        })
+    }
+
+    /// The version of the OpenPGP card specification, implemented by the card.
+    pub fn version(&self) -> (u8, u8) {
+        (self.aid[6], self.aid[7])
+    }
+
+    pub fn manufacturer(&self) -> u16 {
+        u16::from_be_bytes([self.aid[8], self.aid[9]])
+    }
+
+    pub fn serial_number(&self) -> u32 {
+        u32::from_be_bytes([self.aid[10], self.aid[11], self.aid[12], self.aid[13]])
+    }
+
+    pub fn key(&self, slot: KeySlot) -> &KeyInfo {
+        &self.keys[usize::from(slot.index())]
+    }
+}
+
+/// A session with the OpenPGP application of a card.
+pub struct OpenPgpCard<T> {
+    transport: T,
+}
+
+impl<T> fmt::Debug for OpenPgpCard<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpenPgpCard").finish_non_exhaustive()
+    }
+}
+
+impl<T: CardTransport> OpenPgpCard<T> {
+    /// Selects the OpenPGP application of the card connected to `transport`.
+    pub fn select(transport: T) -> Result<Self> {
+        let card = OpenPgpCard { transport };
+        card.command([0x00, 0xA4, 0x04, 0x00], &OPENPGP_AID, false)?;
+
+        Ok(card)
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn application_data(&self) -> Result<ApplicationData> {
+        ApplicationData::from_slice(&self.get_data(0x6E)?)
+    }
+
+    /// The name of the cardholder, in the `surname<<given name` format of the card.
+    pub fn cardholder_name(&self) -> Result<String> {
+        let data = self.get_data(0x65)?;
+        let name = find_tlv(&data, 0x5B)?.unwrap_or_default();
+
+        Ok(String::from_utf8_lossy(name).into_owned())
+    }
+
+    /// The number of signatures the card made with its signing key.
+    pub fn signature_counter(&self) -> Result<u32> {
+        let data = self.get_data(0x7A)?;
+        let counter = find_tlv(&data, 0x93)?.ok_or_else(|| format_err!("missing counter"))?;
+        ensure_eq!(counter.len(), 3, "invalid signature counter");
+
+        Ok(u32::from_be_bytes([0, counter[0], counter[1], counter[2]]))
+    }
+
+    /// Verifies `pin`. The card blocks the PIN after too many wrong attempts.
+    pub fn verify_pin(&self, pin: Pin, value: &[u8]) -> Result<()> {
+        self.command([0x00, 0x20, 0x00, pin.reference()], value, false)?;
+        Ok(())
+    }
+
+    /// Looks up the card slot holding the secret key for `key`, with the public parameters
+    /// `params`.
+    pub fn key<K: PublicKeyTrait>(
+        &self,
+        slot: KeySlot,
+        key: K,
+        params: &PublicParams,
+    ) -> Result<CardKey<'_, T, K>> {
+        let data = self.application_data()?;
+        ensure!(
+            data.key(slot).fingerprint.as_ref() == Some(&key.fingerprint()),
+            "the {:?} slot of the card does not hold the key {}",
+            slot,
+            hex::encode_upper(key.fingerprint())
+        );
+
+        Ok(CardKey {
+            card: self,
+            slot,
+            key,
+            params: params.clone(),
+        })
+    }
+
+    /// Signs `digest`, which was hashed with `hash`, with the key of `algorithm` in `slot`.
+    ///
+    /// The signing slot needs [`Pin::Signing`] to be verified, the authentication slot
+    /// [`Pin::User`].
+    pub fn sign(
+        &self,
+        slot: KeySlot,
+        algorithm: PublicKeyAlgorithm,
+        hash: HashAlgorithm,
+        digest: &[u8],
+    ) -> Result<Vec<Mpi>> {
+        let header = match slot {
+            // PSO: COMPUTE DIGITAL SIGNATURE
+            KeySlot::Signing => [0x00, 0x2A, 0x9E, 0x9A],
+            // INTERNAL AUTHENTICATE
+            KeySlot::Authentication => [0x00, 0x88, 0x00, 0x00],
+            KeySlot::Decryption => bail!("the decryption key can not sign"),
+        };
+
+        match algorithm {
+            PublicKeyAlgorithm::RSA | PublicKeyAlgorithm::RSASign => {
+                let sig = self.command(header, &pgp_rsa::digest_info(hash, digest)?, true)?;
+                Ok(vec![Mpi::from_raw_slice(&sig)])
+            }
+            PublicKeyAlgorithm::ECDSA | PublicKeyAlgorithm::EdDSA => {
+                let sig = self.command(header, digest, true)?;
+                ensure!(
+                    !sig.is_empty() && sig.len() % 2 == 0,
+                    "invalid signature from card"
+                );
+                let (r, s) = sig.split_at(sig.len() / 2);
+
+                Ok(vec![Mpi::from_raw_slice(r), Mpi::from_raw_slice(s)])
+            }
+            _ => unsupported_err!("signing with {:?} keys on a card", algorithm),
+        }
+    }
+
+    /// Decrypts the session key in `mpis` with the key in the decryption slot, which has the
+    /// public parameters `params` and the given `fingerprint`.
+    ///
+    /// Needs [`Pin::User`] to be verified.
+    pub fn decrypt(
+        &self,
+        params: &PublicParams,
+        fingerprint: &Fingerprint,
+        mpis: &[Mpi],
+    ) -> Result<Zeroizing<Vec<u8>>> {
+        // PSO: DECIPHER
+        let header = [0x00, 0x2A, 0x80, 0x86];
+        match params {
+            PublicParams::RSA { n, .. } => {
+                ensure_eq!(mpis.len(), 1, "invalid RSA ciphertext");
+                ensure!(mpis[0].len() <= n.len(), "invalid RSA ciphertext");
+
+                // the padding indicator, followed by the ciphertext in the length of the modulus
+                let mut data = vec![0u8; 1 + n.len() - mpis[0].len()];
+                data.extend_from_slice(&mpis[0]);
+
+                // the card removes the PKCS#1 padding
+                Ok(Zeroizing::new(self.command(header, &data, true)?))
+            }
+            PublicParams::ECDH {
+                curve: curve @ ECCCurve::Curve25519,
+                hash,
+                alg_sym,
+                ..
+            } => {
+                ensure_eq!(mpis.len(), 3, "invalid ECDH ciphertext");
+
+                // the card uses the native X25519 format, without the 0x40 prefix
+                let ephemeral = match mpis[0].split_first() {
+                    Some((0x40, point)) => point,
+                    _ => bail!("invalid ephemeral point"),
+                };
+                let data = tlv(0xA6, &tlv(0x7F49, &tlv(0x86, ephemeral)));
+
+                let shared = Zeroizing::new(self.command(header, &data, true)?);
+                let shared: Zeroizing<[u8; 32]> = match shared.split_first() {
+                    Some((0x40, point)) if point.len() == 32 => point.try_into(),
+                    _ => shared.as_slice().try_into(),
+                }
+                .map(Zeroizing::new)
+                .map_err(|_| format_err!("invalid shared secret from card"))?;
+
+                ecdh::unwrap_session_key(
+                    &shared,
+                    &curve.oid(),
+                    *hash,
+                    *alg_sym,
+                    mpis,
+                    fingerprint.as_bytes(),
+                )
+            }
+            _ => unsupported_err!("decryption with {:?} keys on a card", params),
+        }
+    }
+
+    /// Imports the primary `key` into `slot`, replacing the key held there.
+    ///
+    /// Needs [`Pin::Admin`] to be verified.
+    pub fn import_key<F>(&self, slot: KeySlot, key: &SecretKey, key_pw: F) -> Result<()>
+    where
+        F: PassphraseProvider,
+    {
+        self.import(slot, key, key_pw, key.public_params(), key.created_at())
+    }
+
+    /// Imports `subkey` into `slot`, replacing the key held there.
+    ///
+    /// Needs [`Pin::Admin`] to be verified.
+    pub fn import_subkey<F>(&self, slot: KeySlot, subkey: &SecretSubkey, key_pw: F) -> Result<()>
+    where
+        F: PassphraseProvider,
+    {
+        self.import(
+            slot,
+            subkey,
+            key_pw,
+            subkey.public_params(),
+            subkey.created_at(),
+        )
+    }
+
+    fn import<F>(
+        &self,
+        slot: KeySlot,
+        key: &(impl SecretKeyTrait + KeyTrait),
+        key_pw: F,
+        params: &PublicParams,
+        created_at: &DateTime<Utc>,
+    ) -> Result<()>
+    where
+        F: PassphraseProvider,
+    {
+        let algorithm = key.algorithm();
+        match (slot, algorithm) {
+            (KeySlot::Decryption, PublicKeyAlgorithm::RSA | PublicKeyAlgorithm::ECDH) => {}
+            (KeySlot::Decryption, _) => bail!("{:?} keys can not decrypt", algorithm),
+            (_, PublicKeyAlgorithm::ECDH) => bail!("ECDH keys can not sign"),
+            _ => {}
+        }
+
+        let mut attributes = None;
+        let mut template = Vec::new();
+        let mut values = Vec::new();
+        key.unlock(key_pw, |repr| {
+            let mut push = |tag: u8, value: &[u8]| {
+                template.push(tag);
+                template.extend_from_slice(&tlv_length(value.len()));
+                values.extend_from_slice(value);
+            };
+
+            match repr {
+                SecretKeyRepr::RSA(key) => {
+                    let modulus_bits = u16::try_from(key.n().bits())?;
+                    let primes = key.primes();
+                    ensure_eq!(primes.len(), 2, "multi-prime RSA keys are not supported");
+
+                    let prime_len = usize::from(modulus_bits / 16);
+                    push(0x91, &left_pad(&key.e().to_bytes_be(), 4)?);
+                    push(0x92, &left_pad(&primes[0].to_bytes_be(), prime_len)?);
+                    push(0x93, &left_pad(&primes[1].to_bytes_be(), prime_len)?);
+                    attributes = Some(AlgorithmAttributes::Rsa {
+                        modulus_bits,
+                        exponent_bits: 32,
+                        import_format: 0x00,
+                    });
+                }
+                SecretKeyRepr::ECDSA(key) => {
+                    let (curve, secret) = match key {
+                        #[cfg(feature = "nist-curves")]
+                        ECDSASecretKey::P256(key) => (ECCCurve::P256, key.to_bytes().to_vec()),
+                        #[cfg(feature = "nist-curves")]
+                        ECDSASecretKey::P384(key) => (ECCCurve::P384, key.to_bytes().to_vec()),
+                        #[cfg(feature = "secp256k1")]
+                        ECDSASecretKey::Secp256k1(key) => {
+                            (ECCCurve::Secp256k1, key.to_bytes().to_vec())
+                        }
+                        ECDSASecretKey::Unsupported { curve, .. } => {
+                            unsupported_err!("importing {} keys", curve.name())
+                        }
+                    };
+                    push(0x92, &secret);
+                    attributes = Some(AlgorithmAttributes::Ecc { algorithm, curve });
+                }
+                SecretKeyRepr::EdDSA(key) => {
+                    push(0x92, &key.secret);
+                    attributes = Some(AlgorithmAttributes::Ecc {
+                        algorithm,
+                        curve: ECCCurve::Ed25519,
+                    });
+                }
+                SecretKeyRepr::ECDH(key) => {
+                    // the big endian form, as stored in the secret key packet
+                    push(0x92, &key.secret);
+                    attributes = Some(AlgorithmAttributes::Ecc {
+                        algorithm,
+                        curve: ECCCurve::Curve25519,
+                    });
+                }
+                SecretKeyRepr::DSA(_) => unsupported_err!("importing DSA keys"),
+            }
+
+            Ok(())
+        })?;
+        let attributes = attributes.expect("set when unlocked");
+        if let (PublicParams::RSA { e, .. }, AlgorithmAttributes::Rsa { .. }) =
+            (params, &attributes)
+        {
+            ensure!(e.len() <= 4, "RSA exponent is too large");
+        }
+
+        // PUT DATA: algorithm attributes
+        self.command(
+            [0x00, 0xDA, 0x00, 0xC1 + slot.index()],
+            &attributes.to_bytes(),
+            false,
+        )?;
+
+        // PUT DATA: extended header list
+        let header_list = [
+            &[slot.crt(), 0x00][..],
+            &tlv(0x7F48, &template),
+            &tlv(0x5F48, &values),
+        ]
+        .concat();
+        self.command([0x00, 0xDB, 0x3F, 0xFF], &tlv(0x4D, &header_list), false)?;
+
+        // PUT DATA: fingerprint and creation time
+        self.command(
+            [0x00, 0xDA, 0x00, 0xC7 + slot.index()],
+            key.fingerprint().as_bytes(),
+            false,
+        )?;
+        let created_at = u32::try_from(created_at.timestamp())?;
+        self.command(
+            [0x00, 0xDA, 0x00, 0xCE + slot.index()],
+            &created_at.to_be_bytes(),
+            false,
+        )?;
+
+        Ok(())
+    }
+
+    fn get_data(&self, tag: u16) -> Result<Vec<u8>> {
+        let [p1, p2] = tag.to_be_bytes();
+        self.command([0x00, 0xCA, p1, p2], &[], true)
+    }
+
+    /// Sends a command, chaining its data if needed, and collects the response data.
+    fn command(&self, header: [u8; 4], data: &[u8], expect_response: bool) -> Result<Vec<u8>> {
+        let [cla, ins, p1, p2] = header;
+        let mut chunks = data.chunks(MAX_COMMAND_DATA).peekable();
+        let mut response = loop {
+            let chunk = chunks.next().unwrap_or_default();
+            let last = chunks.peek().is_none();
+
+            let mut apdu = vec![if last { cla } else { cla | 0x10 }, ins, p1, p2];
+            if !chunk.is_empty() {
+                apdu.push(chunk.len() as u8);
+                apdu.extend_from_slice(chunk);
+            }
+            if last && expect_response {
+                apdu.push(0x00);
+            }
+
+            let response = self.transport.transmit(&apdu)?;
+            if last {
+                break response;
+            }
+            check_status(&mut response.clone())?;
+        };
+
+        let mut data = Vec::new();
+        loop {
+            let status = check_status(&mut response)?;
+            data.extend_from_slice(&response);
+
+            match status {
+                // more data is available
+                [0x61, len] => {
+                    response = self.transport.transmit(&[0x00, 0xC0, 0x00, 0x00, len])?;
+                }
+                _ => return Ok(data),
+            }
+        }
+    }
+}
+
+/// Removes the status word from `response`, and returns it, if it indicates success.
+fn check_status(response: &mut Vec<u8>) -> Result<[u8; 2]> {
+    ensure!(response.len() >= 2, "invalid response from card");
+    let status = [response[response.len() - 2], response[response.len() - 1]];
+    response.truncate(response.len() - 2);
+
+    match status {
+        [0x90, 0x00] | [0x61, _] => Ok(status),
+        [0x63, retries] if retries & 0xF0 == 0xC0 => {
+            bail!(format!("wrong PIN, {} tries left", retries & 0x0F))
+        }
+        [0x69, 0x82] => bail!("security status not satisfied, verify the PIN first"),
+        [0x69, 0x83] => bail!("the PIN is blocked"),
+        [0x6A, 0x88] => bail!("referenced data not found"),
+        [0x6A, 0x82] => bail!("the OpenPGP application is not available"),
+        [sw1, sw2] => bail!(format!("card error: status {:02X}{:02X}", sw1, sw2)),
+    }
+}
+
+/// A public key, whose secret key is held in a slot of an OpenPGP card.
+///
+/// Wrap it in an [`ExternalKey`](crate::types::ExternalKey) to sign with it, or pass it to
+/// [`Message::decrypt_with_decryptors`](crate::Message::decrypt_with_decryptors) to decrypt.
+pub struct CardKey<'a, T, K> {
+    card: &'a OpenPgpCard<T>,
+    slot: KeySlot,
+    key: K,
+    params: PublicParams,
+}
+
+impl<'a, T, K: fmt::Debug> fmt::Debug for CardKey<'a, T, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CardKey")
+            .field("slot", &self.slot)
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+impl<'a, T, K: Clone> Clone for CardKey<'a, T, K> {
+    fn clone(&self) -> Self {
+        CardKey {
+            card: self.card,
+            slot: self.slot,
+            key: self.key.clone(),
+            params: self.params.clone(),
+        }
+    }
+}
+
+impl<'a, T, K> CardKey<'a, T, K> {
+    pub fn slot(&self) -> KeySlot {
+        self.slot
+    }
+
+    pub fn public_key(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<'a, T, K: KeyTrait> KeyTrait for CardKey<'a, T, K> {
+    fn fingerprint(&self) -> Fingerprint {
+        self.key.fingerprint()
+    }
+
+    fn key_id(&self) -> KeyId {
+        self.key.key_id()
+    }
+
+    fn algorithm(&self) -> PublicKeyAlgorithm {
+        self.key.algorithm()
+    }
+}
+
+impl<'a, T, K: PublicKeyTrait> PublicKeyTrait for CardKey<'a, T, K> {
+    fn verify_signature(&self, hash: HashAlgorithm, data: &[u8], sig: &[Mpi]) -> Result<()> {
+        self.key.verify_signature(hash, data, sig)
+    }
+
+    fn encrypt<R: CryptoRng + Rng>(&self, rng: &mut R, plain: &[u8]) -> Result<Vec<Mpi>> {
+        self.key.encrypt(rng, plain)
+    }
+
+    fn to_writer_old(&self, writer: &mut impl io::Write) -> Result<()> {
+        self.key.to_writer_old(writer)
+    }
+}
+
+impl<'a, T: CardTransport, K: PublicKeyTrait> Signer for CardKey<'a, T, K> {
+    fn sign(&self, hash: HashAlgorithm, digest: &[u8]) -> Result<Vec<Mpi>> {
+        self.card.sign(self.slot, self.algorithm(), hash, digest)
+    }
+}
+
+impl<'a, T: CardTransport, K: PublicKeyTrait> Decryptor for CardKey<'a, T, K> {
+    fn decrypt(&self, mpis: &[Mpi]) -> Result<Zeroizing<Vec<u8>>> {
+        ensure_eq!(
+            self.slot,
+            KeySlot::Decryption,
+            "only the decryption key can decrypt"
+        );
+        self.card.decrypt(&self.params, &self.fingerprint(), mpis)
+    }
+}
+
+/// Encodes a BER-TLV data object.
+fn tlv(tag: u16, value: &[u8]) -> Vec<u8> {
+    let mut data = match tag.to_be_bytes() {
+        [0, tag] => vec![tag],
+        tag => tag.to_vec(),
+    };
+    data.extend_from_slice(&tlv_length(value.len()));
+    data.extend_from_slice(value);
+    data
+}
+
+fn tlv_length(len: usize) -> Vec<u8> {
+    match len {
+        0..=0x7F => vec![len as u8],
+        0x80..=0xFF => vec![0x81, len as u8],
+        _ => vec![0x82, (len >> 8) as u8, len as u8],
+    }
+}
+
+/// Finds the data object with `tag` in `data`, searching constructed objects recursively.
+fn find_tlv(mut data: &[u8], tag: u16) -> Result<Option<&[u8]>> {
+    while !data.is_empty() {
+        // padding between data objects
+        if data[0] == 0x00 || data[0] == 0xFF {
+            data = &data[1..];
+            continue;
+        }
+
+        let (current, constructed, tag_len) = if data[0] & 0x1F == 0x1F {
+            ensure!(data.len() >= 2, "truncated data object");
+            (
+                u16::from_be_bytes([data[0], data[1]]),
+                data[0] & 0x20 != 0,
+                2,
+            )
+        } else {
+            (u16::from(data[0]), data[0] & 0x20 != 0, 1)
+        };
+        data = &data[tag_len..];
+
+        let (len, len_len) = match data.first() {
+            Some(&len @ 0..=0x7F) => (usize::from(len), 1),
+            Some(0x81) if data.len() >= 2 => (usize::from(data[1]), 2),
+            Some(0x82) if data.len() >= 3 => {
+                (usize::from(u16::from_be_bytes([data[1], data[2]])), 3)
+            }
+            _ => bail!("invalid data object length"),
+        };
+        ensure!(data.len() >= len_len + len, "truncated data object");
+        let value = &data[len_len..len_len + len];
+        data = &data[len_len + len..];
+
+        if current == tag {
+            return Ok(Some(value));
+        }
+        if constructed {
+            if let Some(value) = find_tlv(value, tag)? {
+                return Ok(Some(value));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn left_pad(value: &[u8], len: usize) -> Result<Vec<u8>> {
+    ensure!(value.len() <= len, "value is too large");
+
+    let mut padded = vec![0u8; len - value.len()];
+    padded.extend_from_slice(value);
+    Ok(padded)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::cell::RefCell;
+    use std::io::Read;
+
+    use super::*;
+    use crate::composed::{Esk, Message, SignedPublicKey};
+    use crate::crypto::sym::SymmetricKeyAlgorithm;
+    use crate::test_util::gen_key_with_subkey;
+
+    /// Answers with scripted responses, and records the APDUs it received.
+    struct MockCard {
+        responses: RefCell<Vec<Vec<u8>>>,
+        apdus: RefCell<Vec<Vec<u8>>>,
+    }
+
+    impl MockCard {
+        fn new(responses: &[&[u8]]) -> Self {
+            MockCard {
+                responses: RefCell::new(responses.iter().rev().map(|r| r.to_vec()).collect()),
+                apdus: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl CardTransport for MockCard {
+        fn transmit(&self, apdu: &[u8]) -> Result<Vec<u8>> {
+            self.apdus.borrow_mut().push(apdu.to_vec());
+            self.responses
+                .borrow_mut()
+                .pop()
+                .ok_or_else(|| format_err!("no response left"))
+        }
+    }
+
+    const OK: &[u8] = &[0x90, 0x00];
+
+    /// Builds the application related data, with `fingerprints` in the slots.
+    fn application_data(fingerprints: [Option<&Fingerprint>; 3]) -> Vec<u8> {
+        let ed25519 = [&[0x16][..], &ECCCurve::Ed25519.oid()].concat();
+        let cv25519 = [&[0x12][..], &ECCCurve::Curve25519.oid()].concat();
+        let fingerprints: Vec<u8> = fingerprints
+            .iter()
+            .flat_map(|fp| fp.map_or(vec![0; 20], |fp| fp.to_vec()))
+            .collect();
+        let discretionary = [
+            tlv(0xC1, &ed25519),
+            tlv(0xC2, &cv25519),
+            tlv(0xC3, &[0x01, 0x08, 0x00, 0x00, 0x20, 0x00]),
+            tlv(0xC4, &[0x00, 0x7F, 0x7F, 0x7F, 0x03, 0x00, 0x03]),
+            tlv(0xC5, &fingerprints),
+            tlv(0xCD, &[0x5E, 0x0B, 0xE1, 0x00, 0, 0, 0, 0, 0, 0, 0, 0]),
+        ]
+        .concat();
+        let aid = hex::decode("D2760001240103040006123456780000").unwrap();
+
+        [
+            tlv(0x6E, &[tlv(0x4F, &aid), tlv(0x73, &discretionary)].concat()),
+            OK.to_vec(),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_application_data() {
+        let key = gen_key_with_subkey("Alice <alice@example.com>");
+        let fingerprint = key.fingerprint();
+        let card = OpenPgpCard::select(MockCard::new(&[
+            OK,
+            &application_data([Some(&fingerprint), None, None]),
+        ]))
+        .unwrap();
+
+        let data = card.application_data().unwrap();
+        assert_eq!(data.version(), (3, 4));
+        assert_eq!(data.manufacturer(), 6);
+        assert_eq!(data.serial_number(), 0x12345678);
+        assert_eq!(
+            data.pin_status,
+            PinStatus {
+                signing_pin_valid_for_multiple: false,
+                user_retries: 3,
+                reset_code_retries: 0,
+                admin_retries: 3,
+            }
+        );
+
+        let signing = data.key(KeySlot::Signing);
+        assert_eq!(
+            signing.attributes,
+            AlgorithmAttributes::Ecc {
+                algorithm: PublicKeyAlgorithm::EdDSA,
+                curve: ECCCurve::Ed25519
+            }
+        );
+        assert_eq!(signing.fingerprint, Some(fingerprint));
+        assert_eq!(signing.created_at.unwrap().timestamp(), 0x5E0BE100);
+
+        let decryption = data.key(KeySlot::Decryption);
+        assert_eq!(
+            decryption.attributes,
+            AlgorithmAttributes::Ecc {
+                algorithm: PublicKeyAlgorithm::ECDH,
+                curve: ECCCurve::Curve25519
+            }
+        );
+        assert_eq!(decryption.fingerprint, None);
+        assert_eq!(decryption.created_at, None);
+        assert_eq!(
+            data.key(KeySlot::Authentication).attributes,
+            AlgorithmAttributes::Rsa {
+                modulus_bits: 2048,
+                exponent_bits: 32,
+                import_format: 0
+            }
+        );
+
+        let apdus = card.transport().apdus.borrow();
+        assert_eq!(
+            apdus[0],
+            [&[0x00, 0xA4, 0x04, 0x00, 0x06][..], &OPENPGP_AID].concat()
+        );
+        assert_eq!(apdus[1], [0x00, 0xCA, 0x00, 0x6E, 0x00]);
+    }
+
+    #[test]
+    fn test_sign() {
+        let key = gen_key_with_subkey("Alice <alice@example.com>");
+        let public = key.primary_key.public_key();
+        let digest = HashAlgorithm::SHA2_256.digest(b"hello").unwrap();
+        let mpis = key
+            .create_signature(String::new, HashAlgorithm::SHA2_256, &digest)
+            .unwrap();
+        // the card returns r || s, in two parts
+        let sig = [
+            left_pad(&mpis[0], 32).unwrap(),
+            left_pad(&mpis[1], 32).unwrap(),
+        ]
+        .concat();
+
+        let card = OpenPgpCard::select(MockCard::new(&[
+            OK,
+            &application_data([Some(&key.fingerprint()), None, None]),
+            OK,
+            &[&sig[..40], &[0x61, 24][..]].concat(),
+            &[&sig[40..], OK].concat(),
+        ]))
+        .unwrap();
+        let card_key = card
+            .key(KeySlot::Signing, &public, public.public_params())
+            .unwrap();
+        card.verify_pin(Pin::Signing, b"123456").unwrap();
+
+        let signature = card_key.sign(HashAlgorithm::SHA2_256, &digest).unwrap();
+        public
+            .verify_signature(HashAlgorithm::SHA2_256, &digest, &signature)
+            .unwrap();
+
+        let apdus = card.transport().apdus.borrow();
+        assert_eq!(apdus[2], b"\x00\x20\x00\x81\x06123456");
+        assert_eq!(
+            apdus[3],
+            [&[0x00, 0x2A, 0x9E, 0x9A, 0x20][..], &digest, &[0x00]].concat()
+        );
+        assert_eq!(apdus[4], [0x00, 0xC0, 0x00, 0x00, 24]);
+
+        // the key must be in the slot
+        let card = OpenPgpCard::select(MockCard::new(&[
+            OK,
+            &application_data([None, Some(&key.fingerprint()), None]),
+        ]))
+        .unwrap();
+        assert!(card
+            .key(KeySlot::Signing, &public, public.public_params())
+            .is_err());
+    }
+
+    #[test]
+    fn test_decrypt() {
+        let key = gen_key_with_subkey("Alice <alice@example.com>");
+        let cert: SignedPublicKey = key.clone().into();
+        let subkey = &cert.public_subkeys[0].key;
+        let msg = Message::new_literal("", "hello")
+            .encrypt_to_keys(
+                &mut rand::thread_rng(),
+                SymmetricKeyAlgorithm::AES128,
+                &[subkey],
+            )
+            .unwrap();
+
+        // compute the shared secret with the secret key, as the card would
+        let Message::Encrypted { ref esk, .. } = msg else {
+            panic!("not encrypted");
+        };
+        let Esk::PublicKeyEncryptedSessionKey(ref pkesk) = esk[0] else {
+            panic!("not encrypted to a key");
+        };
+        let ephemeral: [u8; 32] = pkesk.mpis()[0][1..].try_into().unwrap();
+        let mut shared = Vec::new();
+        key.secret_subkeys[0]
+            .unlock(String::new, |repr| {
+                let SecretKeyRepr::ECDH(secret) = repr else {
+                    panic!("not an ECDH key");
+                };
+                let mut secret_le = secret.secret;
+                secret_le.reverse();
+                let point = x25519_dalek::StaticSecret::from(secret_le)
+                    .diffie_hellman(&x25519_dalek::PublicKey::from(ephemeral));
+                shared = point.as_bytes().to_vec();
+                Ok(())
+            })
+            .unwrap();
+
+        let card = OpenPgpCard::select(MockCard::new(&[
+            OK,
+            &application_data([None, Some(&subkey.fingerprint()), None]),
+            OK,
+            &[&shared[..], OK].concat(),
+        ]))
+        .unwrap();
+        let card_key = card
+            .key(KeySlot::Decryption, subkey, subkey.public_params())
+            .unwrap();
+        card.verify_pin(Pin::User, b"123456").unwrap();
+
+        let (mut decrypter, ids) = msg.decrypt_with_decryptors(&[&card_key]).unwrap();
+        assert_eq!(ids, vec![subkey.key_id()]);
+        let mut content = String::new();
+        decrypter.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "hello");
+
+        let apdus = card.transport().apdus.borrow();
+        let data = [&[0xA6, 0x25, 0x7F, 0x49, 0x22, 0x86, 0x20][..], &ephemeral].concat();
+        assert_eq!(
+            apdus[3],
+            [&[0x00, 0x2A, 0x80, 0x86, 0x27][..], &data, &[0x00]].concat()
+        );
+    }
+
+    #[test]
+    fn test_import_key() {
+        let key = gen_key_with_subkey("Alice <alice@example.com>");
+        let subkey = &key.secret_subkeys[0].key;
+        let card = OpenPgpCard::select(MockCard::new(&[OK, OK, OK, OK, OK, OK])).unwrap();
+        card.verify_pin(Pin::Admin, b"12345678").unwrap();
+        card.import_subkey(KeySlot::Decryption, subkey, String::new)
+            .unwrap();
+
+        let mut secret = Vec::new();
+        subkey
+            .unlock(String::new, |repr| {
+                let SecretKeyRepr::ECDH(key) = repr else {
+                    panic!("not an ECDH key");
+                };
+                secret = key.secret.to_vec();
+                Ok(())
+            })
+            .unwrap();
+
+        let apdus = card.transport().apdus.borrow();
+        let attributes = [&[0x12][..], &ECCCurve::Curve25519.oid()].concat();
+        assert_eq!(
+            apdus[2],
+            [
+                &[0x00, 0xDA, 0x00, 0xC2, attributes.len() as u8][..],
+                &attributes
+            ]
+            .concat()
+        );
+        let header_list = [
+            &[
+                0x4D, 0x2A, 0xB8, 0x00, 0x7F, 0x48, 0x02, 0x92, 0x20, 0x5F, 0x48, 0x20,
+            ][..],
+            &secret,
+        ]
+        .concat();
+        assert_eq!(
+            apdus[3],
+            [&[0x00, 0xDB, 0x3F, 0xFF, 0x2C][..], &header_list].concat()
+        );
+        assert_eq!(
+            apdus[4],
+            [
+                &[0x00, 0xDA, 0x00, 0xC8, 0x14][..],
+                subkey.fingerprint().as_bytes()
+            ]
+            .concat()
+        );
+        let created_at = subkey.created_at().timestamp() as u32;
+        assert_eq!(
+            apdus[5],
+            [
+                &[0x00, 0xDA, 0x00, 0xCF, 0x04][..],
+                &created_at.to_be_bytes()
+            ]
+            .concat()
+        );
+
+        // signing keys do not fit into the decryption slot, and the other way around
+        assert!(card
+            .import_key(KeySlot::Decryption, &key.primary_key, String::new)
+            .is_err());
+        assert!(card
+            .import_subkey(KeySlot::Signing, subkey, String::new)
+            .is_err());
+    }
+
+    #[test]
+    fn test_command_chaining() {
+        let card = OpenPgpCard::select(MockCard::new(&[OK, OK, OK])).unwrap();
+        card.command([0x00, 0xDB, 0x3F, 0xFF], &[0xAB; 300], false)
+            .unwrap();
+
+        let apdus = card.transport().apdus.borrow();
+        assert_eq!(apdus[1][..5], [0x10, 0xDB, 0x3F, 0xFF, 0xFF]);
+        assert_eq!(apdus[1].len(), 5 + 255);
+        assert_eq!(apdus[2][..5], [0x00, 0xDB, 0x3F, 0xFF, 45]);
+        assert_eq!(apdus[2].len(), 5 + 45);
+    }
+
+    #[test]
+    fn test_errors() {
+        let card = OpenPgpCard::select(MockCard::new(&[
+            OK,
+            &[0x63, 0xC2],
+            &[0x69, 0x83],
+            &[0x69, 0x82],
+        ]))
+        .unwrap();
+
+        let err = card.verify_pin(Pin::User, b"000000").unwrap_err();
+        assert!(err.to_string().contains("2 tries left"), "{}", err);
+        let err = card.verify_pin(Pin::User, b"000000").unwrap_err();
+        assert!(err.to_string().contains("blocked"), "{}", err);
+        assert!(card
+            .sign(
+                KeySlot::Signing,
+                PublicKeyAlgorithm::EdDSA,
+                HashAlgorithm::SHA2_256,
+                &[0; 32]
+            )
+            .is_err());
+        assert!(card
+            .sign(
+                KeySlot::Decryption,
+                PublicKeyAlgorithm::EdDSA,
+                HashAlgorithm::SHA2_256,
+                &[0; 32]
+            )
+            .is_err());
+
+        assert!(OpenPgpCard::select(MockCard::new(&[&[0x6A, 0x82]])).is_err());
+    }
+
+    #[test]
+    fn test_find_tlv() {
+        let data = [
+            tlv(0x4F, &[1, 2]),
+            tlv(0x73, &tlv(0xC5, &[0xAA; 200])),
+            tlv(0x5F52, &[3]),
+        ]
+        .concat();
+        assert_eq!(find_tlv(&data, 0x4F).unwrap(), Some(&[1, 2][..]));
+        assert_eq!(find_tlv(&data, 0xC5).unwrap(), Some(&[0xAA; 200][..]));
+        assert_eq!(find_tlv(&data, 0x5F52).unwrap(), Some(&[3][..]));
+        assert_eq!(find_tlv(&data, 0xC6).unwrap(), None);
+        assert!(find_tlv(&data[..10], 0xC6).is_err());
+    }
+}
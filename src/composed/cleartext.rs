@@ -0,0 +1,296 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use chrono::SubsecRound;
+
+use crate::composed::{Deserializable, StandaloneSignature};
+use crate::crypto::hash::HashAlgorithm;
+use crate::errors::Result;
+use crate::packet::{SignatureConfig, SignatureType, Subpacket, SubpacketData};
+use crate::types::{PassphraseProvider, PublicKeyTrait, SecretKeyTrait};
+
+const HEADER_LINE: &str = "-----BEGIN PGP SIGNED MESSAGE-----";
+const SIGNATURE_LINE: &str = "-----BEGIN PGP SIGNATURE-----";
+
+/// A message signed using the Cleartext Signature Framework.
+///
+/// The text stays readable without OpenPGP software, and is followed by an armored signature.
+#[derive(Debug, Clone)]
+pub struct CleartextSignedMessage {
+    /// The text, without dash-escaping, with `\n` line endings.
+    text: String,
+    /// The algorithms listed in the `Hash` headers.
+    hashes: Vec<HashAlgorithm>,
+    signatures: Vec<StandaloneSignature>,
+}
+
+impl CleartextSignedMessage {
+    /// Signs `text` with `key`.
+    ///
+    /// Trailing whitespace and the final line ending are not signed, as they do not survive
+    /// transport reliably.
+    pub fn sign<F>(
+        text: &str,
+        key: &impl SecretKeyTrait,
+        key_pw: F,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<Self>
+    where
+        F: PassphraseProvider,
+    {
+        // fails early for algorithms that can not be named in the header
+        hash_name(hash_algorithm)?;
+
+        let hashed_subpackets = vec![
+            Subpacket::regular(SubpacketData::IssuerFingerprint(key.fingerprint())),
+            Subpacket::regular(SubpacketData::SignatureCreationTime(
+                chrono::Utc::now().trunc_subsecs(0),
+            )),
+        ];
+        let unhashed_subpackets = vec![Subpacket::regular(SubpacketData::Issuer(key.key_id()))];
+        let config = SignatureConfig::new_v4(
+            Default::default(),
+            SignatureType::Text,
+            key.algorithm(),
+            hash_algorithm,
+            hashed_subpackets,
+            unhashed_subpackets,
+        );
+
+        let text = text.lines().collect::<Vec<_>>().join("\n");
+        let signature = config.sign(key, key_pw, canonicalize(&text).as_bytes())?;
+
+        Ok(CleartextSignedMessage {
+            text,
+            hashes: vec![hash_algorithm],
+            signatures: vec![StandaloneSignature::new(signature)],
+        })
+    }
+
+    /// Parses a cleartext signed message.
+    ///
+    /// Any text before the `-----BEGIN PGP SIGNED MESSAGE-----` line is ignored.
+    pub fn from_string(input: &str) -> Result<Self> {
+        let mut lines = input.lines().map(|l| l.trim_end_matches('\r'));
+
+        lines
+            .by_ref()
+            .find(|l| l.trim_end() == HEADER_LINE)
+            .ok_or_else(|| format_err!("missing cleartext header"))?;
+
+        let mut hashes = Vec::new();
+        for line in lines.by_ref() {
+            if line.trim().is_empty() {
+                break;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                bail!("invalid cleartext header: {:?}", line);
+            };
+            ensure_eq!(key, "Hash", "unexpected cleartext header");
+            for name in value.split(',') {
+                hashes.push(parse_hash_name(name.trim())?);
+            }
+        }
+
+        let mut text_lines = Vec::new();
+        let mut found_signature = false;
+        for line in lines.by_ref() {
+            if line.trim_end() == SIGNATURE_LINE {
+                found_signature = true;
+                break;
+            }
+            text_lines.push(line.strip_prefix("- ").unwrap_or(line));
+        }
+        ensure!(found_signature, "missing cleartext signature");
+
+        let armored = std::iter::once(SIGNATURE_LINE)
+            .chain(lines)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let (signatures, _headers) = StandaloneSignature::from_string_many(&armored)?;
+        let signatures = signatures.collect::<Result<Vec<_>>>()?;
+        ensure!(!signatures.is_empty(), "missing cleartext signature");
+
+        // without headers, MD5 is implied, which is not supported anyways
+        for signature in &signatures {
+            let hash_alg = signature.signature.config.hash_alg;
+            ensure!(
+                hashes.contains(&hash_alg),
+                "signature hash algorithm {:?} is not listed in the header",
+                hash_alg
+            );
+        }
+
+        Ok(CleartextSignedMessage {
+            text: text_lines.join("\n"),
+            hashes,
+            signatures,
+        })
+    }
+
+    /// The text, without dash-escaping, and with `\n` line endings.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The text, as it is hashed for signing.
+    pub fn signed_text(&self) -> String {
+        canonicalize(&self.text)
+    }
+
+    pub fn signatures(&self) -> &[StandaloneSignature] {
+        &self.signatures
+    }
+
+    /// Verifies the text against `key`, returning the first matching valid signature.
+    pub fn verify(&self, key: &impl PublicKeyTrait) -> Result<&StandaloneSignature> {
+        let signed_text = self.signed_text();
+        let mut last_err = None;
+        for signature in &self.signatures {
+            match signature.verify(key, signed_text.as_bytes()) {
+                Ok(()) => return Ok(signature),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| format_err!("no signature found")))
+    }
+
+    pub fn to_armored_writer(&self, writer: &mut impl io::Write) -> Result<()> {
+        writeln!(writer, "{}", HEADER_LINE)?;
+        let names = self
+            .hashes
+            .iter()
+            .map(|&hash| hash_name(hash))
+            .collect::<Result<Vec<_>>>()?;
+        writeln!(writer, "Hash: {}", names.join(", "))?;
+        writeln!(writer)?;
+
+        for line in self.text.lines() {
+            if line.starts_with('-') {
+                write!(writer, "- ")?;
+            }
+            writeln!(writer, "{}", line)?;
+        }
+
+        let headers: Option<&BTreeMap<String, String>> = None;
+        for signature in &self.signatures {
+            signature.to_armored_writer(writer, headers)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn to_armored_string(&self) -> Result<String> {
+        let mut buf = Vec::new();
+        self.to_armored_writer(&mut buf)?;
+
+        Ok(String::from_utf8(buf).map_err(|err| err.utf8_error())?)
+    }
+}
+
+/// Converts the text into the form that is signed: trailing whitespace is removed from
+/// each line, and lines are separated by `\r\n`.
+fn canonicalize(text: &str) -> String {
+    text.lines()
+        .map(|l| l.trim_end_matches([' ', '\t', '\r']))
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+fn hash_name(hash: HashAlgorithm) -> Result<&'static str> {
+    let name = match hash {
+        HashAlgorithm::MD5 => "MD5",
+        HashAlgorithm::SHA1 => "SHA1",
+        HashAlgorithm::RIPEMD160 => "RIPEMD160",
+        HashAlgorithm::SHA2_224 => "SHA224",
+        HashAlgorithm::SHA2_256 => "SHA256",
+        HashAlgorithm::SHA2_384 => "SHA384",
+        HashAlgorithm::SHA2_512 => "SHA512",
+        HashAlgorithm::SHA3_256 => "SHA3-256",
+        HashAlgorithm::SHA3_512 => "SHA3-512",
+        _ => unsupported_err!("hash algorithm {:?} in cleartext header", hash),
+    };
+
+    Ok(name)
+}
+
+fn parse_hash_name(name: &str) -> Result<HashAlgorithm> {
+    let hash = match name {
+        "MD5" => HashAlgorithm::MD5,
+        "SHA1" => HashAlgorithm::SHA1,
+        "RIPEMD160" => HashAlgorithm::RIPEMD160,
+        "SHA224" => HashAlgorithm::SHA2_224,
+        "SHA256" => HashAlgorithm::SHA2_256,
+        "SHA384" => HashAlgorithm::SHA2_384,
+        "SHA512" => HashAlgorithm::SHA2_512,
+        "SHA3-256" => HashAlgorithm::SHA3_256,
+        "SHA3-512" => HashAlgorithm::SHA3_512,
+        _ => unsupported_err!("hash algorithm {:?} in cleartext header", name),
+    };
+
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::gen_key;
+
+    #[test]
+    fn test_cleartext_roundtrip() {
+        let key = gen_key("Alice <alice@example.com>");
+        let text = "Hello World\n- a dash-escaped line\n-----\ntrailing whitespace  \t\n";
+        let msg =
+            CleartextSignedMessage::sign(text, &key, String::new, HashAlgorithm::SHA2_512).unwrap();
+        assert_eq!(
+            msg.signed_text(),
+            "Hello World\r\n- a dash-escaped line\r\n-----\r\ntrailing whitespace"
+        );
+
+        let armored = msg.to_armored_string().unwrap();
+        assert!(armored.starts_with("-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA512\n\n"));
+        assert!(armored.contains("\n- - a dash-escaped line\n- -----\n"));
+
+        // transport may change line endings and trailing whitespace
+        let transported = armored
+            .replace('\n', "\r\n")
+            .replace("whitespace", "whitespace ");
+        let parsed = CleartextSignedMessage::from_string(&transported).unwrap();
+        assert!(parsed
+            .text()
+            .starts_with("Hello World\n- a dash-escaped line\n-----\ntrailing whitespace"));
+        parsed.verify(&key.public_key()).unwrap();
+
+        let tampered = armored.replace("Hello", "Hallo");
+        let parsed = CleartextSignedMessage::from_string(&tampered).unwrap();
+        assert!(parsed.verify(&key.public_key()).is_err());
+    }
+
+    #[test]
+    fn test_cleartext_headers() {
+        let key = gen_key("Alice <alice@example.com>");
+        let armored =
+            CleartextSignedMessage::sign("Hello", &key, String::new, HashAlgorithm::SHA2_256)
+                .unwrap()
+                .to_armored_string()
+                .unwrap();
+
+        let other_hash = armored.replace("Hash: SHA256", "Hash: SHA1, SHA512");
+        assert!(CleartextSignedMessage::from_string(&other_hash).is_err());
+
+        let multiple = armored.replace("Hash: SHA256", "Hash: SHA1, SHA256");
+        CleartextSignedMessage::from_string(&multiple)
+            .unwrap()
+            .verify(&key.public_key())
+            .unwrap();
+
+        let unknown = armored.replace("Hash: SHA256", "Hash: SHA256\nCharset: UTF-8");
+        assert!(CleartextSignedMessage::from_string(&unknown).is_err());
+
+        let truncated = &armored[..armored.find(SIGNATURE_LINE).unwrap()];
+        assert!(CleartextSignedMessage::from_string(truncated).is_err());
+    }
+}
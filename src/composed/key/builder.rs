@@ -4,7 +4,7 @@ use chrono::{self, SubsecRound};
 use rand::{thread_rng, CryptoRng, Rng};
 use smallvec::SmallVec;
 
-use crate::composed::{KeyDetails, SecretKey, SecretSubkey};
+use crate::composed::{KeyDetails, SecretKey, SecretSubkey, SignedSecretKey};
 use crate::crypto::ecc_curve::ECCCurve;
 use crate::crypto::hash::HashAlgorithm;
 use crate::crypto::public_key::PublicKeyAlgorithm;
@@ -12,7 +12,7 @@ use crate::crypto::sym::SymmetricKeyAlgorithm;
 use crate::crypto::{dsa, ecdh, ecdsa, eddsa, rsa};
 use crate::errors::Result;
 use crate::packet::{self, KeyFlags, UserAttribute, UserId};
-use crate::types::{self, CompressionAlgorithm, PublicParams, RevocationKey};
+use crate::types::{self, CompressionAlgorithm, KeyTrait, PublicParams, RevocationKey};
 
 #[derive(Debug, PartialEq, Eq, Builder)]
 #[builder(build_fn(validate = "Self::validate"))]
@@ -49,6 +49,8 @@ pub struct SecretKeyParams {
     user_attributes: Vec<UserAttribute>,
     #[builder(default)]
     passphrase: Option<String>,
+    /// The creation time of the primary key, which is part of its fingerprint. Set it, together
+    /// with a seeded RNG, to generate the same key again. Truncated to seconds, defaults to now.
     #[builder(default = "chrono::Utc::now().trunc_subsecs(0)")]
     created_at: chrono::DateTime<chrono::Utc>,
     #[builder(default)]
@@ -80,9 +82,12 @@ pub struct SubkeyParams {
     #[builder(default)]
     user_attributes: Vec<UserAttribute>,
     #[builder(default)]
-    passphrase: Option<String>,
-    #[builder(default = "chrono::Utc::now().trunc_subsecs(0)")]
-    created_at: chrono::DateTime<chrono::Utc>,
+    pub(crate) passphrase: Option<String>,
+    /// The creation time of the subkey, which is part of its fingerprint. Truncated to seconds,
+    /// defaults to the creation time of the primary key when generated together with it, and
+    /// to now otherwise.
+    #[builder(default, setter(strip_option))]
+    created_at: Option<chrono::DateTime<chrono::Utc>>,
     #[builder(default)]
     packet_version: types::Version,
     #[builder(default)]
@@ -162,21 +167,96 @@ impl SecretKeyParams {
         self.generate_with_rng(&mut rng)
     }
 
+    /// Generates the key and creates all self-signatures and subkey binding signatures,
+    /// resulting in a complete, ready to use certificate.
+    pub fn generate_signed(self) -> Result<SignedSecretKey> {
+        let mut rng = thread_rng();
+        self.generate_signed_with_rng(&mut rng)
+    }
+
+    /// Same as [`generate_signed`](SecretKeyParams::generate_signed), using the given RNG.
+    pub fn generate_signed_with_rng<R: Rng + CryptoRng>(
+        self,
+        rng: &mut R,
+    ) -> Result<SignedSecretKey> {
+        let passphrase = self.passphrase.clone().unwrap_or_default();
+        let key = self.generate_with_rng(rng)?;
+
+        key.sign(|| passphrase.clone())
+    }
+
     pub fn generate_with_rng<R: Rng + CryptoRng>(self, rng: &mut R) -> Result<SecretKey> {
-        let passphrase = self.passphrase;
+        let passphrase = self.passphrase.clone();
         let (public_params, secret_params) = self.key_type.generate_with_rng(rng, passphrase)?;
         let primary_key = packet::SecretKey {
             details: packet::PublicKey {
                 packet_version: self.packet_version,
                 version: self.version,
                 algorithm: self.key_type.to_alg(),
-                created_at: self.created_at,
-                expiration: self.expiration.map(|v| v.as_secs() as u16),
+                created_at: self.created_at.trunc_subsecs(0),
+                expiration: packet_expiration(self.version, self.expiration),
                 public_params,
             },
             secret_params,
         };
 
+        self.into_key(rng, primary_key)
+    }
+
+    /// Uses the existing `primary_key`, e.g. one imported from PKCS#8 or OpenSSH, instead of
+    /// generating a new one. The user ids, preferences and subkeys are created from these params.
+    ///
+    /// The creation time and versions of `primary_key` are kept, its algorithm has to match the
+    /// key type. If a passphrase is set, an unencrypted `primary_key` is encrypted with it.
+    pub fn import(self, primary_key: packet::SecretKey) -> Result<SecretKey> {
+        let mut rng = thread_rng();
+        self.import_with_rng(&mut rng, primary_key)
+    }
+
+    /// Same as [`import`](SecretKeyParams::import), using the given RNG.
+    pub fn import_with_rng<R: Rng + CryptoRng>(
+        self,
+        rng: &mut R,
+        mut primary_key: packet::SecretKey,
+    ) -> Result<SecretKey> {
+        ensure_eq!(
+            primary_key.algorithm(),
+            self.key_type.to_alg(),
+            "the imported key does not match the key type"
+        );
+        if let (Some(passphrase), types::SecretParams::Plain(_)) =
+            (&self.passphrase, primary_key.secret_params())
+        {
+            primary_key.set_passphrase_with_rng(rng, String::new, Some(passphrase))?;
+        }
+
+        self.into_key(rng, primary_key)
+    }
+
+    /// Imports the existing `primary_key` and creates all self-signatures and subkey binding
+    /// signatures, like [`generate_signed`](SecretKeyParams::generate_signed).
+    pub fn import_signed(self, primary_key: packet::SecretKey) -> Result<SignedSecretKey> {
+        let mut rng = thread_rng();
+        self.import_signed_with_rng(&mut rng, primary_key)
+    }
+
+    /// Same as [`import_signed`](SecretKeyParams::import_signed), using the given RNG.
+    pub fn import_signed_with_rng<R: Rng + CryptoRng>(
+        self,
+        rng: &mut R,
+        primary_key: packet::SecretKey,
+    ) -> Result<SignedSecretKey> {
+        let passphrase = self.passphrase.clone().unwrap_or_default();
+        let key = self.import_with_rng(rng, primary_key)?;
+
+        key.sign(|| passphrase.clone())
+    }
+
+    fn into_key<R: Rng + CryptoRng>(
+        self,
+        rng: &mut R,
+        primary_key: packet::SecretKey,
+    ) -> Result<SecretKey> {
         let mut keyflags = KeyFlags::default();
         keyflags.set_certify(self.can_certify);
         keyflags.set_encrypt_comms(self.can_encrypt);
@@ -197,40 +277,75 @@ impl SecretKeyParams {
                 self.preferred_hash_algorithms,
                 self.preferred_compression_algorithms,
                 self.revocation_key,
+                self.expiration.map(to_key_expiration).transpose()?,
             ),
             Default::default(),
             self.subkeys
                 .into_iter()
-                .map(|subkey| {
-                    let passphrase = subkey.passphrase;
-                    let (public_params, secret_params) = subkey.key_type.generate(passphrase)?;
-                    let mut keyflags = KeyFlags::default();
-                    keyflags.set_certify(subkey.can_certify);
-                    keyflags.set_encrypt_comms(subkey.can_encrypt);
-                    keyflags.set_encrypt_storage(subkey.can_encrypt);
-                    keyflags.set_sign(subkey.can_sign);
-                    keyflags.set_authentication(subkey.can_authenticate);
-
-                    Ok(SecretSubkey::new(
-                        packet::SecretSubkey {
-                            details: packet::PublicSubkey {
-                                packet_version: subkey.packet_version,
-                                version: subkey.version,
-                                algorithm: subkey.key_type.to_alg(),
-                                created_at: subkey.created_at,
-                                expiration: subkey.expiration.map(|v| v.as_secs() as u16),
-                                public_params,
-                            },
-                            secret_params,
-                        },
-                        keyflags,
-                    ))
+                .map(|mut subkey| {
+                    subkey.created_at.get_or_insert(self.created_at);
+                    subkey.generate_with_rng(rng)
                 })
                 .collect::<Result<Vec<_>>>()?,
         ))
     }
 }
 
+/// Only v2 and v3 keys store their expiration, in days, in the key packet itself.
+/// Newer keys use the `KeyExpirationTime` subpacket of their self-signatures instead.
+fn packet_expiration(version: types::KeyVersion, expiration: Option<Duration>) -> Option<u16> {
+    match version {
+        types::KeyVersion::V2 | types::KeyVersion::V3 => {
+            expiration.map(|v| u16::try_from(v.as_secs() / (24 * 60 * 60)).unwrap_or(u16::MAX))
+        }
+        _ => None,
+    }
+}
+
+/// Converts the configured expiration into the duration stored in the `KeyExpirationTime`
+/// subpacket, which is relative to the key creation time.
+fn to_key_expiration(expiration: Duration) -> Result<chrono::Duration> {
+    chrono::Duration::from_std(expiration)
+        .map_err(|_| format_err!("invalid key expiration {:?}", expiration))
+}
+
+impl SubkeyParams {
+    pub fn generate(self) -> Result<SecretSubkey> {
+        let mut rng = thread_rng();
+        self.generate_with_rng(&mut rng)
+    }
+
+    pub fn generate_with_rng<R: Rng + CryptoRng>(self, rng: &mut R) -> Result<SecretSubkey> {
+        let passphrase = self.passphrase;
+        let (public_params, secret_params) = self.key_type.generate_with_rng(rng, passphrase)?;
+        let mut keyflags = KeyFlags::default();
+        keyflags.set_certify(self.can_certify);
+        keyflags.set_encrypt_comms(self.can_encrypt);
+        keyflags.set_encrypt_storage(self.can_encrypt);
+        keyflags.set_sign(self.can_sign);
+        keyflags.set_authentication(self.can_authenticate);
+
+        Ok(SecretSubkey::new(
+            packet::SecretSubkey {
+                details: packet::PublicSubkey {
+                    packet_version: self.packet_version,
+                    version: self.version,
+                    algorithm: self.key_type.to_alg(),
+                    created_at: self
+                        .created_at
+                        .unwrap_or_else(chrono::Utc::now)
+                        .trunc_subsecs(0),
+                    expiration: packet_expiration(self.version, self.expiration),
+                    public_params,
+                },
+                secret_params,
+            },
+            keyflags,
+            self.expiration.map(to_key_expiration).transpose()?,
+        ))
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum KeyType {
     /// Encryption & Signing with RSA and the given bitsize.
@@ -319,7 +434,7 @@ impl KeyType {
                     id,
                 )?)
             }
-            None => types::SecretParams::Plain(plain),
+            None => types::SecretParams::Plain(plain.to_secret()),
         };
 
         Ok((pub_params, secret))
@@ -334,6 +449,7 @@ mod tests {
 
     use crate::composed::{Deserializable, SignedPublicKey, SignedSecretKey};
     use crate::crypto::hash::HashAlgorithm;
+    use crate::ser::Serialize;
     use crate::types::SecretKeyTrait;
 
     use rand::SeedableRng;
@@ -729,4 +845,179 @@ mod tests {
             gen_dsa(rng, DsaKeySize::B3072);
         }
     }
+
+    #[test]
+    fn key_gen_signed_with_subkeys_and_expiration() {
+        let _ = pretty_env_logger::try_init();
+        let rng = &mut ChaCha8Rng::seed_from_u64(0);
+
+        let one_year = Duration::from_secs(365 * 24 * 60 * 60);
+        let key_params = SecretKeyParamsBuilder::default()
+            .key_type(KeyType::EdDSA)
+            .can_certify(true)
+            .can_sign(true)
+            .primary_user_id("Me <me@mail.com>".into())
+            .user_id("Me Too <me-too@mail.com>")
+            .passphrase(Some("hello".into()))
+            .expiration(Some(one_year))
+            .preferred_symmetric_algorithms(smallvec![SymmetricKeyAlgorithm::AES256])
+            .preferred_hash_algorithms(smallvec![HashAlgorithm::SHA2_256])
+            .preferred_compression_algorithms(smallvec![CompressionAlgorithm::ZLIB])
+            .subkey(
+                SubkeyParamsBuilder::default()
+                    .key_type(KeyType::ECDH)
+                    .can_encrypt(true)
+                    .expiration(Some(one_year))
+                    .build()
+                    .unwrap(),
+            )
+            .subkey(
+                SubkeyParamsBuilder::default()
+                    .key_type(KeyType::EdDSA)
+                    .can_sign(true)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        let signed_key = key_params
+            .generate_signed_with_rng(rng)
+            .expect("failed to generate key");
+        signed_key.verify().expect("invalid key");
+
+        let expiration = chrono::Duration::from_std(one_year).unwrap();
+        assert_eq!(signed_key.details.users.len(), 2);
+        for user in &signed_key.details.users {
+            assert_eq!(user.signatures[0].key_expiration_time(), Some(&expiration));
+        }
+        assert_eq!(
+            signed_key.expires_at(),
+            Some(*signed_key.primary_key.created_at() + expiration)
+        );
+
+        assert_eq!(signed_key.secret_subkeys.len(), 2);
+        let enc = &signed_key.secret_subkeys[0];
+        assert!(enc.signatures[0].key_flags().encrypt_comms());
+        assert_eq!(enc.signatures[0].key_expiration_time(), Some(&expiration));
+        let sign = &signed_key.secret_subkeys[1];
+        assert!(sign.signatures[0].key_flags().sign());
+        assert_eq!(sign.signatures[0].key_expiration_time(), None);
+        let backsig = sign.signatures[0]
+            .embedded_signature()
+            .expect("missing back signature");
+        backsig
+            .verify_backwards_key_binding(&sign.key, &signed_key.primary_key)
+            .expect("invalid back signature");
+
+        // expiration survives the round trip through the unsigned public key
+        let public_key = signed_key.public_key();
+        let signed_public_key = public_key
+            .sign(&signed_key, || "hello".into())
+            .expect("failed to sign public key");
+        signed_public_key.verify().expect("invalid public key");
+        assert_eq!(signed_public_key.expires_at(), signed_key.expires_at());
+        assert_eq!(
+            signed_public_key.public_subkeys[0].signatures[0].key_expiration_time(),
+            Some(&expiration)
+        );
+    }
+
+    /// Keys generated from the same seed and creation time are the same.
+    #[test]
+    fn key_gen_golden() {
+        let created_at = chrono::TimeZone::timestamp_opt(&chrono::Utc, 1_700_000_000, 0).unwrap();
+        let gen = |seed| {
+            SecretKeyParamsBuilder::default()
+                .key_type(KeyType::EdDSA)
+                .can_certify(true)
+                .can_sign(true)
+                .primary_user_id("Me <me@mail.com>".into())
+                .passphrase(Some("hello".into()))
+                .created_at(created_at)
+                .subkey(
+                    SubkeyParamsBuilder::default()
+                        .key_type(KeyType::ECDH)
+                        .can_encrypt(true)
+                        .created_at(created_at)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap()
+                .generate_signed_with_rng(&mut ChaCha8Rng::seed_from_u64(seed))
+                .unwrap()
+        };
+
+        let key = gen(0);
+        assert_eq!(
+            hex::encode(key.primary_key.fingerprint()),
+            "4ab4dd809c36e8f38d3ec03e7e51387c388e3bc8"
+        );
+        assert_eq!(
+            hex::encode(key.secret_subkeys[0].key.fingerprint()),
+            "1e7028d5ca44121fbe4aeb2d6e1c0505e021f4a8"
+        );
+        // including the salt and IV of the passphrase protection
+        assert_eq!(
+            key.primary_key.to_bytes().unwrap(),
+            gen(0).primary_key.to_bytes().unwrap()
+        );
+        assert_ne!(
+            key.primary_key.fingerprint(),
+            gen(1).primary_key.fingerprint()
+        );
+    }
+
+    /// Subkeys share the creation time of the primary key, unless set explicitly.
+    #[test]
+    fn key_gen_created_at() {
+        let created_at = chrono::DateTime::parse_from_rfc3339("2023-11-14T22:13:20.5Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let subkey_created_at = created_at + chrono::Duration::days(1);
+        let gen = || {
+            SecretKeyParamsBuilder::default()
+                .key_type(KeyType::EdDSA)
+                .can_certify(true)
+                .can_sign(true)
+                .primary_user_id("Me <me@mail.com>".into())
+                .created_at(created_at)
+                .subkey(
+                    SubkeyParamsBuilder::default()
+                        .key_type(KeyType::ECDH)
+                        .can_encrypt(true)
+                        .build()
+                        .unwrap(),
+                )
+                .subkey(
+                    SubkeyParamsBuilder::default()
+                        .key_type(KeyType::ECDH)
+                        .can_encrypt(true)
+                        .created_at(subkey_created_at)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap()
+                .generate_signed_with_rng(&mut ChaCha8Rng::seed_from_u64(0))
+                .unwrap()
+        };
+
+        let key = gen();
+        assert_eq!(key.primary_key.created_at(), &created_at.trunc_subsecs(0));
+        assert_eq!(
+            key.secret_subkeys[0].key.created_at(),
+            &created_at.trunc_subsecs(0)
+        );
+        assert_eq!(
+            key.secret_subkeys[1].key.created_at(),
+            &subkey_created_at.trunc_subsecs(0)
+        );
+
+        // the same key, after a round trip through its serialization
+        let parsed = SignedSecretKey::from_bytes(&key.to_bytes().unwrap()[..]).unwrap();
+        assert_eq!(parsed.primary_key, gen().primary_key);
+        assert_eq!(parsed.secret_subkeys[1].key, gen().secret_subkeys[1].key);
+    }
 }
@@ -1,17 +1,18 @@
 use std::io;
 
-use chrono::{self, SubsecRound};
+use chrono::{self, Duration, SubsecRound};
 use rand::{CryptoRng, Rng};
-use smallvec::SmallVec;
 
 use crate::composed::{KeyDetails, SignedPublicKey, SignedPublicSubKey};
 use crate::crypto::hash::HashAlgorithm;
 use crate::crypto::public_key::PublicKeyAlgorithm;
 use crate::errors::Result;
 use crate::packet::{
-    self, KeyFlags, SignatureConfigBuilder, SignatureType, Subpacket, SubpacketData,
+    self, KeyFlags, Signature, SignatureConfigBuilder, SignatureType, Subpacket, SubpacketData,
+};
+use crate::types::{
+    Fingerprint, KeyId, KeyTrait, Mpi, PassphraseProvider, PublicKeyTrait, SecretKeyTrait,
 };
-use crate::types::{KeyId, KeyTrait, Mpi, PublicKeyTrait, SecretKeyTrait};
 
 /// User facing interface to work with a public key.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -25,6 +26,8 @@ pub struct PublicKey {
 pub struct PublicSubkey {
     key: packet::PublicSubkey,
     keyflags: KeyFlags,
+    expiration: Option<Duration>,
+    backsig: Option<Signature>,
 }
 
 impl PublicKey {
@@ -42,7 +45,7 @@ impl PublicKey {
 
     pub fn sign<F>(self, sec_key: &impl SecretKeyTrait, key_pw: F) -> Result<SignedPublicKey>
     where
-        F: (FnOnce() -> String) + Clone,
+        F: PassphraseProvider + Clone,
     {
         let primary_key = self.primary_key;
         let details = self.details.sign(sec_key, key_pw.clone())?;
@@ -61,7 +64,7 @@ impl PublicKey {
 }
 
 impl KeyTrait for PublicKey {
-    fn fingerprint(&self) -> Vec<u8> {
+    fn fingerprint(&self) -> Fingerprint {
         self.primary_key.fingerprint()
     }
 
@@ -89,25 +92,48 @@ impl PublicKeyTrait for PublicKey {
 }
 
 impl PublicSubkey {
-    pub fn new(key: packet::PublicSubkey, keyflags: KeyFlags) -> Self {
-        PublicSubkey { key, keyflags }
+    pub fn new(
+        key: packet::PublicSubkey,
+        keyflags: KeyFlags,
+        expiration: Option<Duration>,
+    ) -> Self {
+        PublicSubkey {
+            key,
+            keyflags,
+            expiration,
+            backsig: None,
+        }
+    }
+
+    /// Sets the primary key binding signature ("back signature") of this subkey, which gets
+    /// embedded into the binding signature. Signing capable subkeys need one to be valid.
+    pub fn with_backsig(mut self, backsig: Option<Signature>) -> Self {
+        self.backsig = backsig;
+        self
     }
 
     pub fn sign<F>(self, sec_key: &impl SecretKeyTrait, key_pw: F) -> Result<SignedPublicSubKey>
     where
-        F: (FnOnce() -> String) + Clone,
+        F: PassphraseProvider + Clone,
     {
         let key = self.key;
-        let hashed_subpackets = vec![
+        let mut hashed_subpackets = vec![
             Subpacket::regular(SubpacketData::SignatureCreationTime(
                 chrono::Utc::now().trunc_subsecs(0),
             )),
             Subpacket::regular(SubpacketData::KeyFlags(self.keyflags.into())),
-            Subpacket::regular(SubpacketData::IssuerFingerprint(
-                Default::default(),
-                SmallVec::from_slice(&sec_key.fingerprint()),
-            )),
+            Subpacket::regular(SubpacketData::IssuerFingerprint(sec_key.fingerprint())),
         ];
+        if let Some(expiration) = self.expiration {
+            hashed_subpackets.push(Subpacket::regular(SubpacketData::KeyExpirationTime(
+                expiration,
+            )));
+        }
+        if let Some(backsig) = self.backsig {
+            hashed_subpackets.push(Subpacket::regular(SubpacketData::EmbeddedSignature(
+                Box::new(backsig),
+            )));
+        }
 
         let config = SignatureConfigBuilder::default()
             .typ(SignatureType::SubkeyBinding)
@@ -120,12 +146,16 @@ impl PublicSubkey {
 
         let signatures = vec![config.sign_key_binding(sec_key, key_pw, &key)?];
 
-        Ok(SignedPublicSubKey { key, signatures })
+        Ok(SignedPublicSubKey {
+            key,
+            signatures,
+            experimental: Vec::new(),
+        })
     }
 }
 
 impl KeyTrait for PublicSubkey {
-    fn fingerprint(&self) -> Vec<u8> {
+    fn fingerprint(&self) -> Fingerprint {
         self.key.fingerprint()
     }
 
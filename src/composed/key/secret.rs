@@ -1,13 +1,12 @@
-use chrono::{self, SubsecRound};
-use smallvec::SmallVec;
+use chrono::{self, Duration, SubsecRound};
 
 use crate::composed::{KeyDetails, PublicSubkey, SignedSecretKey, SignedSecretSubKey};
 use crate::crypto::public_key::PublicKeyAlgorithm;
 use crate::errors::Result;
 use crate::packet::{
-    self, KeyFlags, SignatureConfigBuilder, SignatureType, Subpacket, SubpacketData,
+    self, KeyFlags, Signature, SignatureConfigBuilder, SignatureType, Subpacket, SubpacketData,
 };
-use crate::types::{KeyId, KeyTrait, SecretKeyTrait};
+use crate::types::{Fingerprint, KeyId, KeyTrait, PassphraseProvider, SecretKeyTrait};
 
 /// User facing interface to work with a secret key.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -22,6 +21,7 @@ pub struct SecretKey {
 pub struct SecretSubkey {
     key: packet::SecretSubkey,
     keyflags: KeyFlags,
+    expiration: Option<Duration>,
 }
 
 impl SecretKey {
@@ -41,7 +41,7 @@ impl SecretKey {
 
     pub fn sign<F>(self, key_pw: F) -> Result<SignedSecretKey>
     where
-        F: (FnOnce() -> String) + Clone,
+        F: PassphraseProvider + Clone,
     {
         let primary_key = self.primary_key;
         let details = self.details.sign(&primary_key, key_pw.clone())?;
@@ -66,7 +66,7 @@ impl SecretKey {
 }
 
 impl KeyTrait for SecretKey {
-    fn fingerprint(&self) -> Vec<u8> {
+    fn fingerprint(&self) -> Fingerprint {
         self.primary_key.fingerprint()
     }
 
@@ -80,25 +80,93 @@ impl KeyTrait for SecretKey {
 }
 
 impl SecretSubkey {
-    pub fn new(key: packet::SecretSubkey, keyflags: KeyFlags) -> Self {
-        SecretSubkey { key, keyflags }
+    pub fn new(
+        key: packet::SecretSubkey,
+        keyflags: KeyFlags,
+        expiration: Option<Duration>,
+    ) -> Self {
+        SecretSubkey {
+            key,
+            keyflags,
+            expiration,
+        }
     }
 
+    /// Creates the binding signature for this subkey.
+    ///
+    /// Signing capable subkeys get an embedded primary key binding signature as well, for which
+    /// the subkey is unlocked using `key_pw`, see [`sign_with_backsig`](SecretSubkey::sign_with_backsig)
+    /// for subkeys with a different passphrase.
     pub fn sign<F>(self, sec_key: &impl SecretKeyTrait, key_pw: F) -> Result<SignedSecretSubKey>
     where
-        F: (FnOnce() -> String) + Clone,
+        F: PassphraseProvider + Clone,
+    {
+        self.sign_with_backsig(sec_key, key_pw.clone(), key_pw)
+    }
+
+    /// Same as [`sign`](SecretSubkey::sign), but signing capable subkeys additionally get an
+    /// embedded primary key binding signature ("back signature"), created by the subkey itself
+    /// after unlocking it with `subkey_pw`.
+    pub fn sign_with_backsig<F, G>(
+        self,
+        sec_key: &impl SecretKeyTrait,
+        key_pw: F,
+        subkey_pw: G,
+    ) -> Result<SignedSecretSubKey>
+    where
+        F: PassphraseProvider,
+        G: PassphraseProvider,
+    {
+        let backsig = if self.keyflags.sign() {
+            let config = SignatureConfigBuilder::default()
+                .typ(SignatureType::KeyBinding)
+                .pub_alg(self.key.algorithm())
+                .hashed_subpackets(vec![
+                    Subpacket::regular(SubpacketData::SignatureCreationTime(
+                        chrono::Utc::now().trunc_subsecs(0),
+                    )),
+                    Subpacket::regular(SubpacketData::IssuerFingerprint(self.key.fingerprint())),
+                ])
+                .unhashed_subpackets(vec![Subpacket::regular(SubpacketData::Issuer(
+                    self.key.key_id(),
+                ))])
+                .build()?;
+
+            Some(config.sign_backwards_key_binding(&self.key, subkey_pw, sec_key)?)
+        } else {
+            None
+        };
+
+        self.sign_binding(sec_key, key_pw, backsig)
+    }
+
+    fn sign_binding<F>(
+        self,
+        sec_key: &impl SecretKeyTrait,
+        key_pw: F,
+        backsig: Option<Signature>,
+    ) -> Result<SignedSecretSubKey>
+    where
+        F: PassphraseProvider,
     {
         let key = self.key;
-        let hashed_subpackets = vec![
+        let mut hashed_subpackets = vec![
             Subpacket::regular(SubpacketData::SignatureCreationTime(
                 chrono::Utc::now().trunc_subsecs(0),
             )),
             Subpacket::regular(SubpacketData::KeyFlags(self.keyflags.into())),
-            Subpacket::regular(SubpacketData::IssuerFingerprint(
-                Default::default(),
-                SmallVec::from_slice(&sec_key.fingerprint()),
-            )),
+            Subpacket::regular(SubpacketData::IssuerFingerprint(sec_key.fingerprint())),
         ];
+        if let Some(expiration) = self.expiration {
+            hashed_subpackets.push(Subpacket::regular(SubpacketData::KeyExpirationTime(
+                expiration,
+            )));
+        }
+        if let Some(backsig) = backsig {
+            hashed_subpackets.push(Subpacket::regular(SubpacketData::EmbeddedSignature(
+                Box::new(backsig),
+            )));
+        }
 
         let config = SignatureConfigBuilder::default()
             .typ(SignatureType::SubkeyBinding)
@@ -110,12 +178,16 @@ impl SecretSubkey {
             .build()?;
         let signatures = vec![config.sign_key_binding(sec_key, key_pw, &key)?];
 
-        Ok(SignedSecretSubKey { key, signatures })
+        Ok(SignedSecretSubKey {
+            key,
+            signatures,
+            experimental: Vec::new(),
+        })
     }
 }
 
 impl KeyTrait for SecretSubkey {
-    fn fingerprint(&self) -> Vec<u8> {
+    fn fingerprint(&self) -> Fingerprint {
         self.key.fingerprint()
     }
 
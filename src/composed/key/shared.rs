@@ -1,15 +1,14 @@
-use chrono::{self, SubsecRound};
+use chrono::{self, Duration};
 use smallvec::SmallVec;
 
 use crate::composed::SignedKeyDetails;
 use crate::crypto::hash::HashAlgorithm;
 use crate::crypto::sym::SymmetricKeyAlgorithm;
 use crate::errors::Result;
-use crate::packet::{
-    KeyFlags, PacketTrait, SignatureConfigBuilder, SignatureType, Subpacket, SubpacketData,
-    UserAttribute, UserId,
+use crate::packet::{KeyFlags, PacketTrait, SignatureType, SubpacketAreas, UserAttribute, UserId};
+use crate::types::{
+    CompressionAlgorithm, PassphraseProvider, RevocationKey, SecretKeyTrait, SignedUser,
 };
-use crate::types::{CompressionAlgorithm, RevocationKey, SecretKeyTrait};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct KeyDetails {
@@ -21,6 +20,7 @@ pub struct KeyDetails {
     preferred_hash_algorithms: SmallVec<[HashAlgorithm; 8]>,
     preferred_compression_algorithms: SmallVec<[CompressionAlgorithm; 8]>,
     revocation_key: Option<RevocationKey>,
+    key_expiration: Option<Duration>,
 }
 
 impl KeyDetails {
@@ -34,6 +34,7 @@ impl KeyDetails {
         preferred_hash_algorithms: SmallVec<[HashAlgorithm; 8]>,
         preferred_compression_algorithms: SmallVec<[CompressionAlgorithm; 8]>,
         revocation_key: Option<RevocationKey>,
+        key_expiration: Option<Duration>,
     ) -> Self {
         KeyDetails {
             primary_user_id,
@@ -44,101 +45,23 @@ impl KeyDetails {
             preferred_hash_algorithms,
             preferred_compression_algorithms,
             revocation_key,
+            key_expiration,
         }
     }
 
     pub fn sign<F>(self, key: &impl SecretKeyTrait, key_pw: F) -> Result<SignedKeyDetails>
     where
-        F: (FnOnce() -> String) + Clone,
+        F: PassphraseProvider + Clone,
     {
-        let keyflags: SmallVec<[u8; 1]> = self.keyflags.into();
-        let preferred_symmetric_algorithms = self.preferred_symmetric_algorithms;
-        let preferred_hash_algorithms = self.preferred_hash_algorithms;
-        let preferred_compression_algorithms = self.preferred_compression_algorithms;
-        let revocation_key = self.revocation_key;
-
         let mut users = vec![];
 
         // primary user id
-        {
-            let id = self.primary_user_id;
-            let mut hashed_subpackets = vec![
-                Subpacket::regular(SubpacketData::IsPrimary(true)),
-                Subpacket::regular(SubpacketData::SignatureCreationTime(
-                    chrono::Utc::now().trunc_subsecs(0),
-                )),
-                Subpacket::regular(SubpacketData::KeyFlags(keyflags.clone())),
-                Subpacket::regular(SubpacketData::PreferredSymmetricAlgorithms(
-                    preferred_symmetric_algorithms.clone(),
-                )),
-                Subpacket::regular(SubpacketData::PreferredHashAlgorithms(
-                    preferred_hash_algorithms.clone(),
-                )),
-                Subpacket::regular(SubpacketData::PreferredCompressionAlgorithms(
-                    preferred_compression_algorithms.clone(),
-                )),
-                Subpacket::regular(SubpacketData::IssuerFingerprint(
-                    Default::default(),
-                    SmallVec::from_slice(&key.fingerprint()),
-                )),
-            ];
-            if let Some(rkey) = revocation_key {
-                hashed_subpackets.push(Subpacket::regular(SubpacketData::RevocationKey(rkey)));
-            }
-
-            let config = SignatureConfigBuilder::default()
-                .typ(SignatureType::CertGeneric)
-                .pub_alg(key.algorithm())
-                .hashed_subpackets(hashed_subpackets)
-                .unhashed_subpackets(vec![Subpacket::regular(SubpacketData::Issuer(
-                    key.key_id(),
-                ))])
-                .build()?;
-
-            let sig = config.sign_certification(key, key_pw.clone(), id.tag(), &id)?;
-
-            users.push(id.into_signed(sig));
-        }
+        users.push(self.sign_user_id(key, key_pw.clone(), &self.primary_user_id, true)?);
 
         // other user ids
-
-        users.extend(
-            self.user_ids
-                .into_iter()
-                .map(|id| {
-                    let config = SignatureConfigBuilder::default()
-                        .typ(SignatureType::CertGeneric)
-                        .pub_alg(key.algorithm())
-                        .hashed_subpackets(vec![
-                            Subpacket::regular(SubpacketData::SignatureCreationTime(
-                                chrono::Utc::now().trunc_subsecs(0),
-                            )),
-                            Subpacket::regular(SubpacketData::KeyFlags(keyflags.clone())),
-                            Subpacket::regular(SubpacketData::PreferredSymmetricAlgorithms(
-                                preferred_symmetric_algorithms.clone(),
-                            )),
-                            Subpacket::regular(SubpacketData::PreferredHashAlgorithms(
-                                preferred_hash_algorithms.clone(),
-                            )),
-                            Subpacket::regular(SubpacketData::PreferredCompressionAlgorithms(
-                                preferred_compression_algorithms.clone(),
-                            )),
-                            Subpacket::regular(SubpacketData::IssuerFingerprint(
-                                Default::default(),
-                                SmallVec::from_slice(&key.fingerprint()),
-                            )),
-                        ])
-                        .unhashed_subpackets(vec![Subpacket::regular(SubpacketData::Issuer(
-                            key.key_id(),
-                        ))])
-                        .build()?;
-
-                    let sig = config.sign_certification(key, key_pw.clone(), id.tag(), &id)?;
-
-                    Ok(id.into_signed(sig))
-                })
-                .collect::<Result<Vec<_>>>()?,
-        );
+        for id in &self.user_ids {
+            users.push(self.sign_user_id(key, key_pw.clone(), id, false)?);
+        }
 
         let user_attributes = self
             .user_attributes
@@ -151,6 +74,50 @@ impl KeyDetails {
             direct_signatures: Default::default(),
             users,
             user_attributes,
+            experimental: Default::default(),
         })
     }
+
+    /// Creates a self-certification for the given user id, carrying the key flags,
+    /// preferences and expiration of these details.
+    pub(crate) fn sign_user_id<F>(
+        &self,
+        key: &impl SecretKeyTrait,
+        key_pw: F,
+        id: &UserId,
+        is_primary: bool,
+    ) -> Result<SignedUser>
+    where
+        F: PassphraseProvider,
+    {
+        let mut areas = SubpacketAreas::new();
+        if is_primary {
+            areas = areas.primary_user_id();
+        }
+        areas = areas
+            .created(chrono::Utc::now())
+            .key_flags(self.keyflags)
+            .preferred_symmetric_algorithms(self.preferred_symmetric_algorithms.clone())
+            .preferred_hash_algorithms(self.preferred_hash_algorithms.clone())
+            .preferred_compression_algorithms(self.preferred_compression_algorithms.clone())
+            .issuer_fingerprint(key);
+        if is_primary {
+            if let Some(ref rkey) = self.revocation_key {
+                areas = areas.revocation_key(rkey.clone());
+            }
+        }
+        if let Some(expiration) = self.key_expiration {
+            areas = areas.key_expiration(expiration);
+        }
+
+        let config = areas.issuer(key).into_config(
+            SignatureType::CertGeneric,
+            key.algorithm(),
+            Default::default(),
+        );
+
+        let sig = config.sign_certification(key, key_pw, id.tag(), id)?;
+
+        Ok(id.clone().into_signed(sig))
+    }
 }
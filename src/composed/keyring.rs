@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use crate::composed::signed_key::{is_self_certified, verify_subkey_signature, SignedPublicKey};
+use crate::errors::Result;
+use crate::packet::SignatureType;
+use crate::types::{Fingerprint, KeyId, KeyTrait};
+
+/// An in-memory collection of public keys.
+///
+/// Keys can be looked up by the Key ID or fingerprint of their primary key or any of
+/// their subkeys, and by the email addresses of their User IDs. Subkeys and User IDs are
+/// only indexed, if they are bound to the primary key by a valid self-signature.
+#[derive(Debug, Default, Clone)]
+pub struct Keyring {
+    keys: Vec<SignedPublicKey>,
+    by_primary: HashMap<Fingerprint, usize>,
+    by_fingerprint: HashMap<Fingerprint, usize>,
+    // Key IDs are short enough to collide, so they may refer to multiple keys.
+    by_key_id: HashMap<KeyId, Vec<usize>>,
+    by_email: HashMap<String, Vec<usize>>,
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds `key` to the keyring.
+    ///
+    /// If the keyring already contains the same key, both copies are merged.
+    pub fn insert(&mut self, key: SignedPublicKey) -> Result<()> {
+        let index = match self.by_primary.get(&key.fingerprint()) {
+            Some(&index) => {
+                let existing = self.keys[index].clone();
+                self.keys[index] = existing.merge(key)?;
+                index
+            }
+            None => {
+                self.keys.push(key);
+                self.keys.len() - 1
+            }
+        };
+        self.index(index);
+
+        Ok(())
+    }
+
+    /// Finds the key with the given primary key or subkey fingerprint.
+    pub fn get_by_fingerprint(&self, fingerprint: &Fingerprint) -> Option<&SignedPublicKey> {
+        self.by_fingerprint
+            .get(fingerprint)
+            .map(|&index| &self.keys[index])
+    }
+
+    /// Finds all keys with the given primary key or subkey Key ID.
+    pub fn get_by_key_id(&self, key_id: &KeyId) -> Vec<&SignedPublicKey> {
+        self.lookup(self.by_key_id.get(key_id))
+    }
+
+    /// Finds all keys with a User ID for the given email address, ignoring case.
+    pub fn get_by_email(&self, email: &str) -> Vec<&SignedPublicKey> {
+        self.lookup(self.by_email.get(&email.to_lowercase()))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SignedPublicKey> {
+        self.keys.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    fn lookup(&self, indices: Option<&Vec<usize>>) -> Vec<&SignedPublicKey> {
+        indices
+            .map(|indices| indices.iter().map(|&index| &self.keys[index]).collect())
+            .unwrap_or_default()
+    }
+
+    /// Adds the key at `index` to all indices.
+    ///
+    /// Merging only ever adds components to a key, so existing entries stay valid. A primary
+    /// key fingerprint always refers to its own key, even if another key claims it as subkey.
+    fn index(&mut self, index: usize) {
+        fn add(entries: &mut Vec<usize>, index: usize) {
+            if !entries.contains(&index) {
+                entries.push(index);
+            }
+        }
+
+        let key = &self.keys[index];
+        let primary = &key.primary_key;
+
+        self.by_primary.insert(key.fingerprint(), index);
+        self.by_fingerprint.insert(key.fingerprint(), index);
+        add(self.by_key_id.entry(key.key_id()).or_default(), index);
+
+        let subkeys = key.public_subkeys.iter().filter(|subkey| {
+            subkey.signatures.iter().any(|sig| {
+                sig.typ() == SignatureType::SubkeyBinding
+                    && verify_subkey_signature(sig, primary, &subkey.key).is_ok()
+            })
+        });
+        for subkey in subkeys {
+            let fingerprint = subkey.fingerprint();
+            if !self.by_primary.contains_key(&fingerprint) {
+                self.by_fingerprint.entry(fingerprint).or_insert(index);
+            }
+            add(self.by_key_id.entry(subkey.key_id()).or_default(), index);
+        }
+
+        for email in key
+            .details
+            .users
+            .iter()
+            .filter(|user| is_self_certified(user, primary))
+            .filter_map(|user| user.id.email_normalized())
+        {
+            add(self.by_email.entry(email).or_default(), index);
+        }
+    }
+}
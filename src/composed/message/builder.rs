@@ -0,0 +1,637 @@
+use bstr::BStr;
+use chrono::{DateTime, Utc};
+use rand::{CryptoRng, Rng};
+
+use crate::composed::message::types::{encrypt_session_keys, select_recipients};
+use crate::composed::message::Message;
+use crate::composed::signed_key::SignedPublicKey;
+use crate::crypto::hash::HashAlgorithm;
+use crate::crypto::sym::SymmetricKeyAlgorithm;
+use crate::errors::Result;
+use crate::packet::{DataMode, LiteralData, PublicSubkey, CONSOLE_FILE_NAME};
+use crate::policy::Policy;
+use crate::types::{
+    CompressionAlgorithm, Fingerprint, KeyTrait, PassphraseProvider, PublicKeyTrait, SecretKeyTrait,
+};
+
+/// Builds a message, applying signing, compression and encryption in the standard order.
+///
+/// The resulting structure is `encrypted(compressed(one-pass signature, literal, signature))`,
+/// independent of the order in which the steps are configured.
+///
+/// ```ignore
+/// let msg = MessageBuilder::new_literal("hello.txt", "hello world\n")
+///     .sign(&secret_key, || "password".into(), HashAlgorithm::SHA2_256)?
+///     .encrypt_to(&[&encryption_subkey])
+///     .compress(CompressionAlgorithm::ZLIB)
+///     .build(&mut rng)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct MessageBuilder<'a, P = PublicSubkey> {
+    message: Message,
+    compression: Option<CompressionAlgorithm>,
+    negotiate_compression: bool,
+    /// `None` uses the default level of the algorithm.
+    compression_level: Option<u32>,
+    recipients: Vec<&'a P>,
+    certs: Vec<&'a SignedPublicKey>,
+    sender: Option<&'a SignedPublicKey>,
+    /// `None` negotiates the algorithm with the certificate recipients.
+    symmetric_algorithm: Option<SymmetricKeyAlgorithm>,
+    anonymous_recipients: bool,
+    deduplicate_recipients: bool,
+    policy: Option<Policy>,
+    /// `None` uses the current time.
+    reference_time: Option<DateTime<Utc>>,
+}
+
+impl<'a> MessageBuilder<'a> {
+    pub fn new_literal(file_name: impl AsRef<BStr>, data: &str) -> Self {
+        Self::new(Message::new_literal(file_name, data))
+    }
+
+    pub fn new_literal_bytes(file_name: impl AsRef<BStr>, data: &[u8]) -> Self {
+        Self::new(Message::new_literal_bytes(file_name, data))
+    }
+
+    fn new(message: Message) -> Self {
+        MessageBuilder {
+            message,
+            compression: None,
+            negotiate_compression: false,
+            compression_level: None,
+            recipients: Vec::new(),
+            certs: Vec::new(),
+            sender: None,
+            symmetric_algorithm: None,
+            anonymous_recipients: false,
+            deduplicate_recipients: true,
+            policy: None,
+            reference_time: None,
+        }
+    }
+}
+
+impl<'a, P: PublicKeyTrait + Sync> MessageBuilder<'a, P> {
+    /// Signs the literal data with `key`, using a one-pass signature.
+    ///
+    /// Can be called multiple times, to sign the data with several keys.
+    pub fn sign<F>(
+        mut self,
+        key: &impl SecretKeyTrait,
+        key_pw: F,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<Self>
+    where
+        F: PassphraseProvider,
+    {
+        self.message = self.message.sign(key, key_pw, hash_algorithm)?;
+
+        Ok(self)
+    }
+
+    /// Sets the file name of the literal data, which may contain non utf-8 bytes.
+    ///
+    /// The metadata of the literal data is not covered by signatures.
+    pub fn file_name(mut self, file_name: impl AsRef<BStr>) -> Self {
+        self.update_literal(|literal| literal.with_file_name(file_name.as_ref()));
+        self
+    }
+
+    /// Marks the data as "for your eyes only", to be shown to the reader, but not saved to
+    /// disk. This replaces the file name with [`CONSOLE_FILE_NAME`].
+    pub fn for_your_eyes_only(self) -> Self {
+        self.file_name(CONSOLE_FILE_NAME)
+    }
+
+    /// Sets the date of the literal data, the modification date of the file, instead of the
+    /// time the message was created.
+    pub fn date(mut self, date: DateTime<Utc>) -> Self {
+        self.update_literal(|literal| literal.with_created(date));
+        self
+    }
+
+    /// Sets the format of the literal data, which defaults to UTF-8 text for messages created
+    /// from a string, and binary otherwise.
+    ///
+    /// Set it before signing, as the signature type follows it.
+    pub fn data_mode(mut self, mode: DataMode) -> Self {
+        self.update_literal(|literal| literal.with_mode(mode));
+        self
+    }
+
+    fn update_literal(&mut self, update: impl FnOnce(LiteralData) -> LiteralData) {
+        if let Some(literal) = self.message.get_literal_mut() {
+            let placeholder = LiteralData::from_bytes(BStr::new(""), &[]);
+            *literal = update(std::mem::replace(literal, placeholder));
+        }
+    }
+
+    /// Compresses the, possibly signed, data before encrypting it.
+    pub fn compress(mut self, alg: CompressionAlgorithm) -> Self {
+        self.compression = Some(alg);
+        self
+    }
+
+    /// Compresses the data with an algorithm preferred by all certificate recipients, see
+    /// [`CompressionAlgorithm::negotiate`], and leaves it uncompressed if there is none.
+    ///
+    /// Without certificate recipients, ZLIB is used. An algorithm set with
+    /// [`MessageBuilder::compress`] takes precedence.
+    pub fn compress_negotiated(mut self) -> Self {
+        self.negotiate_compression = true;
+        self
+    }
+
+    /// Sets the compression level, from 0 (none) to 9 (best) for ZIP, ZLIB and BZip2.
+    pub fn compression_level(mut self, level: u32) -> Self {
+        self.compression_level = Some(level);
+        self
+    }
+
+    /// Encrypts the message to `keys`, which need to be encryption capable.
+    pub fn encrypt_to<Q: PublicKeyTrait + Sync>(self, keys: &[&'a Q]) -> MessageBuilder<'a, Q> {
+        MessageBuilder {
+            message: self.message,
+            compression: self.compression,
+            negotiate_compression: self.negotiate_compression,
+            compression_level: self.compression_level,
+            recipients: keys.to_vec(),
+            certs: self.certs,
+            sender: self.sender,
+            symmetric_algorithm: self.symmetric_algorithm,
+            anonymous_recipients: self.anonymous_recipients,
+            deduplicate_recipients: self.deduplicate_recipients,
+            policy: self.policy,
+            reference_time: self.reference_time,
+        }
+    }
+
+    /// Encrypts the message to all valid encryption subkeys of `certs`.
+    ///
+    /// Unless set explicitly, the symmetric algorithm is negotiated from their preferences.
+    pub fn encrypt_to_certs(mut self, certs: &[&'a SignedPublicKey]) -> Self {
+        self.certs.extend_from_slice(certs);
+        self
+    }
+
+    /// Also encrypts the message to the valid encryption subkeys of `sender`, so that they
+    /// can read the message later on, like `gpg --encrypt-to`.
+    ///
+    /// This only applies if the message has other recipients.
+    pub fn encrypt_to_self(mut self, sender: &'a SignedPublicKey) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+
+    /// Sets the algorithm used to encrypt the message.
+    ///
+    /// By default, it is negotiated with the certificate recipients, and AES-128 otherwise.
+    pub fn symmetric_algorithm(mut self, alg: SymmetricKeyAlgorithm) -> Self {
+        self.symmetric_algorithm = Some(alg);
+        self
+    }
+
+    /// Hides the recipients, by using the wildcard Key ID in the encrypted session keys.
+    pub fn anonymous_recipients(mut self, anonymous: bool) -> Self {
+        self.anonymous_recipients = anonymous;
+        self
+    }
+
+    /// Encrypts the session key only once to each subkey, even if it was given multiple times,
+    /// e.g. as part of several certificates. Enabled by default.
+    pub fn deduplicate_recipients(mut self, deduplicate: bool) -> Self {
+        self.deduplicate_recipients = deduplicate;
+        self
+    }
+
+    /// Checks the symmetric algorithm and the keys of certificate recipients against `policy`
+    /// when building the message.
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Selects the valid encryption subkeys of certificate recipients at `at`, instead of the
+    /// current time.
+    pub fn reference_time(mut self, at: DateTime<Utc>) -> Self {
+        self.reference_time = Some(at);
+        self
+    }
+
+    pub fn build<R: CryptoRng + Rng>(self, rng: &mut R) -> Result<Message> {
+        let encrypt = !(self.recipients.is_empty() && self.certs.is_empty());
+
+        let mut certs = self.certs;
+        if encrypt {
+            certs.extend(self.sender);
+        }
+        let (negotiated, negotiated_compression, subkeys) = if certs.is_empty() {
+            (
+                SymmetricKeyAlgorithm::default(),
+                CompressionAlgorithm::negotiate(&[]),
+                Vec::new(),
+            )
+        } else {
+            select_recipients(&certs, self.reference_time.unwrap_or_else(Utc::now))?
+        };
+
+        let compression = match self.compression {
+            Some(alg) => Some(alg),
+            // leave the data as is, rather than wrapping it in an uncompressed packet
+            None if self.negotiate_compression
+                && negotiated_compression != CompressionAlgorithm::Uncompressed =>
+            {
+                Some(negotiated_compression)
+            }
+            None => None,
+        };
+        let mut message = self.message;
+        if let Some(alg) = compression {
+            message = match self.compression_level {
+                Some(level) => message.compress_with_level(alg, level)?,
+                None => message.compress(alg)?,
+            };
+        }
+        if !encrypt {
+            return Ok(message);
+        }
+
+        let alg = self.symmetric_algorithm.unwrap_or(negotiated);
+        if let Some(ref policy) = self.policy {
+            policy.check_symmetric_algorithm(alg)?;
+            for subkey in &subkeys {
+                policy.check_public_params(subkey.key.public_params())?;
+            }
+        }
+
+        let session_key = alg.new_session_key(rng);
+        let mut fingerprints = Vec::new();
+        let deduplicate = self.deduplicate_recipients;
+        let mut is_new = |fingerprint: Fingerprint| {
+            if !deduplicate {
+                return true;
+            }
+            if fingerprints.contains(&fingerprint) {
+                return false;
+            }
+            fingerprints.push(fingerprint);
+            true
+        };
+
+        let recipients: Vec<_> = self
+            .recipients
+            .into_iter()
+            .filter(|key| is_new(key.fingerprint()))
+            .collect();
+        let subkeys: Vec<_> = subkeys
+            .into_iter()
+            .filter(|key| is_new(key.fingerprint()))
+            .collect();
+
+        let anonymous = self.anonymous_recipients;
+        let mut esk = encrypt_session_keys(rng, &session_key, alg, &recipients, anonymous)?;
+        esk.extend(encrypt_session_keys(
+            rng,
+            &session_key,
+            alg,
+            &subkeys,
+            anonymous,
+        )?);
+
+        message.encrypt_symmetric(rng, esk, alg, session_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::fs;
+
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::composed::{Deserializable, SignedSecretKey};
+    use crate::packet::{OnePassSignature, Packet, PacketParser};
+    use crate::ser::Serialize;
+    use crate::test_util::{encryption_subkey, gen_key, gen_key_with_subkey, key_params};
+    use crate::types::Tag;
+
+    #[test]
+    fn test_sign_compress_encrypt() {
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        // subkey[0] is the encryption key
+        let pkey = skey.secret_subkeys[0].public_key();
+
+        let msg = MessageBuilder::new_literal("hello.txt", "hello world\n")
+            .encrypt_to(&[&pkey])
+            .compress(CompressionAlgorithm::ZLIB)
+            .sign(&skey, String::new, HashAlgorithm::SHA2_256)
+            .unwrap()
+            .build(&mut thread_rng())
+            .unwrap();
+        assert_eq!(msg.get_recipients(), vec![&pkey.key_id()]);
+
+        let decrypted = msg
+            .decrypt(String::new, &[&skey])
+            .unwrap()
+            .0
+            .next()
+            .unwrap()
+            .unwrap();
+        assert!(matches!(decrypted, Message::Compressed(_)));
+
+        let decompressed = decrypted.decompress().unwrap();
+        assert!(decompressed.is_one_pass_signed());
+        let tags: Vec<Tag> = PacketParser::new(&decompressed.to_bytes().unwrap()[..])
+            .map(|p| p.unwrap().tag())
+            .collect();
+        assert_eq!(
+            tags,
+            vec![Tag::OnePassSignature, Tag::LiteralData, Tag::Signature]
+        );
+        decompressed.verify(&skey.public_key()).unwrap();
+        // text literals are stored with canonical line endings
+        assert_eq!(
+            decompressed.get_content().unwrap().unwrap(),
+            b"hello world\r\n"
+        );
+    }
+
+    #[test]
+    fn test_multiple_signers() {
+        let alice = gen_key("Alice <alice@example.com>");
+        let bob = gen_key("Bob <bob@example.com>");
+        let carol = gen_key("Carol <carol@example.com>");
+
+        let msg = MessageBuilder::new_literal_bytes("data.bin", &[1, 2, 3])
+            .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+            .unwrap()
+            .sign(&bob, String::new, HashAlgorithm::SHA2_512)
+            .unwrap()
+            .build(&mut thread_rng())
+            .unwrap();
+
+        let packets: Vec<Packet> = PacketParser::new(&msg.to_bytes().unwrap()[..])
+            .map(|p| p.unwrap())
+            .collect();
+        let tags: Vec<Tag> = packets.iter().map(|p| p.tag()).collect();
+        assert_eq!(
+            tags,
+            vec![
+                Tag::OnePassSignature,
+                Tag::OnePassSignature,
+                Tag::LiteralData,
+                Tag::Signature,
+                Tag::Signature
+            ]
+        );
+        let ops: Vec<&OnePassSignature> = packets
+            .iter()
+            .filter_map(|p| match p {
+                Packet::OnePassSignature(ops) => Some(ops),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ops[0].key_id, bob.key_id());
+        assert_eq!(ops[0].last, 0);
+        assert_eq!(ops[1].key_id, alice.key_id());
+        assert_eq!(ops[1].last, 1);
+
+        let parsed = Message::from_bytes(&msg.to_bytes().unwrap()[..]).unwrap();
+        parsed.verify(&alice.public_key()).unwrap();
+        parsed.verify(&bob.public_key()).unwrap();
+        assert!(parsed.verify(&carol.public_key()).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_to_self() {
+        let alice = gen_key_with_subkey("Alice <alice@example.com>");
+        let bob = gen_key_with_subkey("Bob <bob@example.com>");
+        let alice_pub: SignedPublicKey = alice.clone().into();
+        let bob_pub: SignedPublicKey = bob.clone().into();
+
+        let msg = MessageBuilder::new_literal("hello.txt", "hello world\n")
+            .encrypt_to_certs(&[&bob_pub, &alice_pub, &bob_pub])
+            .encrypt_to_self(&alice_pub)
+            .build(&mut thread_rng())
+            .unwrap();
+        let mut recipients = msg.get_recipients();
+        recipients.sort_by_key(|id| id.to_vec());
+        let mut expected = [
+            alice.secret_subkeys[0].key_id(),
+            bob.secret_subkeys[0].key_id(),
+        ];
+        expected.sort_by_key(|id| id.to_vec());
+        assert_eq!(recipients, expected.iter().collect::<Vec<_>>());
+        for key in [&alice, &bob] {
+            msg.decrypt(String::new, &[key]).unwrap();
+        }
+
+        let msg = MessageBuilder::new_literal("hello.txt", "hello world\n")
+            .encrypt_to_certs(&[&bob_pub, &bob_pub])
+            .encrypt_to_self(&alice_pub)
+            .deduplicate_recipients(false)
+            .build(&mut thread_rng())
+            .unwrap();
+        assert_eq!(msg.get_recipients().len(), 3);
+
+        // the keys did not exist yet
+        let at = *alice.primary_key.created_at() - chrono::Duration::days(1);
+        assert!(MessageBuilder::new_literal("hello.txt", "hello world\n")
+            .encrypt_to_certs(&[&bob_pub])
+            .reference_time(at)
+            .build(&mut thread_rng())
+            .is_err());
+
+        // without other recipients, the message is not encrypted
+        let msg = MessageBuilder::new_literal("hello.txt", "hello world\n")
+            .encrypt_to_self(&alice_pub)
+            .build(&mut thread_rng())
+            .unwrap();
+        assert!(msg.is_literal());
+    }
+
+    #[test]
+    fn test_policy() {
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let pkey = skey.secret_subkeys[0].public_key();
+
+        let build = |alg| {
+            MessageBuilder::new_literal("hello.txt", "hello world\n")
+                .encrypt_to(&[&pkey])
+                .symmetric_algorithm(alg)
+                .policy(Policy::default())
+                .build(&mut thread_rng())
+        };
+        assert!(build(SymmetricKeyAlgorithm::CAST5).is_err());
+        let msg = build(SymmetricKeyAlgorithm::AES256).unwrap();
+
+        let (mut decrypter, _ids) = msg.decrypt(String::new, &[&skey]).unwrap();
+        decrypter.next().unwrap().unwrap();
+        let strict = crate::policy::PolicyBuilder::default()
+            .symmetric_algorithms(vec![SymmetricKeyAlgorithm::AES128])
+            .build()
+            .unwrap();
+        let (decrypter, _ids) = msg.decrypt(String::new, &[&skey]).unwrap();
+        assert!(decrypter.with_policy(strict).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_literal_metadata() {
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let pkey = skey.secret_subkeys[0].public_key();
+        let date = DateTime::parse_from_rfc3339("2020-01-01T12:00:00.5Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let msg = MessageBuilder::new_literal_bytes("", b"secret\n")
+            .data_mode(DataMode::Text)
+            .sign(&skey, String::new, HashAlgorithm::SHA2_256)
+            .unwrap()
+            .file_name("notes.txt")
+            .date(date)
+            .for_your_eyes_only()
+            .encrypt_to(&[&pkey])
+            .build(&mut thread_rng())
+            .unwrap();
+
+        let decrypted = msg
+            .decrypt(String::new, &[&skey])
+            .unwrap()
+            .0
+            .next()
+            .unwrap()
+            .unwrap();
+        decrypted.verify(&skey.public_key()).unwrap();
+        let literal = decrypted.get_literal().unwrap();
+        assert!(literal.is_for_your_eyes_only());
+        assert_eq!(literal.file_name(), CONSOLE_FILE_NAME);
+        assert_eq!(
+            literal.created(),
+            &DateTime::parse_from_rfc3339("2020-01-01T12:00:00Z").unwrap()
+        );
+        assert_eq!(literal.mode(), DataMode::Text);
+
+        let bytes = msg.to_bytes().unwrap();
+        let (reader, _) = crate::composed::StreamDecrypter::new(&bytes[..])
+            .unwrap()
+            .decrypt(String::new, &[&skey])
+            .unwrap();
+        assert!(reader.is_for_your_eyes_only());
+        assert_eq!(reader.created(), literal.created());
+        assert_eq!(reader.mode(), DataMode::Text);
+    }
+
+    #[test]
+    fn test_literal_only() {
+        let msg = MessageBuilder::new_literal_bytes("data.bin", &[1, 2, 3])
+            .build(&mut thread_rng())
+            .unwrap();
+        assert!(msg.is_literal());
+    }
+
+    #[test]
+    fn test_compression_negotiation() {
+        let gen_key = |id: &str, algs: &[CompressionAlgorithm]| -> SignedSecretKey {
+            key_params(id)
+                .preferred_compression_algorithms(algs.into())
+                .subkey(encryption_subkey())
+                .build()
+                .unwrap()
+                .generate_signed()
+                .unwrap()
+        };
+        let alice = gen_key("Alice <alice@example.com>", &[CompressionAlgorithm::ZIP]);
+        let bob = gen_key(
+            "Bob <bob@example.com>",
+            &[CompressionAlgorithm::ZLIB, CompressionAlgorithm::ZIP],
+        );
+        let carol = gen_key("Carol <carol@example.com>", &[]);
+        let alice_pub: SignedPublicKey = alice.clone().into();
+        let bob_pub: SignedPublicKey = bob.into();
+        let carol_pub: SignedPublicKey = carol.into();
+
+        let decrypted = |msg: Message| {
+            msg.decrypt(String::new, &[&alice])
+                .unwrap()
+                .0
+                .next()
+                .unwrap()
+                .unwrap()
+        };
+
+        let msg = MessageBuilder::new_literal("hello.txt", "hello world\n")
+            .encrypt_to_certs(&[&alice_pub, &bob_pub])
+            .compress_negotiated()
+            .build(&mut thread_rng())
+            .unwrap();
+        match decrypted(msg) {
+            Message::Compressed(data) => {
+                assert_eq!(data.compression_algorithm(), CompressionAlgorithm::ZIP)
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+
+        // no common algorithm
+        let msg = MessageBuilder::new_literal("hello.txt", "hello world\n")
+            .encrypt_to_certs(&[&alice_pub, &carol_pub])
+            .compress_negotiated()
+            .build(&mut thread_rng())
+            .unwrap();
+        assert!(decrypted(msg).is_literal());
+
+        // an explicit algorithm takes precedence
+        let msg = MessageBuilder::new_literal("hello.txt", "hello world\n")
+            .encrypt_to_certs(&[&alice_pub])
+            .compress_negotiated()
+            .compress(CompressionAlgorithm::ZLIB)
+            .build(&mut thread_rng())
+            .unwrap();
+        match decrypted(msg) {
+            Message::Compressed(data) => {
+                assert_eq!(data.compression_algorithm(), CompressionAlgorithm::ZLIB)
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_compression_level() {
+        let text = (0..2000)
+            .map(|i| format!("line {}\n", i % 97))
+            .collect::<String>();
+        let compressed_len = |level: u32| {
+            MessageBuilder::new_literal("hello.txt", &text)
+                .compress(CompressionAlgorithm::ZLIB)
+                .compression_level(level)
+                .build(&mut thread_rng())
+                .unwrap()
+                .to_bytes()
+                .unwrap()
+                .len()
+        };
+        assert!(compressed_len(0) > text.len());
+        assert!(compressed_len(9) < compressed_len(1));
+
+        let msg = MessageBuilder::new_literal("hello.txt", &text)
+            .compress_negotiated()
+            .compression_level(9)
+            .build(&mut thread_rng())
+            .unwrap();
+        assert_eq!(
+            msg.decompress().unwrap().get_content().unwrap().unwrap(),
+            text.replace('\n', "\r\n").as_bytes()
+        );
+    }
+}
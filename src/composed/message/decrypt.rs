@@ -1,82 +1,163 @@
 use std::boxed::Box;
-use std::io::Cursor;
+use std::fmt;
+use std::io::{self, Cursor, Read};
 
-use crate::composed::message::types::{Edata, Message};
-use crate::composed::shared::Deserializable;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::composed::message::stream::{DecryptedReader, ProtectedDataReader};
+use crate::composed::message::types::{Edata, Esk, Message};
+use crate::composed::signed_key::SignedSecretKey;
+use crate::crypto::public_key::PublicKeyAlgorithm;
 use crate::crypto::sym::SymmetricKeyAlgorithm;
 use crate::crypto::{checksum, ecdh, rsa};
-use crate::errors::Result;
-use crate::packet::SymKeyEncryptedSessionKey;
-use crate::types::{KeyTrait, Mpi, SecretKeyRepr, SecretKeyTrait, Tag};
+use crate::errors::{Error, Result};
+use crate::limits::ParseLimits;
+use crate::packet::{PublicKeyEncryptedSessionKey, SymKeyEncryptedSessionKey};
+use crate::policy::Policy;
+#[cfg(feature = "async")]
+use crate::types::AsyncSecretKeyTrait;
+use crate::types::{
+    Decryptor, KeyId, KeyTrait, Mpi, PassphraseProvider, SecretBytes, SecretKeyRepr,
+    SecretKeyTrait, Tag,
+};
 
 /// Decrypts session key using secret key.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(key_id = %locked_key.key_id(), algorithm = ?locked_key.algorithm())
+    )
+)]
 pub fn decrypt_session_key<F>(
     locked_key: &(impl SecretKeyTrait + KeyTrait),
     key_pw: F,
     mpis: &[Mpi],
-) -> Result<(Vec<u8>, SymmetricKeyAlgorithm)>
+) -> Result<SessionKey>
 where
-    F: FnOnce() -> String,
+    F: PassphraseProvider,
 {
     debug!("decrypting session key");
 
-    let mut key: Vec<u8> = Vec::new();
-    let mut alg: Option<SymmetricKeyAlgorithm> = None;
+    let mut session_key = None;
     locked_key.unlock(key_pw, |priv_key| {
-        let decrypted_key = match *priv_key {
-            SecretKeyRepr::RSA(ref priv_key) => {
-                rsa::decrypt(priv_key, mpis, &locked_key.fingerprint())?
-            }
-            SecretKeyRepr::DSA(_) => bail!("DSA is only used for signing"),
-            SecretKeyRepr::ECDSA(_) => bail!("ECDSA is only used for signing"),
-            SecretKeyRepr::ECDH(ref priv_key) => {
-                ecdh::decrypt(priv_key, mpis, &locked_key.fingerprint())?
-            }
-            SecretKeyRepr::EdDSA(_) => unimplemented_err!("EdDSA"),
-        };
+        session_key = Some(decrypt_session_key_with_repr(
+            priv_key,
+            locked_key.fingerprint().as_bytes(),
+            mpis,
+        )?);
+        Ok(())
+    })?;
 
-        let session_key_algorithm = SymmetricKeyAlgorithm::from(decrypted_key[0]);
-        ensure!(
-            session_key_algorithm != SymmetricKeyAlgorithm::Plaintext,
-            "session key algorithm cannot be plaintext"
-        );
-        alg = Some(session_key_algorithm);
-        debug!("alg: {:?}", alg);
-
-        let (k, checksum) = match *priv_key {
-            SecretKeyRepr::ECDH(_) => {
-                let dec_len = decrypted_key.len();
-                (
-                    &decrypted_key[1..dec_len - 2],
-                    &decrypted_key[dec_len - 2..],
-                )
-            }
-            _ => {
-                let key_size = session_key_algorithm.key_size();
-                (
-                    &decrypted_key[1..=key_size],
-                    &decrypted_key[key_size + 1..key_size + 3],
-                )
-            }
-        };
+    Ok(session_key.expect("failed to unlock"))
+}
 
-        key = k.to_vec();
-        checksum::simple(checksum, k)?;
+/// Async variant of [`decrypt_session_key`], using an [`AsyncSecretKeyTrait`].
+#[cfg(feature = "async")]
+pub async fn decrypt_session_key_async<F>(
+    locked_key: &impl AsyncSecretKeyTrait,
+    key_pw: F,
+    mpis: &[Mpi],
+) -> Result<SessionKey>
+where
+    F: PassphraseProvider + Send,
+{
+    debug!("decrypting session key");
 
-        Ok(())
-    })?;
+    let fingerprint = locked_key.fingerprint();
+    let mut session_key = None;
+    locked_key
+        .unlock(key_pw, |priv_key| {
+            session_key = Some(decrypt_session_key_with_repr(
+                priv_key,
+                fingerprint.as_bytes(),
+                mpis,
+            )?);
+            Ok(())
+        })
+        .await?;
+
+    Ok(session_key.expect("failed to unlock"))
+}
+
+/// Decrypts the session key in `mpis` with the unlocked `priv_key`.
+fn decrypt_session_key_with_repr(
+    priv_key: &SecretKeyRepr,
+    fingerprint: &[u8],
+    mpis: &[Mpi],
+) -> Result<SessionKey> {
+    let decrypted_key = match *priv_key {
+        SecretKeyRepr::RSA(ref priv_key) => rsa::decrypt(priv_key, mpis, fingerprint)?,
+        SecretKeyRepr::DSA(_) => bail!("DSA is only used for signing"),
+        SecretKeyRepr::ECDSA(_) => bail!("ECDSA is only used for signing"),
+        SecretKeyRepr::ECDH(ref priv_key) => ecdh::decrypt(priv_key, mpis, fingerprint)?,
+        SecretKeyRepr::EdDSA(_) => unimplemented_err!("EdDSA"),
+    };
 
-    Ok((key, alg.expect("failed to unlock")))
+    parse_session_key(&decrypted_key, matches!(*priv_key, SecretKeyRepr::ECDH(_)))
+}
+
+/// Decrypts the session key in `mpis` with a [`Decryptor`], e.g. a key held by an agent.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(algorithm = ?key.algorithm()))
+)]
+pub fn decrypt_session_key_with_decryptor(
+    key: &impl Decryptor,
+    mpis: &[Mpi],
+) -> Result<SessionKey> {
+    debug!("decrypting session key");
+
+    let decrypted_key = key.decrypt(mpis)?;
+
+    parse_session_key(&decrypted_key, key.algorithm() == PublicKeyAlgorithm::ECDH)
+}
+
+/// Splits a decrypted session key into its algorithm and the key, and verifies its checksum.
+fn parse_session_key(decrypted_key: &[u8], ecdh: bool) -> Result<SessionKey> {
+    ensure!(!decrypted_key.is_empty(), "invalid session key");
+    let session_key_algorithm = SymmetricKeyAlgorithm::from(decrypted_key[0]);
+    ensure!(
+        session_key_algorithm != SymmetricKeyAlgorithm::Plaintext,
+        "session key algorithm cannot be plaintext"
+    );
+    debug!("alg: {:?}", session_key_algorithm);
+
+    let (k, checksum) = if ecdh {
+        let dec_len = decrypted_key.len();
+        ensure!(dec_len >= 3, "invalid session key");
+        (
+            &decrypted_key[1..dec_len - 2],
+            &decrypted_key[dec_len - 2..],
+        )
+    } else {
+        let key_size = session_key_algorithm.key_size();
+        // decrypting with the wrong key, e.g. for anonymous recipients, yields garbage
+        ensure!(decrypted_key.len() >= key_size + 3, "invalid session key");
+        (
+            &decrypted_key[1..=key_size],
+            &decrypted_key[key_size + 1..key_size + 3],
+        )
+    };
+
+    checksum::simple(checksum, k)?;
+
+    Ok(SessionKey::new(k.to_vec(), session_key_algorithm))
 }
 
 /// Decrypts session key from SKESK packet.
 ///
 /// Returns decrypted or derived session key
 /// and symmetric algorithm of the key.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(algorithm = ?packet.sym_algorithm()))
+)]
 pub fn decrypt_session_key_with_password<F>(
     packet: &SymKeyEncryptedSessionKey,
     msg_pw: F,
-) -> Result<(Vec<u8>, SymmetricKeyAlgorithm)>
+) -> Result<SessionKey>
 where
     F: FnOnce() -> String,
 {
@@ -96,10 +177,10 @@ where
         // There is no encrypted session key.
         //
         // S2K-derived key is the session key.
-        return Ok((key, packet_algorithm));
+        return Ok(SessionKey::new(key.to_vec(), packet_algorithm));
     };
 
-    let mut decrypted_key = encrypted_key.to_vec();
+    let mut decrypted_key = Zeroizing::new(encrypted_key.to_vec());
     // packet.sym_algorithm().decrypt(&key, &mut decrypted_key)?;
     let iv = vec![0u8; packet.sym_algorithm().block_size()];
     packet_algorithm.decrypt_with_iv_regular(&key, &iv, &mut decrypted_key)?;
@@ -110,28 +191,452 @@ where
         "session key algorithm cannot be plaintext"
     );
 
-    Ok((decrypted_key[1..].to_vec(), session_key_algorithm))
+    Ok(SessionKey::new(
+        decrypted_key[1..].to_vec(),
+        session_key_algorithm,
+    ))
 }
 
-pub struct MessageDecrypter<'a> {
-    key: Vec<u8>,
+/// Decrypts the session key from the public key encrypted session key packets in `esk`,
+/// using the first matching key of each of `keys`.
+///
+/// Returns the session key, and the [KeyId]s of the keys that were able to decrypt it.
+/// Packets for anonymous recipients are tried with every key of a matching algorithm.
+///
+/// The passphrase for each key is requested separately, a key that fails does not stop the
+/// others from being tried, unless the passphrase provider cancels.
+pub(crate) fn decrypt_session_key_for_keys<G>(
+    esk: &[Esk],
+    key_pw: G,
+    keys: &[&SignedSecretKey],
+) -> Result<(SessionKey, Vec<KeyId>)>
+where
+    G: PassphraseProvider + Clone,
+{
+    let pkesks: Vec<_> = esk
+        .iter()
+        .filter_map(|k| match k {
+            Esk::PublicKeyEncryptedSessionKey(k) => Some(k),
+            _ => None,
+        })
+        .collect();
+
+    let mut valid_keys = Vec::new();
+    for key in keys {
+        // search for a packet with a key id that we have and that key.
+        let mut packet = None;
+        let mut encoding_key = None;
+        let mut encoding_subkey = None;
+
+        for &esk_packet in &pkesks {
+            debug!("esk packet: {:?}", esk_packet);
+            debug!("{:?}", key.key_id());
+            debug!(
+                "{:?}",
+                key.secret_subkeys
+                    .iter()
+                    .map(KeyTrait::key_id)
+                    .collect::<Vec<_>>()
+            );
+
+            // find the key with the matching key id
+
+            if &key.primary_key.key_id() == esk_packet.id() {
+                encoding_key = Some(&key.primary_key);
+            }
+
+            if encoding_key.is_none() {
+                encoding_subkey = key
+                    .secret_subkeys
+                    .iter()
+                    .find(|&subkey| &subkey.key_id() == esk_packet.id());
+            }
+
+            if encoding_key.is_some() || encoding_subkey.is_some() {
+                packet = Some(esk_packet);
+                break;
+            }
+        }
+
+        if let Some(packet) = packet {
+            valid_keys.push((*key, packet, encoding_key, encoding_subkey));
+            continue;
+        }
+
+        // anonymous packets could be for any of our keys of the right algorithm
+        for &esk_packet in pkesks.iter().filter(|p| p.is_anonymous()) {
+            if key.primary_key.algorithm() == esk_packet.algorithm() {
+                valid_keys.push((*key, esk_packet, Some(&key.primary_key), None));
+            }
+            for subkey in &key.secret_subkeys {
+                if subkey.algorithm() == esk_packet.algorithm() {
+                    valid_keys.push((*key, esk_packet, None, Some(subkey)));
+                }
+            }
+        }
+    }
+
+    if valid_keys.is_empty() {
+        return Err(Error::MissingKey);
+    }
+
+    let mut results = Vec::with_capacity(valid_keys.len());
+    for (key, packet, encoding_key, encoding_subkey) in valid_keys {
+        // the passphrase request names the User ID, also for subkeys
+        let key_pw = key.with_user_id(key_pw.clone());
+        let result = if let Some(ek) = encoding_key {
+            (ek.key_id(), decrypt_session_key(ek, key_pw, packet.mpis()))
+        } else if let Some(ek) = encoding_subkey {
+            (ek.key_id(), decrypt_session_key(ek, key_pw, packet.mpis()))
+        } else {
+            unreachable!("either a key or a subkey were found");
+        };
+
+        if let (_, Err(Error::Cancelled)) = result {
+            return Err(Error::Cancelled);
+        }
+        results.push(result);
+    }
+
+    select_session_key(results)
+}
+
+/// Async variant of [`decrypt_session_key_for_keys`], trying each of `keys` on the packets
+/// addressed to its Key ID, or on anonymous packets of the same algorithm.
+#[cfg(feature = "async")]
+pub(crate) async fn decrypt_session_key_for_async_keys<G, K>(
+    esk: &[Esk],
+    key_pw: G,
+    keys: &[&K],
+) -> Result<(SessionKey, Vec<KeyId>)>
+where
+    G: PassphraseProvider + Clone + Send,
+    K: AsyncSecretKeyTrait,
+{
+    let mut results = Vec::new();
+    for (packet, key) in match_packets(esk, keys)? {
+        let result = decrypt_session_key_async(key, key_pw.clone(), packet.mpis()).await;
+        if let Err(Error::Cancelled) = result {
+            return Err(Error::Cancelled);
+        }
+        results.push((key.key_id(), result));
+    }
+
+    select_session_key(results)
+}
+
+/// Variant of [`decrypt_session_key_for_keys`] for [`Decryptor`]s, trying each of `keys` on
+/// the packets addressed to its Key ID, or on anonymous packets of the same algorithm.
+pub(crate) fn decrypt_session_key_for_decryptors<D: Decryptor>(
+    esk: &[Esk],
+    keys: &[&D],
+) -> Result<(SessionKey, Vec<KeyId>)> {
+    let results = match_packets(esk, keys)?
+        .into_iter()
+        .map(|(packet, key)| {
+            let result = decrypt_session_key_with_decryptor(key, packet.mpis());
+            (key.key_id(), result)
+        })
+        .collect();
+
+    select_session_key(results)
+}
+
+/// Pairs each of the single (sub)keys in `keys` with the packet in `esk` addressed to its
+/// Key ID, or with all anonymous packets of the same algorithm.
+fn match_packets<'a, 'k, K: KeyTrait>(
+    esk: &'a [Esk],
+    keys: &[&'k K],
+) -> Result<Vec<(&'a PublicKeyEncryptedSessionKey, &'k K)>> {
+    let pkesks: Vec<_> = esk
+        .iter()
+        .filter_map(|k| match k {
+            Esk::PublicKeyEncryptedSessionKey(k) => Some(k),
+            _ => None,
+        })
+        .collect();
+
+    let mut valid_keys = Vec::new();
+    for &key in keys {
+        match pkesks.iter().find(|p| p.id() == &key.key_id()) {
+            Some(packet) => valid_keys.push((*packet, key)),
+            None => valid_keys.extend(
+                pkesks
+                    .iter()
+                    .filter(|p| p.is_anonymous() && p.algorithm() == key.algorithm())
+                    .map(|packet| (*packet, key)),
+            ),
+        }
+    }
+
+    if valid_keys.is_empty() {
+        return Err(Error::MissingKey);
+    }
+
+    Ok(valid_keys)
+}
+
+/// Returns the session key decrypted by all successful keys in `results`, and the [KeyId]s of
+/// the keys that decrypted it.
+///
+/// If no key succeeded, the error has the failure of each key.
+fn select_session_key(
+    results: Vec<(KeyId, Result<SessionKey>)>,
+) -> Result<(SessionKey, Vec<KeyId>)> {
+    let mut session_keys = Vec::with_capacity(results.len());
+    let mut failures = Vec::new();
+    for (id, result) in results {
+        match result {
+            Ok(session_key) => session_keys.push((id, session_key)),
+            Err(err) => {
+                warn!("failed to decrypt session_key for key {:?}: {:?}", id, err);
+                failures.push((id, err));
+            }
+        }
+    }
+
+    if session_keys.is_empty() {
+        return Err(Error::SessionKeyDecryption { failures });
+    }
+
+    // make sure all the keys are the same, otherwise we are in a bad place
+    let (_key_id, k0) = &session_keys[0];
+    if !session_keys.iter().skip(1).all(|(_, k)| k0 == k) {
+        bail!("found inconsistent session keys, possible message corruption");
+    }
+    ensure!(
+        k0.alg != SymmetricKeyAlgorithm::Plaintext,
+        "session key algorithm cannot be plaintext"
+    );
+
+    let mut ids = Vec::with_capacity(session_keys.len());
+    let mut session_key = None;
+    for (id, key) in session_keys {
+        ids.push(id);
+        session_key.get_or_insert(key);
+    }
+
+    Ok((session_key.expect("not empty"), ids))
+}
+
+/// Decrypts the session key from the first symmetric key encrypted session key packet in `esk`.
+pub(crate) fn decrypt_session_key_for_password<F>(esk: &[Esk], msg_pw: F) -> Result<SessionKey>
+where
+    F: FnOnce() -> String,
+{
+    // TODO: handle multiple passwords
+    let skesk = esk.iter().find_map(|esk| match esk {
+        Esk::SymKeyEncryptedSessionKey(k) => Some(k),
+        _ => None,
+    });
+
+    ensure!(skesk.is_some(), "message is not password protected");
+
+    let session_key = decrypt_session_key_with_password(skesk.expect("checked above"), msg_pw)?;
+    ensure!(
+        session_key.alg != SymmetricKeyAlgorithm::Plaintext,
+        "session key algorithm cannot be plaintext"
+    );
+
+    Ok(session_key)
+}
+
+/// A decrypted session key, together with its algorithm. It is zeroed on drop.
+#[derive(Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
+pub struct SessionKey {
+    key: SecretBytes,
     alg: SymmetricKeyAlgorithm,
+}
+
+impl SessionKey {
+    pub fn new(key: Vec<u8>, alg: SymmetricKeyAlgorithm) -> Self {
+        SessionKey {
+            key: key.into(),
+            alg,
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn algorithm(&self) -> SymmetricKeyAlgorithm {
+        self.alg
+    }
+}
+
+impl fmt::Debug for SessionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SessionKey({:?})", self.alg)
+    }
+}
+
+/// Decrypts the encrypted data packets of a message.
+///
+/// Iterating yields the decrypted messages, while reading yields the content of their literal
+/// data, one after the other. Reading and iterating should not be mixed, as both consume the
+/// decrypted messages.
+///
+/// When reading, integrity protected data is decrypted while it is read, so that memory use
+/// does not depend on the size of the plaintext. Its integrity is checked once all of the data
+/// of a packet was read, so the plaintext must not be trusted before reading returned the end of
+/// the data without an error.
+pub struct MessageDecrypter<'a> {
+    session_key: SessionKey,
     edata: &'a [Edata],
     // position in the edata slice
     pos: usize,
     // the current msgs that are already decrypted
     current_msgs: Option<Box<dyn Iterator<Item = Result<Message>>>>,
+    // the literal data of the current packet, that was not read yet
+    content: Option<Content<'a>>,
+    limits: ParseLimits,
+    // if not set, only the integrity protection is checked, against the default policy
+    policy: Option<Policy>,
+}
+
+/// The literal data of a decrypted packet, while it is read.
+struct Content<'a> {
+    reader: ContentReader<'a>,
+    compressed: bool,
+    // the amount of data that was read
+    len: usize,
+}
+
+enum ContentReader<'a> {
+    /// Integrity protected data, that is decrypted while it is read.
+    Streamed(Box<DecryptedReader<&'a [u8]>>),
+    /// Data without integrity protection, that is decrypted at once.
+    Buffered(Cursor<Vec<u8>>),
+}
+
+impl<'a> Read for ContentReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            ContentReader::Streamed(r) => r.read(buf),
+            ContentReader::Buffered(r) => r.read(buf),
+        }
+    }
 }
 
 impl<'a> MessageDecrypter<'a> {
     pub fn new(session_key: Vec<u8>, alg: SymmetricKeyAlgorithm, edata: &'a [Edata]) -> Self {
+        Self::from_session_key(SessionKey::new(session_key, alg), edata)
+    }
+
+    pub(crate) fn from_session_key(session_key: SessionKey, edata: &'a [Edata]) -> Self {
         MessageDecrypter {
-            key: session_key,
-            alg,
+            session_key,
             edata,
             pos: 0,
             current_msgs: None,
+            content: None,
+            limits: ParseLimits::default(),
+            policy: None,
+        }
+    }
+
+    /// Rejects decrypted messages, and their decompressed data, that exceed `limits`.
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Decides which symmetric algorithms are decrypted, and if encrypted data packets without
+    /// integrity protection are.
+    ///
+    /// Without a policy, all supported symmetric algorithms are decrypted, so that old messages
+    /// can still be read, but data without integrity protection is rejected, with
+    /// [`IntegrityError::MdcMissing`]. Data with a Modification Detection Code that does not
+    /// match is always rejected.
+    ///
+    /// [`IntegrityError::MdcMissing`]: crate::errors::IntegrityError::MdcMissing
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// The session key the message is decrypted with, e.g. to reply using the same session
+    /// key, like `gpg --show-session-key`.
+    pub fn session_key(&self) -> &SessionKey {
+        &self.session_key
+    }
+
+    /// Checks the symmetric algorithm and `packet` against the policy.
+    fn check_policy(&self, packet: &Edata) -> Result<()> {
+        match self.policy {
+            Some(ref policy) => {
+                policy.check_symmetric_algorithm(self.session_key.alg)?;
+                policy.check_edata(packet)
+            }
+            None => Policy::default().check_edata(packet),
+        }
+    }
+
+    /// Decrypts all of the data of `packet`.
+    fn decrypt_packet(&self, packet: &Edata) -> Result<Vec<u8>> {
+        self.check_policy(packet)?;
+
+        let protected = packet.tag() == Tag::SymEncryptedProtectedData;
+        debug!("decrypting protected = {:?}", protected);
+
+        if protected {
+            // decrypted while it is read, so that the ciphertext is not copied
+            let mut decrypted = Vec::new();
+            ProtectedDataReader::from_body(packet.data(), &self.session_key)?
+                .read_to_end(&mut decrypted)
+                .map_err(into_error)?;
+            Ok(decrypted)
+        } else {
+            let SessionKey { ref key, alg } = self.session_key;
+            let mut decrypted = packet.data().to_vec();
+            let prefix_len = decrypted.len() - alg.decrypt(key, &mut decrypted)?.len();
+            decrypted.drain(..prefix_len);
+            Ok(decrypted)
+        }
+    }
+
+    /// Starts reading the literal data of `packet`.
+    fn open_content(&self, packet: &'a Edata) -> Result<Content<'a>> {
+        if packet.tag() == Tag::SymEncryptedProtectedData {
+            self.check_policy(packet)?;
+            let decrypted = ProtectedDataReader::from_body(packet.data(), &self.session_key)?;
+            let reader = DecryptedReader::new(decrypted)?;
+
+            return Ok(Content {
+                compressed: reader.is_compressed(),
+                reader: ContentReader::Streamed(Box::new(reader)),
+                len: 0,
+            });
         }
+
+        let decrypted = self.decrypt_packet(packet)?;
+        let msg = Message::from_bytes_many_with_limits(Cursor::new(decrypted), self.limits.clone())
+            .next()
+            .ok_or(Error::NoMatchingPacket)??;
+        let compressed = matches!(msg, Message::Compressed(_));
+        let content = msg
+            .decompress_with_limits(&self.limits)?
+            .get_content()?
+            .ok_or_else(|| format_err!("nested encrypted messages are not supported"))?;
+
+        Ok(Content {
+            reader: ContentReader::Buffered(Cursor::new(content)),
+            compressed,
+            len: 0,
+        })
+    }
+}
+
+/// Returns the error of a reader, like [`Error::IntegrityCheckFailed`], that was wrapped into
+/// an `io::Error`.
+fn into_error(err: io::Error) -> Error {
+    if !err.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+        return err.into();
+    }
+    match err.into_inner().map(|inner| inner.downcast::<Error>()) {
+        Some(Ok(inner)) => *inner,
+        _ => unreachable!("checked above"),
     }
 }
 
@@ -148,20 +653,11 @@ impl<'a> Iterator for MessageDecrypter<'a> {
             let packet = &self.edata[self.pos];
             self.pos += 1;
 
-            let mut res = packet.data()[..].to_vec();
-            let protected = packet.tag() == Tag::SymEncryptedProtectedData;
-
-            debug!("decrypting protected = {:?}", protected);
-
-            let decrypted_packet: &[u8] = if protected {
-                err_opt!(self.alg.decrypt_protected(&self.key, &mut res))
-            } else {
-                err_opt!(self.alg.decrypt(&self.key, &mut res))
-            };
-
-            self.current_msgs = Some(Message::from_bytes_many(Cursor::new(
-                decrypted_packet.to_vec(),
-            )));
+            let decrypted = err_opt!(self.decrypt_packet(packet));
+            self.current_msgs = Some(Message::from_bytes_many_with_limits(
+                Cursor::new(decrypted),
+                self.limits.clone(),
+            ));
         };
 
         let mut msgs = self.current_msgs.take().expect("just checked");
@@ -171,3 +667,37 @@ impl<'a> Iterator for MessageDecrypter<'a> {
         next
     }
 }
+
+impl<'a> Read for MessageDecrypter<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if let Some(ref mut content) = self.content {
+                let read = content.reader.read(buf)?;
+                content.len += read;
+                let checked = if content.compressed {
+                    self.limits.check_decompressed_size(content.len)
+                } else {
+                    self.limits.check_packet_size(content.len)
+                };
+                checked.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                if read > 0 {
+                    return Ok(read);
+                }
+            }
+
+            let Some(packet) = self.edata.get(self.pos) else {
+                self.content = None;
+                return Ok(0);
+            };
+            self.pos += 1;
+            let content = self
+                .open_content(packet)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            self.content = Some(content);
+        }
+    }
+}
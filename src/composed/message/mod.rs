@@ -1,6 +1,12 @@
+mod builder;
 mod decrypt;
 mod parser;
+mod signer;
+mod stream;
 mod types;
 
+pub use self::builder::*;
 pub use self::decrypt::*;
+pub use self::signer::*;
+pub use self::stream::*;
 pub use self::types::*;
@@ -5,14 +5,56 @@ use std::iter::Peekable;
 use crate::composed::message::Message;
 use crate::composed::Deserializable;
 use crate::errors::Result;
-use crate::packet::Packet;
+use crate::limits::ParseLimits;
+use crate::packet::{Packet, PacketParser};
 use crate::types::Tag;
 
 pub struct MessageParser<I: Sized + Iterator<Item = Result<Packet>>> {
     source: Peekable<I>,
+    limits: ParseLimits,
 }
 
-fn next<I: Iterator<Item = Result<Packet>>>(packets: &mut Peekable<I>) -> Option<Result<Message>> {
+/// Parses the next message, which is nested `depth` levels deep in signed messages,
+/// together with the experimental packets that follow it.
+fn next<I: Iterator<Item = Result<Packet>>>(
+    packets: &mut Peekable<I>,
+    depth: usize,
+    limits: &ParseLimits,
+) -> Option<Result<Message>> {
+    let message = match next_message(packets, depth, limits)? {
+        Ok(message) => message,
+        Err(err) => return Some(Err(err)),
+    };
+
+    let mut experimental = Vec::new();
+    while let Some(res) =
+        packets.next_if(|res| res.as_ref().is_ok_and(|p| p.tag().is_experimental()))
+    {
+        match res {
+            Ok(packet) => experimental.push(packet.try_into().expect("peeked")),
+            Err(e) => return Some(Err(e)),
+        }
+    }
+
+    if experimental.is_empty() {
+        Some(Ok(message))
+    } else {
+        Some(Ok(Message::Experimental {
+            message: Box::new(message),
+            packets: experimental,
+        }))
+    }
+}
+
+fn next_message<I: Iterator<Item = Result<Packet>>>(
+    packets: &mut Peekable<I>,
+    depth: usize,
+    limits: &ParseLimits,
+) -> Option<Result<Message>> {
+    if let Err(err) = limits.check_nesting_depth(depth) {
+        return Some(Err(err));
+    }
+
     while let Some(res) = packets.by_ref().next() {
         let packet = match res {
             Ok(packet) => packet,
@@ -102,7 +144,7 @@ fn next<I: Iterator<Item = Result<Packet>>>(packets: &mut Peekable<I>) -> Option
             Tag::Signature => {
                 return match packet.try_into() {
                     Ok(signature) => {
-                        let message = match next(packets.by_ref()) {
+                        let message = match next(packets.by_ref(), depth + 1, limits) {
                             Some(Ok(m)) => Some(Box::new(m)),
                             Some(Err(err)) => return Some(Err(err)),
                             None => None,
@@ -122,7 +164,7 @@ fn next<I: Iterator<Item = Result<Packet>>>(packets: &mut Peekable<I>) -> Option
                     Ok(p) => {
                         let one_pass_signature = Some(p);
 
-                        let message = match next(packets.by_ref()) {
+                        let message = match next(packets.by_ref(), depth + 1, limits) {
                             Some(Ok(m)) => Some(Box::new(m)),
                             Some(Err(err)) => return Some(Err(err)),
                             None => None,
@@ -150,9 +192,16 @@ fn next<I: Iterator<Item = Result<Packet>>>(packets: &mut Peekable<I>) -> Option
                     Err(err) => Some(Err(err)),
                 };
             }
-            Tag::Marker => {
-                // Marker Packets are ignored
+            Tag::Marker
+            | Tag::Trust
+            | Tag::Private60
+            | Tag::Private61
+            | Tag::Private62
+            | Tag::Private63 => {
+                // Marker and Trust packets are ignored, as are experimental packets that
+                // do not follow a message
                 // see https://tools.ietf.org/html/rfc4880#section-5.8
+                warn!("skipping {:?} packet", tag);
             }
             _ => {
                 return Some(Err(format_err!("unexpected packet {:?}", packet.tag())));
@@ -167,7 +216,30 @@ impl<I: Sized + Iterator<Item = Result<Packet>>> Iterator for MessageParser<I> {
     type Item = Result<Message>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        next(self.source.by_ref())
+        next(self.source.by_ref(), 0, &self.limits)
+    }
+}
+
+impl Message {
+    /// Parses a list of messages in raw byte format, rejecting input that exceeds `limits`.
+    pub fn from_bytes_many_with_limits<'a>(
+        bytes: impl std::io::Read + 'a,
+        limits: ParseLimits,
+    ) -> Box<dyn Iterator<Item = Result<Self>> + 'a> {
+        let packets = PacketParser::with_limits(bytes, limits.clone())
+            .filter_map(crate::composed::shared::filter_parsed_packet_results);
+
+        Box::new(MessageParser {
+            source: packets.peekable(),
+            limits,
+        })
+    }
+
+    /// Parses a single message in raw byte format, rejecting input that exceeds `limits`.
+    pub fn from_bytes_with_limits(bytes: impl std::io::Read, limits: ParseLimits) -> Result<Self> {
+        Self::from_bytes_many_with_limits(bytes, limits)
+            .next()
+            .ok_or(crate::errors::Error::NoMatchingPacket)?
     }
 }
 
@@ -179,6 +251,7 @@ impl Deserializable for Message {
     ) -> Box<dyn Iterator<Item = Result<Self>> + 'a> {
         Box::new(MessageParser {
             source: packets.peekable(),
+            limits: ParseLimits::default(),
         })
     }
 }
@@ -0,0 +1,293 @@
+use std::io;
+
+use bstr::BStr;
+use byteorder::{BigEndian, WriteBytesExt};
+
+use crate::crypto::hash::{HashAlgorithm, Hasher};
+use crate::errors::Result;
+use crate::packet::{
+    canonicalize_text, write_packet, DataMode, OnePassSignature, Signature, SignatureConfig,
+    SignatureType, SubpacketAreas,
+};
+use crate::types::{PassphraseProvider, SecretKeyTrait, Tag};
+use crate::util::write_packet_length;
+
+/// The size of the partial body chunks written by [`SignerWriter`], as a power of 2.
+const PARTIAL_CHUNK_POWER: u8 = 13;
+const PARTIAL_CHUNK_SIZE: usize = 1 << PARTIAL_CHUNK_POWER;
+
+/// Hashes data for a signature incrementally, as it is written, so that input of any size can
+/// be signed, e.g. for detached signatures.
+///
+/// ```rust
+/// # fn main() -> pgp::errors::Result<()> {
+/// use std::io::Write;
+///
+/// use pgp::crypto::hash::HashAlgorithm;
+/// use pgp::packet::SignatureType;
+/// use pgp::types::SecretKeyTrait;
+/// use pgp::{KeyType, SecretKeyParamsBuilder, SigningHasher, StandaloneSignature};
+///
+/// let key = SecretKeyParamsBuilder::default()
+///     .key_type(KeyType::EdDSA)
+///     .can_sign(true)
+///     .primary_user_id("Alice <alice@example.com>".into())
+///     .build()
+///     .expect("valid key parameters")
+///     .generate_signed()?;
+///
+/// let mut hasher = SigningHasher::new(&key, SignatureType::Binary, HashAlgorithm::SHA2_256)?;
+/// for _ in 0..1024 {
+///     hasher.write_all(b"some data\n")?;
+/// }
+/// let signature = StandaloneSignature::new(hasher.finish(&key, String::new)?);
+///
+/// signature.verify(&key.public_key(), "some data\n".repeat(1024).as_bytes())?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SigningHasher {
+    config: SignatureConfig,
+    hasher: Box<dyn Hasher>,
+    /// Whether the last text chunk ended in `\r`.
+    prev_was_cr: bool,
+    canonical: Vec<u8>,
+}
+
+impl SigningHasher {
+    /// Creates a hasher for a `Binary` or `Text` signature by `key`, created now.
+    pub fn new(
+        key: &impl SecretKeyTrait,
+        typ: SignatureType,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<Self> {
+        Self::from_config(SubpacketAreas::for_key(key).into_config(
+            typ,
+            key.algorithm(),
+            hash_algorithm,
+        ))
+    }
+
+    /// Creates a hasher for a signature with the given configuration.
+    pub fn from_config(config: SignatureConfig) -> Result<Self> {
+        ensure!(
+            matches!(config.typ, SignatureType::Binary | SignatureType::Text),
+            "invalid signature type for signing data: {:?}",
+            config.typ
+        );
+        let hasher = config.hash_alg.new_hasher()?;
+
+        Ok(SigningHasher {
+            config,
+            hasher,
+            prev_was_cr: false,
+            canonical: Vec::new(),
+        })
+    }
+
+    pub fn config(&self) -> &SignatureConfig {
+        &self.config
+    }
+
+    /// Hashes `data`, returning it in the form it was hashed in, with `\r\n` line endings
+    /// for text signatures.
+    fn hash<'b>(&'b mut self, data: &'b [u8]) -> &'b [u8] {
+        if self.config.typ == SignatureType::Text {
+            self.canonical.clear();
+            canonicalize_text(data, &mut self.prev_was_cr, &mut self.canonical);
+            self.hasher.update(&self.canonical);
+            &self.canonical
+        } else {
+            self.hasher.update(data);
+            data
+        }
+    }
+
+    /// Creates the signature over all of the data that was written.
+    pub fn finish<F>(self, key: &impl SecretKeyTrait, key_pw: F) -> Result<Signature>
+    where
+        F: PassphraseProvider,
+    {
+        self.config.sign_hashed(key, key_pw, self.hasher)
+    }
+}
+
+impl io::Write for SigningHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.hash(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes a one-pass signed message, signing the literal data as it is written.
+///
+/// The literal data is written using partial body lengths, so its size does not need to be
+/// known in advance. [`SignerWriter::finish`] must be called to write the signature.
+pub struct SignerWriter<'a, K, W: io::Write> {
+    key: &'a K,
+    hasher: SigningHasher,
+    inner: W,
+    /// Literal data that was not written yet, as it does not fill a partial chunk.
+    chunk: Vec<u8>,
+}
+
+impl<'a, K: SecretKeyTrait, W: io::Write> SignerWriter<'a, K, W> {
+    /// Writes the one-pass signature and the literal data header for `file_name` to `inner`.
+    ///
+    /// Text data, for any `mode` other than `Binary`, is written with `\r\n` line endings.
+    pub fn new(
+        mut inner: W,
+        key: &'a K,
+        hash_algorithm: HashAlgorithm,
+        file_name: &BStr,
+        mode: DataMode,
+    ) -> Result<Self> {
+        ensure!(file_name.len() <= 255, "file name too long");
+
+        let typ = match mode {
+            DataMode::Binary => SignatureType::Binary,
+            _ => SignatureType::Text,
+        };
+        let hasher = SigningHasher::new(key, typ, hash_algorithm)?;
+        let ops =
+            OnePassSignature::from_details(typ, hash_algorithm, key.algorithm(), key.key_id());
+        write_packet(&mut inner, &ops)?;
+
+        let mut chunk = Vec::with_capacity(PARTIAL_CHUNK_SIZE);
+        chunk.push(mode.into());
+        chunk.push(file_name.len() as u8);
+        chunk.extend_from_slice(file_name);
+        chunk.write_u32::<BigEndian>(chrono::Utc::now().timestamp() as u32)?;
+
+        // new format packet header, the lengths follow with each chunk
+        inner.write_all(&[0xC0 | Tag::LiteralData as u8])?;
+
+        Ok(SignerWriter {
+            key,
+            hasher,
+            inner,
+            chunk,
+        })
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Writes the remaining literal data and the signature, returning the inner writer.
+    pub fn finish<F>(mut self, key_pw: F) -> Result<W>
+    where
+        F: PassphraseProvider,
+    {
+        write_packet_length(self.chunk.len(), &mut self.inner)?;
+        self.inner.write_all(&self.chunk)?;
+
+        let signature = self.hasher.finish(self.key, key_pw)?;
+        write_packet(&mut self.inner, &signature)?;
+
+        Ok(self.inner)
+    }
+}
+
+impl<'a, K, W: io::Write> io::Write for SignerWriter<'a, K, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let hashed = self.hasher.hash(buf);
+        self.chunk.extend_from_slice(hashed);
+
+        while self.chunk.len() >= PARTIAL_CHUNK_SIZE {
+            self.inner.write_all(&[0xE0 | PARTIAL_CHUNK_POWER])?;
+            self.inner.write_all(&self.chunk[..PARTIAL_CHUNK_SIZE])?;
+            self.chunk.drain(..PARTIAL_CHUNK_SIZE);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::io::Write;
+
+    use super::*;
+    use crate::composed::{Deserializable, Message, StandaloneSignature};
+    use crate::test_util::gen_key;
+    use crate::types::KeyTrait;
+
+    #[test]
+    fn test_signing_hasher() {
+        let key = gen_key("Alice <alice@example.com>");
+        let text = "hello\r\nworld\rwith mixed\nline endings\n".repeat(1000);
+
+        let mut hasher =
+            SigningHasher::new(&key, SignatureType::Text, HashAlgorithm::SHA2_256).unwrap();
+        // split line endings across writes
+        for chunk in text.as_bytes().chunks(7) {
+            hasher.write_all(chunk).unwrap();
+        }
+        let signature = StandaloneSignature::new(hasher.finish(&key, String::new).unwrap());
+        signature
+            .verify(&key.public_key(), text.as_bytes())
+            .unwrap();
+
+        let config = SignatureConfig::new_v4(
+            Default::default(),
+            SignatureType::Key,
+            key.algorithm(),
+            HashAlgorithm::SHA2_256,
+            Vec::new(),
+            Vec::new(),
+        );
+        assert!(SigningHasher::from_config(config).is_err());
+    }
+
+    #[test]
+    fn test_signer_writer() {
+        let key = gen_key("Alice <alice@example.com>");
+        let data: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+
+        let mut writer = SignerWriter::new(
+            Vec::new(),
+            &key,
+            HashAlgorithm::SHA2_512,
+            b"data.bin".into(),
+            DataMode::Binary,
+        )
+        .unwrap();
+        for chunk in data.chunks(1000) {
+            writer.write_all(chunk).unwrap();
+        }
+        let out = writer.finish(String::new).unwrap();
+
+        let msg = Message::from_bytes(&out[..]).unwrap();
+        assert!(msg.is_one_pass_signed());
+        assert_eq!(msg.get_literal().unwrap().data(), &data[..]);
+        msg.verify(&key.public_key()).unwrap();
+
+        // small text messages are written without partial lengths
+        let mut writer = SignerWriter::new(
+            Vec::new(),
+            &key,
+            HashAlgorithm::SHA2_256,
+            b"hello.txt".into(),
+            DataMode::Utf8,
+        )
+        .unwrap();
+        writer.write_all(b"hello world\n").unwrap();
+        let out = writer.finish(String::new).unwrap();
+
+        let msg = Message::from_bytes(&out[..]).unwrap();
+        assert_eq!(msg.get_content().unwrap().unwrap(), b"hello world\r\n");
+        msg.verify(&key.public_key()).unwrap();
+    }
+}
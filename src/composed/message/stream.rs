@@ -0,0 +1,799 @@
+use std::io::{self, Read};
+
+use bstr::{BStr, BString};
+use byteorder::{BigEndian, ReadBytesExt};
+#[cfg(feature = "bzip2")]
+use bzip2::read::BzDecoder;
+use chrono::{DateTime, TimeZone, Utc};
+use flate2::read::{DeflateDecoder, ZlibDecoder};
+use sha1::{Digest, Sha1};
+
+use crate::composed::message::decrypt::*;
+use crate::composed::message::types::Esk;
+use crate::composed::signed_key::SignedSecretKey;
+use crate::crypto::checksum;
+use crate::crypto::sym::StreamDecryptor;
+use crate::errors::{Error, IntegrityError, Result};
+use crate::packet::{single, DataMode, Packet, CONSOLE_FILE_NAME};
+use crate::types::{CompressionAlgorithm, KeyId, PacketLength, PassphraseProvider, Tag, Version};
+
+/// MDC is 1 byte packet tag, 1 byte length prefix and 20 bytes SHA1 hash.
+const MDC_LEN: usize = 22;
+
+/// The amount of ciphertext that is decrypted at once.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// The maximum size of packets that are read into memory, like encrypted session keys and
+/// signatures.
+const MAX_PACKET_SIZE: u64 = 1024 * 1024;
+
+/// Decrypts a message while it is read, so that memory usage stays constant regardless of
+/// the size of the message.
+///
+/// Only messages with integrity protection are supported. The integrity of the message is
+/// checked once all of the data has been read, so the plaintext must not be trusted before
+/// [`DecryptedReader`] has returned the end of the data without an error.
+///
+/// ```rust
+/// # fn main() -> pgp::errors::Result<()> {
+/// use std::io::Read;
+///
+/// use pgp::crypto::sym::SymmetricKeyAlgorithm;
+/// use pgp::ser::Serialize;
+/// use pgp::types::StringToKey;
+/// use pgp::{Message, StreamDecrypter};
+///
+/// let mut rng = rand::thread_rng();
+/// let s2k = StringToKey::new_default(&mut rng);
+/// let encrypted = Message::new_literal("hello.txt", "hello world\n")
+///     .encrypt_with_password(&mut rng, s2k, SymmetricKeyAlgorithm::AES128, || "hunter2".into())?
+///     .to_bytes()?;
+///
+/// let mut reader = StreamDecrypter::new(&encrypted[..])?.decrypt_with_password(|| "hunter2".into())?;
+/// let mut plaintext = Vec::new();
+/// reader.read_to_end(&mut plaintext)?;
+/// assert_eq!(plaintext, b"hello world\r\n");
+/// # Ok(())
+/// # }
+/// ```
+pub struct StreamDecrypter<R> {
+    source: R,
+    esk: Vec<Esk>,
+    length: PacketLength,
+}
+
+impl<R: Read> StreamDecrypter<R> {
+    /// Reads the encrypted session key packets from `source`, up to the start of the
+    /// encrypted data.
+    pub fn new(mut source: R) -> Result<Self> {
+        let mut esk = Vec::new();
+        loop {
+            let Some((ver, tag, length)) = read_header(&mut source)? else {
+                bail!("missing encrypted data");
+            };
+            match tag {
+                Tag::PublicKeyEncryptedSessionKey | Tag::SymKeyEncryptedSessionKey => {
+                    match read_packet(&mut source, ver, tag, length)? {
+                        Packet::PublicKeyEncryptedSessionKey(p) => {
+                            esk.push(Esk::PublicKeyEncryptedSessionKey(p))
+                        }
+                        Packet::SymKeyEncryptedSessionKey(p) => {
+                            esk.push(Esk::SymKeyEncryptedSessionKey(p))
+                        }
+                        _ => unreachable!("tag checked above"),
+                    }
+                }
+                Tag::Marker
+                | Tag::Trust
+                | Tag::Private60
+                | Tag::Private61
+                | Tag::Private62
+                | Tag::Private63 => {
+                    warn!("skipping {:?} packet", tag);
+                    skip_packet(&mut source, length)?
+                }
+                Tag::SymEncryptedProtectedData => {
+                    return Ok(StreamDecrypter {
+                        source,
+                        esk,
+                        length,
+                    });
+                }
+                Tag::SymEncryptedData => {
+                    unsupported_err!("streaming decryption without integrity protection")
+                }
+                _ => bail!("unexpected packet {:?} in encrypted message", tag),
+            }
+        }
+    }
+
+    /// The encrypted session key packets of the message.
+    pub fn esk(&self) -> &[Esk] {
+        &self.esk
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.source
+    }
+
+    /// Decrypts the message using the given keys.
+    /// Returns the reader for the plaintext, and a list of [KeyId]s that are valid recipients
+    /// of this message.
+    pub fn decrypt<G>(
+        self,
+        key_pw: G,
+        keys: &[&SignedSecretKey],
+    ) -> Result<(DecryptedReader<R>, Vec<KeyId>)>
+    where
+        G: PassphraseProvider + Clone,
+    {
+        let (session_key, ids) = decrypt_session_key_for_keys(&self.esk, key_pw, keys)?;
+
+        Ok((self.decrypt_with_session_key(&session_key)?, ids))
+    }
+
+    /// Decrypts the message using the given password.
+    pub fn decrypt_with_password<F>(self, msg_pw: F) -> Result<DecryptedReader<R>>
+    where
+        F: FnOnce() -> String,
+    {
+        let session_key = decrypt_session_key_for_password(&self.esk, msg_pw)?;
+
+        self.decrypt_with_session_key(&session_key)
+    }
+
+    /// Decrypts the message using a session key that was obtained by other means.
+    pub fn decrypt_with_session_key(self, session_key: &SessionKey) -> Result<DecryptedReader<R>> {
+        let mut source = BodyReader::new(self.source, self.length);
+        ensure_eq!(source.read_u8()?, 0x01, "first bytes must be 0x01");
+
+        let decrypted = ProtectedDataReader::new(source, session_key)?;
+        DecryptedReader::new(decrypted)
+    }
+}
+
+/// The plaintext of a message decrypted by [`StreamDecrypter`].
+///
+/// Reads the content of the literal data packet, after decompressing it if needed.
+/// Signatures in the message are skipped, and not verified.
+pub struct DecryptedReader<R: Read> {
+    literal: LiteralReader<ProtectedDataReader<R>>,
+    done: bool,
+}
+
+impl<R: Read> DecryptedReader<R> {
+    pub(crate) fn new(decrypted: ProtectedDataReader<R>) -> Result<Self> {
+        Ok(DecryptedReader {
+            literal: LiteralReader::new(decrypted)?,
+            done: false,
+        })
+    }
+
+    pub fn is_binary(&self) -> bool {
+        self.literal.is_binary()
+    }
+
+    /// The file name of the literal data, which may contain non utf-8 bytes.
+    pub fn file_name(&self) -> &BStr {
+        self.literal.file_name()
+    }
+
+    pub fn created(&self) -> &DateTime<Utc> {
+        self.literal.created()
+    }
+
+    pub fn mode(&self) -> DataMode {
+        self.literal.mode()
+    }
+
+    /// Whether the data is "for your eyes only", see [`CONSOLE_FILE_NAME`].
+    pub fn is_for_your_eyes_only(&self) -> bool {
+        self.literal.is_for_your_eyes_only()
+    }
+
+    pub(crate) fn is_compressed(&self) -> bool {
+        self.literal.is_compressed()
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        self.literal.get_mut().source.get_mut()
+    }
+}
+
+impl<R: Read> Read for DecryptedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.literal.read(buf)?;
+
+        if read == 0 && !buf.is_empty() && !self.done {
+            // the integrity check is only done when all of the encrypted data was read
+            io::copy(self.literal.get_mut(), &mut io::sink())?;
+            self.done = true;
+        }
+
+        Ok(read)
+    }
+}
+
+/// Reads the content of the literal data packet of an unencrypted message while it is read,
+/// after decompressing it if needed, so that messages of any size can be written to disk.
+///
+/// Signatures in the message are skipped, and not verified. Use [`StreamDecrypter`] for
+/// encrypted messages.
+///
+/// ```rust
+/// # fn main() -> pgp::errors::Result<()> {
+/// use std::io::Read;
+///
+/// use pgp::ser::Serialize;
+/// use pgp::types::CompressionAlgorithm;
+/// use pgp::{LiteralReader, Message};
+///
+/// let message = Message::new_literal_bytes("data.bin", &[1, 2, 3])
+///     .compress(CompressionAlgorithm::ZLIB)?
+///     .to_bytes()?;
+///
+/// let mut reader = LiteralReader::new(&message[..])?;
+/// assert_eq!(reader.file_name(), "data.bin");
+/// let mut data = Vec::new();
+/// reader.read_to_end(&mut data)?;
+/// assert_eq!(data, [1, 2, 3]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct LiteralReader<R: Read> {
+    plaintext: Plaintext<R>,
+    mode: DataMode,
+    file_name: BString,
+    created: DateTime<Utc>,
+}
+
+enum Plaintext<R: Read> {
+    Literal(BodyReader<R>),
+    Compressed(BodyReader<Decompressor<BodyReader<R>>>),
+}
+
+impl<R: Read> LiteralReader<R> {
+    /// Reads the message from `source` up to the start of the literal data.
+    pub fn new(mut source: R) -> Result<Self> {
+        let plaintext = match find_data(&mut source)? {
+            (Tag::LiteralData, length) => Plaintext::Literal(BodyReader::new(source, length)),
+            (_, length) => {
+                let mut compressed = BodyReader::new(source, length);
+                let mut decompressor = Decompressor::new(compressed.read_u8()?, compressed)?;
+                match find_data(&mut decompressor)? {
+                    (Tag::LiteralData, length) => {
+                        Plaintext::Compressed(BodyReader::new(decompressor, length))
+                    }
+                    _ => unsupported_err!("nested compressed data"),
+                }
+            }
+        };
+
+        let mut reader = LiteralReader {
+            plaintext,
+            mode: DataMode::Binary,
+            file_name: BString::default(),
+            created: Utc::now(),
+        };
+
+        reader.mode = reader.read_u8()?.into();
+        let name_len = reader.read_u8()?;
+        let mut file_name = vec![0u8; name_len.into()];
+        reader.read_exact(&mut file_name)?;
+        reader.file_name = file_name.into();
+        reader.created = Utc
+            .timestamp_opt(reader.read_u32::<BigEndian>()?.into(), 0)
+            .single()
+            .ok_or_else(|| format_err!("invalid literal data creation time"))?;
+
+        Ok(reader)
+    }
+
+    pub fn is_binary(&self) -> bool {
+        matches!(self.mode, DataMode::Binary)
+    }
+
+    /// The file name of the literal data, which may contain non utf-8 bytes.
+    pub fn file_name(&self) -> &BStr {
+        self.file_name.as_ref()
+    }
+
+    pub fn created(&self) -> &DateTime<Utc> {
+        &self.created
+    }
+
+    pub fn mode(&self) -> DataMode {
+        self.mode
+    }
+
+    /// Whether the data is "for your eyes only", see [`CONSOLE_FILE_NAME`].
+    pub fn is_for_your_eyes_only(&self) -> bool {
+        self.file_name == CONSOLE_FILE_NAME
+    }
+
+    fn is_compressed(&self) -> bool {
+        matches!(self.plaintext, Plaintext::Compressed(_))
+    }
+
+    /// The source of the message, after the literal data.
+    fn get_mut(&mut self) -> &mut R {
+        match self.plaintext {
+            Plaintext::Literal(ref mut r) => r.get_mut(),
+            Plaintext::Compressed(ref mut r) => r.get_mut().get_mut().get_mut(),
+        }
+    }
+}
+
+impl<R: Read> Read for LiteralReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.plaintext {
+            Plaintext::Literal(ref mut r) => r.read(buf),
+            Plaintext::Compressed(ref mut r) => r.read(buf),
+        }
+    }
+}
+
+/// Skips signature, marker, trust and experimental packets, up to the literal or compressed
+/// data packet.
+fn find_data(source: &mut impl Read) -> Result<(Tag, PacketLength)> {
+    loop {
+        let Some((_, tag, length)) = read_header(source)? else {
+            bail!("missing literal data");
+        };
+        match tag {
+            Tag::LiteralData | Tag::CompressedData => return Ok((tag, length)),
+            Tag::OnePassSignature | Tag::Signature => skip_packet(source, length)?,
+            Tag::Marker
+            | Tag::Trust
+            | Tag::Private60
+            | Tag::Private61
+            | Tag::Private62
+            | Tag::Private63 => {
+                warn!("skipping {:?} packet", tag);
+                skip_packet(source, length)?
+            }
+            _ => bail!("unexpected packet {:?} before the literal data", tag),
+        }
+    }
+}
+
+/// Reads a packet header, returning `None` at the end of the input.
+/// Ref: https://tools.ietf.org/html/rfc4880.html#section-4.2
+fn read_header(source: &mut impl Read) -> Result<Option<(Version, Tag, PacketLength)>> {
+    let mut first = [0u8; 1];
+    if source.read(&mut first)? == 0 {
+        return Ok(None);
+    }
+    let first = first[0];
+    ensure!(first & 0x80 != 0, "invalid packet header {:#04x}", first);
+
+    let (ver, tag, length) = if first & 0x40 != 0 {
+        (Version::New, first & 0x3F, read_length(source)?)
+    } else {
+        let length = match first & 0x03 {
+            0 => PacketLength::Fixed(source.read_u8()?.into()),
+            1 => PacketLength::Fixed(source.read_u16::<BigEndian>()?.into()),
+            2 => PacketLength::Fixed(source.read_u32::<BigEndian>()? as usize),
+            _ => PacketLength::Indeterminate,
+        };
+        (Version::Old, (first >> 2) & 0x0F, length)
+    };
+    let tag = Tag::try_from(tag).map_err(|_| format_err!("invalid packet tag {}", tag))?;
+
+    Ok(Some((ver, tag, length)))
+}
+
+/// Reads a new format packet length.
+/// Ref: https://tools.ietf.org/html/rfc4880.html#section-4.2.2
+fn read_length(source: &mut impl Read) -> io::Result<PacketLength> {
+    let olen = source.read_u8()?;
+    let length = match olen {
+        0..=191 => PacketLength::Fixed(olen.into()),
+        192..=223 => PacketLength::Fixed(
+            ((usize::from(olen) - 192) << 8) + 192 + usize::from(source.read_u8()?),
+        ),
+        224..=254 => PacketLength::Partial(1 << (olen & 0x1F)),
+        255 => PacketLength::Fixed(source.read_u32::<BigEndian>()? as usize),
+    };
+
+    Ok(length)
+}
+
+fn read_packet(
+    source: &mut impl Read,
+    ver: Version,
+    tag: Tag,
+    length: PacketLength,
+) -> Result<Packet> {
+    let mut body = Vec::new();
+    BodyReader::new(source, length)
+        .take(MAX_PACKET_SIZE)
+        .read_to_end(&mut body)?;
+
+    single::body_parser(ver, tag, &body)
+}
+
+fn skip_packet(source: &mut impl Read, length: PacketLength) -> Result<()> {
+    io::copy(&mut BodyReader::new(source, length), &mut io::sink())?;
+
+    Ok(())
+}
+
+/// Reads the body of a single packet, following partial body lengths.
+struct BodyReader<R> {
+    source: R,
+    length: PacketLength,
+}
+
+impl<R: Read> BodyReader<R> {
+    fn new(source: R, length: PacketLength) -> Self {
+        BodyReader { source, length }
+    }
+
+    fn get_mut(&mut self) -> &mut R {
+        &mut self.source
+    }
+}
+
+impl<R: Read> Read for BodyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.length {
+                PacketLength::Indeterminate => return self.source.read(buf),
+                PacketLength::Partial(0) => self.length = read_length(&mut self.source)?,
+                PacketLength::Fixed(0) => return Ok(0),
+                PacketLength::Fixed(ref mut remaining)
+                | PacketLength::Partial(ref mut remaining) => {
+                    let len = buf.len().min(*remaining);
+                    let read = self.source.read(&mut buf[..len])?;
+                    if read == 0 && len > 0 {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    *remaining -= read;
+
+                    return Ok(read);
+                }
+            }
+        }
+    }
+}
+
+/// Decrypts the body of a symmetrically encrypted integrity protected data packet, checking
+/// the modification detection code at the end.
+pub(crate) struct ProtectedDataReader<R> {
+    source: BodyReader<R>,
+    decryptor: StreamDecryptor,
+    hasher: Sha1,
+    /// Decrypted data that was not returned yet, the last `MDC_LEN` bytes are held back, as
+    /// they might be the MDC.
+    buffer: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> ProtectedDataReader<R> {
+    /// Decrypts `body`, the data of the packet after its version.
+    pub(crate) fn from_body(body: R, session_key: &SessionKey) -> Result<Self> {
+        Self::new(
+            BodyReader::new(body, PacketLength::Indeterminate),
+            session_key,
+        )
+    }
+
+    fn new(mut source: BodyReader<R>, session_key: &SessionKey) -> Result<Self> {
+        let alg = session_key.algorithm();
+        let mut decryptor = alg.stream_decryptor(session_key.key())?;
+
+        // We do not do "quick check" here, see `SymmetricKeyAlgorithm::decrypt_with_iv`.
+        let mut prefix = vec![0u8; alg.block_size() + 2];
+        source.read_exact(&mut prefix)?;
+        decryptor(&mut prefix);
+
+        let mut hasher = Sha1::new();
+        hasher.update(&prefix);
+
+        Ok(ProtectedDataReader {
+            source,
+            decryptor,
+            hasher,
+            buffer: Vec::with_capacity(CHUNK_SIZE + MDC_LEN),
+            done: false,
+        })
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        while !self.done && self.buffer.len() <= MDC_LEN {
+            let start = self.buffer.len();
+            self.buffer.resize(start + CHUNK_SIZE, 0);
+            let read = self.source.read(&mut self.buffer[start..]);
+            self.buffer.truncate(start + *read.as_ref().unwrap_or(&0));
+
+            match read? {
+                0 => {
+                    self.done = true;
+                    self.check_mdc()?;
+                }
+                _ => (self.decryptor)(&mut self.buffer[start..]),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_mdc(&mut self) -> io::Result<()> {
+        if self.buffer.len() < MDC_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                Error::IntegrityCheckFailed(IntegrityError::Truncated),
+            ));
+        }
+
+        let (data, mdc) = self.buffer.split_at(self.buffer.len() - MDC_LEN);
+        self.hasher.update(data);
+        self.hasher.update(&mdc[..2]);
+        let sha1 = self.hasher.finalize_reset();
+        if !checksum::check_mdc(mdc, &sha1) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                Error::IntegrityCheckFailed(IntegrityError::MdcMismatch),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for ProtectedDataReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.fill()?;
+
+        let available = self.buffer.len().saturating_sub(MDC_LEN);
+        let len = buf.len().min(available);
+        buf[..len].copy_from_slice(&self.buffer[..len]);
+        if !self.done {
+            // once done, all the data was hashed for the MDC check
+            self.hasher.update(&self.buffer[..len]);
+        }
+        self.buffer.drain(..len);
+
+        Ok(len)
+    }
+}
+
+enum Decompressor<R: Read> {
+    Uncompressed(R),
+    Zip(DeflateDecoder<R>),
+    Zlib(ZlibDecoder<R>),
+    #[cfg(feature = "bzip2")]
+    Bzip2(BzDecoder<R>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::read::Decoder<'static, io::BufReader<R>>),
+}
+
+impl<R: Read> Decompressor<R> {
+    fn new(alg: u8, source: R) -> Result<Self> {
+        let alg = CompressionAlgorithm::from(alg);
+        let decompressor = match alg {
+            CompressionAlgorithm::Uncompressed => Decompressor::Uncompressed(source),
+            CompressionAlgorithm::ZIP => Decompressor::Zip(DeflateDecoder::new(source)),
+            CompressionAlgorithm::ZLIB => Decompressor::Zlib(ZlibDecoder::new(source)),
+            #[cfg(feature = "bzip2")]
+            CompressionAlgorithm::BZip2 => Decompressor::Bzip2(BzDecoder::new(source)),
+            #[cfg(not(feature = "bzip2"))]
+            CompressionAlgorithm::BZip2 => unsupported_alg_err!(Compression, alg),
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd => {
+                Decompressor::Zstd(zstd::stream::read::Decoder::new(source)?)
+            }
+            #[cfg(not(feature = "zstd"))]
+            CompressionAlgorithm::Zstd => unsupported_alg_err!(Compression, alg),
+            CompressionAlgorithm::Private10 | CompressionAlgorithm::Other(_) => {
+                unsupported_alg_err!(Compression, alg)
+            }
+        };
+
+        Ok(decompressor)
+    }
+
+    fn get_mut(&mut self) -> &mut R {
+        match self {
+            Decompressor::Uncompressed(r) => r,
+            Decompressor::Zip(r) => r.get_mut(),
+            Decompressor::Zlib(r) => r.get_mut(),
+            #[cfg(feature = "bzip2")]
+            Decompressor::Bzip2(r) => r.get_mut(),
+            #[cfg(feature = "zstd")]
+            Decompressor::Zstd(r) => r.get_mut().get_mut(),
+        }
+    }
+}
+
+impl<R: Read> Read for Decompressor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Decompressor::Uncompressed(r) => r.read(buf),
+            Decompressor::Zip(r) => r.read(buf),
+            Decompressor::Zlib(r) => r.read(buf),
+            #[cfg(feature = "bzip2")]
+            Decompressor::Bzip2(r) => r.read(buf),
+            #[cfg(feature = "zstd")]
+            Decompressor::Zstd(r) => r.read(buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::fs::File;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::composed::{Deserializable, Message, SignedSecretKey};
+    use crate::crypto::sym::SymmetricKeyAlgorithm;
+    use crate::ser::Serialize;
+    use crate::types::{KeyTrait, StringToKey};
+    use crate::util::write_packet_length;
+
+    fn read_all(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    /// Rewrites the encrypted data packet at the end of `msg` to use partial body lengths.
+    fn to_partial(msg: &Message) -> Vec<u8> {
+        let Message::Encrypted { esk, edata } = msg else {
+            panic!("not encrypted");
+        };
+        let mut out = Vec::new();
+        for esk in esk {
+            esk.to_writer(&mut out).unwrap();
+        }
+        let mut body = vec![0x01];
+        body.extend_from_slice(edata[0].data());
+
+        out.push(0xC0 | Tag::SymEncryptedProtectedData as u8);
+        let mut chunks = body.chunks(512).peekable();
+        while let Some(chunk) = chunks.next() {
+            if chunks.peek().is_some() && chunk.len() == 512 {
+                out.push(0xE9);
+            } else {
+                write_packet_length(chunk.len(), &mut out).unwrap();
+            }
+            out.extend_from_slice(chunk);
+        }
+        out
+    }
+
+    #[test]
+    fn test_stream_decrypt() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let text = "hello world\n".repeat(10_000);
+        let msg = Message::new_literal("hello.txt", &text)
+            .sign(
+                &skey,
+                String::new,
+                crate::crypto::hash::HashAlgorithm::SHA2_256,
+            )
+            .unwrap()
+            .compress(CompressionAlgorithm::ZLIB)
+            .unwrap()
+            .encrypt_to_keys(
+                &mut rng,
+                SymmetricKeyAlgorithm::AES128,
+                &[&skey.secret_subkeys[0]],
+            )
+            .unwrap();
+        let expected = text.replace('\n', "\r\n");
+
+        let encrypted = msg.to_bytes().unwrap();
+        let (mut reader, ids) = StreamDecrypter::new(&encrypted[..])
+            .unwrap()
+            .decrypt(String::new, &[&skey])
+            .unwrap();
+        assert_eq!(ids, vec![skey.secret_subkeys[0].key_id()]);
+        assert_eq!(reader.file_name(), "hello.txt");
+        assert!(!reader.is_binary());
+        assert_eq!(read_all(&mut reader).unwrap(), expected.as_bytes());
+
+        let partial = to_partial(&msg);
+        let (mut reader, _) = StreamDecrypter::new(&partial[..])
+            .unwrap()
+            .decrypt(String::new, &[&skey])
+            .unwrap();
+        assert_eq!(read_all(&mut reader).unwrap(), expected.as_bytes());
+    }
+
+    #[test]
+    fn test_stream_decrypt_tampered() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let s2k = StringToKey::new_default(&mut rng);
+        let data = vec![7u8; 100_000];
+        let msg = Message::new_literal_bytes("data.bin", &data)
+            .encrypt_with_password(&mut rng, s2k, SymmetricKeyAlgorithm::AES256, || "pw".into())
+            .unwrap();
+
+        let mut encrypted = msg.to_bytes().unwrap();
+        let mut reader = StreamDecrypter::new(&encrypted[..])
+            .unwrap()
+            .decrypt_with_password(|| "pw".into())
+            .unwrap();
+        assert!(reader.is_binary());
+        assert_eq!(read_all(&mut reader).unwrap(), data);
+
+        // flip a bit in the middle of the literal data
+        let middle = encrypted.len() / 2;
+        encrypted[middle] ^= 1;
+        let mut reader = StreamDecrypter::new(&encrypted[..])
+            .unwrap()
+            .decrypt_with_password(|| "pw".into())
+            .unwrap();
+        assert!(read_all(&mut reader).is_err());
+
+        let truncated = &encrypted[..encrypted.len() - 10];
+        let mut reader = StreamDecrypter::new(truncated)
+            .unwrap()
+            .decrypt_with_password(|| "pw".into())
+            .unwrap();
+        assert!(read_all(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_literal_reader() {
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let data: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+
+        // a literal data packet with partial body lengths
+        let mut body = vec![b'b', 8];
+        body.extend_from_slice(b"data.bin");
+        body.extend_from_slice(&[0, 0, 0, 42]);
+        body.extend_from_slice(&data);
+        let mut partial = vec![0xC0 | Tag::LiteralData as u8];
+        let mut chunks = body.chunks(1024).peekable();
+        while let Some(chunk) = chunks.next() {
+            if chunks.peek().is_some() {
+                partial.push(0xEA);
+            } else {
+                write_packet_length(chunk.len(), &mut partial).unwrap();
+            }
+            partial.extend_from_slice(chunk);
+        }
+
+        let mut reader = LiteralReader::new(&partial[..]).unwrap();
+        assert!(reader.is_binary());
+        assert_eq!(reader.file_name(), "data.bin");
+        assert_eq!(reader.created().timestamp(), 42);
+        assert_eq!(read_all(&mut reader).unwrap(), data);
+
+        let msg = Message::new_literal_bytes(CONSOLE_FILE_NAME, &data)
+            .sign(
+                &skey,
+                String::new,
+                crate::crypto::hash::HashAlgorithm::SHA2_256,
+            )
+            .unwrap()
+            .compress(CompressionAlgorithm::ZLIB)
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        let mut reader = LiteralReader::new(&msg[..]).unwrap();
+        assert!(reader.is_for_your_eyes_only());
+        assert_eq!(read_all(&mut reader).unwrap(), data);
+
+        let literal = Message::new_literal_bytes("data.bin", &data);
+        let literal = literal.get_literal().unwrap();
+        assert_eq!(read_all(&mut literal.reader()).unwrap(), data);
+    }
+}
@@ -1,32 +1,35 @@
+use std::borrow::Cow;
 use std::boxed::Box;
-use std::collections::BTreeMap;
 use std::convert::TryFrom;
-use std::io;
+use std::io::{self, Read};
 
 use bstr::BStr;
-use chrono::{self, SubsecRound};
 use flate2::write::{DeflateEncoder, ZlibEncoder};
 use flate2::Compression;
 use rand::{CryptoRng, Rng};
-use smallvec::SmallVec;
+use zeroize::Zeroizing;
 
 use crate::armor;
 use crate::composed::message::decrypt::*;
-use crate::composed::shared::Deserializable;
-use crate::composed::signed_key::SignedSecretKey;
-use crate::composed::StandaloneSignature;
+use crate::composed::signed_key::{SignedPublicKey, SignedPublicSubKey, SignedSecretKey};
+use crate::composed::{StandaloneSignature, VerificationResult};
 use crate::crypto::hash::HashAlgorithm;
 use crate::crypto::sym::SymmetricKeyAlgorithm;
 use crate::errors::{Error, Result};
+use crate::limits::ParseLimits;
 use crate::packet::{
-    write_packet, CompressedData, LiteralData, OnePassSignature, Packet,
-    PublicKeyEncryptedSessionKey, Signature, SignatureConfig, SignatureType, Subpacket,
-    SubpacketData, SymEncryptedData, SymEncryptedProtectedData, SymKeyEncryptedSessionKey,
+    write_packet, CompressedData, Experimental, LiteralData, OnePassSignature, Packet,
+    PublicKeyEncryptedSessionKey, Signature, SignatureConfig, SignatureType, SubpacketAreas,
+    SymEncryptedData, SymEncryptedProtectedData, SymKeyEncryptedSessionKey,
 };
+use crate::policy::Policy;
 use crate::ser::Serialize;
+use crate::tofu::{self, TrustStore};
+#[cfg(feature = "async")]
+use crate::types::AsyncSecretKeyTrait;
 use crate::types::{
-    CompressionAlgorithm, KeyId, KeyTrait, KeyVersion, PublicKeyTrait, SecretKeyTrait, StringToKey,
-    Tag,
+    CompressionAlgorithm, Decryptor, KeyId, KeyTrait, PassphraseProvider, PublicKeyTrait,
+    SecretKeyTrait, StringToKey, Tag,
 };
 
 /// An [OpenPGP message](https://tools.ietf.org/html/rfc4880.html#section-11.3)
@@ -46,6 +49,12 @@ pub enum Message {
         esk: Vec<Esk>,
         edata: Vec<Edata>,
     },
+    /// A message followed by packets with private or experimental tags, which are kept
+    /// so that the message is written back unchanged.
+    Experimental {
+        message: Box<Message>,
+        packets: Vec<Experimental>,
+    },
 }
 
 /// Encrypted Session Key
@@ -194,6 +203,14 @@ impl Serialize for Message {
                     e.to_writer(writer)?;
                 }
 
+                Ok(())
+            }
+            Message::Experimental { message, packets } => {
+                message.to_writer(writer)?;
+                for p in packets {
+                    write_packet(writer, p)?;
+                }
+
                 Ok(())
             }
         }
@@ -211,6 +228,18 @@ impl Message {
 
     /// Compresses the message.
     pub fn compress(&self, alg: CompressionAlgorithm) -> Result<Self> {
+        self.compress_internal(alg, None)
+    }
+
+    /// Compresses the message with the given level, from 0 (none) to 9 (best) for ZIP, ZLIB
+    /// and BZip2. Zstd takes levels from 1 to 22.
+    pub fn compress_with_level(&self, alg: CompressionAlgorithm, level: u32) -> Result<Self> {
+        self.compress_internal(alg, Some(level))
+    }
+
+    /// Compresses the message, using the default level of the algorithm if `level` is `None`.
+    fn compress_internal(&self, alg: CompressionAlgorithm, level: Option<u32>) -> Result<Self> {
+        let flate_level = level.map_or_else(Compression::default, |l| Compression::new(l.min(9)));
         let data = match alg {
             CompressionAlgorithm::Uncompressed => {
                 let mut data = Vec::new();
@@ -218,18 +247,38 @@ impl Message {
                 data
             }
             CompressionAlgorithm::ZIP => {
-                let mut enc = DeflateEncoder::new(Vec::new(), Compression::default());
+                let mut enc = DeflateEncoder::new(Vec::new(), flate_level);
                 self.to_writer(&mut enc)?;
                 enc.finish()?
             }
             CompressionAlgorithm::ZLIB => {
-                let mut enc = ZlibEncoder::new(Vec::new(), Compression::default());
+                let mut enc = ZlibEncoder::new(Vec::new(), flate_level);
+                self.to_writer(&mut enc)?;
+                enc.finish()?
+            }
+            #[cfg(feature = "bzip2")]
+            CompressionAlgorithm::BZip2 => {
+                let level = level.map_or_else(bzip2::Compression::default, |l| {
+                    bzip2::Compression::new(l.clamp(1, 9))
+                });
+                let mut enc = bzip2::write::BzEncoder::new(Vec::new(), level);
+                self.to_writer(&mut enc)?;
+                enc.finish()?
+            }
+            #[cfg(not(feature = "bzip2"))]
+            CompressionAlgorithm::BZip2 => unsupported_alg_err!(Compression, alg),
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd => {
+                // 0 selects the default level
+                let level = level.map_or(0, |l| l.min(22) as i32);
+                let mut enc = zstd::stream::write::Encoder::new(Vec::new(), level)?;
                 self.to_writer(&mut enc)?;
                 enc.finish()?
             }
-            CompressionAlgorithm::BZip2 => unimplemented_err!("BZip2"),
+            #[cfg(not(feature = "zstd"))]
+            CompressionAlgorithm::Zstd => unsupported_alg_err!(Compression, alg),
             CompressionAlgorithm::Private10 | CompressionAlgorithm::Other(_) => {
-                unsupported_err!("CompressionAlgorithm {} is unsupported", u8::from(alg))
+                unsupported_alg_err!(Compression, alg)
             }
         };
 
@@ -238,38 +287,92 @@ impl Message {
         )))
     }
 
+    /// Returns the message without the experimental packets that follow it.
+    fn without_experimental(&self) -> &Message {
+        match self {
+            Message::Experimental { message, .. } => message.without_experimental(),
+            _ => self,
+        }
+    }
+
     /// Decompresses the data if compressed.
     pub fn decompress(self) -> Result<Self> {
+        self.decompress_with_limits(&ParseLimits::default())
+    }
+
+    /// Decompresses the data if compressed, rejecting decompressed data and messages that
+    /// exceed `limits`.
+    pub fn decompress_with_limits(self, limits: &ParseLimits) -> Result<Self> {
         match self {
-            Message::Compressed(data) => Message::from_bytes(data.decompress()?),
+            Message::Compressed(data) => decompress_message(&data, limits),
+            Message::Experimental { message, .. } => message.decompress_with_limits(limits),
             _ => Ok(self),
         }
     }
 
     /// Encrypt the message to the list of passed in public keys.
+    ///
+    /// `alg` and the keys are used as given, use [`MessageBuilder::policy`] to check them
+    /// against a [`Policy`].
+    ///
+    /// [`MessageBuilder::policy`]: crate::composed::MessageBuilder::policy
     pub fn encrypt_to_keys<R: CryptoRng + Rng>(
         &self,
         rng: &mut R,
         alg: SymmetricKeyAlgorithm,
-        pkeys: &[&impl PublicKeyTrait],
+        pkeys: &[&(impl PublicKeyTrait + Sync)],
+    ) -> Result<Self> {
+        self.encrypt_to_keys_internal(rng, alg, pkeys, false)
+    }
+
+    /// Same as [`encrypt_to_keys`](Message::encrypt_to_keys), but hides the recipients by
+    /// using the wildcard Key ID in place of theirs.
+    pub fn encrypt_to_keys_anonymous<R: CryptoRng + Rng>(
+        &self,
+        rng: &mut R,
+        alg: SymmetricKeyAlgorithm,
+        pkeys: &[&(impl PublicKeyTrait + Sync)],
+    ) -> Result<Self> {
+        self.encrypt_to_keys_internal(rng, alg, pkeys, true)
+    }
+
+    fn encrypt_to_keys_internal<R: CryptoRng + Rng>(
+        &self,
+        rng: &mut R,
+        alg: SymmetricKeyAlgorithm,
+        pkeys: &[&(impl PublicKeyTrait + Sync)],
+        anonymous: bool,
     ) -> Result<Self> {
         // 1. Generate a session key.
         let session_key = alg.new_session_key(rng);
 
         // 2. Encrypt (pub) the session key, to each PublicKey.
-        let esk = pkeys
-            .iter()
-            .map(|pkey| {
-                let pkes =
-                    PublicKeyEncryptedSessionKey::from_session_key(rng, &session_key, alg, pkey)?;
-                Ok(Esk::PublicKeyEncryptedSessionKey(pkes))
-            })
-            .collect::<Result<_>>()?;
+        let esk = encrypt_session_keys(rng, &session_key, alg, pkeys, anonymous)?;
 
         // 3. Encrypt (sym) the data using the session key.
         self.encrypt_symmetric(rng, esk, alg, session_key)
     }
 
+    /// Encrypts the message to all valid encryption subkeys of the given certificates.
+    ///
+    /// The symmetric algorithm is the strongest one preferred by all recipients, see
+    /// [`SymmetricKeyAlgorithm::negotiate`]. It and the subkeys are checked against the default
+    /// [`Policy`].
+    pub fn encrypt_to_certs<R: CryptoRng + Rng>(
+        &self,
+        rng: &mut R,
+        certs: &[&SignedPublicKey],
+    ) -> Result<Self> {
+        let (alg, _, subkeys) = select_recipients(certs, chrono::Utc::now())?;
+        let policy = Policy::default();
+        policy.check_symmetric_algorithm(alg)?;
+        for subkey in &subkeys {
+            policy.check_public_params(subkey.key.public_params())?;
+        }
+
+        self.encrypt_to_keys(rng, alg, &subkeys)
+    }
+
     /// Encrypt the message using the given password.
     pub fn encrypt_with_password<R, F>(
         &self,
@@ -297,13 +400,40 @@ impl Message {
         self.encrypt_symmetric(rng, vec![skesk], alg, session_key)
     }
 
+    /// Encrypt the message to the list of passed in public keys, and additionally using the
+    /// given password. It can then be decrypted with any of the keys, or the password.
+    pub fn encrypt_to_keys_and_password<R, F>(
+        &self,
+        rng: &mut R,
+        s2k: StringToKey,
+        alg: SymmetricKeyAlgorithm,
+        pkeys: &[&(impl PublicKeyTrait + Sync)],
+        msg_pw: F,
+    ) -> Result<Self>
+    where
+        R: Rng + CryptoRng,
+        F: FnOnce() -> String + Clone,
+    {
+        // 1. Generate a session key.
+        let session_key = alg.new_session_key(rng);
+
+        // 2. Encrypt the session key, to each PublicKey and using the password.
+        let mut esk = encrypt_session_keys(rng, &session_key, alg, pkeys, false)?;
+        esk.push(Esk::SymKeyEncryptedSessionKey(
+            SymKeyEncryptedSessionKey::encrypt(msg_pw, &session_key, s2k, alg)?,
+        ));
+
+        // 3. Encrypt (sym) the data using the session key.
+        self.encrypt_symmetric(rng, esk, alg, session_key)
+    }
+
     /// Symmetrically encrypts oneself using the provided `session_key`.
-    fn encrypt_symmetric<R: CryptoRng + Rng>(
+    pub(crate) fn encrypt_symmetric<R: CryptoRng + Rng>(
         &self,
         rng: &mut R,
         esk: Vec<Esk>,
         alg: SymmetricKeyAlgorithm,
-        session_key: Vec<u8>,
+        session_key: Zeroizing<Vec<u8>>,
     ) -> Result<Self> {
         let data = self.to_bytes()?;
 
@@ -315,6 +445,14 @@ impl Message {
     }
 
     /// Sign this message using the provided key.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key_id = %key.key_id(), ?hash_algorithm)
+        )
+    )]
     pub fn sign<F>(
         self,
         key: &impl SecretKeyTrait,
@@ -322,64 +460,85 @@ impl Message {
         hash_algorithm: HashAlgorithm,
     ) -> Result<Self>
     where
-        F: FnOnce() -> String,
+        F: PassphraseProvider,
+    {
+        let (signature_config, data, nested) = self.signature_config(key, hash_algorithm)?;
+        let signature = signature_config.sign(key, key_pw, &data[..])?;
+
+        Ok(self.into_signed(signature, key.key_id(), nested))
+    }
+
+    /// Async variant of [`sign`](Message::sign), for keys whose signing operation is
+    /// delegated, e.g. to a remote service.
+    #[cfg(feature = "async")]
+    pub async fn sign_async<F>(
+        self,
+        key: &impl AsyncSecretKeyTrait,
+        key_pw: F,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<Self>
+    where
+        F: PassphraseProvider + Send,
     {
-        let key_id = key.key_id();
-        let algorithm = key.algorithm();
-        let hashed_subpackets = vec![
-            Subpacket::regular(SubpacketData::IssuerFingerprint(
-                KeyVersion::V4,
-                SmallVec::from_slice(&key.fingerprint()),
-            )),
-            Subpacket::regular(SubpacketData::SignatureCreationTime(
-                chrono::Utc::now().trunc_subsecs(0),
-            )),
-        ];
-        let unhashed_subpackets = vec![Subpacket::regular(SubpacketData::Issuer(key_id.clone()))];
-
-        let (typ, signature) = match self {
-            Message::Literal(ref l) => {
+        let (signature_config, data, nested) = self.signature_config(key, hash_algorithm)?;
+        let signature = signature_config.sign_async(key, key_pw, &data[..]).await?;
+
+        Ok(self.into_signed(signature, key.key_id(), nested))
+    }
+
+    /// Prepares the signature over this message, returning its config, the data to sign and
+    /// whether it signs the literal data of an already one-pass signed message.
+    fn signature_config(
+        &self,
+        key: &impl KeyTrait,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<(SignatureConfig, Cow<'_, [u8]>, bool)> {
+        // Experimental packets following the message are not signed.
+        let message = self.without_experimental();
+
+        // Signing an already one-pass signed message adds another signature over the same
+        // literal data, instead of signing the nested signature.
+        let nested_literal = match message {
+            Message::Signed {
+                one_pass_signature: Some(_),
+                ..
+            } => message.get_literal(),
+            _ => None,
+        };
+
+        let (typ, data) = match (message, nested_literal) {
+            (Message::Literal(l), _) | (_, Some(l)) => {
                 let typ = if l.is_binary() {
                     SignatureType::Binary
                 } else {
                     SignatureType::Text
                 };
-
-                let signature_config = SignatureConfig::new_v4(
-                    Default::default(),
-                    typ,
-                    algorithm,
-                    hash_algorithm,
-                    hashed_subpackets,
-                    unhashed_subpackets,
-                );
-                (typ, signature_config.sign(key, key_pw, l.data())?)
+                (typ, Cow::Borrowed(l.data()))
             }
-            _ => {
-                let typ = SignatureType::Binary;
-                let signature_config = SignatureConfig::new_v4(
-                    Default::default(),
-                    typ,
-                    algorithm,
-                    hash_algorithm,
-                    hashed_subpackets,
-                    unhashed_subpackets,
-                );
+            (_, None) => (SignatureType::Binary, Cow::Owned(message.to_bytes()?)),
+        };
 
-                let data = self.to_bytes()?;
-                let cursor = io::Cursor::new(data);
-                let signature = signature_config.sign(key, key_pw, cursor)?;
+        let signature_config =
+            SubpacketAreas::for_key(key).into_config(typ, key.algorithm(), hash_algorithm);
 
-                (typ, signature)
-            }
-        };
-        let ops = OnePassSignature::from_details(typ, hash_algorithm, algorithm, key_id);
+        Ok((signature_config, data, nested_literal.is_some()))
+    }
+
+    /// Wraps this message into a one-pass signed message with `signature`.
+    fn into_signed(self, signature: Signature, key_id: KeyId, nested: bool) -> Self {
+        let config = &signature.config;
+        let mut ops =
+            OnePassSignature::from_details(config.typ, config.hash_alg, config.pub_alg, key_id);
+        if nested {
+            // the next packet is another one-pass signature over the same data
+            ops.last = 0;
+        }
 
-        Ok(Message::Signed {
+        Message::Signed {
             message: Some(Box::new(self)),
             one_pass_signature: Some(ops),
             signature,
-        })
+        }
     }
 
     /// Convert the message to a standalone signature according to the cleartext framework.
@@ -396,7 +555,17 @@ impl Message {
     ///
     /// Decompresses up to one layer of compressed data.
     pub fn verify(&self, key: &impl PublicKeyTrait) -> Result<()> {
-        self.verify_internal(key, true)
+        self.verify_with_limits(key, &ParseLimits::default())
+    }
+
+    /// Same as [`verify`](Message::verify), but rejects decompressed data and messages that
+    /// exceed `limits`.
+    pub fn verify_with_limits(
+        &self,
+        key: &impl PublicKeyTrait,
+        limits: &ParseLimits,
+    ) -> Result<()> {
+        self.verify_internal(key, true, limits)
     }
 
     /// Verifies this message.
@@ -404,13 +573,34 @@ impl Message {
     ///
     /// If `decompress` is true and the message is compressed,
     /// the message is decompressed and verified.
-    fn verify_internal(&self, key: &impl PublicKeyTrait, decompress: bool) -> Result<()> {
+    fn verify_internal(
+        &self,
+        key: &impl PublicKeyTrait,
+        decompress: bool,
+        limits: &ParseLimits,
+    ) -> Result<()> {
         match self {
             Message::Signed {
-                signature, message, ..
+                signature,
+                message,
+                one_pass_signature,
             } => {
+                // With `last == 0`, this signature covers the same data as the nested one.
+                if let (Some(ops), Some(message)) = (one_pass_signature, message) {
+                    if ops.last == 0 {
+                        if !Signature::match_identity(signature, key) {
+                            return message.verify_internal(key, decompress, limits);
+                        }
+                        let literal = message
+                            .get_literal()
+                            .ok_or_else(|| format_err!("missing literal data"))?;
+                        return signature.verify(key, literal.data());
+                    }
+                }
+
                 if let Some(message) = message {
-                    match **message {
+                    let message = message.without_experimental();
+                    match *message {
                         Message::Literal(ref data) => signature.verify(key, data.data()),
                         _ => {
                             let data = &message.to_bytes()?;
@@ -424,12 +614,15 @@ impl Message {
             }
             Message::Compressed(data) => {
                 if decompress {
-                    let msg = Message::from_bytes(data.decompress()?)?;
-                    msg.verify_internal(key, false)
+                    let msg = decompress_message(data, limits)?;
+                    msg.verify_internal(key, false, limits)
                 } else {
                     bail!("Recursive decompression not allowed");
                 }
             }
+            Message::Experimental { message, .. } => {
+                message.verify_internal(key, decompress, limits)
+            }
             // We don't know how to verify a signature for other Message types, and shouldn't return Ok
             _ => Err(Error::Unsupported(format!(
                 "Unexpected message format: {self:?}",
@@ -437,6 +630,133 @@ impl Message {
         }
     }
 
+    /// Verifies all signatures of this message against `certs`.
+    ///
+    /// Unlike [`verify`](Message::verify), this reports each signature, including the
+    /// signing key and [`PolicyWarning`]s, instead of only checking one key.
+    ///
+    /// Signatures are checked against the default [`Policy`].
+    ///
+    /// Decompresses up to one layer of compressed data.
+    pub fn verify_signatures<'a>(
+        &self,
+        certs: &[&'a SignedPublicKey],
+    ) -> Result<Vec<VerificationResult<'a>>> {
+        self.verify_signatures_with_policy(certs, &Policy::default())
+    }
+
+    /// Same as [`verify_signatures`](Message::verify_signatures), using the given `policy`.
+    pub fn verify_signatures_with_policy<'a>(
+        &self,
+        certs: &[&'a SignedPublicKey],
+        policy: &Policy,
+    ) -> Result<Vec<VerificationResult<'a>>> {
+        self.verify_signatures_at(certs, policy, chrono::Utc::now())
+    }
+
+    /// Same as [`verify_signatures`](Message::verify_signatures), and records the signers of
+    /// valid signatures in `store`, reporting changed keys as
+    /// [`TrustEvent::Conflict`](crate::tofu::TrustEvent::Conflict).
+    pub fn verify_signatures_with_trust<'a>(
+        &self,
+        certs: &[&'a SignedPublicKey],
+        store: &(impl TrustStore + ?Sized),
+    ) -> Result<Vec<VerificationResult<'a>>> {
+        let now = chrono::Utc::now();
+        let mut results = self.verify_signatures_at(certs, &Policy::default(), now)?;
+        tofu::observe_results(store, &mut results, now)?;
+
+        Ok(results)
+    }
+
+    /// Same as [`verify_signatures_with_policy`](Message::verify_signatures_with_policy), but
+    /// checks creation and expiration times against the reference time `at` instead of the
+    /// current time, e.g. to verify historical documents.
+    pub fn verify_signatures_at<'a>(
+        &self,
+        certs: &[&'a SignedPublicKey],
+        policy: &Policy,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<VerificationResult<'a>>> {
+        self.verify_signatures_with_limits(certs, policy, at, &ParseLimits::default())
+    }
+
+    /// Same as [`verify_signatures_at`](Message::verify_signatures_at), but rejects
+    /// decompressed data and messages that exceed `limits`.
+    pub fn verify_signatures_with_limits<'a>(
+        &self,
+        certs: &[&'a SignedPublicKey],
+        policy: &Policy,
+        at: chrono::DateTime<chrono::Utc>,
+        limits: &ParseLimits,
+    ) -> Result<Vec<VerificationResult<'a>>> {
+        let mut results = Vec::new();
+        self.verify_signatures_internal(certs, policy, true, &at, limits, &mut results)?;
+
+        Ok(results)
+    }
+
+    fn verify_signatures_internal<'a>(
+        &self,
+        certs: &[&'a SignedPublicKey],
+        policy: &Policy,
+        decompress: bool,
+        at: &chrono::DateTime<chrono::Utc>,
+        limits: &ParseLimits,
+        results: &mut Vec<VerificationResult<'a>>,
+    ) -> Result<()> {
+        match self {
+            Message::Signed {
+                signature,
+                message,
+                one_pass_signature,
+            } => {
+                let Some(message) = message else {
+                    unimplemented_err!("no message, what to do?");
+                };
+
+                // With `last == 0`, this signature covers the same data as the nested one.
+                let nested = matches!(one_pass_signature, Some(ops) if ops.last == 0);
+                if nested || message.is_literal() {
+                    let literal = message
+                        .get_literal()
+                        .ok_or_else(|| format_err!("missing literal data"))?;
+                    results.push(VerificationResult::new(
+                        signature,
+                        literal.data(),
+                        certs,
+                        policy,
+                        at,
+                    ));
+                } else {
+                    let data = message.without_experimental().to_bytes()?;
+                    results.push(VerificationResult::new(signature, &data, certs, policy, at));
+                }
+
+                if nested {
+                    message.verify_signatures_internal(
+                        certs, policy, decompress, at, limits, results,
+                    )?;
+                }
+
+                Ok(())
+            }
+            Message::Compressed(data) => {
+                if decompress {
+                    let msg = decompress_message(data, limits)?;
+                    msg.verify_signatures_internal(certs, policy, false, at, limits, results)
+                } else {
+                    bail!("Recursive decompression not allowed");
+                }
+            }
+            Message::Literal(_) => Ok(()),
+            Message::Encrypted { .. } => bail!("message is encrypted"),
+            Message::Experimental { message, .. } => {
+                message.verify_signatures_internal(certs, policy, decompress, at, limits, results)
+            }
+        }
+    }
+
     /// Returns a list of [KeyId]s that the message is encrypted to. For non encrypted messages this list is empty.
     pub fn get_recipients(&self) -> Vec<&KeyId> {
         match self {
@@ -447,19 +767,29 @@ impl Message {
                     _ => None,
                 })
                 .collect(),
+            Message::Experimental { message, .. } => message.get_recipients(),
             _ => Vec::new(),
         }
     }
 
     /// Decrypt the message using the given key.
     /// Returns a message decrypter, and a list of [KeyId]s that are valid recipients of this message.
+    ///
+    /// Packets for anonymous recipients are tried with every key of a matching algorithm.
+    ///
+    /// The passphrase of each matching (sub)key is requested from `key_pw` separately. If no key
+    /// can decrypt the session key, [`Error::SessionKeyDecryption`] has the error of each key.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(keys = keys.len()))
+    )]
     pub fn decrypt<'a, G>(
         &'a self,
         key_pw: G,
         keys: &[&SignedSecretKey],
     ) -> Result<(MessageDecrypter<'a>, Vec<KeyId>)>
     where
-        G: FnOnce() -> String + Clone,
+        G: PassphraseProvider + Clone,
     {
         match self {
             Message::Compressed { .. } | Message::Literal { .. } => {
@@ -470,104 +800,75 @@ impl Message {
                 None => bail!("not encrypted"),
             },
             Message::Encrypted { esk, edata, .. } => {
-                let valid_keys = keys
-                    .iter()
-                    .filter_map(|key| {
-                        // search for a packet with a key id that we have and that key.
-                        let mut packet = None;
-                        let mut encoding_key = None;
-                        let mut encoding_subkey = None;
-
-                        for esk_packet in esk.iter().filter_map(|k| match k {
-                            Esk::PublicKeyEncryptedSessionKey(k) => Some(k),
-                            _ => None,
-                        }) {
-                            debug!("esk packet: {:?}", esk_packet);
-                            debug!("{:?}", key.key_id());
-                            debug!(
-                                "{:?}",
-                                key.secret_subkeys
-                                    .iter()
-                                    .map(KeyTrait::key_id)
-                                    .collect::<Vec<_>>()
-                            );
-
-                            // find the key with the matching key id
-
-                            if &key.primary_key.key_id() == esk_packet.id() {
-                                encoding_key = Some(&key.primary_key);
-                            }
-
-                            if encoding_key.is_none() {
-                                encoding_subkey = key
-                                    .secret_subkeys
-                                    .iter()
-                                    .find(|&subkey| &subkey.key_id() == esk_packet.id());
-                            }
-
-                            if encoding_key.is_some() || encoding_subkey.is_some() {
-                                packet = Some(esk_packet);
-                                break;
-                            }
-                        }
+                let (session_key, ids) = decrypt_session_key_for_keys(esk, key_pw, keys)?;
 
-                        packet.map(|packet| (packet, encoding_key, encoding_subkey))
-                    })
-                    .collect::<Vec<_>>();
+                Ok((MessageDecrypter::from_session_key(session_key, edata), ids))
+            }
+            Message::Experimental { message, .. } => message.decrypt(key_pw, keys),
+        }
+    }
 
-                if valid_keys.is_empty() {
-                    return Err(Error::MissingKey);
+    /// Async variant of [`decrypt`](Message::decrypt), for keys whose secret operations are
+    /// delegated, e.g. to a remote service.
+    ///
+    /// Each of `keys` is a single (sub)key, which is tried on the encrypted session keys
+    /// addressed to its [KeyId], or on anonymous ones of the same algorithm.
+    #[cfg(feature = "async")]
+    pub async fn decrypt_async<'a, G, K>(
+        &'a self,
+        key_pw: G,
+        keys: &[&K],
+    ) -> Result<(MessageDecrypter<'a>, Vec<KeyId>)>
+    where
+        G: PassphraseProvider + Clone + Send,
+        K: AsyncSecretKeyTrait,
+    {
+        let mut message = self;
+        loop {
+            match message {
+                Message::Compressed { .. } | Message::Literal { .. } => {
+                    bail!("not encrypted");
                 }
+                Message::Signed { message: inner, .. } => match inner {
+                    Some(inner) => message = inner,
+                    None => bail!("not encrypted"),
+                },
+                Message::Encrypted { esk, edata, .. } => {
+                    let (session_key, ids) =
+                        decrypt_session_key_for_async_keys(esk, key_pw, keys).await?;
+
+                    return Ok((MessageDecrypter::from_session_key(session_key, edata), ids));
+                }
+                Message::Experimental { message: inner, .. } => message = inner,
+            }
+        }
+    }
 
-                let session_keys = valid_keys
-                    .iter()
-                    .map(|(packet, encoding_key, encoding_subkey)| {
-                        if let Some(ek) = encoding_key {
-                            Ok((
-                                ek.key_id(),
-                                decrypt_session_key(ek, key_pw.clone(), packet.mpis())?,
-                            ))
-                        } else if let Some(ek) = encoding_subkey {
-                            Ok((
-                                ek.key_id(),
-                                decrypt_session_key(ek, key_pw.clone(), packet.mpis())?,
-                            ))
-                        } else {
-                            unreachable!("either a key or a subkey were found");
-                        }
-                    })
-                    .filter(|res| match res {
-                        Ok(_) => true,
-                        Err(err) => {
-                            warn!("failed to decrypt session_key for key: {:?}", err);
-                            false
-                        }
-                    })
-                    .collect::<Result<Vec<_>>>()?;
-
-                ensure!(!session_keys.is_empty(), "failed to decrypt session key");
-
-                // make sure all the keys are the same, otherwise we are in a bad place
-                let (session_key, session_key_algorithm) = {
-                    let (_key_id, k0) = &session_keys[0];
-                    if !session_keys.iter().skip(1).all(|(_, k)| k0 == k) {
-                        bail!("found inconsistent session keys, possible message corruption");
-                    }
-
-                    // TODO: avoid cloning
-                    (k0.0.clone(), k0.1)
-                };
-
-                let ids = session_keys.into_iter().map(|(k, _)| k).collect();
-                ensure!(
-                    session_key_algorithm != SymmetricKeyAlgorithm::Plaintext,
-                    "session key algorithm cannot be plaintext"
-                );
-
-                Ok((
-                    MessageDecrypter::new(session_key, session_key_algorithm, edata),
-                    ids,
-                ))
+    /// Decrypt the message using keys whose secret key material is not available, e.g. keys
+    /// held by an agent or on a smartcard.
+    ///
+    /// Each of `keys` is a single (sub)key, which is tried on the encrypted session keys
+    /// addressed to its [KeyId], or on anonymous ones of the same algorithm.
+    pub fn decrypt_with_decryptors<'a, D: Decryptor>(
+        &'a self,
+        keys: &[&D],
+    ) -> Result<(MessageDecrypter<'a>, Vec<KeyId>)> {
+        let mut message = self;
+        loop {
+            match message {
+                Message::Compressed { .. } | Message::Literal { .. } => {
+                    bail!("not encrypted");
+                }
+                Message::Signed { message: inner, .. } => match inner {
+                    Some(inner) => message = inner,
+                    None => bail!("not encrypted"),
+                },
+                Message::Encrypted { esk, edata, .. } => {
+                    let (session_key, ids) = decrypt_session_key_for_decryptors(esk, keys)?;
+
+                    return Ok((MessageDecrypter::from_session_key(session_key, edata), ids));
+                }
+                Message::Experimental { message: inner, .. } => message = inner,
             }
         }
     }
@@ -587,26 +888,45 @@ impl Message {
                 None => bail!("not encrypted"),
             },
             Message::Encrypted { esk, edata, .. } => {
-                // TODO: handle multiple passwords
-                let skesk = esk.iter().find_map(|esk| match esk {
-                    Esk::SymKeyEncryptedSessionKey(k) => Some(k),
-                    _ => None,
-                });
+                let session_key = decrypt_session_key_for_password(esk, msg_pw)?;
 
-                ensure!(skesk.is_some(), "message is not password protected");
+                Ok(MessageDecrypter::from_session_key(session_key, edata))
+            }
+            Message::Experimental { message, .. } => message.decrypt_with_password(msg_pw),
+        }
+    }
 
-                let (session_key, session_key_algorithm) =
-                    decrypt_session_key_with_password(skesk.expect("checked above"), msg_pw)?;
+    /// Decrypt the message using a session key that was obtained by other means, e.g. from
+    /// `gpg --show-session-key`. The encrypted session key packets are ignored.
+    pub fn decrypt_with_session_key(
+        &self,
+        session_key: &[u8],
+        alg: SymmetricKeyAlgorithm,
+    ) -> Result<MessageDecrypter<'_>> {
+        match self {
+            Message::Compressed { .. } | Message::Literal { .. } => {
+                bail!("not encrypted");
+            }
+            Message::Signed { message, .. } => match message {
+                Some(ref message) => message.decrypt_with_session_key(session_key, alg),
+                None => bail!("not encrypted"),
+            },
+            Message::Encrypted { edata, .. } => {
                 ensure!(
-                    session_key_algorithm != SymmetricKeyAlgorithm::Plaintext,
+                    alg != SymmetricKeyAlgorithm::Plaintext,
                     "session key algorithm cannot be plaintext"
                 );
+                ensure_eq!(
+                    session_key.len(),
+                    alg.key_size(),
+                    "invalid session key length for {:?}",
+                    alg
+                );
 
-                Ok(MessageDecrypter::new(
-                    session_key,
-                    session_key_algorithm,
-                    edata,
-                ))
+                Ok(MessageDecrypter::new(session_key.to_vec(), alg, edata))
+            }
+            Message::Experimental { message, .. } => {
+                message.decrypt_with_session_key(session_key, alg)
             }
         }
     }
@@ -617,6 +937,7 @@ impl Message {
             Message::Signed {
                 one_pass_signature, ..
             } => one_pass_signature.is_some(),
+            Message::Experimental { message, .. } => message.is_one_pass_signed(),
             _ => false,
         }
     }
@@ -628,6 +949,7 @@ impl Message {
                 .as_ref()
                 .map(|msg| msg.is_literal())
                 .unwrap_or_default(),
+            Message::Experimental { message, .. } => message.is_literal(),
             _ => false,
         }
     }
@@ -636,6 +958,18 @@ impl Message {
         match self {
             Message::Literal(ref data) => Some(data),
             Message::Signed { message, .. } => message.as_ref().and_then(|msg| msg.get_literal()),
+            Message::Experimental { message, .. } => message.get_literal(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get_literal_mut(&mut self) -> Option<&mut LiteralData> {
+        match self {
+            Message::Literal(ref mut data) => Some(data),
+            Message::Signed { message, .. } => {
+                message.as_mut().and_then(|msg| msg.get_literal_mut())
+            }
+            Message::Experimental { message, .. } => message.get_literal_mut(),
             _ => None,
         }
     }
@@ -644,13 +978,23 @@ impl Message {
     ///
     /// Decompresses up to one layer of compressed data.
     pub fn get_content(&self) -> Result<Option<Vec<u8>>> {
-        self.get_content_internal(true)
+        self.get_content_with_limits(&ParseLimits::default())
+    }
+
+    /// Same as [`get_content`](Message::get_content), but rejects decompressed data and
+    /// messages that exceed `limits`.
+    pub fn get_content_with_limits(&self, limits: &ParseLimits) -> Result<Option<Vec<u8>>> {
+        self.get_content_internal(true, limits)
     }
 
     /// Returns the underlying content and `None` if the message is encrypted.
     ///
     /// If `decompress` is true, may decompress a compressed message.
-    fn get_content_internal(&self, decompress: bool) -> Result<Option<Vec<u8>>> {
+    fn get_content_internal(
+        &self,
+        decompress: bool,
+        limits: &ParseLimits,
+    ) -> Result<Option<Vec<u8>>> {
         match self {
             Message::Literal(ref data) => Ok(Some(data.data().to_vec())),
             Message::Signed { message, .. } => Ok(message
@@ -659,35 +1003,141 @@ impl Message {
                 .map(|l| l.data().to_vec())),
             Message::Compressed(data) => {
                 if decompress {
-                    let msg = Message::from_bytes(data.decompress()?)?;
-                    msg.get_content_internal(false)
+                    let msg = decompress_message(data, limits)?;
+                    msg.get_content_internal(false, limits)
                 } else {
                     bail!("Recursive decompression not allowed");
                 }
             }
             Message::Encrypted { .. } => Ok(None),
+            Message::Experimental { message, .. } => {
+                message.get_content_internal(decompress, limits)
+            }
         }
     }
 
-    pub fn to_armored_writer(
+    pub fn to_armored_writer<'a>(
         &self,
         writer: &mut impl io::Write,
-        headers: Option<&BTreeMap<String, String>>,
+        opts: impl Into<armor::ArmorOptions<'a>>,
     ) -> Result<()> {
-        armor::write(self, armor::BlockType::Message, writer, headers)
+        armor::write(self, armor::BlockType::Message, writer, opts)
     }
 
-    pub fn to_armored_bytes(&self, headers: Option<&BTreeMap<String, String>>) -> Result<Vec<u8>> {
+    pub fn to_armored_bytes<'a>(
+        &self,
+        opts: impl Into<armor::ArmorOptions<'a>>,
+    ) -> Result<Vec<u8>> {
         let mut buf = Vec::new();
 
-        self.to_armored_writer(&mut buf, headers)?;
+        self.to_armored_writer(&mut buf, opts)?;
 
         Ok(buf)
     }
 
-    pub fn to_armored_string(&self, headers: Option<&BTreeMap<String, String>>) -> Result<String> {
-        Ok(::std::str::from_utf8(&self.to_armored_bytes(headers)?)?.to_string())
+    pub fn to_armored_string<'a>(
+        &self,
+        opts: impl Into<armor::ArmorOptions<'a>>,
+    ) -> Result<String> {
+        Ok(::std::str::from_utf8(&self.to_armored_bytes(opts)?)?.to_string())
+    }
+}
+
+/// Decompresses `data` into a message, reading at most as much data as `limits` allow.
+fn decompress_message(data: &CompressedData, limits: &ParseLimits) -> Result<Message> {
+    let max = limits.max_decompressed_size();
+    let mut decompressed = Vec::new();
+    // read one byte more than allowed, to detect oversized data
+    data.decompress()?
+        .take((max as u64).saturating_add(1))
+        .read_to_end(&mut decompressed)?;
+    limits.check_decompressed_size(decompressed.len())?;
+
+    Message::from_bytes_with_limits(&decompressed[..], limits.clone())
+}
+
+/// Selects the encryption subkeys of `certs` that are valid at `at`, together with the
+/// symmetric and compression algorithms negotiated between the recipients.
+pub(crate) fn select_recipients<'a>(
+    certs: &[&'a SignedPublicKey],
+    at: chrono::DateTime<chrono::Utc>,
+) -> Result<(
+    SymmetricKeyAlgorithm,
+    CompressionAlgorithm,
+    Vec<&'a SignedPublicSubKey>,
+)> {
+    ensure!(!certs.is_empty(), "no recipients");
+
+    let mut preferences = Vec::with_capacity(certs.len());
+    let mut compression_preferences = Vec::with_capacity(certs.len());
+    let mut subkeys = Vec::new();
+    for cert in certs {
+        let valid = cert.valid_at(at)?;
+        let encryption_subkeys = valid.encryption_subkeys();
+        ensure!(
+            !encryption_subkeys.is_empty(),
+            "key {:X} has no valid encryption subkey",
+            cert.fingerprint()
+        );
+
+        preferences.push(valid.preferred_symmetric_algorithms());
+        compression_preferences.push(valid.preferred_compression_algorithms());
+        subkeys.extend(encryption_subkeys.into_iter().map(|subkey| subkey.subkey()));
+    }
+
+    Ok((
+        SymmetricKeyAlgorithm::negotiate(&preferences),
+        CompressionAlgorithm::negotiate(&compression_preferences),
+        subkeys,
+    ))
+}
+
+/// Encrypts `session_key` to each of `keys`.
+///
+/// With the `rayon` feature, the session key is encrypted to several keys in parallel. Each
+/// key then gets its own RNG, seeded from `rng`, so the result still only depends on `rng`.
+pub(crate) fn encrypt_session_keys<R: CryptoRng + Rng>(
+    rng: &mut R,
+    session_key: &[u8],
+    alg: SymmetricKeyAlgorithm,
+    keys: &[&(impl PublicKeyTrait + Sync)],
+    anonymous: bool,
+) -> Result<Vec<Esk>> {
+    #[cfg(feature = "rayon")]
+    if keys.len() > 1 {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+        use rayon::prelude::*;
+
+        let seeds: Vec<<StdRng as SeedableRng>::Seed> = keys.iter().map(|_| rng.gen()).collect();
+        return keys
+            .par_iter()
+            .zip(seeds)
+            .map(|(key, seed)| {
+                let mut rng = StdRng::from_seed(seed);
+                encrypt_session_key(&mut rng, session_key, alg, *key, anonymous)
+            })
+            .collect();
+    }
+
+    keys.iter()
+        .map(|key| encrypt_session_key(rng, session_key, alg, *key, anonymous))
+        .collect()
+}
+
+fn encrypt_session_key<R: CryptoRng + Rng>(
+    rng: &mut R,
+    session_key: &[u8],
+    alg: SymmetricKeyAlgorithm,
+    key: &impl PublicKeyTrait,
+    anonymous: bool,
+) -> Result<Esk> {
+    let mut pkes = PublicKeyEncryptedSessionKey::from_session_key(rng, session_key, alg, key)?;
+    if anonymous {
+        pkes = pkes.into_anonymous();
     }
+
+    Ok(Esk::PublicKeyEncryptedSessionKey(pkes))
 }
 
 #[cfg(test)]
@@ -702,7 +1152,9 @@ mod tests {
     use crate::composed::{Deserializable, Message, SignedSecretKey};
     use crate::crypto::hash::HashAlgorithm;
     use crate::crypto::sym::SymmetricKeyAlgorithm;
-    use crate::types::{CompressionAlgorithm, SecretKeyTrait};
+    use crate::limits::ParseLimitsBuilder;
+    use crate::test_util::{encryption_subkey, gen_key_with_subkey, key_params};
+    use crate::types::{CompressionAlgorithm, SecretKeyTrait, Version};
 
     #[test]
     fn test_compression_zlib() {
@@ -724,6 +1176,97 @@ mod tests {
         assert_eq!(&lit_msg, &uncompressed_msg);
     }
 
+    #[test]
+    #[cfg(feature = "bzip2")]
+    fn test_compression_bzip2() {
+        let lit_msg = Message::new_literal("hello-bzip2.txt", "hello world");
+
+        let compressed_msg = lit_msg.compress(CompressionAlgorithm::BZip2).unwrap();
+        let uncompressed_msg = compressed_msg.decompress().unwrap();
+
+        assert_eq!(&lit_msg, &uncompressed_msg);
+    }
+
+    #[test]
+    #[cfg(feature = "bzip2")]
+    fn test_bzip2_gnupg() {
+        // encrypted and compressed by GnuPG, with `--compress-algo bzip2`
+        let (msg, _headers) =
+            Message::from_armor_single(fs::File::open("./tests/bzip2.asc").unwrap()).unwrap();
+
+        let decrypted = msg
+            .decrypt_with_password(|| "test".into())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert!(matches!(&decrypted, Message::Compressed(data)
+            if data.compression_algorithm() == CompressionAlgorithm::BZip2));
+        assert_eq!(decrypted.get_content().unwrap().unwrap(), b"hello bzip2\n");
+
+        let bytes = msg.to_bytes().unwrap();
+        let mut reader = crate::composed::StreamDecrypter::new(&bytes[..])
+            .unwrap()
+            .decrypt_with_password(|| "test".into())
+            .unwrap();
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"hello bzip2\n");
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_compression_zstd() {
+        let lit_msg = Message::new_literal("hello-zstd.txt", &"hello world\n".repeat(100));
+
+        let compressed_msg = lit_msg.compress(CompressionAlgorithm::Zstd).unwrap();
+        let bytes = compressed_msg.to_bytes().unwrap();
+        assert_eq!(bytes[2], 100);
+        let uncompressed_msg = compressed_msg.clone().decompress().unwrap();
+        assert_eq!(&lit_msg, &uncompressed_msg);
+
+        let mut rng = thread_rng();
+        let s2k = StringToKey::new_default(&mut rng);
+        let encrypted = compressed_msg
+            .encrypt_with_password(&mut rng, s2k, SymmetricKeyAlgorithm::AES128, || {
+                "secret".into()
+            })
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        let mut reader = crate::composed::StreamDecrypter::new(&encrypted[..])
+            .unwrap()
+            .decrypt_with_password(|| "secret".into())
+            .unwrap();
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).unwrap();
+        assert_eq!(content, lit_msg.get_content().unwrap().unwrap());
+    }
+
+    #[test]
+    #[cfg(not(feature = "zstd"))]
+    fn test_compression_zstd_disabled() {
+        use crate::errors::Algorithm;
+
+        let lit_msg = Message::new_literal("hello-zstd.txt", "hello world");
+        assert!(lit_msg.compress(CompressionAlgorithm::Zstd).is_err());
+
+        let compressed_msg = Message::Compressed(CompressedData::from_compressed(
+            CompressionAlgorithm::Zstd,
+            vec![0x28, 0xB5, 0x2F, 0xFD],
+        ));
+        let err = compressed_msg.decompress().unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::UnsupportedAlgorithm {
+                    which: Algorithm::Compression(CompressionAlgorithm::Zstd)
+                }
+            ),
+            "{err}"
+        );
+    }
+
     #[test]
     fn test_compression_uncompressed() {
         let lit_msg = Message::new_literal("hello.txt", "hello world");
@@ -814,6 +1357,446 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_encrypt_to_certs() {
+        let gen_key = |id: &str, algs: &[SymmetricKeyAlgorithm], subkeys: usize| {
+            let mut params = key_params(id);
+            params.preferred_symmetric_algorithms(algs.into());
+            for _ in 0..subkeys {
+                params.subkey(encryption_subkey());
+            }
+            params.build().unwrap().generate_signed().unwrap()
+        };
+        let alice = gen_key(
+            "alice",
+            &[SymmetricKeyAlgorithm::AES128, SymmetricKeyAlgorithm::AES256],
+            1,
+        );
+        let bob = gen_key(
+            "bob",
+            &[SymmetricKeyAlgorithm::AES192, SymmetricKeyAlgorithm::AES256],
+            2,
+        );
+        let carol = gen_key("carol", &[SymmetricKeyAlgorithm::AES256], 0);
+        let alice_pub: SignedPublicKey = alice.clone().into();
+        let bob_pub: SignedPublicKey = bob.clone().into();
+        let carol_pub: SignedPublicKey = carol.into();
+
+        let (alg, _, subkeys) =
+            select_recipients(&[&alice_pub, &bob_pub], chrono::Utc::now()).unwrap();
+        assert_eq!(alg, SymmetricKeyAlgorithm::AES256);
+        assert_eq!(subkeys.len(), 3);
+
+        let msg = Message::new_literal("hello.txt", "hello world\n");
+        let encrypted = msg
+            .encrypt_to_certs(&mut thread_rng(), &[&alice_pub, &bob_pub])
+            .unwrap();
+        assert_eq!(encrypted.get_recipients().len(), 3);
+        for key in [&alice, &bob] {
+            let decrypted = encrypted
+                .decrypt(String::new, &[key])
+                .unwrap()
+                .0
+                .next()
+                .unwrap()
+                .unwrap();
+            assert_eq!(decrypted, msg);
+        }
+
+        // every recipient needs an encryption subkey
+        assert!(msg
+            .encrypt_to_certs(&mut thread_rng(), &[&alice_pub, &carol_pub])
+            .is_err());
+        assert!(msg.encrypt_to_certs(&mut thread_rng(), &[]).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_passphrase_per_key() {
+        use crate::composed::{KeyType, SubkeyParamsBuilder};
+        use crate::types::PassphraseRequest;
+
+        /// Uses the User ID as passphrase, and cancels for keys without one.
+        #[derive(Clone)]
+        struct UserIdPassphrase;
+
+        impl PassphraseProvider for UserIdPassphrase {
+            fn passphrase(&mut self, request: &PassphraseRequest) -> Result<Zeroizing<String>> {
+                let user_id = request.user_id().ok_or(Error::Cancelled)?;
+                Ok(Zeroizing::new(user_id.to_string()))
+            }
+        }
+
+        let gen_key = |id: &str| {
+            key_params(id)
+                .passphrase(Some(id.into()))
+                .subkey(
+                    SubkeyParamsBuilder::default()
+                        .key_type(KeyType::ECDH)
+                        .can_encrypt(true)
+                        .passphrase(Some(id.into()))
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap()
+                .generate_signed()
+                .unwrap()
+        };
+        let alice = gen_key("alice");
+        let bob = gen_key("bob");
+        let alice_pub: SignedPublicKey = alice.clone().into();
+        let bob_pub: SignedPublicKey = bob.clone().into();
+        let alice_id = alice.secret_subkeys[0].key_id();
+        let bob_id = bob.secret_subkeys[0].key_id();
+
+        let msg = Message::new_literal("hello.txt", "hello world\n");
+        let encrypted = msg
+            .encrypt_to_certs(&mut thread_rng(), &[&alice_pub, &bob_pub])
+            .unwrap();
+
+        // the passphrase of each subkey is requested with the User ID of its certificate
+        let (_, ids) = encrypted
+            .decrypt(UserIdPassphrase, &[&alice, &bob])
+            .unwrap();
+        assert_eq!(ids, vec![alice_id.clone(), bob_id.clone()]);
+
+        // a key with the wrong passphrase does not stop the others
+        let (mut decrypter, ids) = encrypted
+            .decrypt(|| "bob".to_string(), &[&alice, &bob])
+            .unwrap();
+        assert_eq!(ids, vec![bob_id.clone()]);
+        assert_eq!(decrypter.next().unwrap().unwrap(), msg);
+
+        match encrypted.decrypt(|| "eve".to_string(), &[&alice, &bob]) {
+            Err(Error::SessionKeyDecryption { failures }) => {
+                assert_eq!(failures.len(), 2);
+                assert_eq!(failures[0].0, alice_id);
+                assert_eq!(failures[1].0, bob_id);
+                assert!(failures
+                    .iter()
+                    .all(|(_, err)| matches!(err, Error::BadPassphrase)));
+            }
+            Err(err) => panic!("unexpected error {err:?}"),
+            Ok(_) => panic!("decrypted with the wrong passphrase"),
+        }
+    }
+
+    #[test]
+    fn test_anonymous_encryption() {
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        // subkey[0] is the encryption key
+        let pkey = skey.secret_subkeys[0].public_key();
+
+        let msg = Message::new_literal("hello.txt", "hello world\n");
+        let encrypted = msg
+            .encrypt_to_keys_anonymous(
+                &mut thread_rng(),
+                SymmetricKeyAlgorithm::AES128,
+                &[&pkey][..],
+            )
+            .unwrap();
+        assert_eq!(encrypted.get_recipients(), vec![&KeyId::WILDCARD]);
+
+        let parsed = Message::from_bytes(&encrypted.to_bytes().unwrap()[..]).unwrap();
+        let (mut decrypter, ids) = parsed.decrypt(String::new, &[&skey]).unwrap();
+        assert_eq!(ids, vec![pkey.key_id()]);
+        assert_eq!(decrypter.next().unwrap().unwrap(), msg);
+
+        // keys of the same algorithm, that are not the recipient, fail
+        let other = gen_key_with_subkey("other");
+        assert_eq!(other.secret_subkeys[0].algorithm(), pkey.algorithm());
+        assert!(parsed.decrypt(String::new, &[&other]).is_err());
+    }
+
+    #[test]
+    fn test_decrypter_read() {
+        let mut rng = thread_rng();
+        let s2k = StringToKey::new_default(&mut rng);
+        let text = "hello world\n".repeat(100);
+        let msg = Message::new_literal("hello.txt", &text)
+            .compress(CompressionAlgorithm::ZIP)
+            .unwrap();
+        let encrypted = msg
+            .encrypt_with_password(&mut rng, s2k, SymmetricKeyAlgorithm::AES128, || {
+                "secret".into()
+            })
+            .unwrap();
+
+        let mut decrypter = encrypted.decrypt_with_password(|| "secret".into()).unwrap();
+        let mut content = String::new();
+        io::Read::read_to_string(&mut decrypter, &mut content).unwrap();
+        assert_eq!(content, text.replace('\n', "\r\n"));
+    }
+
+    #[test]
+    fn test_decrypter_read_tampered() {
+        use crate::errors::IntegrityError;
+        use crate::packet::SymEncryptedProtectedData;
+
+        let mut rng = thread_rng();
+        let alg = SymmetricKeyAlgorithm::AES128;
+        let key = alg.new_session_key(&mut rng);
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let plaintext = Message::new_literal_bytes("data.bin", &data)
+            .to_bytes()
+            .unwrap();
+        let encrypted = alg
+            .encrypt_protected_with_rng(&mut rng, &key, &plaintext)
+            .unwrap();
+        let read = |encrypted: &[u8]| {
+            let packet = SymEncryptedProtectedData::from_slice(
+                Default::default(),
+                &[&[1], encrypted].concat(),
+            )
+            .unwrap();
+            let edata = [Edata::SymEncryptedProtectedData(packet)];
+            let mut content = Vec::new();
+            MessageDecrypter::new(key.to_vec(), alg, &edata)
+                .read_to_end(&mut content)
+                .map(|_| content)
+        };
+
+        assert_eq!(read(&encrypted).unwrap(), data);
+
+        // the modified data is read before the MDC is checked, at the end
+        let mut modified = encrypted.clone();
+        let len = modified.len();
+        modified[len - 30] ^= 1;
+        let err = read(&modified).unwrap_err();
+        assert!(matches!(
+            err.into_inner()
+                .unwrap()
+                .downcast::<Error>()
+                .map(|err| *err),
+            Ok(Error::IntegrityCheckFailed(IntegrityError::MdcMismatch))
+        ));
+    }
+
+    #[test]
+    fn test_decompress_limits() {
+        // highly compressible data, like a decompression bomb
+        let text = "a".repeat(100_000);
+        let msg = Message::new_literal("hello.txt", &text)
+            .compress(CompressionAlgorithm::ZLIB)
+            .unwrap();
+
+        let limits = ParseLimitsBuilder::default()
+            .max_decompressed_size(10_000)
+            .build()
+            .unwrap();
+        assert!(msg.clone().decompress_with_limits(&limits).is_err());
+        assert!(msg.decompress().is_ok());
+
+        let mut rng = thread_rng();
+        let s2k = StringToKey::new_default(&mut rng);
+        let text = "a".repeat(100_000);
+        let encrypted = Message::new_literal("hello.txt", &text)
+            .compress(CompressionAlgorithm::ZIP)
+            .unwrap()
+            .encrypt_with_password(&mut rng, s2k, SymmetricKeyAlgorithm::AES128, || {
+                "secret".into()
+            })
+            .unwrap();
+
+        let mut decrypter = encrypted
+            .decrypt_with_password(|| "secret".into())
+            .unwrap()
+            .with_limits(limits);
+        let mut content = Vec::new();
+        assert!(decrypter.read_to_end(&mut content).is_err());
+
+        // the compressed packet itself is too large
+        let limits = ParseLimitsBuilder::default()
+            .max_packet_size(64)
+            .build()
+            .unwrap();
+        let mut decrypter = encrypted
+            .decrypt_with_password(|| "secret".into())
+            .unwrap()
+            .with_limits(limits);
+        assert!(decrypter.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_verify_limits() {
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let pkey: SignedPublicKey = skey.clone().into();
+
+        let text = "a".repeat(100_000);
+        let msg = Message::new_literal("hello.txt", &text)
+            .sign(&skey, || "".into(), HashAlgorithm::SHA2_256)
+            .unwrap()
+            .compress(CompressionAlgorithm::ZLIB)
+            .unwrap();
+
+        let limits = ParseLimitsBuilder::default()
+            .max_decompressed_size(10_000)
+            .build()
+            .unwrap();
+        assert!(msg.verify_with_limits(&pkey, &limits).is_err());
+        assert!(msg.get_content_with_limits(&limits).is_err());
+        assert!(msg
+            .verify_signatures_with_limits(
+                &[&pkey],
+                &Policy::default(),
+                chrono::Utc::now(),
+                &limits
+            )
+            .is_err());
+
+        msg.verify(&pkey).unwrap();
+        assert_eq!(msg.get_content().unwrap().unwrap(), text.as_bytes());
+        assert!(msg.verify_signatures(&[&pkey]).unwrap()[0].is_valid());
+    }
+
+    #[test]
+    fn test_nesting_limits() {
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+
+        let mut msg = Message::new_literal("hello.txt", "hello world\n");
+        for _ in 0..3 {
+            msg = msg
+                .sign(&skey, || "".into(), HashAlgorithm::SHA2_256)
+                .unwrap();
+        }
+        let bytes = msg.to_bytes().unwrap();
+
+        let limits = ParseLimitsBuilder::default()
+            .max_nesting_depth(3)
+            .build()
+            .unwrap();
+        let parsed = Message::from_bytes_with_limits(&bytes[..], limits).unwrap();
+        assert_eq!(parsed, msg);
+
+        let limits = ParseLimitsBuilder::default()
+            .max_nesting_depth(2)
+            .build()
+            .unwrap();
+        assert!(Message::from_bytes_with_limits(&bytes[..], limits).is_err());
+    }
+
+    #[test]
+    fn test_session_key_decryption() {
+        let mut rng = thread_rng();
+        let alg = SymmetricKeyAlgorithm::AES256;
+        let session_key = alg.new_session_key(&mut rng);
+
+        let msg = Message::new_literal("hello.txt", "hello world\n");
+        let encrypted = msg
+            .encrypt_symmetric(&mut rng, Vec::new(), alg, session_key.clone())
+            .unwrap();
+
+        let decrypted = encrypted
+            .decrypt_with_session_key(&session_key, alg)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(decrypted, msg);
+
+        // the session key of a regular decryption can be used the same way
+        let s2k = StringToKey::new_default(&mut rng);
+        let encrypted = msg
+            .encrypt_with_password(&mut rng, s2k, alg, || "secret".into())
+            .unwrap();
+        let decrypter = encrypted.decrypt_with_password(|| "secret".into()).unwrap();
+        let exported = decrypter.session_key();
+        assert_eq!(exported.algorithm(), alg);
+        assert_eq!(format!("{:?}", exported), "SessionKey(AES256)");
+        let decrypted = encrypted
+            .decrypt_with_session_key(exported.key(), exported.algorithm())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(decrypted, msg);
+
+        let mut wrong_key = session_key.clone();
+        wrong_key[0] ^= 1;
+        assert!(encrypted
+            .decrypt_with_session_key(&wrong_key, alg)
+            .unwrap()
+            .next()
+            .unwrap()
+            .is_err());
+        assert!(encrypted
+            .decrypt_with_session_key(&session_key, SymmetricKeyAlgorithm::AES128)
+            .is_err());
+        assert!(msg.decrypt_with_session_key(&session_key, alg).is_err());
+    }
+
+    #[test]
+    fn test_mdc_errors() {
+        use crate::errors::IntegrityError;
+        use crate::packet::{SymEncryptedData, SymEncryptedProtectedData};
+        use crate::policy::PolicyBuilder;
+
+        let mut rng = thread_rng();
+        let alg = SymmetricKeyAlgorithm::AES128;
+        let key = alg.new_session_key(&mut rng);
+        let msg = Message::new_literal("hello.txt", "hello world\n");
+        let plaintext = msg.to_bytes().unwrap();
+
+        let decrypt = |edata: Edata, policy: Policy| {
+            let edata = [edata];
+            MessageDecrypter::new(key.to_vec(), alg, &edata)
+                .with_policy(policy)
+                .next()
+                .unwrap()
+        };
+        let protected = |data: &[u8]| {
+            let packet =
+                SymEncryptedProtectedData::from_slice(Default::default(), &[&[1], data].concat());
+            Edata::SymEncryptedProtectedData(packet.unwrap())
+        };
+        let integrity_error = |res: Result<Message>| match res {
+            Err(Error::IntegrityCheckFailed(err)) => err,
+            res => panic!("unexpected result {res:?}"),
+        };
+
+        let data = alg
+            .encrypt_protected_with_rng(&mut rng, &key, &plaintext)
+            .unwrap();
+        assert_eq!(decrypt(protected(&data), Policy::default()).unwrap(), msg);
+
+        let mut modified = data.clone();
+        modified[alg.block_size() + 4] ^= 1;
+        assert_eq!(
+            integrity_error(decrypt(protected(&modified), Policy::default())),
+            IntegrityError::MdcMismatch
+        );
+
+        let truncated = &data[..alg.block_size() + 10];
+        assert_eq!(
+            integrity_error(decrypt(protected(truncated), Policy::default())),
+            IntegrityError::Truncated
+        );
+
+        // data without MDC is only decrypted if the policy allows it
+        let unprotected = Edata::SymEncryptedData(
+            SymEncryptedData::from_slice(Default::default(), &[0; 64]).unwrap(),
+        );
+        assert_eq!(
+            integrity_error(decrypt(unprotected.clone(), Policy::default())),
+            IntegrityError::MdcMissing
+        );
+        let policy = PolicyBuilder::default().require_mdc(false).build().unwrap();
+        // CFB resynchronization is not implemented yet
+        assert!(matches!(
+            decrypt(unprotected, policy),
+            Err(Error::Unimplemented(_))
+        ));
+    }
+
     #[test]
     fn test_password_encryption() {
         let _ = pretty_env_logger::try_init();
@@ -846,6 +1829,48 @@ mod tests {
         assert_eq!(compressed_msg, decrypted);
     }
 
+    #[test]
+    fn test_password_and_key_encryption() {
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        // subkey[0] is the encryption key
+        let pkey = skey.secret_subkeys[0].public_key();
+        let mut rng = thread_rng();
+        let s2k = StringToKey::new_default(&mut rng);
+
+        let msg = Message::new_literal("hello.txt", "hello world\n");
+        let encrypted = msg
+            .encrypt_to_keys_and_password(
+                &mut rng,
+                s2k,
+                SymmetricKeyAlgorithm::AES128,
+                &[&pkey][..],
+                || "secret".into(),
+            )
+            .unwrap();
+        let armored = encrypted.to_armored_bytes(None).unwrap();
+        let parsed = Message::from_armor_single(Cursor::new(&armored)).unwrap().0;
+
+        let decrypted = parsed
+            .decrypt_with_password(|| "secret".into())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(decrypted, msg);
+
+        let (mut decrypter, ids) = parsed.decrypt(String::new, &[&skey]).unwrap();
+        assert_eq!(ids, vec![pkey.key_id()]);
+        assert_eq!(decrypter.next().unwrap().unwrap(), msg);
+
+        assert!(parsed
+            .decrypt_with_password(|| "wrong".into())
+            .and_then(|mut d| d.next().unwrap())
+            .is_err());
+    }
+
     #[test]
     fn test_no_plaintext_decryption() {
         // Invalid message "encrypted" with plaintext algorithm.
@@ -1084,6 +2109,178 @@ mod tests {
         signed_msg.verify(&verify).expect("signature seems bad");
     }
 
+    /// Messages encrypted with the same seed are the same, for both the session key and its
+    /// encryption to the key or password.
+    #[test]
+    fn test_encrypt_golden() {
+        use rand::SeedableRng;
+        use rand_chacha::ChaCha8Rng;
+        use sha2::{Digest, Sha256};
+
+        use crate::composed::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+        use crate::packet::LiteralData;
+        use crate::types::{StringToKey, Version};
+
+        let created_at = chrono::TimeZone::timestamp_opt(&chrono::Utc, 1_700_000_000, 0).unwrap();
+        let rng = &mut ChaCha8Rng::seed_from_u64(0);
+        let key = SecretKeyParamsBuilder::default()
+            .key_type(KeyType::EdDSA)
+            .can_sign(true)
+            .primary_user_id("Me <me@mail.com>".into())
+            .created_at(created_at)
+            .subkey(
+                SubkeyParamsBuilder::default()
+                    .key_type(KeyType::ECDH)
+                    .can_encrypt(true)
+                    .created_at(created_at)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+            .generate_signed_with_rng(rng)
+            .unwrap();
+        let pkey = key.secret_subkeys[0].public_key();
+
+        // binary, no file name, fixed date
+        let literal = LiteralData::from_slice(
+            Version::New,
+            &[
+                b'b', 0, 0x65, 0x53, 0xf1, 0x00, b'h', b'e', b'l', b'l', b'o',
+            ],
+        )
+        .unwrap();
+        let msg = Message::Literal(literal);
+
+        let encrypt = |seed| {
+            let rng = &mut ChaCha8Rng::seed_from_u64(seed);
+            let to_key = msg
+                .encrypt_to_keys(rng, SymmetricKeyAlgorithm::AES128, &[&pkey])
+                .unwrap();
+            let s2k = StringToKey::new_default(rng);
+            let to_password = msg
+                .encrypt_with_password(rng, s2k, SymmetricKeyAlgorithm::AES128, || "secret".into())
+                .unwrap();
+            [to_key, to_password].map(|msg| hex::encode(Sha256::digest(msg.to_bytes().unwrap())))
+        };
+
+        assert_eq!(
+            encrypt(0),
+            [
+                "b1d9adef2c6dfbecdb2f0aadd393bd3917345eb12524be8c0bddeeff96a853f9",
+                "f931f0de8a7b9a7ba3f9d3579e0cb774ba4e86667cb0e04ef27873d292ecdfb2",
+            ]
+        );
+        assert_ne!(encrypt(0), encrypt(1));
+
+        let decrypted = Message::from_bytes(
+            &msg.encrypt_to_keys(rng, SymmetricKeyAlgorithm::AES128, &[&pkey])
+                .unwrap()
+                .to_bytes()
+                .unwrap()[..],
+        )
+        .unwrap()
+        .decrypt(String::new, &[&key])
+        .unwrap()
+        .0
+        .next()
+        .unwrap()
+        .unwrap();
+        assert_eq!(decrypted, msg);
+    }
+
+    #[test]
+    fn test_encrypt_to_many_keys() {
+        use rand::SeedableRng;
+        use rand_chacha::ChaCha8Rng;
+
+        let rng = &mut ChaCha8Rng::seed_from_u64(0);
+        let keys: Vec<_> = (0..4)
+            .map(|i| {
+                key_params(&format!("Me {i} <me{i}@mail.com>"))
+                    .subkey(encryption_subkey())
+                    .build()
+                    .unwrap()
+                    .generate_signed_with_rng(rng)
+                    .unwrap()
+            })
+            .collect();
+        let pkeys: Vec<_> = keys.iter().map(|k| &k.secret_subkeys[0]).collect();
+
+        let msg = Message::new_literal("hello.txt", "hello");
+        let encrypt = |seed| {
+            let rng = &mut ChaCha8Rng::seed_from_u64(seed);
+            msg.encrypt_to_keys(rng, SymmetricKeyAlgorithm::AES128, &pkeys)
+                .unwrap()
+                .to_bytes()
+                .unwrap()
+        };
+
+        let encrypted = encrypt(0);
+        assert_eq!(encrypted, encrypt(0));
+        assert_ne!(encrypted, encrypt(1));
+
+        let encrypted = Message::from_bytes(&encrypted[..]).unwrap();
+        let Message::Encrypted { esk, .. } = &encrypted else {
+            panic!("not encrypted");
+        };
+        assert_eq!(esk.len(), keys.len());
+        for key in &keys {
+            let (mut decrypter, ids) = encrypted.decrypt(String::new, &[key]).unwrap();
+            assert_eq!(ids, vec![key.secret_subkeys[0].key_id()]);
+            assert_eq!(decrypter.next().unwrap().unwrap(), msg);
+        }
+    }
+
+    #[test]
+    fn test_experimental_round_trip() {
+        let (skey, _headers) = SignedSecretKey::from_armor_single(
+            fs::File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let pkey = skey.public_key();
+
+        let experimental =
+            |tag, body: &[u8]| Experimental::from_slice(Version::New, tag, body).unwrap();
+        let Message::Signed {
+            message,
+            one_pass_signature,
+            signature,
+        } = Message::new_literal("hello.txt", "hello world\n")
+            .sign(&skey, || "".into(), HashAlgorithm::SHA2_256)
+            .unwrap()
+        else {
+            panic!("not signed");
+        };
+
+        // experimental packets after the literal data and after the signature
+        let msg = Message::Experimental {
+            message: Box::new(Message::Signed {
+                message: Some(Box::new(Message::Experimental {
+                    message: message.unwrap(),
+                    packets: vec![experimental(Tag::Private60, &[1, 2, 3])],
+                })),
+                one_pass_signature,
+                signature,
+            }),
+            packets: vec![
+                experimental(Tag::Private61, &[4]),
+                experimental(Tag::Private63, &[]),
+            ],
+        };
+        let bytes = msg.to_bytes().unwrap();
+
+        let parsed = Message::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(parsed, msg);
+        assert_eq!(parsed.to_bytes().unwrap(), bytes);
+
+        parsed.verify(&pkey).unwrap();
+        assert_eq!(
+            parsed.get_content().unwrap(),
+            Some(b"hello world\r\n".to_vec())
+        );
+    }
+
     /// Tests that decompressing compression quine does not result in stack overflow.
     /// quine.out comes from <https://mumble.net/~campbell/misc/pgp-quine/>
     /// See <https://mumble.net/~campbell/2013/10/08/compression> for details.
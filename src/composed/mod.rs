@@ -2,11 +2,17 @@ pub mod key;
 pub mod message;
 pub mod signed_key;
 
+mod cleartext;
+mod keyring;
 mod shared;
 mod signature;
+mod verification;
 
+pub use self::cleartext::*;
 pub use self::key::*;
+pub use self::keyring::*;
 pub use self::message::*;
 pub use self::shared::Deserializable;
 pub use self::signature::*;
 pub use self::signed_key::*;
+pub use self::verification::*;
@@ -1,12 +1,16 @@
 use std::collections::BTreeMap;
-use std::io::{Cursor, Read, Seek};
+use std::io::{self, Cursor, Read, Seek};
 
 use crate::armor::{self, BlockType};
 use crate::errors::{Error, Result};
-use crate::packet::{Packet, PacketParser};
+use crate::packet::{Packet, PacketParser, ParseWarning};
 
 pub trait Deserializable: Sized {
     /// Parse a single byte encoded composition.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(kind = std::any::type_name::<Self>()))
+    )]
     fn from_bytes(bytes: impl Read) -> Result<Self> {
         let mut el = Self::from_bytes_many(bytes);
         el.next().ok_or(Error::NoMatchingPacket)?
@@ -43,12 +47,8 @@ pub trait Deserializable: Sized {
         Box<dyn Iterator<Item = Result<Self>> + 'a>,
         BTreeMap<String, String>,
     )> {
-        let mut dearmor = armor::Dearmor::new(input);
-        dearmor.read_header()?;
-        // Safe to unwrap, as read_header succeeded.
-        let typ = dearmor
-            .typ
-            .ok_or_else(|| format_err!("dearmor failed to retrieve armor type"))?;
+        let reader = armor::ArmorReader::new(io::BufReader::new(input))?;
+        let typ = reader.typ();
 
         // TODO: add typ information to the key possibly?
         match typ {
@@ -59,10 +59,10 @@ pub trait Deserializable: Sized {
             | BlockType::MultiPartMessage(_, _)
             | BlockType::Signature
             | BlockType::File => {
-                let headers = dearmor.headers.clone(); // FIXME: avoid clone
+                let headers = reader.headers().clone(); // FIXME: avoid clone
 
                 // TODO: check that the result is what it actually said.
-                Ok((Self::from_bytes_many(dearmor), headers))
+                Ok((Self::from_bytes_many(reader), headers))
             }
             BlockType::PublicKeyPKCS1(_)
             | BlockType::PublicKeyPKCS8
@@ -82,6 +82,18 @@ pub trait Deserializable: Sized {
         Self::from_packets(packets.peekable())
     }
 
+    /// Parse a list of compositions in raw byte format, skipping packets that can not be
+    /// parsed, e.g. when importing keyserver dumps.
+    ///
+    /// Returns the compositions, and a warning for every packet that was skipped.
+    fn from_bytes_many_recovering(bytes: impl Read) -> (Vec<Result<Self>>, Vec<ParseWarning>) {
+        let mut parser = PacketParser::new(bytes).recover(true);
+        let packets = parser.by_ref().filter_map(filter_parsed_packet_results);
+        let compositions = Self::from_packets(packets.peekable()).collect();
+
+        (compositions, parser.take_warnings())
+    }
+
     /// Turn a list of packets into a usable representation.
     fn from_packets<'a, I: Iterator<Item = Result<Packet>> + 'a>(
         packets: std::iter::Peekable<I>,
@@ -90,21 +102,29 @@ pub trait Deserializable: Sized {
 
 /// Process results from low level packet parser:
 ///
-/// - Skip Marker packets.
+/// - Skip Marker packets, and the Trust packets GnuPG adds to keyrings and exports.
 /// - Pass through other packets.
 /// - Skip any `Error::Unsupported`, those were marked as "safe to ignore" by the low level parser.
-/// - Skip `Error::Incomplete`
+/// - Skip `Error::Truncated`
 /// - Skip `Error::EllipticCurve`
-/// - Pass through other errors.
+/// - Pass through other errors, keeping their position in the input.
 pub(crate) fn filter_parsed_packet_results(p: Result<Packet>) -> Option<Result<Packet>> {
     match &p {
-        Ok(Packet::Marker(_m)) => {
-            debug!("skipping marker packet");
+        Ok(Packet::Marker(_)) => {
+            // see https://tools.ietf.org/html/rfc4880#section-5.8
+            warn!("skipping marker packet");
+            None
+        }
+        Ok(Packet::Trust(_)) => {
+            // local to the implementation that wrote them,
+            // see https://tools.ietf.org/html/rfc4880#section-5.10
+            warn!("skipping trust packet");
             None
         }
         Ok(_) => Some(p),
         Err(e) => {
-            if let Error::InvalidPacketContent(b) = &e {
+            let e = e.without_context();
+            if let Error::InvalidPacketContent(b) = e {
                 let err: &Error = b; // unbox
                 if let Error::Unsupported(e) = err {
                     // "Error::Unsupported" signals parser errors that we can safely ignore
@@ -121,16 +141,25 @@ pub(crate) fn filter_parsed_packet_results(p: Result<Packet>) -> Option<Result<P
                     return None;
                 }
             }
-            if let Error::Incomplete(_i) = e {
+            if let Error::Truncated { .. } = e {
                 // We ignore incomplete packets for now (some of these occur in the SKS dumps under `tests`)
                 warn!("skipping incomplete packet: {p:?}");
                 return None;
             }
 
             // Pass through all other errors from the low level parser, they should be surfaced
-            Some(Err(Error::Message(format!(
-                "unexpected packet data: {e:?}"
-            ))))
+            let err = Error::Message(format!("unexpected packet data: {e:?}"));
+            match p {
+                Err(Error::InPacket {
+                    offset, index, tag, ..
+                }) => Some(Err(Error::InPacket {
+                    offset,
+                    index,
+                    tag,
+                    source: Box::new(err),
+                })),
+                _ => Some(Err(err)),
+            }
         }
     }
 }
@@ -1,14 +1,18 @@
-use std::collections::BTreeMap;
 use std::convert::TryInto;
+use std::io::{BufReader, Read};
 use std::iter::Peekable;
 
 use crate::armor;
 use crate::composed::Deserializable;
+use crate::crypto::hash::HashAlgorithm;
 use crate::errors::Result;
-use crate::packet::{Packet, Signature};
+use crate::packet::{
+    Notation, Packet, Signature, SignatureConfig, SignatureType, Subpacket, SubpacketAreas,
+    SubpacketData,
+};
 use crate::ser::Serialize;
-use crate::types::PublicKeyTrait;
 use crate::types::Tag;
+use crate::types::{PassphraseProvider, PublicKeyTrait, SecretKeyTrait};
 
 /// Standalone signature as defined by the cleartext framework.
 #[derive(Debug, Clone)]
@@ -21,30 +25,191 @@ impl StandaloneSignature {
         StandaloneSignature { signature }
     }
 
-    pub fn to_armored_writer(
+    pub fn to_armored_writer<'a>(
         &self,
         writer: &mut impl std::io::Write,
-        headers: Option<&BTreeMap<String, String>>,
+        opts: impl Into<armor::ArmorOptions<'a>>,
     ) -> Result<()> {
-        armor::write(self, armor::BlockType::Signature, writer, headers)
+        armor::write(self, armor::BlockType::Signature, writer, opts)
     }
 
-    pub fn to_armored_bytes(&self, headers: Option<&BTreeMap<String, String>>) -> Result<Vec<u8>> {
+    pub fn to_armored_bytes<'a>(
+        &self,
+        opts: impl Into<armor::ArmorOptions<'a>>,
+    ) -> Result<Vec<u8>> {
         let mut buf = Vec::new();
 
-        self.to_armored_writer(&mut buf, headers)?;
+        self.to_armored_writer(&mut buf, opts)?;
 
         Ok(buf)
     }
 
-    pub fn to_armored_string(&self, headers: Option<&BTreeMap<String, String>>) -> Result<String> {
-        Ok(::std::str::from_utf8(&self.to_armored_bytes(headers)?)?.to_string())
+    pub fn to_armored_string<'a>(
+        &self,
+        opts: impl Into<armor::ArmorOptions<'a>>,
+    ) -> Result<String> {
+        Ok(::std::str::from_utf8(&self.to_armored_bytes(opts)?)?.to_string())
     }
 
     /// Verify this signature.
     pub fn verify(&self, key: &impl PublicKeyTrait, content: &[u8]) -> Result<()> {
         self.signature.verify(key, content)
     }
+
+    /// Verifies a standalone or timestamp signature, which does not sign any data.
+    pub fn verify_standalone(&self, key: &impl PublicKeyTrait) -> Result<()> {
+        self.signature.verify_standalone(key)
+    }
+
+    /// Verifies a timestamp signature over `target`.
+    pub fn verify_timestamp(&self, key: &impl PublicKeyTrait, target: &Signature) -> Result<()> {
+        ensure_eq!(
+            self.signature.typ(),
+            SignatureType::Timestamp,
+            "not a timestamp signature"
+        );
+        let (pub_alg, hash_alg, hash) = self
+            .signature
+            .signature_target()
+            .ok_or_else(|| format_err!("missing signature target"))?;
+        ensure_eq!(pub_alg, target.config.pub_alg, "signature target mismatch");
+        ensure_eq!(
+            hash,
+            &target_hash(target, hash_alg)?[..],
+            "signature target mismatch"
+        );
+
+        self.signature.verify_standalone(key)
+    }
+}
+
+/// A signature, that is stored separately from the data it signs.
+pub type DetachedSignature = StandaloneSignature;
+
+/// Creates a detached signature over the data read from `data`.
+///
+/// The data is hashed while it is read, so it is never held in memory as a whole.
+pub fn sign_detached<R, F>(
+    data: R,
+    key: &impl SecretKeyTrait,
+    key_pw: F,
+    hash_algorithm: HashAlgorithm,
+) -> Result<DetachedSignature>
+where
+    R: Read,
+    F: PassphraseProvider,
+{
+    sign_detached_typ(data, key, key_pw, hash_algorithm, SignatureType::Binary)
+}
+
+/// Same as [`sign_detached`], but creates a text signature.
+///
+/// Line endings are canonicalized before hashing, so the signature stays valid when the
+/// line endings of the document are converted.
+pub fn sign_detached_text<R, F>(
+    data: R,
+    key: &impl SecretKeyTrait,
+    key_pw: F,
+    hash_algorithm: HashAlgorithm,
+) -> Result<DetachedSignature>
+where
+    R: Read,
+    F: PassphraseProvider,
+{
+    sign_detached_typ(data, key, key_pw, hash_algorithm, SignatureType::Text)
+}
+
+fn sign_detached_typ<R, F>(
+    data: R,
+    key: &impl SecretKeyTrait,
+    key_pw: F,
+    hash_algorithm: HashAlgorithm,
+    typ: SignatureType,
+) -> Result<DetachedSignature>
+where
+    R: Read,
+    F: PassphraseProvider,
+{
+    let config = signature_config(key, hash_algorithm, typ, Vec::new());
+    let signature = config.sign(key, key_pw, BufReader::new(data))?;
+
+    Ok(StandaloneSignature::new(signature))
+}
+
+/// Creates a standalone signature, which only signs its own subpackets, such as `notations`.
+pub fn sign_standalone<F>(
+    key: &impl SecretKeyTrait,
+    key_pw: F,
+    hash_algorithm: HashAlgorithm,
+    notations: Vec<Notation>,
+) -> Result<StandaloneSignature>
+where
+    F: PassphraseProvider,
+{
+    let subpackets = notations.into_iter().map(Subpacket::from).collect();
+    let config = signature_config(key, hash_algorithm, SignatureType::Standalone, subpackets);
+
+    Ok(StandaloneSignature::new(
+        config.sign_standalone(key, key_pw)?,
+    ))
+}
+
+/// Creates a timestamp signature, attesting that `target` existed at the time of signing.
+///
+/// The target is referenced by its hash, in a Signature Target subpacket.
+pub fn sign_timestamp<F>(
+    target: &Signature,
+    key: &impl SecretKeyTrait,
+    key_pw: F,
+    hash_algorithm: HashAlgorithm,
+    notations: Vec<Notation>,
+) -> Result<StandaloneSignature>
+where
+    F: PassphraseProvider,
+{
+    let mut subpackets = vec![Subpacket::regular(SubpacketData::SignatureTarget(
+        target.config.pub_alg,
+        hash_algorithm,
+        target_hash(target, hash_algorithm)?,
+    ))];
+    subpackets.extend(notations.into_iter().map(Subpacket::from));
+    let config = signature_config(key, hash_algorithm, SignatureType::Timestamp, subpackets);
+
+    Ok(StandaloneSignature::new(
+        config.sign_standalone(key, key_pw)?,
+    ))
+}
+
+/// Builds the configuration shared by all signatures created in this module.
+fn signature_config(
+    key: &impl SecretKeyTrait,
+    hash_algorithm: HashAlgorithm,
+    typ: SignatureType,
+    subpackets: Vec<Subpacket>,
+) -> SignatureConfig {
+    subpackets
+        .into_iter()
+        .fold(SubpacketAreas::for_key(key), SubpacketAreas::subpacket)
+        .into_config(typ, key.algorithm(), hash_algorithm)
+}
+
+/// Hashes the serialized `target` signature, to reference it in a Signature Target subpacket.
+fn target_hash(target: &Signature, hash_algorithm: HashAlgorithm) -> Result<Vec<u8>> {
+    let mut hasher = hash_algorithm.new_hasher()?;
+    hasher.update(&target.to_bytes()?);
+
+    Ok(hasher.finish())
+}
+
+/// Verifies the detached `signature` over the data read from `data`.
+///
+/// The data is hashed while it is read, so it is never held in memory as a whole.
+pub fn verify_detached<R: Read>(
+    data: R,
+    signature: &DetachedSignature,
+    key: &impl PublicKeyTrait,
+) -> Result<()> {
+    signature.signature.verify(key, BufReader::new(data))
 }
 
 impl Serialize for StandaloneSignature {
@@ -80,9 +245,135 @@ fn next<I: Iterator<Item = Result<Packet>>>(
     match packets.by_ref().next() {
         Some(Ok(packet)) => match packet.tag() {
             Tag::Signature => Some(packet.try_into().map(StandaloneSignature::new)),
+            tag if tag.is_experimental() => {
+                // standalone signatures have no place for these
+                warn!("skipping {:?} packet", tag);
+                next(packets)
+            }
             _ => Some(Err(format_err!("unexpected packet {:?}", packet.tag()))),
         },
         Some(Err(e)) => Some(Err(e)),
         None => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::io::{self, Cursor};
+
+    use super::*;
+    use crate::test_util::gen_key;
+
+    /// Produces a large amount of data, without storing it.
+    fn data(len: u64) -> impl Read {
+        io::repeat(0x42).take(len)
+    }
+
+    #[test]
+    fn test_detached_signature() {
+        let key = gen_key("Alice <alice@example.com>");
+        let public_key = key.public_key();
+
+        let len = 16 * 1024 * 1024;
+        let signature =
+            sign_detached(data(len), &key, String::new, HashAlgorithm::SHA2_256).unwrap();
+        verify_detached(data(len), &signature, &public_key).unwrap();
+        assert!(verify_detached(data(len + 1), &signature, &public_key).is_err());
+
+        let armored = signature.to_armored_string(None).unwrap();
+        let (parsed, _) = DetachedSignature::from_string(&armored).unwrap();
+        verify_detached(data(len), &parsed, &public_key).unwrap();
+
+        let binary = signature.to_bytes().unwrap();
+        let parsed = DetachedSignature::from_bytes(Cursor::new(binary)).unwrap();
+        verify_detached(data(len), &parsed, &public_key).unwrap();
+    }
+
+    #[test]
+    fn test_standalone_and_timestamp_signatures() {
+        let key = gen_key("Notary <notary@example.com>");
+        let public_key = key.public_key();
+        let notations = vec![
+            Notation::new("document@example.com", "contract v2"),
+            Notation::binary("document@example.com", vec![0x00, 0xFF]).critical(),
+        ];
+
+        let standalone = sign_standalone(
+            &key,
+            String::new,
+            HashAlgorithm::SHA2_256,
+            notations.clone(),
+        )
+        .unwrap();
+        assert_eq!(standalone.signature.typ(), SignatureType::Standalone);
+        assert_eq!(
+            standalone.signature.notations(),
+            notations.iter().collect::<Vec<_>>()
+        );
+        standalone.verify_standalone(&public_key).unwrap();
+
+        let (parsed, _) =
+            StandaloneSignature::from_string(&standalone.to_armored_string(None).unwrap()).unwrap();
+        parsed.verify_standalone(&public_key).unwrap();
+        assert_eq!(
+            parsed.signature.notations(),
+            notations.iter().collect::<Vec<_>>()
+        );
+
+        let document =
+            sign_detached(&b"contract"[..], &key, String::new, HashAlgorithm::SHA2_256).unwrap();
+        let timestamp = sign_timestamp(
+            &document.signature,
+            &key,
+            String::new,
+            HashAlgorithm::SHA2_512,
+            Vec::new(),
+        )
+        .unwrap();
+        assert_eq!(timestamp.signature.typ(), SignatureType::Timestamp);
+        assert_eq!(timestamp.signature.signature_target().unwrap().2.len(), 64);
+        timestamp
+            .verify_timestamp(&public_key, &document.signature)
+            .unwrap();
+        assert!(timestamp
+            .verify_timestamp(&public_key, &standalone.signature)
+            .is_err());
+        assert!(standalone
+            .verify_timestamp(&public_key, &document.signature)
+            .is_err());
+        assert!(document.verify_standalone(&public_key).is_err());
+    }
+
+    #[test]
+    fn test_detached_text_signature() {
+        let key = gen_key("Alice <alice@example.com>");
+        let public_key = key.public_key();
+
+        let text = "first line\nsecond line\r\nthird line\rlast line\n";
+        let signature =
+            sign_detached_text(text.as_bytes(), &key, String::new, HashAlgorithm::SHA2_256)
+                .unwrap();
+        assert_eq!(signature.signature.typ(), SignatureType::Text);
+
+        for converted in [
+            text.to_string(),
+            text.replace("\r\n", "\n").replace('\r', "\n"),
+            text.replace("\r\n", "\n")
+                .replace('\r', "\n")
+                .replace('\n', "\r\n"),
+        ] {
+            verify_detached(converted.as_bytes(), &signature, &public_key).unwrap();
+        }
+        assert!(verify_detached(&b"first line second line"[..], &signature, &public_key).is_err());
+
+        // binary signatures are sensitive to line endings
+        let signature =
+            sign_detached(text.as_bytes(), &key, String::new, HashAlgorithm::SHA2_256).unwrap();
+        verify_detached(text.as_bytes(), &signature, &public_key).unwrap();
+        assert!(
+            verify_detached(text.replace('\r', "").as_bytes(), &signature, &public_key).is_err()
+        );
+    }
+}
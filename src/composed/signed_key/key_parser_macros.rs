@@ -64,11 +64,13 @@ macro_rules! key_parser {
                 debug!("  signatures");
                 let mut revocation_signatures = Vec::new();
                 let mut direct_signatures = Vec::new();
+                let mut experimental = Vec::new();
 
                while let Some(packet) = packets.next_if(|p| {
-                    p.as_ref().is_ok_and(|p| p.tag() == Tag::Signature)
+                    p.as_ref().is_ok_and(|p| p.tag() == Tag::Signature || p.tag().is_experimental())
                 }) {
                     match packet {
+                        Ok(packet::Packet::Experimental(p)) => experimental.push(p),
                         Ok(packet) => {
                             debug!("parsing signature {:?}", packet.tag());
                             let sig: Signature = err_opt!(packet.try_into());
@@ -114,11 +116,16 @@ macro_rules! key_parser {
                             // --- zero or more signature packets
 
                             let mut sigs = Vec::new();
+                            let mut experimental = Vec::new();
 
                             while let Some(res) = packets.next_if(|p| {
-                                p.as_ref().is_ok_and(|p| p.tag() == Tag::Signature)
+                                p.as_ref().is_ok_and(|p| p.tag() == Tag::Signature || p.tag().is_experimental())
                             }) {
                                 let packet = match res {
+                                    Ok(packet::Packet::Experimental(p)) => {
+                                        experimental.push(p);
+                                        continue;
+                                    }
                                     Ok(packet) => packet,
                                     Err(e) => return Some(Err(e)),
                                 };
@@ -127,7 +134,9 @@ macro_rules! key_parser {
                                 sigs.push(sig);
                             }
 
-                            users.push(SignedUser::new(id, sigs));
+                            let mut user = SignedUser::new(id, sigs);
+                            user.experimental = experimental;
+                            users.push(user);
                         }
                         Tag::UserAttribute => {
                             let attr: UserAttribute = err_opt!(packet.try_into());
@@ -135,10 +144,15 @@ macro_rules! key_parser {
                             // --- zero or more signature packets
 
                             let mut sigs = Vec::new();
+                            let mut experimental = Vec::new();
                             while let Some(res) = packets.next_if(|p| {
-                                p.as_ref().is_ok_and(|p| p.tag() == Tag::Signature)
+                                p.as_ref().is_ok_and(|p| p.tag() == Tag::Signature || p.tag().is_experimental())
                             }) {
                                 let packet = match res {
+                                    Ok(packet::Packet::Experimental(p)) => {
+                                        experimental.push(p);
+                                        continue;
+                                    }
                                     Ok(packet) => packet,
                                     Err(e) => return Some(Err(e)),
                                 };
@@ -147,7 +161,9 @@ macro_rules! key_parser {
                                 sigs.push(sig);
                             }
 
-                            user_attributes.push(SignedUserAttribute::new(attr, sigs));
+                            let mut attr = SignedUserAttribute::new(attr, sigs);
+                            attr.experimental = experimental;
+                            user_attributes.push(attr);
                         }
                         _ => break,
                     }
@@ -185,10 +201,12 @@ macro_rules! key_parser {
                             Tag::$subkey_tag => {
                                 let subkey: $inner_subkey_type = err_opt!(packet.try_into());
                                 let mut sigs = Vec::new();
+                                let mut experimental = Vec::new();
                                 while let Some(res) = packets.next_if(|packet| {
-                                    packet.is_ok() && packet.as_ref().unwrap().tag() == Tag::Signature
+                                    packet.as_ref().is_ok_and(|p| p.tag() == Tag::Signature || p.tag().is_experimental())
                                 }) {
                                     match res {
+                                        Ok(packet::Packet::Experimental(p)) => experimental.push(p),
                                         Ok(packet) => {
                                             let sig: Signature = err_opt!(packet.try_into());
                                             sigs.push(sig);
@@ -197,21 +215,26 @@ macro_rules! key_parser {
                                     }
                                 }
 
-                                $subkey_container.push(<$subkey_type>::new(subkey, sigs));
+                                let mut subkey = <$subkey_type>::new(subkey, sigs);
+                                subkey.experimental = experimental;
+                                $subkey_container.push(subkey);
                             }
                         )*
                             _ => unreachable!()
                     }
                 }
 
+                let mut details = $crate::composed::signed_key::SignedKeyDetails::new(
+                    revocation_signatures,
+                    direct_signatures,
+                    users,
+                    user_attributes,
+                );
+                details.experimental = experimental;
+
                 Some(Ok(<$key_type>::new(
                     primary_key,
-                    $crate::composed::signed_key::SignedKeyDetails::new(
-                        revocation_signatures,
-                        direct_signatures,
-                        users,
-                        user_attributes,
-                    ),
+                    details,
                     $( $subkey_container, )*
                 )))
             }
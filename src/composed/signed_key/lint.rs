@@ -0,0 +1,164 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+use crate::composed::signed_key::shared::{is_issued_by, verify_subkey_signature};
+use crate::composed::signed_key::{SignedPublicKey, SignedPublicSubKey};
+use crate::crypto::hash::HashAlgorithm;
+use crate::packet::{Signature, SignatureType};
+use crate::policy::Policy;
+use crate::types::{KeyId, KeyTrait, SignedUser, Tag};
+
+/// A problem found when linting a [`SignedPublicKey`], see [`SignedPublicKey::lint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Lint {
+    /// The key is not valid, e.g. because it is revoked, expired or has no valid
+    /// self-signature.
+    InvalidKey { reason: String },
+    /// The parameters of the primary key or a subkey are rejected by the policy, e.g. a small
+    /// RSA modulus.
+    WeakKey { key_id: KeyId, reason: String },
+    /// A self-signature uses a hash algorithm rejected by the policy, e.g. SHA-1.
+    WeakSelfSignature {
+        typ: SignatureType,
+        hash: HashAlgorithm,
+        reason: String,
+    },
+    /// A User ID has no valid self-certification.
+    UncertifiedUserId { id: String },
+    /// A User ID is marked as primary, but its self-certification expired.
+    ExpiredPrimaryUserId { id: String },
+    /// A subkey has no valid binding signature.
+    UnboundSubkey { key_id: KeyId },
+    /// A signing subkey is bound without a valid primary key binding signature
+    /// ("back signature"), so it can not be used to sign.
+    MissingBackSignature { key_id: KeyId },
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::InvalidKey { reason } => write!(f, "invalid key: {}", reason),
+            Lint::WeakKey { key_id, reason } => write!(f, "weak key {}: {}", key_id, reason),
+            Lint::WeakSelfSignature { typ, reason, .. } => {
+                write!(f, "weak {:?} self-signature: {}", typ, reason)
+            }
+            Lint::UncertifiedUserId { id } => write!(f, "uncertified user id {:?}", id),
+            Lint::ExpiredPrimaryUserId { id } => {
+                write!(f, "expired certification of primary user id {:?}", id)
+            }
+            Lint::UnboundSubkey { key_id } => write!(f, "unbound subkey {}", key_id),
+            Lint::MissingBackSignature { key_id } => {
+                write!(
+                    f,
+                    "missing primary key binding for signing subkey {}",
+                    key_id
+                )
+            }
+        }
+    }
+}
+
+/// Finds the problems of `key` at the reference time `at`.
+pub(crate) fn lint(key: &SignedPublicKey, policy: &Policy, at: DateTime<Utc>) -> Vec<Lint> {
+    let primary = &key.primary_key;
+    let mut lints = Vec::new();
+
+    if let Err(err) = key.valid_at(at) {
+        lints.push(Lint::InvalidKey {
+            reason: err.to_string(),
+        });
+    }
+    if let Err(err) = policy.check_public_params(primary.public_params()) {
+        lints.push(Lint::WeakKey {
+            key_id: primary.key_id(),
+            reason: err.to_string(),
+        });
+    }
+
+    let details = &key.details;
+    let self_signatures = details
+        .revocation_signatures
+        .iter()
+        .chain(&details.direct_signatures)
+        .chain(details.users.iter().flat_map(|u| &u.signatures))
+        .chain(details.user_attributes.iter().flat_map(|a| &a.signatures))
+        .filter(|sig| is_issued_by(sig, primary))
+        .chain(key.public_subkeys.iter().flat_map(|s| &s.signatures));
+    for sig in self_signatures {
+        if let Err(err) = policy.check_signature(sig) {
+            lints.push(Lint::WeakSelfSignature {
+                typ: sig.typ(),
+                hash: sig.config.hash_alg,
+                reason: err.to_string(),
+            });
+        }
+    }
+
+    for user in &details.users {
+        lint_user(key, user, &at, &mut lints);
+    }
+    for subkey in &key.public_subkeys {
+        lint_subkey(key, subkey, policy, &mut lints);
+    }
+
+    lints
+}
+
+fn lint_user(key: &SignedPublicKey, user: &SignedUser, at: &DateTime<Utc>, lints: &mut Vec<Lint>) {
+    let primary = &key.primary_key;
+    let id = user.id.id().to_string();
+
+    let newest = user
+        .signatures
+        .iter()
+        .filter(|sig| sig.is_certification() && sig.typ() != SignatureType::CertRevocation)
+        .filter(|sig| {
+            is_issued_by(sig, primary)
+                && sig
+                    .verify_certification(primary, Tag::UserId, &user.id)
+                    .is_ok()
+        })
+        .max_by_key(|sig| sig.created());
+
+    match newest {
+        None => lints.push(Lint::UncertifiedUserId { id }),
+        Some(sig) if sig.is_primary() && sig.is_expired_at(at) => {
+            lints.push(Lint::ExpiredPrimaryUserId { id })
+        }
+        Some(_) => {}
+    }
+}
+
+fn lint_subkey(
+    key: &SignedPublicKey,
+    subkey: &SignedPublicSubKey,
+    policy: &Policy,
+    lints: &mut Vec<Lint>,
+) {
+    let primary = &key.primary_key;
+    let key_id = subkey.key_id();
+
+    if let Err(err) = policy.check_public_params(subkey.key.public_params()) {
+        lints.push(Lint::WeakKey {
+            key_id: key_id.clone(),
+            reason: err.to_string(),
+        });
+    }
+
+    let bindings: Vec<&Signature> = subkey
+        .signatures
+        .iter()
+        .filter(|sig| sig.typ() == SignatureType::SubkeyBinding)
+        .filter(|sig| sig.verify_key_binding(primary, &subkey.key).is_ok())
+        .collect();
+    if bindings.is_empty() {
+        lints.push(Lint::UnboundSubkey { key_id });
+    } else if bindings
+        .iter()
+        .all(|sig| verify_subkey_signature(sig, primary, &subkey.key).is_err())
+    {
+        lints.push(Lint::MissingBackSignature { key_id });
+    }
+}
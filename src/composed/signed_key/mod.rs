@@ -108,12 +108,16 @@
 #[macro_use]
 mod key_parser_macros;
 
+mod lint;
 mod parse;
 mod public;
 mod secret;
 mod shared;
+mod validity;
 
+pub use self::lint::Lint;
 pub use self::parse::*;
 pub use self::public::*;
 pub use self::secret::*;
 pub use self::shared::*;
+pub use self::validity::*;
@@ -19,12 +19,8 @@ pub fn from_armor_many<'a, R: io::Read + io::Seek + 'a>(
     Box<dyn Iterator<Item = Result<PublicOrSecret>> + 'a>,
     BTreeMap<String, String>,
 )> {
-    let mut dearmor = armor::Dearmor::new(input);
-    dearmor.read_header()?;
-    // Safe to unwrap, as read_header succeeded.
-    let typ = dearmor
-        .typ
-        .ok_or_else(|| format_err!("dearmor failed to retrieve armor type"))?;
+    let reader = armor::ArmorReader::new(io::BufReader::new(input))?;
+    let typ = reader.typ();
 
     // TODO: add typ information to the key possibly?
     match typ {
@@ -35,9 +31,9 @@ pub fn from_armor_many<'a, R: io::Read + io::Seek + 'a>(
         | BlockType::MultiPartMessage(_, _)
         | BlockType::Signature
         | BlockType::File => {
-            let headers = dearmor.headers.clone(); // FIXME: avoid clone
-                                                   // TODO: check that the result is what it actually said.
-            Ok((from_bytes_many(dearmor), headers))
+            let headers = reader.headers().clone(); // FIXME: avoid clone
+                                                    // TODO: check that the result is what it actually said.
+            Ok((from_bytes_many(reader), headers))
         }
         BlockType::PublicKeyPKCS1(_)
         | BlockType::PublicKeyPKCS8
@@ -105,3 +101,134 @@ impl<I: Sized + Iterator<Item = Result<Packet>>> Iterator for PubPrivIterator<I>
         }
     }
 }
+
+/// Parses a binary keyring, as written by GnuPG (`pubring.gpg`, `secring.gpg`).
+///
+/// The data is split into certificates at each primary key, and trust packets are skipped.
+/// A damaged certificate results in an error, after which parsing continues with the next
+/// primary key.
+pub fn from_keyring_many<'a>(
+    bytes: impl io::Read + 'a,
+) -> Box<dyn Iterator<Item = Result<PublicOrSecret>> + 'a> {
+    let packets = PacketParser::new(bytes)
+        .filter_map(crate::composed::shared::filter_parsed_packet_results)
+        .peekable();
+
+    Box::new(KeyringIterator { inner: packets })
+}
+
+pub struct KeyringIterator<I: Sized + Iterator<Item = Result<Packet>>> {
+    inner: iter::Peekable<I>,
+}
+
+impl<I: Sized + Iterator<Item = Result<Packet>>> Iterator for KeyringIterator<I> {
+    type Item = Result<PublicOrSecret>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // skip to the next primary key
+        let primary_key = loop {
+            match self.inner.next()? {
+                Ok(packet) if is_primary_key(&packet) => break packet,
+                Ok(packet) => warn!("ignoring unexpected packet {:?}", packet.tag()),
+                Err(err) => warn!("ignoring damaged packet: {:?}", err),
+            }
+        };
+
+        let mut packets = vec![primary_key];
+        let mut damaged = None;
+        while let Some(res) = self
+            .inner
+            .next_if(|p| !matches!(p, Ok(p) if is_primary_key(p)))
+        {
+            match res {
+                Ok(packet) => packets.push(packet),
+                Err(err) => {
+                    damaged.get_or_insert(err);
+                }
+            }
+        }
+
+        if let Some(err) = damaged {
+            return Some(Err(err));
+        }
+
+        Some(parse_certificate(packets))
+    }
+}
+
+/// Parses and verifies a binary keyring in parallel, splitting it at each primary key.
+///
+/// Returns the same certificates as [`from_keyring_many`], in the same order, with an error
+/// for each certificate that fails [`PublicOrSecret::verify`].
+#[cfg(feature = "rayon")]
+pub fn from_keyring_parallel(bytes: &[u8]) -> Vec<Result<PublicOrSecret>> {
+    use rayon::prelude::*;
+
+    let mut starts = Vec::new();
+    let mut parser = crate::packet::PacketRefParser::new(bytes);
+    loop {
+        let offset = parser.offset();
+        match parser.next() {
+            Some(Ok(packet)) if matches!(packet.tag(), Tag::PublicKey | Tag::SecretKey) => {
+                starts.push(offset)
+            }
+            Some(Ok(_)) => {}
+            // the rest is left to `from_keyring_many`, to be handled like sequential parsing
+            Some(Err(_)) | None => break,
+        }
+    }
+    if starts.first() != Some(&0) {
+        starts.insert(0, 0);
+    }
+
+    let chunks: Vec<&[u8]> = starts
+        .iter()
+        .zip(starts.iter().skip(1).chain(iter::once(&bytes.len())))
+        .map(|(&start, &end)| &bytes[start..end])
+        .collect();
+
+    chunks
+        .par_iter()
+        .flat_map_iter(|chunk| {
+            from_keyring_many(*chunk)
+                .map(|key| {
+                    let key = key?;
+                    key.verify()?;
+                    Ok(key)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn is_primary_key(packet: &Packet) -> bool {
+    matches!(packet.tag(), Tag::PublicKey | Tag::SecretKey)
+}
+
+/// Parses the packets of a single certificate, starting with its primary key.
+fn parse_certificate(packets: Vec<Packet>) -> Result<PublicOrSecret> {
+    let tag = packets[0].tag();
+    let packets = packets.into_iter().map(Ok).peekable();
+
+    let (key, mut rest) = match tag {
+        Tag::SecretKey => {
+            let mut parser = SignedSecretKeyParser::from_packets(packets);
+            let key = parser.next().map(|key| key.map(PublicOrSecret::Secret));
+            (key, parser.into_inner())
+        }
+        _ => {
+            let mut parser = SignedPublicKeyParser::from_packets(packets);
+            let key = parser.next().map(|key| key.map(PublicOrSecret::Public));
+            (key, parser.into_inner())
+        }
+    };
+
+    if let Some(Ok(packet)) = rest.next() {
+        warn!(
+            "ignoring unexpected packet {:?} in certificate",
+            packet.tag()
+        );
+    }
+
+    key.ok_or(Error::NoMatchingPacket)?
+}
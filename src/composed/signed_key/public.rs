@@ -1,4 +1,3 @@
-use std::collections::BTreeMap;
 use std::io;
 
 use chrono::{DateTime, Utc};
@@ -6,13 +5,17 @@ use rand::{CryptoRng, Rng};
 
 use crate::armor;
 use crate::composed::key::{PublicKey, PublicSubkey};
-use crate::composed::signed_key::SignedKeyDetails;
+use crate::composed::signed_key::shared::{
+    merge_experimental, merge_signatures, minimize_signatures, verify_subkey_signature,
+};
+use crate::composed::signed_key::{lint, Lint, SignedKeyDetails, ValidCert};
 use crate::crypto::hash::HashAlgorithm;
 use crate::crypto::public_key::PublicKeyAlgorithm;
 use crate::errors::Result;
 use crate::packet::{self, write_packet, SignatureType};
+use crate::policy::Policy;
 use crate::ser::Serialize;
-use crate::types::{KeyId, KeyTrait, Mpi, PublicKeyTrait};
+use crate::types::{Fingerprint, KeyId, KeyTrait, Mpi, PublicKeyTrait};
 
 /// Represents a Public PGP key, which is signed and either received or ready to be transferred.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -63,6 +66,141 @@ impl SignedPublicKey {
         Some(*self.primary_key.created_at() + expiration)
     }
 
+    /// Checks if this key was revoked, either by itself, or by one of the given designated
+    /// revokers.
+    pub fn is_revoked<P>(&self, revokers: &[P]) -> bool
+    where
+        P: PublicKeyTrait,
+    {
+        self.details.is_revoked(&self.primary_key, revokers)
+    }
+
+    /// Merges another copy of the same key into this one.
+    ///
+    /// This combines User IDs, User Attributes, subkeys and all their signatures, such as
+    /// third-party certifications and revocations. Identical signatures are only kept once.
+    pub fn merge(mut self, other: SignedPublicKey) -> Result<Self> {
+        ensure_eq!(
+            self.fingerprint(),
+            other.fingerprint(),
+            "can not merge different keys"
+        );
+
+        self.details.merge(other.details);
+
+        for subkey in other.public_subkeys {
+            let fingerprint = subkey.fingerprint();
+            match self
+                .public_subkeys
+                .iter_mut()
+                .find(|s| s.fingerprint() == fingerprint)
+            {
+                Some(existing) => {
+                    merge_signatures(&mut existing.signatures, subkey.signatures);
+                    merge_experimental(&mut existing.experimental, subkey.experimental);
+                }
+                None => self.public_subkeys.push(subkey),
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Minimizes this key, for use cases such as Autocrypt or WKD.
+    ///
+    /// Third-party certifications and expired signatures are removed, and only the most
+    /// recent self-signature of each component is kept, together with any revocations.
+    pub fn minimize(mut self) -> Self {
+        let now = Utc::now();
+
+        self.details.minimize(&self.primary_key);
+        for subkey in &mut self.public_subkeys {
+            let key = &subkey.key;
+            minimize_signatures(&mut subkey.signatures, &self.primary_key, &now, |sig| {
+                verify_subkey_signature(sig, &self.primary_key, key)
+            });
+        }
+        self.public_subkeys
+            .retain(|subkey| !subkey.signatures.is_empty());
+
+        self
+    }
+
+    /// Same as [`minimize`](SignedPublicKey::minimize), additionally only keeping the given
+    /// User ID, and no User Attributes.
+    pub fn filter(self, user_id: &str) -> Result<Self> {
+        let mut key = self.minimize();
+
+        key.details.users.retain(|user| user.id.id() == user_id);
+        key.details.user_attributes.clear();
+        ensure!(
+            !key.details.users.is_empty(),
+            "user id {:?} not found",
+            user_id
+        );
+
+        Ok(key)
+    }
+
+    /// Same as [`filter`](SignedPublicKey::filter), keeping the User IDs for the given email
+    /// address, ignoring case.
+    pub fn filter_email(self, email: &str) -> Result<Self> {
+        let email = email.trim().to_lowercase();
+        let mut key = self.minimize();
+
+        key.details
+            .users
+            .retain(|user| user.id.email_normalized().as_deref() == Some(email.as_str()));
+        key.details.user_attributes.clear();
+        ensure!(
+            !key.details.users.is_empty(),
+            "no user id for {:?} found",
+            email
+        );
+
+        Ok(key)
+    }
+
+    /// Validates this key at the reference time `at`, see [`ValidCert`].
+    pub fn valid_at(&self, at: DateTime<Utc>) -> Result<ValidCert<'_>> {
+        ValidCert::new(self, at)
+    }
+
+    /// Checks this key for problems, like weak algorithms or unbound subkeys, that make it
+    /// unsuitable for use under `policy`.
+    ///
+    /// An empty list means no problems were found.
+    pub fn lint(&self, policy: &Policy) -> Vec<Lint> {
+        self.lint_at(policy, Utc::now())
+    }
+
+    /// Same as [`SignedPublicKey::lint`], but checks expiration and validity at `at`.
+    pub fn lint_at(&self, policy: &Policy, at: DateTime<Utc>) -> Vec<Lint> {
+        lint::lint(self, policy, at)
+    }
+
+    /// Returns the subkey to use for encryption at `at`.
+    ///
+    /// This is the newest subkey with encryption key flags, that is neither expired nor revoked
+    /// at that time. Returns `None` if there is no such subkey, or the key itself is not valid.
+    pub fn encryption_subkey(&self, at: DateTime<Utc>) -> Option<&SignedPublicSubKey> {
+        let valid = self.valid_at(at).ok()?;
+        let subkey = valid.encryption_subkey()?.subkey();
+
+        Some(subkey)
+    }
+
+    /// Returns the subkey to use for signing at `at`.
+    ///
+    /// This is the newest subkey with the signing key flag, that is neither expired nor revoked
+    /// at that time. Returns `None` if there is no such subkey, or the key itself is not valid.
+    pub fn signing_subkey(&self, at: DateTime<Utc>) -> Option<&SignedPublicSubKey> {
+        let valid = self.valid_at(at).ok()?;
+        let subkey = valid.signing_subkey()?.subkey();
+
+        Some(subkey)
+    }
+
     fn verify_public_subkeys(&self) -> Result<()> {
         for subkey in &self.public_subkeys {
             subkey.verify(&self.primary_key)?;
@@ -78,24 +216,44 @@ impl SignedPublicKey {
         Ok(())
     }
 
-    pub fn to_armored_writer(
+    /// Same as [`SignedPublicKey::verify`], but verifies independent signatures in parallel,
+    /// which speeds up certificates with many User IDs or subkeys.
+    #[cfg(feature = "rayon")]
+    pub fn verify_parallel(&self) -> Result<()> {
+        use rayon::prelude::*;
+
+        self.details.verify_parallel(&self.primary_key)?;
+        self.public_subkeys
+            .par_iter()
+            .try_for_each(|subkey| subkey.verify(&self.primary_key))?;
+
+        Ok(())
+    }
+
+    pub fn to_armored_writer<'a>(
         &self,
         writer: &mut impl io::Write,
-        headers: Option<&BTreeMap<String, String>>,
+        opts: impl Into<armor::ArmorOptions<'a>>,
     ) -> Result<()> {
-        armor::write(self, armor::BlockType::PublicKey, writer, headers)
+        armor::write(self, armor::BlockType::PublicKey, writer, opts)
     }
 
-    pub fn to_armored_bytes(&self, headers: Option<&BTreeMap<String, String>>) -> Result<Vec<u8>> {
+    pub fn to_armored_bytes<'a>(
+        &self,
+        opts: impl Into<armor::ArmorOptions<'a>>,
+    ) -> Result<Vec<u8>> {
         let mut buf = Vec::new();
 
-        self.to_armored_writer(&mut buf, headers)?;
+        self.to_armored_writer(&mut buf, opts)?;
 
         Ok(buf)
     }
 
-    pub fn to_armored_string(&self, headers: Option<&BTreeMap<String, String>>) -> Result<String> {
-        Ok(::std::str::from_utf8(&self.to_armored_bytes(headers)?)?.to_string())
+    pub fn to_armored_string<'a>(
+        &self,
+        opts: impl Into<armor::ArmorOptions<'a>>,
+    ) -> Result<String> {
+        Ok(::std::str::from_utf8(&self.to_armored_bytes(opts)?)?.to_string())
     }
 
     pub fn as_unsigned(&self) -> PublicKey {
@@ -111,7 +269,7 @@ impl SignedPublicKey {
 }
 
 impl KeyTrait for SignedPublicKey {
-    fn fingerprint(&self) -> Vec<u8> {
+    fn fingerprint(&self) -> Fingerprint {
         self.primary_key.fingerprint()
     }
 
@@ -155,6 +313,8 @@ impl Serialize for SignedPublicKey {
 pub struct SignedPublicSubKey {
     pub key: packet::PublicSubkey,
     pub signatures: Vec<packet::Signature>,
+    /// Packets with private or experimental tags, that followed this subkey.
+    pub experimental: Vec<packet::Experimental>,
 }
 
 impl SignedPublicSubKey {
@@ -173,32 +333,38 @@ impl SignedPublicSubKey {
             }
         });
 
-        SignedPublicSubKey { key, signatures }
+        SignedPublicSubKey {
+            key,
+            signatures,
+            experimental: Vec::new(),
+        }
     }
 
     pub fn verify(&self, key: &impl PublicKeyTrait) -> Result<()> {
         ensure!(!self.signatures.is_empty(), "missing subkey bindings");
         for sig in &self.signatures {
-            sig.verify_key_binding(key, &self.key)?;
+            verify_subkey_signature(sig, key, &self.key)?;
         }
 
         Ok(())
     }
 
     pub fn as_unsigned(&self) -> PublicSubkey {
-        let keyflags = self
-            .signatures
-            .first()
-            .expect("missing signatures")
-            .key_flags();
-
-        PublicSubkey::new(self.key.clone(), keyflags)
+        let sig = self.signatures.first().expect("missing signatures");
+        let keyflags = sig.key_flags();
+        let expiration = sig.key_expiration_time().cloned();
+
+        PublicSubkey::new(self.key.clone(), keyflags, expiration).with_backsig(
+            sig.embedded_signatures()
+                .find(|backsig| backsig.typ() == SignatureType::KeyBinding)
+                .cloned(),
+        )
     }
 }
 
 impl KeyTrait for SignedPublicSubKey {
     /// Returns the fingerprint of the key.
-    fn fingerprint(&self) -> Vec<u8> {
+    fn fingerprint(&self) -> Fingerprint {
         self.key.fingerprint()
     }
 
@@ -232,6 +398,9 @@ impl Serialize for SignedPublicSubKey {
         for sig in &self.signatures {
             write_packet(writer, sig)?;
         }
+        for packet in &self.experimental {
+            write_packet(writer, packet)?;
+        }
 
         Ok(())
     }
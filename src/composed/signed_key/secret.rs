@@ -1,17 +1,28 @@
-use std::collections::BTreeMap;
 use std::io;
 
-use chrono::{DateTime, Utc};
-use rand::{CryptoRng, Rng};
+use bstr::BString;
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+use rand::{thread_rng, CryptoRng, Rng};
 
-use crate::composed::key::{PublicKey, PublicSubkey};
+use crate::composed::key::{PublicKey, PublicSubkey, SubkeyParams};
+use crate::composed::signed_key::shared::verify_subkey_signature;
 use crate::composed::signed_key::{SignedKeyDetails, SignedPublicSubKey};
+use crate::composed::StandaloneSignature;
 use crate::crypto::hash::HashAlgorithm;
 use crate::crypto::public_key::PublicKeyAlgorithm;
 use crate::errors::Result;
-use crate::packet::{self, write_packet, SignatureType};
+#[cfg(feature = "ssh")]
+use crate::formats::ssh;
+use crate::packet::{
+    self, write_packet, CertificationType, PacketTrait, RevocationCode, SignatureConfig,
+    SignatureConfigBuilder, SignatureType, SignatureVersion, Subpacket, SubpacketData,
+    UserAttribute, UserId,
+};
 use crate::ser::Serialize;
-use crate::types::{KeyId, KeyTrait, Mpi, PublicKeyTrait, SecretKeyRepr, SecretKeyTrait};
+use crate::types::{
+    Fingerprint, KeyId, KeyTrait, Mpi, PassphraseProvider, PublicKeyTrait, SecretKeyRepr,
+    SecretKeyTrait, WithUserId,
+};
 use crate::{armor, SignedPublicKey};
 
 /// Represents a secret signed PGP key.
@@ -82,6 +93,437 @@ impl SignedSecretKey {
         Some(*self.primary_key.created_at() + expiration)
     }
 
+    /// Checks if this key was revoked, either by itself, or by one of the given designated
+    /// revokers.
+    pub fn is_revoked<P>(&self, revokers: &[P]) -> bool
+    where
+        P: PublicKeyTrait,
+    {
+        self.details.is_revoked(&self.primary_key, revokers)
+    }
+
+    /// Adds a new User ID to this key.
+    ///
+    /// The User ID is self-certified with the key flags, preferences and expiration of the
+    /// current primary User ID.
+    pub fn add_user_id<F>(mut self, id: &str, key_pw: F) -> Result<Self>
+    where
+        F: PassphraseProvider,
+    {
+        ensure!(!self.details.users.is_empty(), "key has no user ids");
+        ensure!(
+            !self.details.users.iter().any(|user| user.id.id() == id),
+            "user id {:?} already exists",
+            id
+        );
+
+        let user_id = UserId::from_str(Default::default(), id);
+        let user =
+            self.details
+                .as_unsigned()
+                .sign_user_id(&self.primary_key, key_pw, &user_id, false)?;
+        self.details.users.push(user);
+
+        Ok(self)
+    }
+
+    /// Adds a new User Attribute, e.g. a photo ID created with [`UserAttribute::new_jpeg`],
+    /// to this key, self-certified by the primary key.
+    pub fn add_user_attribute<F>(mut self, attr: UserAttribute, key_pw: F) -> Result<Self>
+    where
+        F: PassphraseProvider,
+    {
+        ensure!(
+            !self.details.user_attributes.iter().any(|a| a.attr == attr),
+            "user attribute already exists"
+        );
+
+        let attr = attr.sign(&self.primary_key, key_pw)?;
+        self.details.user_attributes.push(attr);
+
+        Ok(self)
+    }
+
+    /// Generates a new subkey and binds it to this key.
+    ///
+    /// Signing capable subkeys additionally get an embedded primary key binding signature.
+    pub fn add_subkey<F>(self, params: SubkeyParams, key_pw: F) -> Result<Self>
+    where
+        F: PassphraseProvider,
+    {
+        let mut rng = thread_rng();
+        self.add_subkey_with_rng(&mut rng, params, key_pw)
+    }
+
+    /// Same as [`add_subkey`](SignedSecretKey::add_subkey), using the given RNG.
+    pub fn add_subkey_with_rng<R, F>(
+        mut self,
+        rng: &mut R,
+        params: SubkeyParams,
+        key_pw: F,
+    ) -> Result<Self>
+    where
+        R: Rng + CryptoRng,
+        F: PassphraseProvider,
+    {
+        let subkey_pw = params.passphrase.clone().unwrap_or_default();
+        let subkey =
+            params
+                .generate_with_rng(rng)?
+                .sign_with_backsig(&self.primary_key, key_pw, || subkey_pw.clone())?;
+        self.secret_subkeys.push(subkey);
+
+        Ok(self)
+    }
+
+    /// Updates the expiration of the primary key.
+    ///
+    /// All self-certifications of the User IDs are replaced with new ones, that carry the
+    /// given Key Expiration Time, relative to the creation of the key. `None` removes the
+    /// expiration.
+    pub fn set_expiration<F>(mut self, expiration: Option<Duration>, key_pw: F) -> Result<Self>
+    where
+        F: PassphraseProvider + Clone,
+    {
+        ensure!(!self.details.users.is_empty(), "key has no user ids");
+
+        let key_id = self.primary_key.key_id();
+        let fingerprint = self.primary_key.fingerprint();
+
+        for user in &mut self.details.users {
+            let id = &user.id;
+            let (self_sigs, mut signatures): (Vec<_>, Vec<_>) =
+                user.signatures.drain(..).partition(|sig| {
+                    sig.is_certification()
+                        && sig.typ() != SignatureType::CertRevocation
+                        && (sig.issuer().contains(&&key_id)
+                            || sig.issuer_fingerprint().contains(&&fingerprint))
+                        && sig
+                            .verify_certification(&self.primary_key, id.tag(), id)
+                            .is_ok()
+                });
+
+            // the most recent self-certification is the template for the new one
+            let self_sig = self_sigs
+                .iter()
+                .max_by_key(|sig| sig.created())
+                .ok_or_else(|| format_err!("missing self-certification for {}", user.id))?;
+            let sig = with_key_expiration(self_sig, expiration)?.sign_certification(
+                &self.primary_key,
+                key_pw.clone(),
+                user.id.tag(),
+                &user.id,
+            )?;
+            signatures.insert(0, sig);
+            user.signatures = signatures;
+        }
+
+        Ok(self)
+    }
+
+    /// Updates the expiration of the subkey with the given Key ID.
+    ///
+    /// The binding signatures of the subkey are replaced with a new one, that carries the given
+    /// Key Expiration Time, relative to the creation of the subkey. `None` removes the
+    /// expiration.
+    pub fn set_subkey_expiration<F>(
+        mut self,
+        key_id: &KeyId,
+        expiration: Option<Duration>,
+        key_pw: F,
+    ) -> Result<Self>
+    where
+        F: PassphraseProvider,
+    {
+        if let Some(subkey) = self
+            .secret_subkeys
+            .iter_mut()
+            .find(|subkey| &subkey.key_id() == key_id)
+        {
+            subkey.signatures = rebind_subkey(
+                &self.primary_key,
+                key_pw,
+                &subkey.key,
+                &subkey.signatures,
+                expiration,
+            )?;
+        } else if let Some(subkey) = self
+            .public_subkeys
+            .iter_mut()
+            .find(|subkey| &subkey.key_id() == key_id)
+        {
+            subkey.signatures = rebind_subkey(
+                &self.primary_key,
+                key_pw,
+                &subkey.key,
+                &subkey.signatures,
+                expiration,
+            )?;
+        } else {
+            bail!("unknown subkey {:?}", key_id);
+        }
+
+        Ok(self)
+    }
+
+    /// Changes the passphrase protecting the primary key and all secret subkeys.
+    ///
+    /// The secret material is decrypted with `old_pw` and encrypted again with `new_pw`,
+    /// using a fresh S2K. Either all keys are updated, or an error is returned.
+    pub fn set_passphrase<F>(self, old_pw: F, new_pw: &str) -> Result<Self>
+    where
+        F: PassphraseProvider + Clone,
+    {
+        let mut rng = thread_rng();
+        self.set_passphrase_with_rng(&mut rng, old_pw, Some(new_pw))
+    }
+
+    /// Removes the passphrase protection of the primary key and all secret subkeys.
+    pub fn remove_passphrase<F>(self, old_pw: F) -> Result<Self>
+    where
+        F: PassphraseProvider + Clone,
+    {
+        let mut rng = thread_rng();
+        self.set_passphrase_with_rng(&mut rng, old_pw, None)
+    }
+
+    /// Same as [`set_passphrase`](SignedSecretKey::set_passphrase), using the given RNG.
+    /// A `new_pw` of `None` removes the passphrase protection.
+    pub fn set_passphrase_with_rng<R, F>(
+        mut self,
+        rng: &mut R,
+        old_pw: F,
+        new_pw: Option<&str>,
+    ) -> Result<Self>
+    where
+        R: Rng + CryptoRng,
+        F: PassphraseProvider + Clone,
+    {
+        let old_pw = self.with_user_id(old_pw);
+        self.primary_key
+            .set_passphrase_with_rng(rng, old_pw.clone(), new_pw)?;
+        for subkey in &mut self.secret_subkeys {
+            subkey
+                .key
+                .set_passphrase_with_rng(rng, old_pw.clone(), new_pw)?;
+        }
+
+        Ok(self)
+    }
+
+    /// Certifies the User ID `user_id` of `key`, with the given level of verification.
+    ///
+    /// The returned certification can be added to the signatures of the User ID.
+    pub fn certify<F>(
+        &self,
+        key: &SignedPublicKey,
+        user_id: &str,
+        typ: CertificationType,
+        key_pw: F,
+    ) -> Result<packet::Signature>
+    where
+        F: PassphraseProvider,
+    {
+        self.certify_with_trust(key, user_id, typ, None, key_pw)
+    }
+
+    /// Same as [`certify`](SignedSecretKey::certify), additionally marking `key` as a
+    /// trusted introducer, using a Trust Signature and optional Regular Expression
+    /// subpackets.
+    pub fn certify_with_trust<F>(
+        &self,
+        key: &SignedPublicKey,
+        user_id: &str,
+        typ: CertificationType,
+        trust: Option<&CertificationTrust>,
+        key_pw: F,
+    ) -> Result<packet::Signature>
+    where
+        F: PassphraseProvider,
+    {
+        let user = key
+            .details
+            .users
+            .iter()
+            .find(|user| user.id.id() == user_id)
+            .ok_or_else(|| format_err!("unknown user id {:?}", user_id))?;
+
+        let mut hashed_subpackets = vec![
+            Subpacket::regular(SubpacketData::SignatureCreationTime(
+                Utc::now().trunc_subsecs(0),
+            )),
+            Subpacket::regular(SubpacketData::IssuerFingerprint(
+                self.primary_key.fingerprint(),
+            )),
+        ];
+        if let Some(trust) = trust {
+            hashed_subpackets.push(Subpacket::regular(SubpacketData::TrustSignature(
+                trust.depth,
+                trust.amount,
+            )));
+            for regex in &trust.regexes {
+                // null terminated, see RFC 4880 section 5.2.3.14
+                let mut regex = BString::from(regex.as_str());
+                regex.push(0);
+                hashed_subpackets
+                    .push(Subpacket::critical(SubpacketData::RegularExpression(regex)));
+            }
+        }
+
+        SignatureConfigBuilder::default()
+            .typ(typ.into())
+            .pub_alg(self.primary_key.algorithm())
+            .hashed_subpackets(hashed_subpackets)
+            .unhashed_subpackets(vec![Subpacket::regular(SubpacketData::Issuer(
+                self.primary_key.key_id(),
+            ))])
+            .build()?
+            .sign_third_party_certification(
+                &self.primary_key,
+                key_pw,
+                &key.primary_key,
+                user.id.tag(),
+                &user.id,
+            )
+    }
+
+    /// Revokes this key, by adding a key revocation signature.
+    pub fn revoke<F>(mut self, code: RevocationCode, reason: &str, key_pw: F) -> Result<Self>
+    where
+        F: PassphraseProvider,
+    {
+        let sig = self.create_key_revocation(code, reason, key_pw)?;
+        self.details.revocation_signatures.push(sig);
+
+        Ok(self)
+    }
+
+    /// Revokes the subkey with the given Key ID, by adding a subkey revocation signature.
+    pub fn revoke_subkey<F>(
+        mut self,
+        key_id: &KeyId,
+        code: RevocationCode,
+        reason: &str,
+        key_pw: F,
+    ) -> Result<Self>
+    where
+        F: PassphraseProvider,
+    {
+        let config = revocation_config(
+            &self.primary_key,
+            SignatureType::SubkeyRevocation,
+            code,
+            reason,
+        )?;
+
+        if let Some(subkey) = self
+            .secret_subkeys
+            .iter_mut()
+            .find(|subkey| &subkey.key_id() == key_id)
+        {
+            let sig = config.sign_key_binding(&self.primary_key, key_pw, &subkey.key)?;
+            subkey.signatures.push(sig);
+        } else if let Some(subkey) = self
+            .public_subkeys
+            .iter_mut()
+            .find(|subkey| &subkey.key_id() == key_id)
+        {
+            let sig = config.sign_key_binding(&self.primary_key, key_pw, &subkey.key)?;
+            subkey.signatures.push(sig);
+        } else {
+            bail!("unknown subkey {:?}", key_id);
+        }
+
+        Ok(self)
+    }
+
+    /// Revokes the self-certification of the given User ID, by adding a certification
+    /// revocation signature.
+    pub fn revoke_user_id<F>(
+        mut self,
+        id: &str,
+        code: RevocationCode,
+        reason: &str,
+        key_pw: F,
+    ) -> Result<Self>
+    where
+        F: PassphraseProvider,
+    {
+        let config = revocation_config(
+            &self.primary_key,
+            SignatureType::CertRevocation,
+            code,
+            reason,
+        )?;
+
+        let user = self
+            .details
+            .users
+            .iter_mut()
+            .find(|user| user.id.id() == id)
+            .ok_or_else(|| format_err!("unknown user id {:?}", id))?;
+        let sig = config.sign_certification(&self.primary_key, key_pw, user.id.tag(), &user.id)?;
+        user.signatures.push(sig);
+
+        Ok(self)
+    }
+
+    /// Creates an armored revocation certificate for this key.
+    ///
+    /// The certificate consists of a single key revocation signature, that can be
+    /// published later on to revoke the key, without needing access to the secret key.
+    pub fn create_revocation_certificate<F>(
+        &self,
+        code: RevocationCode,
+        reason: &str,
+        key_pw: F,
+    ) -> Result<String>
+    where
+        F: PassphraseProvider,
+    {
+        let sig = self.create_key_revocation(code, reason, key_pw)?;
+
+        let mut buf = Vec::new();
+        armor::write(
+            &StandaloneSignature::new(sig),
+            armor::BlockType::PublicKey,
+            &mut buf,
+            None,
+        )?;
+
+        Ok(::std::str::from_utf8(&buf)?.to_string())
+    }
+
+    /// Adds the primary User ID to the passphrase requests of `pw`.
+    pub(crate) fn with_user_id<F>(&self, pw: F) -> WithUserId<F> {
+        let user = self
+            .details
+            .users
+            .iter()
+            .find(|user| user.is_primary())
+            .or_else(|| self.details.users.first());
+
+        WithUserId::new(pw, user.map(|user| user.id.id().to_string()))
+    }
+
+    fn create_key_revocation<F>(
+        &self,
+        code: RevocationCode,
+        reason: &str,
+        key_pw: F,
+    ) -> Result<packet::Signature>
+    where
+        F: PassphraseProvider,
+    {
+        revocation_config(
+            &self.primary_key,
+            SignatureType::KeyRevocation,
+            code,
+            reason,
+        )?
+        .sign_key(&self.primary_key, key_pw, &self.primary_key)
+    }
+
     fn verify_public_subkeys(&self) -> Result<()> {
         for subkey in &self.public_subkeys {
             subkey.verify(&self.primary_key)?;
@@ -106,30 +548,189 @@ impl SignedSecretKey {
         Ok(())
     }
 
-    pub fn to_armored_writer(
+    /// Exports the primary key in the `openssh-key-v1` format of OpenSSH, with the first
+    /// User ID as comment.
+    ///
+    /// The secret key is unlocked with `key_pw`. With a `passphrase`, the exported key is
+    /// encrypted the way `ssh-keygen` does.
+    #[cfg(feature = "ssh")]
+    pub fn to_ssh_private_key<F>(&self, key_pw: F, passphrase: Option<&str>) -> Result<String>
+    where
+        F: PassphraseProvider,
+    {
+        let mut rng = thread_rng();
+        self.to_ssh_private_key_with_rng(&mut rng, key_pw, passphrase)
+    }
+
+    /// Same as [`to_ssh_private_key`](SignedSecretKey::to_ssh_private_key), using the given RNG.
+    #[cfg(feature = "ssh")]
+    pub fn to_ssh_private_key_with_rng<R, F>(
+        &self,
+        rng: &mut R,
+        key_pw: F,
+        passphrase: Option<&str>,
+    ) -> Result<String>
+    where
+        R: Rng + CryptoRng,
+        F: PassphraseProvider,
+    {
+        let comment = self
+            .details
+            .users
+            .first()
+            .map(|user| user.id.id().to_string())
+            .unwrap_or_default();
+
+        let mut pem = None;
+        self.unlock(key_pw, |repr| {
+            pem = Some(ssh::private_key_pem(
+                rng,
+                self.primary_key.public_params(),
+                repr,
+                &comment,
+                passphrase,
+            )?);
+            Ok(())
+        })?;
+
+        Ok(pem.expect("set when unlocked"))
+    }
+
+    pub fn to_armored_writer<'a>(
         &self,
         writer: &mut impl io::Write,
-        headers: Option<&BTreeMap<String, String>>,
+        opts: impl Into<armor::ArmorOptions<'a>>,
     ) -> Result<()> {
-        armor::write(self, armor::BlockType::PrivateKey, writer, headers)
+        armor::write(self, armor::BlockType::PrivateKey, writer, opts)
     }
 
-    pub fn to_armored_bytes(&self, headers: Option<&BTreeMap<String, String>>) -> Result<Vec<u8>> {
+    pub fn to_armored_bytes<'a>(
+        &self,
+        opts: impl Into<armor::ArmorOptions<'a>>,
+    ) -> Result<Vec<u8>> {
         let mut buf = Vec::new();
 
-        self.to_armored_writer(&mut buf, headers)?;
+        self.to_armored_writer(&mut buf, opts)?;
 
         Ok(buf)
     }
 
-    pub fn to_armored_string(&self, headers: Option<&BTreeMap<String, String>>) -> Result<String> {
-        Ok(::std::str::from_utf8(&self.to_armored_bytes(headers)?)?.to_string())
+    pub fn to_armored_string<'a>(
+        &self,
+        opts: impl Into<armor::ArmorOptions<'a>>,
+    ) -> Result<String> {
+        Ok(::std::str::from_utf8(&self.to_armored_bytes(opts)?)?.to_string())
     }
 }
 
+/// Trust Signature parameters of a certification.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CertificationTrust {
+    /// Level of the trust, `1` for a trusted introducer, `2` for a meta introducer.
+    pub depth: u8,
+    /// Amount of trust, `60` for partial and `120` for complete trust.
+    pub amount: u8,
+    /// Regular expressions, limiting the User IDs the trust applies to.
+    pub regexes: Vec<String>,
+}
+
+/// Creates a copy of the configuration of `sig`, with a fresh creation time and the given
+/// Key Expiration Time.
+fn with_key_expiration(
+    sig: &packet::Signature,
+    expiration: Option<Duration>,
+) -> Result<SignatureConfig> {
+    ensure!(
+        matches!(sig.config.version, SignatureVersion::V4),
+        "can only update v4 signatures, found {:?}",
+        sig.config.version
+    );
+
+    let mut config = sig.config.clone();
+    config.hashed_subpackets.retain(|p| {
+        !matches!(
+            p.data,
+            SubpacketData::SignatureCreationTime(_) | SubpacketData::KeyExpirationTime(_)
+        )
+    });
+    config.hashed_subpackets.insert(
+        0,
+        Subpacket::regular(SubpacketData::SignatureCreationTime(
+            Utc::now().trunc_subsecs(0),
+        )),
+    );
+    if let Some(expiration) = expiration {
+        config
+            .hashed_subpackets
+            .push(Subpacket::regular(SubpacketData::KeyExpirationTime(
+                expiration,
+            )));
+    }
+
+    Ok(config)
+}
+
+/// Creates the configuration for a revocation signature of type `typ`, issued by `key`.
+fn revocation_config(
+    key: &packet::SecretKey,
+    typ: SignatureType,
+    code: RevocationCode,
+    reason: &str,
+) -> Result<SignatureConfig> {
+    SignatureConfigBuilder::default()
+        .typ(typ)
+        .pub_alg(key.algorithm())
+        .hashed_subpackets(vec![
+            Subpacket::regular(SubpacketData::SignatureCreationTime(
+                Utc::now().trunc_subsecs(0),
+            )),
+            Subpacket::regular(SubpacketData::RevocationReason(code, reason.into())),
+            Subpacket::regular(SubpacketData::IssuerFingerprint(key.fingerprint())),
+        ])
+        .unhashed_subpackets(vec![Subpacket::regular(SubpacketData::Issuer(
+            key.key_id(),
+        ))])
+        .build()
+}
+
+/// Replaces the binding signatures of `subkey` with a new one, carrying the given expiration.
+/// Revocations are kept.
+fn rebind_subkey<F>(
+    primary_key: &packet::SecretKey,
+    key_pw: F,
+    subkey: &impl PublicKeyTrait,
+    signatures: &[packet::Signature],
+    expiration: Option<Duration>,
+) -> Result<Vec<packet::Signature>>
+where
+    F: PassphraseProvider,
+{
+    let binding = signatures
+        .iter()
+        .filter(|sig| {
+            sig.typ() == SignatureType::SubkeyBinding
+                && sig.verify_key_binding(primary_key, subkey).is_ok()
+        })
+        .max_by_key(|sig| sig.created())
+        .ok_or_else(|| format_err!("missing subkey binding"))?;
+
+    let sig =
+        with_key_expiration(binding, expiration)?.sign_key_binding(primary_key, key_pw, subkey)?;
+
+    let mut res = vec![sig];
+    res.extend(
+        signatures
+            .iter()
+            .filter(|sig| sig.typ() != SignatureType::SubkeyBinding)
+            .cloned(),
+    );
+
+    Ok(res)
+}
+
 impl KeyTrait for SignedSecretKey {
     /// Returns the fingerprint of the associated primary key.
-    fn fingerprint(&self) -> Vec<u8> {
+    fn fingerprint(&self) -> Fingerprint {
         self.primary_key.fingerprint()
     }
 
@@ -164,17 +765,18 @@ impl SecretKeyTrait for SignedSecretKey {
 
     fn unlock<F, G>(&self, pw: F, work: G) -> Result<()>
     where
-        F: FnOnce() -> String,
+        F: PassphraseProvider,
         G: FnOnce(&SecretKeyRepr) -> Result<()>,
     {
-        self.primary_key.unlock(pw, work)
+        self.primary_key.unlock(self.with_user_id(pw), work)
     }
 
     fn create_signature<F>(&self, key_pw: F, hash: HashAlgorithm, data: &[u8]) -> Result<Vec<Mpi>>
     where
-        F: FnOnce() -> String,
+        F: PassphraseProvider,
     {
-        self.primary_key.create_signature(key_pw, hash, data)
+        self.primary_key
+            .create_signature(self.with_user_id(key_pw), hash, data)
     }
 
     fn public_key(&self) -> Self::PublicKey {
@@ -214,6 +816,8 @@ impl PublicKeyTrait for SignedSecretKey {
 pub struct SignedSecretSubKey {
     pub key: packet::SecretSubkey,
     pub signatures: Vec<packet::Signature>,
+    /// Packets with private or experimental tags, that followed this subkey.
+    pub experimental: Vec<packet::Experimental>,
 }
 
 impl SignedSecretSubKey {
@@ -232,14 +836,18 @@ impl SignedSecretSubKey {
             }
         });
 
-        SignedSecretSubKey { key, signatures }
+        SignedSecretSubKey {
+            key,
+            signatures,
+            experimental: Vec::new(),
+        }
     }
 
     pub fn verify(&self, key: &impl PublicKeyTrait) -> Result<()> {
         ensure!(!self.signatures.is_empty(), "missing subkey bindings");
 
         for sig in &self.signatures {
-            sig.verify_key_binding(key, &self.key)?;
+            verify_subkey_signature(sig, key, &self.key)?;
         }
 
         Ok(())
@@ -248,7 +856,7 @@ impl SignedSecretSubKey {
 
 impl KeyTrait for SignedSecretSubKey {
     /// Returns the fingerprint of the key.
-    fn fingerprint(&self) -> Vec<u8> {
+    fn fingerprint(&self) -> Fingerprint {
         self.key.fingerprint()
     }
 
@@ -268,6 +876,9 @@ impl Serialize for SignedSecretSubKey {
         for sig in &self.signatures {
             write_packet(writer, sig)?;
         }
+        for packet in &self.experimental {
+            write_packet(writer, packet)?;
+        }
 
         Ok(())
     }
@@ -278,7 +889,7 @@ impl SecretKeyTrait for SignedSecretSubKey {
 
     fn unlock<F, G>(&self, pw: F, work: G) -> Result<()>
     where
-        F: FnOnce() -> String,
+        F: PassphraseProvider,
         G: FnOnce(&SecretKeyRepr) -> Result<()>,
     {
         self.key.unlock(pw, work)
@@ -286,19 +897,21 @@ impl SecretKeyTrait for SignedSecretSubKey {
 
     fn create_signature<F>(&self, key_pw: F, hash: HashAlgorithm, data: &[u8]) -> Result<Vec<Mpi>>
     where
-        F: FnOnce() -> String,
+        F: PassphraseProvider,
     {
         self.key.create_signature(key_pw, hash, data)
     }
 
     fn public_key(&self) -> Self::PublicKey {
-        let keyflags = self
-            .signatures
-            .first()
-            .expect("invalid signed subkey")
-            .key_flags();
-
-        PublicSubkey::new(self.key.public_key(), keyflags)
+        let sig = self.signatures.first().expect("invalid signed subkey");
+        let keyflags = sig.key_flags();
+        let expiration = sig.key_expiration_time().cloned();
+
+        PublicSubkey::new(self.key.public_key(), keyflags, expiration).with_backsig(
+            sig.embedded_signatures()
+                .find(|backsig| backsig.typ() == SignatureType::KeyBinding)
+                .cloned(),
+        )
     }
 }
 
@@ -334,6 +947,8 @@ impl From<SignedSecretKey> for SignedPublicKey {
 
 impl From<SignedSecretSubKey> for SignedPublicSubKey {
     fn from(value: SignedSecretSubKey) -> Self {
-        SignedPublicSubKey::new(value.key.public_key(), value.signatures)
+        let mut subkey = SignedPublicSubKey::new(value.key.public_key(), value.signatures);
+        subkey.experimental = value.experimental;
+        subkey
     }
 }
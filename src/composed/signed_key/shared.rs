@@ -1,16 +1,19 @@
-use std::collections::BTreeMap;
 use std::io;
 
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
 use smallvec::SmallVec;
 
+use crate::armor;
 use crate::composed::key::KeyDetails;
 use crate::composed::signed_key::{SignedPublicKey, SignedSecretKey};
 use crate::crypto::public_key::PublicKeyAlgorithm;
 use crate::errors::Result;
-use crate::packet;
+use crate::packet::{self, SignatureType, SubpacketData};
 use crate::ser::Serialize;
-use crate::types::{KeyId, KeyTrait, PublicKeyTrait, SignedUser, SignedUserAttribute};
+use crate::types::{
+    Fingerprint, KeyId, KeyTrait, PublicKeyTrait, RevocationKey, SignedUser, SignedUserAttribute,
+    Tag,
+};
 
 /// Shared details between secret and public keys.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -19,6 +22,9 @@ pub struct SignedKeyDetails {
     pub direct_signatures: Vec<packet::Signature>,
     pub users: Vec<SignedUser>,
     pub user_attributes: Vec<SignedUserAttribute>,
+    /// Packets with private or experimental tags, that followed the primary key and its
+    /// signatures.
+    pub experimental: Vec<packet::Experimental>,
 }
 
 impl SignedKeyDetails {
@@ -51,6 +57,7 @@ impl SignedKeyDetails {
             direct_signatures,
             users,
             user_attributes,
+            experimental: Vec::new(),
         }
     }
 
@@ -88,13 +95,98 @@ impl SignedKeyDetails {
     }
 
     fn verify_revocation_signatures(&self, key: &impl PublicKeyTrait) -> Result<()> {
+        let revocation_keys = self.revocation_keys(key);
+
         for sig in &self.revocation_signatures {
+            if !is_issued_by(sig, key)
+                && revocation_keys
+                    .iter()
+                    .any(|rkey| is_issued_by_fingerprint(sig, &rkey.fingerprint))
+            {
+                // Issued by a designated revoker, which can only be checked
+                // given the key of the revoker, see `is_revoked`.
+                continue;
+            }
             sig.verify_key(key)?;
         }
 
         Ok(())
     }
 
+    /// Returns the designated revokers of this key.
+    ///
+    /// These are collected from the Revocation Key subpackets of all direct key signatures
+    /// and User ID self-certifications, that were issued by `key`. The issuer subpackets are
+    /// only used to find these signatures, they are verified before they are trusted.
+    pub fn revocation_keys(&self, key: &impl PublicKeyTrait) -> Vec<&RevocationKey> {
+        let direct_signatures = self
+            .direct_signatures
+            .iter()
+            .filter(|sig| is_issued_by(sig, key) && sig.verify_key(key).is_ok());
+        let certifications = self.users.iter().flat_map(|user| {
+            user.signatures.iter().filter(|sig| {
+                is_issued_by(sig, key)
+                    && sig.verify_certification(key, Tag::UserId, &user.id).is_ok()
+            })
+        });
+
+        direct_signatures
+            .chain(certifications)
+            .flat_map(|sig| sig.config.hashed_subpackets())
+            .filter_map(|p| match &p.data {
+                SubpacketData::RevocationKey(rkey) => Some(rkey),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Checks if `key` was revoked, either by itself, or by one of its designated revokers.
+    ///
+    /// Revocations issued by a designated revoker are only considered, if the revoker is part of
+    /// `revokers`, and matches the fingerprint and algorithm of one of the Revocation Key
+    /// subpackets of `key`. Both regular and sensitive designations are honored, the sensitive
+    /// flag only restricts how the designation is shared.
+    pub fn is_revoked<P>(&self, key: &impl PublicKeyTrait, revokers: &[P]) -> bool
+    where
+        P: PublicKeyTrait,
+    {
+        !self.key_revocations(key, revokers).is_empty()
+    }
+
+    /// Returns the valid key revocations of `key`, issued either by itself, or by one of its
+    /// designated revokers in `revokers`, see [`SignedKeyDetails::is_revoked`].
+    pub fn key_revocations<P>(
+        &self,
+        key: &impl PublicKeyTrait,
+        revokers: &[P],
+    ) -> Vec<&packet::Signature>
+    where
+        P: PublicKeyTrait,
+    {
+        let revocation_keys = self.revocation_keys(key);
+
+        self.revocation_signatures
+            .iter()
+            .filter(|sig| {
+                if sig.typ() != SignatureType::KeyRevocation {
+                    return false;
+                }
+
+                if sig.verify_key(key).is_ok() {
+                    return true;
+                }
+
+                revokers.iter().any(|revoker| {
+                    let fingerprint = revoker.fingerprint();
+                    revocation_keys.iter().any(|rkey| {
+                        rkey.fingerprint == fingerprint && rkey.algorithm == revoker.algorithm()
+                    }) && is_issued_by_fingerprint(sig, &fingerprint)
+                        && sig.verify_key_third_party(revoker, key).is_ok()
+                })
+            })
+            .collect()
+    }
+
     fn verify_direct_signatures(&self, key: &impl PublicKeyTrait) -> Result<()> {
         for sig in &self.direct_signatures {
             sig.verify_key(key)?;
@@ -112,6 +204,98 @@ impl SignedKeyDetails {
         Ok(())
     }
 
+    /// Same as [`SignedKeyDetails::verify`], but verifies the signatures of the User IDs and
+    /// User Attributes in parallel.
+    #[cfg(feature = "rayon")]
+    pub fn verify_parallel(&self, key: &(impl PublicKeyTrait + Sync)) -> Result<()> {
+        use rayon::prelude::*;
+
+        self.users
+            .par_iter()
+            .try_for_each(|user| user.verify(key))?;
+        self.user_attributes
+            .par_iter()
+            .try_for_each(|attr| attr.verify(key))?;
+        self.verify_revocation_signatures(key)?;
+        self.verify_direct_signatures(key)?;
+
+        Ok(())
+    }
+
+    /// Merges the details of another copy of the same key into these details.
+    ///
+    /// New User IDs and User Attributes are added, and signatures that are not yet known
+    /// are added to the existing ones. Identical signatures are only kept once.
+    pub fn merge(&mut self, other: SignedKeyDetails) {
+        merge_signatures(&mut self.revocation_signatures, other.revocation_signatures);
+        merge_signatures(&mut self.direct_signatures, other.direct_signatures);
+        merge_experimental(&mut self.experimental, other.experimental);
+
+        for user in other.users {
+            match self.users.iter_mut().find(|u| u.id == user.id) {
+                Some(existing) => {
+                    merge_signatures(&mut existing.signatures, user.signatures);
+                    merge_experimental(&mut existing.experimental, user.experimental);
+                }
+                None => self.users.push(user),
+            }
+        }
+
+        for attr in other.user_attributes {
+            match self
+                .user_attributes
+                .iter_mut()
+                .find(|a| a.attr == attr.attr)
+            {
+                Some(existing) => {
+                    merge_signatures(&mut existing.signatures, attr.signatures);
+                    merge_experimental(&mut existing.experimental, attr.experimental);
+                }
+                None => self.user_attributes.push(attr),
+            }
+        }
+    }
+
+    /// Strips all signatures not issued by `key` or one of its designated revokers, as well
+    /// as expired ones, and only keeps the most recent self-signature per User ID and User
+    /// Attribute. Components without any remaining signature are removed.
+    pub fn minimize(&mut self, key: &impl PublicKeyTrait) {
+        let now = Utc::now();
+        let revokers: Vec<_> = self
+            .revocation_keys(key)
+            .into_iter()
+            .map(|rkey| rkey.fingerprint.clone())
+            .collect();
+
+        self.revocation_signatures.retain(|sig| {
+            !sig.is_expired_at(&now)
+                && ((is_issued_by(sig, key) && sig.verify_key(key).is_ok())
+                    || revokers
+                        .iter()
+                        .any(|fingerprint| is_issued_by_fingerprint(sig, fingerprint)))
+        });
+        minimize_signatures(&mut self.direct_signatures, key, &now, |sig| {
+            sig.verify_key(key)
+        });
+
+        for user in &mut self.users {
+            let id = &user.id;
+            minimize_signatures(&mut user.signatures, key, &now, |sig| {
+                sig.verify_certification(key, Tag::UserId, id)
+            });
+        }
+        self.users.retain(|user| !user.signatures.is_empty());
+
+        for attr in &mut self.user_attributes {
+            let attribute = &attr.attr;
+            minimize_signatures(&mut attr.signatures, key, &now, |sig| {
+                sig.verify_certification(key, Tag::UserAttribute, attribute)
+            });
+        }
+        self.user_attributes
+            .retain(|attr| !attr.signatures.is_empty());
+    }
+
     pub fn as_unsigned(&self) -> KeyDetails {
         let primary_user = self.users.iter().find(|u| u.is_primary()).map_or_else(
             || self.users.first().expect("missing user ids"),
@@ -148,10 +332,123 @@ impl SignedKeyDetails {
             preferred_hash_algorithms,
             preferred_compression_algorithms,
             revocation_key,
+            primary_sig.key_expiration_time().cloned(),
         )
     }
 }
 
+/// Verifies a signature on the subkey `subkey` of `key`.
+///
+/// Bindings of signing capable subkeys additionally need a valid embedded primary key
+/// binding signature ("back signature"), otherwise anyone could bind somebody else's
+/// signing key as their own subkey.
+pub(crate) fn verify_subkey_signature(
+    sig: &packet::Signature,
+    key: &impl PublicKeyTrait,
+    subkey: &impl PublicKeyTrait,
+) -> Result<()> {
+    sig.verify_key_binding(key, subkey)?;
+
+    if sig.typ() == SignatureType::SubkeyBinding && sig.key_flags().sign() {
+        // other embedded signatures may precede the primary key binding
+        let mut backsigs = sig
+            .embedded_signatures()
+            .filter(|backsig| backsig.typ() == SignatureType::KeyBinding)
+            .peekable();
+        ensure!(
+            backsigs.peek().is_some(),
+            "missing primary key binding for signing subkey"
+        );
+        ensure!(
+            backsigs.any(|backsig| backsig.verify_backwards_key_binding(subkey, key).is_ok()),
+            "invalid primary key binding for signing subkey"
+        );
+    }
+
+    Ok(())
+}
+
+/// Adds all signatures from `other` to `target`, that are not yet part of it.
+///
+/// Copies of a signature that only differ in their unhashed subpackets are the same
+/// signature, the copy in `target` is kept unchanged.
+pub(crate) fn merge_signatures(target: &mut Vec<packet::Signature>, other: Vec<packet::Signature>) {
+    for sig in other {
+        if !target.iter().any(|t| t.eq_ignoring_unhashed(&sig)) {
+            target.push(sig);
+        }
+    }
+}
+
+/// Adds all experimental packets from `other` to `target`, that are not yet part of it.
+pub(crate) fn merge_experimental(
+    target: &mut Vec<packet::Experimental>,
+    other: Vec<packet::Experimental>,
+) {
+    for p in other {
+        if !target.contains(&p) {
+            target.push(p);
+        }
+    }
+}
+
+/// Only keeps the signatures issued by `key`, that pass `verify` and are not expired at
+/// `now`. Of these, all revocations and the most recent other signature are kept.
+pub(crate) fn minimize_signatures<F>(
+    signatures: &mut Vec<packet::Signature>,
+    key: &impl PublicKeyTrait,
+    now: &DateTime<Utc>,
+    verify: F,
+) where
+    F: Fn(&packet::Signature) -> Result<()>,
+{
+    signatures
+        .retain(|sig| is_issued_by(sig, key) && !sig.is_expired_at(now) && verify(sig).is_ok());
+
+    let newest = signatures
+        .iter()
+        .filter(|sig| !is_revocation(sig))
+        .max_by_key(|sig| sig.created())
+        .cloned();
+    signatures.retain(|sig| is_revocation(sig) || Some(sig) == newest.as_ref());
+}
+
+fn is_revocation(sig: &packet::Signature) -> bool {
+    matches!(
+        sig.typ(),
+        SignatureType::KeyRevocation
+            | SignatureType::SubkeyRevocation
+            | SignatureType::CertRevocation
+    )
+}
+
+/// Does `sig` claim to be issued by `key`?
+pub(crate) fn is_issued_by(sig: &packet::Signature, key: &impl PublicKeyTrait) -> bool {
+    is_issued_by_fingerprint(sig, &key.fingerprint())
+}
+
+/// Is `user` bound to `key` by a valid, non-revocation self-certification?
+pub(crate) fn is_self_certified(user: &SignedUser, key: &impl PublicKeyTrait) -> bool {
+    user.signatures.iter().any(|sig| {
+        sig.is_certification()
+            && sig.typ() != SignatureType::CertRevocation
+            && is_issued_by(sig, key)
+            && sig.verify_certification(key, Tag::UserId, &user.id).is_ok()
+    })
+}
+
+/// Does `sig` claim to be issued by the key with the given `fingerprint`?
+fn is_issued_by_fingerprint(sig: &packet::Signature, fingerprint: &Fingerprint) -> bool {
+    if sig.issuer_fingerprint().contains(&fingerprint) {
+        return true;
+    }
+
+    fingerprint
+        .key_id()
+        .map(|key_id| sig.issuer().contains(&&key_id))
+        .unwrap_or(false)
+}
+
 impl Serialize for SignedKeyDetails {
     fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
         for sig in &self.revocation_signatures {
@@ -162,6 +459,10 @@ impl Serialize for SignedKeyDetails {
             packet::write_packet(writer, sig)?;
         }
 
+        for p in &self.experimental {
+            packet::write_packet(writer, p)?;
+        }
+
         for user in &self.users {
             user.to_writer(writer)?;
         }
@@ -189,28 +490,37 @@ impl PublicOrSecret {
         }
     }
 
-    pub fn to_armored_writer(
+    pub fn to_armored_writer<'a>(
         &self,
         writer: &mut impl io::Write,
-        headers: Option<&BTreeMap<String, String>>,
+        opts: impl Into<armor::ArmorOptions<'a>>,
     ) -> Result<()> {
+        let opts: armor::ArmorOptions<'a> = opts.into();
         match self {
-            PublicOrSecret::Public(k) => k.to_armored_writer(writer, headers),
-            PublicOrSecret::Secret(k) => k.to_armored_writer(writer, headers),
+            PublicOrSecret::Public(k) => k.to_armored_writer(writer, opts),
+            PublicOrSecret::Secret(k) => k.to_armored_writer(writer, opts),
         }
     }
 
-    pub fn to_armored_bytes(&self, headers: Option<&BTreeMap<String, String>>) -> Result<Vec<u8>> {
+    pub fn to_armored_bytes<'a>(
+        &self,
+        opts: impl Into<armor::ArmorOptions<'a>>,
+    ) -> Result<Vec<u8>> {
+        let opts: armor::ArmorOptions<'a> = opts.into();
         match self {
-            PublicOrSecret::Public(k) => k.to_armored_bytes(headers),
-            PublicOrSecret::Secret(k) => k.to_armored_bytes(headers),
+            PublicOrSecret::Public(k) => k.to_armored_bytes(opts),
+            PublicOrSecret::Secret(k) => k.to_armored_bytes(opts),
         }
     }
 
-    pub fn to_armored_string(&self, headers: Option<&BTreeMap<String, String>>) -> Result<String> {
+    pub fn to_armored_string<'a>(
+        &self,
+        opts: impl Into<armor::ArmorOptions<'a>>,
+    ) -> Result<String> {
+        let opts: armor::ArmorOptions<'a> = opts.into();
         match self {
-            PublicOrSecret::Public(k) => k.to_armored_string(headers),
-            PublicOrSecret::Secret(k) => k.to_armored_string(headers),
+            PublicOrSecret::Public(k) => k.to_armored_string(opts),
+            PublicOrSecret::Secret(k) => k.to_armored_string(opts),
         }
     }
 
@@ -256,7 +566,7 @@ impl Serialize for PublicOrSecret {
 
 impl KeyTrait for PublicOrSecret {
     /// Returns the fingerprint of the key.
-    fn fingerprint(&self) -> Vec<u8> {
+    fn fingerprint(&self) -> Fingerprint {
         match self {
             PublicOrSecret::Public(k) => k.fingerprint(),
             PublicOrSecret::Secret(k) => k.fingerprint(),
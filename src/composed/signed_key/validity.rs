@@ -0,0 +1,446 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::composed::signed_key::shared::{is_issued_by, verify_subkey_signature};
+use crate::composed::signed_key::{SignedPublicKey, SignedPublicSubKey};
+use crate::crypto::sym::SymmetricKeyAlgorithm;
+use crate::errors::Result;
+use crate::packet::{KeyFlags, RevocationCode, Signature, SignatureType};
+use crate::ser::Serialize;
+use crate::types::{
+    CompressionAlgorithm, KeyTrait, PublicKeyTrait, SignedUser, SignedUserAttribute, Tag,
+};
+
+/// A view on a [`SignedPublicKey`], that only contains the components which are valid at a
+/// given reference time.
+///
+/// All self-signatures are verified, and for each component the newest effective signature
+/// is selected. User IDs and subkeys that are revoked, expired or not (yet) bound at the
+/// reference time are left out.
+#[derive(Debug, Clone)]
+pub struct ValidCert<'a> {
+    key: &'a SignedPublicKey,
+    reference_time: DateTime<Utc>,
+    direct_signature: Option<&'a Signature>,
+    users: Vec<ValidUserId<'a>>,
+    user_attributes: Vec<ValidUserAttribute<'a>>,
+    subkeys: Vec<ValidSubkey<'a>>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// A User ID, together with its effective self-certification.
+#[derive(Debug, Clone)]
+pub struct ValidUserId<'a> {
+    user: &'a SignedUser,
+    binding: &'a Signature,
+}
+
+/// A User Attribute, together with its effective self-certification.
+#[derive(Debug, Clone)]
+pub struct ValidUserAttribute<'a> {
+    attr: &'a SignedUserAttribute,
+    binding: &'a Signature,
+}
+
+/// A subkey, together with its effective binding signature.
+#[derive(Debug, Clone)]
+pub struct ValidSubkey<'a> {
+    subkey: &'a SignedPublicSubKey,
+    binding: &'a Signature,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl<'a> ValidCert<'a> {
+    /// Validates `key` at the reference time `at`.
+    ///
+    /// Fails if the primary key itself is not valid at that time, because it was not yet
+    /// created, is revoked or expired, or has no valid self-signature.
+    ///
+    /// Only self-issued revocations are considered, see [`ValidCert::with_revokers`] to also
+    /// honor revocations by designated revokers.
+    pub fn new(key: &'a SignedPublicKey, at: DateTime<Utc>) -> Result<Self> {
+        Self::with_revokers::<SignedPublicKey>(key, at, &[])
+    }
+
+    /// Same as [`ValidCert::new`], but the key is also considered revoked, if one of its
+    /// designated revokers in `revokers` revoked it.
+    pub fn with_revokers<P>(
+        key: &'a SignedPublicKey,
+        at: DateTime<Utc>,
+        revokers: &[P],
+    ) -> Result<Self>
+    where
+        P: PublicKeyTrait,
+    {
+        let primary = &key.primary_key;
+
+        ensure!(*primary.created_at() <= at, "key was created after {}", at);
+        ensure!(
+            !key.details
+                .key_revocations(primary, revokers)
+                .into_iter()
+                .any(|sig| is_effective_revocation(sig, &at)),
+            "key is revoked"
+        );
+
+        let direct_signature = newest(
+            key.details
+                .direct_signatures
+                .iter()
+                .filter(|sig| sig.typ() == SignatureType::Key)
+                .filter(|sig| is_active(sig, &at) && sig.verify_key(primary).is_ok()),
+        );
+
+        let mut users: Vec<_> = key
+            .details
+            .users
+            .iter()
+            .filter_map(|user| ValidUserId::new(key, user, &at))
+            .collect();
+        // the primary User ID comes first, then the others by age of their certification
+        users.sort_by_key(|user| {
+            (
+                !user.binding.is_primary(),
+                std::cmp::Reverse(user.binding.created()),
+            )
+        });
+        ensure!(
+            direct_signature.is_some() || !users.is_empty(),
+            "no valid self-signature found"
+        );
+
+        let expiration = direct_signature
+            .and_then(|sig| sig.key_expiration_time())
+            .or_else(|| users.first().and_then(|u| u.binding.key_expiration_time()));
+        let expires_at = expires_at(primary.created_at(), expiration);
+        if let Some(expires_at) = expires_at {
+            ensure!(at < expires_at, "key expired at {}", expires_at);
+        }
+
+        let user_attributes = key
+            .details
+            .user_attributes
+            .iter()
+            .filter_map(|attr| ValidUserAttribute::new(key, attr, &at))
+            .collect();
+
+        let subkeys = key
+            .public_subkeys
+            .iter()
+            .filter_map(|subkey| ValidSubkey::new(key, subkey, &at))
+            .collect();
+
+        Ok(ValidCert {
+            key,
+            reference_time: at,
+            direct_signature,
+            users,
+            user_attributes,
+            subkeys,
+            expires_at,
+        })
+    }
+
+    /// The underlying key.
+    pub fn key(&self) -> &'a SignedPublicKey {
+        self.key
+    }
+
+    /// The time this validation refers to.
+    pub fn reference_time(&self) -> &DateTime<Utc> {
+        &self.reference_time
+    }
+
+    /// The newest valid direct key signature, if any.
+    pub fn direct_signature(&self) -> Option<&'a Signature> {
+        self.direct_signature
+    }
+
+    /// The primary User ID, if the key has any valid User ID.
+    pub fn primary_user_id(&self) -> Option<&ValidUserId<'a>> {
+        self.users.first()
+    }
+
+    /// All valid User IDs, starting with the primary one.
+    pub fn user_ids(&self) -> &[ValidUserId<'a>] {
+        &self.users
+    }
+
+    /// All valid User Attributes.
+    pub fn user_attributes(&self) -> &[ValidUserAttribute<'a>] {
+        &self.user_attributes
+    }
+
+    /// All valid subkeys.
+    pub fn subkeys(&self) -> &[ValidSubkey<'a>] {
+        &self.subkeys
+    }
+
+    /// The newest valid subkey, that can be used to encrypt messages or storage.
+    pub fn encryption_subkey(&self) -> Option<&ValidSubkey<'a>> {
+        self.newest_subkey(|flags| flags.encrypt_comms() || flags.encrypt_storage())
+    }
+
+    /// All valid subkeys, that can be used to encrypt messages or storage.
+    pub fn encryption_subkeys(&self) -> Vec<&ValidSubkey<'a>> {
+        self.subkeys
+            .iter()
+            .filter(|subkey| {
+                let flags = subkey.key_flags();
+                flags.encrypt_comms() || flags.encrypt_storage()
+            })
+            .collect()
+    }
+
+    /// The newest valid subkey, that can be used to sign data.
+    pub fn signing_subkey(&self) -> Option<&ValidSubkey<'a>> {
+        self.newest_subkey(|flags| flags.sign())
+    }
+
+    fn newest_subkey(&self, usable: impl Fn(KeyFlags) -> bool) -> Option<&ValidSubkey<'a>> {
+        self.subkeys
+            .iter()
+            .filter(|subkey| usable(subkey.key_flags()))
+            .max_by_key(|subkey| subkey.subkey.key.created_at())
+    }
+
+    /// The key flags of the primary key.
+    ///
+    /// They are taken from the direct key signature, if it carries any, and from the
+    /// certification of the primary User ID otherwise.
+    pub fn key_flags(&self) -> KeyFlags {
+        self.direct_signature
+            .map(|sig| sig.key_flags())
+            .filter(|flags| *flags != KeyFlags::default())
+            .or_else(|| self.users.first().map(|u| u.binding.key_flags()))
+            .unwrap_or_default()
+    }
+
+    /// The preferred symmetric algorithms of the key holder.
+    ///
+    /// Like the key flags, they are taken from the direct key signature if it lists any.
+    pub fn preferred_symmetric_algorithms(&self) -> &'a [SymmetricKeyAlgorithm] {
+        self.direct_signature
+            .map(|sig| sig.preferred_symmetric_algs())
+            .filter(|algs| !algs.is_empty())
+            .or_else(|| {
+                self.users
+                    .first()
+                    .map(|u| u.binding.preferred_symmetric_algs())
+            })
+            .unwrap_or_default()
+    }
+
+    /// The preferred compression algorithms of the key holder.
+    ///
+    /// Like the symmetric algorithms, they are taken from the direct key signature if it
+    /// lists any.
+    pub fn preferred_compression_algorithms(&self) -> &'a [CompressionAlgorithm] {
+        self.direct_signature
+            .map(|sig| sig.preferred_compression_algs())
+            .filter(|algs| !algs.is_empty())
+            .or_else(|| {
+                self.users
+                    .first()
+                    .map(|u| u.binding.preferred_compression_algs())
+            })
+            .unwrap_or_default()
+    }
+
+    /// The expiration of the primary key, if any.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_at
+    }
+}
+
+impl<'a> ValidUserId<'a> {
+    fn new(key: &'a SignedPublicKey, user: &'a SignedUser, at: &DateTime<Utc>) -> Option<Self> {
+        let binding = self_certification(key, &user.signatures, Tag::UserId, &user.id, at)?;
+
+        Some(ValidUserId { user, binding })
+    }
+
+    /// The User ID.
+    pub fn user(&self) -> &'a SignedUser {
+        self.user
+    }
+
+    /// The effective self-certification of the User ID.
+    pub fn binding_signature(&self) -> &'a Signature {
+        self.binding
+    }
+
+    /// Whether this User ID is marked as primary.
+    pub fn is_primary(&self) -> bool {
+        self.binding.is_primary()
+    }
+}
+
+impl<'a> ValidUserAttribute<'a> {
+    fn new(
+        key: &'a SignedPublicKey,
+        attr: &'a SignedUserAttribute,
+        at: &DateTime<Utc>,
+    ) -> Option<Self> {
+        let binding =
+            self_certification(key, &attr.signatures, Tag::UserAttribute, &attr.attr, at)?;
+
+        Some(ValidUserAttribute { attr, binding })
+    }
+
+    /// The User Attribute.
+    pub fn user_attribute(&self) -> &'a SignedUserAttribute {
+        self.attr
+    }
+
+    /// The effective self-certification of the User Attribute.
+    pub fn binding_signature(&self) -> &'a Signature {
+        self.binding
+    }
+}
+
+impl<'a> ValidSubkey<'a> {
+    fn new(
+        key: &'a SignedPublicKey,
+        subkey: &'a SignedPublicSubKey,
+        at: &DateTime<Utc>,
+    ) -> Option<Self> {
+        let primary = &key.primary_key;
+
+        if *subkey.key.created_at() > *at {
+            return None;
+        }
+
+        let revoked = subkey
+            .signatures
+            .iter()
+            .filter(|sig| sig.typ() == SignatureType::SubkeyRevocation)
+            .any(|sig| {
+                is_effective_revocation(sig, at)
+                    && verify_subkey_signature(sig, primary, &subkey.key).is_ok()
+            });
+        if revoked {
+            return None;
+        }
+
+        let binding = newest(
+            subkey
+                .signatures
+                .iter()
+                .filter(|sig| sig.typ() == SignatureType::SubkeyBinding)
+                .filter(|sig| {
+                    is_active(sig, at) && verify_subkey_signature(sig, primary, &subkey.key).is_ok()
+                }),
+        )?;
+
+        let expires_at = expires_at(subkey.key.created_at(), binding.key_expiration_time());
+        if matches!(expires_at, Some(expires_at) if expires_at <= *at) {
+            return None;
+        }
+
+        Some(ValidSubkey {
+            subkey,
+            binding,
+            expires_at,
+        })
+    }
+
+    /// The subkey.
+    pub fn subkey(&self) -> &'a SignedPublicSubKey {
+        self.subkey
+    }
+
+    /// The effective binding signature of the subkey.
+    pub fn binding_signature(&self) -> &'a Signature {
+        self.binding
+    }
+
+    /// The key flags of the subkey.
+    pub fn key_flags(&self) -> KeyFlags {
+        self.binding.key_flags()
+    }
+
+    /// The expiration of the subkey, if any.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_at
+    }
+}
+
+impl KeyTrait for ValidSubkey<'_> {
+    fn fingerprint(&self) -> crate::types::Fingerprint {
+        self.subkey.fingerprint()
+    }
+
+    fn key_id(&self) -> crate::types::KeyId {
+        self.subkey.key_id()
+    }
+
+    fn algorithm(&self) -> crate::crypto::public_key::PublicKeyAlgorithm {
+        self.subkey.algorithm()
+    }
+}
+
+/// Finds the newest self-certification of `id` in `signatures`, that is active at `at`.
+///
+/// Returns `None` if there is no such certification, or if it is revoked.
+fn self_certification<'a>(
+    key: &SignedPublicKey,
+    signatures: &'a [Signature],
+    tag: Tag,
+    id: &impl Serialize,
+    at: &DateTime<Utc>,
+) -> Option<&'a Signature> {
+    let primary = &key.primary_key;
+    let is_self_signature = |sig: &&Signature| {
+        is_issued_by(sig, primary) && sig.verify_certification(primary, tag, id).is_ok()
+    };
+
+    let binding = newest(
+        signatures
+            .iter()
+            .filter(|sig| sig.is_certification() && sig.typ() != SignatureType::CertRevocation)
+            .filter(|sig| is_active(sig, at))
+            .filter(is_self_signature),
+    )?;
+
+    // a revocation only applies to certifications made before it
+    let revoked = signatures
+        .iter()
+        .filter(|sig| sig.typ() == SignatureType::CertRevocation)
+        .filter(|sig| is_active(sig, at) && sig.created() >= binding.created())
+        .any(|sig| is_self_signature(&sig));
+    if revoked {
+        return None;
+    }
+
+    Some(binding)
+}
+
+/// Was `sig` created at or before `at`, and is it not yet expired?
+fn is_active(sig: &Signature, at: &DateTime<Utc>) -> bool {
+    sig.created().map(|created| created <= at).unwrap_or(false) && !sig.is_expired_at(at)
+}
+
+/// Does the revocation `sig` apply at `at`?
+///
+/// Revocations stating the key was superseded or retired only apply from their creation on,
+/// all other ones (e.g. for compromised keys) apply regardless of time.
+fn is_effective_revocation(sig: &Signature, at: &DateTime<Utc>) -> bool {
+    match sig.revocation_reason_code() {
+        Some(RevocationCode::KeySuperseded) | Some(RevocationCode::KeyRetired) => {
+            is_active(sig, at)
+        }
+        _ => true,
+    }
+}
+
+fn newest<'a>(sigs: impl Iterator<Item = &'a Signature>) -> Option<&'a Signature> {
+    sigs.max_by_key(|sig| sig.created())
+}
+
+/// Computes the expiration from the creation time and a Key Expiration Time.
+/// A value of zero means it never expires.
+fn expires_at(created: &DateTime<Utc>, expiration: Option<&Duration>) -> Option<DateTime<Utc>> {
+    expiration
+        .filter(|expiration| !expiration.is_zero())
+        .map(|expiration| *created + *expiration)
+}
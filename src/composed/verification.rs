@@ -0,0 +1,638 @@
+use chrono::{DateTime, Utc};
+
+use crate::composed::signed_key::{verify_subkey_signature, SignedPublicKey, SignedPublicSubKey};
+use crate::crypto::hash::HashAlgorithm;
+use crate::errors::{Error, Result};
+use crate::packet::{RevocationCode, Signature, SignatureType};
+use crate::policy::Policy;
+use crate::tofu::TrustEvent;
+use crate::types::{KeyId, KeyTrait, Tag};
+
+/// The outcome of verifying a single signature against a set of certificates.
+#[derive(Debug)]
+pub struct VerificationResult<'a> {
+    signature: Signature,
+    signer: Option<Signer<'a>>,
+    result: Result<()>,
+    revocations: Vec<Revocation>,
+    warnings: Vec<PolicyWarning>,
+    trust: Vec<(String, TrustEvent)>,
+}
+
+/// The key that issued a signature, and the certificate it belongs to.
+#[derive(Debug, Clone, Copy)]
+pub struct Signer<'a> {
+    cert: &'a SignedPublicKey,
+    subkey: Option<&'a SignedPublicSubKey>,
+}
+
+/// Concerns about a signature, that do not make it invalid on their own, but that callers may
+/// want to act on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PolicyWarning {
+    /// The signature uses a hash algorithm that the policy does not accept for it.
+    WeakHash(HashAlgorithm),
+    /// The signature has no creation time.
+    MissingCreationTime,
+    /// The signature is expired.
+    SignatureExpired,
+    /// The signing key, or its certificate, is expired.
+    KeyExpired,
+    /// The signing key, or its certificate, is revoked, but the revocation does not apply to
+    /// the signature, see [`Revocation`].
+    KeyRevoked,
+}
+
+/// A revocation of the key that issued a signature, or of the User ID it was issued as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Revocation {
+    target: RevocationTarget,
+    reason: Option<RevocationCode>,
+    created_at: Option<DateTime<Utc>>,
+}
+
+/// The component a [`Revocation`] applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RevocationTarget {
+    /// The certificate, including all of its subkeys.
+    Key,
+    /// The signing subkey.
+    Subkey,
+    /// The User ID named in the signature.
+    UserId,
+}
+
+impl<'a> VerificationResult<'a> {
+    /// Verifies `signature` over `data`, using the matching key in `certs`, at the reference
+    /// time `at`.
+    ///
+    /// Valid signatures, that are not accepted by `policy`, are reported as invalid.
+    pub(crate) fn new(
+        signature: &Signature,
+        data: &[u8],
+        certs: &[&'a SignedPublicKey],
+        policy: &Policy,
+        at: &DateTime<Utc>,
+    ) -> Self {
+        let mut signer = None;
+        let mut result = Err(Error::MissingKey);
+        for candidate in candidates(signature, certs) {
+            let res = match candidate.subkey {
+                Some(subkey) => signature.verify(subkey, data),
+                None => signature.verify(&candidate.cert.primary_key, data),
+            };
+            // keep the first candidate, unless a later one verifies
+            if signer.is_none() || res.is_ok() {
+                signer = Some(candidate);
+                result = res;
+            }
+            if result.is_ok() {
+                break;
+            }
+        }
+
+        if let (Ok(()), Some(signer)) = (&result, &signer) {
+            let params = match signer.subkey {
+                Some(subkey) => subkey.key.public_params(),
+                None => signer.cert.primary_key.public_params(),
+            };
+            result = policy
+                .check_creation_time(signature.created(), at)
+                .and_then(|_| policy.check_signature(signature))
+                .and_then(|_| policy.check_public_params(params));
+        }
+
+        let revocations = signer
+            .as_ref()
+            .map(|signer| revocations(signature, signer, certs))
+            .unwrap_or_default();
+        if result.is_ok() {
+            if let Some(revocation) = revocations
+                .iter()
+                .find(|r| r.invalidates(signature.created()))
+            {
+                result = Err(format_err!(
+                    "signing key revoked: {:?}",
+                    revocation.reason()
+                ));
+            }
+        }
+
+        let mut warnings = warnings(signature, signer.as_ref(), policy, at);
+        if result.is_ok()
+            && revocations
+                .iter()
+                .any(|r| r.target != RevocationTarget::UserId)
+        {
+            warnings.push(PolicyWarning::KeyRevoked);
+        }
+
+        VerificationResult {
+            signature: signature.clone(),
+            signer,
+            result,
+            revocations,
+            warnings,
+            trust: Vec::new(),
+        }
+    }
+
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// The key that issued the signature, `None` if it is not in the given certificates.
+    pub fn signer(&self) -> Option<&Signer<'a>> {
+        self.signer.as_ref()
+    }
+
+    pub fn created_at(&self) -> Option<&DateTime<Utc>> {
+        self.signature.created()
+    }
+
+    /// Whether the signature is cryptographically valid.
+    pub fn is_valid(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    /// The reason the signature could not be verified.
+    pub fn error(&self) -> Option<&Error> {
+        self.result.as_ref().err()
+    }
+
+    /// Revocations of the signing key, its certificate, or the signer's User ID, whether or
+    /// not they invalidate the signature.
+    pub fn revocations(&self) -> &[Revocation] {
+        &self.revocations
+    }
+
+    pub fn warnings(&self) -> &[PolicyWarning] {
+        &self.warnings
+    }
+
+    /// The [trust events](crate::tofu) for the email addresses of the signer, empty unless
+    /// the signature was checked against a [`TrustStore`](crate::tofu::TrustStore).
+    pub fn trust(&self) -> &[(String, TrustEvent)] {
+        &self.trust
+    }
+
+    pub(crate) fn set_trust(&mut self, trust: Vec<(String, TrustEvent)>) {
+        self.trust = trust;
+    }
+}
+
+/// Serialized as a summary, with the Key ID of the signer and the error message, if any.
+#[cfg(feature = "serde")]
+impl serde::Serialize for VerificationResult<'_> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("VerificationResult", 6)?;
+        state.serialize_field("valid", &self.is_valid())?;
+        state.serialize_field("error", &self.error().map(|err| err.to_string()))?;
+        state.serialize_field("signer", &self.signer.as_ref().map(|s| s.key_id()))?;
+        state.serialize_field("created", &self.created_at())?;
+        state.serialize_field("revocations", &self.revocations)?;
+        state.serialize_field("warnings", &self.warnings)?;
+        state.end()
+    }
+}
+
+impl Revocation {
+    pub fn target(&self) -> RevocationTarget {
+        self.target
+    }
+
+    pub fn reason(&self) -> Option<RevocationCode> {
+        self.reason
+    }
+
+    pub fn created_at(&self) -> Option<&DateTime<Utc>> {
+        self.created_at.as_ref()
+    }
+
+    /// Whether this is a hard revocation, e.g. because the key was compromised.
+    ///
+    /// Hard revocations invalidate all signatures, also past ones. Soft revocations, for keys
+    /// that were superseded or retired, only invalidate signatures made after them.
+    pub fn is_hard(&self) -> bool {
+        !matches!(
+            self.reason,
+            Some(RevocationCode::KeySuperseded)
+                | Some(RevocationCode::KeyRetired)
+                | Some(RevocationCode::CertUserIdInvalid)
+        )
+    }
+
+    /// Whether this revocation invalidates a signature created at `created`.
+    ///
+    /// User ID revocations never do, as they do not affect the key itself.
+    pub fn invalidates(&self, created: Option<&DateTime<Utc>>) -> bool {
+        if self.target == RevocationTarget::UserId {
+            return false;
+        }
+        if self.is_hard() {
+            return true;
+        }
+
+        match (&self.created_at, created) {
+            (Some(revoked), Some(created)) => revoked <= created,
+            _ => true,
+        }
+    }
+
+    fn new(target: RevocationTarget, sig: &Signature) -> Self {
+        Revocation {
+            target,
+            reason: sig.revocation_reason_code().copied(),
+            created_at: sig.created().copied(),
+        }
+    }
+}
+
+impl<'a> Signer<'a> {
+    /// The certificate of the signing key.
+    pub fn cert(&self) -> &'a SignedPublicKey {
+        self.cert
+    }
+
+    /// The signing subkey, `None` if the signature was made by the primary key.
+    pub fn subkey(&self) -> Option<&'a SignedPublicSubKey> {
+        self.subkey
+    }
+
+    /// The Key ID of the signing key.
+    pub fn key_id(&self) -> KeyId {
+        match self.subkey {
+            Some(subkey) => subkey.key_id(),
+            None => self.cert.key_id(),
+        }
+    }
+}
+
+/// All keys in `certs` that could have issued `signature`.
+///
+/// Subkeys are only considered, if they are bound to their certificate as signing keys.
+fn candidates<'a>(signature: &Signature, certs: &[&'a SignedPublicKey]) -> Vec<Signer<'a>> {
+    let mut candidates = Vec::new();
+    for &cert in certs {
+        if Signature::match_identity(signature, &cert.primary_key) {
+            candidates.push(Signer { cert, subkey: None });
+        }
+        for subkey in &cert.public_subkeys {
+            if Signature::match_identity(signature, subkey) && is_signing_subkey(cert, subkey) {
+                candidates.push(Signer {
+                    cert,
+                    subkey: Some(subkey),
+                });
+            }
+        }
+    }
+
+    candidates
+}
+
+/// Does the newest valid binding signature of `subkey` allow it to sign data?
+///
+/// The binding of a signing subkey must carry a valid back signature, see
+/// [`verify_subkey_signature`], so a certificate can not claim somebody else's key.
+fn is_signing_subkey(cert: &SignedPublicKey, subkey: &SignedPublicSubKey) -> bool {
+    binding(cert, subkey).is_some_and(|sig| sig.key_flags().sign())
+}
+
+/// The newest valid binding signature of `subkey`.
+fn binding<'a>(cert: &SignedPublicKey, subkey: &'a SignedPublicSubKey) -> Option<&'a Signature> {
+    subkey
+        .signatures
+        .iter()
+        .filter(|sig| sig.typ() == SignatureType::SubkeyBinding)
+        .filter(|sig| verify_subkey_signature(sig, &cert.primary_key, &subkey.key).is_ok())
+        .max_by_key(|sig| sig.created())
+}
+
+/// Revocations of the signing key, its certificate and the signer's User ID.
+///
+/// Revocations of the certificate are also accepted from its designated revokers, if they
+/// are part of `certs`.
+fn revocations(
+    signature: &Signature,
+    signer: &Signer<'_>,
+    certs: &[&SignedPublicKey],
+) -> Vec<Revocation> {
+    let primary = &signer.cert.primary_key;
+    let details = &signer.cert.details;
+    let mut revocations = Vec::new();
+
+    for sig in details.key_revocations(primary, certs) {
+        revocations.push(Revocation::new(RevocationTarget::Key, sig));
+    }
+
+    if let Some(subkey) = signer.subkey {
+        for sig in &subkey.signatures {
+            if sig.typ() == SignatureType::SubkeyRevocation
+                && verify_subkey_signature(sig, primary, &subkey.key).is_ok()
+            {
+                revocations.push(Revocation::new(RevocationTarget::Subkey, sig));
+            }
+        }
+    }
+
+    if let Some(user_id) = signature.signers_userid() {
+        for user in details.users.iter().filter(|u| u.id.id() == user_id) {
+            for sig in &user.signatures {
+                if sig.typ() == SignatureType::CertRevocation
+                    && sig
+                        .verify_certification(primary, Tag::UserId, &user.id)
+                        .is_ok()
+                {
+                    revocations.push(Revocation::new(RevocationTarget::UserId, sig));
+                }
+            }
+        }
+    }
+
+    revocations
+}
+
+fn warnings(
+    signature: &Signature,
+    signer: Option<&Signer<'_>>,
+    policy: &Policy,
+    at: &DateTime<Utc>,
+) -> Vec<PolicyWarning> {
+    let mut warnings = Vec::new();
+
+    let hash_alg = signature.config.hash_alg;
+    if policy.check_hash(hash_alg, signature.created()).is_err() {
+        warnings.push(PolicyWarning::WeakHash(hash_alg));
+    }
+    if signature.created().is_none() {
+        warnings.push(PolicyWarning::MissingCreationTime);
+    }
+    if signature.is_expired_at(at) {
+        warnings.push(PolicyWarning::SignatureExpired);
+    }
+
+    if let Some(signer) = signer {
+        let subkey_expired = signer.subkey.is_some_and(|subkey| {
+            binding(signer.cert, subkey)
+                .and_then(|sig| sig.key_expiration_time())
+                .filter(|expiration| !expiration.is_zero())
+                .is_some_and(|expiration| *subkey.key.created_at() + *expiration <= *at)
+        });
+        // a Key Expiration Time of zero means it never expires
+        let cert_expired = signer
+            .cert
+            .expires_at()
+            .filter(|expires_at| expires_at != signer.cert.primary_key.created_at())
+            .is_some_and(|expires_at| expires_at <= *at);
+        if cert_expired || subkey_expired {
+            warnings.push(PolicyWarning::KeyExpired);
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::composed::{KeyType, Message, SubkeyParamsBuilder};
+    use crate::test_util::{gen_key, key_params};
+
+    #[test]
+    fn test_verify_signatures() {
+        let alice = gen_key("Alice <alice@example.com>");
+        let bob = gen_key("Bob <bob@example.com>");
+        let alice_pub: SignedPublicKey = alice.clone().into();
+
+        let msg = Message::new_literal("hello.txt", "hello world\n")
+            .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+            .unwrap()
+            .sign(&bob, String::new, HashAlgorithm::SHA1)
+            .unwrap()
+            .compress(crate::types::CompressionAlgorithm::ZLIB)
+            .unwrap();
+
+        let results = msg.verify_signatures(&[&alice_pub]).unwrap();
+        assert_eq!(results.len(), 2);
+
+        // the outer signature is by bob, whose certificate is unknown
+        assert!(!results[0].is_valid());
+        assert!(matches!(results[0].error(), Some(Error::MissingKey)));
+        assert!(results[0].signer().is_none());
+        assert_eq!(
+            results[0].warnings(),
+            &[PolicyWarning::WeakHash(HashAlgorithm::SHA1)]
+        );
+
+        assert!(results[1].is_valid());
+        let signer = results[1].signer().unwrap();
+        assert_eq!(signer.cert().fingerprint(), alice.fingerprint());
+        assert!(signer.subkey().is_none());
+        assert_eq!(signer.key_id(), alice.key_id());
+        assert!(results[1].created_at().is_some());
+        assert!(results[1].warnings().is_empty());
+
+        let strict = crate::policy::PolicyBuilder::default()
+            .hash_algorithms(vec![HashAlgorithm::SHA2_512])
+            .build()
+            .unwrap();
+        let results = msg
+            .verify_signatures_with_policy(&[&alice_pub], &strict)
+            .unwrap();
+        assert!(!results[1].is_valid());
+        assert!(results[1].signer().is_some());
+        assert_eq!(
+            results[1].warnings(),
+            &[PolicyWarning::WeakHash(HashAlgorithm::SHA2_256)]
+        );
+
+        // SHA-1 is not weak to a policy that accepts it regardless of the signature's age
+        let sha1 = crate::policy::PolicyBuilder::default()
+            .sha1_cutoff(None)
+            .build()
+            .unwrap();
+        let results = msg
+            .verify_signatures_with_policy(&[&alice_pub], &sha1)
+            .unwrap();
+        assert!(results[0].warnings().is_empty());
+
+        // verifying at an earlier time
+        let created = *results[1].created_at().unwrap();
+        let results = msg
+            .verify_signatures_at(
+                &[&alice_pub],
+                &Policy::default(),
+                created - chrono::Duration::minutes(1),
+            )
+            .unwrap();
+        assert!(!results[1].is_valid());
+        let tolerant = crate::policy::PolicyBuilder::default()
+            .clock_skew_tolerance(chrono::Duration::minutes(2))
+            .build()
+            .unwrap();
+        let results = msg
+            .verify_signatures_at(
+                &[&alice_pub],
+                &tolerant,
+                created - chrono::Duration::minutes(1),
+            )
+            .unwrap();
+        assert!(results[1].is_valid());
+
+        let literal = Message::new_literal("hello.txt", "hello world\n");
+        assert!(literal.verify_signatures(&[&alice_pub]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_signing_subkey() {
+        use crate::packet::{Subpacket, SubpacketData};
+
+        let bob = key_params("Bob <bob@example.com>")
+            .subkey(
+                SubkeyParamsBuilder::default()
+                    .key_type(KeyType::EdDSA)
+                    .can_sign(true)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+            .generate_signed()
+            .unwrap();
+        let bob_pub: SignedPublicKey = bob.clone().into();
+        let msg = Message::new_literal("hello.txt", "hello world\n")
+            .sign(&bob.secret_subkeys[0], String::new, HashAlgorithm::SHA2_256)
+            .unwrap();
+
+        let results = msg.verify_signatures(&[&bob_pub]).unwrap();
+        assert!(results[0].is_valid());
+        let signer = results[0].signer().unwrap();
+        assert_eq!(signer.cert().fingerprint(), bob.fingerprint());
+        assert_eq!(signer.key_id(), bob.secret_subkeys[0].key_id());
+        assert!(results[0].warnings().is_empty());
+
+        // a newer binding that does not verify, expiring the subkey right away
+        let mut forged = bob_pub.clone();
+        let mut binding = forged.public_subkeys[0].signatures[0].clone();
+        binding.config.hashed_subpackets = vec![
+            Subpacket::regular(SubpacketData::SignatureCreationTime(
+                Utc::now() + chrono::Duration::days(1),
+            )),
+            Subpacket::regular(SubpacketData::KeyExpirationTime(chrono::Duration::seconds(
+                1,
+            ))),
+        ];
+        forged.public_subkeys[0].signatures.push(binding);
+        let results = msg
+            .verify_signatures_at(
+                &[&forged],
+                &Policy::default(),
+                Utc::now() + chrono::Duration::hours(1),
+            )
+            .unwrap();
+        assert!(results[0].is_valid());
+        assert!(results[0].warnings().is_empty());
+
+        // bob's subkey, appended to mallory's certificate without a binding by mallory
+        let mut mallory: SignedPublicKey = gen_key("Mallory <mallory@example.com>").into();
+        mallory
+            .public_subkeys
+            .push(bob_pub.public_subkeys[0].clone());
+        let results = msg.verify_signatures(&[&mallory]).unwrap();
+        assert!(!results[0].is_valid());
+        assert!(matches!(results[0].error(), Some(Error::MissingKey)));
+        assert!(results[0].signer().is_none());
+    }
+
+    #[test]
+    fn test_revoked_signer() {
+        let alice = gen_key("Alice <alice@example.com>");
+        let msg = Message::new_literal("hello.txt", "hello world\n")
+            .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+            .unwrap();
+
+        let compromised: SignedPublicKey = alice
+            .revoke(RevocationCode::KeyCompromised, "", String::new)
+            .unwrap()
+            .into();
+        let results = msg.verify_signatures(&[&compromised]).unwrap();
+        assert!(!results[0].is_valid());
+        let revocation = &results[0].revocations()[0];
+        assert_eq!(revocation.target(), RevocationTarget::Key);
+        assert_eq!(revocation.reason(), Some(RevocationCode::KeyCompromised));
+        assert!(revocation.is_hard());
+
+        // soft revocations only apply to signatures made after them
+        let created = *results[0].created_at().unwrap();
+        let soft = |target, created_at| Revocation {
+            target,
+            reason: Some(RevocationCode::KeySuperseded),
+            created_at: Some(created_at),
+        };
+        let later = created + chrono::Duration::days(1);
+        assert!(!soft(RevocationTarget::Key, later).invalidates(Some(&created)));
+        assert!(soft(RevocationTarget::Key, created).invalidates(Some(&created)));
+        assert!(soft(RevocationTarget::Subkey, later).invalidates(None));
+        assert!(!soft(RevocationTarget::UserId, created).invalidates(Some(&created)));
+    }
+
+    #[test]
+    fn test_designated_revoker() {
+        use crate::packet::{SignatureConfigBuilder, Subpacket, SubpacketData};
+        use crate::types::{RevocationKey, RevocationKeyClass};
+
+        let revoker = gen_key("Revoker <revoker@example.com>");
+        let alice = key_params("Alice <alice@example.com>")
+            .revocation_key(Some(RevocationKey::new(
+                RevocationKeyClass::Default,
+                revoker.algorithm(),
+                revoker.fingerprint(),
+            )))
+            .build()
+            .unwrap()
+            .generate_signed()
+            .unwrap();
+        let msg = Message::new_literal("hello.txt", "hello world\n")
+            .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+            .unwrap();
+
+        let revocation = SignatureConfigBuilder::default()
+            .typ(SignatureType::KeyRevocation)
+            .pub_alg(revoker.algorithm())
+            .hashed_subpackets(vec![
+                Subpacket::regular(SubpacketData::SignatureCreationTime(Utc::now())),
+                Subpacket::regular(SubpacketData::RevocationReason(
+                    RevocationCode::KeyCompromised,
+                    "lost".into(),
+                )),
+                Subpacket::regular(SubpacketData::IssuerFingerprint(revoker.fingerprint())),
+            ])
+            .unhashed_subpackets(vec![])
+            .build()
+            .unwrap()
+            .sign_key(&revoker.primary_key, String::new, &alice.primary_key)
+            .unwrap();
+        let mut revoked: SignedPublicKey = alice.into();
+        revoked.details.revocation_signatures.push(revocation);
+        let revoker: SignedPublicKey = revoker.into();
+
+        // the revocation is only known to be valid given the revoker's certificate
+        let results = msg.verify_signatures(&[&revoked]).unwrap();
+        assert!(results[0].is_valid());
+        assert!(results[0].revocations().is_empty());
+
+        let results = msg.verify_signatures(&[&revoked, &revoker]).unwrap();
+        assert!(!results[0].is_valid());
+        assert_eq!(results[0].revocations()[0].target(), RevocationTarget::Key);
+    }
+}
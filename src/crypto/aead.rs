@@ -4,6 +4,7 @@ use num_enum::TryFromPrimitive;
 #[derive(Debug, PartialEq, Eq, Copy, Clone, TryFromPrimitive)]
 #[repr(u8)]
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AeadAlgorithm {
     /// None
     #[default]
@@ -4,14 +4,19 @@ use generic_array::sequence::{Concat, Split};
 use generic_array::typenum::U8;
 use generic_array::GenericArray;
 
+use crate::crypto::checksum;
 use crate::errors::Result;
 
 const IV: [u8; 8] = [0xA6, 0xA6, 0xA6, 0xA6, 0xA6, 0xA6, 0xA6, 0xA6];
 
 /// AES Key Wrap
 /// As defined in RFC 3394.
+///
+/// The key encryption key must be 128, 192 or 256 bits long, and the data must
+/// consist of at least one 64bit block.
 pub fn wrap(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
     ensure_eq!(data.len() % 8, 0, "data must be a multiple of 64bit");
+    ensure!(!data.is_empty(), "data must not be empty");
 
     let aes_size = key.len() * 8;
     match aes_size {
@@ -24,8 +29,12 @@ pub fn wrap(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
 
 /// AES Key Unwrap
 /// As defined in RFC 3394.
+///
+/// The key encryption key must be 128, 192 or 256 bits long, and the wrapped
+/// data must consist of at least two 64bit blocks.
 pub fn unwrap(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
     ensure_eq!(data.len() % 8, 0, "data must be a multiple of 64bit");
+    ensure!(data.len() >= 16, "wrapped data must be at least 128bit");
 
     let aes_size = key.len() * 8;
     match aes_size {
@@ -37,7 +46,7 @@ pub fn unwrap(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
 }
 
 macro_rules! impl_aes_kw {
-    ($name_wrap:ident, $name_unwrap:ident, $size:expr, $hasher:ty) => {
+    ($name_wrap:ident, $name_unwrap:ident, $hasher:ty) => {
         #[inline]
         fn $name_wrap(key: &[u8], data: &[u8]) -> Vec<u8> {
             // 0) Prepare inputs
@@ -100,7 +109,7 @@ macro_rules! impl_aes_kw {
             let n = (data.len() / 8) - 1;
 
             let c: Vec<_> = data.chunks(8).map(|chunk|{
-                GenericArray::<u8, _>::clone_from_slice(chunk)
+                GenericArray::<u8, U8>::clone_from_slice(chunk)
             }).collect();
 
             let key = GenericArray::from_slice(key);
@@ -145,7 +154,7 @@ macro_rules! impl_aes_kw {
 
             // 3) output the results
 
-            if &a == GenericArray::<u8, U8>::from_slice(&IV) {
+            if checksum::ct_eq(&a, &IV) {
                 Ok(r.iter().fold(Vec::with_capacity(r.len() * 8), |mut acc, v| {
                     acc.extend(v);
                     acc
@@ -157,12 +166,14 @@ macro_rules! impl_aes_kw {
     };
 }
 
-impl_aes_kw!(wrap_128, unwrap_128, 128, aes::Aes128);
-impl_aes_kw!(wrap_192, unwrap_192, 192, aes::Aes192);
-impl_aes_kw!(wrap_256, unwrap_256, 256, aes::Aes256);
+impl_aes_kw!(wrap_128, unwrap_128, aes::Aes128);
+impl_aes_kw!(wrap_192, unwrap_192, aes::Aes192);
+impl_aes_kw!(wrap_256, unwrap_256, aes::Aes256);
 
 #[cfg(test)]
 mod tests {
+    #![allow(clippy::unwrap_used)]
+
     use super::*;
 
     macro_rules! test_aes_kw {
@@ -225,4 +236,52 @@ mod tests {
         "00112233445566778899AABBCCDDEEFF000102030405060708090A0B0C0D0E0F",
         "28C9F404C4B810F4CBCCB35CFB87F8263F5786E2D80ED326CBC7F0E71A99F43BFB988B9B7A02DD21"
     );
+
+    #[test]
+    fn invalid_kek_size() {
+        let kek = [0u8; 20];
+        let data = [0u8; 16];
+
+        assert!(wrap(&kek, &data).is_err());
+        assert!(unwrap(&kek, &[0u8; 24]).is_err());
+    }
+
+    #[test]
+    fn invalid_data_size() {
+        for kek in [&[0u8; 16][..], &[0u8; 24][..], &[0u8; 32][..]] {
+            // not a multiple of 64bit
+            assert!(wrap(kek, &[0u8; 17]).is_err());
+            assert!(unwrap(kek, &[0u8; 25]).is_err());
+
+            // too short
+            assert!(wrap(kek, &[]).is_err());
+            assert!(unwrap(kek, &[]).is_err());
+            assert!(unwrap(kek, &[0u8; 8]).is_err());
+        }
+    }
+
+    #[test]
+    fn unwrap_integrity_check() {
+        for (kek, wrapped) in [
+            (
+                "000102030405060708090A0B0C0D0E0F",
+                "1FA68B0A8112B447AEF34BD8FB5A7B829D3E862371D2CFE5",
+            ),
+            (
+                "000102030405060708090A0B0C0D0E0F1011121314151617",
+                "031D33264E15D33268F24EC260743EDCE1C6C7DDEE725A936BA814915C6762D2",
+            ),
+            (
+                "000102030405060708090A0B0C0D0E0F101112131415161718191A1B1C1D1E1F",
+                "28C9F404C4B810F4CBCCB35CFB87F8263F5786E2D80ED326CBC7F0E71A99F43BFB988B9B7A02DD21",
+            ),
+        ] {
+            let kek = hex::decode(kek).unwrap();
+            let mut wrapped = hex::decode(wrapped).unwrap();
+            let last = wrapped.len() - 1;
+            wrapped[last] ^= 0x01;
+
+            assert!(unwrap(&kek, &wrapped).is_err());
+        }
+    }
 }
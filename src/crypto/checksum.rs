@@ -3,6 +3,7 @@ use std::io;
 
 use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
 use sha1::{Digest, Sha1};
+use subtle::ConstantTimeEq;
 
 use crate::errors::Result;
 
@@ -14,15 +15,28 @@ pub fn simple(actual: &[u8], data: &[u8]) -> Result<()> {
     // identifier, modulo 65536.
     let expected_checksum = calculate_simple(data);
 
-    ensure_eq!(
-        &actual[..2],
-        &expected_checksum.to_be_bytes()[..],
+    ensure!(
+        ct_eq(&actual[..2], &expected_checksum.to_be_bytes()),
         "invalid simple checksum"
     );
 
     Ok(())
 }
 
+/// Compares `a` and `b` in constant time, to not leak how much of checksums, integrity checks
+/// and secret values matched. Only their lengths are not kept secret.
+#[inline]
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// Checks a Modification Detection Code packet, the `0xD3 0x14` header followed by the SHA-1
+/// hash, against the hash `sha1` of the data and the header.
+pub(crate) fn check_mdc(mdc: &[u8], sha1: &[u8]) -> bool {
+    let expected = [&[0xD3, 0x14][..], sha1].concat();
+    ct_eq(mdc, &expected)
+}
+
 #[inline]
 pub fn simple_to_writer<W: io::Write>(data: &[u8], writer: &mut W) -> io::Result<()> {
     let mut hasher = SimpleChecksum::default();
@@ -93,3 +107,31 @@ where
     }
     digest.finalize()[..20].to_vec()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple() {
+        let data = [1u8, 2, 3, 0xff];
+        assert_eq!(calculate_simple(&data), 0x0105);
+        assert!(simple(&[0x01, 0x05], &data).is_ok());
+        assert!(simple(&[0x01, 0x06], &data).is_err());
+
+        assert!(ct_eq(&[1, 2, 3], &[1, 2, 3]));
+        assert!(!ct_eq(&[1, 2, 3], &[1, 2, 4]));
+        assert!(!ct_eq(&[1, 2, 3], &[1, 2]));
+    }
+
+    #[test]
+    fn test_check_mdc() {
+        let sha1 = calculate_sha1([&b"hello"[..], &[0xD3, 0x14]]);
+        let mut mdc = [&[0xD3, 0x14][..], &sha1].concat();
+        assert!(check_mdc(&mdc, &sha1));
+
+        mdc[0] = 0xD4;
+        assert!(!check_mdc(&mdc, &sha1));
+        assert!(!check_mdc(&mdc[..21], &sha1));
+    }
+}
@@ -1,6 +1,7 @@
 use block_padding::{Padding, Pkcs7};
 use generic_array::{typenum::U8, GenericArray};
 use rand::{CryptoRng, Rng};
+use subtle::{Choice, ConstantTimeEq};
 use x25519_dalek::{PublicKey, StaticSecret};
 use zeroize::{Zeroize, Zeroizing};
 
@@ -26,7 +27,13 @@ pub fn generate_key<R: Rng + CryptoRng>(rng: &mut R) -> (PublicParams, PlainSecr
     let mut secret_key_bytes = Zeroizing::new([0u8; SECRET_KEY_LENGTH]);
     rng.fill_bytes(&mut *secret_key_bytes);
 
-    let secret = StaticSecret::from(*secret_key_bytes);
+    import_key(&secret_key_bytes)
+}
+
+/// Converts an existing X25519 secret key, in its native little endian form, into the
+/// OpenPGP key parameters.
+pub fn import_key(secret: &[u8; SECRET_KEY_LENGTH]) -> (PublicParams, PlainSecretParams) {
+    let secret = StaticSecret::from(*secret);
     let public = PublicKey::from(&secret);
 
     // public key
@@ -88,19 +95,18 @@ pub fn build_ecdh_param(
 }
 
 /// ECDH decryption.
-pub fn decrypt(priv_key: &ECDHSecretKey, mpis: &[Mpi], fingerprint: &[u8]) -> Result<Vec<u8>> {
+pub fn decrypt(
+    priv_key: &ECDHSecretKey,
+    mpis: &[Mpi],
+    fingerprint: &[u8],
+) -> Result<Zeroizing<Vec<u8>>> {
     debug!("ECDH decrypt");
 
-    let param = build_ecdh_param(&priv_key.oid, priv_key.alg_sym, priv_key.hash, fingerprint);
-
     // 33 = 0x40 + 32bits
     ensure_eq!(mpis.len(), 3);
     ensure_eq!(mpis[0].len(), 33, "invalid public point");
     ensure_eq!(priv_key.secret.len(), 32, "invalid secret point");
 
-    // encrypted and wrapped value derived from the session key
-    let encrypted_session_key = mpis[2].as_bytes();
-
     let their_public = {
         // public part of the ephemeral key (removes 0x40 prefix)
         let ephemeral_public_key = &mpis[0].as_bytes()[1..];
@@ -128,13 +134,38 @@ pub fn decrypt(priv_key: &ECDHSecretKey, mpis: &[Mpi], fingerprint: &[u8]) -> Re
     // derive shared secret
     let shared_secret = our_secret.diffie_hellman(&their_public);
 
-    // Perform key derivation
-    let z = kdf(
-        priv_key.hash,
+    unwrap_session_key(
         shared_secret.as_bytes(),
-        priv_key.alg_sym.key_size(),
-        &param,
-    )?;
+        &priv_key.oid,
+        priv_key.hash,
+        priv_key.alg_sym,
+        mpis,
+        fingerprint,
+    )
+}
+
+/// Derives the key encryption key from the `shared_secret` and unwraps the session key in
+/// `mpis` with it.
+///
+/// This is the part of ECDH decryption after the key agreement, which may happen elsewhere,
+/// e.g. on a smartcard.
+pub fn unwrap_session_key(
+    shared_secret: &[u8; 32],
+    oid: &[u8],
+    hash: HashAlgorithm,
+    alg_sym: SymmetricKeyAlgorithm,
+    mpis: &[Mpi],
+    fingerprint: &[u8],
+) -> Result<Zeroizing<Vec<u8>>> {
+    ensure_eq!(mpis.len(), 3);
+
+    let param = build_ecdh_param(oid, alg_sym, hash, fingerprint);
+
+    // encrypted and wrapped value derived from the session key
+    let encrypted_session_key = mpis[2].as_bytes();
+
+    // Perform key derivation
+    let z = kdf(hash, shared_secret, alg_sym.key_size(), &param)?;
 
     // Peform AES Key Unwrap
     let encrypted_key_len: usize = match mpis[1].first() {
@@ -146,7 +177,7 @@ pub fn decrypt(priv_key: &ECDHSecretKey, mpis: &[Mpi], fingerprint: &[u8]) -> Re
     encrypted_session_key_vec[(encrypted_key_len - encrypted_session_key.len())..]
         .copy_from_slice(encrypted_session_key);
 
-    let mut decrypted_key_padded = aes_kw::unwrap(&z, &encrypted_session_key_vec)?;
+    let mut decrypted_key_padded = Zeroizing::new(aes_kw::unwrap(&z, &encrypted_session_key_vec)?);
     // PKCS5-style unpadding (PKCS5 is PKCS7 with a blocksize of 8).
     //
     // RFC 6637 describes the padding:
@@ -174,11 +205,12 @@ pub fn decrypt(priv_key: &ECDHSecretKey, mpis: &[Mpi], fingerprint: &[u8]) -> Re
         // Expected length of the unpadded message
         let unpadded_len = len - *pad as usize;
 
-        // All bytes that constitute the padding must have the value of `pad`
-        if decrypted_key_padded[unpadded_len..]
+        // All bytes that constitute the padding must have the value of `pad`, checked in
+        // constant time to not give away where the padding is wrong
+        let valid_padding = decrypted_key_padded[unpadded_len..]
             .iter()
-            .any(|byte| byte != pad)
-        {
+            .fold(Choice::from(1), |valid, byte| valid & byte.ct_eq(pad));
+        if !bool::from(valid_padding) {
             return Err(Error::UnpadError);
         }
 
@@ -195,13 +227,18 @@ pub fn decrypt(priv_key: &ECDHSecretKey, mpis: &[Mpi], fingerprint: &[u8]) -> Re
 
 /// Key Derivation Function for ECDH (as defined in RFC 6637).
 /// https://tools.ietf.org/html/rfc6637#section-7
-fn kdf(hash: HashAlgorithm, x: &[u8; 32], length: usize, param: &[u8]) -> Result<Vec<u8>> {
+fn kdf(
+    hash: HashAlgorithm,
+    x: &[u8; 32],
+    length: usize,
+    param: &[u8],
+) -> Result<Zeroizing<Vec<u8>>> {
     let prefix = vec![0, 0, 0, 1];
 
     let values: Vec<&[u8]> = vec![&prefix, x, param];
-    let data = values.concat();
+    let data = Zeroizing::new(values.concat());
 
-    let mut digest = hash.digest(&data)?;
+    let mut digest = Zeroizing::new(hash.digest(&data)?);
     digest.truncate(length);
 
     Ok(digest)
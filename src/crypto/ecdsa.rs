@@ -1,5 +1,13 @@
+// without any curve, the parameters of the stubs are unused
+#![cfg_attr(
+    not(any(feature = "nist-curves", feature = "secp256k1")),
+    allow(unused_variables)
+)]
+
+#[cfg(any(feature = "nist-curves", feature = "secp256k1"))]
 use elliptic_curve::sec1::ToEncodedPoint;
 use rand::{CryptoRng, Rng};
+#[cfg(any(feature = "nist-curves", feature = "secp256k1"))]
 use signature::hazmat::{PrehashSigner, PrehashVerifier};
 
 use crate::crypto::ecc_curve::ECCCurve;
@@ -14,12 +22,28 @@ pub fn generate_key<R: Rng + CryptoRng>(
     curve: &ECCCurve,
 ) -> Result<(PublicParams, PlainSecretParams)> {
     match curve {
+        #[cfg(feature = "nist-curves")]
+        ECCCurve::P256 => import_key(curve, &p256::SecretKey::random(rng).to_bytes()),
+        #[cfg(feature = "nist-curves")]
+        ECCCurve::P384 => import_key(curve, &p384::SecretKey::random(rng).to_bytes()),
+        #[cfg(feature = "secp256k1")]
+        ECCCurve::Secp256k1 => import_key(curve, &k256::SecretKey::random(rng).to_bytes()),
+        _ => unsupported_err!("curve {:?} for ECDSA", curve),
+    }
+}
+
+/// Converts an existing ECDSA secret key, given as its big endian scalar, into the OpenPGP key
+/// parameters.
+pub fn import_key(curve: &ECCCurve, secret: &[u8]) -> Result<(PublicParams, PlainSecretParams)> {
+    match curve {
+        #[cfg(feature = "nist-curves")]
         ECCCurve::P256 => {
-            let secret = p256::SecretKey::random(rng);
+            let secret = p256::SecretKey::from_slice(secret)?;
             let public = secret.public_key();
             let secret = Mpi::from_raw_slice(secret.to_bytes().as_slice());
 
             Ok((
+                #[cfg(feature = "nist-curves")]
                 PublicParams::ECDSA(EcdsaPublicParams::P256 {
                     key: public,
                     p: Mpi::from_raw_slice(public.to_encoded_point(false).as_bytes()),
@@ -28,12 +52,14 @@ pub fn generate_key<R: Rng + CryptoRng>(
             ))
         }
 
+        #[cfg(feature = "nist-curves")]
         ECCCurve::P384 => {
-            let secret = p384::SecretKey::random(rng);
+            let secret = p384::SecretKey::from_slice(secret)?;
             let public = secret.public_key();
             let secret = Mpi::from_raw_slice(secret.to_bytes().as_slice());
 
             Ok((
+                #[cfg(feature = "nist-curves")]
                 PublicParams::ECDSA(EcdsaPublicParams::P384 {
                     key: public,
                     p: Mpi::from_raw_slice(public.to_encoded_point(false).as_bytes()),
@@ -42,8 +68,9 @@ pub fn generate_key<R: Rng + CryptoRng>(
             ))
         }
 
+        #[cfg(feature = "secp256k1")]
         ECCCurve::Secp256k1 => {
-            let secret = k256::SecretKey::random(rng);
+            let secret = k256::SecretKey::from_slice(secret)?;
             let public = secret.public_key();
             let secret = Mpi::from_raw_slice(secret.to_bytes().as_slice());
 
@@ -68,6 +95,7 @@ pub fn verify(
     sig: &[Mpi],
 ) -> Result<()> {
     match p {
+        #[cfg(feature = "nist-curves")]
         EcdsaPublicParams::P256 { key, .. } => {
             const FLEN: usize = 32;
             ensure_eq!(sig.len(), 2);
@@ -88,6 +116,7 @@ pub fn verify(
 
             Ok(())
         }
+        #[cfg(feature = "nist-curves")]
         EcdsaPublicParams::P384 { key, .. } => {
             const FLEN: usize = 48;
             ensure_eq!(sig.len(), 2);
@@ -111,6 +140,7 @@ pub fn verify(
 
             Ok(())
         }
+        #[cfg(feature = "secp256k1")]
         EcdsaPublicParams::Secp256k1 { key, .. } => {
             const FLEN: usize = 32;
             ensure_eq!(sig.len(), 2);
@@ -143,29 +173,30 @@ pub fn sign(
     _hash: HashAlgorithm,
     digest: &[u8],
 ) -> Result<Vec<Vec<u8>>> {
-    let (r, s) = match secret_key {
+    match secret_key {
+        #[cfg(feature = "nist-curves")]
         ECDSASecretKey::P256(secret_key) => {
             let secret = p256::ecdsa::SigningKey::from(secret_key);
             let signature: p256::ecdsa::Signature = secret.sign_prehash(digest)?;
             let (r, s) = signature.split_bytes();
-            (r.to_vec(), s.to_vec())
+            Ok(vec![r.to_vec(), s.to_vec()])
         }
+        #[cfg(feature = "nist-curves")]
         ECDSASecretKey::P384(secret_key) => {
             let secret = p384::ecdsa::SigningKey::from(secret_key);
             let signature: p384::ecdsa::Signature = secret.sign_prehash(digest)?;
             let (r, s) = signature.split_bytes();
-            (r.to_vec(), s.to_vec())
+            Ok(vec![r.to_vec(), s.to_vec()])
         }
+        #[cfg(feature = "secp256k1")]
         ECDSASecretKey::Secp256k1(secret_key) => {
             let secret = k256::ecdsa::SigningKey::from(secret_key);
             let signature: k256::ecdsa::Signature = secret.sign_prehash(digest)?;
             let (r, s) = signature.split_bytes();
-            (r.to_vec(), s.to_vec())
+            Ok(vec![r.to_vec(), s.to_vec()])
         }
         ECDSASecretKey::Unsupported { curve, .. } => {
             unsupported_err!("curve {:?} for ECDSA", curve)
         }
-    };
-
-    Ok(vec![r, s])
+    }
 }
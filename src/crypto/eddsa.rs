@@ -11,7 +11,16 @@ use crate::types::{EdDSASecretKey, Mpi, PlainSecretParams, PublicParams};
 pub fn generate_key<R: Rng + CryptoRng>(rng: &mut R) -> (PublicParams, PlainSecretParams) {
     let mut bytes = Zeroizing::new([0u8; ed25519_dalek::SECRET_KEY_LENGTH]);
     rng.fill_bytes(&mut *bytes);
-    let secret = ed25519_dalek::SigningKey::from_bytes(&bytes);
+    let params = import_key(&bytes);
+    bytes.zeroize();
+
+    params
+}
+
+/// Converts an existing Ed25519 secret key, given as its 32 byte seed, into the OpenPGP key
+/// parameters.
+pub fn import_key(seed: &[u8; 32]) -> (PublicParams, PlainSecretParams) {
+    let secret = ed25519_dalek::SigningKey::from_bytes(seed);
     let public = ed25519_dalek::VerifyingKey::from(&secret);
 
     // public key
@@ -21,7 +30,6 @@ pub fn generate_key<R: Rng + CryptoRng>(rng: &mut R) -> (PublicParams, PlainSecr
 
     // secret key
     let p = Mpi::from_raw_slice(&secret.to_bytes());
-    bytes.zeroize();
 
     (
         PublicParams::EdDSA {
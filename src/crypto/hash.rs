@@ -11,6 +11,7 @@ use crate::errors::Result;
 /// Available hash algorithms.
 /// Ref: https://tools.ietf.org/html/rfc4880.html#section-9.4
 #[derive(Debug, PartialEq, Eq, Copy, Clone, FromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum HashAlgorithm {
     None = 0,
@@ -102,7 +103,7 @@ impl HashAlgorithm {
             HashAlgorithm::SHA2_224 => Ok(Box::<Sha2_224Hasher>::default()),
             HashAlgorithm::SHA3_256 => Ok(Box::<Sha3_256Hasher>::default()),
             HashAlgorithm::SHA3_512 => Ok(Box::<Sha3_512Hasher>::default()),
-            _ => unimplemented_err!("hasher {:?}", self),
+            _ => unsupported_alg_err!(Hash, self),
         }
     }
 
@@ -120,7 +121,7 @@ impl HashAlgorithm {
             HashAlgorithm::SHA3_512 => sha3::Sha3_512::digest(data).to_vec(),
 
             HashAlgorithm::Private10 => unsupported_err!("Private10 should not be used"),
-            _ => unimplemented_err!("hasher: {:?}", self),
+            _ => unsupported_alg_err!(Hash, self),
         })
     }
 
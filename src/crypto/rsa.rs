@@ -5,6 +5,7 @@ use num_bigint::BigUint;
 use rand::{CryptoRng, Rng};
 use ripemd::Ripemd160;
 use rsa::pkcs1v15::{Pkcs1v15Encrypt, Signature as RsaSignature, SigningKey, VerifyingKey};
+use rsa::Pkcs1v15Sign;
 use rsa::{
     traits::{PrivateKeyParts, PublicKeyParts},
     RsaPrivateKey, RsaPublicKey,
@@ -14,6 +15,7 @@ use sha2::{Sha224, Sha256, Sha384, Sha512};
 use sha3::{Sha3_256, Sha3_512};
 use signature::hazmat::{PrehashSigner, PrehashVerifier};
 use signature::SignatureEncoding;
+use zeroize::Zeroizing;
 
 use crate::crypto::hash::HashAlgorithm;
 use crate::errors::Result;
@@ -22,14 +24,18 @@ use crate::types::{Mpi, PlainSecretParams, PublicParams};
 const MAX_KEY_SIZE: usize = 16384;
 
 /// RSA decryption using PKCS1v15 padding.
-pub fn decrypt(priv_key: &RsaPrivateKey, mpis: &[Mpi], _fingerprint: &[u8]) -> Result<Vec<u8>> {
+pub fn decrypt(
+    priv_key: &RsaPrivateKey,
+    mpis: &[Mpi],
+    _fingerprint: &[u8],
+) -> Result<Zeroizing<Vec<u8>>> {
     // rsa consist of exactly one mpi
     ensure_eq!(mpis.len(), 1, "invalid input");
 
     let mpi = &mpis[0];
     let m = priv_key.decrypt(Pkcs1v15Encrypt, mpi.as_bytes())?;
 
-    Ok(m)
+    Ok(Zeroizing::new(m))
 }
 
 /// RSA encryption using PKCS1v15 padding.
@@ -55,15 +61,23 @@ pub fn generate_key<R: Rng + CryptoRng>(
     bit_size: usize,
 ) -> Result<(PublicParams, PlainSecretParams)> {
     let key = RsaPrivateKey::new(rng, bit_size)?;
+    import_key(&key)
+}
 
+/// Converts an existing RSA key into the OpenPGP key parameters.
+pub fn import_key(key: &RsaPrivateKey) -> Result<(PublicParams, PlainSecretParams)> {
+    ensure_eq!(
+        key.primes().len(),
+        2,
+        "multi-prime RSA keys are not supported"
+    );
     let p = &key.primes()[0];
     let q = &key.primes()[1];
     let u = p
         .clone()
         .mod_inverse(q)
-        .expect("invalid prime")
-        .to_biguint()
-        .expect("invalid prime");
+        .and_then(|u| u.to_biguint())
+        .ok_or_else(|| format_err!("invalid prime"))?;
 
     Ok((
         PublicParams::RSA {
@@ -97,6 +111,32 @@ where
         .map_err(Into::into)
 }
 
+/// Encodes `digest` as the DigestInfo structure, that PKCS1v15 signatures are computed over,
+/// e.g. by a smartcard.
+pub fn digest_info(hash: HashAlgorithm, digest: &[u8]) -> Result<Vec<u8>> {
+    let padding = match hash {
+        HashAlgorithm::None => return Err(format_err!("none")),
+        HashAlgorithm::MD5 => Pkcs1v15Sign::new::<Md5>(),
+        HashAlgorithm::RIPEMD160 => Pkcs1v15Sign::new::<Ripemd160>(),
+        HashAlgorithm::SHA1 => Pkcs1v15Sign::new::<Sha1>(),
+        HashAlgorithm::SHA2_224 => Pkcs1v15Sign::new::<Sha224>(),
+        HashAlgorithm::SHA2_256 => Pkcs1v15Sign::new::<Sha256>(),
+        HashAlgorithm::SHA2_384 => Pkcs1v15Sign::new::<Sha384>(),
+        HashAlgorithm::SHA2_512 => Pkcs1v15Sign::new::<Sha512>(),
+        HashAlgorithm::SHA3_256 => Pkcs1v15Sign::new::<Sha3_256>(),
+        HashAlgorithm::SHA3_512 => Pkcs1v15Sign::new::<Sha3_512>(),
+        HashAlgorithm::Private10 => unsupported_err!("Private10 should not be used"),
+        HashAlgorithm::Other(_) => unsupported_alg_err!(Hash, hash),
+    };
+    ensure_eq!(
+        Some(digest.len()),
+        padding.hash_len,
+        "invalid digest length"
+    );
+
+    Ok([&padding.prefix[..], digest].concat())
+}
+
 /// Verify a RSA, PKCS1v15 padded signature.
 pub fn verify(
     n: &[u8],
@@ -134,7 +174,7 @@ pub fn verify(
         HashAlgorithm::SHA3_256 => verify_int::<Sha3_256>(key, hashed, &signature),
         HashAlgorithm::SHA3_512 => verify_int::<Sha3_512>(key, hashed, &signature),
         HashAlgorithm::Private10 => unsupported_err!("Private10 should not be used"),
-        HashAlgorithm::Other(o) => unsupported_err!("Hash algorithm {} is unsupported", o),
+        HashAlgorithm::Other(_) => unsupported_alg_err!(Hash, hash),
     }
     .map_err(Into::into)
 }
@@ -153,7 +193,7 @@ pub fn sign(key: &RsaPrivateKey, hash: HashAlgorithm, digest: &[u8]) -> Result<V
         HashAlgorithm::SHA3_256 => sign_int::<Sha3_256>(key.clone(), digest),
         HashAlgorithm::SHA3_512 => sign_int::<Sha3_512>(key.clone(), digest),
         HashAlgorithm::Private10 => unsupported_err!("Private10 should not be used"),
-        HashAlgorithm::Other(o) => unsupported_err!("Hash algorithm {} is unsupported", o),
+        HashAlgorithm::Other(_) => unsupported_alg_err!(Hash, hash),
     }?;
 
     Ok(vec![sig.to_vec()])
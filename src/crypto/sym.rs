@@ -1,18 +1,23 @@
 use aes::{Aes128, Aes192, Aes256};
+#[cfg(feature = "legacy-ciphers")]
 use blowfish::Blowfish;
 use camellia::{Camellia128, Camellia192, Camellia256};
+#[cfg(feature = "legacy-ciphers")]
 use cast5::Cast5;
 use cfb_mode::cipher::{AsyncStreamCipher, KeyIvInit};
 use cfb_mode::{BufDecryptor, BufEncryptor, Decryptor, Encryptor};
+#[cfg(feature = "legacy-ciphers")]
 use des::TdesEde3;
+#[cfg(feature = "legacy-ciphers")]
 use idea::Idea;
 use num_enum::{FromPrimitive, IntoPrimitive};
 use rand::{thread_rng, CryptoRng, Rng};
 use sha1::{Digest, Sha1};
 use twofish::Twofish;
+use zeroize::Zeroizing;
 
 use crate::crypto::checksum;
-use crate::errors::{Error, Result};
+use crate::errors::{Error, IntegrityError, Result};
 
 macro_rules! decrypt {
     ($mode:ident, $key:expr, $iv:expr, $prefix:expr, $data:expr, $bs:expr, $resync:expr) => {{
@@ -26,7 +31,7 @@ macro_rules! decrypt {
         // for details.
 
         if $resync {
-            unimplemented!("CFB resync is not here");
+            unimplemented_err!("CFB resync is not here");
         // debug!("resync {}", hex::encode(&$prefix[2..$bs + 2]));
         // let mut mode = Cfb::<$mode>::new_from_slices($key, &$prefix[2..$bs + 2])?;
         // mode.decrypt($data);
@@ -42,7 +47,7 @@ macro_rules! encrypt {
         mode.encrypt($prefix);
 
         if $resync {
-            unimplemented!("CFB resync is not here");
+            unimplemented_err!("CFB resync is not here");
         // debug!("resync {}", hex::encode(&$prefix[2..$bs + 2]));
         // let mut mode = Cfb::<$mode>::new_var($key, &$prefix[2..$bs + 2])?;
         // mode.encrypt($data);
@@ -58,6 +63,12 @@ macro_rules! decrypt_regular {
         mode.decrypt($ciphertext);
     }};
 }
+macro_rules! stream_decryptor {
+    ($mode:ident, $key:expr, $iv:expr) => {{
+        let mut mode = BufDecryptor::<$mode>::new_from_slices($key, $iv)?;
+        Box::new(move |data: &mut [u8]| mode.decrypt(data))
+    }};
+}
 macro_rules! encrypt_regular {
     ($mode:ident, $key:expr, $iv:expr, $plaintext:expr) => {{
         let mode = Encryptor::<$mode>::new_from_slices($key, $iv)?;
@@ -65,8 +76,12 @@ macro_rules! encrypt_regular {
     }};
 }
 
+/// Decrypts the data passed to it in place, continuing where the previous call stopped.
+pub type StreamDecryptor = Box<dyn FnMut(&mut [u8]) + Send>;
+
 /// Available [symmetric key algorithms](https://tools.ietf.org/html/rfc4880#section-9.2).
 #[derive(Debug, PartialEq, Eq, Copy, Clone, FromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum SymmetricKeyAlgorithm {
     /// Plaintext or unencrypted data
@@ -108,7 +123,30 @@ impl Default for SymmetricKeyAlgorithm {
 
 impl zeroize::DefaultIsZeroes for SymmetricKeyAlgorithm {}
 
+/// Algorithms considered for negotiation, strongest first.
+const NEGOTIATION_ORDER: &[SymmetricKeyAlgorithm] = &[
+    SymmetricKeyAlgorithm::AES256,
+    SymmetricKeyAlgorithm::Twofish,
+    SymmetricKeyAlgorithm::Camellia256,
+    SymmetricKeyAlgorithm::AES192,
+    SymmetricKeyAlgorithm::Camellia192,
+    SymmetricKeyAlgorithm::AES128,
+    SymmetricKeyAlgorithm::Camellia128,
+];
+
 impl SymmetricKeyAlgorithm {
+    /// Picks the strongest algorithm supported by all recipients, given their preferences.
+    ///
+    /// Falls back to AES-128, which every implementation must support, if there is no
+    /// common algorithm.
+    pub fn negotiate(preferences: &[&[SymmetricKeyAlgorithm]]) -> Self {
+        NEGOTIATION_ORDER
+            .iter()
+            .copied()
+            .find(|alg| preferences.iter().all(|prefs| prefs.contains(alg)))
+            .unwrap_or_default()
+    }
+
     /// The size of a single block in bytes.
     /// Based on https://github.com/gpg/libgcrypt/blob/master/cipher
     pub fn block_size(self) -> usize {
@@ -170,14 +208,14 @@ impl SymmetricKeyAlgorithm {
 
         // MDC is 1 byte packet tag, 1 byte length prefix and 20 bytes SHA1 hash.
         let mdc_len = 22;
+        if res.len() < mdc_len {
+            return Err(Error::IntegrityCheckFailed(IntegrityError::Truncated));
+        }
         let (data, mdc) = res.split_at(res.len() - mdc_len);
 
         let sha1 = checksum::calculate_sha1([prefix, data, &mdc[0..2]]);
-        if mdc[0] != 0xD3 || // Invalid MDC tag
-           mdc[1] != 0x14 || // Invalid MDC length
-           mdc[2..] != sha1[..]
-        {
-            Err(Error::MdcError)
+        if !checksum::check_mdc(mdc, &sha1) {
+            Err(Error::IntegrityCheckFailed(IntegrityError::MdcMismatch))
         } else {
             Ok(data)
         }
@@ -212,6 +250,7 @@ impl SymmetricKeyAlgorithm {
         {
             match self {
                 SymmetricKeyAlgorithm::Plaintext => {}
+                #[cfg(feature = "legacy-ciphers")]
                 SymmetricKeyAlgorithm::IDEA => decrypt!(
                     Idea,
                     key,
@@ -222,6 +261,7 @@ impl SymmetricKeyAlgorithm {
                     resync
                 ),
 
+                #[cfg(feature = "legacy-ciphers")]
                 SymmetricKeyAlgorithm::TripleDES => {
                     decrypt!(
                         TdesEde3,
@@ -233,6 +273,7 @@ impl SymmetricKeyAlgorithm {
                         resync
                     );
                 }
+                #[cfg(feature = "legacy-ciphers")]
                 SymmetricKeyAlgorithm::CAST5 => decrypt!(
                     Cast5,
                     key,
@@ -242,6 +283,7 @@ impl SymmetricKeyAlgorithm {
                     bs,
                     resync
                 ),
+                #[cfg(feature = "legacy-ciphers")]
                 SymmetricKeyAlgorithm::Blowfish => decrypt!(
                     Blowfish,
                     key,
@@ -314,8 +356,13 @@ impl SymmetricKeyAlgorithm {
                     bs,
                     resync
                 ),
+                #[cfg(not(feature = "legacy-ciphers"))]
+                SymmetricKeyAlgorithm::IDEA
+                | SymmetricKeyAlgorithm::TripleDES
+                | SymmetricKeyAlgorithm::CAST5
+                | SymmetricKeyAlgorithm::Blowfish => unsupported_alg_err!(Symmetric, self),
                 SymmetricKeyAlgorithm::Private10 | SymmetricKeyAlgorithm::Other(_) => {
-                    unimplemented_err!("SymmetricKeyAlgorithm {} is unsupported", u8::from(self))
+                    unsupported_alg_err!(Symmetric, self)
                 }
             }
         }
@@ -333,13 +380,17 @@ impl SymmetricKeyAlgorithm {
     ) -> Result<()> {
         match self {
             SymmetricKeyAlgorithm::Plaintext => {}
+            #[cfg(feature = "legacy-ciphers")]
             SymmetricKeyAlgorithm::IDEA => {
                 decrypt_regular!(Idea, key, iv_vec, ciphertext)
             }
+            #[cfg(feature = "legacy-ciphers")]
             SymmetricKeyAlgorithm::TripleDES => {
                 decrypt_regular!(TdesEde3, key, iv_vec, ciphertext);
             }
+            #[cfg(feature = "legacy-ciphers")]
             SymmetricKeyAlgorithm::CAST5 => decrypt_regular!(Cast5, key, iv_vec, ciphertext),
+            #[cfg(feature = "legacy-ciphers")]
             SymmetricKeyAlgorithm::Blowfish => {
                 decrypt_regular!(Blowfish, key, iv_vec, ciphertext)
             }
@@ -364,14 +415,54 @@ impl SymmetricKeyAlgorithm {
             SymmetricKeyAlgorithm::Camellia256 => {
                 decrypt_regular!(Camellia256, key, iv_vec, ciphertext)
             }
+            #[cfg(not(feature = "legacy-ciphers"))]
+            SymmetricKeyAlgorithm::IDEA
+            | SymmetricKeyAlgorithm::TripleDES
+            | SymmetricKeyAlgorithm::CAST5
+            | SymmetricKeyAlgorithm::Blowfish => unsupported_alg_err!(Symmetric, self),
             SymmetricKeyAlgorithm::Private10 | SymmetricKeyAlgorithm::Other(_) => {
-                unimplemented_err!("SymmetricKeyAlgorithm {} is unsupported", u8::from(self))
+                unsupported_alg_err!(Symmetric, self)
             }
         }
 
         Ok(())
     }
 
+    /// Creates a CFB decryptor, that decrypts data in place and can be fed the ciphertext in
+    /// chunks of any size. Uses an IV of all zeroes, as specified in the openpgp cfb mode.
+    /// Does not do resynchronization.
+    pub fn stream_decryptor(self, key: &[u8]) -> Result<StreamDecryptor> {
+        let iv_vec = vec![0u8; self.block_size()];
+        let decryptor: StreamDecryptor = match self {
+            #[cfg(feature = "legacy-ciphers")]
+            SymmetricKeyAlgorithm::IDEA => stream_decryptor!(Idea, key, &iv_vec),
+            #[cfg(feature = "legacy-ciphers")]
+            SymmetricKeyAlgorithm::TripleDES => stream_decryptor!(TdesEde3, key, &iv_vec),
+            #[cfg(feature = "legacy-ciphers")]
+            SymmetricKeyAlgorithm::CAST5 => stream_decryptor!(Cast5, key, &iv_vec),
+            #[cfg(feature = "legacy-ciphers")]
+            SymmetricKeyAlgorithm::Blowfish => stream_decryptor!(Blowfish, key, &iv_vec),
+            SymmetricKeyAlgorithm::AES128 => stream_decryptor!(Aes128, key, &iv_vec),
+            SymmetricKeyAlgorithm::AES192 => stream_decryptor!(Aes192, key, &iv_vec),
+            SymmetricKeyAlgorithm::AES256 => stream_decryptor!(Aes256, key, &iv_vec),
+            SymmetricKeyAlgorithm::Twofish => stream_decryptor!(Twofish, key, &iv_vec),
+            SymmetricKeyAlgorithm::Camellia128 => stream_decryptor!(Camellia128, key, &iv_vec),
+            SymmetricKeyAlgorithm::Camellia192 => stream_decryptor!(Camellia192, key, &iv_vec),
+            SymmetricKeyAlgorithm::Camellia256 => stream_decryptor!(Camellia256, key, &iv_vec),
+            SymmetricKeyAlgorithm::Plaintext => bail!("cannot decrypt plaintext"),
+            #[cfg(not(feature = "legacy-ciphers"))]
+            SymmetricKeyAlgorithm::IDEA
+            | SymmetricKeyAlgorithm::TripleDES
+            | SymmetricKeyAlgorithm::CAST5
+            | SymmetricKeyAlgorithm::Blowfish => unsupported_alg_err!(Symmetric, self),
+            SymmetricKeyAlgorithm::Private10 | SymmetricKeyAlgorithm::Other(_) => {
+                unsupported_alg_err!(Symmetric, self)
+            }
+        };
+
+        Ok(decryptor)
+    }
+
     /// Encrypt the data using CFB mode, without padding. Overwrites the input.
     /// Uses an IV of all zeroes, as specified in the openpgp cfb mode.
     pub fn encrypt_with_rng<R: CryptoRng + Rng>(
@@ -480,15 +571,19 @@ impl SymmetricKeyAlgorithm {
         {
             match self {
                 SymmetricKeyAlgorithm::Plaintext => {}
+                #[cfg(feature = "legacy-ciphers")]
                 SymmetricKeyAlgorithm::IDEA => {
                     encrypt!(Idea, key, iv_vec, prefix, data, bs, resync)
                 }
+                #[cfg(feature = "legacy-ciphers")]
                 SymmetricKeyAlgorithm::TripleDES => {
                     encrypt!(TdesEde3, key, iv_vec, prefix, data, bs, resync);
                 }
+                #[cfg(feature = "legacy-ciphers")]
                 SymmetricKeyAlgorithm::CAST5 => {
                     encrypt!(Cast5, key, iv_vec, prefix, data, bs, resync)
                 }
+                #[cfg(feature = "legacy-ciphers")]
                 SymmetricKeyAlgorithm::Blowfish => {
                     encrypt!(Blowfish, key, iv_vec, prefix, data, bs, resync)
                 }
@@ -513,8 +608,13 @@ impl SymmetricKeyAlgorithm {
                 SymmetricKeyAlgorithm::Camellia256 => {
                     encrypt!(Camellia256, key, iv_vec, prefix, data, bs, resync)
                 }
+                #[cfg(not(feature = "legacy-ciphers"))]
+                SymmetricKeyAlgorithm::IDEA
+                | SymmetricKeyAlgorithm::TripleDES
+                | SymmetricKeyAlgorithm::CAST5
+                | SymmetricKeyAlgorithm::Blowfish => unsupported_alg_err!(Symmetric, self),
                 SymmetricKeyAlgorithm::Private10 | SymmetricKeyAlgorithm::Other(_) => {
-                    bail!("SymmetricKeyAlgorithm {} is unsupported", u8::from(self))
+                    unsupported_alg_err!(Symmetric, self)
                 }
             }
         }
@@ -532,11 +632,15 @@ impl SymmetricKeyAlgorithm {
         // TODO: actual cfb mode used in pgp
         match self {
             SymmetricKeyAlgorithm::Plaintext => {}
+            #[cfg(feature = "legacy-ciphers")]
             SymmetricKeyAlgorithm::IDEA => encrypt_regular!(Idea, key, iv_vec, plaintext),
+            #[cfg(feature = "legacy-ciphers")]
             SymmetricKeyAlgorithm::TripleDES => {
                 encrypt_regular!(TdesEde3, key, iv_vec, plaintext);
             }
+            #[cfg(feature = "legacy-ciphers")]
             SymmetricKeyAlgorithm::CAST5 => encrypt_regular!(Cast5, key, iv_vec, plaintext),
+            #[cfg(feature = "legacy-ciphers")]
             SymmetricKeyAlgorithm::Blowfish => {
                 encrypt_regular!(Blowfish, key, iv_vec, plaintext)
             }
@@ -553,16 +657,21 @@ impl SymmetricKeyAlgorithm {
             SymmetricKeyAlgorithm::Camellia256 => {
                 encrypt_regular!(Camellia256, key, iv_vec, plaintext)
             }
+            #[cfg(not(feature = "legacy-ciphers"))]
+            SymmetricKeyAlgorithm::IDEA
+            | SymmetricKeyAlgorithm::TripleDES
+            | SymmetricKeyAlgorithm::CAST5
+            | SymmetricKeyAlgorithm::Blowfish => unsupported_alg_err!(Symmetric, self),
             SymmetricKeyAlgorithm::Private10 | SymmetricKeyAlgorithm::Other(_) => {
-                unimplemented_err!("SymmetricKeyAlgorithm {} is unsupported", u8::from(self))
+                unsupported_alg_err!(Symmetric, self)
             }
         }
         Ok(())
     }
 
     /// Generate a new session key.
-    pub fn new_session_key<R: Rng + CryptoRng>(self, rng: &mut R) -> Vec<u8> {
-        let mut session_key = vec![0u8; self.key_size()];
+    pub fn new_session_key<R: Rng + CryptoRng>(self, rng: &mut R) -> Zeroizing<Vec<u8>> {
+        let mut session_key = Zeroizing::new(vec![0u8; self.key_size()]);
         rng.fill_bytes(&mut session_key);
         session_key
     }
@@ -575,6 +684,30 @@ mod tests {
     use rand::{Rng, SeedableRng};
     use rand_xorshift::XorShiftRng;
 
+    #[test]
+    fn test_negotiate() {
+        use SymmetricKeyAlgorithm::*;
+
+        assert_eq!(SymmetricKeyAlgorithm::negotiate(&[]), AES256);
+        assert_eq!(
+            SymmetricKeyAlgorithm::negotiate(&[&[AES128, AES256], &[AES192, AES256]]),
+            AES256
+        );
+        assert_eq!(
+            SymmetricKeyAlgorithm::negotiate(&[&[AES256, AES192], &[CAST5, AES192]]),
+            AES192
+        );
+        assert_eq!(
+            SymmetricKeyAlgorithm::negotiate(&[&[AES256], &[Camellia256]]),
+            AES128
+        );
+        // weak algorithms are never chosen
+        assert_eq!(
+            SymmetricKeyAlgorithm::negotiate(&[&[TripleDES], &[TripleDES]]),
+            AES128
+        );
+    }
+
     macro_rules! roundtrip {
         ($name:ident, $alg:path) => {
             #[test]
@@ -614,15 +747,37 @@ mod tests {
     roundtrip!(roundtrip_aes128, SymmetricKeyAlgorithm::AES128);
     roundtrip!(roundtrip_aes192, SymmetricKeyAlgorithm::AES192);
     roundtrip!(roundtrip_aes256, SymmetricKeyAlgorithm::AES256);
+    #[cfg(feature = "legacy-ciphers")]
     roundtrip!(roundtrip_tripledes, SymmetricKeyAlgorithm::TripleDES);
+    #[cfg(feature = "legacy-ciphers")]
     roundtrip!(roundtrip_blowfish, SymmetricKeyAlgorithm::Blowfish);
     roundtrip!(roundtrip_twofish, SymmetricKeyAlgorithm::Twofish);
+    #[cfg(feature = "legacy-ciphers")]
     roundtrip!(roundtrip_cast5, SymmetricKeyAlgorithm::CAST5);
+    #[cfg(feature = "legacy-ciphers")]
     roundtrip!(roundtrip_idea, SymmetricKeyAlgorithm::IDEA);
     roundtrip!(roundtrip_camellia128, SymmetricKeyAlgorithm::Camellia128);
     roundtrip!(roundtrip_camellia192, SymmetricKeyAlgorithm::Camellia192);
     roundtrip!(roundtrip_camellia256, SymmetricKeyAlgorithm::Camellia256);
 
+    #[test]
+    #[cfg(not(feature = "legacy-ciphers"))]
+    fn test_legacy_ciphers_disabled() {
+        use crate::errors::Algorithm;
+
+        let alg = SymmetricKeyAlgorithm::CAST5;
+        let err = alg
+            .encrypt_protected(&[0u8; 16], b"hello")
+            .expect_err("legacy ciphers are disabled");
+        assert!(matches!(
+            err,
+            Error::UnsupportedAlgorithm {
+                which: Algorithm::Symmetric(SymmetricKeyAlgorithm::CAST5)
+            }
+        ));
+        assert!(alg.stream_decryptor(&[0u8; 16]).is_err());
+    }
+
     #[test]
     pub fn decrypt_without_enough_ciphertext() {
         let key: [u8; 0] = [];
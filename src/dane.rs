@@ -0,0 +1,169 @@
+//! # DANE
+//!
+//! Helpers for publishing keys in the DNS, using OPENPGPKEY records as described in
+//! [RFC 7929](https://www.rfc-editor.org/rfc/rfc7929).
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+use crate::composed::{Deserializable, SignedPublicKey};
+use crate::errors::Result;
+use crate::ser::Serialize;
+
+/// The label under which OPENPGPKEY records are published.
+const LABEL: &str = "_openpgpkey";
+
+/// The number of bytes of the local part hash used in the owner name.
+const HASH_LEN: usize = 28;
+
+/// An OPENPGPKEY record, publishing a key for one email address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenPgpKeyRecord {
+    owner: String,
+    data: Vec<u8>,
+}
+
+impl OpenPgpKeyRecord {
+    /// Creates the record for `email`, containing the minimized `key`, with only the User IDs
+    /// for `email`.
+    pub fn new(key: &SignedPublicKey, email: &str) -> Result<Self> {
+        let owner = owner_name(email)?;
+        let data = key.clone().filter_email(email)?.to_bytes()?;
+
+        Ok(OpenPgpKeyRecord { owner, data })
+    }
+
+    /// The fully qualified owner name, e.g.
+    /// `c93f1e400f26708f98cb19d936620da35eec8f72e57f9eec01c1afd6._openpgpkey.example.com.`.
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    /// The record data, the binary encoded key.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Formats the record as a zone file entry.
+    pub fn to_zone_entry(&self) -> String {
+        format!(
+            "{} IN OPENPGPKEY {}",
+            self.owner,
+            STANDARD.encode(&self.data)
+        )
+    }
+}
+
+/// Computes the owner name for the record of `email`.
+///
+/// The local part is hashed as is, while the domain is lowercased.
+pub fn owner_name(email: &str) -> Result<String> {
+    let Some((local_part, domain)) = email.trim().rsplit_once('@') else {
+        bail!("invalid email address: {}", email);
+    };
+    ensure!(
+        !local_part.is_empty() && !domain.is_empty(),
+        "invalid email address: {}",
+        email
+    );
+
+    let hash = Sha256::digest(local_part.as_bytes());
+    let domain = domain.trim_end_matches('.').to_lowercase();
+
+    Ok(format!(
+        "{}.{}.{}.",
+        hex::encode(&hash[..HASH_LEN]),
+        LABEL,
+        domain
+    ))
+}
+
+/// Parses the data of an OPENPGPKEY record, as returned by a resolver.
+pub fn parse_rdata(rdata: &[u8]) -> Result<SignedPublicKey> {
+    SignedPublicKey::from_bytes(rdata)
+}
+
+/// Parses the data of an OPENPGPKEY record in presentation format, as found in zone files
+/// and the output of tools like `dig`.
+///
+/// Both the base64 encoding, which may be split by whitespace, and the generic `\# <len> <hex>`
+/// encoding of [RFC 3597](https://www.rfc-editor.org/rfc/rfc3597) are supported.
+pub fn parse_presentation(input: &str) -> Result<SignedPublicKey> {
+    let mut parts = input.split_whitespace();
+    let rdata = if input.trim_start().starts_with("\\#") {
+        parts.next();
+        let len: usize = parts
+            .next()
+            .and_then(|len| len.parse().ok())
+            .ok_or_else(|| format_err!("invalid generic record length"))?;
+        let rdata = hex::decode(parts.collect::<String>())?;
+        ensure_eq!(rdata.len(), len, "invalid generic record length");
+        rdata
+    } else {
+        STANDARD
+            .decode(parts.collect::<String>())
+            .map_err(|err| format_err!("invalid base64 record data: {}", err))?
+    };
+
+    parse_rdata(&rdata)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::gen_key;
+
+    #[test]
+    fn test_owner_name() {
+        // RFC 7929, section 3
+        assert_eq!(
+            owner_name("hugh@example.com").unwrap(),
+            "c93f1e400f26708f98cb19d936620da35eec8f72e57f9eec01c1afd6._openpgpkey.example.com."
+        );
+        assert_eq!(
+            owner_name("hugh@EXAMPLE.com").unwrap(),
+            owner_name("hugh@example.com").unwrap()
+        );
+        assert_ne!(
+            owner_name("Hugh@example.com").unwrap(),
+            owner_name("hugh@example.com").unwrap()
+        );
+        assert!(owner_name("example.com").is_err());
+    }
+
+    #[test]
+    fn test_record() {
+        let key = gen_key("Hugh <hugh@example.com>")
+            .add_user_id("Hugh <hugh@example.org>", String::new)
+            .unwrap();
+        let key: SignedPublicKey = key.into();
+
+        let record = OpenPgpKeyRecord::new(&key, "hugh@example.com").unwrap();
+        assert_eq!(record.owner(), owner_name("hugh@example.com").unwrap());
+        assert!(OpenPgpKeyRecord::new(&key, "hugh@example.net").is_err());
+
+        let published = parse_rdata(record.data()).unwrap();
+        assert_eq!(published.details.users.len(), 1);
+        assert_eq!(published.primary_key, key.primary_key);
+
+        let entry = record.to_zone_entry();
+        let (owner, data) = entry.split_once(" IN OPENPGPKEY ").unwrap();
+        assert_eq!(owner, record.owner());
+        assert_eq!(parse_presentation(data).unwrap(), published);
+
+        // split into chunks, as done by dig
+        let chunked: Vec<String> = data
+            .as_bytes()
+            .chunks(56)
+            .map(|c| String::from_utf8(c.to_vec()).unwrap())
+            .collect();
+        assert_eq!(parse_presentation(&chunked.join(" ")).unwrap(), published);
+
+        let generic = format!("\\# {} {}", record.data().len(), hex::encode(record.data()));
+        assert_eq!(parse_presentation(&generic).unwrap(), published);
+        assert!(parse_presentation("\\# 3 0102").is_err());
+    }
+}
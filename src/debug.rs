@@ -0,0 +1,387 @@
+//! # Debug
+//!
+//! Describes the packets found in an input, similar to `gpg --list-packets`, without
+//! decrypting anything.
+//!
+//! ```rust
+//! # fn main() -> pgp::errors::Result<()> {
+//! use pgp::debug::dump_packets;
+//! use pgp::ser::Serialize;
+//! use pgp::types::Tag;
+//! use pgp::{Deserializable, SignedPublicKey};
+//!
+//! let armored = std::fs::read_to_string("./tests/autocrypt/alice@autocrypt.example.pub.asc")?;
+//! let input = SignedPublicKey::from_string(&armored)?.0.to_bytes()?;
+//!
+//! let packets = dump_packets(&input[..])?;
+//! assert_eq!(packets[0].tag, Tag::PublicKey);
+//! for packet in &packets {
+//!     println!("{}", packet);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::io::Read;
+
+use chrono::{DateTime, Utc};
+
+use crate::errors::Result;
+use crate::limits::ParseLimits;
+use crate::packet::{Packet, PacketRef, PacketRefParser, Subpacket};
+use crate::types::{KeyTrait, KeyVersion, PacketLength, SecretParams, Tag, Version};
+
+/// The description of a single packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PacketInfo {
+    /// The offset of the packet in its input. For packets inside compressed data, this is the
+    /// offset in the decompressed data.
+    pub offset: usize,
+    pub tag: Tag,
+    pub packet_version: Version,
+    /// The length of the header, including any partial body length octets.
+    pub header_len: usize,
+    pub body_len: usize,
+    /// Whether the body uses partial body lengths.
+    pub partial: bool,
+    /// The parsed details of the packet, like algorithms and key ids, as name and value.
+    pub fields: Vec<(&'static str, String)>,
+    /// The subpackets of a signature packet.
+    pub subpackets: Vec<SubpacketInfo>,
+    /// The packets contained in a compressed data packet.
+    pub packets: Vec<PacketInfo>,
+    /// The error, if the body could not be parsed or decompressed.
+    pub error: Option<String>,
+}
+
+/// The description of a signature subpacket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubpacketInfo {
+    /// Whether the subpacket is covered by the signature.
+    pub hashed: bool,
+    pub critical: bool,
+    /// The raw subpacket type.
+    pub typ: u8,
+    pub description: String,
+}
+
+/// Describes all packets read from `reader`, which must contain binary, not armored, data.
+///
+/// Compressed data packets are decompressed, within the default [`ParseLimits`], to describe
+/// the packets they contain. Errors parsing a packet body are recorded in the [`PacketInfo`],
+/// while errors reading the packet headers are returned.
+pub fn dump_packets(mut reader: impl Read) -> Result<Vec<PacketInfo>> {
+    let mut input = Vec::new();
+    reader.read_to_end(&mut input)?;
+
+    dump(&input, &ParseLimits::default(), 0)
+}
+
+fn dump(input: &[u8], limits: &ParseLimits, depth: usize) -> Result<Vec<PacketInfo>> {
+    PacketRefParser::new(input)
+        .map(|packet| Ok(describe(&packet?, limits, depth)))
+        .collect()
+}
+
+fn describe(packet: &PacketRef<'_>, limits: &ParseLimits, depth: usize) -> PacketInfo {
+    let mut info = PacketInfo {
+        offset: packet.offset(),
+        tag: packet.tag(),
+        packet_version: packet.packet_version(),
+        header_len: packet.raw().len().saturating_sub(packet.body().len()),
+        body_len: packet.body().len(),
+        partial: matches!(packet.packet_length(), PacketLength::Partial(_)),
+        fields: Vec::new(),
+        subpackets: Vec::new(),
+        packets: Vec::new(),
+        error: None,
+    };
+
+    match packet.to_packet() {
+        Ok(parsed) => {
+            if let Err(err) = describe_body(&mut info, &parsed, limits, depth) {
+                info.error = Some(err.to_string());
+            }
+        }
+        Err(err) => info.error = Some(err.to_string()),
+    }
+
+    info
+}
+
+fn describe_body(
+    info: &mut PacketInfo,
+    packet: &Packet,
+    limits: &ParseLimits,
+    depth: usize,
+) -> Result<()> {
+    let fields = &mut info.fields;
+    match packet {
+        Packet::PublicKey(key) => key_fields(fields, key, key.version(), key.created_at()),
+        Packet::PublicSubkey(key) => key_fields(fields, key, key.version(), key.created_at()),
+        Packet::SecretKey(key) => {
+            key_fields(fields, key, key.version(), key.created_at());
+            secret_fields(fields, key.secret_params());
+        }
+        Packet::SecretSubkey(key) => {
+            key_fields(fields, key, key.version(), key.created_at());
+            secret_fields(fields, key.secret_params());
+        }
+        Packet::Signature(sig) => {
+            fields.push(("version", format!("{:?}", sig.config.version)));
+            fields.push(("type", format!("{:?}", sig.typ())));
+            fields.push(("public key algorithm", format!("{:?}", sig.config.pub_alg)));
+            fields.push(("hash algorithm", format!("{:?}", sig.config.hash_alg)));
+            if let Some(created) = sig.created() {
+                fields.push(("created", created.to_string()));
+            }
+            for issuer in sig.issuer() {
+                fields.push(("issuer", issuer.to_string()));
+            }
+            for fingerprint in sig.issuer_fingerprint() {
+                fields.push(("issuer fingerprint", fingerprint.to_string()));
+            }
+            fields.push(("hash prefix", hex::encode(sig.signed_hash_value)));
+
+            let hashed = sig.config.hashed_subpackets.iter().map(|s| (true, s));
+            let unhashed = sig.config.unhashed_subpackets.iter().map(|s| (false, s));
+            info.subpackets = hashed.chain(unhashed).map(subpacket_info).collect();
+        }
+        Packet::OnePassSignature(ops) => {
+            fields.push(("type", format!("{:?}", ops.typ)));
+            fields.push(("public key algorithm", format!("{:?}", ops.pub_algorithm)));
+            fields.push(("hash algorithm", format!("{:?}", ops.hash_algorithm)));
+            fields.push(("key id", ops.key_id.to_string()));
+            fields.push(("last", (ops.last != 0).to_string()));
+        }
+        Packet::PublicKeyEncryptedSessionKey(pkesk) => {
+            fields.push(("key id", pkesk.id().to_string()));
+            fields.push(("public key algorithm", format!("{:?}", pkesk.algorithm())));
+        }
+        Packet::SymKeyEncryptedSessionKey(skesk) => {
+            fields.push((
+                "symmetric algorithm",
+                format!("{:?}", skesk.sym_algorithm()),
+            ));
+            fields.push(("s2k", format!("{:?}", skesk.s2k().typ())));
+            fields.push(("s2k hash algorithm", format!("{:?}", skesk.s2k().hash())));
+            if let Some(count) = skesk.s2k().count() {
+                fields.push(("s2k count", count.to_string()));
+            }
+        }
+        Packet::LiteralData(data) => {
+            fields.push(("mode", format!("{:?}", data.mode())));
+            fields.push(("file name", data.file_name().to_string()));
+            fields.push(("created", data.created().to_string()));
+            fields.push(("data length", data.data().len().to_string()));
+        }
+        Packet::CompressedData(data) => {
+            fields.push(("algorithm", format!("{:?}", data.compression_algorithm())));
+            limits.check_nesting_depth(depth + 1)?;
+
+            let max = limits.max_decompressed_size();
+            let mut decompressed = Vec::new();
+            // read one byte more than allowed, to detect oversized data
+            data.decompress()?
+                .take((max as u64).saturating_add(1))
+                .read_to_end(&mut decompressed)?;
+            limits.check_decompressed_size(decompressed.len())?;
+            fields.push(("decompressed length", decompressed.len().to_string()));
+
+            info.packets = dump(&decompressed, limits, depth + 1)?;
+        }
+        Packet::UserId(id) => fields.push(("id", id.id().to_string())),
+        Packet::UserAttribute(attr) => match attr.jpeg() {
+            Some(jpeg) => fields.push(("image length", jpeg.len().to_string())),
+            None => fields.push(("type", attr.to_u8().to_string())),
+        },
+        Packet::SymEncryptedData(data) => {
+            fields.push(("data length", data.data().len().to_string()))
+        }
+        Packet::SymEncryptedProtectedData(data) => {
+            fields.push(("data length", data.data().len().to_string()))
+        }
+        Packet::Trust(trust) => fields.push(("data", hex::encode(trust.data()))),
+        Packet::Experimental(p) => fields.push(("body length", p.body().len().to_string())),
+        Packet::Marker(_) | Packet::ModDetectionCode(_) => {}
+    }
+
+    Ok(())
+}
+
+fn key_fields(
+    fields: &mut Vec<(&'static str, String)>,
+    key: &impl KeyTrait,
+    version: KeyVersion,
+    created: &DateTime<Utc>,
+) {
+    fields.push(("version", format!("{:?}", version)));
+    fields.push(("algorithm", format!("{:?}", key.algorithm())));
+    fields.push(("created", created.to_string()));
+    fields.push(("key id", key.key_id().to_string()));
+    fields.push(("fingerprint", key.fingerprint().to_string()));
+}
+
+fn secret_fields(fields: &mut Vec<(&'static str, String)>, params: &SecretParams) {
+    let protection = if params.is_encrypted() {
+        format!("encrypted, s2k usage {}", params.string_to_key_id())
+    } else {
+        "none".to_string()
+    };
+    fields.push(("protection", protection));
+}
+
+fn subpacket_info((hashed, subpacket): (bool, &Subpacket)) -> SubpacketInfo {
+    SubpacketInfo {
+        hashed,
+        critical: subpacket.is_critical,
+        typ: subpacket.typ().as_u8(false),
+        description: format!("{:?}", subpacket.data),
+    }
+}
+
+impl PacketInfo {
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        let pad = "    ".repeat(indent);
+        write!(
+            f,
+            "{}offset {}: {:?} ({:?} format, header {} bytes, body {} bytes{})",
+            pad,
+            self.offset,
+            self.tag,
+            self.packet_version,
+            self.header_len,
+            self.body_len,
+            if self.partial { ", partial" } else { "" }
+        )?;
+        for (name, value) in &self.fields {
+            write!(f, "\n{}    {}: {}", pad, name, value)?;
+        }
+        for subpacket in &self.subpackets {
+            write!(
+                f,
+                "\n{}    {} subpacket {}{}: {}",
+                pad,
+                if subpacket.hashed {
+                    "hashed"
+                } else {
+                    "unhashed"
+                },
+                subpacket.typ,
+                if subpacket.critical {
+                    " (critical)"
+                } else {
+                    ""
+                },
+                subpacket.description
+            )?;
+        }
+        if let Some(ref err) = self.error {
+            write!(f, "\n{}    error: {}", pad, err)?;
+        }
+        for packet in &self.packets {
+            writeln!(f)?;
+            packet.fmt_indented(f, indent + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for PacketInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::fs::File;
+
+    use super::*;
+    use crate::armor::Dearmor;
+    use crate::composed::{Deserializable, SignedSecretKey};
+    use crate::ser::Serialize;
+
+    #[test]
+    fn test_dump_key() {
+        let (key, _headers) = SignedSecretKey::from_armor_single(
+            File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let input = key.to_bytes().unwrap();
+
+        let packets = dump_packets(&input[..]).unwrap();
+        let tags: Vec<_> = packets.iter().map(|p| p.tag).collect();
+        assert_eq!(
+            tags,
+            vec![
+                Tag::SecretKey,
+                Tag::UserId,
+                Tag::Signature,
+                Tag::SecretSubkey,
+                Tag::Signature
+            ]
+        );
+        assert!(packets.iter().all(|p| p.error.is_none()));
+        assert_eq!(
+            packets
+                .iter()
+                .map(|p| p.header_len + p.body_len)
+                .sum::<usize>(),
+            input.len()
+        );
+
+        let primary = &packets[0];
+        assert!(primary
+            .fields
+            .contains(&("key id", key.key_id().to_string())));
+        assert!(primary.fields.contains(&("protection", "none".to_string())));
+        assert!(packets[2].subpackets.iter().any(|s| s.hashed && s.typ == 2));
+        assert!(packets[2].to_string().contains("hashed subpacket 2"));
+
+        // truncated input
+        assert!(dump_packets(&input[..input.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_dump_compressed() {
+        let mut bytes = Vec::new();
+        Dearmor::new(File::open("./tests/partial.asc").unwrap())
+            .read_to_end(&mut bytes)
+            .unwrap();
+
+        let packets = dump_packets(&bytes[..]).unwrap();
+        assert!(packets.iter().any(|p| p.partial));
+        let encrypted = packets
+            .iter()
+            .find(|p| p.tag == Tag::SymEncryptedProtectedData)
+            .unwrap();
+        assert!(encrypted.error.is_none());
+        assert!(packets
+            .iter()
+            .any(|p| p.tag == Tag::PublicKeyEncryptedSessionKey
+                || p.tag == Tag::SymKeyEncryptedSessionKey));
+
+        let data = crate::Message::new_literal("hello.txt", "hello world")
+            .compress(crate::types::CompressionAlgorithm::ZLIB)
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        let packets = dump_packets(&data[..]).unwrap();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].tag, Tag::CompressedData);
+        assert_eq!(packets[0].packets.len(), 1);
+        let literal = &packets[0].packets[0];
+        assert_eq!(literal.tag, Tag::LiteralData);
+        assert!(literal
+            .fields
+            .contains(&("file name", "hello.txt".to_string())));
+        assert!(packets[0]
+            .to_string()
+            .contains("\n    offset 0: LiteralData"));
+    }
+}
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::num::TryFromIntError;
 
 use ed25519_dalek::SignatureError;
@@ -6,26 +7,38 @@ use nom::{
     ErrorConvert,
 };
 
+use crate::crypto::hash::HashAlgorithm;
+use crate::crypto::public_key::PublicKeyAlgorithm;
+use crate::crypto::sym::SymmetricKeyAlgorithm;
+use crate::types::{CompressionAlgorithm, KeyId, Tag};
+
 pub type Result<T> = ::std::result::Result<T, Error>;
 
 // custom nom error types
 pub const MPI_TOO_LONG: u32 = 1000;
 
 /// Error types
+///
+/// Callers can match on the specific variants, e.g. to ask for the passphrase again on
+/// [`Error::BadPassphrase`]. New variants may be added in minor releases.
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
     #[error("failed to parse {0:?}")]
     ParsingError(nom::error::ErrorKind),
     #[error("invalid input")]
     InvalidInput,
-    #[error("incomplete input: {0:?}")]
-    Incomplete(nom::Needed),
+    /// The input ended early, `needed` is the number of missing bytes, if known.
+    #[error("incomplete input: {:?}", needed.map_or(nom::Needed::Unknown, nom::Needed::new))]
+    Truncated { needed: Option<usize> },
     #[error("invalid armor wrappers")]
     InvalidArmorWrappers,
     #[error("invalid crc24 checksum")]
     InvalidChecksum,
     #[error("failed to decode base64 {0:?}")]
     Base64DecodeError(#[from] base64::DecodeError),
+    #[error("failed to decode hex {0:?}")]
+    HexDecodeError(#[from] hex::FromHexError),
     #[error("requested data size is larger than the packet body")]
     RequestedSizeTooLarge,
     #[error("no matching packet found")]
@@ -70,18 +83,107 @@ pub enum Error {
     InvalidPacketContent(Box<Error>),
     #[error("Signature {0:?}")]
     SignatureError(#[from] SignatureError),
-    #[error("Modification Detection Code error")]
-    MdcError,
+    /// The integrity protection of encrypted data is missing or does not match, it was
+    /// modified, truncated or decrypted with the wrong key.
+    #[error("integrity check failed: {0}")]
+    IntegrityCheckFailed(IntegrityError),
     #[error("Invalid size conversion {0}")]
     TryFromInt(#[from] TryFromIntError),
+    /// Unlocking a secret key failed, because the passphrase is wrong.
+    #[error("invalid passphrase")]
+    BadPassphrase,
+    #[error("Unsupported: \"{which} is unsupported\"")]
+    UnsupportedAlgorithm { which: Algorithm },
+    /// A [`PassphraseProvider`](crate::types::PassphraseProvider) cancelled unlocking a key.
+    #[error("cancelled")]
+    Cancelled,
+    /// None of the keys could decrypt the session key of a message, `failures` has the error
+    /// of each key that was tried.
+    #[error(
+        "failed to decrypt session key: {}",
+        failures
+            .iter()
+            .map(|(key_id, err)| format!("{key_id}: {err}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )]
+    SessionKeyDecryption { failures: Vec<(KeyId, Error)> },
+    /// A packet failed to parse, with its position in the input.
+    ///
+    /// `index` is the number of packets that were parsed before it, `tag` is known if the
+    /// packet header could be parsed. Use [`Error::without_context`] to get the inner error.
+    #[error(
+        "{source} (packet {index}{}, at offset {offset})",
+        tag.map(|tag| format!(" {tag:?}")).unwrap_or_default()
+    )]
+    InPacket {
+        offset: usize,
+        index: usize,
+        tag: Option<Tag>,
+        source: Box<Error>,
+    },
+}
+
+/// An algorithm, as reported by [`Error::UnsupportedAlgorithm`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Algorithm {
+    PublicKey(PublicKeyAlgorithm),
+    Symmetric(SymmetricKeyAlgorithm),
+    Hash(HashAlgorithm),
+    Compression(CompressionAlgorithm),
+}
+
+/// Why the integrity check of encrypted data failed, as reported by
+/// [`Error::IntegrityCheckFailed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum IntegrityError {
+    /// The data is not integrity protected, and the policy requires it.
+    #[error("missing Modification Detection Code")]
+    MdcMissing,
+    /// The Modification Detection Code does not match the data.
+    #[error("Modification Detection Code mismatch")]
+    MdcMismatch,
+    /// The ciphertext is too short to hold the Modification Detection Code.
+    #[error("truncated ciphertext")]
+    Truncated,
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Algorithm::PublicKey(alg) => write!(f, "PublicKeyAlgorithm {}", u8::from(*alg)),
+            Algorithm::Symmetric(alg) => write!(f, "SymmetricKeyAlgorithm {}", u8::from(*alg)),
+            Algorithm::Hash(alg) => write!(f, "Hash algorithm {}", u8::from(*alg)),
+            Algorithm::Compression(alg) => write!(f, "CompressionAlgorithm {}", u8::from(*alg)),
+        }
+    }
 }
 
 impl Error {
+    pub(crate) fn truncated(needed: nom::Needed) -> Self {
+        Error::Truncated {
+            needed: match needed {
+                nom::Needed::Size(size) => Some(size.get()),
+                nom::Needed::Unknown => None,
+            },
+        }
+    }
+
+    /// The error without the position added by [`Error::InPacket`].
+    pub fn without_context(&self) -> &Error {
+        match self {
+            Error::InPacket { source, .. } => source.without_context(),
+            err => err,
+        }
+    }
+
     pub fn as_code(&self) -> u32 {
         match self {
             Error::ParsingError(_) => 0,
             Error::InvalidInput => 1,
-            Error::Incomplete(_) => 2,
+            Error::Truncated { .. } => 2,
             Error::InvalidArmorWrappers => 3,
             Error::InvalidChecksum => 4,
             Error::Base64DecodeError(_) => 5,
@@ -106,9 +208,15 @@ impl Error {
             Error::ParseIntError(_) => 24,
             Error::InvalidPacketContent(_) => 25,
             Error::SignatureError(_) => 26,
-            Error::MdcError => 27,
+            Error::IntegrityCheckFailed(_) => 27,
             Error::TryFromInt(_) => 28,
             Error::EllipticCurve(_) => 29,
+            Error::HexDecodeError(_) => 30,
+            Error::BadPassphrase => 31,
+            Error::UnsupportedAlgorithm { .. } => 32,
+            Error::Cancelled => 33,
+            Error::SessionKeyDecryption { .. } => 34,
+            Error::InPacket { source, .. } => source.as_code(),
         }
     }
 }
@@ -152,7 +260,7 @@ impl ErrorConvert<Self> for Error {
 impl From<nom::Err<Error>> for Error {
     fn from(err: nom::Err<Error>) -> Self {
         match err {
-            nom::Err::Incomplete(err) => Self::Incomplete(err),
+            nom::Err::Incomplete(needed) => Self::truncated(needed),
             nom::Err::Error(err) | nom::Err::Failure(err) => err,
         }
     }
@@ -161,7 +269,7 @@ impl From<nom::Err<Error>> for Error {
 impl<T> From<nom::Err<nom::error::Error<T>>> for Error {
     fn from(err: nom::Err<nom::error::Error<T>>) -> Error {
         match err {
-            nom::Err::Incomplete(err) => Self::Incomplete(err),
+            nom::Err::Incomplete(needed) => Self::truncated(needed),
             nom::Err::Error(err) | nom::Err::Failure(err) => Self::ParsingError(err.code),
         }
     }
@@ -217,6 +325,15 @@ macro_rules! unsupported_err {
     };
 }
 
+#[macro_export]
+macro_rules! unsupported_alg_err {
+    ($kind:ident, $alg:expr) => {
+        return Err($crate::errors::Error::UnsupportedAlgorithm {
+            which: $crate::errors::Algorithm::$kind($alg),
+        })
+    };
+}
+
 #[macro_export]
 macro_rules! bail {
     ($e:expr) => {
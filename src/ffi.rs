@@ -0,0 +1,473 @@
+//! # FFI
+//!
+//! A C ABI for parsing keys, encrypting, decrypting, signing and verifying, enabled with the
+//! `ffi` feature. The matching declarations are in `include/rpgp.h`.
+//!
+//! Keys and buffers are passed as opaque handles, which must be released with the matching
+//! `rpgp_*_free` function. Functions returning a handle return `NULL` on failure, functions
+//! returning an `int` return `0` on success and `-1` on failure. The message of the last
+//! failure on the current thread is returned by [`rpgp_last_error`].
+//!
+//! Keys, messages and signatures are accepted both armored and binary.
+
+#![allow(unsafe_code)]
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::io::Cursor;
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+
+use crate::composed::{
+    sign_detached, Deserializable, Message, SignedPublicKey, SignedSecretKey, StandaloneSignature,
+};
+use crate::crypto::hash::HashAlgorithm;
+use crate::errors::{Error, Result};
+use crate::ser::Serialize;
+use crate::types::KeyTrait;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// An owned byte buffer, returned by the functions producing data.
+pub struct Buffer(Vec<u8>);
+
+/// Runs `f`, recording its error or panic as the last error of this thread.
+fn guard<T>(f: impl FnOnce() -> Result<T>) -> Option<T> {
+    let res = panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(format_err!("panic in rpgp")));
+    match res {
+        Ok(value) => Some(value),
+        Err(err) => {
+            // error messages never contain NUL bytes, but do not fail if they do
+            let msg = CString::new(err.to_string().replace('\0', "")).unwrap_or_default();
+            LAST_ERROR.with(|last| *last.borrow_mut() = Some(msg));
+            None
+        }
+    }
+}
+
+fn into_handle<T>(value: Option<T>) -> *mut T {
+    value.map_or(ptr::null_mut(), |v| Box::into_raw(Box::new(v)))
+}
+
+fn into_status(value: Option<()>) -> c_int {
+    value.map_or(-1, |_| 0)
+}
+
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, or be `NULL` if `len` is `0`.
+unsafe fn input<'a>(data: *const u8, len: usize) -> Result<&'a [u8]> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    ensure!(!data.is_null(), "null data pointer");
+
+    Ok(slice::from_raw_parts(data, len))
+}
+
+/// # Safety
+///
+/// `ptr` must be `NULL` or point to a handle created by this module.
+unsafe fn handle<'a, T>(ptr: *const T) -> Result<&'a T> {
+    ptr.as_ref().ok_or_else(|| format_err!("null handle"))
+}
+
+/// # Safety
+///
+/// `password` must be `NULL` or point to a NUL terminated string.
+unsafe fn password(password: *const c_char) -> Result<String> {
+    if password.is_null() {
+        return Ok(String::new());
+    }
+
+    Ok(CStr::from_ptr(password).to_str()?.to_string())
+}
+
+/// Parses `input`, detecting whether it is armored.
+fn parse<T: Deserializable>(input: &[u8]) -> Result<T> {
+    let start = input.iter().position(|b| !b.is_ascii_whitespace());
+    if start.is_some_and(|start| input[start..].starts_with(b"-----")) {
+        let (value, _headers) = T::from_armor_single(Cursor::new(input))?;
+        Ok(value)
+    } else {
+        T::from_bytes(input)
+    }
+}
+
+/// Returns the message of the last error on the current thread, or `NULL` if there was none.
+///
+/// The string must be released with [`rpgp_string_free`].
+#[no_mangle]
+pub extern "C" fn rpgp_last_error() -> *mut c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null_mut(), |msg| msg.clone().into_raw())
+    })
+}
+
+/// Releases a string returned by this library.
+///
+/// # Safety
+///
+/// `s` must be `NULL` or a string returned by this library, that was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn rpgp_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Returns a pointer to the contents of `buffer`.
+///
+/// # Safety
+///
+/// `buffer` must be a valid buffer handle.
+#[no_mangle]
+pub unsafe extern "C" fn rpgp_buffer_data(buffer: *const Buffer) -> *const u8 {
+    buffer.as_ref().map_or(ptr::null(), |b| b.0.as_ptr())
+}
+
+/// Returns the length of the contents of `buffer`.
+///
+/// # Safety
+///
+/// `buffer` must be a valid buffer handle.
+#[no_mangle]
+pub unsafe extern "C" fn rpgp_buffer_len(buffer: *const Buffer) -> usize {
+    buffer.as_ref().map_or(0, |b| b.0.len())
+}
+
+/// Releases `buffer`.
+///
+/// # Safety
+///
+/// `buffer` must be `NULL` or a buffer handle, that was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn rpgp_buffer_free(buffer: *mut Buffer) {
+    if !buffer.is_null() {
+        drop(Box::from_raw(buffer));
+    }
+}
+
+/// Parses a public key.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rpgp_public_key_parse(
+    data: *const u8,
+    len: usize,
+) -> *mut SignedPublicKey {
+    into_handle(guard(|| {
+        let key: SignedPublicKey = parse(input(data, len)?)?;
+        key.verify()?;
+        Ok(key)
+    }))
+}
+
+/// Returns the fingerprint of `key`, as upper case hex.
+///
+/// The string must be released with [`rpgp_string_free`].
+///
+/// # Safety
+///
+/// `key` must be a valid public key handle.
+#[no_mangle]
+pub unsafe extern "C" fn rpgp_public_key_fingerprint(key: *const SignedPublicKey) -> *mut c_char {
+    guard(|| {
+        let fingerprint = format!("{:X}", handle(key)?.fingerprint());
+        CString::new(fingerprint).map_err(|_| Error::InvalidInput)
+    })
+    .map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Serializes `key`, armored if `armor` is set.
+///
+/// # Safety
+///
+/// `key` must be a valid public key handle.
+#[no_mangle]
+pub unsafe extern "C" fn rpgp_public_key_serialize(
+    key: *const SignedPublicKey,
+    armor: bool,
+) -> *mut Buffer {
+    into_handle(guard(|| {
+        let key = handle(key)?;
+        let bytes = if armor {
+            key.to_armored_bytes(None)?
+        } else {
+            key.to_bytes()?
+        };
+        Ok(Buffer(bytes))
+    }))
+}
+
+/// Releases `key`.
+///
+/// # Safety
+///
+/// `key` must be `NULL` or a public key handle, that was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn rpgp_public_key_free(key: *mut SignedPublicKey) {
+    if !key.is_null() {
+        drop(Box::from_raw(key));
+    }
+}
+
+/// Parses a secret key.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rpgp_secret_key_parse(
+    data: *const u8,
+    len: usize,
+) -> *mut SignedSecretKey {
+    into_handle(guard(|| {
+        let key: SignedSecretKey = parse(input(data, len)?)?;
+        key.verify()?;
+        Ok(key)
+    }))
+}
+
+/// Returns the public key of `key`, which must be released with [`rpgp_public_key_free`].
+///
+/// # Safety
+///
+/// `key` must be a valid secret key handle.
+#[no_mangle]
+pub unsafe extern "C" fn rpgp_secret_key_public_key(
+    key: *const SignedSecretKey,
+) -> *mut SignedPublicKey {
+    into_handle(guard(|| Ok(SignedPublicKey::from(handle(key)?.clone()))))
+}
+
+/// Releases `key`.
+///
+/// # Safety
+///
+/// `key` must be `NULL` or a secret key handle, that was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn rpgp_secret_key_free(key: *mut SignedSecretKey) {
+    if !key.is_null() {
+        drop(Box::from_raw(key));
+    }
+}
+
+/// Encrypts `data` to the `keys_len` public keys in `keys`, returning an armored message.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, and `keys` to `keys_len` valid public key
+/// handles.
+#[no_mangle]
+pub unsafe extern "C" fn rpgp_encrypt(
+    data: *const u8,
+    len: usize,
+    keys: *const *const SignedPublicKey,
+    keys_len: usize,
+) -> *mut Buffer {
+    into_handle(guard(|| {
+        let data = input(data, len)?;
+        ensure!(keys_len > 0, "no recipients");
+        ensure!(!keys.is_null(), "null keys pointer");
+        let keys = slice::from_raw_parts(keys, keys_len)
+            .iter()
+            .map(|&key| handle(key))
+            .collect::<Result<Vec<_>>>()?;
+
+        let msg = Message::new_literal_bytes("", data)
+            .encrypt_to_certs(&mut rand::thread_rng(), &keys)?;
+        Ok(Buffer(msg.to_armored_bytes(None)?))
+    }))
+}
+
+/// Decrypts the message in `data` with `key`, returning its content.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, `key` must be a valid secret key handle, and
+/// `password` must be `NULL` or a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rpgp_decrypt(
+    data: *const u8,
+    len: usize,
+    key: *const SignedSecretKey,
+    password: *const c_char,
+) -> *mut Buffer {
+    into_handle(guard(|| {
+        let msg: Message = parse(input(data, len)?)?;
+        let key = handle(key)?;
+        let password = self::password(password)?;
+
+        let (mut decrypter, _ids) = msg.decrypt(|| password.clone(), &[key])?;
+        let decrypted = decrypter
+            .next()
+            .ok_or_else(|| format_err!("no encrypted data"))??;
+        let content = decrypted
+            .get_content()?
+            .ok_or_else(|| format_err!("no content"))?;
+        Ok(Buffer(content))
+    }))
+}
+
+/// Creates an armored, detached signature over `data` with `key`.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, `key` must be a valid secret key handle, and
+/// `password` must be `NULL` or a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rpgp_sign_detached(
+    data: *const u8,
+    len: usize,
+    key: *const SignedSecretKey,
+    password: *const c_char,
+) -> *mut Buffer {
+    into_handle(guard(|| {
+        let data = input(data, len)?;
+        let key = handle(key)?;
+        let password = self::password(password)?;
+
+        let signature = sign_detached(data, key, || password.clone(), HashAlgorithm::SHA2_256)?;
+        Ok(Buffer(signature.to_armored_bytes(None)?))
+    }))
+}
+
+/// Verifies the detached signature in `sig` over `data`, made by `key` or one of its subkeys.
+///
+/// # Safety
+///
+/// `data` must point to `len` and `sig` to `sig_len` readable bytes, and `key` must be a
+/// valid public key handle.
+#[no_mangle]
+pub unsafe extern "C" fn rpgp_verify_detached(
+    data: *const u8,
+    len: usize,
+    sig: *const u8,
+    sig_len: usize,
+    key: *const SignedPublicKey,
+) -> c_int {
+    into_status(guard(|| {
+        let data = input(data, len)?;
+        let signature: StandaloneSignature = parse(input(sig, sig_len)?)?;
+        let key = handle(key)?;
+
+        let primary = signature.verify(key, data);
+        if primary.is_ok()
+            || key
+                .public_subkeys
+                .iter()
+                .any(|subkey| signature.verify(subkey, data).is_ok())
+        {
+            return Ok(());
+        }
+        primary
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::gen_key_with_subkey;
+
+    unsafe fn contents(buffer: *mut Buffer) -> Vec<u8> {
+        assert!(!buffer.is_null(), "{}", last_error());
+        let data = slice::from_raw_parts(rpgp_buffer_data(buffer), rpgp_buffer_len(buffer));
+        let contents = data.to_vec();
+        rpgp_buffer_free(buffer);
+        contents
+    }
+
+    unsafe fn last_error() -> String {
+        let err = rpgp_last_error();
+        assert!(!err.is_null());
+        let msg = CStr::from_ptr(err).to_str().unwrap().to_string();
+        rpgp_string_free(err);
+        msg
+    }
+
+    #[test]
+    fn test_ffi_roundtrip() {
+        let key = gen_key_with_subkey("Alice <alice@example.com>");
+        let armored = key.to_armored_bytes(None).unwrap();
+        let text = b"hello world";
+
+        unsafe {
+            let secret = rpgp_secret_key_parse(armored.as_ptr(), armored.len());
+            assert!(!secret.is_null());
+            let public = rpgp_secret_key_public_key(secret);
+            assert!(!public.is_null());
+
+            let fingerprint = rpgp_public_key_fingerprint(public);
+            assert_eq!(
+                CStr::from_ptr(fingerprint).to_str().unwrap(),
+                format!("{:X}", key.fingerprint())
+            );
+            rpgp_string_free(fingerprint);
+
+            // binary keys are accepted as well
+            let bytes = contents(rpgp_public_key_serialize(public, false));
+            let parsed = rpgp_public_key_parse(bytes.as_ptr(), bytes.len());
+            assert!(!parsed.is_null());
+            rpgp_public_key_free(parsed);
+
+            let keys = [public as *const SignedPublicKey];
+            let msg = contents(rpgp_encrypt(
+                text.as_ptr(),
+                text.len(),
+                keys.as_ptr(),
+                keys.len(),
+            ));
+            let decrypted = contents(rpgp_decrypt(msg.as_ptr(), msg.len(), secret, ptr::null()));
+            assert_eq!(decrypted, text);
+
+            let sig = contents(rpgp_sign_detached(
+                text.as_ptr(),
+                text.len(),
+                secret,
+                ptr::null(),
+            ));
+            assert_eq!(
+                rpgp_verify_detached(text.as_ptr(), text.len(), sig.as_ptr(), sig.len(), public),
+                0
+            );
+            assert_eq!(
+                rpgp_verify_detached(text.as_ptr(), 5, sig.as_ptr(), sig.len(), public),
+                -1
+            );
+            assert!(!last_error().is_empty());
+
+            rpgp_public_key_free(public);
+            rpgp_secret_key_free(secret);
+        }
+    }
+
+    #[test]
+    fn test_ffi_errors() {
+        unsafe {
+            assert!(rpgp_public_key_parse(b"garbage".as_ptr(), 7).is_null());
+            assert!(!last_error().is_empty());
+
+            assert!(rpgp_public_key_parse(ptr::null(), 10).is_null());
+            assert_eq!(last_error(), "\"null data pointer\"");
+
+            assert!(rpgp_public_key_fingerprint(ptr::null()).is_null());
+            assert!(rpgp_encrypt(b"hello".as_ptr(), 5, ptr::null(), 0).is_null());
+            assert_eq!(last_error(), "\"no recipients\"");
+
+            rpgp_public_key_free(ptr::null_mut());
+            rpgp_buffer_free(ptr::null_mut());
+            rpgp_string_free(ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! # JSON Web Key
+//!
+//! Writer for public keys in the JSON Web Key format of
+//! [RFC 7517](https://datatracker.ietf.org/doc/html/rfc7517), as used by JOSE libraries.
+//!
+//! RSA keys, ECDSA and ECDH keys on the NIST P-256, P-384, P-521 and secp256k1 curves, as
+//! well as Ed25519 and X25519 keys ([RFC 8037](https://datatracker.ietf.org/doc/html/rfc8037))
+//! are supported.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+
+use crate::crypto::ecc_curve::ECCCurve;
+use crate::errors::Result;
+use crate::formats::native_point;
+use crate::types::PublicParams;
+
+/// A public key as a JSON Web Key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Jwk {
+    /// An RSA key, with the big endian modulus `n` and exponent `e`.
+    Rsa { n: Vec<u8>, e: Vec<u8> },
+    /// A key on the elliptic curve `crv`, with the big endian coordinates `x` and `y`.
+    Ec {
+        crv: &'static str,
+        x: Vec<u8>,
+        y: Vec<u8>,
+    },
+    /// An Ed25519 or X25519 key on the curve `crv`, with the public key `x`.
+    Okp { crv: &'static str, x: Vec<u8> },
+}
+
+impl Jwk {
+    /// Converts the public key with the parameters `params`.
+    pub fn from_public_params(params: &PublicParams) -> Result<Self> {
+        match params {
+            PublicParams::RSA { n, e } => Ok(Jwk::Rsa {
+                n: n.as_bytes().to_vec(),
+                e: e.as_bytes().to_vec(),
+            }),
+            PublicParams::ECDSA(ecdsa) => Self::ec(&ecdsa.curve(), ecdsa.point()),
+            PublicParams::ECDH {
+                curve: ECCCurve::Curve25519,
+                p,
+                ..
+            } => Ok(Jwk::Okp {
+                crv: "X25519",
+                x: native_point(p.as_bytes())?.to_vec(),
+            }),
+            PublicParams::ECDH { curve, p, .. } => Self::ec(curve, p.as_bytes()),
+            PublicParams::EdDSA {
+                curve: ECCCurve::Ed25519,
+                q,
+            } => Ok(Jwk::Okp {
+                crv: "Ed25519",
+                x: native_point(q.as_bytes())?.to_vec(),
+            }),
+            _ => unsupported_err!("JSON Web Keys for parameters {:?}", params),
+        }
+    }
+
+    fn ec(curve: &ECCCurve, point: &[u8]) -> Result<Self> {
+        let crv = match curve {
+            ECCCurve::P256 => "P-256",
+            ECCCurve::P384 => "P-384",
+            ECCCurve::P521 => "P-521",
+            ECCCurve::Secp256k1 => "secp256k1",
+            _ => unsupported_err!("JSON Web Keys on curve {}", curve.name()),
+        };
+
+        // the uncompressed SEC1 encoding of the point
+        match point.split_first() {
+            Some((0x04, xy)) if !xy.is_empty() && xy.len() % 2 == 0 => {
+                let (x, y) = xy.split_at(xy.len() / 2);
+                Ok(Jwk::Ec {
+                    crv,
+                    x: x.to_vec(),
+                    y: y.to_vec(),
+                })
+            }
+            _ => bail!("invalid point on curve {}", curve.name()),
+        }
+    }
+
+    /// Serializes the key as a JSON object.
+    ///
+    /// The members are sorted and there is no whitespace, so this is also the input of the
+    /// thumbprint defined in [RFC 7638](https://datatracker.ietf.org/doc/html/rfc7638).
+    pub fn to_json(&self) -> String {
+        let b64 = |value: &[u8]| URL_SAFE_NO_PAD.encode(value);
+
+        match self {
+            Jwk::Rsa { n, e } => {
+                format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, b64(e), b64(n))
+            }
+            Jwk::Ec { crv, x, y } => format!(
+                r#"{{"crv":"{}","kty":"EC","x":"{}","y":"{}"}}"#,
+                crv,
+                b64(x),
+                b64(y)
+            ),
+            Jwk::Okp { crv, x } => {
+                format!(r#"{{"crv":"{}","kty":"OKP","x":"{}"}}"#, crv, b64(x))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    #[cfg(feature = "nist-curves")]
+    use p256::elliptic_curve::sec1::ToEncodedPoint;
+
+    use super::*;
+    #[cfg(feature = "nist-curves")]
+    use crate::crypto::ecdsa;
+
+    #[test]
+    fn test_ed25519() {
+        // example from RFC 8037, appendix A.2
+        let params = PublicParams::EdDSA {
+            curve: ECCCurve::Ed25519,
+            q: hex::decode("40d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a")
+                .unwrap()
+                .into(),
+        };
+
+        assert_eq!(
+            Jwk::from_public_params(&params).unwrap().to_json(),
+            r#"{"crv":"Ed25519","kty":"OKP","x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"}"#
+        );
+    }
+
+    #[test]
+    fn test_rsa() {
+        let params = PublicParams::RSA {
+            n: vec![0xc5, 0x01].into(),
+            e: vec![0x01, 0x00, 0x01].into(),
+        };
+
+        assert_eq!(
+            Jwk::from_public_params(&params).unwrap().to_json(),
+            r#"{"e":"AQAB","kty":"RSA","n":"xQE"}"#
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "nist-curves")]
+    fn test_ec() {
+        let (params, _) = ecdsa::import_key(&ECCCurve::P256, &[1u8; 32]).unwrap();
+        let jwk = Jwk::from_public_params(&params).unwrap();
+
+        let point = p256::SecretKey::from_slice(&[1u8; 32])
+            .unwrap()
+            .public_key()
+            .to_encoded_point(false);
+        assert_eq!(
+            jwk,
+            Jwk::Ec {
+                crv: "P-256",
+                x: point.x().unwrap().to_vec(),
+                y: point.y().unwrap().to_vec(),
+            }
+        );
+    }
+}
@@ -0,0 +1,455 @@
+//! # Keybox
+//!
+//! Reader for the keybox format, which GnuPG 2.1 and later uses to store public keys
+//! (`pubring.kbx`).
+//!
+//! A keybox is a sequence of blobs. Besides the header blob, each blob contains either an
+//! OpenPGP certificate or an X.509 certificate, together with some metadata used by GnuPG
+//! for lookups.
+//!
+//! Ref: https://git.gnupg.org/cgi-bin/gitweb.cgi?p=gnupg.git;a=blob;f=kbx/keybox-blob.c
+
+use std::io::{self, Read};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use chrono::{DateTime, TimeZone, Utc};
+use nom::bytes::complete::{tag, take};
+use nom::combinator::map_opt;
+use nom::number::complete::{be_u16, be_u32, be_u8};
+
+use crate::composed::signed_key::{from_keyring_many, PublicOrSecret, SignedPublicKey};
+use crate::errors::{Error, IResult, Result};
+use crate::types::KeyId;
+
+/// Upper bound for the size of a single blob, GnuPG itself limits them to 5 MiB.
+const MAX_BLOB_SIZE: u32 = 16 * 1024 * 1024;
+
+/// A single blob of a keybox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Blob {
+    /// An unused blob.
+    Empty,
+    /// The first blob of a keybox.
+    Header(HeaderBlob),
+    OpenPgp(OpenPgpBlob),
+    X509(X509Blob),
+    Unknown {
+        typ: u8,
+        data: Vec<u8>,
+    },
+}
+
+/// The first blob of a keybox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderBlob {
+    pub version: u8,
+    pub flags: u16,
+    pub created_at: DateTime<Utc>,
+    pub last_maintenance: DateTime<Utc>,
+}
+
+/// A blob containing an OpenPGP certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenPgpBlob {
+    pub info: BlobInfo,
+    /// The certificate, as a sequence of OpenPGP packets, possibly including trust packets.
+    pub keyblock: Vec<u8>,
+}
+
+/// A blob containing an X.509 certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct X509Blob {
+    pub info: BlobInfo,
+    /// The DER encoded certificate.
+    pub certificate: Vec<u8>,
+}
+
+/// The metadata GnuPG stores about the certificate of a blob.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobInfo {
+    pub version: u8,
+    pub flags: u16,
+    /// The primary key and subkeys of an OpenPGP certificate, or the single key of an X.509
+    /// certificate.
+    pub keys: Vec<KeyInfo>,
+    pub serial_number: Vec<u8>,
+    /// The User IDs of an OpenPGP certificate, or the issuer, subject and alternative names
+    /// of an X.509 certificate.
+    pub user_ids: Vec<Vec<u8>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The metadata GnuPG stores about a single key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyInfo {
+    /// The fingerprint, 20 bytes in version 1 blobs (right aligned for shorter fingerprints),
+    /// 32 bytes in version 2 blobs.
+    pub fingerprint: Vec<u8>,
+    pub key_id: Option<KeyId>,
+    pub flags: u16,
+}
+
+impl OpenPgpBlob {
+    /// Parses the OpenPGP certificate of this blob.
+    pub fn certificate(&self) -> Result<SignedPublicKey> {
+        match from_keyring_many(&self.keyblock[..]).next() {
+            Some(Ok(PublicOrSecret::Public(key))) => Ok(key),
+            Some(Ok(PublicOrSecret::Secret(_))) => bail!("unexpected secret key in keybox"),
+            Some(Err(err)) => Err(err),
+            None => Err(Error::NoMatchingPacket),
+        }
+    }
+}
+
+impl X509Blob {
+    /// The issuer of the certificate.
+    pub fn issuer(&self) -> Option<&[u8]> {
+        self.info.user_ids.first().map(|id| &id[..])
+    }
+
+    /// The subject of the certificate.
+    pub fn subject(&self) -> Option<&[u8]> {
+        self.info.user_ids.get(1).map(|id| &id[..])
+    }
+
+    /// The subject alternative names of the certificate.
+    pub fn alt_names(&self) -> &[Vec<u8>] {
+        self.info.user_ids.get(2..).unwrap_or_default()
+    }
+}
+
+/// Reads the blobs of a keybox.
+pub struct KeyboxReader<R> {
+    inner: R,
+    failed: bool,
+}
+
+impl<R: Read> KeyboxReader<R> {
+    pub fn new(inner: R) -> Self {
+        KeyboxReader {
+            inner,
+            failed: false,
+        }
+    }
+
+    /// Returns the OpenPGP certificates stored in this keybox, skipping all other blobs.
+    pub fn certificates(self) -> impl Iterator<Item = Result<SignedPublicKey>> {
+        self.filter_map(|blob| match blob {
+            Ok(Blob::OpenPgp(blob)) => Some(blob.certificate()),
+            Ok(_) => None,
+            Err(err) => Some(Err(err)),
+        })
+    }
+
+    fn read_blob(&mut self) -> Result<Option<Blob>> {
+        let len = match self.inner.read_u32::<BigEndian>() {
+            Ok(len) => len,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        ensure!(
+            (6..=MAX_BLOB_SIZE).contains(&len),
+            "invalid blob length {}",
+            len
+        );
+
+        // offsets in the blob are relative to its start, including the length
+        let mut blob = vec![0u8; len as usize];
+        blob[..4].copy_from_slice(&len.to_be_bytes());
+        self.inner.read_exact(&mut blob[4..])?;
+
+        let (_, blob) = parse_blob(&blob)?;
+
+        Ok(Some(blob))
+    }
+}
+
+impl<R: Read> Iterator for KeyboxReader<R> {
+    type Item = Result<Blob>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let res = self.read_blob();
+        // the blob boundaries are lost, if a blob could not be read
+        if matches!(res, Err(Error::IOError(_))) {
+            self.failed = true;
+        }
+
+        res.transpose()
+    }
+}
+
+fn timestamp(i: &[u8]) -> IResult<&[u8], DateTime<Utc>> {
+    map_opt(be_u32, |v| Utc.timestamp_opt(i64::from(v), 0).single())(i)
+}
+
+fn parse_blob(blob: &[u8]) -> IResult<&[u8], Blob> {
+    let (i, _len) = be_u32(blob)?;
+    let (i, typ) = be_u8(i)?;
+    let (i, version) = be_u8(i)?;
+
+    match typ {
+        0 => Ok((i, Blob::Empty)),
+        1 => {
+            let (i, flags) = be_u16(i)?;
+            let (i, _) = tag(b"KBXf")(i)?;
+            let (i, _reserved) = be_u32(i)?;
+            let (i, created_at) = timestamp(i)?;
+            let (i, last_maintenance) = timestamp(i)?;
+
+            Ok((
+                i,
+                Blob::Header(HeaderBlob {
+                    version,
+                    flags,
+                    created_at,
+                    last_maintenance,
+                }),
+            ))
+        }
+        2 | 3 => {
+            let (i, flags) = be_u16(i)?;
+            let (i, data_offset) = be_u32(i)?;
+            let (i, data_len) = be_u32(i)?;
+            let data = slice_at(blob, data_offset, data_len)?.to_vec();
+
+            let (mut i, num_keys) = be_u16(i)?;
+            let (rest, key_info_len) = be_u16(i)?;
+            i = rest;
+            let mut keys = Vec::with_capacity(num_keys.into());
+            for _ in 0..num_keys {
+                let (rest, info) = take(key_info_len)(i)?;
+                i = rest;
+                let (_, key) = parse_key_info(blob, version, info)?;
+                keys.push(key);
+            }
+
+            let (i, serial_len) = be_u16(i)?;
+            let (i, serial_number) = take(serial_len)(i)?;
+
+            let (mut i, num_uids) = be_u16(i)?;
+            let (rest, uid_info_len) = be_u16(i)?;
+            i = rest;
+            let mut user_ids = Vec::with_capacity(num_uids.into());
+            for _ in 0..num_uids {
+                let (rest, info) = take(uid_info_len)(i)?;
+                i = rest;
+                let (info, offset) = be_u32(info)?;
+                let (_, len) = be_u32(info)?;
+                user_ids.push(slice_at(blob, offset, len)?.to_vec());
+            }
+
+            let (i, num_sigs) = be_u16(i)?;
+            let (i, sig_info_len) = be_u16(i)?;
+            let (i, _sigs) = take(usize::from(num_sigs) * usize::from(sig_info_len))(i)?;
+
+            // ownertrust, validity, reserved, recheck after and latest timestamp
+            let (i, _) = take(12u8)(i)?;
+            let (i, created_at) = timestamp(i)?;
+
+            let info = BlobInfo {
+                version,
+                flags,
+                keys,
+                serial_number: serial_number.to_vec(),
+                user_ids,
+                created_at,
+            };
+            let blob = if typ == 2 {
+                Blob::OpenPgp(OpenPgpBlob {
+                    info,
+                    keyblock: data,
+                })
+            } else {
+                Blob::X509(X509Blob {
+                    info,
+                    certificate: data,
+                })
+            };
+
+            Ok((i, blob))
+        }
+        _ => Ok((
+            &[],
+            Blob::Unknown {
+                typ,
+                data: blob.to_vec(),
+            },
+        )),
+    }
+}
+
+fn parse_key_info<'a>(blob: &'a [u8], version: u8, i: &'a [u8]) -> IResult<&'a [u8], KeyInfo> {
+    if version >= 2 {
+        let (i, fingerprint) = take(32u8)(i)?;
+        let (i, flags) = be_u16(i)?;
+        let key_id = KeyId::from_slice(&fingerprint[..8]).ok();
+
+        Ok((
+            i,
+            KeyInfo {
+                fingerprint: fingerprint.to_vec(),
+                key_id,
+                flags,
+            },
+        ))
+    } else {
+        let (i, fingerprint) = take(20u8)(i)?;
+        let (i, key_id_offset) = be_u32(i)?;
+        let (i, flags) = be_u16(i)?;
+        // X.509 certificates have no Key ID
+        let key_id = match key_id_offset {
+            0 => None,
+            offset => Some(KeyId::from_slice(slice_at(blob, offset, 8)?).map_err(nom::Err::Error)?),
+        };
+
+        Ok((
+            i,
+            KeyInfo {
+                fingerprint: fingerprint.to_vec(),
+                key_id,
+                flags,
+            },
+        ))
+    }
+}
+
+/// Returns the `len` bytes at `offset` of `blob`.
+fn slice_at(blob: &[u8], offset: u32, len: u32) -> std::result::Result<&[u8], nom::Err<Error>> {
+    let start = offset as usize;
+    let end = start.saturating_add(len as usize);
+
+    blob.get(start..end)
+        .ok_or_else(|| nom::Err::Error(format_err!("offset {} out of bounds", offset)))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::fs;
+
+    use super::*;
+    use crate::composed::Deserializable;
+    use crate::ser::Serialize;
+    use crate::types::KeyTrait;
+
+    fn header_blob() -> Vec<u8> {
+        let mut blob = vec![0, 0, 0, 32, 1, 1, 0, 2];
+        blob.extend_from_slice(b"KBXf");
+        blob.extend_from_slice(&[0; 4]);
+        blob.extend_from_slice(&1_600_000_000u32.to_be_bytes());
+        blob.extend_from_slice(&1_600_000_001u32.to_be_bytes());
+        blob.extend_from_slice(&[0; 8]);
+        blob
+    }
+
+    /// Creates a version 1 blob, the way GnuPG lays it out.
+    fn openpgp_blob(key: &SignedPublicKey) -> Vec<u8> {
+        let keyblock = key.to_bytes().unwrap();
+        let user_id = key.details.users[0].id.id().to_vec();
+
+        let mut blob = vec![0, 0, 0, 0, 2, 1, 0, 0];
+        // keyblock offset and length, patched below
+        blob.extend_from_slice(&[0; 8]);
+        // one key
+        blob.extend_from_slice(&[0, 1, 0, 28]);
+        let fingerprint_offset = blob.len();
+        blob.extend_from_slice(key.fingerprint().as_bytes());
+        blob.extend_from_slice(&(fingerprint_offset as u32 + 12).to_be_bytes());
+        blob.extend_from_slice(&[0; 4]);
+        // no serial number
+        blob.extend_from_slice(&[0, 0]);
+        // one user id, patched below
+        blob.extend_from_slice(&[0, 1, 0, 12]);
+        let user_id_offset = blob.len();
+        blob.extend_from_slice(&[0; 12]);
+        // no signatures
+        blob.extend_from_slice(&[0, 0, 0, 4]);
+        blob.extend_from_slice(&[0; 12]);
+        blob.extend_from_slice(&1_600_000_000u32.to_be_bytes());
+        // no reserved space
+        blob.extend_from_slice(&[0; 4]);
+
+        let offset = blob.len() as u32;
+        blob[user_id_offset..user_id_offset + 4].copy_from_slice(&offset.to_be_bytes());
+        blob[user_id_offset + 4..user_id_offset + 8]
+            .copy_from_slice(&(user_id.len() as u32).to_be_bytes());
+        blob.extend_from_slice(&user_id);
+
+        let offset = blob.len() as u32;
+        blob[8..12].copy_from_slice(&offset.to_be_bytes());
+        blob[12..16].copy_from_slice(&(keyblock.len() as u32).to_be_bytes());
+        blob.extend_from_slice(&keyblock);
+
+        // checksum
+        blob.extend_from_slice(&[0; 20]);
+        let len = blob.len() as u32;
+        blob[..4].copy_from_slice(&len.to_be_bytes());
+        blob
+    }
+
+    #[test]
+    fn test_read_keybox() {
+        let (key, _) = SignedPublicKey::from_string(
+            &fs::read_to_string("./tests/opengpg-interop/testcases/keys/gnupg-v1-001.asc").unwrap(),
+        )
+        .unwrap();
+
+        let mut keybox = header_blob();
+        keybox.extend(openpgp_blob(&key));
+        // an empty blob
+        keybox.extend_from_slice(&[0, 0, 0, 6, 0, 1]);
+
+        let blobs: Vec<_> = KeyboxReader::new(&keybox[..])
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(blobs.len(), 3);
+
+        match &blobs[0] {
+            Blob::Header(header) => {
+                assert_eq!(header.version, 1);
+                assert_eq!(header.created_at.timestamp(), 1_600_000_000);
+            }
+            blob => panic!("unexpected blob {blob:?}"),
+        }
+        match &blobs[1] {
+            Blob::OpenPgp(blob) => {
+                assert_eq!(blob.info.keys.len(), 1);
+                assert_eq!(blob.info.keys[0].fingerprint, key.fingerprint().to_vec());
+                assert_eq!(blob.info.keys[0].key_id, Some(key.key_id()));
+                assert_eq!(
+                    blob.info.user_ids,
+                    vec![key.details.users[0].id.id().to_vec()]
+                );
+                assert_eq!(blob.certificate().unwrap(), key);
+            }
+            blob => panic!("unexpected blob {blob:?}"),
+        }
+        assert_eq!(blobs[2], Blob::Empty);
+
+        let certs: Vec<_> = KeyboxReader::new(&keybox[..])
+            .certificates()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(certs, vec![key]);
+    }
+
+    #[test]
+    fn test_read_keybox_invalid_offset() {
+        let (key, _) = SignedPublicKey::from_string(
+            &fs::read_to_string("./tests/opengpg-interop/testcases/keys/gnupg-v1-001.asc").unwrap(),
+        )
+        .unwrap();
+
+        let mut blob = openpgp_blob(&key);
+        blob[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+
+        let mut reader = KeyboxReader::new(&blob[..]);
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+}
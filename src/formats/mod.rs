@@ -0,0 +1,24 @@
+//! # Formats
+//!
+//! Readers and writers for file formats, that are used by other implementations to store keys.
+
+pub mod jwk;
+pub mod keybox;
+pub mod paperkey;
+#[cfg(feature = "pkcs8")]
+pub mod pkcs8;
+#[cfg(feature = "spki")]
+pub mod spki;
+#[cfg(feature = "ssh")]
+pub mod ssh;
+pub mod trustdb;
+
+use crate::errors::Result;
+
+/// Strips the prefix of the native point format from an Ed25519 or X25519 public key.
+pub(crate) fn native_point(p: &[u8]) -> Result<&[u8]> {
+    match p.split_first() {
+        Some((0x40, p)) if p.len() == 32 => Ok(p),
+        _ => bail!("invalid native point"),
+    }
+}
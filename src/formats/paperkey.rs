@@ -0,0 +1,288 @@
+//! # Paperkey
+//!
+//! Reader and writer for the format of [paperkey](https://www.jabberwocky.com/software/paperkey/),
+//! a backup of a secret key on paper. Only the secret parts of the key packets are stored,
+//! which are a lot shorter than the full key. Together with the public key, which is not
+//! secret and can be kept anywhere, they restore the secret key.
+//!
+//! The raw format is a version octet, followed by the fingerprint, the length and the secret
+//! parameters of each secret key packet. For printing it is encoded in base16, with a CRC-24
+//! at the end of each line and a final line holding the CRC-24 of all data, so that typing
+//! errors are detected on restore.
+
+use zeroize::Zeroizing;
+
+use crate::composed::{SignedPublicKey, SignedSecretKey, SignedSecretSubKey};
+use crate::errors::Result;
+use crate::packet::{self, PacketTrait};
+use crate::ser::Serialize;
+use crate::types::{Fingerprint, KeyTrait, KeyVersion, SecretParams};
+
+/// The version of the paperkey format.
+const FORMAT_VERSION: u8 = 0;
+
+/// The number of octets on each line of the base16 format.
+const LINE_LEN: usize = 20;
+
+const HEADER: &str = "\
+# File format:
+# a) 1 octet:  Version of the paperkey format (currently 0).
+# b) 1 octet:  OpenPGP key or subkey version (currently 4)
+# c) n octets: Key fingerprint (20 octets for a version 4 key or subkey)
+# d) 2 octets: 16-bit big endian length of the following secret data
+# e) n octets: Secret data: a partial OpenPGP secret key or subkey packet as
+#              specified in RFC 4880, starting with the string-to-key usage
+#              octet and continuing until the end of the packet.
+# Repeat fields b through e as needed to cover all subkeys.
+#
+# Each base16 line ends with a CRC-24 of that line.
+# The entire block of data ends with a CRC-24 of the entire block of data.
+
+";
+
+/// Extracts the secret parts of the primary key and all secret subkeys of `key`, in the raw
+/// paperkey format.
+///
+/// Encrypted secret keys stay encrypted.
+pub fn to_raw(key: &SignedSecretKey) -> Result<Zeroizing<Vec<u8>>> {
+    let mut out = Zeroizing::new(vec![FORMAT_VERSION]);
+    write_secret(
+        &mut out,
+        &key.primary_key.fingerprint(),
+        key.primary_key.secret_params(),
+    )?;
+    for subkey in &key.secret_subkeys {
+        write_secret(
+            &mut out,
+            &subkey.key.fingerprint(),
+            subkey.key.secret_params(),
+        )?;
+    }
+
+    Ok(out)
+}
+
+/// Extracts the secret parts of `key` like [`to_raw`], encoded in the printable base16 format
+/// of paperkey.
+pub fn to_base16(key: &SignedSecretKey) -> Result<Zeroizing<String>> {
+    let data = to_raw(key)?;
+
+    let mut out = Zeroizing::new(format!(
+        "# Secret portions of key {}\n#\n{}",
+        hex::encode_upper(key.fingerprint()),
+        HEADER
+    ));
+    let mut number = 1;
+    for line in data.chunks(LINE_LEN) {
+        out.push_str(&format!("{number:3}: "));
+        for byte in line {
+            out.push_str(&format!("{byte:02X} "));
+        }
+        out.push_str(&format!("{:06X}\n", crc24::hash_raw(line)));
+        number += 1;
+    }
+    out.push_str(&format!("{number:3}: {:06X}\n", crc24::hash_raw(&data)));
+
+    Ok(out)
+}
+
+/// Restores the secret key from the public key `public` and the secret parts in the raw
+/// paperkey format.
+///
+/// Subkeys without secret parts stay public subkeys.
+pub fn from_raw(public: &SignedPublicKey, data: &[u8]) -> Result<SignedSecretKey> {
+    let (version, mut rest) = data
+        .split_first()
+        .ok_or_else(|| format_err!("empty paperkey"))?;
+    ensure_eq!(*version, FORMAT_VERSION, "unsupported paperkey version");
+
+    let mut secrets = Vec::new();
+    while let Some((&version, data)) = rest.split_first() {
+        let len = match KeyVersion::from(version) {
+            KeyVersion::V2 | KeyVersion::V3 => 16,
+            KeyVersion::V4 => 20,
+            _ => unsupported_err!("paperkey for version {} keys", version),
+        };
+        ensure!(data.len() >= len + 2, "truncated paperkey");
+        let (fingerprint, data) = data.split_at(len);
+        let (secret_len, data) = data.split_at(2);
+        let secret_len = u16::from_be_bytes([secret_len[0], secret_len[1]]) as usize;
+        ensure!(data.len() >= secret_len, "truncated paperkey");
+        let (secret, data) = data.split_at(secret_len);
+
+        secrets.push((Fingerprint::new(version.into(), fingerprint)?, secret));
+        rest = data;
+    }
+    let find = |fingerprint: &Fingerprint| {
+        secrets
+            .iter()
+            .find(|(fp, _)| fp == fingerprint)
+            .map(|(_, secret)| *secret)
+    };
+
+    let secret = find(&public.primary_key.fingerprint())
+        .ok_or_else(|| format_err!("the paperkey does not contain the primary key"))?;
+    let primary_key = packet::SecretKey::from_slice(
+        public.primary_key.packet_version(),
+        &Zeroizing::new([&public.primary_key.to_bytes()?[..], secret].concat()),
+    )?;
+
+    let mut public_subkeys = Vec::new();
+    let mut secret_subkeys = Vec::new();
+    for subkey in &public.public_subkeys {
+        match find(&subkey.key.fingerprint()) {
+            Some(secret) => {
+                let key = packet::SecretSubkey::from_slice(
+                    subkey.key.packet_version(),
+                    &Zeroizing::new([&subkey.key.to_bytes()?[..], secret].concat()),
+                )?;
+                let mut secret = SignedSecretSubKey::new(key, subkey.signatures.clone());
+                secret.experimental = subkey.experimental.clone();
+                secret_subkeys.push(secret);
+            }
+            None => public_subkeys.push(subkey.clone()),
+        }
+    }
+
+    Ok(SignedSecretKey::new(
+        primary_key,
+        public.details.clone(),
+        public_subkeys,
+        secret_subkeys,
+    ))
+}
+
+/// Restores the secret key from the public key `public` and the secret parts in the base16
+/// format, e.g. as typed in from a printout.
+///
+/// The checksums of every line are verified, to point out typing errors.
+pub fn from_base16(public: &SignedPublicKey, text: &str) -> Result<SignedSecretKey> {
+    from_raw(public, &decode_base16(text)?)
+}
+
+fn decode_base16(text: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let mut data = Zeroizing::new(Vec::new());
+    let mut expected = 1;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (number, values) = line
+            .split_once(':')
+            .ok_or_else(|| format_err!("invalid paperkey line {:?}", line))?;
+        let number: usize = number.trim().parse()?;
+        ensure_eq!(number, expected, "unexpected paperkey line number");
+
+        let mut values: Vec<_> = values.split_whitespace().collect();
+        let crc = values
+            .pop()
+            .ok_or_else(|| format_err!("missing checksum on paperkey line {}", number))?;
+        let crc = u32::from_str_radix(crc, 16)?;
+
+        // the last line only holds the checksum of all data
+        if values.is_empty() {
+            ensure_eq!(crc, crc24::hash_raw(&data), "invalid paperkey checksum");
+            return Ok(data);
+        }
+
+        let line = Zeroizing::new(hex::decode(values.concat())?);
+        ensure_eq!(
+            crc,
+            crc24::hash_raw(&line),
+            "invalid checksum on paperkey line {}",
+            number
+        );
+        data.extend_from_slice(&line);
+        expected += 1;
+    }
+
+    bail!("incomplete paperkey, the final checksum is missing")
+}
+
+fn write_secret(out: &mut Vec<u8>, fingerprint: &Fingerprint, params: &SecretParams) -> Result<()> {
+    let secret = Zeroizing::new(params.to_bytes()?);
+
+    out.push(fingerprint.version().into());
+    out.extend_from_slice(fingerprint.as_bytes());
+    out.extend_from_slice(&u16::try_from(secret.len())?.to_be_bytes());
+    out.extend_from_slice(&secret);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::composed::{KeyType, SubkeyParamsBuilder};
+    use crate::test_util::key_params;
+
+    fn key(passphrase: Option<String>) -> SignedSecretKey {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        key_params("Alice <alice@example.com>")
+            .passphrase(passphrase.clone())
+            .subkey(
+                SubkeyParamsBuilder::default()
+                    .key_type(KeyType::ECDH)
+                    .can_encrypt(true)
+                    .passphrase(passphrase)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+            .generate_signed_with_rng(&mut rng)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for passphrase in [None, Some("hello".to_string())] {
+            let key = key(passphrase);
+            let public = SignedPublicKey::from(key.clone());
+
+            let text = to_base16(&key).unwrap();
+            assert!(text.starts_with(&format!(
+                "# Secret portions of key {}\n",
+                hex::encode_upper(key.fingerprint())
+            )));
+            assert!(text.contains("\n  1: 00 04 "));
+
+            let restored = from_base16(&public, &text).unwrap();
+            assert_eq!(restored, key);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let key = key(None);
+        let public = SignedPublicKey::from(key.clone());
+        let text = to_base16(&key).unwrap();
+
+        // a typing error on the first line
+        let typo = text.replacen("  1: 00 04 ", "  1: 00 05 ", 1);
+        let err = from_base16(&public, &typo).unwrap_err();
+        assert!(err.to_string().contains("paperkey line 1"), "{}", err);
+
+        // a missing line
+        let lines: Vec<_> = text
+            .lines()
+            .filter(|line| !line.starts_with("  2:"))
+            .collect();
+        assert!(from_base16(&public, &lines.join("\n")).is_err());
+
+        // a missing final checksum
+        let lines: Vec<_> = text.lines().collect();
+        assert!(from_base16(&public, &lines[..lines.len() - 1].join("\n")).is_err());
+
+        // the secret parts of an unknown key
+        let mut raw = to_raw(&key).unwrap().to_vec();
+        raw[2..22].copy_from_slice(&[0; 20]);
+        assert!(from_raw(&public, &raw).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+//! # PKCS#8 and SEC1
+//!
+//! Reader for secret keys stored in the DER encoded `PrivateKeyInfo` structure of
+//! [PKCS#8](https://datatracker.ietf.org/doc/html/rfc5208), as used by OpenSSL and most
+//! key management systems, and in the `ECPrivateKey` structure of
+//! [SEC1](https://datatracker.ietf.org/doc/html/rfc5915).
+//!
+//! PKCS#8 keys can be RSA, ECDSA on the NIST P-256, P-384 and secp256k1 curves, Ed25519 and
+//! X25519 keys. The latter are imported as ECDH keys.
+
+use const_oid::ObjectIdentifier;
+use pkcs8::der::asn1::OctetStringRef;
+use pkcs8::der::Decode;
+use pkcs8::PrivateKeyInfo;
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::RsaPrivateKey;
+use sec1::EcPrivateKey;
+use zeroize::Zeroizing;
+
+use crate::crypto::ecc_curve::{ecc_curve_from_oid, ECCCurve};
+use crate::crypto::{ecdh, ecdsa, eddsa, rsa as pgp_rsa};
+use crate::errors::Result;
+use crate::types::{PlainSecretParams, PublicParams};
+
+const RSA_ENCRYPTION: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const ID_EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+const ID_X25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.110");
+const ID_ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+
+/// Reads the secret key from a DER encoded PKCS#8 `PrivateKeyInfo` structure, returning its
+/// OpenPGP key parameters.
+///
+/// Encrypted PKCS#8 keys are not supported.
+pub fn from_pkcs8_der(der: &[u8]) -> Result<(PublicParams, PlainSecretParams)> {
+    let info =
+        PrivateKeyInfo::from_der(der).map_err(|err| format_err!("invalid PKCS#8: {}", err))?;
+
+    match info.algorithm.oid {
+        RSA_ENCRYPTION => {
+            let key = RsaPrivateKey::from_pkcs1_der(info.private_key)
+                .map_err(|err| format_err!("invalid RSA key: {}", err))?;
+            pgp_rsa::import_key(&key)
+        }
+        ID_EC_PUBLIC_KEY => {
+            let curve = info
+                .algorithm
+                .parameters_oid()
+                .ok()
+                .and_then(|oid| ecc_curve_from_oid(oid.as_bytes()))
+                .ok_or_else(|| format_err!("invalid PKCS#8: missing curve"))?;
+            from_sec1(info.private_key, Some(curve))
+        }
+        ID_ED25519 => Ok(eddsa::import_key(&*curve_private_key(info.private_key)?)),
+        ID_X25519 => Ok(ecdh::import_key(&*curve_private_key(info.private_key)?)),
+        oid => unsupported_err!("PKCS#8 keys of algorithm {}", oid),
+    }
+}
+
+/// Reads the secret key from a DER encoded SEC1 `ECPrivateKey` structure, returning its
+/// OpenPGP key parameters.
+///
+/// The structure has to name its curve.
+pub fn from_sec1_der(der: &[u8]) -> Result<(PublicParams, PlainSecretParams)> {
+    from_sec1(der, None)
+}
+
+/// Reads a SEC1 `ECPrivateKey`, whose curve may be known from the surrounding structure.
+fn from_sec1(der: &[u8], curve: Option<ECCCurve>) -> Result<(PublicParams, PlainSecretParams)> {
+    let key = EcPrivateKey::from_der(der).map_err(|err| format_err!("invalid SEC1: {}", err))?;
+
+    let named_curve = match key.parameters.and_then(|params| params.named_curve()) {
+        Some(oid) => Some(
+            ecc_curve_from_oid(oid.as_bytes())
+                .ok_or_else(|| format_err!("unsupported curve {}", oid))?,
+        ),
+        None => None,
+    };
+    let curve = match (curve, named_curve) {
+        (Some(curve), Some(named_curve)) => {
+            ensure_eq!(curve, named_curve, "inconsistent curves");
+            curve
+        }
+        (Some(curve), None) | (None, Some(curve)) => curve,
+        (None, None) => bail!("invalid SEC1: missing curve"),
+    };
+
+    ecdsa::import_key(&curve, key.private_key)
+}
+
+/// Reads the `CurvePrivateKey` of Ed25519 and X25519 keys, defined in RFC 8410.
+fn curve_private_key(der: &[u8]) -> Result<Zeroizing<[u8; 32]>> {
+    let key = OctetStringRef::from_der(der)
+        .map_err(|err| format_err!("invalid curve private key: {}", err))?;
+
+    let mut secret = Zeroizing::new([0u8; 32]);
+    ensure_eq!(key.as_bytes().len(), 32, "invalid curve private key length");
+    secret.copy_from_slice(key.as_bytes());
+
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    #[cfg(feature = "nist-curves")]
+    use crate::types::EcdsaPublicParams;
+
+    #[test]
+    fn test_ed25519() {
+        // test vector from RFC 8410, section 10.3
+        let der = hex::decode(
+            "302e020100300506032b657004220420\
+             d4ee72dbf913584ad5b6d8f1f769f8ad3afe7c28cbf1d4fbe097a88f44755842",
+        )
+        .unwrap();
+        let (public, secret) = from_pkcs8_der(&der).unwrap();
+
+        assert_eq!(
+            public,
+            PublicParams::EdDSA {
+                curve: ECCCurve::Ed25519,
+                q: hex::decode(
+                    "4019bf44096984cdfe8541bac167dc3b96c85086aa30b6b6cb0c5c38ad703166e1"
+                )
+                .unwrap()
+                .into()
+            }
+        );
+        assert!(matches!(secret, PlainSecretParams::EdDSA(_)));
+    }
+
+    #[test]
+    fn test_rsa_ecdsa() {
+        use rand::SeedableRng;
+        use rand_chacha::ChaCha8Rng;
+        use rsa::pkcs8::EncodePrivateKey;
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        let key = RsaPrivateKey::new(&mut rng, 2048).unwrap();
+        let der = key.to_pkcs8_der().unwrap();
+        assert_eq!(
+            from_pkcs8_der(der.as_bytes()).unwrap(),
+            pgp_rsa::import_key(&key).unwrap()
+        );
+
+        #[cfg(feature = "nist-curves")]
+        {
+            let key = p384::SecretKey::random(&mut rng);
+            let der = key.to_pkcs8_der().unwrap();
+            assert_eq!(
+                from_pkcs8_der(der.as_bytes()).unwrap(),
+                ecdsa::import_key(&ECCCurve::P384, &key.to_bytes()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_x25519() {
+        // the private key of Alice from RFC 7748, section 6.1
+        let der = hex::decode(
+            "302e020100300506032b656e04220420\
+             77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a",
+        )
+        .unwrap();
+        let (public, _) = from_pkcs8_der(&der).unwrap();
+
+        match public {
+            PublicParams::ECDH { curve, p, .. } => {
+                assert_eq!(curve, ECCCurve::Curve25519);
+                assert_eq!(
+                    hex::encode(p.as_bytes()),
+                    "408520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a"
+                );
+            }
+            _ => panic!("unexpected parameters {:?}", public),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "nist-curves")]
+    fn test_sec1() {
+        // a P-256 key, without the optional public key
+        let der = hex::decode(
+            "30310201010420\
+             8d2d7e2ba52e4aee72f39c2d5e18bbe5e1c5c7d6a3d1f2ac7a4a2e1b9f0e1d2c\
+             a00a06082a8648ce3d030107",
+        )
+        .unwrap();
+        let (public, _) = from_sec1_der(&der).unwrap();
+        assert!(matches!(
+            public,
+            PublicParams::ECDSA(EcdsaPublicParams::P256 { .. })
+        ));
+
+        // without the curve, a SEC1 key can not be read
+        let der = hex::decode(
+            "30250201010420\
+             8d2d7e2ba52e4aee72f39c2d5e18bbe5e1c5c7d6a3d1f2ac7a4a2e1b9f0e1d2c",
+        )
+        .unwrap();
+        assert!(from_sec1_der(&der).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+//! # SubjectPublicKeyInfo
+//!
+//! Writer for the `SubjectPublicKeyInfo` structure of
+//! [RFC 5280](https://datatracker.ietf.org/doc/html/rfc5280#section-4.1.2.7), the public key
+//! format of X.509 certificates, TLS libraries and `openssl pkey -pubin`.
+//!
+//! RSA keys, ECDSA and ECDH keys on named curves, as well as Ed25519 and X25519 keys
+//! ([RFC 8410](https://datatracker.ietf.org/doc/html/rfc8410)) are supported.
+
+use const_oid::ObjectIdentifier;
+use rsa::pkcs1::RsaPublicKey;
+use spki::der::asn1::{BitStringRef, UintRef};
+use spki::der::pem::{self, LineEnding};
+use spki::der::{AnyRef, Encode};
+use spki::{AlgorithmIdentifierRef, SubjectPublicKeyInfoRef};
+
+use crate::crypto::ecc_curve::ECCCurve;
+use crate::errors::{Error, Result};
+use crate::formats::native_point;
+use crate::types::PublicParams;
+
+const RSA_ENCRYPTION: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const ID_EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+const ID_X25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.110");
+const ID_ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+
+/// Encodes the public key with the parameters `params` as a DER encoded
+/// `SubjectPublicKeyInfo`.
+pub fn to_der(params: &PublicParams) -> Result<Vec<u8>> {
+    let rsa_key;
+    let named_curve;
+    let (algorithm, parameters, key) = match params {
+        PublicParams::RSA { n, e } => {
+            rsa_key = RsaPublicKey {
+                modulus: UintRef::new(n.as_bytes()).map_err(encoding_err)?,
+                public_exponent: UintRef::new(e.as_bytes()).map_err(encoding_err)?,
+            }
+            .to_der()
+            .map_err(encoding_err)?;
+            (RSA_ENCRYPTION, Some(AnyRef::NULL), &rsa_key[..])
+        }
+        PublicParams::ECDSA(ecdsa) => {
+            named_curve = curve_oid(&ecdsa.curve())?;
+            (
+                ID_EC_PUBLIC_KEY,
+                Some(AnyRef::from(&named_curve)),
+                ecdsa.point(),
+            )
+        }
+        PublicParams::ECDH {
+            curve: ECCCurve::Curve25519,
+            p,
+            ..
+        } => (ID_X25519, None, native_point(p.as_bytes())?),
+        PublicParams::ECDH { curve, p, .. } => {
+            named_curve = curve_oid(curve)?;
+            (
+                ID_EC_PUBLIC_KEY,
+                Some(AnyRef::from(&named_curve)),
+                p.as_bytes(),
+            )
+        }
+        PublicParams::EdDSA {
+            curve: ECCCurve::Ed25519,
+            q,
+        } => (ID_ED25519, None, native_point(q.as_bytes())?),
+        _ => unsupported_err!("SubjectPublicKeyInfo for parameters {:?}", params),
+    };
+
+    SubjectPublicKeyInfoRef {
+        algorithm: AlgorithmIdentifierRef {
+            oid: algorithm,
+            parameters,
+        },
+        subject_public_key: BitStringRef::from_bytes(key).map_err(encoding_err)?,
+    }
+    .to_der()
+    .map_err(encoding_err)
+}
+
+/// Encodes the public key with the parameters `params` as a PEM encoded
+/// `SubjectPublicKeyInfo`, i.e. a `-----BEGIN PUBLIC KEY-----` block.
+pub fn to_pem(params: &PublicParams) -> Result<String> {
+    pem::encode_string("PUBLIC KEY", LineEnding::LF, &to_der(params)?)
+        .map_err(|err| format_err!("failed to encode PEM: {}", err))
+}
+
+fn encoding_err(err: spki::der::Error) -> Error {
+    format_err!("failed to encode SubjectPublicKeyInfo: {}", err)
+}
+
+fn curve_oid(curve: &ECCCurve) -> Result<ObjectIdentifier> {
+    ObjectIdentifier::from_bytes(&curve.oid())
+        .map_err(|err| format_err!("invalid curve {}: {}", curve.name(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use rsa::pkcs8::DecodePublicKey;
+
+    use super::*;
+    #[cfg(feature = "nist-curves")]
+    use crate::crypto::ecdsa;
+    use crate::crypto::{ecdh, rsa as pgp_rsa};
+
+    #[test]
+    fn test_ed25519() {
+        // example from RFC 8410, section 10.1
+        let params = PublicParams::EdDSA {
+            curve: ECCCurve::Ed25519,
+            q: hex::decode("4019bf44096984cdfe8541bac167dc3b96c85086aa30b6b6cb0c5c38ad703166e1")
+                .unwrap()
+                .into(),
+        };
+
+        assert_eq!(
+            to_pem(&params).unwrap(),
+            "-----BEGIN PUBLIC KEY-----\n\
+             MCowBQYDK2VwAyEAGb9ECWmEzf6FQbrBZ9w7lshQhqowtrbLDFw4rXAxZuE=\n\
+             -----END PUBLIC KEY-----\n"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        let key = rsa::RsaPrivateKey::new(&mut rng, 2048).unwrap();
+        let (params, _) = pgp_rsa::import_key(&key).unwrap();
+        let der = to_der(&params).unwrap();
+        assert_eq!(
+            rsa::RsaPublicKey::from_public_key_der(&der).unwrap(),
+            key.into()
+        );
+
+        #[cfg(feature = "nist-curves")]
+        {
+            let key = p256::SecretKey::random(&mut rng);
+            let (params, _) = ecdsa::import_key(&ECCCurve::P256, &key.to_bytes()).unwrap();
+            let der = to_der(&params).unwrap();
+            assert_eq!(
+                p256::PublicKey::from_public_key_der(&der).unwrap(),
+                key.public_key()
+            );
+        }
+
+        let secret = [7u8; 32];
+        let (params, _) = ecdh::import_key(&secret);
+        let der = to_der(&params).unwrap();
+        let public = x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::from(secret));
+        assert_eq!(
+            der,
+            [
+                &hex::decode("302a300506032b656e032100").unwrap(),
+                &public.as_bytes()[..]
+            ]
+            .concat()
+        );
+    }
+}
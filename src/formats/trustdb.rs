@@ -0,0 +1,373 @@
+//! # GnuPG trust
+//!
+//! Readers for the trust decisions of GnuPG, to migrate them to other applications or to the
+//! [web of trust](crate::wot) of this crate:
+//!
+//! - the text format of `gpg --export-ownertrust`, one `<fingerprint>:<ownertrust>:` line per
+//!   key with an assigned owner trust,
+//! - the trust database `trustdb.gpg` itself, read-only, which also holds the computed
+//!   validity of the User IDs.
+//!
+//! The trust database is a sequence of 40 byte records. Only the version, trust and validity
+//! records are read, the hash tables GnuPG uses for lookups are skipped.
+//!
+//! Ref: https://git.gnupg.org/cgi-bin/gitweb.cgi?p=gnupg.git;a=blob;f=g10/tdbio.c
+
+use chrono::{DateTime, TimeZone, Utc};
+use num_enum::{FromPrimitive, IntoPrimitive};
+use ripemd::{Digest, Ripemd160};
+
+use crate::errors::Result;
+use crate::types::{Fingerprint, KeyVersion};
+
+/// The length of a record of the trust database.
+const RECORD_LEN: usize = 40;
+
+const RECTYPE_VER: u8 = 1;
+const RECTYPE_TRUST: u8 = 12;
+const RECTYPE_VALID: u8 = 13;
+
+/// The bits of owner trust and validity values holding the [`TrustLevel`].
+const TRUST_MASK: u8 = 0x0f;
+const TRUST_FLAG_REVOKED: u8 = 0x20;
+const TRUST_FLAG_DISABLED: u8 = 0x80;
+
+/// An owner trust or validity value of GnuPG.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, FromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum TrustLevel {
+    Unknown = 0,
+    Expired = 1,
+    Undefined = 2,
+    Never = 3,
+    Marginal = 4,
+    Full = 5,
+    Ultimate = 6,
+
+    #[num_enum(catch_all)]
+    Other(u8),
+}
+
+/// An owner trust assignment, as exported by `gpg --export-ownertrust`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnerTrustEntry {
+    /// The fingerprint of the primary key.
+    pub fingerprint: Fingerprint,
+    pub trust: TrustLevel,
+    /// The key was disabled by the user.
+    pub disabled: bool,
+}
+
+impl OwnerTrustEntry {
+    fn from_value(fingerprint: Fingerprint, value: u8) -> Self {
+        OwnerTrustEntry {
+            fingerprint,
+            trust: TrustLevel::from(value & TRUST_MASK),
+            disabled: value & TRUST_FLAG_DISABLED != 0,
+        }
+    }
+}
+
+/// Parses the output of `gpg --export-ownertrust`.
+///
+/// Empty lines and comments, starting with `#`, are skipped.
+pub fn parse_ownertrust(text: &str) -> Result<Vec<OwnerTrustEntry>> {
+    let mut entries = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split(':');
+        let (Some(fingerprint), Some(value)) = (fields.next(), fields.next()) else {
+            bail!("invalid ownertrust line {:?}", line);
+        };
+        let value: u8 = value.parse()?;
+
+        entries.push(OwnerTrustEntry::from_value(
+            Fingerprint::from_hex(fingerprint)?,
+            value,
+        ));
+    }
+
+    Ok(entries)
+}
+
+/// The contents of a GnuPG trust database, `trustdb.gpg`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustDb {
+    pub version: u8,
+    /// The number of marginally trusted introducers needed for a valid User ID.
+    pub marginals_needed: u8,
+    /// The number of fully trusted introducers needed for a valid User ID.
+    pub completes_needed: u8,
+    /// The maximum length of certification chains.
+    pub max_cert_depth: u8,
+    /// The trust model, `1` for the classic PGP model.
+    pub trust_model: u8,
+    /// The minimum certification level, that certifications need to count.
+    pub min_cert_level: u8,
+    pub created_at: DateTime<Utc>,
+    /// When the validity values need to be computed again, if ever.
+    pub next_check: Option<DateTime<Utc>>,
+    pub keys: Vec<KeyTrust>,
+}
+
+/// The trust record of a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyTrust {
+    /// The fingerprint of the primary key.
+    pub fingerprint: Fingerprint,
+    pub ownertrust: TrustLevel,
+    /// The key was disabled by the user.
+    pub disabled: bool,
+    /// The length of the shortest certification chain from an ultimately trusted key.
+    pub depth: u8,
+    /// The minimum owner trust, derived from trust signatures.
+    pub min_ownertrust: TrustLevel,
+    pub user_ids: Vec<UserIdValidity>,
+}
+
+/// The computed validity of a User ID of a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserIdValidity {
+    /// The RIPEMD-160 hash of the User ID.
+    pub name_hash: [u8; 20],
+    pub validity: TrustLevel,
+    /// The User ID, or the key, is revoked.
+    pub revoked: bool,
+    /// The number of fully trusted introducers that certified the User ID.
+    pub full_count: u8,
+    /// The number of marginally trusted introducers that certified the User ID.
+    pub marginal_count: u8,
+}
+
+impl TrustDb {
+    /// Reads a trust database.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        ensure!(
+            !data.is_empty() && data.len() % RECORD_LEN == 0,
+            "invalid trustdb length {}",
+            data.len()
+        );
+        let records: Vec<&[u8]> = data.chunks_exact(RECORD_LEN).collect();
+
+        let ver = records[0];
+        ensure!(
+            ver[0] == RECTYPE_VER && &ver[1..4] == b"gpg",
+            "not a trustdb file"
+        );
+        ensure_eq!(ver[4], 3, "unsupported trustdb version");
+
+        let mut keys = Vec::new();
+        for record in records.iter().filter(|r| r[0] == RECTYPE_TRUST) {
+            let mut user_ids = Vec::new();
+            let mut next = be_u32(&record[26..30]);
+            while next != 0 {
+                let valid = records
+                    .get(next as usize)
+                    .filter(|r| r[0] == RECTYPE_VALID)
+                    .ok_or_else(|| format_err!("invalid trustdb record {}", next))?;
+                ensure!(
+                    user_ids.len() < records.len(),
+                    "loop in trustdb validity list"
+                );
+
+                user_ids.push(UserIdValidity {
+                    name_hash: valid[2..22].try_into().expect("fixed length"),
+                    validity: TrustLevel::from(valid[22] & TRUST_MASK),
+                    revoked: valid[22] & TRUST_FLAG_REVOKED != 0,
+                    full_count: valid[27],
+                    marginal_count: valid[28],
+                });
+                next = be_u32(&valid[23..27]);
+            }
+
+            let ownertrust = OwnerTrustEntry::from_value(
+                Fingerprint::new(KeyVersion::V4, &record[2..22])?,
+                record[22],
+            );
+            keys.push(KeyTrust {
+                fingerprint: ownertrust.fingerprint,
+                ownertrust: ownertrust.trust,
+                disabled: ownertrust.disabled,
+                depth: record[23],
+                min_ownertrust: TrustLevel::from(record[24] & TRUST_MASK),
+                user_ids,
+            });
+        }
+
+        Ok(TrustDb {
+            version: ver[4],
+            marginals_needed: ver[5],
+            completes_needed: ver[6],
+            max_cert_depth: ver[7],
+            trust_model: ver[8],
+            min_cert_level: ver[9],
+            created_at: timestamp(&ver[12..16])
+                .ok_or_else(|| format_err!("invalid trustdb creation time"))?,
+            next_check: timestamp(&ver[16..20]).filter(|t| t.timestamp() != 0),
+            keys,
+        })
+    }
+
+    /// The keys with an assigned owner trust, like `gpg --export-ownertrust` lists them.
+    pub fn ownertrust(&self) -> Vec<OwnerTrustEntry> {
+        self.keys
+            .iter()
+            .filter(|key| key.ownertrust != TrustLevel::Unknown || key.disabled)
+            .map(|key| OwnerTrustEntry {
+                fingerprint: key.fingerprint.clone(),
+                trust: key.ownertrust,
+                disabled: key.disabled,
+            })
+            .collect()
+    }
+
+    /// The trust record of the key with the primary key fingerprint `fingerprint`.
+    pub fn key(&self, fingerprint: &Fingerprint) -> Option<&KeyTrust> {
+        self.keys.iter().find(|key| &key.fingerprint == fingerprint)
+    }
+}
+
+impl KeyTrust {
+    /// The computed validity of the User ID `user_id` of this key.
+    pub fn validity(&self, user_id: &[u8]) -> Option<&UserIdValidity> {
+        let hash: [u8; 20] = Ripemd160::digest(user_id).into();
+        self.user_ids.iter().find(|uid| uid.name_hash == hash)
+    }
+}
+
+fn be_u32(data: &[u8]) -> u32 {
+    u32::from_be_bytes(data.try_into().expect("fixed length"))
+}
+
+fn timestamp(data: &[u8]) -> Option<DateTime<Utc>> {
+    Utc.timestamp_opt(i64::from(be_u32(data)), 0).single()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    const ME: &str = "D01130A92A4BC7755C246DAB1AE2B6CDA5AD44C3";
+    const ALICE: &str = "9E57E251148ECFB40D10082DEE9F3CAE0660EB84";
+
+    fn record(typ: u8, body: &[u8]) -> Vec<u8> {
+        let mut record = vec![typ, 0];
+        record.extend_from_slice(body);
+        record.resize(RECORD_LEN, 0);
+        record
+    }
+
+    /// A trust database the way GnuPG writes it, with an ultimately trusted key, that
+    /// certified a marginally trusted one.
+    fn trustdb() -> Vec<u8> {
+        // "gpg" overlaps the reserved octet in the version record
+        let mut db = hex::decode(
+            "0167706703030105010200006ad2b94c6e95204c0000000000000000000000000000000000000001",
+        )
+        .unwrap();
+        // the hash table
+        for _ in 1..30 {
+            db.extend(record(10, &[]));
+        }
+
+        let trust = |fingerprint: &str, ownertrust: u8, valid: u32| {
+            let mut body = hex::decode(fingerprint).unwrap();
+            body.extend_from_slice(&[ownertrust, 0, 0, 0]);
+            body.extend_from_slice(&valid.to_be_bytes());
+            record(RECTYPE_TRUST, &body)
+        };
+        let valid = |user_id: &str, validity: u8, full_count: u8| {
+            let mut body = Ripemd160::digest(user_id).to_vec();
+            body.push(validity);
+            body.extend_from_slice(&[0; 4]);
+            body.extend_from_slice(&[full_count, 0]);
+            record(RECTYPE_VALID, &body)
+        };
+        db.extend(trust(ME, 6, 33));
+        db.extend(trust(ALICE, 4 | TRUST_FLAG_DISABLED, 32));
+        db.extend(valid("Alice <alice@example.com>", 5, 1));
+        db.extend(valid("Me <me@example.com>", 6, 0));
+        db
+    }
+
+    #[test]
+    fn test_trustdb() {
+        let db = TrustDb::from_bytes(&trustdb()).unwrap();
+        assert_eq!(db.version, 3);
+        assert_eq!(db.marginals_needed, 3);
+        assert_eq!(db.completes_needed, 1);
+        assert_eq!(db.max_cert_depth, 5);
+        assert_eq!(db.created_at.timestamp(), 0x6ad2b94c);
+        assert_eq!(db.next_check.unwrap().timestamp(), 0x6e95204c);
+        assert_eq!(db.keys.len(), 2);
+
+        let me = db.key(&Fingerprint::from_hex(ME).unwrap()).unwrap();
+        assert_eq!(me.ownertrust, TrustLevel::Ultimate);
+        assert_eq!(
+            me.validity(b"Me <me@example.com>").unwrap().validity,
+            TrustLevel::Ultimate
+        );
+        let alice = db.key(&Fingerprint::from_hex(ALICE).unwrap()).unwrap();
+        assert_eq!(alice.ownertrust, TrustLevel::Marginal);
+        assert!(alice.disabled);
+        let uid = alice.validity(b"Alice <alice@example.com>").unwrap();
+        assert_eq!(uid.validity, TrustLevel::Full);
+        assert_eq!(uid.full_count, 1);
+        assert!(alice.validity(b"Alice <alice@example.org>").is_none());
+
+        assert_eq!(
+            db.ownertrust(),
+            parse_ownertrust(&format!("{ME}:6:\n{ALICE}:132:\n")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_trustdb_invalid() {
+        let mut db = trustdb();
+        assert!(TrustDb::from_bytes(&db[..db.len() - 1]).is_err());
+
+        // a validity list pointing to a trust record
+        let alice = 31 * RECORD_LEN;
+        db[alice + 26..alice + 30].copy_from_slice(&30u32.to_be_bytes());
+        assert!(TrustDb::from_bytes(&db).is_err());
+
+        db[1..4].copy_from_slice(b"pgp");
+        assert!(TrustDb::from_bytes(&db).is_err());
+    }
+
+    #[test]
+    fn test_parse_ownertrust() {
+        let text = format!(
+            "# List of assigned trustvalues, created Fri Oct 16 23:54:52 2026 UTC\n\
+             # (Use \"gpg --import-ownertrust\" to restore them)\n\
+             {ME}:6:\n\
+             \n\
+             {ALICE}:3:\n"
+        );
+        let entries = parse_ownertrust(&text).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                OwnerTrustEntry {
+                    fingerprint: Fingerprint::from_hex(ME).unwrap(),
+                    trust: TrustLevel::Ultimate,
+                    disabled: false,
+                },
+                OwnerTrustEntry {
+                    fingerprint: Fingerprint::from_hex(ALICE).unwrap(),
+                    trust: TrustLevel::Never,
+                    disabled: false,
+                },
+            ]
+        );
+
+        assert!(parse_ownertrust("D01130A92A4BC7755C246DAB1AE2B6CDA5AD44C3\n").is_err());
+        assert!(parse_ownertrust("D01130A9:6:\n").is_err());
+        assert!(parse_ownertrust(&format!("{ME}:full:\n")).is_err());
+    }
+}
@@ -0,0 +1,908 @@
+//! # gpg-agent
+//!
+//! A client for gpg-agent, speaking the
+//! [Assuan](https://www.gnupg.org/documentation/manuals/assuan/) protocol, to use keys held by
+//! GnuPG, including keys on smartcards it manages.
+//!
+//! The agent addresses keys by their [`Keygrip`], which is derived from the public key
+//! parameters. An [`AgentKey`] pairs a public key with the agent holding its secret key, and
+//! implements [`Signer`] and [`Decryptor`].
+//!
+//! ```rust,no_run
+//! # #[cfg(unix)]
+//! # fn main() -> pgp::errors::Result<()> {
+//! use pgp::crypto::hash::HashAlgorithm;
+//! use pgp::gpg_agent::GpgAgent;
+//! use pgp::types::ExternalKey;
+//! use pgp::{Deserializable, Message, SignedPublicKey};
+//!
+//! let (cert, _headers) = SignedPublicKey::from_string(&std::fs::read_to_string("key.asc")?)?;
+//! let agent = GpgAgent::connect_default()?;
+//! let key = agent.key(&cert.primary_key, cert.primary_key.public_params())?;
+//!
+//! let msg = Message::new_literal("", "hello").sign(
+//!     &ExternalKey(key),
+//!     String::new,
+//!     HashAlgorithm::SHA2_256,
+//! )?;
+//! # Ok(())
+//! # }
+//! # #[cfg(not(unix))]
+//! # fn main() {}
+//! ```
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use rand::{CryptoRng, Rng};
+use sha1::{Digest, Sha1};
+use zeroize::Zeroizing;
+
+use crate::crypto::ecc_curve::ECCCurve;
+use crate::crypto::ecdh;
+use crate::crypto::hash::HashAlgorithm;
+use crate::crypto::public_key::PublicKeyAlgorithm;
+use crate::errors::Result;
+use crate::types::{
+    Decryptor, EcdsaPublicParams, Fingerprint, KeyId, KeyTrait, Mpi, PublicKeyTrait, PublicParams,
+    Signer,
+};
+
+/// Assuan lines are limited to 1000 bytes, so data is sent in chunks that stay below that
+/// even when every byte needs escaping.
+const DATA_CHUNK_SIZE: usize = 300;
+
+/// The S-expressions returned by the agent are nested only a few levels deep, deeper ones are
+/// rejected instead of risking a stack overflow.
+const MAX_SEXP_DEPTH: usize = 16;
+
+/// Identifies a key independently of its OpenPGP packet, as used by gpg-agent and libgcrypt.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Keygrip([u8; 20]);
+
+impl Keygrip {
+    /// Computes the keygrip of the key with the given public parameters.
+    pub fn from_public_params(params: &PublicParams) -> Result<Self> {
+        let mut hasher = Sha1::new();
+        match params {
+            // the modulus in the signed format, as libgcrypt stores it
+            PublicParams::RSA { n, .. } => hasher.update(signed(n.as_bytes())),
+            PublicParams::DSA { p, q, g, y } => {
+                for (name, value) in [("p", p), ("q", q), ("g", g), ("y", y)] {
+                    hash_element(&mut hasher, name, value.as_bytes());
+                }
+            }
+            PublicParams::ECDSA(params) => {
+                let (curve, p) = match params {
+                    #[cfg(feature = "nist-curves")]
+                    EcdsaPublicParams::P256 { p, .. } => (ECCCurve::P256, p),
+                    #[cfg(feature = "nist-curves")]
+                    EcdsaPublicParams::P384 { p, .. } => (ECCCurve::P384, p),
+                    #[cfg(feature = "secp256k1")]
+                    EcdsaPublicParams::Secp256k1 { p, .. } => (ECCCurve::Secp256k1, p),
+                    EcdsaPublicParams::Unsupported { curve, .. } => {
+                        unsupported_err!(format!("keygrip for curve {}", curve.name()))
+                    }
+                };
+                hash_ecc(&mut hasher, &curve, p.as_bytes())?;
+            }
+            PublicParams::ECDH { curve, p, .. } => {
+                hash_ecc(&mut hasher, curve, strip_point_prefix(curve, p.as_bytes()))?
+            }
+            PublicParams::EdDSA { curve, q } => {
+                hash_ecc(&mut hasher, curve, strip_point_prefix(curve, q.as_bytes()))?
+            }
+            PublicParams::Elgamal { p, g, y } => {
+                for (name, value) in [("p", p), ("g", g), ("y", y)] {
+                    hash_element(&mut hasher, name, value.as_bytes());
+                }
+            }
+            PublicParams::Unknown { .. } => unsupported_err!("keygrip for unknown algorithm"),
+        }
+
+        Ok(Keygrip(hasher.finalize().into()))
+    }
+
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let mut keygrip = [0u8; 20];
+        hex::decode_to_slice(hex, &mut keygrip)?;
+
+        Ok(Keygrip(keygrip))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Keygrip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode_upper(self.0))
+    }
+}
+
+impl fmt::Debug for Keygrip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Keygrip({})", hex::encode_upper(self.0))
+    }
+}
+
+/// The domain parameters libgcrypt hashes into the keygrip of an ECC key, as
+/// `(p, a, b, g, n)`. The values of `a` and `b` are the ones from libgcrypt's curve table,
+/// which stores some of them as negative numbers, of which only the magnitude is hashed.
+fn curve_params(curve: &ECCCurve) -> Result<[&'static str; 5]> {
+    let params = match curve {
+        ECCCurve::Ed25519 => [
+            "7FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFED",
+            "01",
+            "2DFC9311D490018C7338BF8688861767FF8FF5B2BEBE27548A14B235ECA6874A",
+            "04216936D3CD6E53FEC0A4E231FDD6DC5C692CC7609525A7B2C9562D608F25D51A\
+             6666666666666666666666666666666666666666666666666666666666666658",
+            "1000000000000000000000000000000014DEF9DEA2F79CD65812631A5CF5D3ED",
+        ],
+        ECCCurve::Curve25519 => [
+            "7FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFED",
+            "01DB41",
+            "01",
+            "040000000000000000000000000000000000000000000000000000000000000009\
+             20AE19A1B8A086B4E01EDD2C7748D14C923D4D7E6D7C61B229E9C5A27ECED3D9",
+            "1000000000000000000000000000000014DEF9DEA2F79CD65812631A5CF5D3ED",
+        ],
+        ECCCurve::P256 => [
+            "FFFFFFFF00000001000000000000000000000000FFFFFFFFFFFFFFFFFFFFFFFF",
+            "FFFFFFFF00000001000000000000000000000000FFFFFFFFFFFFFFFFFFFFFFFC",
+            "5AC635D8AA3A93E7B3EBBD55769886BC651D06B0CC53B0F63BCE3C3E27D2604B",
+            "046B17D1F2E12C4247F8BCE6E563A440F277037D812DEB33A0F4A13945D898C296\
+             4FE342E2FE1A7F9B8EE7EB4A7C0F9E162BCE33576B315ECECBB6406837BF51F5",
+            "FFFFFFFF00000000FFFFFFFFFFFFFFFFBCE6FAADA7179E84F3B9CAC2FC632551",
+        ],
+        ECCCurve::P384 => [
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFE\
+             FFFFFFFF0000000000000000FFFFFFFF",
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFE\
+             FFFFFFFF0000000000000000FFFFFFFC",
+            "B3312FA7E23EE7E4988E056BE3F82D19181D9C6EFE8141120314088F5013875A\
+             C656398D8A2ED19D2A85C8EDD3EC2AEF",
+            "04AA87CA22BE8B05378EB1C71EF320AD746E1D3B628BA79B9859F741E082542A38\
+             5502F25DBF55296C3A545E3872760AB73617DE4A96262C6F5D9E98BF9292DC29\
+             F8F41DBD289A147CE9DA3113B5F0B8C00A60B1CE1D7E819D7A431D7C90EA0E5F",
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFC7634D81F4372DDF\
+             581A0DB248B0A77AECEC196ACCC52973",
+        ],
+        ECCCurve::Secp256k1 => [
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC2F",
+            "",
+            "07",
+            "0479BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798\
+             483ADA7726A3C4655DA4FBFC0E1108A8FD17B448A68554199C47D08FFB10D4B8",
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141",
+        ],
+        _ => unsupported_err!(format!("keygrip for curve {}", curve.name())),
+    };
+
+    Ok(params)
+}
+
+fn hash_ecc(hasher: &mut Sha1, curve: &ECCCurve, q: &[u8]) -> Result<()> {
+    let params = curve_params(curve)?;
+    for (name, value) in ["p", "a", "b", "g", "n"].into_iter().zip(params) {
+        hash_element(hasher, name, &hex::decode(value)?);
+    }
+    hash_element(hasher, "q", q);
+
+    Ok(())
+}
+
+fn hash_element(hasher: &mut Sha1, name: &str, value: &[u8]) {
+    hasher.update(format!("(1:{}{}:", name, value.len()));
+    hasher.update(value);
+    hasher.update(")");
+}
+
+/// libgcrypt uses the compact form of 25519 points, without the `0x40` prefix.
+fn strip_point_prefix<'a>(curve: &ECCCurve, point: &'a [u8]) -> &'a [u8] {
+    match (curve, point.split_first()) {
+        (ECCCurve::Ed25519 | ECCCurve::Curve25519, Some((0x40, rest))) => rest,
+        _ => point,
+    }
+}
+
+/// A connection to gpg-agent.
+///
+/// The agent asks for the passphrase of a key itself, through its pinentry, when the key is
+/// first used.
+pub struct GpgAgent<S> {
+    conn: RefCell<BufReader<S>>,
+}
+
+impl<S> fmt::Debug for GpgAgent<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GpgAgent").finish_non_exhaustive()
+    }
+}
+
+#[cfg(unix)]
+impl GpgAgent<std::os::unix::net::UnixStream> {
+    /// Connects to the agent listening on the socket at `path`.
+    pub fn connect(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        Self::new(std::os::unix::net::UnixStream::connect(path)?)
+    }
+
+    /// Connects to the agent of the current user, locating its socket with `gpgconf`.
+    pub fn connect_default() -> Result<Self> {
+        let output = std::process::Command::new("gpgconf")
+            .args(["--list-dirs", "agent-socket"])
+            .output()?;
+        ensure!(output.status.success(), "gpgconf failed: {}", output.status);
+
+        // special characters in the path are percent-escaped
+        let path = percent_decode(std::str::from_utf8(&output.stdout)?.trim().as_bytes())?;
+        Self::connect(std::str::from_utf8(&path)?)
+    }
+}
+
+impl<S: Read + Write> GpgAgent<S> {
+    /// Starts a session on `stream`, which is connected to the agent.
+    pub fn new(stream: S) -> Result<Self> {
+        let agent = GpgAgent {
+            conn: RefCell::new(BufReader::new(stream)),
+        };
+        // the agent greets with an OK
+        agent.read_response(None)?;
+
+        Ok(agent)
+    }
+
+    /// Sets an option of the session, e.g. `ttyname=/dev/pts/1` for the pinentry.
+    pub fn set_option(&self, option: &str) -> Result<()> {
+        self.transact(&format!("OPTION {}", option), None)?;
+        Ok(())
+    }
+
+    /// Checks whether the agent holds the secret key with the given keygrip.
+    pub fn has_key(&self, keygrip: &Keygrip) -> Result<bool> {
+        match self.transact(&format!("HAVEKEY {}", keygrip), None) {
+            Ok(_) => Ok(true),
+            Err(AgentError::Agent(_)) => Ok(false),
+            Err(AgentError::Other(err)) => Err(err),
+        }
+    }
+
+    /// Looks up the secret key for `key`, with the public parameters `params`.
+    pub fn key<K: PublicKeyTrait>(
+        &self,
+        key: K,
+        params: &PublicParams,
+    ) -> Result<AgentKey<'_, S, K>> {
+        let keygrip = Keygrip::from_public_params(params)?;
+        ensure!(
+            self.has_key(&keygrip)?,
+            "gpg-agent does not hold the secret key {}",
+            keygrip
+        );
+
+        Ok(AgentKey {
+            agent: self,
+            key,
+            params: params.clone(),
+            keygrip,
+        })
+    }
+
+    /// Signs `digest`, which was hashed with `hash`, with the key `keygrip`.
+    pub fn sign(&self, keygrip: &Keygrip, hash: HashAlgorithm, digest: &[u8]) -> Result<Vec<Mpi>> {
+        let hash = match hash {
+            HashAlgorithm::MD5 => "--hash=md5",
+            HashAlgorithm::SHA1 => "--hash=sha1",
+            HashAlgorithm::RIPEMD160 => "--hash=rmd160",
+            HashAlgorithm::SHA2_224 => "--hash=sha224",
+            HashAlgorithm::SHA2_256 => "--hash=sha256",
+            HashAlgorithm::SHA2_384 => "--hash=sha384",
+            HashAlgorithm::SHA2_512 => "--hash=sha512",
+            // libgcrypt algorithm ids
+            HashAlgorithm::SHA3_256 => "313",
+            HashAlgorithm::SHA3_512 => "315",
+            _ => unsupported_err!(format!("signing {:?} digests with gpg-agent", hash)),
+        };
+
+        self.transact("RESET", None)?;
+        self.transact(&format!("SIGKEY {}", keygrip), None)?;
+        self.transact(
+            &format!("SETHASH {} {}", hash, hex::encode_upper(digest)),
+            None,
+        )?;
+        let sig = Sexp::parse(&self.transact("PKSIGN", None)?)?;
+
+        let Some([Sexp::List(values), ..]) = sig.find("sig-val") else {
+            bail!("invalid signature from gpg-agent");
+        };
+        let (algorithm, names): (_, &[&str]) = match values.split_first() {
+            Some((Sexp::Atom(algorithm), _)) if algorithm == b"rsa" => (algorithm, &["s"]),
+            Some((Sexp::Atom(algorithm), _)) => (algorithm, &["r", "s"]),
+            _ => bail!("invalid signature from gpg-agent"),
+        };
+        debug!(
+            "gpg-agent signature: {}",
+            String::from_utf8_lossy(algorithm)
+        );
+
+        names
+            .iter()
+            .map(|name| match values[1..].iter().find_map(|v| v.find(name)) {
+                Some([Sexp::Atom(value)]) => Ok(Mpi::from_raw_slice(value)),
+                _ => bail!("invalid signature from gpg-agent"),
+            })
+            .collect()
+    }
+
+    /// Decrypts the encrypted value `ciphertext`, an S-expression in libgcrypt's format, with
+    /// the key `keygrip`.
+    fn decrypt(&self, keygrip: &Keygrip, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        self.transact("RESET", None)?;
+        self.transact(&format!("SETKEY {}", keygrip), None)?;
+        let plain = Sexp::parse(&self.transact("PKDECRYPT", Some(ciphertext))?)?;
+
+        match plain.find("value") {
+            Some([Sexp::Atom(value)]) => Ok(value.clone()),
+            _ => bail!("invalid decryption result from gpg-agent"),
+        }
+    }
+
+    /// Sends `command` and returns the data of the response. If the agent inquires data,
+    /// `inquiry` is sent.
+    fn transact(
+        &self,
+        command: &str,
+        inquiry: Option<&[u8]>,
+    ) -> std::result::Result<Vec<u8>, AgentError> {
+        debug!(
+            "gpg-agent: {}",
+            command.split(' ').next().unwrap_or_default()
+        );
+        let mut conn = self.conn.borrow_mut();
+        let stream = conn.get_mut();
+        stream.write_all(command.as_bytes())?;
+        stream.write_all(b"\n")?;
+        stream.flush()?;
+        drop(conn);
+
+        self.read_response(inquiry)
+    }
+
+    fn read_response(&self, inquiry: Option<&[u8]>) -> std::result::Result<Vec<u8>, AgentError> {
+        let mut conn = self.conn.borrow_mut();
+        let mut data = Vec::new();
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            conn.read_until(b'\n', &mut line)?;
+            if line.pop() != Some(b'\n') {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+
+            match line.split(|&b| b == b' ').next().unwrap_or_default() {
+                b"OK" => return Ok(data),
+                b"ERR" => {
+                    let message = String::from_utf8_lossy(&line[3..]).trim().to_string();
+                    return Err(AgentError::Agent(message));
+                }
+                b"D" => data.extend(percent_decode(line.get(2..).unwrap_or_default())?),
+                b"INQUIRE" => {
+                    let stream = conn.get_mut();
+                    // other inquiries, e.g. about a launched pinentry, need no data
+                    if let Some(inquiry) =
+                        inquiry.filter(|_| line.starts_with(b"INQUIRE CIPHERTEXT"))
+                    {
+                        for chunk in inquiry.chunks(DATA_CHUNK_SIZE) {
+                            stream.write_all(b"D ")?;
+                            stream.write_all(&percent_encode(chunk))?;
+                            stream.write_all(b"\n")?;
+                        }
+                    }
+                    stream.write_all(b"END\n")?;
+                    stream.flush()?;
+                }
+                // status and comment lines
+                b"S" | b"#" => {}
+                _ => {
+                    return Err(
+                        format_err!("unexpected response from gpg-agent: {:?}", line).into(),
+                    )
+                }
+            }
+        }
+    }
+}
+
+/// Distinguishes errors reported by the agent from communication errors.
+enum AgentError {
+    Agent(String),
+    Other(crate::errors::Error),
+}
+
+impl From<crate::errors::Error> for AgentError {
+    fn from(err: crate::errors::Error) -> Self {
+        AgentError::Other(err)
+    }
+}
+
+impl From<io::Error> for AgentError {
+    fn from(err: io::Error) -> Self {
+        AgentError::Other(err.into())
+    }
+}
+
+impl From<AgentError> for crate::errors::Error {
+    fn from(err: AgentError) -> Self {
+        match err {
+            AgentError::Agent(message) => format_err!("gpg-agent: {}", message),
+            AgentError::Other(err) => err,
+        }
+    }
+}
+
+/// A public key, whose secret key is held by gpg-agent.
+///
+/// Wrap it in an [`ExternalKey`](crate::types::ExternalKey) to sign with it, or pass it to
+/// [`Message::decrypt_with_decryptors`](crate::Message::decrypt_with_decryptors) to decrypt.
+pub struct AgentKey<'a, S, K> {
+    agent: &'a GpgAgent<S>,
+    key: K,
+    params: PublicParams,
+    keygrip: Keygrip,
+}
+
+impl<'a, S, K: fmt::Debug> fmt::Debug for AgentKey<'a, S, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AgentKey")
+            .field("key", &self.key)
+            .field("keygrip", &self.keygrip)
+            .finish()
+    }
+}
+
+impl<'a, S, K: Clone> Clone for AgentKey<'a, S, K> {
+    fn clone(&self) -> Self {
+        AgentKey {
+            agent: self.agent,
+            key: self.key.clone(),
+            params: self.params.clone(),
+            keygrip: self.keygrip,
+        }
+    }
+}
+
+impl<'a, S, K> AgentKey<'a, S, K> {
+    pub fn keygrip(&self) -> &Keygrip {
+        &self.keygrip
+    }
+
+    pub fn public_key(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<'a, S, K: KeyTrait> KeyTrait for AgentKey<'a, S, K> {
+    fn fingerprint(&self) -> Fingerprint {
+        self.key.fingerprint()
+    }
+
+    fn key_id(&self) -> KeyId {
+        self.key.key_id()
+    }
+
+    fn algorithm(&self) -> PublicKeyAlgorithm {
+        self.key.algorithm()
+    }
+}
+
+impl<'a, S, K: PublicKeyTrait> PublicKeyTrait for AgentKey<'a, S, K> {
+    fn verify_signature(&self, hash: HashAlgorithm, data: &[u8], sig: &[Mpi]) -> Result<()> {
+        self.key.verify_signature(hash, data, sig)
+    }
+
+    fn encrypt<R: CryptoRng + Rng>(&self, rng: &mut R, plain: &[u8]) -> Result<Vec<Mpi>> {
+        self.key.encrypt(rng, plain)
+    }
+
+    fn to_writer_old(&self, writer: &mut impl io::Write) -> Result<()> {
+        self.key.to_writer_old(writer)
+    }
+}
+
+impl<'a, S: Read + Write, K: PublicKeyTrait> Signer for AgentKey<'a, S, K> {
+    fn sign(&self, hash: HashAlgorithm, digest: &[u8]) -> Result<Vec<Mpi>> {
+        self.agent.sign(&self.keygrip, hash, digest)
+    }
+}
+
+impl<'a, S: Read + Write, K: PublicKeyTrait> Decryptor for AgentKey<'a, S, K> {
+    fn decrypt(&self, mpis: &[Mpi]) -> Result<Zeroizing<Vec<u8>>> {
+        match &self.params {
+            PublicParams::RSA { .. } => {
+                ensure_eq!(mpis.len(), 1, "invalid RSA ciphertext");
+                let mut ciphertext = b"(7:enc-val(5:flags5:pkcs1)(3:rsa".to_vec();
+                write_element(&mut ciphertext, "a", &signed(&mpis[0]));
+                ciphertext.extend_from_slice(b"))");
+
+                // the agent removes the PKCS#1 padding, as requested by the flag
+                Ok(Zeroizing::new(
+                    self.agent.decrypt(&self.keygrip, &ciphertext)?,
+                ))
+            }
+            PublicParams::ECDH {
+                curve: ECCCurve::Curve25519,
+                hash,
+                alg_sym,
+                ..
+            } => {
+                ensure_eq!(mpis.len(), 3, "invalid ECDH ciphertext");
+                let mut ciphertext = b"(7:enc-val(4:ecdh".to_vec();
+                write_element(&mut ciphertext, "s", &signed(&mpis[2]));
+                write_element(&mut ciphertext, "e", &mpis[0]);
+                ciphertext.extend_from_slice(b"))");
+
+                // the agent returns the shared point
+                let shared = Zeroizing::new(self.agent.decrypt(&self.keygrip, &ciphertext)?);
+                let shared: Zeroizing<[u8; 32]> =
+                    strip_point_prefix(&ECCCurve::Curve25519, &shared)
+                        .try_into()
+                        .map(Zeroizing::new)
+                        .map_err(|_| format_err!("invalid shared point from gpg-agent"))?;
+
+                ecdh::unwrap_session_key(
+                    &shared,
+                    &ECCCurve::Curve25519.oid(),
+                    *hash,
+                    *alg_sym,
+                    mpis,
+                    self.fingerprint().as_bytes(),
+                )
+            }
+            _ => unsupported_err!(format!(
+                "decryption with {:?} keys in gpg-agent",
+                self.algorithm()
+            )),
+        }
+    }
+}
+
+fn write_element(out: &mut Vec<u8>, name: &str, value: &[u8]) {
+    out.extend_from_slice(format!("({}:{}{}:", name.len(), name, value.len()).as_bytes());
+    out.extend_from_slice(value);
+    out.push(b')');
+}
+
+/// Prefixes a zero, so the value is not read as a negative number.
+fn signed(value: &[u8]) -> Vec<u8> {
+    match value.first() {
+        Some(b) if b & 0x80 != 0 => [&[0][..], value].concat(),
+        _ => value.to_vec(),
+    }
+}
+
+fn percent_encode(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(data.len());
+    for &b in data {
+        if matches!(b, b'%' | b'\r' | b'\n') {
+            encoded.extend_from_slice(format!("%{:02X}", b).as_bytes());
+        } else {
+            encoded.push(b);
+        }
+    }
+
+    encoded
+}
+
+fn percent_decode(data: &[u8]) -> Result<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(&b) = bytes.next() {
+        if b == b'%' {
+            let hex = [
+                bytes.next().copied().unwrap_or_default(),
+                bytes.next().copied().unwrap_or_default(),
+            ];
+            decoded.push(hex::decode(hex)?[0]);
+        } else {
+            decoded.push(b);
+        }
+    }
+
+    Ok(decoded)
+}
+
+/// A canonical S-expression, as exchanged with the agent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Sexp {
+    Atom(Vec<u8>),
+    List(Vec<Sexp>),
+}
+
+impl Sexp {
+    fn parse(input: &[u8]) -> Result<Self> {
+        let (sexp, rest) = Self::parse_partial(input, 0)?;
+        ensure!(rest.is_empty(), "trailing data after S-expression");
+
+        Ok(sexp)
+    }
+
+    fn parse_partial(input: &[u8], depth: usize) -> Result<(Self, &[u8])> {
+        match input.split_first() {
+            Some((b'(', mut rest)) => {
+                ensure!(depth < MAX_SEXP_DEPTH, "S-expression nested too deeply");
+                let mut items = Vec::new();
+                loop {
+                    if let Some((b')', tail)) = rest.split_first() {
+                        return Ok((Sexp::List(items), tail));
+                    }
+                    let (item, tail) = Self::parse_partial(rest, depth + 1)?;
+                    items.push(item);
+                    rest = tail;
+                }
+            }
+            Some((b'0'..=b'9', _)) => {
+                let colon = input
+                    .iter()
+                    .position(|&b| b == b':')
+                    .ok_or_else(|| format_err!("invalid S-expression"))?;
+                let len: usize = std::str::from_utf8(&input[..colon])?.parse()?;
+                let rest = &input[colon + 1..];
+                ensure!(rest.len() >= len, "truncated S-expression");
+
+                Ok((Sexp::Atom(rest[..len].to_vec()), &rest[len..]))
+            }
+            _ => bail!("invalid S-expression"),
+        }
+    }
+
+    /// Finds the first list named `name`, and returns its other elements.
+    fn find(&self, name: &str) -> Option<&[Sexp]> {
+        let Sexp::List(items) = self else {
+            return None;
+        };
+        match items.split_first() {
+            Some((Sexp::Atom(atom), rest)) if atom == name.as_bytes() => Some(rest),
+            _ => items.iter().find_map(|item| item.find(name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::fs;
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::composed::{Deserializable, Message, SignedPublicKey};
+    use crate::test_util::{gen_key, gen_key_with_subkey};
+    use crate::types::{SecretKeyRepr, SecretKeyTrait};
+
+    /// Answers with scripted responses, and records what the client sent.
+    struct MockAgent {
+        responses: Cursor<Vec<u8>>,
+        sent: Vec<u8>,
+    }
+
+    impl MockAgent {
+        fn new(responses: &[&[u8]]) -> Self {
+            MockAgent {
+                responses: Cursor::new(responses.concat()),
+                sent: Vec::new(),
+            }
+        }
+    }
+
+    impl Read for MockAgent {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.responses.read(buf)
+        }
+    }
+
+    impl Write for MockAgent {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.sent.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn data_line(data: &[u8]) -> Vec<u8> {
+        [b"D ", &percent_encode(data)[..], b"\n"].concat()
+    }
+
+    fn sent(agent: GpgAgent<MockAgent>) -> String {
+        String::from_utf8_lossy(&agent.conn.into_inner().into_inner().sent).into_owned()
+    }
+
+    #[test]
+    fn test_keygrip() {
+        // keygrips as listed by `gpg --with-keygrip`
+        for (file, keygrips) in [
+            (
+                "rsa2048",
+                &[
+                    "15DDB655C683FFF6239313EB9036F48C2A65C75F",
+                    "CCA618AA9E7BEA175915B43711859C026133B4DA",
+                ][..],
+            ),
+            (
+                "ed25519",
+                &[
+                    "8FAD37E590FB3F825B3D89F3B12A311D3A6B6BB4",
+                    "2A6617E36E162DF42F83E662C18E2A6A02A63B32",
+                ],
+            ),
+            ("nistp256", &["3F674ED89956873A3F9448339C1939108D93B9AD"]),
+            ("nistp384", &["E57CFF0B4AB5FD54102E4A2184BEE0C4EFE416A7"]),
+            ("secp256k1", &["53CC98E4328D2854ED549AB75EC98885946D80EB"]),
+        ] {
+            let (key, _) = SignedPublicKey::from_string(
+                &fs::read_to_string(format!("./tests/unit-tests/keygrip/{}.pub.asc", file))
+                    .unwrap(),
+            )
+            .unwrap();
+
+            let params = std::iter::once(key.primary_key.public_params())
+                .chain(key.public_subkeys.iter().map(|k| k.key.public_params()));
+            let computed: Vec<_> = params
+                .map(|p| Keygrip::from_public_params(p).unwrap().to_string())
+                .collect();
+            assert_eq!(computed, keygrips, "{}", file);
+        }
+    }
+
+    #[test]
+    fn test_sign() {
+        let key = gen_key("Alice <alice@example.com>");
+        let public = key.primary_key.public_key();
+        let keygrip = Keygrip::from_public_params(public.public_params()).unwrap();
+
+        // sign the same digest with the secret key, as the agent would
+        let digest = HashAlgorithm::SHA2_256.digest(b"hello").unwrap();
+        let mpis = key
+            .create_signature(String::new, HashAlgorithm::SHA2_256, &digest)
+            .unwrap();
+        let mut sig = b"(7:sig-val(5:eddsa".to_vec();
+        write_element(&mut sig, "r", &mpis[0]);
+        write_element(&mut sig, "s", &mpis[1]);
+        sig.extend_from_slice(b"))");
+
+        let agent = GpgAgent::new(MockAgent::new(&[
+            b"# comment\nOK Pleased to meet you\n",
+            b"OK\n",
+            b"OK\n",
+            b"OK\n",
+            b"OK\n",
+            b"S INQUIRE_MAXLEN 255\n",
+            &data_line(&sig),
+            b"OK\n",
+        ]))
+        .unwrap();
+        let agent_key = agent.key(&public, public.public_params()).unwrap();
+        assert_eq!(agent_key.keygrip(), &keygrip);
+
+        let signature = agent_key.sign(HashAlgorithm::SHA2_256, &digest).unwrap();
+        assert_eq!(signature, mpis);
+        public
+            .verify_signature(HashAlgorithm::SHA2_256, &digest, &signature)
+            .unwrap();
+
+        assert_eq!(
+            sent(agent),
+            format!(
+                "HAVEKEY {keygrip}\nRESET\nSIGKEY {keygrip}\nSETHASH --hash=sha256 {}\nPKSIGN\n",
+                hex::encode_upper(&digest)
+            )
+        );
+    }
+
+    #[test]
+    fn test_decrypt() {
+        let key = gen_key_with_subkey("Alice <alice@example.com>");
+        let cert: SignedPublicKey = key.clone().into();
+        let subkey = &cert.public_subkeys[0].key;
+
+        let msg = Message::new_literal("", "hello")
+            .encrypt_to_keys(
+                &mut rand::thread_rng(),
+                crate::crypto::sym::SymmetricKeyAlgorithm::AES128,
+                &[subkey],
+            )
+            .unwrap();
+
+        // compute the shared point with the secret key, as the agent would
+        let Message::Encrypted { ref esk, .. } = msg else {
+            panic!("not encrypted");
+        };
+        let crate::composed::Esk::PublicKeyEncryptedSessionKey(ref pkesk) = esk[0] else {
+            panic!("not encrypted to a key");
+        };
+        let mut shared = Vec::new();
+        key.secret_subkeys[0]
+            .unlock(String::new, |repr| {
+                let SecretKeyRepr::ECDH(secret) = repr else {
+                    panic!("not an ECDH key");
+                };
+                let mut secret_le = secret.secret;
+                secret_le.reverse();
+                let ephemeral: [u8; 32] = pkesk.mpis()[0][1..].try_into().unwrap();
+                let point = x25519_dalek::StaticSecret::from(secret_le)
+                    .diffie_hellman(&x25519_dalek::PublicKey::from(ephemeral));
+                shared = [&[0x40][..], point.as_bytes()].concat();
+                Ok(())
+            })
+            .unwrap();
+        let mut value = Vec::new();
+        write_element(&mut value, "value", &shared);
+
+        let agent = GpgAgent::new(MockAgent::new(&[
+            b"OK Pleased to meet you\n",
+            b"OK\n",
+            b"OK\n",
+            b"OK\n",
+            b"INQUIRE CIPHERTEXT\n",
+            &data_line(&value),
+            b"OK\n",
+        ]))
+        .unwrap();
+        let agent_key = agent.key(subkey, subkey.public_params()).unwrap();
+
+        let (mut decrypter, ids) = msg.decrypt_with_decryptors(&[&agent_key]).unwrap();
+        assert_eq!(ids, vec![subkey.key_id()]);
+        let mut content = String::new();
+        decrypter.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "hello");
+
+        let sent = sent(agent);
+        assert!(sent.contains("\nSETKEY "));
+        assert!(sent.contains("\nPKDECRYPT\nD (7:enc-val(4:ecdh(1:s"));
+        assert!(sent.ends_with(")))\nEND\n"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let agent = GpgAgent::new(MockAgent::new(&[
+            b"OK Pleased to meet you\n",
+            b"ERR 67108881 No secret key <GPG Agent>\n",
+            b"ERR 67108881 No secret key <GPG Agent>\n",
+        ]))
+        .unwrap();
+        let keygrip = Keygrip::from_hex("15DDB655C683FFF6239313EB9036F48C2A65C75F").unwrap();
+        assert!(!agent.has_key(&keygrip).unwrap());
+        let err = agent
+            .sign(&keygrip, HashAlgorithm::SHA2_256, &[0; 32])
+            .unwrap_err();
+        assert!(err.to_string().contains("No secret key"), "{}", err);
+
+        // the connection was closed
+        assert!(agent.has_key(&keygrip).is_err());
+        assert!(GpgAgent::new(MockAgent::new(&[b"OK"])).is_err());
+
+        // a data line without any data
+        let agent =
+            GpgAgent::new(MockAgent::new(&[b"OK Pleased to meet you\n", b"D\nOK\n"])).unwrap();
+        assert!(agent.has_key(&keygrip).unwrap());
+    }
+
+    #[test]
+    fn test_sexp() {
+        let sexp = Sexp::parse(b"(7:sig-val(3:rsa(1:s3:a)b)))").unwrap();
+        assert_eq!(sexp.find("s"), Some(&[Sexp::Atom(b"a)b".to_vec())][..]));
+        assert_eq!(sexp.find("r"), None);
+
+        assert!(Sexp::parse(b"(1:a").is_err());
+        assert!(Sexp::parse(b"(5:a)").is_err());
+        assert!(Sexp::parse(b"(1:a))").is_err());
+        assert!(Sexp::parse(b"(x)").is_err());
+
+        let nested = |depth| [&b"(".repeat(depth)[..], b"1:a", &b")".repeat(depth)].concat();
+        assert!(Sexp::parse(&nested(MAX_SEXP_DEPTH)).is_ok());
+        assert!(Sexp::parse(&nested(100_000)).is_err());
+    }
+}
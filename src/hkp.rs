@@ -0,0 +1,500 @@
+//! # HKP
+//!
+//! A client for the HTTP Keyserver Protocol, as described in
+//! [draft-shaw-openpgp-hkp](https://datatracker.ietf.org/doc/html/draft-shaw-openpgp-hkp-00).
+//!
+//! This module does not perform any IO itself. [`Keyserver`] builds the requests and parses
+//! the responses, so it can be driven by any HTTP stack, blocking or async. For blocking
+//! stacks, [`HkpClient`] combines it with an [`HttpTransport`].
+
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::composed::{Deserializable, SignedPublicKey};
+use crate::crypto::public_key::PublicKeyAlgorithm;
+use crate::errors::Result;
+use crate::types::{Fingerprint, KeyId, KeyTrait};
+
+const LOOKUP_PATH: &str = "/pks/lookup";
+const ADD_PATH: &str = "/pks/add";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpMethod {
+    Get,
+    Post,
+}
+
+/// An HTTP request, to be sent by the transport.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub method: HttpMethod,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// The response to an [`HttpRequest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+/// Sends HTTP requests on behalf of an [`HkpClient`].
+pub trait HttpTransport {
+    fn send(&self, request: HttpRequest) -> Result<HttpResponse>;
+}
+
+/// Builds HKP requests for a keyserver, and parses its responses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keyserver {
+    base_url: String,
+}
+
+impl Keyserver {
+    /// Creates a keyserver for `base_url`, e.g. `https://keys.openpgp.org`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        let mut base_url = base_url.into();
+        while base_url.ends_with('/') {
+            base_url.pop();
+        }
+
+        Keyserver { base_url }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Builds a request for the keys matching `search`.
+    pub fn get_request(&self, search: &str) -> HttpRequest {
+        self.lookup_request("get", search)
+    }
+
+    /// Builds a request for the machine-readable index of the keys matching `search`.
+    pub fn index_request(&self, search: &str) -> HttpRequest {
+        self.lookup_request("index", search)
+    }
+
+    /// Builds a request uploading `key` to the keyserver.
+    pub fn submit_request(&self, key: &SignedPublicKey) -> Result<HttpRequest> {
+        let body = format!("keytext={}", url_encode(&key.to_armored_string(None)?));
+
+        Ok(HttpRequest {
+            method: HttpMethod::Post,
+            url: format!("{}{}", self.base_url, ADD_PATH),
+            headers: vec![(
+                "Content-Type".to_string(),
+                "application/x-www-form-urlencoded".to_string(),
+            )],
+            body: body.into_bytes(),
+        })
+    }
+
+    /// Parses the response to a [`get_request`](Self::get_request).
+    ///
+    /// A server reporting that no keys matched results in an empty list.
+    pub fn parse_get_response(&self, response: &HttpResponse) -> Result<Vec<SignedPublicKey>> {
+        let Some(body) = Self::body(response)? else {
+            return Ok(Vec::new());
+        };
+        let (keys, _headers) = SignedPublicKey::from_string_many(body)?;
+
+        keys.collect()
+    }
+
+    /// Parses the response to an [`index_request`](Self::index_request).
+    ///
+    /// A server reporting that no keys matched results in an empty list.
+    pub fn parse_index_response(&self, response: &HttpResponse) -> Result<Vec<IndexEntry>> {
+        match Self::body(response)? {
+            Some(body) => parse_index(body),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Checks the response to a [`submit_request`](Self::submit_request).
+    pub fn parse_submit_response(&self, response: &HttpResponse) -> Result<()> {
+        ensure!(
+            (200..300).contains(&response.status),
+            "keyserver rejected the key: status {}",
+            response.status
+        );
+
+        Ok(())
+    }
+
+    fn lookup_request(&self, op: &str, search: &str) -> HttpRequest {
+        HttpRequest {
+            method: HttpMethod::Get,
+            url: format!(
+                "{}{}?op={}&options=mr&search={}",
+                self.base_url,
+                LOOKUP_PATH,
+                op,
+                url_encode(search)
+            ),
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Returns the body of a successful lookup, or `None` if nothing was found.
+    fn body(response: &HttpResponse) -> Result<Option<&str>> {
+        match response.status {
+            404 => Ok(None),
+            200..=299 => Ok(Some(std::str::from_utf8(&response.body)?)),
+            status => bail!("keyserver lookup failed: status {}", status),
+        }
+    }
+}
+
+/// A blocking HKP client.
+#[derive(Debug, Clone)]
+pub struct HkpClient<T> {
+    keyserver: Keyserver,
+    transport: T,
+}
+
+impl<T: HttpTransport> HkpClient<T> {
+    pub fn new(keyserver: Keyserver, transport: T) -> Self {
+        HkpClient {
+            keyserver,
+            transport,
+        }
+    }
+
+    pub fn keyserver(&self) -> &Keyserver {
+        &self.keyserver
+    }
+
+    /// Fetches the key with the given primary key fingerprint.
+    pub fn lookup_by_fingerprint(
+        &self,
+        fingerprint: &Fingerprint,
+    ) -> Result<Option<SignedPublicKey>> {
+        let request = self.keyserver.get_request(&format!("0x{:X}", fingerprint));
+        let response = self.transport.send(request)?;
+        let keys = self.keyserver.parse_get_response(&response)?;
+
+        // servers may return keys matching a subkey, or even the wrong key
+        Ok(keys
+            .into_iter()
+            .find(|key| &key.fingerprint() == fingerprint))
+    }
+
+    /// Fetches all keys with a User ID for the given email address, ignoring case.
+    pub fn lookup_by_email(&self, email: &str) -> Result<Vec<SignedPublicKey>> {
+        let email = email.to_lowercase();
+        let response = self.transport.send(self.keyserver.get_request(&email))?;
+        let keys = self.keyserver.parse_get_response(&response)?;
+
+        // searches are usually substring matches, so only keep exact ones
+        Ok(keys
+            .into_iter()
+            .filter(|key| {
+                key.details
+                    .users
+                    .iter()
+                    .any(|user| user.id.email_normalized().as_deref() == Some(email.as_str()))
+            })
+            .collect())
+    }
+
+    /// Fetches the machine-readable index of the keys matching `search`.
+    pub fn search(&self, search: &str) -> Result<Vec<IndexEntry>> {
+        let response = self.transport.send(self.keyserver.index_request(search))?;
+        self.keyserver.parse_index_response(&response)
+    }
+
+    /// Uploads `key` to the keyserver.
+    pub fn submit_key(&self, key: &SignedPublicKey) -> Result<()> {
+        let response = self.transport.send(self.keyserver.submit_request(key)?)?;
+        self.keyserver.parse_submit_response(&response)
+    }
+}
+
+/// A key, as listed in the machine-readable index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// The Key ID or fingerprint of the primary key, as sent by the server.
+    pub key: String,
+    pub algorithm: Option<PublicKeyAlgorithm>,
+    pub key_len: Option<u32>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub flags: IndexFlags,
+    pub user_ids: Vec<IndexUserId>,
+}
+
+impl IndexEntry {
+    /// The fingerprint, if the server sent one instead of a Key ID.
+    pub fn fingerprint(&self) -> Option<Fingerprint> {
+        match self.key.len() {
+            32 | 40 | 64 => Fingerprint::from_hex(&self.key).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn key_id(&self) -> Option<KeyId> {
+        match self.fingerprint() {
+            Some(fingerprint) => fingerprint.key_id(),
+            None => KeyId::from_hex(&self.key).ok(),
+        }
+    }
+}
+
+/// A User ID, as listed in the machine-readable index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexUserId {
+    pub id: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub flags: IndexFlags,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexFlags {
+    pub revoked: bool,
+    pub disabled: bool,
+    pub expired: bool,
+}
+
+impl IndexFlags {
+    fn parse(flags: &str) -> Self {
+        IndexFlags {
+            revoked: flags.contains('r'),
+            disabled: flags.contains('d'),
+            expired: flags.contains('e'),
+        }
+    }
+}
+
+/// Parses the machine-readable index format.
+pub fn parse_index(input: &str) -> Result<Vec<IndexEntry>> {
+    let mut entries: Vec<IndexEntry> = Vec::new();
+
+    for line in input.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let fields: Vec<&str> = line.split(':').collect();
+        let field = |i: usize| fields.get(i).copied().unwrap_or_default();
+
+        match fields[0] {
+            "info" => ensure_eq!(field(1), "1", "unsupported index version"),
+            "pub" => entries.push(IndexEntry {
+                key: field(1).to_string(),
+                algorithm: parse_number::<u8>(field(2))?.map(PublicKeyAlgorithm::from),
+                key_len: parse_number(field(3))?,
+                created_at: parse_time(field(4))?,
+                expires_at: parse_time(field(5))?,
+                flags: IndexFlags::parse(field(6)),
+                user_ids: Vec::new(),
+            }),
+            "uid" => {
+                let Some(entry) = entries.last_mut() else {
+                    bail!("index user id without a key");
+                };
+                entry.user_ids.push(IndexUserId {
+                    id: url_decode(field(1))?,
+                    created_at: parse_time(field(2))?,
+                    expires_at: parse_time(field(3))?,
+                    flags: IndexFlags::parse(field(4)),
+                });
+            }
+            // unknown lines must be ignored, to allow for extensions
+            _ => {}
+        }
+    }
+
+    Ok(entries)
+}
+
+fn parse_number<T: std::str::FromStr>(field: &str) -> Result<Option<T>> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+
+    field
+        .parse()
+        .map(Some)
+        .map_err(|_| format_err!("invalid number in index: {}", field))
+}
+
+fn parse_time(field: &str) -> Result<Option<DateTime<Utc>>> {
+    match parse_number::<i64>(field)? {
+        Some(secs) => {
+            Ok(Some(Utc.timestamp_opt(secs, 0).single().ok_or_else(
+                || format_err!("invalid time in index: {}", secs),
+            )?))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Percent-encodes everything but unreserved characters.
+pub(crate) fn url_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for b in input.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            encoded.push(char::from(b));
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+
+    encoded
+}
+
+fn url_decode(input: &str) -> Result<String> {
+    let mut decoded = Vec::with_capacity(input.len());
+    let mut bytes = input.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [
+                bytes.next().unwrap_or_default(),
+                bytes.next().unwrap_or_default(),
+            ];
+            decoded.push(hex::decode(hex)?[0]);
+        } else {
+            decoded.push(b);
+        }
+    }
+
+    Ok(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::test_util::gen_public_key;
+
+    /// Records all requests, and answers them with a fixed response.
+    struct MockTransport {
+        requests: RefCell<Vec<HttpRequest>>,
+        response: HttpResponse,
+    }
+
+    impl MockTransport {
+        fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+            MockTransport {
+                requests: RefCell::new(Vec::new()),
+                response: HttpResponse {
+                    status,
+                    body: body.into(),
+                },
+            }
+        }
+    }
+
+    impl HttpTransport for &MockTransport {
+        fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+            self.requests.borrow_mut().push(request);
+            Ok(self.response.clone())
+        }
+    }
+
+    #[test]
+    fn test_lookup_by_fingerprint() {
+        let key = gen_public_key("Alice <alice@example.com>");
+        let transport = MockTransport::new(200, key.to_armored_string(None).unwrap());
+        let client = HkpClient::new(Keyserver::new("https://keys.example.com/"), &transport);
+
+        let found = client.lookup_by_fingerprint(&key.fingerprint()).unwrap();
+        assert_eq!(found, Some(key.clone()));
+
+        let requests = transport.requests.borrow();
+        assert_eq!(requests[0].method, HttpMethod::Get);
+        assert_eq!(
+            requests[0].url,
+            format!(
+                "https://keys.example.com/pks/lookup?op=get&options=mr&search=0x{:X}",
+                key.fingerprint()
+            )
+        );
+    }
+
+    #[test]
+    fn test_lookup_by_email() {
+        let key = gen_public_key("Alice <alice@example.com>");
+        let transport = MockTransport::new(200, key.to_armored_string(None).unwrap());
+        let client = HkpClient::new(Keyserver::new("https://keys.example.com"), &transport);
+
+        assert_eq!(
+            client.lookup_by_email("Alice@Example.com").unwrap(),
+            vec![key]
+        );
+        assert!(client.lookup_by_email("al@example.com").unwrap().is_empty());
+        assert!(transport.requests.borrow()[0]
+            .url
+            .ends_with("search=alice%40example.com"));
+
+        let transport = MockTransport::new(404, "No results found");
+        let client = HkpClient::new(Keyserver::new("https://keys.example.com"), &transport);
+        assert!(client
+            .lookup_by_email("alice@example.com")
+            .unwrap()
+            .is_empty());
+
+        let transport = MockTransport::new(500, "");
+        let client = HkpClient::new(Keyserver::new("https://keys.example.com"), &transport);
+        assert!(client.lookup_by_email("alice@example.com").is_err());
+    }
+
+    #[test]
+    fn test_submit_key() {
+        let key = gen_public_key("Alice <alice@example.com>");
+        let transport = MockTransport::new(200, "");
+        let client = HkpClient::new(Keyserver::new("https://keys.example.com"), &transport);
+        client.submit_key(&key).unwrap();
+
+        let request = transport.requests.borrow()[0].clone();
+        assert_eq!(request.method, HttpMethod::Post);
+        assert_eq!(request.url, "https://keys.example.com/pks/add");
+        let body = String::from_utf8(request.body).unwrap();
+        let armored = url_decode(body.strip_prefix("keytext=").unwrap()).unwrap();
+        assert_eq!(SignedPublicKey::from_string(&armored).unwrap().0, key);
+
+        let transport = MockTransport::new(400, "");
+        let client = HkpClient::new(Keyserver::new("https://keys.example.com"), &transport);
+        assert!(client.submit_key(&key).is_err());
+    }
+
+    #[test]
+    fn test_parse_index() {
+        let index = "info:1:2\n\
+                     pub:2A4F2E6E4B0544C8D6D82A7A3CC2B3DD63C52D1F:1:2048:1388790578::\n\
+                     uid:Alice %3Calice@example.com%3E:1388790578::\n\
+                     uid:Alice (work) %3Calice@work.example.com%3E:1388790600:1420326600:e\n\
+                     pub:3CC2B3DD63C52D1F:22::1388790578:1420326578:r\n\
+                     unknown:ignored\n";
+        let entries = parse_index(index).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let alice = &entries[0];
+        assert_eq!(alice.algorithm, Some(PublicKeyAlgorithm::RSA));
+        assert_eq!(alice.key_len, Some(2048));
+        assert_eq!(alice.created_at.unwrap().timestamp(), 1388790578);
+        assert_eq!(alice.expires_at, None);
+        assert_eq!(alice.flags, IndexFlags::default());
+        assert_eq!(
+            alice.key_id().unwrap(),
+            KeyId::from_hex("3CC2B3DD63C52D1F").unwrap()
+        );
+        assert_eq!(alice.user_ids.len(), 2);
+        assert_eq!(alice.user_ids[0].id, "Alice <alice@example.com>");
+        assert!(alice.user_ids[1].flags.expired);
+
+        let other = &entries[1];
+        assert_eq!(other.fingerprint(), None);
+        assert_eq!(other.key_id(), alice.key_id());
+        assert_eq!(other.key_len, None);
+        assert!(other.flags.revoked);
+        assert!(other.user_ids.is_empty());
+
+        assert!(parse_index("info:2:1\n").is_err());
+        assert!(parse_index("uid:Alice:::\n").is_err());
+        assert!(parse_index("pub:3CC2B3DD63C52D1F:x\n").is_err());
+    }
+}
@@ -9,7 +9,11 @@
 //! [signing and verifying with external hashing]: crate::composed::signed_key
 //! [packet based signing and verifying]: crate::packet
 
-#![forbid(unsafe_code)]
+#![cfg_attr(
+    not(any(feature = "ffi", feature = "secure-memory")),
+    forbid(unsafe_code)
+)]
+#![cfg_attr(any(feature = "ffi", feature = "secure-memory"), deny(unsafe_code))]
 #![deny(
     clippy::all,
     clippy::style,
@@ -25,8 +29,14 @@
 extern crate nom;
 #[macro_use]
 extern crate generic_array;
+#[cfg(not(feature = "tracing"))]
 #[macro_use]
 extern crate log;
+// the logging macros emit tracing events instead, which are forwarded to `log` when no
+// tracing subscriber is installed
+#[cfg(feature = "tracing")]
+#[macro_use]
+extern crate tracing;
 #[macro_use]
 extern crate derive_builder;
 #[macro_use]
@@ -45,23 +55,56 @@ pub mod util;
 #[macro_use]
 pub mod errors;
 pub mod armor;
+#[cfg(feature = "async")]
+pub mod async_io;
 pub mod base64_decoder;
 pub mod base64_reader;
+#[cfg(feature = "card")]
+pub mod card;
 pub mod composed;
 pub mod crypto;
+pub mod dane;
 pub mod de;
+pub mod debug;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod formats;
+#[cfg(feature = "gpg-agent")]
+pub mod gpg_agent;
+#[cfg(feature = "hkp")]
+pub mod hkp;
+pub mod limits;
 pub mod line_reader;
 pub mod line_writer;
 pub mod normalize_lines;
 pub mod packet;
+pub mod policy;
 pub mod ser;
+#[cfg(feature = "shamir")]
+pub mod shamir;
+#[cfg(feature = "ssh-agent")]
+pub mod ssh_agent;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod store;
+#[cfg(test)]
+pub(crate) mod test_util;
+pub mod tofu;
 pub mod types;
+#[cfg(feature = "vks")]
+pub mod vks;
+#[cfg(feature = "wkd")]
+pub mod wkd;
+#[cfg(feature = "wot")]
+pub mod wot;
 
 // reexports for easier use
 #[allow(unused_imports)]
 pub use self::composed::key::*;
 pub use self::composed::*;
 pub use self::packet::Signature;
+/// The attribute to implement [`AsyncSecretKeyTrait`](types::AsyncSecretKeyTrait) with.
+#[cfg(feature = "async")]
+pub use async_trait::async_trait;
 
 /// The version of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,108 @@
+//! # Limits
+//!
+//! Bounds the resources that parsing and decrypting untrusted input may use, to guard against
+//! decompression bombs, deeply nested messages and hostile partial body lengths.
+//!
+//! ```rust
+//! use pgp::limits::ParseLimitsBuilder;
+//!
+//! let limits = ParseLimitsBuilder::default()
+//!     .max_packet_size(16 * 1024 * 1024)
+//!     .max_decompressed_size(64 * 1024 * 1024)
+//!     .build()
+//!     .unwrap();
+//!
+//! assert!(limits.check_packet_size(1024).is_ok());
+//! assert!(limits.check_packet_size(32 * 1024 * 1024).is_err());
+//! ```
+
+use crate::errors::Result;
+
+/// The resource limits applied while parsing and decrypting.
+///
+/// The default allows packets and decompressed data of up to 1 GiB, signatures nested up to
+/// 32 levels deep, and any number of packets.
+#[derive(Debug, Clone, PartialEq, Eq, Builder)]
+#[builder(default)]
+pub struct ParseLimits {
+    /// The maximum size of a single packet body, including all of its partial body chunks.
+    max_packet_size: usize,
+    /// The maximum size of the data produced by decompressing a compressed data packet.
+    max_decompressed_size: usize,
+    /// The maximum depth of nested signed messages.
+    max_nesting_depth: usize,
+    /// The maximum number of packets read from a single input.
+    max_packets: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            max_packet_size: 1024 * 1024 * 1024,
+            max_decompressed_size: 1024 * 1024 * 1024,
+            max_nesting_depth: 32,
+            max_packets: usize::MAX,
+        }
+    }
+}
+
+impl ParseLimits {
+    pub fn max_packet_size(&self) -> usize {
+        self.max_packet_size
+    }
+
+    pub fn max_decompressed_size(&self) -> usize {
+        self.max_decompressed_size
+    }
+
+    pub fn max_nesting_depth(&self) -> usize {
+        self.max_nesting_depth
+    }
+
+    pub fn max_packets(&self) -> usize {
+        self.max_packets
+    }
+
+    pub fn check_packet_size(&self, size: usize) -> Result<()> {
+        ensure!(
+            size <= self.max_packet_size,
+            "packet of at least {} bytes exceeds the limit of {} bytes",
+            size,
+            self.max_packet_size
+        );
+
+        Ok(())
+    }
+
+    pub fn check_decompressed_size(&self, size: usize) -> Result<()> {
+        ensure!(
+            size <= self.max_decompressed_size,
+            "decompressed data of at least {} bytes exceeds the limit of {} bytes",
+            size,
+            self.max_decompressed_size
+        );
+
+        Ok(())
+    }
+
+    pub fn check_nesting_depth(&self, depth: usize) -> Result<()> {
+        ensure!(
+            depth <= self.max_nesting_depth,
+            "message nesting depth exceeds the limit of {}",
+            self.max_nesting_depth
+        );
+
+        Ok(())
+    }
+
+    /// Checks the number of packets read so far.
+    pub fn check_packet_count(&self, count: usize) -> Result<()> {
+        ensure!(
+            count <= self.max_packets,
+            "input exceeds the limit of {} packets",
+            self.max_packets
+        );
+
+        Ok(())
+    }
+}
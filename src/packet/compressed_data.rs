@@ -1,6 +1,8 @@
 use std::fmt;
 use std::io::{self, Cursor, Read};
 
+#[cfg(feature = "bzip2")]
+use bzip2::read::BzDecoder;
 use flate2::read::{DeflateDecoder, ZlibDecoder};
 
 use crate::errors::Result;
@@ -15,11 +17,14 @@ pub struct CompressedData {
     compressed_data: Vec<u8>,
 }
 
-pub enum Decompressor<R> {
+pub enum Decompressor<R: io::BufRead> {
     Uncompressed(Cursor<R>),
     Zip(DeflateDecoder<R>),
     Zlib(ZlibDecoder<R>),
-    Bzip2,
+    #[cfg(feature = "bzip2")]
+    Bzip2(BzDecoder<R>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::read::Decoder<'static, R>),
 }
 
 impl<'a> Read for Decompressor<&'a [u8]> {
@@ -28,7 +33,10 @@ impl<'a> Read for Decompressor<&'a [u8]> {
             Decompressor::Uncompressed(ref mut c) => c.read(into),
             Decompressor::Zip(ref mut c) => c.read(into),
             Decompressor::Zlib(ref mut c) => c.read(into),
-            Decompressor::Bzip2 => unimplemented!("bzip2"),
+            #[cfg(feature = "bzip2")]
+            Decompressor::Bzip2(ref mut c) => c.read(into),
+            #[cfg(feature = "zstd")]
+            Decompressor::Zstd(ref mut c) => c.read(into),
         }
     }
 }
@@ -65,14 +73,32 @@ impl CompressedData {
             CompressionAlgorithm::ZLIB => Ok(Decompressor::Zlib(ZlibDecoder::new(
                 &self.compressed_data[..],
             ))),
-            CompressionAlgorithm::BZip2 => unimplemented_err!("BZip2"),
-            CompressionAlgorithm::Private10 | CompressionAlgorithm::Other(_) => unsupported_err!(
-                "CompressionAlgorithm {} is unsupported",
-                u8::from(self.compression_algorithm)
-            ),
+            #[cfg(feature = "bzip2")]
+            CompressionAlgorithm::BZip2 => Ok(Decompressor::Bzip2(BzDecoder::new(
+                &self.compressed_data[..],
+            ))),
+            #[cfg(not(feature = "bzip2"))]
+            CompressionAlgorithm::BZip2 => {
+                unsupported_alg_err!(Compression, self.compression_algorithm)
+            }
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd => Ok(Decompressor::Zstd(
+                zstd::stream::read::Decoder::with_buffer(&self.compressed_data[..])?,
+            )),
+            #[cfg(not(feature = "zstd"))]
+            CompressionAlgorithm::Zstd => {
+                unsupported_alg_err!(Compression, self.compression_algorithm)
+            }
+            CompressionAlgorithm::Private10 | CompressionAlgorithm::Other(_) => {
+                unsupported_alg_err!(Compression, self.compression_algorithm)
+            }
         }
     }
 
+    pub fn compression_algorithm(&self) -> CompressionAlgorithm {
+        self.compression_algorithm
+    }
+
     pub fn compressed_data(&self) -> &[u8] {
         &self.compressed_data
     }
@@ -0,0 +1,53 @@
+use std::io;
+
+use crate::errors::Result;
+use crate::packet::PacketTrait;
+use crate::ser::Serialize;
+use crate::types::{Tag, Version};
+
+/// Packet with a tag reserved for private or experimental use.
+/// https://tools.ietf.org/html/rfc4880.html#section-4.3
+///
+/// The body is kept as is, so that the packet is written back unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Experimental {
+    packet_version: Version,
+    tag: Tag,
+    body: Vec<u8>,
+}
+
+impl Experimental {
+    /// Parses an `Experimental` packet with the given tag from the given slice.
+    pub fn from_slice(packet_version: Version, tag: Tag, input: &[u8]) -> Result<Self> {
+        ensure!(tag.is_experimental(), "not an experimental tag: {:?}", tag);
+
+        Ok(Experimental {
+            packet_version,
+            tag,
+            body: input.to_vec(),
+        })
+    }
+
+    /// The uninterpreted packet body.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+}
+
+impl Serialize for Experimental {
+    fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.body)?;
+
+        Ok(())
+    }
+}
+
+impl PacketTrait for Experimental {
+    fn packet_version(&self) -> Version {
+        self.packet_version
+    }
+
+    fn tag(&self) -> Tag {
+        self.tag
+    }
+}
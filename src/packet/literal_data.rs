@@ -17,6 +17,10 @@ use crate::packet::PacketTrait;
 use crate::ser::Serialize;
 use crate::types::{Tag, Version};
 
+/// The file name marking literal data as "for your eyes only", which should be shown to the
+/// reader, but not saved to disk.
+pub const CONSOLE_FILE_NAME: &[u8] = b"_CONSOLE";
+
 /// Literal Data Packet
 /// https://tools.ietf.org/html/rfc4880.html#section-5.9
 #[derive(Clone, PartialEq, Eq)]
@@ -83,6 +87,52 @@ impl LiteralData {
         &self.data
     }
 
+    /// A reader over the data.
+    ///
+    /// The data of a parsed packet is held in memory, use
+    /// [`LiteralReader`](crate::composed::LiteralReader) or
+    /// [`StreamDecrypter`](crate::composed::StreamDecrypter) to read messages of any size
+    /// while they are parsed.
+    pub fn reader(&self) -> impl io::Read + '_ {
+        &self.data[..]
+    }
+
+    pub fn mode(&self) -> DataMode {
+        self.mode
+    }
+
+    pub fn file_name(&self) -> &BStr {
+        self.file_name.as_ref()
+    }
+
+    pub fn created(&self) -> &DateTime<Utc> {
+        &self.created
+    }
+
+    /// Whether the data is "for your eyes only", see [`CONSOLE_FILE_NAME`].
+    pub fn is_for_your_eyes_only(&self) -> bool {
+        self.file_name == CONSOLE_FILE_NAME
+    }
+
+    /// Sets the file name, which may contain non utf-8 bytes.
+    pub fn with_file_name(mut self, file_name: impl Into<BString>) -> Self {
+        self.file_name = file_name.into();
+        self
+    }
+
+    /// Sets the modification date of the file, truncated to seconds.
+    pub fn with_created(mut self, created: DateTime<Utc>) -> Self {
+        self.created = created.trunc_subsecs(0);
+        self
+    }
+
+    /// Sets the format of the data. The data itself is not changed, text given as a string
+    /// already has canonical line endings.
+    pub fn with_mode(mut self, mode: DataMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
     /// Convert the data to a UTF-8 string, if appropriate for the type.
     /// Returns `None` if `mode` is `Binary`, or the data is not valid UTF-8.
     pub fn to_string(&self) -> Option<String> {
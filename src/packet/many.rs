@@ -4,21 +4,63 @@ use buffer_redux::Buffer;
 use nom::{Needed, Offset};
 
 use crate::errors::{Error, Result};
+use crate::limits::ParseLimits;
 use crate::packet::packet_sum::Packet;
 use crate::packet::single::{self, ParseResult};
 use crate::types::Tag;
 
-const MAX_CAPACITY: usize = 1024 * 1024 * 1024;
-
 pub struct PacketParser<R> {
     inner: R,
     capacity: usize,
     buffer: Buffer,
     failed: bool,
+    limits: ParseLimits,
+    /// The number of packets parsed so far.
+    count: usize,
+    /// The offset of the buffer in the input.
+    offset: usize,
+    /// Skip packets that fail to parse, instead of stopping.
+    recover: bool,
+    warnings: Vec<ParseWarning>,
+}
+
+/// A packet that was skipped by a [`PacketParser`] in recovery mode.
+#[derive(Debug)]
+pub struct ParseWarning {
+    offset: usize,
+    length: usize,
+    tag: Option<Tag>,
+    error: Error,
+}
+
+impl ParseWarning {
+    /// The offset of the skipped data in the input.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The number of bytes that were skipped.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// The tag of the skipped packet, if its header could be parsed.
+    pub fn tag(&self) -> Option<Tag> {
+        self.tag
+    }
+
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
 }
 
 impl<R: Read> PacketParser<R> {
     pub fn new(inner: R) -> Self {
+        Self::with_limits(inner, ParseLimits::default())
+    }
+
+    /// Creates a parser that rejects packets, and inputs, exceeding `limits`.
+    pub fn with_limits(inner: R, limits: ParseLimits) -> Self {
         PacketParser {
             inner,
             // the inital capacity of our buffer
@@ -27,6 +69,67 @@ impl<R: Read> PacketParser<R> {
             // TODO: only use when available
             buffer: Buffer::with_capacity(1024),
             failed: false,
+            limits,
+            count: 0,
+            offset: 0,
+            recover: false,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Enables recovery mode, in which packets that fail to parse are skipped, using the
+    /// length from their header when it can be parsed, and recorded as [`ParseWarning`]s.
+    ///
+    /// Truncated input and exceeded limits still end parsing.
+    pub fn recover(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        self
+    }
+
+    /// The packets that were skipped so far.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// Takes the packets that were skipped so far.
+    pub fn take_warnings(&mut self) -> Vec<ParseWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Records a warning for `length` skipped bytes at the current offset, merging adjacent
+    /// skipped bytes without a packet header. Truncated input is always reported on its own.
+    fn warn(&mut self, length: usize, tag: Option<Tag>, error: Error) {
+        warn!(
+            "skipping {} bytes at offset {}: {:?}",
+            length, self.offset, error
+        );
+        match self.warnings.last_mut() {
+            Some(last)
+                if tag.is_none()
+                    && last.tag.is_none()
+                    && !matches!(error, Error::PacketIncomplete)
+                    && !matches!(last.error, Error::PacketIncomplete)
+                    && last.offset + last.length == self.offset =>
+            {
+                last.length += length;
+            }
+            _ => self.warnings.push(ParseWarning {
+                offset: self.offset,
+                length,
+                tag,
+                error,
+            }),
+        }
+        self.offset += length;
+    }
+
+    /// Adds the current position in the input to `error`.
+    fn in_packet(&self, tag: Option<Tag>, error: Error) -> Error {
+        Error::InPacket {
+            offset: self.offset,
+            index: self.count,
+            tag,
+            source: Box::new(error),
         }
     }
 }
@@ -39,12 +142,14 @@ impl<R: Read> Iterator for PacketParser<R> {
             return None;
         }
 
-        let b = &mut self.buffer;
         let mut needed: Option<Needed> = None;
         let mut second_round = false;
-        let inner = &mut self.inner;
 
         loop {
+            let b = &mut self.buffer;
+            let limits = &self.limits;
+            let inner = &mut self.inner;
+
             // read some data
             let sz = match b.read_from(inner) {
                 Ok(sz) => sz,
@@ -65,7 +170,12 @@ impl<R: Read> Iterator for PacketParser<R> {
                     // Cancel if we didn't receive enough bytes from our source, the second time around.
                     // TODO: b.reset();
                     self.failed = true;
-                    return Some(Err(Error::PacketIncomplete));
+                    if self.recover {
+                        let length = b.len();
+                        self.warn(length, None, Error::PacketIncomplete);
+                        return None;
+                    }
+                    return Some(Err(self.in_packet(None, Error::PacketIncomplete)));
                 }
                 second_round = true;
             }
@@ -77,15 +187,21 @@ impl<R: Read> Iterator for PacketParser<R> {
             .and_then(|(rest, (ver, tag, _packet_length, body))| match body {
                 ParseResult::Indeterminate => {
                     let mut body = rest.to_vec();
-                    inner.read_to_end(&mut body)?;
+                    // read one byte more than allowed, to detect oversized packets
+                    let max = limits.max_packet_size().saturating_add(1);
+                    inner
+                        .take(max.saturating_sub(body.len()) as u64)
+                        .read_to_end(&mut body)?;
+                    limits.check_packet_size(body.len())?;
                     match single::body_parser(ver, tag, &body) {
-                        Err(Error::Incomplete(n)) => Err(Error::Incomplete(n)),
-                        p => Ok((rest.len() + body.len(), p)),
+                        Err(Error::Truncated { needed }) => Err(Error::Truncated { needed }),
+                        p => Ok((tag, rest.len() + body.len(), p)),
                     }
                 }
                 ParseResult::Fixed(body) => {
+                    limits.check_packet_size(body.len())?;
                     let p = single::body_parser(ver, tag, body);
-                    Ok((b.buf().offset(rest), p))
+                    Ok((tag, b.buf().offset(rest), p))
                 }
                 ParseResult::Partial(body) => {
                     ensure!(
@@ -114,37 +230,79 @@ impl<R: Read> Iterator for PacketParser<R> {
                         );
                     }
 
+                    limits.check_packet_size(body.iter().map(|chunk| chunk.len()).sum())?;
                     let p = single::body_parser(ver, tag, &body.concat());
-                    Ok((b.buf().offset(rest), p))
+                    Ok((tag, b.buf().offset(rest), p))
                 }
             });
 
             let res_body = match res_header {
                 Ok(val) => Some(val),
                 Err(err) => match err {
-                    Error::Incomplete(n) => {
+                    Error::Truncated { needed: n } => {
                         debug!("incomplete {:?}", n);
-                        needed = Some(n);
+                        needed = Some(n.map_or(Needed::Unknown, Needed::new));
                         None
                     }
+                    _ if self.recover => {
+                        // skip the packet if its header can be parsed, otherwise a single byte
+                        let (tag, length) = match single::parser(b.buf()) {
+                            // the rest of the buffer belongs to this packet
+                            Ok((_, (_, tag, _, ParseResult::Indeterminate))) => {
+                                (Some(tag), b.len())
+                            }
+                            Ok((rest, (_, tag, _, _))) => (Some(tag), b.buf().offset(rest)),
+                            Err(_) => (None, 1),
+                        };
+                        b.consume(length);
+                        self.warn(length, tag, err);
+                        needed = None;
+                        second_round = false;
+                        continue;
+                    }
                     _ => {
                         warn!("parsing error {:?}", err);
+                        let tag = match single::parser(b.buf()) {
+                            Ok((_, (_, tag, _, _))) => Some(tag),
+                            Err(_) => None,
+                        };
                         self.failed = true;
-                        return Some(Err(err));
+                        return Some(Err(self.in_packet(tag, err)));
                     }
                 },
             };
 
-            if let Some((length, p)) = res_body {
+            if let Some((tag, length, p)) = res_body {
                 debug!("got packet: {:#?} {}", p, length);
                 assert!(length > 0);
                 b.consume(length);
+                let p = match p {
+                    Err(err) if self.recover => {
+                        self.warn(length, Some(tag), err);
+                        needed = None;
+                        second_round = false;
+                        continue;
+                    }
+                    Err(err) => Err(self.in_packet(Some(tag), err)),
+                    p => p,
+                };
+                self.offset += length;
+                self.count += 1;
+                if let Err(err) = limits.check_packet_count(self.count) {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
                 return Some(p);
             }
 
-            // if the parser returned `Incomplete`, and it needs more data than the buffer can hold, we grow the buffer.
+            // if the parser returned `Incomplete`, and it needs more data than the buffer can hold, we grow the buffer,
+            // unless the packet exceeds the limits.
             if let Some(Needed::Size(sz)) = needed {
-                if b.usable_space() < sz.into() && self.capacity * 2 < MAX_CAPACITY {
+                if let Err(err) = limits.check_packet_size(b.len().saturating_add(sz.into())) {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+                if b.usable_space() < sz.into() {
                     self.capacity *= 2;
                     let capacity = self.capacity;
                     b.make_room();
@@ -168,6 +326,129 @@ mod tests {
     use crate::ser::Serialize;
     use crate::types::Tag;
 
+    #[test]
+    fn test_packet_parser_limits() {
+        use crate::composed::Message;
+        use crate::limits::ParseLimitsBuilder;
+
+        let mut bytes = Vec::new();
+        for _ in 0..3 {
+            Message::new_literal("hello.txt", &"hello world\n".repeat(1000))
+                .to_writer(&mut bytes)
+                .unwrap();
+        }
+        assert_eq!(PacketParser::new(&bytes[..]).count(), 3);
+
+        let limits = ParseLimitsBuilder::default()
+            .max_packet_size(4096)
+            .build()
+            .unwrap();
+        let res: Vec<_> = PacketParser::with_limits(&bytes[..], limits).collect();
+        assert_eq!(res.len(), 1);
+        assert!(res[0].is_err());
+
+        let limits = ParseLimitsBuilder::default()
+            .max_packets(2)
+            .build()
+            .unwrap();
+        let res: Vec<_> = PacketParser::with_limits(&bytes[..], limits).collect();
+        assert_eq!(res.len(), 3);
+        assert!(res[1].is_ok());
+        assert!(res[2].is_err());
+    }
+
+    #[test]
+    fn test_packet_parser_recover() {
+        use crate::composed::Message;
+
+        let mut bytes = Vec::new();
+        Message::new_literal("hello.txt", "hello")
+            .to_writer(&mut bytes)
+            .unwrap();
+        let first = bytes.len();
+        // a signature packet with an invalid body
+        bytes.extend_from_slice(&[0xC2, 0x03, 0xFF, 0xFF, 0xFF]);
+        // bytes that are not a packet header
+        bytes.extend_from_slice(&[0x00, 0x01]);
+        Message::new_literal("world.txt", "world")
+            .to_writer(&mut bytes)
+            .unwrap();
+
+        let packets: Vec<_> = PacketParser::new(&bytes[..]).collect();
+        assert_eq!(packets.iter().filter(|p| p.is_ok()).count(), 1);
+        match packets[1].as_ref().unwrap_err() {
+            Error::InPacket {
+                offset,
+                index,
+                tag,
+                source,
+            } => {
+                assert_eq!(*offset, first);
+                assert_eq!(*index, 1);
+                assert_eq!(*tag, Some(Tag::Signature));
+                assert!(matches!(**source, Error::InvalidPacketContent(_)));
+            }
+            err => panic!("unexpected error {err:?}"),
+        }
+
+        let mut parser = PacketParser::new(&bytes[..]).recover(true);
+        let packets: Vec<_> = parser.by_ref().collect::<Result<_>>().unwrap();
+        assert_eq!(packets.len(), 2);
+        assert!(packets.iter().all(|p| p.tag() == Tag::LiteralData));
+
+        let warnings = parser.warnings();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].offset(), first);
+        assert_eq!(warnings[0].length(), 5);
+        assert_eq!(warnings[0].tag(), Some(Tag::Signature));
+        assert_eq!(warnings[1].offset(), first + 5);
+        assert_eq!(warnings[1].length(), 2);
+        assert_eq!(warnings[1].tag(), None);
+
+        // truncated input ends parsing, with a warning
+        let mut parser = PacketParser::new(&bytes[..bytes.len() - 1]).recover(true);
+        assert_eq!(parser.by_ref().count(), 1);
+        let warnings = parser.warnings();
+        assert_eq!(warnings.len(), 3);
+        assert!(matches!(warnings[2].error(), Error::PacketIncomplete));
+    }
+
+    #[test]
+    fn test_experimental_packets() {
+        use crate::composed::{Deserializable, Message};
+        use crate::packet::Packet;
+
+        let literal = Message::new_literal("hello.txt", "hello");
+        let mut bytes = vec![0xC0 | Tag::Private60 as u8, 0x03, 0x01, 0x02, 0x03];
+        literal.to_writer(&mut bytes).unwrap();
+        bytes.extend_from_slice(&[0xC0 | Tag::Private63 as u8, 0x00]);
+
+        let packets: Vec<_> = PacketParser::new(&bytes[..])
+            .collect::<Result<_>>()
+            .unwrap();
+        let tags: Vec<_> = packets.iter().map(|p| p.tag()).collect();
+        assert_eq!(tags, [Tag::Private60, Tag::LiteralData, Tag::Private63]);
+        match &packets[0] {
+            Packet::Experimental(p) => assert_eq!(p.body(), &[1, 2, 3]),
+            p => panic!("unexpected packet {p:?}"),
+        }
+
+        let mut written = Vec::new();
+        for packet in &packets {
+            packet.to_writer(&mut written).unwrap();
+        }
+        assert_eq!(written, bytes);
+
+        // the leading packet is skipped, the trailing one is kept with the message
+        assert_eq!(
+            Message::from_bytes(&bytes[..]).unwrap(),
+            Message::Experimental {
+                message: Box::new(literal),
+                packets: vec![packets.into_iter().last().unwrap().try_into().unwrap()],
+            }
+        );
+    }
+
     #[test]
     #[ignore]
     fn test_packet_roundtrip_0001() {
@@ -79,8 +79,9 @@
 //! ```
 
 mod many;
+mod packet_ref;
 mod packet_sum;
-mod single;
+pub(crate) mod single;
 
 #[macro_use]
 mod secret_key_macro;
@@ -88,6 +89,7 @@ mod secret_key_macro;
 mod public_key_macro;
 
 mod compressed_data;
+mod experimental;
 mod key;
 mod literal_data;
 mod marker;
@@ -106,6 +108,7 @@ mod public_key_parser;
 mod secret_key_parser;
 
 pub use self::compressed_data::*;
+pub use self::experimental::*;
 pub use self::key::*;
 pub use self::literal_data::*;
 pub use self::marker::*;
@@ -121,4 +124,5 @@ pub use self::user_attribute::*;
 pub use self::user_id::*;
 
 pub use self::many::*;
+pub use self::packet_ref::*;
 pub use self::packet_sum::*;
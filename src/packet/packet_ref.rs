@@ -0,0 +1,264 @@
+use std::borrow::Cow;
+use std::io;
+
+use nom::Offset;
+
+use crate::errors::Result;
+use crate::packet::packet_sum::Packet;
+use crate::packet::single::{self, ParseResult};
+use crate::types::{PacketLength, Tag, Version};
+
+/// A packet that borrows its body from the input it was parsed from.
+///
+/// Only the header is parsed, so scanning large inputs, like keyrings, does not allocate,
+/// unless the packet uses partial body lengths. Use [`PacketRef::to_packet`] to parse the body
+/// of the packets that are needed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PacketRef<'a> {
+    packet_version: Version,
+    tag: Tag,
+    packet_length: PacketLength,
+    offset: usize,
+    raw: &'a [u8],
+    body: Cow<'a, [u8]>,
+}
+
+impl<'a> PacketRef<'a> {
+    /// The header format of the packet, `Old` or `New`.
+    pub fn packet_version(&self) -> Version {
+        self.packet_version
+    }
+
+    /// The length from the header. For partial body lengths, this is the length of the first
+    /// chunk.
+    pub fn packet_length(&self) -> &PacketLength {
+        &self.packet_length
+    }
+
+    /// The offset of the packet in the input.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The packet as it is found in the input, including the header, and any partial body
+    /// length headers.
+    pub fn raw(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    /// The raw body of the packet, without the header.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Parses the body into an owned [`Packet`].
+    pub fn to_packet(&self) -> Result<Packet> {
+        single::body_parser(self.packet_version, self.tag, &self.body)
+    }
+}
+
+/// Parses packets from a buffer, e.g. a memory mapped file, without copying their bodies.
+///
+/// Packets are yielded as they are found, with their position in the input, and are not
+/// composed into messages or keys. This allows tools to count, report on or splice raw packet
+/// streams.
+///
+/// ```rust
+/// # fn main() -> pgp::errors::Result<()> {
+/// use pgp::packet::PacketRefParser;
+/// use pgp::ser::Serialize;
+/// use pgp::types::Tag;
+/// use pgp::{Deserializable, SignedPublicKey};
+///
+/// let armored = std::fs::read_to_string("./tests/autocrypt/alice@autocrypt.example.pub.asc")?;
+/// let input = SignedPublicKey::from_string(&armored)?.0.to_bytes()?;
+///
+/// let user_ids = PacketRefParser::new(&input)
+///     .filter_map(|p| p.ok())
+///     .filter(|p| p.tag() == Tag::UserId)
+///     .count();
+/// assert_eq!(user_ids, 1);
+/// # Ok(())
+/// # }
+/// ```
+pub struct PacketRefParser<'a> {
+    input: &'a [u8],
+    /// The length of the original input.
+    len: usize,
+    failed: bool,
+}
+
+impl<'a> PacketRefParser<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        PacketRefParser {
+            input,
+            len: input.len(),
+            failed: false,
+        }
+    }
+
+    /// The offset of the next packet in the input.
+    pub fn offset(&self) -> usize {
+        self.len - self.input.len()
+    }
+}
+
+impl<'a> Iterator for PacketRefParser<'a> {
+    type Item = Result<PacketRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.input.is_empty() {
+            return None;
+        }
+
+        let (rest, (packet_version, tag, packet_length, body)) = match single::parser(self.input) {
+            Ok(res) => res,
+            Err(err) => {
+                self.failed = true;
+                return Some(Err(err.into()));
+            }
+        };
+
+        let (body, consumed) = match body {
+            ParseResult::Fixed(body) => (Cow::Borrowed(body), self.input.offset(rest)),
+            // the rest of the input belongs to this packet
+            ParseResult::Indeterminate => (Cow::Borrowed(rest), self.input.len()),
+            ParseResult::Partial(chunks) => (Cow::Owned(chunks.concat()), self.input.offset(rest)),
+        };
+        let offset = self.offset();
+        let raw = &self.input[..consumed];
+        self.input = &self.input[consumed..];
+
+        Some(Ok(PacketRef {
+            packet_version,
+            tag,
+            packet_length,
+            offset,
+            raw,
+            body,
+        }))
+    }
+}
+
+/// Writes the packets in `input` with headers in the given format, e.g. to serialize a key with
+/// old format headers.
+///
+/// Only the headers are changed, the bodies are copied unchanged. Partial body lengths are
+/// replaced by a single length, and tags above 15 are always written in the new format.
+pub fn rewrite_packet_headers(
+    input: &[u8],
+    packet_version: Version,
+    writer: &mut impl io::Write,
+) -> Result<()> {
+    for packet in PacketRefParser::new(input) {
+        let packet = packet?;
+        packet_version.write_header(writer, packet.tag() as u8, packet.body().len())?;
+        writer.write_all(packet.body())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::fs::File;
+    use std::io::Read;
+
+    use super::*;
+    use crate::armor::Dearmor;
+    use crate::composed::{Deserializable, SignedSecretKey};
+    use crate::packet::PacketParser;
+    use crate::ser::Serialize;
+    use crate::types::KeyTrait;
+
+    #[test]
+    fn test_packet_ref_parser() {
+        let (key, _headers) = SignedSecretKey::from_armor_single(
+            File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let input = key.to_bytes().unwrap();
+
+        let refs: Vec<_> = PacketRefParser::new(&input).collect::<Result<_>>().unwrap();
+        let owned: Vec<_> = PacketParser::new(&input[..])
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(refs.len(), owned.len());
+        let mut offset = 0;
+        for (r, o) in refs.iter().zip(&owned) {
+            assert_eq!(r.offset(), offset);
+            assert_eq!(r.raw(), &input[offset..offset + r.raw().len()]);
+            offset += r.raw().len();
+            assert!(matches!(r.body, Cow::Borrowed(_)));
+            assert_eq!(
+                r.to_packet().unwrap().to_bytes().unwrap(),
+                o.to_bytes().unwrap()
+            );
+        }
+
+        // truncated input yields an error, after the complete packets
+        let truncated = &input[..input.len() - 1];
+        let res: Vec<_> = PacketRefParser::new(truncated).collect();
+        assert_eq!(res.len(), owned.len());
+        assert!(res.last().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_packet_ref_partial() {
+        let mut dearmor = Dearmor::new(File::open("./tests/partial.asc").unwrap());
+        let mut bytes = Vec::new();
+        dearmor.read_to_end(&mut bytes).unwrap();
+
+        let refs: Vec<_> = PacketRefParser::new(&bytes).collect::<Result<_>>().unwrap();
+        let owned: Vec<_> = PacketParser::new(&bytes[..])
+            .collect::<Result<_>>()
+            .unwrap();
+        assert!(refs
+            .iter()
+            .any(|r| matches!(r.packet_length(), PacketLength::Partial(_))));
+        assert_eq!(
+            refs.iter().map(|r| r.raw()).collect::<Vec<_>>().concat(),
+            bytes
+        );
+        assert_eq!(refs.len(), owned.len());
+        for (r, o) in refs.iter().zip(&owned) {
+            assert_eq!(
+                r.to_packet().unwrap().to_bytes().unwrap(),
+                o.to_bytes().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_rewrite_packet_headers() {
+        let (key, _headers) = SignedSecretKey::from_armor_single(
+            File::open("./tests/autocrypt/alice@autocrypt.example.sec.asc").unwrap(),
+        )
+        .unwrap();
+        let input = key.to_bytes().unwrap();
+
+        let mut old = Vec::new();
+        rewrite_packet_headers(&input, Version::Old, &mut old).unwrap();
+        let refs: Vec<_> = PacketRefParser::new(&old).collect::<Result<_>>().unwrap();
+        assert!(refs.iter().all(|r| r.packet_version() == Version::Old));
+
+        // the header format is kept when serializing again
+        let parsed = SignedSecretKey::from_bytes(&old[..]).unwrap();
+        assert_eq!(parsed.fingerprint(), key.fingerprint());
+        parsed.verify().unwrap();
+        assert_eq!(parsed.to_bytes().unwrap(), old);
+
+        let mut new = Vec::new();
+        rewrite_packet_headers(&old, Version::New, &mut new).unwrap();
+        let refs: Vec<_> = PacketRefParser::new(&new).collect::<Result<_>>().unwrap();
+        assert!(refs.iter().all(|r| r.packet_version() == Version::New));
+        let parsed = SignedSecretKey::from_bytes(&new[..]).unwrap();
+        assert_eq!(parsed.to_bytes().unwrap(), new);
+    }
+}
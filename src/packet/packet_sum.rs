@@ -2,8 +2,8 @@ use std::io;
 
 use crate::errors::Result;
 use crate::packet::{
-    CompressedData, LiteralData, Marker, ModDetectionCode, OnePassSignature, PublicKey,
-    PublicKeyEncryptedSessionKey, PublicSubkey, SecretKey, SecretSubkey, Signature,
+    CompressedData, Experimental, LiteralData, Marker, ModDetectionCode, OnePassSignature,
+    PublicKey, PublicKeyEncryptedSessionKey, PublicSubkey, SecretKey, SecretSubkey, Signature,
     SymEncryptedData, SymEncryptedProtectedData, SymKeyEncryptedSessionKey, Trust, UserAttribute,
     UserId,
 };
@@ -30,6 +30,7 @@ pub enum Packet {
     Trust(Trust),
     UserAttribute(UserAttribute),
     UserId(UserId),
+    Experimental(Experimental),
 }
 
 impl Packet {
@@ -53,6 +54,7 @@ impl Packet {
             Packet::Trust(_) => Tag::Trust,
             Packet::UserAttribute(_) => Tag::UserAttribute,
             Packet::UserId(_) => Tag::UserId,
+            Packet::Experimental(p) => p.tag(),
         }
     }
 
@@ -75,6 +77,7 @@ impl Packet {
             Packet::Trust(p) => p.packet_version(),
             Packet::UserAttribute(p) => p.packet_version(),
             Packet::UserId(p) => p.packet_version(),
+            Packet::Experimental(p) => p.packet_version(),
         }
     }
 }
@@ -97,7 +100,8 @@ impl_try_from_into!(
     SymKeyEncryptedSessionKey => SymKeyEncryptedSessionKey,
     Trust => Trust,
     UserAttribute => UserAttribute,
-    UserId => UserId
+    UserId => UserId,
+    Experimental => Experimental
 );
 
 // TODO: move to its own file
@@ -121,6 +125,7 @@ impl Serialize for Packet {
             Packet::Trust(p) => write_packet(writer, &p),
             Packet::UserAttribute(p) => write_packet(writer, &p),
             Packet::UserId(p) => write_packet(writer, &p),
+            Packet::Experimental(p) => write_packet(writer, &p),
         }
     }
 }
@@ -140,8 +145,24 @@ impl<'a, T: 'a + PacketTrait> PacketTrait for &'a T {
     }
 }
 
+/// Writes the packet header and body.
+///
+/// Parsed packet bodies are written back unchanged. The header keeps the old or new format,
+/// but uses the shortest length encoding; [`PacketRef::raw`](crate::packet::PacketRef::raw)
+/// provides packets with their original headers.
 pub fn write_packet(writer: &mut impl io::Write, packet: &impl PacketTrait) -> Result<()> {
-    let packet_version = packet.packet_version();
+    write_packet_with_version(writer, packet, packet.packet_version())
+}
+
+/// Writes the packet with a header in the given format, instead of the one it was parsed or
+/// created with, e.g. old format headers for consumers that predate RFC 4880.
+///
+/// Tags above 15 can not be expressed in the old format, and are written in the new format.
+pub fn write_packet_with_version(
+    writer: &mut impl io::Write,
+    packet: &impl PacketTrait,
+    packet_version: Version,
+) -> Result<()> {
     let mut buf = Vec::new();
     packet.to_writer(&mut buf)?;
     debug!(
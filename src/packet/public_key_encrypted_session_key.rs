@@ -68,10 +68,28 @@ impl PublicKeyEncryptedSessionKey {
         })
     }
 
+    /// Replaces the recipient's Key ID with the wildcard Key ID, so the packet does not reveal
+    /// the recipient. Recipients then have to try all of their keys to decrypt it.
+    pub fn into_anonymous(self) -> Self {
+        PublicKeyEncryptedSessionKey {
+            id: KeyId::WILDCARD,
+            ..self
+        }
+    }
+
+    /// Whether the recipient is hidden behind the wildcard Key ID.
+    pub fn is_anonymous(&self) -> bool {
+        self.id.is_wildcard()
+    }
+
     pub fn id(&self) -> &KeyId {
         &self.id
     }
 
+    pub fn algorithm(&self) -> PublicKeyAlgorithm {
+        self.algorithm
+    }
+
     pub fn mpis(&self) -> &[Mpi] {
         &self.mpis
     }
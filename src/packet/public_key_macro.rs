@@ -86,6 +86,31 @@ macro_rules! impl_public_key {
                 unimplemented!("verify");
             }
 
+            /// Formats the key as a line of an OpenSSH `authorized_keys` file, e.g.
+            /// `ssh-ed25519 AAAA...`.
+            #[cfg(feature = "ssh")]
+            pub fn to_ssh_public_key(&self) -> $crate::errors::Result<String> {
+                $crate::formats::ssh::public_key_line(&self.public_params, None)
+            }
+
+            /// Encodes the key as a DER encoded X.509 `SubjectPublicKeyInfo`.
+            #[cfg(feature = "spki")]
+            pub fn to_spki_der(&self) -> $crate::errors::Result<Vec<u8>> {
+                $crate::formats::spki::to_der(&self.public_params)
+            }
+
+            /// Encodes the key as a PEM encoded X.509 `SubjectPublicKeyInfo`, i.e. a
+            /// `-----BEGIN PUBLIC KEY-----` block.
+            #[cfg(feature = "spki")]
+            pub fn to_spki_pem(&self) -> $crate::errors::Result<String> {
+                $crate::formats::spki::to_pem(&self.public_params)
+            }
+
+            /// Converts the key into a JSON Web Key.
+            pub fn to_jwk(&self) -> $crate::errors::Result<$crate::formats::jwk::Jwk> {
+                $crate::formats::jwk::Jwk::from_public_params(&self.public_params)
+            }
+
             fn to_writer_old<W: std::io::Write>(
                 &self,
                 writer: &mut W,
@@ -124,7 +149,7 @@ macro_rules! impl_public_key {
                 key_pw: F,
             ) -> $crate::errors::Result<$crate::packet::Signature>
             where
-                F: FnOnce() -> String,
+                F: $crate::types::PassphraseProvider,
             {
                 use chrono::SubsecRound;
 
@@ -183,15 +208,15 @@ macro_rules! impl_public_key {
 
         impl $crate::types::KeyTrait for $name {
             /// Returns the fingerprint of this key.
-            fn fingerprint(&self) -> Vec<u8> {
+            fn fingerprint(&self) -> $crate::types::Fingerprint {
                 use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
                 use md5::Md5;
                 use sha1::{Digest, Sha1};
 
                 use $crate::ser::Serialize;
-                use $crate::types::KeyVersion;
+                use $crate::types::{Fingerprint, KeyVersion};
 
-                match self.version() {
+                let fingerprint = match self.version() {
                     KeyVersion::V2 | KeyVersion::V3 => {
                         let mut h = Md5::new();
                         self.public_params
@@ -225,7 +250,9 @@ macro_rules! impl_public_key {
                     }
                     KeyVersion::V5 => unimplemented!("V5 keys"),
                     KeyVersion::Other(v) => unimplemented!("Unsupported key version {}", v),
-                }
+                };
+
+                Fingerprint::new(self.version(), &fingerprint).expect("fixed size fingerprint")
             }
 
             fn key_id(&self) -> $crate::types::KeyId {
@@ -242,10 +269,7 @@ macro_rules! impl_public_key {
                     },
                     KeyVersion::V4 => {
                         // Lower 64 bits
-                        let f = self.fingerprint();
-                        let offset = f.len() - 8;
-
-                        KeyId::from_slice(&f[offset..]).expect("fixed size slice")
+                        self.fingerprint().key_id().expect("fixed size slice")
                     }
                     KeyVersion::V5 => unimplemented!("V5 keys"),
                     KeyVersion::Other(v) => unimplemented!("Unsupported key version {}", v),
@@ -341,7 +365,7 @@ macro_rules! impl_public_key {
                         curve,
                         alg_sym,
                         hash,
-                        &self.fingerprint(),
+                        self.fingerprint().as_bytes(),
                         p.as_bytes(),
                         plain,
                     ),
@@ -44,6 +44,78 @@ macro_rules! impl_secret_key {
                 })
             }
 
+            /// Imports the unencrypted secret key from a DER encoded PKCS#8 `PrivateKeyInfo`,
+            /// as a v4 key created at `created_at`.
+            ///
+            /// The fingerprint of the key depends on `created_at`, so the same time has to be
+            /// used to import a key repeatedly.
+            #[cfg(feature = "pkcs8")]
+            pub fn from_pkcs8(
+                der: &[u8],
+                created_at: chrono::DateTime<chrono::Utc>,
+            ) -> $crate::errors::Result<Self> {
+                let (public_params, secret_params) = $crate::formats::pkcs8::from_pkcs8_der(der)?;
+                Self::from_params(public_params, secret_params, created_at)
+            }
+
+            /// Imports the unencrypted secret key from a DER encoded SEC1 `ECPrivateKey`,
+            /// as a v4 ECDSA key created at `created_at`.
+            #[cfg(feature = "pkcs8")]
+            pub fn from_sec1(
+                der: &[u8],
+                created_at: chrono::DateTime<chrono::Utc>,
+            ) -> $crate::errors::Result<Self> {
+                let (public_params, secret_params) = $crate::formats::pkcs8::from_sec1_der(der)?;
+                Self::from_params(public_params, secret_params, created_at)
+            }
+
+            /// Imports the secret key from an OpenSSH private key file, as a v4 key created at
+            /// `created_at`.
+            ///
+            /// `passphrase` is only used if the OpenSSH key is encrypted. The imported key is
+            /// unencrypted.
+            #[cfg(feature = "ssh")]
+            pub fn from_openssh(
+                pem: &str,
+                passphrase: Option<&str>,
+                created_at: chrono::DateTime<chrono::Utc>,
+            ) -> $crate::errors::Result<Self> {
+                let (public_params, secret_params) =
+                    $crate::formats::ssh::from_private_key_pem(pem, passphrase)?;
+                Self::from_params(public_params, secret_params, created_at)
+            }
+
+            #[cfg(any(feature = "pkcs8", feature = "ssh"))]
+            fn from_params(
+                public_params: $crate::types::PublicParams,
+                secret_params: $crate::types::PlainSecretParams,
+                created_at: chrono::DateTime<chrono::Utc>,
+            ) -> $crate::errors::Result<Self> {
+                use chrono::SubsecRound;
+                use $crate::crypto::public_key::PublicKeyAlgorithm;
+                use $crate::types::PublicParams;
+
+                let algorithm = match public_params {
+                    PublicParams::RSA { .. } => PublicKeyAlgorithm::RSA,
+                    PublicParams::ECDSA(_) => PublicKeyAlgorithm::ECDSA,
+                    PublicParams::EdDSA { .. } => PublicKeyAlgorithm::EdDSA,
+                    PublicParams::ECDH { .. } => PublicKeyAlgorithm::ECDH,
+                    _ => unsupported_err!("importing keys with parameters {:?}", public_params),
+                };
+
+                Ok($name {
+                    details: $crate::packet::$details {
+                        packet_version: Default::default(),
+                        version: $crate::types::KeyVersion::V4,
+                        algorithm,
+                        created_at: created_at.trunc_subsecs(0),
+                        expiration: None,
+                        public_params,
+                    },
+                    secret_params: $crate::types::SecretParams::Plain(secret_params.to_secret()),
+                })
+            }
+
             pub fn version(&self) -> $crate::types::KeyVersion {
                 self.details.version()
             }
@@ -70,9 +142,15 @@ macro_rules! impl_secret_key {
                 ciphertext: &$crate::types::EncryptedSecretParams,
             ) -> $crate::errors::Result<$crate::types::SecretKeyRepr>
             where
-                F: FnOnce() -> String,
+                F: $crate::types::PassphraseProvider,
             {
-                let plain = ciphertext.unlock(pw, self.details.algorithm, self.public_params())?;
+                let request = $crate::types::PassphraseRequest::new(self);
+                let plain = ciphertext.unlock(
+                    pw,
+                    &request,
+                    self.details.algorithm,
+                    self.public_params(),
+                )?;
                 self.repr_from_plaintext(&plain)
             }
 
@@ -87,6 +165,52 @@ macro_rules! impl_secret_key {
                 &self.secret_params
             }
 
+            /// Replaces the protection of the secret parameters.
+            ///
+            /// The parameters are decrypted using `old_pw`, if they are encrypted, and then
+            /// encrypted with `new_pw`, using a fresh S2K. If `new_pw` is `None`, they are
+            /// stored unencrypted.
+            pub fn set_passphrase_with_rng<R, F>(
+                &mut self,
+                rng: &mut R,
+                old_pw: F,
+                new_pw: Option<&str>,
+            ) -> $crate::errors::Result<()>
+            where
+                R: rand::Rng + rand::CryptoRng,
+                F: $crate::types::PassphraseProvider,
+            {
+                use $crate::types::SecretParams;
+
+                let plain = match self.secret_params {
+                    SecretParams::Plain(ref k) => k.clone(),
+                    SecretParams::Encrypted(ref k) => k.unlock(
+                        old_pw,
+                        &$crate::types::PassphraseRequest::new(self),
+                        self.details.algorithm,
+                        self.public_params(),
+                    )?,
+                };
+
+                self.secret_params = match new_pw {
+                    Some(new_pw) => {
+                        let s2k = $crate::types::StringToKey::new_default(rng);
+                        SecretParams::Encrypted(plain.encrypt(
+                            rng,
+                            new_pw,
+                            $crate::crypto::sym::SymmetricKeyAlgorithm::AES256,
+                            s2k,
+                            self.version(),
+                            // encrypted, sha1 checksum
+                            254,
+                        )?)
+                    }
+                    None => SecretParams::Plain(plain),
+                };
+
+                Ok(())
+            }
+
             /// Checks if we should expect a SHA1 checksum in the encrypted part.
             pub fn has_sha1_checksum(&self) -> bool {
                 self.secret_params.string_to_key_id() == 254
@@ -122,7 +246,7 @@ macro_rules! impl_secret_key {
                 key_pw: F,
             ) -> $crate::errors::Result<$crate::packet::Signature>
             where
-                F: FnOnce() -> String,
+                F: $crate::types::PassphraseProvider,
             {
                 use chrono::SubsecRound;
                 let mut config = $crate::packet::SignatureConfigBuilder::default();
@@ -157,7 +281,7 @@ macro_rules! impl_secret_key {
             /// Unlock the raw data in the secret parameters.
             fn unlock<F, G>(&self, pw: F, work: G) -> $crate::errors::Result<()>
             where
-                F: FnOnce() -> String,
+                F: $crate::types::PassphraseProvider,
                 G: FnOnce(&$crate::types::SecretKeyRepr) -> $crate::errors::Result<()>,
             {
                 use $crate::types::SecretParams;
@@ -170,6 +294,18 @@ macro_rules! impl_secret_key {
                 work(&decrypted)
             }
 
+            #[cfg_attr(
+                feature = "tracing",
+                tracing::instrument(
+                    level = "debug",
+                    skip_all,
+                    fields(
+                        key_id = %$crate::types::KeyTrait::key_id(self),
+                        algorithm = ?$crate::types::KeyTrait::algorithm(self),
+                        ?hash,
+                    )
+                )
+            )]
             fn create_signature<F>(
                 &self,
                 key_pw: F,
@@ -177,7 +313,7 @@ macro_rules! impl_secret_key {
                 data: &[u8],
             ) -> $crate::errors::Result<Vec<$crate::types::Mpi>>
             where
-                F: FnOnce() -> String,
+                F: $crate::types::PassphraseProvider,
             {
                 use $crate::crypto::ecc_curve::ECCCurve;
                 use $crate::types::{PublicParams, SecretKeyRepr};
@@ -276,7 +412,7 @@ macro_rules! impl_secret_key {
 
         impl $crate::types::KeyTrait for $name {
             /// Returns the fingerprint of this key.
-            fn fingerprint(&self) -> Vec<u8> {
+            fn fingerprint(&self) -> $crate::types::Fingerprint {
                 self.details.fingerprint()
             }
 
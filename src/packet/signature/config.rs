@@ -7,9 +7,13 @@ use chrono::{DateTime, Utc};
 use crate::crypto::hash::{HashAlgorithm, Hasher};
 use crate::crypto::public_key::PublicKeyAlgorithm;
 use crate::errors::{Error, Result};
-use crate::packet::{Signature, SignatureType, SignatureVersion, Subpacket, SubpacketData};
+use crate::packet::{
+    Notation, Signature, SignatureType, SignatureVersion, Subpacket, SubpacketData,
+};
 use crate::ser::Serialize;
-use crate::types::{KeyId, PublicKeyTrait, SecretKeyTrait, Tag};
+#[cfg(feature = "async")]
+use crate::types::AsyncSecretKeyTrait;
+use crate::types::{Fingerprint, KeyId, PassphraseProvider, PublicKeyTrait, SecretKeyTrait, Tag};
 
 #[derive(Clone, PartialEq, Eq, Builder)]
 #[builder(build_fn(error = "Error"))]
@@ -53,15 +57,33 @@ impl SignatureConfig {
         }
     }
 
+    /// Adds `notation` to the hashed subpackets, after the ones already added.
+    pub fn add_notation(&mut self, notation: Notation) {
+        self.hashed_subpackets.push(notation.into());
+    }
+
     /// Sign the given data.
     pub fn sign<F, R>(self, key: &impl SecretKeyTrait, key_pw: F, data: R) -> Result<Signature>
     where
-        F: FnOnce() -> String,
+        F: PassphraseProvider,
         R: Read,
     {
         let mut hasher = self.hash_alg.new_hasher()?;
 
         self.hash_data_to_sign(&mut *hasher, data)?;
+        self.sign_hashed(key, key_pw, hasher)
+    }
+
+    /// Sign data that was already hashed into `hasher`, e.g. while it was streamed.
+    pub fn sign_hashed<F>(
+        self,
+        key: &impl SecretKeyTrait,
+        key_pw: F,
+        mut hasher: Box<dyn Hasher>,
+    ) -> Result<Signature>
+    where
+        F: PassphraseProvider,
+    {
         let len = self.hash_signature_data(&mut *hasher)?;
         hasher.update(&self.trailer(len)?);
 
@@ -73,6 +95,50 @@ impl SignatureConfig {
         Ok(Signature::from_config(self, signed_hash_value, signature))
     }
 
+    /// Async variant of [`sign`](SignatureConfig::sign), for keys whose signing operation is
+    /// delegated, e.g. to a remote service. The data is hashed before awaiting the key.
+    #[cfg(feature = "async")]
+    pub async fn sign_async<F, R>(
+        self,
+        key: &impl AsyncSecretKeyTrait,
+        key_pw: F,
+        data: R,
+    ) -> Result<Signature>
+    where
+        F: PassphraseProvider + Send,
+        R: Read,
+    {
+        let hash = {
+            let mut hasher = self.hash_alg.new_hasher()?;
+            self.hash_data_to_sign(&mut *hasher, data)?;
+            let len = self.hash_signature_data(&mut *hasher)?;
+            hasher.update(&self.trailer(len)?);
+            hasher.finish()
+        };
+
+        let signed_hash_value = [hash[0], hash[1]];
+        let signature = key.create_signature(key_pw, self.hash_alg, &hash).await?;
+
+        Ok(Signature::from_config(self, signed_hash_value, signature))
+    }
+
+    /// Create a standalone or timestamp signature, which does not sign any data.
+    pub fn sign_standalone<F>(self, key: &impl SecretKeyTrait, key_pw: F) -> Result<Signature>
+    where
+        F: PassphraseProvider,
+    {
+        ensure!(
+            matches!(
+                self.typ,
+                SignatureType::Standalone | SignatureType::Timestamp
+            ),
+            "invalid signature type for a standalone signature: {:?}",
+            self.typ
+        );
+
+        self.sign(key, key_pw, std::io::empty())
+    }
+
     /// Create a certification siganture.
     pub fn sign_certification<F>(
         self,
@@ -82,7 +148,22 @@ impl SignatureConfig {
         id: &impl Serialize,
     ) -> Result<Signature>
     where
-        F: FnOnce() -> String,
+        F: PassphraseProvider,
+    {
+        self.sign_third_party_certification(key, key_pw, key, tag, id)
+    }
+
+    /// Create a certification signature over `id` of `key`, issued by `signing_key`.
+    pub fn sign_third_party_certification<F>(
+        self,
+        signing_key: &impl SecretKeyTrait,
+        key_pw: F,
+        key: &impl PublicKeyTrait,
+        tag: Tag,
+        id: &impl Serialize,
+    ) -> Result<Signature>
+    where
+        F: PassphraseProvider,
     {
         ensure!(
             self.is_certification(),
@@ -128,7 +209,7 @@ impl SignatureConfig {
         let hash = &hasher.finish()[..];
 
         let signed_hash_value = [hash[0], hash[1]];
-        let signature = key.create_signature(key_pw, self.hash_alg, hash)?;
+        let signature = signing_key.create_signature(key_pw, self.hash_alg, hash)?;
 
         Ok(Signature::from_config(self, signed_hash_value, signature))
     }
@@ -141,7 +222,7 @@ impl SignatureConfig {
         key: &impl PublicKeyTrait,
     ) -> Result<Signature>
     where
-        F: FnOnce() -> String,
+        F: PassphraseProvider,
     {
         debug!(
             "signing key binding: {:#?} - {:#?} - {:#?}",
@@ -166,6 +247,41 @@ impl SignatureConfig {
         Ok(Signature::from_config(self, signed_hash_value, signature))
     }
 
+    /// Sign a primary key binding, or "back signature".
+    ///
+    /// The `signing_key` is the subkey, `key` the primary key it is bound to.
+    pub fn sign_backwards_key_binding<F>(
+        self,
+        signing_key: &impl SecretKeyTrait,
+        key_pw: F,
+        key: &impl PublicKeyTrait,
+    ) -> Result<Signature>
+    where
+        F: PassphraseProvider,
+    {
+        debug!(
+            "signing backwards key binding: {:#?} - {:#?} - {:#?}",
+            self, signing_key, key
+        );
+
+        let mut hasher = self.hash_alg.new_hasher()?;
+
+        // Primary key
+        key.to_writer_old(&mut hasher)?;
+
+        // Subkey, which creates the signature
+        signing_key.to_writer_old(&mut hasher)?;
+
+        let len = self.hash_signature_data(&mut *hasher)?;
+        hasher.update(&self.trailer(len)?);
+
+        let hash = &hasher.finish()[..];
+        let signed_hash_value = [hash[0], hash[1]];
+        let signature = signing_key.create_signature(key_pw, self.hash_alg, hash)?;
+
+        Ok(Signature::from_config(self, signed_hash_value, signature))
+    }
+
     /// Signs a direct key signature or a revocation.
     pub fn sign_key<F>(
         self,
@@ -174,7 +290,7 @@ impl SignatureConfig {
         key: &impl PublicKeyTrait,
     ) -> Result<Signature>
     where
-        F: FnOnce() -> String,
+        F: PassphraseProvider,
     {
         debug!("signing key (revocation): {:#?} - {:#?}", self, key);
 
@@ -261,18 +377,10 @@ impl SignatureConfig {
         R: Read,
     {
         match self.typ {
-            SignatureType::Text |
-                // assumes that the passed in text was already valid utf8 and normalized
-            SignatureType::Binary => {
-                Ok(std::io::copy(&mut data, hasher)? as usize)
-            }
-            SignatureType::Timestamp |
-            SignatureType::Standalone => {
-                let mut val = [0u8;1];
-                data.read_exact(&mut val[..])?;
-                hasher.update(&val[..]);
-                Ok(1)
-            }
+            SignatureType::Text => hash_text(hasher, data),
+            SignatureType::Binary => Ok(std::io::copy(&mut data, hasher)? as usize),
+            // only the signature itself is hashed
+            SignatureType::Timestamp | SignatureType::Standalone => Ok(0),
             SignatureType::CertGeneric
             | SignatureType::CertPersona
             | SignatureType::CertCasual
@@ -396,11 +504,11 @@ impl SignatureConfig {
     /// https://datatracker.ietf.org/doc/html/draft-ietf-openpgp-rfc4880bis-10#name-issuer-fingerprint
     ///
     /// Returns Issuer Fingerprint subpacket data from both the hashed and unhashed area.
-    pub fn issuer_fingerprint(&self) -> Vec<&[u8]> {
+    pub fn issuer_fingerprint(&self) -> Vec<&Fingerprint> {
         self.hashed_subpackets()
             .chain(self.unhashed_subpackets())
             .filter_map(|sp| match &sp.data {
-                SubpacketData::IssuerFingerprint(_, fp) => Some(fp.as_slice()),
+                SubpacketData::IssuerFingerprint(fp) => Some(fp),
                 _ => None,
             })
             .collect()
@@ -421,3 +529,46 @@ impl fmt::Debug for SignatureConfig {
             .finish()
     }
 }
+
+/// Hashes the text read from `data`, in its canonical form with `\r\n` line endings.
+///
+/// Any other line ending, including a lone `\r`, is converted, so documents with mixed line
+/// endings hash the same as other implementations, such as GnuPG, do.
+fn hash_text<R: Read>(hasher: &mut dyn Hasher, mut data: R) -> Result<usize> {
+    let mut buf = [0u8; 8 * 1024];
+    let mut canonical = Vec::with_capacity(2 * buf.len());
+    let mut prev_was_cr = false;
+    let mut len = 0;
+
+    loop {
+        let read = match data.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        canonical.clear();
+        canonicalize_text(&buf[..read], &mut prev_was_cr, &mut canonical);
+        hasher.update(&canonical);
+        len += canonical.len();
+    }
+
+    Ok(len)
+}
+
+/// Appends `data` to `out`, with every line ending converted to `\r\n`.
+///
+/// `prev_was_cr` carries whether the previous chunk ended in `\r`, so text can be converted
+/// in chunks of any size.
+pub(crate) fn canonicalize_text(data: &[u8], prev_was_cr: &mut bool, out: &mut Vec<u8>) {
+    for &b in data {
+        match b {
+            // the line ending was already written for the preceding \r
+            b'\n' if *prev_was_cr => {}
+            b'\r' | b'\n' => out.extend_from_slice(b"\r\n"),
+            _ => out.push(b),
+        }
+        *prev_was_cr = b == b'\r';
+    }
+}
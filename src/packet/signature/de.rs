@@ -18,8 +18,8 @@ use crate::de::Deserialize;
 use crate::errors::{IResult, Result};
 use crate::packet::signature::types::*;
 use crate::types::{
-    mpi, CompressionAlgorithm, KeyId, KeyVersion, Mpi, MpiRef, RevocationKey, RevocationKeyClass,
-    Version,
+    mpi, CompressionAlgorithm, Fingerprint, KeyId, KeyVersion, Mpi, MpiRef, RevocationKey,
+    RevocationKeyClass, Version,
 };
 use crate::util::{clone_into_array, packet_length};
 
@@ -154,7 +154,7 @@ fn revocation_key(i: &[u8]) -> IResult<&[u8], SubpacketData> {
             map_res(be_u8, RevocationKeyClass::try_from),
             map(be_u8, PublicKeyAlgorithm::from),
             // TODO: V5 Keys have 32 octets here
-            take(20u8),
+            map_res(take(20u8), |fp| Fingerprint::new(KeyVersion::V4, fp)),
         )),
         |(class, algorithm, fp)| {
             SubpacketData::RevocationKey(RevocationKey::new(class, algorithm, fp))
@@ -164,10 +164,8 @@ fn revocation_key(i: &[u8]) -> IResult<&[u8], SubpacketData> {
 
 /// Parse a notation data subpacket
 /// Ref: https://tools.ietf.org/html/rfc4880.html#section-5.2.3.16
-fn notation_data(i: &[u8]) -> IResult<&[u8], SubpacketData> {
-    // Flags
-    let (i, readable) = map(be_u8, |v| v == 0x80)(i)?;
-    let (i, _) = tag(&[0, 0, 0])(i)?;
+fn notation_data(i: &[u8], is_critical: bool) -> IResult<&[u8], SubpacketData> {
+    let (i, flags) = be_u32(i)?;
     let (i, name_len) = be_u16(i)?;
     let (i, value_len) = be_u16(i)?;
     let (i, name) = map(take(name_len), BString::from)(i)?;
@@ -176,9 +174,11 @@ fn notation_data(i: &[u8]) -> IResult<&[u8], SubpacketData> {
     Ok((
         i,
         SubpacketData::Notation(Notation {
-            readable,
             name,
             value,
+            human_readable: flags & Notation::HUMAN_READABLE != 0,
+            critical: is_critical,
+            other_flags: flags & !Notation::HUMAN_READABLE,
         }),
     ))
 }
@@ -270,10 +270,10 @@ fn embedded_sig(i: &[u8]) -> IResult<&[u8], SubpacketData> {
 /// Parse an issuer subpacket
 /// Ref: https://tools.ietf.org/html/draft-ietf-openpgp-rfc4880bis-05#section-5.2.3.28
 fn issuer_fingerprint(i: &[u8]) -> IResult<&[u8], SubpacketData> {
-    map(
+    map_res(
         pair(map(be_u8, KeyVersion::from), rest),
         |(version, fingerprint)| {
-            SubpacketData::IssuerFingerprint(version, SmallVec::from_slice(fingerprint))
+            Fingerprint::new(version, fingerprint).map(SubpacketData::IssuerFingerprint)
         },
     )(i)
 }
@@ -288,7 +288,12 @@ fn pref_aead_alg(body: &[u8]) -> IResult<&[u8], SubpacketData> {
     Ok((&b""[..], SubpacketData::PreferredAeadAlgorithms(list)))
 }
 
-fn subpacket(typ: SubpacketType, is_critical: bool, body: &[u8]) -> IResult<&[u8], Subpacket> {
+fn subpacket(
+    typ: SubpacketType,
+    is_critical: bool,
+    long_length: bool,
+    body: &[u8],
+) -> IResult<&[u8], Subpacket> {
     use self::SubpacketType::*;
     debug!("parsing subpacket: {:?} {}", typ, hex::encode(body));
 
@@ -303,7 +308,7 @@ fn subpacket(typ: SubpacketType, is_critical: bool, body: &[u8]) -> IResult<&[u8
         PreferredSymmetricAlgorithms => pref_sym_alg(body),
         RevocationKey => revocation_key(body),
         Issuer => issuer(body),
-        Notation => notation_data(body),
+        Notation => notation_data(body, is_critical),
         PreferredHashAlgorithms => pref_hash_alg(body),
         PreferredCompressionAlgorithms => pref_com_alg(body),
         KeyServerPreferences => key_server_prefs(body),
@@ -325,22 +330,45 @@ fn subpacket(typ: SubpacketType, is_critical: bool, body: &[u8]) -> IResult<&[u8
         Other(n) => Ok((body, SubpacketData::Other(n, body.to_vec()))),
     };
 
-    let res = res.map(|(body, data)| (body, Subpacket { is_critical, data }));
-
-    if res.is_err() {
-        warn!("invalid subpacket: {:?} {:?}", typ, res);
+    let mut subpacket = Subpacket {
+        is_critical,
+        data: SubpacketData::Other(typ.as_u8(false), body.to_vec()),
+        long_length,
+    };
+    match res {
+        Ok((_, data)) => {
+            let raw = std::mem::replace(&mut subpacket.data, data);
+            // keep subpackets that would not be written back as they were found as raw data, so
+            // that signatures over them can be verified and serialized unchanged
+            let mut serialized = Vec::with_capacity(body.len());
+            if subpacket.body_to_writer(&mut serialized).is_err() || serialized != body {
+                warn!("non canonical subpacket: {:?}", typ);
+                subpacket.data = raw;
+            }
+        }
+        Err(err) => {
+            warn!("invalid subpacket: {:?} {:?}", typ, err);
+        }
     }
 
-    res
+    Ok((&body[body.len()..], subpacket))
 }
 
 fn subpackets<'a>(i: &'a [u8]) -> IResult<&'a [u8], Vec<Subpacket>> {
     many0(complete(|i: &'a [u8]| {
         // the subpacket length (1, 2, or 5 octets)
+        let long_length = i.first() == Some(&0xFF);
         let (i, len) = packet_length(i)?;
+        if len == 0 {
+            return Err(nom::Err::Error(crate::errors::Error::Message(
+                "empty subpacket".to_string(),
+            )));
+        }
         // the subpacket type (1 octet)
         let (i, typ) = map(be_u8, SubpacketType::from_u8)(i)?;
-        map_parser(take(len - 1), move |b| subpacket(typ.0, typ.1, b))(i)
+        map_parser(take(len - 1), move |b| {
+            subpacket(typ.0, typ.1, long_length, b)
+        })(i)
     }))(i)
 }
 
@@ -484,11 +512,36 @@ fn parse(packet_version: Version) -> impl Fn(&[u8]) -> IResult<&[u8], Signature>
     }
 }
 
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Subpacket {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        struct Raw {
+            #[serde(rename = "type")]
+            typ: SubpacketType,
+            critical: bool,
+            body: String,
+        }
+
+        let raw = Raw::deserialize(deserializer)?;
+        let body = hex::decode(raw.body).map_err(D::Error::custom)?;
+        let (_, subpacket) = subpacket(raw.typ, raw.critical, false, &body)
+            .map_err(|err| D::Error::custom(crate::errors::Error::from(err)))?;
+
+        Ok(subpacket)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::unwrap_used)]
 
     use super::*;
+    use crate::ser::Serialize;
     use crate::{Deserializable, StandaloneSignature};
     use std::io::Cursor;
 
@@ -526,4 +579,106 @@ SW+kj0jFDKC2xb/o8hbkTpwPtsoI
         assert!(rc.is_some());
         assert!(matches!(rc.unwrap(), RevocationCode::Other(0x42)));
     }
+
+    #[test]
+    fn test_subpackets_roundtrip() {
+        let input = [
+            // creation time, with a 5 octet length
+            &[0xFF, 0, 0, 0, 5, 2, 0x55, 0xd2, 0x4a, 0xb2][..],
+            // critical key flags
+            &[2, 0x80 | 27, 0x03],
+            // preferred aead algorithms, with an unknown algorithm
+            &[2, 34, 0x42],
+            // creation time with trailing data
+            &[6, 2, 0x55, 0xd2, 0x4a, 0xb2, 0],
+            // unknown subpacket
+            &[3, 60, 1, 2],
+        ]
+        .concat();
+
+        let (rest, parsed) = subpackets(&input).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(parsed.len(), 5);
+        assert!(parsed[0].long_length);
+        assert!(matches!(
+            parsed[0].data,
+            SubpacketData::SignatureCreationTime(_)
+        ));
+        assert!(parsed[1].is_critical);
+        assert_eq!(parsed[1].data, SubpacketData::KeyFlags([0x03].into()));
+        assert_eq!(parsed[2].data, SubpacketData::Other(34, vec![0x42]));
+        assert_eq!(
+            parsed[3].data,
+            SubpacketData::Other(2, vec![0x55, 0xd2, 0x4a, 0xb2, 0])
+        );
+        assert_eq!(parsed[4].data, SubpacketData::Other(60, vec![1, 2]));
+
+        let mut out = Vec::new();
+        for subpacket in &parsed {
+            subpacket.to_writer(&mut out).unwrap();
+        }
+        assert_eq!(out, input);
+
+        // zero length subpackets are invalid
+        let (_, parsed) = subpackets(&[0, 2][..]).unwrap();
+        assert!(parsed.is_empty());
+    }
+
+    #[test]
+    fn test_notations() {
+        let notation = |flags: [u8; 4], name: &[u8], value: &[u8]| {
+            [
+                &flags[..],
+                &(name.len() as u16).to_be_bytes(),
+                &(value.len() as u16).to_be_bytes(),
+                name,
+                value,
+            ]
+            .concat()
+        };
+        let readable = notation([0x80, 0, 0, 0], b"a@example.com", b"one");
+        let binary = notation([0, 0, 0, 0], b"a@example.com", &[0, 0xFF]);
+        let reserved = notation([0x80, 0, 0x01, 0], b"b@example.com", b"two");
+
+        let mut input = Vec::new();
+        for (critical, body) in [(false, &readable), (true, &binary), (false, &reserved)] {
+            input.push(body.len() as u8 + 1);
+            input.push(if critical { 0x80 | 20 } else { 20 });
+            input.extend_from_slice(body);
+        }
+
+        let (rest, parsed) = subpackets(&input).unwrap();
+        assert!(rest.is_empty());
+        let notations: Vec<_> = parsed
+            .iter()
+            .map(|p| match &p.data {
+                SubpacketData::Notation(n) => n.clone(),
+                other => panic!("unexpected subpacket {other:?}"),
+            })
+            .collect();
+
+        assert_eq!(
+            notations,
+            vec![
+                Notation::new("a@example.com", "one"),
+                Notation::binary("a@example.com", vec![0, 0xFF]).critical(),
+                Notation {
+                    other_flags: 0x0100,
+                    ..Notation::new("b@example.com", "two")
+                },
+            ]
+        );
+
+        let mut out = Vec::new();
+        for subpacket in &parsed {
+            subpacket.to_writer(&mut out).unwrap();
+        }
+        assert_eq!(out, input);
+
+        let mut out = Vec::new();
+        for notation in notations {
+            Subpacket::from(notation).to_writer(&mut out).unwrap();
+        }
+        assert_eq!(out, input);
+    }
 }
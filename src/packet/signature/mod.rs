@@ -1,7 +1,9 @@
 pub mod config;
 pub mod de;
 pub mod ser;
+pub mod subpackets;
 pub mod types;
 
 pub use self::config::*;
+pub use self::subpackets::*;
 pub use self::types::*;
@@ -28,7 +28,7 @@ impl Subpacket {
         u32::try_from(d.num_seconds()).unwrap_or(u32::MAX)
     }
 
-    fn body_to_writer(&self, writer: &mut impl io::Write) -> Result<()> {
+    pub(super) fn body_to_writer(&self, writer: &mut impl io::Write) -> Result<()> {
         match &self.data {
             SubpacketData::SignatureCreationTime(t) => {
                 writer.write_u32::<BigEndian>(t.timestamp() as u32)?;
@@ -79,8 +79,7 @@ impl Subpacket {
                 writer.write_all(server.as_bytes())?;
             }
             SubpacketData::Notation(notation) => {
-                let is_readable = if notation.readable { 0x80 } else { 0 };
-                writer.write_all(&[is_readable, 0, 0, 0])?;
+                writer.write_u32::<BigEndian>(notation.flags())?;
 
                 writer.write_u16::<BigEndian>(notation.name.len() as u16)?;
 
@@ -91,7 +90,7 @@ impl Subpacket {
             }
             SubpacketData::RevocationKey(rev_key) => {
                 writer.write_all(&[rev_key.class as u8, rev_key.algorithm.into()])?;
-                writer.write_all(&rev_key.fingerprint[..])?;
+                writer.write_all(rev_key.fingerprint.as_bytes())?;
             }
             SubpacketData::SignersUserID(body) => {
                 writer.write_all(body.as_ref())?;
@@ -109,9 +108,9 @@ impl Subpacket {
                 let val = u8::from(*is_exportable);
                 writer.write_all(&[val])?;
             }
-            SubpacketData::IssuerFingerprint(version, fp) => {
-                writer.write_all(&[u8::from(*version)])?;
-                writer.write_all(fp)?;
+            SubpacketData::IssuerFingerprint(fp) => {
+                writer.write_all(&[u8::from(fp.version())])?;
+                writer.write_all(fp.as_bytes())?;
             }
             SubpacketData::PreferredAeadAlgorithms(algs) => {
                 writer.write_all(&algs.iter().map(|&alg| alg as u8).collect::<Vec<_>>())?;
@@ -170,7 +169,7 @@ impl Subpacket {
             SubpacketData::TrustSignature(_, _) => 2,
             SubpacketData::RegularExpression(regexp) => regexp.len(),
             SubpacketData::ExportableCertification(_) => 1,
-            SubpacketData::IssuerFingerprint(_, fp) => 1 + fp.len(),
+            SubpacketData::IssuerFingerprint(fp) => 1 + fp.as_bytes().len(),
             SubpacketData::PreferredAeadAlgorithms(algs) => algs.len(),
             SubpacketData::Experimental(_, body) => body.len(),
             SubpacketData::Other(_, body) => body.len(),
@@ -208,7 +207,7 @@ impl Subpacket {
             SubpacketData::TrustSignature(_, _) => SubpacketType::TrustSignature,
             SubpacketData::RegularExpression(_) => SubpacketType::RegularExpression,
             SubpacketData::ExportableCertification(_) => SubpacketType::ExportableCertification,
-            SubpacketData::IssuerFingerprint(_, _) => SubpacketType::IssuerFingerprint,
+            SubpacketData::IssuerFingerprint(_) => SubpacketType::IssuerFingerprint,
             SubpacketData::PreferredAeadAlgorithms(_) => SubpacketType::PreferredAead,
             SubpacketData::Experimental(n, _) => SubpacketType::Experimental(*n),
             SubpacketData::Other(n, _) => SubpacketType::Other(*n),
@@ -219,7 +218,13 @@ impl Subpacket {
 
 impl Serialize for Subpacket {
     fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
-        write_packet_length(1 + self.body_len()?, writer)?;
+        let len = 1 + self.body_len()?;
+        if self.long_length {
+            writer.write_all(&[0xFF])?;
+            writer.write_u32::<BigEndian>(len.try_into()?)?;
+        } else {
+            write_packet_length(len, writer)?;
+        }
         writer.write_all(&[self.typ().as_u8(self.is_critical)])?;
         self.body_to_writer(writer)?;
 
@@ -326,6 +331,27 @@ impl Signature {
     }
 }
 
+/// Serialized as the subpacket type, the critical bit and the hex encoded body, which is
+/// parsed again when deserializing.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Subpacket {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::{Error, SerializeStruct};
+
+        let mut body = Vec::new();
+        self.body_to_writer(&mut body).map_err(S::Error::custom)?;
+
+        let mut state = serializer.serialize_struct("Subpacket", 3)?;
+        state.serialize_field("type", &self.typ())?;
+        state.serialize_field("critical", &self.is_critical)?;
+        state.serialize_field("body", &hex::encode(body))?;
+        state.end()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::unwrap_used)]
@@ -0,0 +1,265 @@
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+use smallvec::SmallVec;
+
+use crate::crypto::hash::HashAlgorithm;
+use crate::crypto::public_key::PublicKeyAlgorithm;
+use crate::crypto::sym::SymmetricKeyAlgorithm;
+use crate::packet::{
+    Features, KeyFlags, Notation, SignatureConfig, SignatureType, Subpacket, SubpacketData,
+};
+use crate::types::{CompressionAlgorithm, KeyTrait, RevocationKey};
+
+/// Builds the hashed and unhashed subpacket areas of a new signature.
+///
+/// Each subpacket is placed in the area it belongs to: the issuer key ID in the unhashed area,
+/// where it only serves as a hint to find the key, and everything else in the hashed area, so
+/// that it is covered by the signature. Setting a subpacket that can only appear once again
+/// replaces it, in its original position, while notations are added in order.
+///
+/// ```
+/// # use pgp::composed::{KeyType, SecretKeyParamsBuilder};
+/// # use pgp::crypto::hash::HashAlgorithm;
+/// # use pgp::packet::{Notation, SignatureType, SubpacketAreas};
+/// # use pgp::types::{KeyTrait, SecretKeyTrait};
+/// # fn main() -> pgp::errors::Result<()> {
+/// # let key = SecretKeyParamsBuilder::default()
+/// #     .key_type(KeyType::EdDSA)
+/// #     .can_sign(true)
+/// #     .primary_user_id("Alice <alice@example.com>".into())
+/// #     .build()
+/// #     .expect("valid key parameters")
+/// #     .generate_signed()?;
+/// let config = SubpacketAreas::for_key(&key)
+///     .signature_expiration(chrono::Duration::days(7))
+///     .notation(Notation::new("ticket@example.com", "1234"))
+///     .into_config(SignatureType::Binary, key.algorithm(), HashAlgorithm::SHA2_256);
+/// let signature = config.sign(&key, String::new, &b"hello"[..])?;
+///
+/// signature.verify(&key.public_key(), &b"hello"[..])?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SubpacketAreas {
+    hashed: Vec<Subpacket>,
+    unhashed: Vec<Subpacket>,
+}
+
+impl SubpacketAreas {
+    /// Creates empty subpacket areas.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates the subpacket areas of a signature by `key`, created now.
+    pub fn for_key(key: &impl KeyTrait) -> Self {
+        SubpacketAreas::new()
+            .issuer_fingerprint(key)
+            .created(Utc::now())
+            .issuer(key)
+    }
+
+    /// Adds `subpacket` to the area it belongs to, replacing a previous subpacket of the same
+    /// type, unless several of them are allowed.
+    pub fn subpacket(mut self, subpacket: Subpacket) -> Self {
+        let area = if is_hashed(&subpacket.data) {
+            &mut self.hashed
+        } else {
+            &mut self.unhashed
+        };
+
+        let existing = if is_repeatable(&subpacket.data) {
+            None
+        } else {
+            let typ = subpacket.typ();
+            area.iter_mut().find(|p| p.typ() == typ)
+        };
+        match existing {
+            Some(existing) => *existing = subpacket,
+            None => area.push(subpacket),
+        }
+
+        self
+    }
+
+    fn regular(self, data: SubpacketData) -> Self {
+        self.subpacket(Subpacket::regular(data))
+    }
+
+    /// Sets the signature creation time, truncated to seconds.
+    pub fn created(self, created: DateTime<Utc>) -> Self {
+        self.regular(SubpacketData::SignatureCreationTime(
+            created.trunc_subsecs(0),
+        ))
+    }
+
+    /// Sets the fingerprint of the issuing key.
+    pub fn issuer_fingerprint(self, key: &impl KeyTrait) -> Self {
+        self.regular(SubpacketData::IssuerFingerprint(key.fingerprint()))
+    }
+
+    /// Sets the key ID of the issuing key.
+    pub fn issuer(self, key: &impl KeyTrait) -> Self {
+        self.regular(SubpacketData::Issuer(key.key_id()))
+    }
+
+    /// Sets how long after its creation the signature expires.
+    pub fn signature_expiration(self, expiration: Duration) -> Self {
+        self.regular(SubpacketData::SignatureExpirationTime(expiration))
+    }
+
+    /// Sets how long after its creation the signed key expires.
+    pub fn key_expiration(self, expiration: Duration) -> Self {
+        self.regular(SubpacketData::KeyExpirationTime(expiration))
+    }
+
+    /// Adds a notation, critical if the notation is.
+    pub fn notation(self, notation: Notation) -> Self {
+        self.subpacket(notation.into())
+    }
+
+    pub fn key_flags(self, flags: KeyFlags) -> Self {
+        self.regular(SubpacketData::KeyFlags(flags.into()))
+    }
+
+    pub fn features(self, features: Features) -> Self {
+        self.regular(SubpacketData::Features(features.into()))
+    }
+
+    pub fn preferred_symmetric_algorithms(
+        self,
+        algorithms: SmallVec<[SymmetricKeyAlgorithm; 8]>,
+    ) -> Self {
+        self.regular(SubpacketData::PreferredSymmetricAlgorithms(algorithms))
+    }
+
+    pub fn preferred_hash_algorithms(self, algorithms: SmallVec<[HashAlgorithm; 8]>) -> Self {
+        self.regular(SubpacketData::PreferredHashAlgorithms(algorithms))
+    }
+
+    pub fn preferred_compression_algorithms(
+        self,
+        algorithms: SmallVec<[CompressionAlgorithm; 8]>,
+    ) -> Self {
+        self.regular(SubpacketData::PreferredCompressionAlgorithms(algorithms))
+    }
+
+    /// Marks the signed User ID as the primary one.
+    pub fn primary_user_id(self) -> Self {
+        self.regular(SubpacketData::IsPrimary(true))
+    }
+
+    /// Adds a key allowed to revoke the signed key.
+    pub fn revocation_key(self, key: RevocationKey) -> Self {
+        self.regular(SubpacketData::RevocationKey(key))
+    }
+
+    pub fn hashed(&self) -> &[Subpacket] {
+        &self.hashed
+    }
+
+    pub fn unhashed(&self) -> &[Subpacket] {
+        &self.unhashed
+    }
+
+    /// Returns the hashed and the unhashed subpackets.
+    pub fn into_parts(self) -> (Vec<Subpacket>, Vec<Subpacket>) {
+        (self.hashed, self.unhashed)
+    }
+
+    /// Creates the configuration for a version 4 signature with these subpackets.
+    pub fn into_config(
+        self,
+        typ: SignatureType,
+        pub_alg: PublicKeyAlgorithm,
+        hash_alg: HashAlgorithm,
+    ) -> SignatureConfig {
+        SignatureConfig::new_v4(
+            Default::default(),
+            typ,
+            pub_alg,
+            hash_alg,
+            self.hashed,
+            self.unhashed,
+        )
+    }
+}
+
+/// Whether the subpacket belongs into the hashed area.
+fn is_hashed(data: &SubpacketData) -> bool {
+    !matches!(data, SubpacketData::Issuer(_))
+}
+
+/// Whether a signature may contain several subpackets of this type.
+fn is_repeatable(data: &SubpacketData) -> bool {
+    matches!(
+        data,
+        SubpacketData::Notation(_)
+            | SubpacketData::RevocationKey(_)
+            | SubpacketData::EmbeddedSignature(_)
+            | SubpacketData::Experimental(..)
+            | SubpacketData::Other(..)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::packet::SubpacketType;
+    use crate::test_util::gen_key;
+    use crate::types::SecretKeyTrait;
+
+    #[test]
+    fn test_subpacket_areas() {
+        let key = gen_key("Alice <alice@example.com>");
+        let created = DateTime::parse_from_rfc3339("2023-11-14T22:13:20.5Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let mut flags = KeyFlags::default();
+        flags.set_sign(true);
+        let areas = SubpacketAreas::for_key(&key)
+            .notation(Notation::new("a@example.com", "one"))
+            .created(created)
+            .key_flags(Default::default())
+            .key_flags(flags)
+            .notation(Notation::binary("a@example.com", vec![0, 1]).critical());
+
+        let hashed: Vec<_> = areas.hashed().iter().map(|p| p.typ()).collect();
+        assert_eq!(
+            hashed,
+            vec![
+                SubpacketType::IssuerFingerprint,
+                SubpacketType::SignatureCreationTime,
+                SubpacketType::Notation,
+                SubpacketType::KeyFlags,
+                SubpacketType::Notation,
+            ]
+        );
+        assert_eq!(
+            areas.hashed()[1].data,
+            SubpacketData::SignatureCreationTime(created.trunc_subsecs(0))
+        );
+        assert_eq!(
+            areas.hashed()[3].data,
+            SubpacketData::KeyFlags(flags.into())
+        );
+        assert!(areas.hashed()[4].is_critical);
+        assert_eq!(
+            areas.unhashed(),
+            &[Subpacket::regular(SubpacketData::Issuer(key.key_id()))]
+        );
+
+        let config = areas.into_config(
+            SignatureType::Binary,
+            key.algorithm(),
+            HashAlgorithm::SHA2_256,
+        );
+        let signature = config.sign(&key, String::new, &b"hello"[..]).unwrap();
+        signature.verify(&key.public_key(), &b"hello"[..]).unwrap();
+        assert_eq!(signature.created(), Some(&created.trunc_subsecs(0)));
+        assert_eq!(signature.notations().len(), 2);
+    }
+}
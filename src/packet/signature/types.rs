@@ -4,7 +4,6 @@ use std::io::Read;
 use bstr::{BStr, BString};
 use byteorder::{BigEndian, ByteOrder};
 use chrono::{DateTime, Duration, Utc};
-use iter_read::IterRead;
 use num_enum::{FromPrimitive, IntoPrimitive, TryFromPrimitive};
 
 use crate::crypto::aead::AeadAlgorithm;
@@ -12,13 +11,11 @@ use crate::crypto::hash::HashAlgorithm;
 use crate::crypto::public_key::PublicKeyAlgorithm;
 use crate::crypto::sym::SymmetricKeyAlgorithm;
 use crate::errors::Result;
-use crate::line_writer::LineBreak;
-use crate::normalize_lines::Normalized;
 use crate::packet::signature::SignatureConfig;
 use crate::packet::PacketTrait;
 use crate::ser::Serialize;
 use crate::types::{
-    self, CompressionAlgorithm, KeyId, KeyVersion, Mpi, PublicKeyTrait, Tag, Version,
+    self, CompressionAlgorithm, Fingerprint, KeyId, Mpi, PublicKeyTrait, Tag, Version,
 };
 use smallvec::SmallVec;
 
@@ -80,12 +77,40 @@ impl Signature {
         self.config.typ()
     }
 
+    /// Whether `other` is the same signature, except for the unhashed subpackets.
+    ///
+    /// The unhashed area is not covered by the signature, anyone can change it without
+    /// invalidating the signature.
+    pub fn eq_ignoring_unhashed(&self, other: &Signature) -> bool {
+        let SignatureConfig {
+            version,
+            typ,
+            pub_alg,
+            hash_alg,
+            unhashed_subpackets: _,
+            hashed_subpackets,
+            created,
+            issuer,
+        } = &self.config;
+
+        self.packet_version == other.packet_version
+            && self.signed_hash_value == other.signed_hash_value
+            && self.signature == other.signature
+            && version == &other.config.version
+            && typ == &other.config.typ
+            && pub_alg == &other.config.pub_alg
+            && hash_alg == &other.config.hash_alg
+            && hashed_subpackets == &other.config.hashed_subpackets
+            && created == &other.config.created
+            && issuer == &other.config.issuer
+    }
+
     /// Does `key` match any issuer or issuer_fingerprint subpacket in `sig`?
     /// If yes, we consider `key` a candidate to verify `sig` against.
     ///
     /// We also consider `key` a match for `sig` by default, if `sig` contains no issuer-related
     /// subpackets.
-    fn match_identity(sig: &Signature, key: &impl PublicKeyTrait) -> bool {
+    pub(crate) fn match_identity(sig: &Signature, key: &impl PublicKeyTrait) -> bool {
         let issuers = sig.issuer();
         let issuer_fps = sig.issuer_fingerprint();
 
@@ -97,7 +122,7 @@ impl Signature {
 
         // Does any issuer or issuer fingerprint subpacket matche the identity of `sig`?
         issuers.iter().any(|&key_id| key_id == &key.key_id())
-            || issuer_fps.iter().any(|&fp| fp == key.fingerprint())
+            || issuer_fps.iter().any(|&fp| fp == &key.fingerprint())
     }
 
     /// Verify this signature.
@@ -113,14 +138,7 @@ impl Signature {
 
         let mut hasher = self.config.hash_alg.new_hasher()?;
 
-        if matches!(self.typ(), SignatureType::Text) {
-            let normalized = Normalized::new(data.bytes().flat_map(|b| b.ok()), LineBreak::Crlf);
-
-            self.config
-                .hash_data_to_sign(&mut *hasher, IterRead::new(normalized))?;
-        } else {
-            self.config.hash_data_to_sign(&mut *hasher, data)?;
-        }
+        self.config.hash_data_to_sign(&mut *hasher, data)?;
         let len = self.config.hash_signature_data(&mut *hasher)?;
         hasher.update(&self.config.trailer(len)?);
 
@@ -134,6 +152,20 @@ impl Signature {
         key.verify_signature(self.config.hash_alg, hash, &self.signature)
     }
 
+    /// Verifies a standalone or timestamp signature, which does not sign any data.
+    pub fn verify_standalone(&self, key: &impl PublicKeyTrait) -> Result<()> {
+        ensure!(
+            matches!(
+                self.typ(),
+                SignatureType::Standalone | SignatureType::Timestamp
+            ),
+            "invalid signature type for a standalone signature: {:?}",
+            self.typ()
+        );
+
+        self.verify(key, std::io::empty())
+    }
+
     /// Verifies a certification signature type.
     pub fn verify_certification(
         &self,
@@ -141,11 +173,22 @@ impl Signature {
         tag: Tag,
         id: &impl Serialize,
     ) -> Result<()> {
-        let key_id = key.key_id();
+        self.verify_third_party_certification(key, key, tag, id)
+    }
+
+    /// Verifies a certification signature type over `id` of `key`, issued by `signing_key`.
+    pub fn verify_third_party_certification(
+        &self,
+        signing_key: &impl PublicKeyTrait,
+        key: &impl PublicKeyTrait,
+        tag: Tag,
+        id: &impl Serialize,
+    ) -> Result<()> {
+        let key_id = signing_key.key_id();
         debug!("verifying certification {:?} {:#?}", key_id, self);
 
         ensure!(
-            Self::match_identity(self, key),
+            Self::match_identity(self, signing_key),
             "verify_certification: No matching issuer or issuer_fingerprint for Key ID: {:?}",
             key_id,
         );
@@ -200,7 +243,7 @@ impl Signature {
             "certification: invalid signed hash value"
         );
 
-        key.verify_signature(self.config.hash_alg, hash, &self.signature)
+        signing_key.verify_signature(self.config.hash_alg, hash, &self.signature)
     }
 
     /// Verifies a key binding (which binds a subkey to the primary key).
@@ -284,12 +327,26 @@ impl Signature {
 
     /// Verifies a direct key signature or a revocation.
     pub fn verify_key(&self, key: &impl PublicKeyTrait) -> Result<()> {
-        debug!("verifying key (revocation): {:#?} - {:#?}", self, key);
+        self.verify_key_third_party(key, key)
+    }
+
+    /// Verifies a direct key signature or a revocation over `key`, issued by `signing_key`.
+    ///
+    /// This is used for revocations by a designated revoker.
+    pub fn verify_key_third_party(
+        &self,
+        signing_key: &impl PublicKeyTrait,
+        key: &impl PublicKeyTrait,
+    ) -> Result<()> {
+        debug!(
+            "verifying key (revocation): {:#?} - {:#?} - {:#?}",
+            self, signing_key, key
+        );
 
         ensure!(
-            Self::match_identity(self, key),
+            Self::match_identity(self, signing_key),
             "verify_key: No matching issuer or issuer_fingerprint for Key ID: {:?}",
-            &key.key_id(),
+            &signing_key.key_id(),
         );
 
         let mut hasher = self.config.hash_alg.new_hasher()?;
@@ -311,7 +368,7 @@ impl Signature {
             "key: invalid signed hash value"
         );
 
-        key.verify_signature(self.config.hash_alg, hash, &self.signature)
+        signing_key.verify_signature(self.config.hash_alg, hash, &self.signature)
     }
 
     /// Returns if the signature is a certification or not.
@@ -333,6 +390,17 @@ impl Signature {
         })
     }
 
+    /// Checks if the signature is expired at the given time, based on its
+    /// Signature Expiration Time. A value of zero means it never expires.
+    pub fn is_expired_at(&self, at: &DateTime<Utc>) -> bool {
+        match (self.created(), self.signature_expiration_time()) {
+            (Some(created), Some(expiration)) if !expiration.is_zero() => {
+                *created + *expiration <= *at
+            }
+            _ => false,
+        }
+    }
+
     pub fn created(&self) -> Option<&DateTime<Utc>> {
         self.config.created()
     }
@@ -341,7 +409,7 @@ impl Signature {
         self.config.issuer()
     }
 
-    pub fn issuer_fingerprint(&self) -> Vec<&[u8]> {
+    pub fn issuer_fingerprint(&self) -> Vec<&Fingerprint> {
         self.config.issuer_fingerprint()
     }
 
@@ -375,14 +443,14 @@ impl Signature {
             .unwrap_or_else(|| &[][..])
     }
 
-    pub fn key_server_prefs(&self) -> &[u8] {
+    pub fn key_server_prefs(&self) -> KeyServerPreferences {
         self.config
             .hashed_subpackets()
             .find_map(|p| match &p.data {
-                SubpacketData::KeyServerPreferences(d) => Some(&d[..]),
+                SubpacketData::KeyServerPreferences(d) => Some(d[..].into()),
                 _ => None,
             })
-            .unwrap_or_else(|| &[][..])
+            .unwrap_or_default()
     }
 
     pub fn key_flags(&self) -> KeyFlags {
@@ -395,14 +463,14 @@ impl Signature {
             .unwrap_or_default()
     }
 
-    pub fn features(&self) -> &[u8] {
+    pub fn features(&self) -> Features {
         self.config
             .hashed_subpackets()
             .find_map(|p| match &p.data {
-                SubpacketData::Features(d) => Some(&d[..]),
+                SubpacketData::Features(d) => Some(d[..].into()),
                 _ => None,
             })
-            .unwrap_or_else(|| &[][..])
+            .unwrap_or_default()
     }
 
     pub fn revocation_reason_code(&self) -> Option<&RevocationCode> {
@@ -440,6 +508,11 @@ impl Signature {
     }
 
     pub fn embedded_signature(&self) -> Option<&Signature> {
+        self.embedded_signatures().next()
+    }
+
+    /// All embedded signatures, in the order they appear.
+    pub fn embedded_signatures(&self) -> impl Iterator<Item = &Signature> {
         // We consider data from both the hashed and unhashed area here, because the embedded
         // signature is inherently cryptographically secured. An attacker can't add a valid
         // embedded signature, canonicalization will remove any invalid embedded signature
@@ -447,7 +520,7 @@ impl Signature {
         self.config
             .hashed_subpackets()
             .chain(self.config.unhashed_subpackets())
-            .find_map(|p| match &p.data {
+            .filter_map(|p| match &p.data {
                 SubpacketData::EmbeddedSignature(d) => Some(&**d),
                 _ => None,
             })
@@ -460,6 +533,7 @@ impl Signature {
         })
     }
 
+    /// The notations in the hashed area, in the order they appear.
     pub fn notations(&self) -> Vec<&Notation> {
         self.config
             .hashed_subpackets()
@@ -470,6 +544,17 @@ impl Signature {
             .collect()
     }
 
+    /// The signature this signature refers to, as its public key algorithm, hash algorithm
+    /// and hash.
+    pub fn signature_target(&self) -> Option<(PublicKeyAlgorithm, HashAlgorithm, &[u8])> {
+        self.config.hashed_subpackets().find_map(|p| match &p.data {
+            SubpacketData::SignatureTarget(pub_alg, hash_alg, hash) => {
+                Some((*pub_alg, *hash_alg, &hash[..]))
+            }
+            _ => None,
+        })
+    }
+
     pub fn revocation_key(&self) -> Option<&types::RevocationKey> {
         self.config.hashed_subpackets().find_map(|p| match &p.data {
             SubpacketData::RevocationKey(d) => Some(d),
@@ -522,6 +607,7 @@ impl Signature {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, FromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum SignatureVersion {
     /// Deprecated
@@ -541,6 +627,7 @@ impl Default for SignatureVersion {
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum SignatureType {
     /// Signature of a binary document.
@@ -637,7 +724,32 @@ pub enum SignatureType {
     ThirdParty = 0x50,
 }
 
+/// The level of verification a certification of a User ID expresses.
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum CertificationType {
+    /// No particular assertion about how well the identity was checked.
+    Generic,
+    /// No verification of the identity was done.
+    Persona,
+    /// Some casual verification of the identity was done.
+    Casual,
+    /// Substantial verification of the identity was done.
+    Positive,
+}
+
+impl From<CertificationType> for SignatureType {
+    fn from(typ: CertificationType) -> Self {
+        match typ {
+            CertificationType::Generic => SignatureType::CertGeneric,
+            CertificationType::Persona => SignatureType::CertPersona,
+            CertificationType::Casual => SignatureType::CertCasual,
+            CertificationType::Positive => SignatureType::CertPositive,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Available signature subpacket types
 pub enum SubpacketType {
     SignatureCreationTime,
@@ -753,6 +865,9 @@ impl SubpacketType {
 pub struct Subpacket {
     pub is_critical: bool,
     pub data: SubpacketData,
+    /// Whether the length is written as 5 octets, even if it fits into fewer, to preserve the
+    /// encoding of parsed subpackets.
+    pub long_length: bool,
 }
 
 impl Subpacket {
@@ -761,6 +876,7 @@ impl Subpacket {
         Subpacket {
             is_critical: false,
             data,
+            long_length: false,
         }
     }
 
@@ -769,6 +885,7 @@ impl Subpacket {
         Subpacket {
             is_critical: true,
             data,
+            long_length: false,
         }
     }
 }
@@ -805,7 +922,7 @@ pub enum SubpacketData {
     TrustSignature(u8, u8),
     RegularExpression(BString),
     ExportableCertification(bool),
-    IssuerFingerprint(KeyVersion, SmallVec<[u8; 20]>),
+    IssuerFingerprint(Fingerprint),
     PreferredAeadAlgorithms(SmallVec<[AeadAlgorithm; 2]>),
     Experimental(u8, SmallVec<[u8; 2]>),
     Other(u8, Vec<u8>),
@@ -836,21 +953,161 @@ impl<'a> From<&'a [u8]> for KeyFlags {
     }
 }
 
+impl From<Vec<u8>> for KeyFlags {
+    fn from(other: Vec<u8>) -> Self {
+        other[..].into()
+    }
+}
+
 impl From<KeyFlags> for SmallVec<[u8; 1]> {
     fn from(flags: KeyFlags) -> Self {
         smallvec![flags.0]
     }
 }
 
+bitfield! {
+    /// Features supported by the implementation of the key holder.
+    #[derive(Default, PartialEq, Eq, Copy, Clone)]
+    pub struct Features(u8);
+    impl Debug;
+
+    /// Symmetrically Encrypted Integrity Protected Data, version 1 (Modification Detection).
+    pub seipd_v1, set_seipd_v1: 0;
+    /// AEAD Encrypted Data packets.
+    pub aead, set_aead: 1;
+    /// Version 5 public keys.
+    pub v5_keys, set_v5_keys: 2;
+    /// Symmetrically Encrypted Integrity Protected Data, version 2.
+    pub seipd_v2, set_seipd_v2: 3;
+}
+
+impl<'a> From<&'a [u8]> for Features {
+    fn from(other: &'a [u8]) -> Self {
+        if other.is_empty() {
+            Default::default()
+        } else {
+            Features(other[0])
+        }
+    }
+}
+
+impl From<Vec<u8>> for Features {
+    fn from(other: Vec<u8>) -> Self {
+        other[..].into()
+    }
+}
+
+impl From<Features> for SmallVec<[u8; 1]> {
+    fn from(features: Features) -> Self {
+        smallvec![features.0]
+    }
+}
+
+bitfield! {
+    /// Preferences of the key holder about how the key is handled on a key server.
+    #[derive(Default, PartialEq, Eq, Copy, Clone)]
+    pub struct KeyServerPreferences(u8);
+    impl Debug;
+
+    /// Only the key holder, or the key server administrator, may modify the key.
+    pub no_modify, set_no_modify: 7;
+}
+
+impl<'a> From<&'a [u8]> for KeyServerPreferences {
+    fn from(other: &'a [u8]) -> Self {
+        if other.is_empty() {
+            Default::default()
+        } else {
+            KeyServerPreferences(other[0])
+        }
+    }
+}
+
+impl From<Vec<u8>> for KeyServerPreferences {
+    fn from(other: Vec<u8>) -> Self {
+        other[..].into()
+    }
+}
+
+impl From<KeyServerPreferences> for SmallVec<[u8; 4]> {
+    fn from(prefs: KeyServerPreferences) -> Self {
+        smallvec![prefs.0]
+    }
+}
+
+/// A notation, a name-value pair attached to a signature.
+///
+/// Signatures can carry several notations, including several with the same name, which are
+/// kept in the order they were found.
+/// Ref: https://tools.ietf.org/html/rfc4880.html#section-5.2.3.16
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Notation {
-    pub readable: bool,
     pub name: BString,
+    /// The value, text if `human_readable` is set, otherwise arbitrary bytes.
     pub value: BString,
+    pub human_readable: bool,
+    /// Whether the notation subpacket is marked critical, i.e. must be understood to consider
+    /// the signature valid.
+    pub critical: bool,
+    /// The flags other than the human-readable flag, which are reserved. Kept so that parsed
+    /// signatures serialize unchanged.
+    pub other_flags: u32,
+}
+
+impl Notation {
+    /// The human-readable notation flag.
+    pub const HUMAN_READABLE: u32 = 0x8000_0000;
+
+    /// Creates a human-readable, non critical notation.
+    pub fn new(name: impl Into<BString>, value: impl Into<BString>) -> Self {
+        Notation {
+            name: name.into(),
+            value: value.into(),
+            human_readable: true,
+            critical: false,
+            other_flags: 0,
+        }
+    }
+
+    /// Creates a non critical notation with a binary value.
+    pub fn binary(name: impl Into<BString>, value: impl Into<BString>) -> Self {
+        Notation {
+            human_readable: false,
+            ..Notation::new(name, value)
+        }
+    }
+
+    /// Marks the notation as critical.
+    pub fn critical(mut self) -> Self {
+        self.critical = true;
+        self
+    }
+
+    /// The flags as they are written in the subpacket.
+    pub fn flags(&self) -> u32 {
+        let readable = if self.human_readable {
+            Self::HUMAN_READABLE
+        } else {
+            0
+        };
+        readable | (self.other_flags & !Self::HUMAN_READABLE)
+    }
+}
+
+impl From<Notation> for Subpacket {
+    /// Wraps the notation into a subpacket, critical if the notation is.
+    fn from(notation: Notation) -> Self {
+        Subpacket {
+            is_critical: notation.critical,
+            data: SubpacketData::Notation(notation),
+            long_length: false,
+        }
+    }
 }
 
 /// Codes for revocation reasons
 #[derive(Debug, PartialEq, Eq, Copy, Clone, FromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum RevocationCode {
     /// No reason specified (key revocations or cert revocations)
@@ -948,6 +1205,35 @@ mod tests {
         assert_eq!(flags.0, 0x80);
     }
 
+    #[test]
+    fn test_features() {
+        let features: Features = Default::default();
+        assert_eq!(features.0, 0x00);
+
+        let features: Features = vec![0x09].into();
+        assert!(features.seipd_v1());
+        assert!(!features.aead());
+        assert!(features.seipd_v2());
+
+        let mut features = Features::default();
+        features.set_seipd_v1(true);
+        features.set_aead(true);
+        let wire: SmallVec<[u8; 1]> = features.into();
+        assert_eq!(&wire[..], &[0x03]);
+    }
+
+    #[test]
+    fn test_key_server_preferences() {
+        let prefs: KeyServerPreferences = (&[][..]).into();
+        assert!(!prefs.no_modify());
+
+        let mut prefs = KeyServerPreferences::default();
+        prefs.set_no_modify(true);
+        let wire: SmallVec<[u8; 4]> = prefs.into();
+        assert_eq!(&wire[..], &[0x80]);
+        assert_eq!(KeyServerPreferences::from(wire.to_vec()), prefs);
+    }
+
     #[test]
     fn test_critical() {
         use SubpacketType::*;
@@ -15,8 +15,8 @@ use crate::de::Deserialize;
 use crate::errors::{Error, IResult, Result};
 use crate::packet::packet_sum::Packet;
 use crate::packet::{
-    CompressedData, LiteralData, Marker, ModDetectionCode, OnePassSignature, PublicKey,
-    PublicKeyEncryptedSessionKey, PublicSubkey, SecretKey, SecretSubkey, Signature,
+    CompressedData, Experimental, LiteralData, Marker, ModDetectionCode, OnePassSignature,
+    PublicKey, PublicKeyEncryptedSessionKey, PublicSubkey, SecretKey, SecretSubkey, Signature,
     SymEncryptedData, SymEncryptedProtectedData, SymKeyEncryptedSessionKey, Trust, UserAttribute,
     UserId,
 };
@@ -157,6 +157,10 @@ pub fn parser(i: &[u8]) -> IResult<&[u8], (Version, Tag, PacketLength, ParseResu
     Ok((i, (head.0, head.1, head.2, body)))
 }
 
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(?tag, ?ver, len = body.len()))
+)]
 pub fn body_parser(ver: Version, tag: Tag, body: &[u8]) -> Result<Packet> {
     let res: Result<Packet> = match tag {
         Tag::PublicKeyEncryptedSessionKey => {
@@ -182,11 +186,14 @@ pub fn body_parser(ver: Version, tag: Tag, body: &[u8]) -> Result<Packet> {
             SymEncryptedProtectedData::from_slice(ver, body).map(Into::into)
         }
         Tag::ModDetectionCode => ModDetectionCode::from_slice(ver, body).map(Into::into),
+        Tag::Private60 | Tag::Private61 | Tag::Private62 | Tag::Private63 => {
+            Experimental::from_slice(ver, tag, body).map(Into::into)
+        }
     };
 
     match res {
         Ok(res) => Ok(res),
-        Err(Error::Incomplete(n)) => Err(Error::Incomplete(n)),
+        Err(Error::Truncated { needed }) => Err(Error::Truncated { needed }),
         Err(err) => {
             warn!("invalid packet: {:?} {:?}\n{}", err, tag, hex::encode(body));
             Err(Error::InvalidPacketContent(Box::new(err)))
@@ -13,19 +13,30 @@ use crate::types::{Tag, Version};
 #[derive(Debug, PartialEq, Eq)]
 pub struct Trust {
     packet_version: Version,
+    data: Vec<u8>,
 }
 
 impl Trust {
     /// Parses a `Trust` packet from the given slice.
-    pub fn from_slice(packet_version: Version, _: &[u8]) -> Result<Self> {
+    pub fn from_slice(packet_version: Version, input: &[u8]) -> Result<Self> {
         warn!("Trust packet detected, ignoring");
 
-        Ok(Trust { packet_version })
+        Ok(Trust {
+            packet_version,
+            data: input.to_vec(),
+        })
+    }
+
+    /// The implementation defined trust data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
     }
 }
 
 impl Serialize for Trust {
-    fn to_writer<W: io::Write>(&self, _writer: &mut W) -> Result<()> {
+    fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.data)?;
+
         Ok(())
     }
 }
@@ -4,7 +4,7 @@ use chrono::{SubsecRound, Utc};
 
 use byteorder::{LittleEndian, WriteBytesExt};
 use nom::bytes::streaming::take;
-use nom::combinator::{map, map_parser, rest};
+use nom::combinator::{map, map_opt, map_parser, rest};
 use nom::multi::length_data;
 use nom::number::streaming::{be_u8, le_u16};
 use nom::sequence::pair;
@@ -12,7 +12,7 @@ use nom::sequence::pair;
 use crate::errors::{IResult, Result};
 use crate::packet::{PacketTrait, Signature, SignatureConfigBuilder, SignatureType, Subpacket};
 use crate::ser::Serialize;
-use crate::types::{SecretKeyTrait, SignedUserAttribute, Tag, Version};
+use crate::types::{PassphraseProvider, SecretKeyTrait, SignedUserAttribute, Tag, Version};
 use crate::util::{packet_length, write_packet_length};
 
 use super::SubpacketData;
@@ -33,7 +33,26 @@ pub enum UserAttribute {
     },
 }
 
+/// Version 1 of the image header.
+const IMAGE_HEADER_V1: u8 = 1;
+/// Image encoding format JPEG.
+const IMAGE_ENCODING_JPEG: u8 = 1;
+
 impl UserAttribute {
+    /// Creates an image attribute, containing the given JPEG image, e.g. for a photo ID.
+    pub fn new_jpeg(data: Vec<u8>) -> Self {
+        // version 1 image header: version, encoding and 12 reserved octets
+        let mut header = vec![0u8; 14];
+        header[0] = IMAGE_HEADER_V1;
+        header[1] = IMAGE_ENCODING_JPEG;
+
+        UserAttribute::Image {
+            packet_version: Default::default(),
+            header,
+            data,
+        }
+    }
+
     /// Parses a `UserAttribute` packet from the given slice.
     pub fn from_slice(packet_version: Version, input: &[u8]) -> Result<Self> {
         let (_, pk) = parse(packet_version)(input)?;
@@ -48,11 +67,29 @@ impl UserAttribute {
         }
     }
 
+    /// Returns the JPEG image, if this is an image attribute with a version 1 header.
+    pub fn jpeg(&self) -> Option<&[u8]> {
+        match self {
+            UserAttribute::Image { header, data, .. }
+                if header.len() >= 2
+                    && header[0] == IMAGE_HEADER_V1
+                    && header[1] == IMAGE_ENCODING_JPEG =>
+            {
+                Some(data)
+            }
+            _ => None,
+        }
+    }
+
     pub fn packet_len(&self) -> usize {
         match self {
-            UserAttribute::Image { ref data, .. } => {
-                // typ + image header + data length
-                1 + 16 + data.len()
+            UserAttribute::Image {
+                ref header,
+                ref data,
+                ..
+            } => {
+                // typ + image header length + image header + data length
+                1 + 2 + header.len() + data.len()
             }
             UserAttribute::Unknown { ref data, .. } => {
                 // typ + data length
@@ -63,7 +100,7 @@ impl UserAttribute {
 
     pub fn sign<F>(&self, key: &impl SecretKeyTrait, key_pw: F) -> Result<SignedUserAttribute>
     where
-        F: FnOnce() -> String,
+        F: PassphraseProvider,
     {
         let config = SignatureConfigBuilder::default()
             .typ(SignatureType::CertGeneric)
@@ -104,7 +141,7 @@ fn image(packet_version: Version) -> impl Fn(&[u8]) -> IResult<&[u8], UserAttrib
         map(
             pair(
                 // little endian, for historical reasons..
-                length_data(map(le_u16, |l| l - 2)),
+                length_data(map_opt(le_u16, |l| l.checked_sub(2))),
                 // the actual image is the rest
                 rest,
             ),
@@ -8,7 +8,7 @@ use crate::packet::{
     PacketTrait, Signature, SignatureConfigBuilder, SignatureType, Subpacket, SubpacketData,
 };
 use crate::ser::Serialize;
-use crate::types::{SecretKeyTrait, SignedUser, Tag, Version};
+use crate::types::{PassphraseProvider, SecretKeyTrait, SignedUser, Tag, Version};
 
 /// User ID Packet
 /// https://tools.ietf.org/html/rfc4880.html#section-5.11
@@ -38,9 +38,46 @@ impl UserId {
         self.id.as_ref()
     }
 
+    /// Splits the User ID into its conventional parts, `Name (Comment) <email>`.
+    ///
+    /// Invalid UTF-8 is replaced, and parts that are missing or malformed are `None`.
+    pub fn components(&self) -> UserIdComponents {
+        let id = String::from_utf8_lossy(&self.id);
+        let id = id.trim();
+
+        let (rest, email) = match id.rfind('<') {
+            Some(start) if id.ends_with('>') => {
+                let email = id[start + 1..id.len() - 1].trim();
+                (&id[..start], Some(email).filter(|e| is_addr_spec(e)))
+            }
+            _ if is_addr_spec(id) => ("", Some(id)),
+            _ => (id, None),
+        };
+
+        let rest = rest.trim();
+        let (name, comment) = match rest.rfind('(') {
+            Some(start) if rest.ends_with(')') => {
+                (&rest[..start], Some(rest[start + 1..rest.len() - 1].trim()))
+            }
+            _ => (rest, None),
+        };
+        let name = name.trim().trim_matches('"').trim();
+
+        UserIdComponents {
+            name: Some(name).filter(|n| !n.is_empty()).map(Into::into),
+            comment: comment.filter(|c| !c.is_empty()).map(Into::into),
+            email: email.map(Into::into),
+        }
+    }
+
+    /// The email address of this User ID in lower case, suitable for lookups.
+    pub fn email_normalized(&self) -> Option<String> {
+        self.components().email.map(|email| email.to_lowercase())
+    }
+
     pub fn sign<F>(&self, key: &impl SecretKeyTrait, key_pw: F) -> Result<SignedUser>
     where
-        F: FnOnce() -> String,
+        F: PassphraseProvider,
     {
         let config = SignatureConfigBuilder::default()
             .typ(SignatureType::CertGeneric)
@@ -63,6 +100,30 @@ impl UserId {
     }
 }
 
+/// The conventional parts of a User ID, see [`UserId::components`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UserIdComponents {
+    pub name: Option<String>,
+    pub comment: Option<String>,
+    pub email: Option<String>,
+}
+
+/// Checks that `s` looks like an RFC 2822 `addr-spec`, i.e. `local@domain`.
+fn is_addr_spec(s: &str) -> bool {
+    let Some((local, domain)) = s.rsplit_once('@') else {
+        return false;
+    };
+    let valid = |part: &str| {
+        !part.is_empty()
+            && !part
+                .chars()
+                .any(|c| c.is_whitespace() || c.is_control() || "<>()[],;:\"".contains(c))
+    };
+
+    valid(local) && valid(domain) && !domain.starts_with('.') && !domain.ends_with('.')
+}
+
 impl Serialize for UserId {
     fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
         writer.write_all(&self.id)?;
@@ -86,3 +147,73 @@ impl PacketTrait for UserId {
         Tag::UserId
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    fn components(id: &str) -> UserIdComponents {
+        UserId::from_str(Version::New, id).components()
+    }
+
+    #[test]
+    fn test_components() {
+        assert_eq!(
+            components("Alice Lovelace (work) <alice@example.com>"),
+            UserIdComponents {
+                name: Some("Alice Lovelace".into()),
+                comment: Some("work".into()),
+                email: Some("alice@example.com".into()),
+            }
+        );
+        assert_eq!(
+            components("\"Lovelace, Alice\" <alice@example.com>"),
+            UserIdComponents {
+                name: Some("Lovelace, Alice".into()),
+                comment: None,
+                email: Some("alice@example.com".into()),
+            }
+        );
+        assert_eq!(
+            components("alice@example.com"),
+            UserIdComponents {
+                name: None,
+                comment: None,
+                email: Some("alice@example.com".into()),
+            }
+        );
+        assert_eq!(
+            components("<alice@example.com>"),
+            UserIdComponents {
+                name: None,
+                comment: None,
+                email: Some("alice@example.com".into()),
+            }
+        );
+        assert_eq!(
+            components("Alice (no email)"),
+            UserIdComponents {
+                name: Some("Alice".into()),
+                comment: Some("no email".into()),
+                email: None,
+            }
+        );
+        assert_eq!(components("Alice <not an email>").email, None);
+    }
+
+    #[test]
+    fn test_components_invalid_utf8() {
+        let id = UserId::from_slice(Version::New, b"Al\xffce <alice@example.com>").unwrap();
+        let components = id.components();
+        assert_eq!(components.name, Some("Al\u{fffd}ce".into()));
+        assert_eq!(components.email, Some("alice@example.com".into()));
+    }
+
+    #[test]
+    fn test_email_normalized() {
+        let id = UserId::from_str(Version::New, "Alice <Alice@Example.COM>");
+        assert_eq!(id.email_normalized(), Some("alice@example.com".into()));
+    }
+}
@@ -0,0 +1,233 @@
+//! # Policy
+//!
+//! Restricts the algorithms and packet types that are accepted when verifying, decrypting
+//! and encrypting messages.
+//!
+//! ```rust
+//! use pgp::crypto::hash::HashAlgorithm;
+//! use pgp::policy::PolicyBuilder;
+//!
+//! let policy = PolicyBuilder::default()
+//!     .hash_algorithms(vec![HashAlgorithm::SHA2_256, HashAlgorithm::SHA2_512])
+//!     .min_rsa_bits(3072)
+//!     .build()
+//!     .unwrap();
+//!
+//! assert!(policy.check_hash(HashAlgorithm::SHA2_512, None).is_ok());
+//! assert!(policy.check_hash(HashAlgorithm::SHA1, None).is_err());
+//! ```
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use crate::composed::Edata;
+use crate::crypto::hash::HashAlgorithm;
+use crate::crypto::sym::SymmetricKeyAlgorithm;
+use crate::errors::{Error, IntegrityError, Result};
+use crate::packet::Signature;
+use crate::types::PublicParams;
+
+/// The algorithms and packet types an application accepts.
+///
+/// The default only accepts algorithms that are considered secure today, with the exception
+/// of SHA-1 for signatures made before 2013.
+#[derive(Debug, Clone, PartialEq, Eq, Builder)]
+#[builder(default)]
+pub struct Policy {
+    /// Hash algorithms accepted in signatures.
+    hash_algorithms: Vec<HashAlgorithm>,
+    /// Symmetric algorithms accepted for encryption and decryption.
+    symmetric_algorithms: Vec<SymmetricKeyAlgorithm>,
+    /// The minimum size of RSA keys, in bits.
+    min_rsa_bits: usize,
+    /// If SHA-1 is accepted, only signatures created before this time are.
+    /// `None` accepts SHA-1 signatures regardless of their age.
+    sha1_cutoff: Option<DateTime<Utc>>,
+    /// Rejects encrypted data without integrity protection. Data with a Modification
+    /// Detection Code that does not match is always rejected.
+    require_mdc: bool,
+    /// How far in the future of the reference time signatures may have been created, to
+    /// allow for clocks that are slightly off.
+    clock_skew_tolerance: Duration,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            hash_algorithms: vec![
+                HashAlgorithm::SHA1,
+                HashAlgorithm::SHA2_224,
+                HashAlgorithm::SHA2_256,
+                HashAlgorithm::SHA2_384,
+                HashAlgorithm::SHA2_512,
+                HashAlgorithm::SHA3_256,
+                HashAlgorithm::SHA3_512,
+            ],
+            symmetric_algorithms: vec![
+                SymmetricKeyAlgorithm::AES128,
+                SymmetricKeyAlgorithm::AES192,
+                SymmetricKeyAlgorithm::AES256,
+                SymmetricKeyAlgorithm::Twofish,
+                SymmetricKeyAlgorithm::Camellia128,
+                SymmetricKeyAlgorithm::Camellia192,
+                SymmetricKeyAlgorithm::Camellia256,
+            ],
+            min_rsa_bits: 2048,
+            sha1_cutoff: Utc.with_ymd_and_hms(2013, 1, 1, 0, 0, 0).single(),
+            require_mdc: true,
+            clock_skew_tolerance: Duration::zero(),
+        }
+    }
+}
+
+impl Policy {
+    /// Checks the hash algorithm of a signature created at `created`.
+    pub fn check_hash(&self, hash: HashAlgorithm, created: Option<&DateTime<Utc>>) -> Result<()> {
+        ensure!(
+            self.hash_algorithms.contains(&hash),
+            "hash algorithm {:?} rejected by policy",
+            hash
+        );
+        if hash == HashAlgorithm::SHA1 {
+            if let Some(cutoff) = self.sha1_cutoff {
+                ensure!(
+                    created.is_some_and(|created| *created < cutoff),
+                    "SHA-1 signatures created after {} rejected by policy",
+                    cutoff
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that a signature created at `created` already existed at the reference time `at`,
+    /// within the clock skew tolerance.
+    pub fn check_creation_time(
+        &self,
+        created: Option<&DateTime<Utc>>,
+        at: &DateTime<Utc>,
+    ) -> Result<()> {
+        if let Some(created) = created {
+            let latest = at
+                .checked_add_signed(self.clock_skew_tolerance)
+                .unwrap_or(*at);
+            ensure!(
+                *created <= latest,
+                "signature created in the future, at {}",
+                created
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn check_signature(&self, signature: &Signature) -> Result<()> {
+        self.check_hash(signature.config.hash_alg, signature.created())
+    }
+
+    pub fn check_symmetric_algorithm(&self, alg: SymmetricKeyAlgorithm) -> Result<()> {
+        ensure!(
+            self.symmetric_algorithms.contains(&alg),
+            "symmetric algorithm {:?} rejected by policy",
+            alg
+        );
+
+        Ok(())
+    }
+
+    /// Checks the public parameters of a signing or encryption key.
+    pub fn check_public_params(&self, params: &PublicParams) -> Result<()> {
+        if let PublicParams::RSA { n, .. } = params {
+            let bytes = n.as_bytes();
+            let bits = bytes
+                .first()
+                .map_or(0, |&b| bytes.len() * 8 - b.leading_zeros() as usize);
+            ensure!(
+                bits >= self.min_rsa_bits,
+                "RSA key size of {} bits rejected by policy",
+                bits
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Checks the encrypted data packet of a message.
+    pub fn check_edata(&self, edata: &Edata) -> Result<()> {
+        if self.require_mdc && matches!(edata, Edata::SymEncryptedData(_)) {
+            return Err(Error::IntegrityCheckFailed(IntegrityError::MdcMissing));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn test_default_policy() {
+        let policy = Policy::default();
+        let before = Utc.with_ymd_and_hms(2010, 1, 1, 0, 0, 0).unwrap();
+        let after = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+
+        policy
+            .check_hash(HashAlgorithm::SHA2_256, Some(&after))
+            .unwrap();
+        policy
+            .check_hash(HashAlgorithm::SHA1, Some(&before))
+            .unwrap();
+        assert!(policy
+            .check_hash(HashAlgorithm::SHA1, Some(&after))
+            .is_err());
+        assert!(policy.check_hash(HashAlgorithm::SHA1, None).is_err());
+        assert!(policy
+            .check_hash(HashAlgorithm::MD5, Some(&before))
+            .is_err());
+
+        policy
+            .check_symmetric_algorithm(SymmetricKeyAlgorithm::AES256)
+            .unwrap();
+        assert!(policy
+            .check_symmetric_algorithm(SymmetricKeyAlgorithm::CAST5)
+            .is_err());
+
+        policy.check_creation_time(Some(&before), &after).unwrap();
+        policy.check_creation_time(None, &after).unwrap();
+        assert!(policy.check_creation_time(Some(&after), &before).is_err());
+    }
+
+    #[test]
+    fn test_custom_policy() {
+        let policy = PolicyBuilder::default()
+            .sha1_cutoff(None)
+            .min_rsa_bits(3072)
+            .build()
+            .unwrap();
+        policy.check_hash(HashAlgorithm::SHA1, None).unwrap();
+
+        let at = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let policy = PolicyBuilder::default()
+            .clock_skew_tolerance(Duration::minutes(5))
+            .min_rsa_bits(3072)
+            .build()
+            .unwrap();
+        policy
+            .check_creation_time(Some(&(at + Duration::minutes(5))), &at)
+            .unwrap();
+        assert!(policy
+            .check_creation_time(Some(&(at + Duration::minutes(6))), &at)
+            .is_err());
+
+        let rsa = |bytes: usize| PublicParams::RSA {
+            n: vec![0x80; bytes].into(),
+            e: vec![1, 0, 1].into(),
+        };
+        policy.check_public_params(&rsa(384)).unwrap();
+        assert!(policy.check_public_params(&rsa(256)).is_err());
+        Policy::default().check_public_params(&rsa(256)).unwrap();
+    }
+}
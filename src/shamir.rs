@@ -0,0 +1,294 @@
+//! # Shamir secret sharing
+//!
+//! Splits the secret key material of a key into `n` shares, of which any `threshold` restore
+//! the secret key, while fewer reveal nothing about it. This is the secret-sharing mechanism
+//! the `shared` key flag (`0x10`) of RFC 4880 refers to.
+//!
+//! The shared secret is the [paperkey](crate::formats::paperkey) data of the key, i.e. the
+//! secret parts of its key packets, which stay encrypted if the key is protected with a
+//! passphrase. Together with the public key, the combined shares restore the secret key
+//! packets.
+//!
+//! The sharing is done bytewise over GF(2^8), using the polynomial of AES.
+//!
+//! ```no_run
+//! # use pgp::composed::{Deserializable, SignedPublicKey, SignedSecretKey};
+//! # use pgp::shamir::{self, Share};
+//! # fn main() -> pgp::errors::Result<()> {
+//! # let (key, _) = SignedSecretKey::from_string("")?;
+//! let shares = shamir::split(&key, 3, 5)?;
+//! let shares: Vec<Vec<u8>> = shares.iter().map(Share::to_bytes).collect();
+//!
+//! // later, with any three of the shares and the public key
+//! let public = SignedPublicKey::from(key);
+//! let shares = shares[2..]
+//!     .iter()
+//!     .map(|share| Share::from_bytes(share))
+//!     .collect::<pgp::errors::Result<Vec<_>>>()?;
+//! let key = shamir::combine(&public, &shares)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use rand::{thread_rng, CryptoRng, Rng};
+use zeroize::Zeroizing;
+
+use crate::composed::{SignedPublicKey, SignedSecretKey};
+use crate::errors::Result;
+use crate::formats::paperkey;
+
+/// The version of the encoding of a share.
+const SHARE_VERSION: u8 = 1;
+
+/// A single share of a secret key.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Share {
+    threshold: u8,
+    index: u8,
+    data: Zeroizing<Vec<u8>>,
+}
+
+impl Share {
+    /// The number of shares needed to restore the key.
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    /// The index of this share, starting at 1.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// Encodes the share as a version octet, the threshold, the index and the share data.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![SHARE_VERSION, self.threshold, self.index];
+        out.extend_from_slice(&self.data);
+        out
+    }
+
+    /// Decodes a share encoded with [`to_bytes`](Share::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        match bytes {
+            [SHARE_VERSION, threshold, index, data @ ..] => {
+                ensure!(*threshold >= 2, "invalid share threshold {}", threshold);
+                ensure!(*index != 0, "invalid share index");
+                ensure!(!data.is_empty(), "empty share");
+
+                Ok(Share {
+                    threshold: *threshold,
+                    index: *index,
+                    data: Zeroizing::new(data.to_vec()),
+                })
+            }
+            [version, ..] => unsupported_err!("share version {}", version),
+            [] => bail!("empty share"),
+        }
+    }
+}
+
+impl fmt::Debug for Share {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Share")
+            .field("threshold", &self.threshold)
+            .field("index", &self.index)
+            .field("data", &"[..]")
+            .finish()
+    }
+}
+
+/// Splits the secret key material of `key` into `shares` shares, any `threshold` of which
+/// restore it with [`combine`].
+pub fn split(key: &SignedSecretKey, threshold: u8, shares: u8) -> Result<Vec<Share>> {
+    let mut rng = thread_rng();
+    split_with_rng(&mut rng, key, threshold, shares)
+}
+
+/// Same as [`split`], using the given RNG.
+pub fn split_with_rng<R: CryptoRng + Rng>(
+    rng: &mut R,
+    key: &SignedSecretKey,
+    threshold: u8,
+    shares: u8,
+) -> Result<Vec<Share>> {
+    ensure!(threshold >= 2, "the threshold must be at least 2");
+    ensure!(
+        shares >= threshold,
+        "the number of shares must be at least the threshold"
+    );
+
+    let secret = paperkey::to_raw(key)?;
+    let mut result: Vec<_> = (1..=shares)
+        .map(|index| Share {
+            threshold,
+            index,
+            data: Zeroizing::new(Vec::with_capacity(secret.len())),
+        })
+        .collect();
+
+    // a random polynomial of degree `threshold - 1` for each byte, with the byte as the
+    // constant term
+    let mut coefficients = Zeroizing::new(vec![0u8; threshold as usize]);
+    for byte in secret.iter() {
+        coefficients[0] = *byte;
+        rng.fill(&mut coefficients[1..]);
+
+        for share in &mut result {
+            let value = coefficients
+                .iter()
+                .rev()
+                .fold(0, |acc, coefficient| gf_mul(acc, share.index) ^ coefficient);
+            share.data.push(value);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Restores the secret key from the public key `public` and at least as many `shares` as the
+/// threshold they were created with.
+///
+/// Shares of different splits can not be combined, this is detected as an invalid result in
+/// most cases.
+pub fn combine(public: &SignedPublicKey, shares: &[Share]) -> Result<SignedSecretKey> {
+    let first = shares.first().ok_or_else(|| format_err!("no shares"))?;
+    let threshold = first.threshold as usize;
+    ensure!(
+        shares.len() >= threshold,
+        "{} shares are needed, but only {} given",
+        threshold,
+        shares.len()
+    );
+    let shares = &shares[..threshold];
+    for (i, share) in shares.iter().enumerate() {
+        ensure_eq!(share.threshold, first.threshold, "inconsistent shares");
+        ensure_eq!(share.data.len(), first.data.len(), "inconsistent shares");
+        ensure!(
+            shares[..i].iter().all(|other| other.index != share.index),
+            "duplicate share {}",
+            share.index
+        );
+    }
+
+    // the Lagrange basis polynomials, evaluated at 0
+    let basis: Vec<u8> = shares
+        .iter()
+        .map(|share| {
+            shares
+                .iter()
+                .filter(|other| other.index != share.index)
+                .fold(1, |acc, other| {
+                    gf_mul(acc, gf_div(other.index, other.index ^ share.index))
+                })
+        })
+        .collect();
+
+    let secret: Zeroizing<Vec<u8>> = Zeroizing::new(
+        (0..first.data.len())
+            .map(|i| {
+                shares
+                    .iter()
+                    .zip(&basis)
+                    .fold(0, |acc, (share, basis)| acc ^ gf_mul(share.data[i], *basis))
+            })
+            .collect(),
+    );
+
+    paperkey::from_raw(public, &secret)
+}
+
+/// Multiplication in GF(2^8), reduced by the polynomial x^8 + x^4 + x^3 + x + 1, without
+/// branches depending on the values.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    for _ in 0..8 {
+        product ^= a & (b & 1).wrapping_neg();
+        let carry = (a >> 7).wrapping_neg();
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+/// Division in GF(2^8), using a^-1 = a^254.
+fn gf_div(a: u8, b: u8) -> u8 {
+    let mut inverse = 1;
+    let mut power = b;
+    for _ in 0..7 {
+        power = gf_mul(power, power);
+        inverse = gf_mul(inverse, power);
+    }
+    gf_mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::composed::{KeyType, SubkeyParamsBuilder};
+    use crate::test_util::key_params;
+
+    #[test]
+    fn test_gf() {
+        // example from FIPS 197, section 4.2
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        assert_eq!(gf_mul(0x57, 0x13), 0xfe);
+
+        for a in 1..=255 {
+            assert_eq!(gf_mul(gf_div(1, a), a), 1, "{}", a);
+            assert_eq!(gf_div(a, a), 1, "{}", a);
+        }
+    }
+
+    #[test]
+    fn test_split_combine() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let key = key_params("Alice <alice@example.com>")
+            .passphrase(Some("hello".into()))
+            .subkey(
+                SubkeyParamsBuilder::default()
+                    .key_type(KeyType::ECDH)
+                    .can_encrypt(true)
+                    .passphrase(Some("hello".into()))
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+            .generate_signed_with_rng(&mut rng)
+            .unwrap();
+        let public = SignedPublicKey::from(key.clone());
+
+        let shares = split_with_rng(&mut rng, &key, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+        let shares: Vec<_> = shares
+            .iter()
+            .map(|share| Share::from_bytes(&share.to_bytes()).unwrap())
+            .collect();
+
+        for subset in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let subset: Vec<_> = subset.iter().map(|i| shares[*i].clone()).collect();
+            assert_eq!(combine(&public, &subset).unwrap(), key);
+        }
+        assert_eq!(combine(&public, &shares).unwrap(), key);
+
+        // too few shares
+        assert!(combine(&public, &shares[..2]).is_err());
+        // duplicate shares
+        let duplicate = [shares[0].clone(), shares[1].clone(), shares[0].clone()];
+        assert!(combine(&public, &duplicate).is_err());
+        // shares of different splits
+        let other = split_with_rng(&mut rng, &key, 3, 5).unwrap();
+        let mixed = [shares[0].clone(), shares[1].clone(), other[2].clone()];
+        assert!(combine(&public, &mixed).is_err());
+
+        // invalid parameters
+        assert!(split_with_rng(&mut rng, &key, 1, 5).is_err());
+        assert!(split_with_rng(&mut rng, &key, 3, 2).is_err());
+    }
+}
@@ -0,0 +1,445 @@
+//! # ssh-agent
+//!
+//! A client for ssh-agent, speaking the
+//! [SSH agent protocol](https://datatracker.ietf.org/doc/html/draft-miller-ssh-agent), to make
+//! OpenPGP signatures with keys held by an SSH agent.
+//!
+//! The agent hashes the data it is asked to sign itself, so only keys whose signatures are
+//! made over the OpenPGP digest as is can be used. This holds for Ed25519 keys, which sign the
+//! digest as their message. RSA and ECDSA keys in the agent would sign a hash of the digest,
+//! which OpenPGP implementations can not verify, so they are rejected.
+//!
+//! An [`AgentKey`] pairs a public key with the agent holding its secret key, and implements
+//! [`Signer`].
+//!
+//! ```rust,no_run
+//! # #[cfg(unix)]
+//! # fn main() -> pgp::errors::Result<()> {
+//! use pgp::crypto::hash::HashAlgorithm;
+//! use pgp::ssh_agent::SshAgent;
+//! use pgp::types::ExternalKey;
+//! use pgp::{Deserializable, Message, SignedPublicKey};
+//!
+//! let (cert, _headers) = SignedPublicKey::from_string(&std::fs::read_to_string("key.asc")?)?;
+//! let agent = SshAgent::connect_env()?;
+//! let key = agent.key(&cert.primary_key, cert.primary_key.public_params())?;
+//!
+//! let msg = Message::new_literal("", "hello").sign(
+//!     &ExternalKey(key),
+//!     String::new,
+//!     HashAlgorithm::SHA2_256,
+//! )?;
+//! # Ok(())
+//! # }
+//! # #[cfg(not(unix))]
+//! # fn main() {}
+//! ```
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use rand::{CryptoRng, Rng};
+
+use crate::crypto::ecc_curve::ECCCurve;
+use crate::crypto::hash::HashAlgorithm;
+use crate::crypto::public_key::PublicKeyAlgorithm;
+use crate::errors::Result;
+use crate::formats::ssh::{self, write_string, WireReader};
+use crate::types::{Fingerprint, KeyId, KeyTrait, Mpi, PublicKeyTrait, PublicParams, Signer};
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+
+/// Agent messages are limited to 256 KiB by OpenSSH.
+const MAX_MESSAGE_LEN: usize = 256 * 1024;
+
+/// A key held by the agent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// The public key, in the SSH wire format.
+    pub blob: Vec<u8>,
+    pub comment: String,
+}
+
+/// A connection to ssh-agent.
+pub struct SshAgent<S> {
+    conn: RefCell<S>,
+}
+
+impl<S> fmt::Debug for SshAgent<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SshAgent").finish_non_exhaustive()
+    }
+}
+
+#[cfg(unix)]
+impl SshAgent<std::os::unix::net::UnixStream> {
+    /// Connects to the agent listening on the socket at `path`.
+    pub fn connect(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        Ok(Self::new(std::os::unix::net::UnixStream::connect(path)?))
+    }
+
+    /// Connects to the agent of the current session, whose socket is set in `SSH_AUTH_SOCK`.
+    pub fn connect_env() -> Result<Self> {
+        let path = std::env::var_os("SSH_AUTH_SOCK")
+            .ok_or_else(|| format_err!("SSH_AUTH_SOCK not set"))?;
+        Self::connect(path)
+    }
+}
+
+impl<S: Read + Write> SshAgent<S> {
+    /// Starts a session on `stream`, which is connected to the agent.
+    pub fn new(stream: S) -> Self {
+        SshAgent {
+            conn: RefCell::new(stream),
+        }
+    }
+
+    /// Lists the keys held by the agent.
+    pub fn identities(&self) -> Result<Vec<Identity>> {
+        let response = self.request(SSH_AGENTC_REQUEST_IDENTITIES, &[])?;
+        let mut reader = WireReader(&response);
+        ensure_eq!(
+            reader.byte()?,
+            SSH_AGENT_IDENTITIES_ANSWER,
+            "unexpected response from ssh-agent"
+        );
+
+        let count = reader.u32()?;
+        let mut identities = Vec::new();
+        for _ in 0..count {
+            identities.push(Identity {
+                blob: reader.string()?.to_vec(),
+                comment: String::from_utf8_lossy(reader.string()?).into_owned(),
+            });
+        }
+
+        Ok(identities)
+    }
+
+    /// Looks up the secret key for `key`, with the public parameters `params`.
+    pub fn key<K: PublicKeyTrait>(
+        &self,
+        key: K,
+        params: &PublicParams,
+    ) -> Result<AgentKey<'_, S, K>> {
+        let blob = public_key_blob(params)?;
+        ensure!(
+            self.identities()?.iter().any(|id| id.blob == blob),
+            "ssh-agent does not hold the secret key {}",
+            hex::encode_upper(key.fingerprint())
+        );
+
+        Ok(AgentKey {
+            agent: self,
+            key,
+            blob,
+        })
+    }
+
+    /// Asks the agent to sign `data` with the key `blob`, returning the signature in the SSH
+    /// wire format.
+    pub fn sign_data(&self, blob: &[u8], data: &[u8], flags: u32) -> Result<Vec<u8>> {
+        let mut request = Vec::new();
+        write_string(&mut request, blob);
+        write_string(&mut request, data);
+        request.extend_from_slice(&flags.to_be_bytes());
+
+        let response = self.request(SSH_AGENTC_SIGN_REQUEST, &request)?;
+        let mut reader = WireReader(&response);
+        ensure_eq!(
+            reader.byte()?,
+            SSH_AGENT_SIGN_RESPONSE,
+            "unexpected response from ssh-agent"
+        );
+
+        Ok(reader.string()?.to_vec())
+    }
+
+    /// Signs `digest` with the Ed25519 key `blob`.
+    fn sign_ed25519(&self, blob: &[u8], digest: &[u8]) -> Result<Vec<Mpi>> {
+        let signature = self.sign_data(blob, digest, 0)?;
+        let mut reader = WireReader(&signature);
+        ensure_eq!(
+            reader.string()?,
+            b"ssh-ed25519",
+            "unexpected signature type from ssh-agent"
+        );
+        let sig = reader.string()?;
+        ensure_eq!(sig.len(), 64, "invalid signature from ssh-agent");
+
+        Ok(vec![
+            Mpi::from_raw_slice(&sig[..32]),
+            Mpi::from_raw_slice(&sig[32..]),
+        ])
+    }
+
+    /// Sends a request, and reads the response, failing if the agent refused it.
+    fn request(&self, kind: u8, contents: &[u8]) -> Result<Vec<u8>> {
+        let mut conn = self.conn.borrow_mut();
+
+        let len = u32::try_from(contents.len() + 1)?;
+        let mut message = len.to_be_bytes().to_vec();
+        message.push(kind);
+        message.extend_from_slice(contents);
+        conn.write_all(&message)?;
+        conn.flush()?;
+
+        let mut len = [0u8; 4];
+        conn.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        ensure!(
+            len > 0 && len <= MAX_MESSAGE_LEN,
+            "invalid message length from ssh-agent"
+        );
+
+        let mut response = vec![0u8; len];
+        conn.read_exact(&mut response)?;
+        ensure!(
+            response[0] != SSH_AGENT_FAILURE,
+            "ssh-agent refused the request"
+        );
+
+        Ok(response)
+    }
+}
+
+/// A public key, whose secret key is held by ssh-agent.
+///
+/// Wrap it in an [`ExternalKey`](crate::types::ExternalKey) to sign with it.
+pub struct AgentKey<'a, S, K> {
+    agent: &'a SshAgent<S>,
+    key: K,
+    blob: Vec<u8>,
+}
+
+impl<'a, S, K: fmt::Debug> fmt::Debug for AgentKey<'a, S, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AgentKey").field("key", &self.key).finish()
+    }
+}
+
+impl<'a, S, K: Clone> Clone for AgentKey<'a, S, K> {
+    fn clone(&self) -> Self {
+        AgentKey {
+            agent: self.agent,
+            key: self.key.clone(),
+            blob: self.blob.clone(),
+        }
+    }
+}
+
+impl<'a, S, K> AgentKey<'a, S, K> {
+    /// The public key, in the SSH wire format.
+    pub fn blob(&self) -> &[u8] {
+        &self.blob
+    }
+
+    pub fn public_key(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<'a, S, K: KeyTrait> KeyTrait for AgentKey<'a, S, K> {
+    fn fingerprint(&self) -> Fingerprint {
+        self.key.fingerprint()
+    }
+
+    fn key_id(&self) -> KeyId {
+        self.key.key_id()
+    }
+
+    fn algorithm(&self) -> PublicKeyAlgorithm {
+        self.key.algorithm()
+    }
+}
+
+impl<'a, S, K: PublicKeyTrait> PublicKeyTrait for AgentKey<'a, S, K> {
+    fn verify_signature(&self, hash: HashAlgorithm, data: &[u8], sig: &[Mpi]) -> Result<()> {
+        self.key.verify_signature(hash, data, sig)
+    }
+
+    fn encrypt<R: CryptoRng + Rng>(&self, rng: &mut R, plain: &[u8]) -> Result<Vec<Mpi>> {
+        self.key.encrypt(rng, plain)
+    }
+
+    fn to_writer_old(&self, writer: &mut impl io::Write) -> Result<()> {
+        self.key.to_writer_old(writer)
+    }
+}
+
+impl<'a, S: Read + Write, K: PublicKeyTrait> Signer for AgentKey<'a, S, K> {
+    fn sign(&self, _hash: HashAlgorithm, digest: &[u8]) -> Result<Vec<Mpi>> {
+        self.agent.sign_ed25519(&self.blob, digest)
+    }
+}
+
+/// Encodes the public key with the parameters `params` in the SSH wire format, as the agent
+/// lists it.
+fn public_key_blob(params: &PublicParams) -> Result<Vec<u8>> {
+    match params {
+        PublicParams::EdDSA {
+            curve: ECCCurve::Ed25519,
+            ..
+        } => ssh::public_key_blob(params),
+        // the agent hashes the data itself, so the signature would not be over the digest
+        PublicParams::RSA { .. } | PublicParams::ECDSA(_) => unsupported_err!(
+            "signing with {} keys in ssh-agent, only Ed25519 keys can make OpenPGP signatures",
+            params_name(params)
+        ),
+        _ => unsupported_err!("signing with {} keys in ssh-agent", params_name(params)),
+    }
+}
+
+fn params_name(params: &PublicParams) -> &'static str {
+    match params {
+        PublicParams::RSA { .. } => "RSA",
+        PublicParams::DSA { .. } => "DSA",
+        PublicParams::ECDSA(_) => "ECDSA",
+        PublicParams::ECDH { .. } => "ECDH",
+        PublicParams::Elgamal { .. } => "Elgamal",
+        PublicParams::EdDSA { .. } => "EdDSA",
+        PublicParams::Unknown { .. } => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::composed::KeyType;
+    use crate::test_util::{gen_key, key_params};
+    use crate::types::SecretKeyTrait;
+
+    /// Answers with scripted responses, and records what the client sent.
+    struct MockAgent {
+        responses: Cursor<Vec<u8>>,
+        sent: Vec<u8>,
+    }
+
+    impl MockAgent {
+        fn new(responses: &[Vec<u8>]) -> Self {
+            MockAgent {
+                responses: Cursor::new(
+                    responses
+                        .iter()
+                        .flat_map(|r| [&(r.len() as u32).to_be_bytes()[..], r].concat())
+                        .collect(),
+                ),
+                sent: Vec::new(),
+            }
+        }
+    }
+
+    impl Read for MockAgent {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.responses.read(buf)
+        }
+    }
+
+    impl Write for MockAgent {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.sent.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn identities_answer(blobs: &[&[u8]]) -> Vec<u8> {
+        let mut answer = vec![SSH_AGENT_IDENTITIES_ANSWER];
+        answer.extend_from_slice(&(blobs.len() as u32).to_be_bytes());
+        for blob in blobs {
+            write_string(&mut answer, blob);
+            write_string(&mut answer, b"alice@example.com");
+        }
+        answer
+    }
+
+    #[test]
+    fn test_sign() {
+        let key = gen_key("Alice <alice@example.com>");
+        let public = key.primary_key.public_key();
+        let blob = public_key_blob(public.public_params()).unwrap();
+        assert_eq!(&blob[..15], b"\x00\x00\x00\x0bssh-ed25519");
+
+        // sign the same digest with the secret key, as the agent would
+        let digest = HashAlgorithm::SHA2_256.digest(b"hello").unwrap();
+        let mpis = key
+            .create_signature(String::new, HashAlgorithm::SHA2_256, &digest)
+            .unwrap();
+        let mut signature = Vec::new();
+        write_string(&mut signature, b"ssh-ed25519");
+        let mut sig = [0u8; 64];
+        sig[32 - mpis[0].len()..32].copy_from_slice(&mpis[0]);
+        sig[64 - mpis[1].len()..].copy_from_slice(&mpis[1]);
+        write_string(&mut signature, &sig);
+        let mut sign_response = vec![SSH_AGENT_SIGN_RESPONSE];
+        write_string(&mut sign_response, &signature);
+
+        let agent = SshAgent::new(MockAgent::new(&[
+            identities_answer(&[b"other", &blob]),
+            sign_response,
+        ]));
+        let agent_key = agent.key(&public, public.public_params()).unwrap();
+
+        let sig = agent_key.sign(HashAlgorithm::SHA2_256, &digest).unwrap();
+        assert_eq!(sig, mpis);
+        public
+            .verify_signature(HashAlgorithm::SHA2_256, &digest, &sig)
+            .unwrap();
+
+        let mut sign_request = vec![SSH_AGENTC_SIGN_REQUEST];
+        write_string(&mut sign_request, &blob);
+        write_string(&mut sign_request, &digest);
+        sign_request.extend_from_slice(&[0; 4]);
+        assert_eq!(
+            agent.conn.into_inner().sent,
+            [
+                &[0, 0, 0, 1, SSH_AGENTC_REQUEST_IDENTITIES][..],
+                &(sign_request.len() as u32).to_be_bytes(),
+                &sign_request,
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let key = gen_key("Alice <alice@example.com>");
+        let public = key.primary_key.public_key();
+
+        // the key is not in the agent
+        let agent = SshAgent::new(MockAgent::new(&[identities_answer(&[b"other"])]));
+        assert!(agent.key(&public, public.public_params()).is_err());
+
+        // the agent refuses to sign
+        let blob = public_key_blob(public.public_params()).unwrap();
+        let agent = SshAgent::new(MockAgent::new(&[
+            identities_answer(&[&blob]),
+            vec![SSH_AGENT_FAILURE],
+        ]));
+        let agent_key = agent.key(&public, public.public_params()).unwrap();
+        assert!(agent_key.sign(HashAlgorithm::SHA2_256, &[0; 32]).is_err());
+
+        // RSA signatures are made over a hash of the data
+        let key = key_params("Alice <alice@example.com>")
+            .key_type(KeyType::Rsa(2048))
+            .build()
+            .unwrap()
+            .generate_signed()
+            .unwrap();
+        let public = key.primary_key.public_key();
+        let agent = SshAgent::new(MockAgent::new(&[]));
+        let err = agent.key(&public, public.public_params()).unwrap_err();
+        assert!(err.to_string().contains("only Ed25519"), "{}", err);
+    }
+}
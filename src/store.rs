@@ -0,0 +1,320 @@
+//! # Store
+//!
+//! A simple certificate store, backed by a directory.
+//!
+//! Certificates are stored by the fingerprint of their primary key, and indexed by the email
+//! addresses of their User IDs:
+//!
+//! ```text
+//! <root>/certs/<fingerprint>   the binary encoded certificate
+//! <root>/emails/<hex(email)>   the fingerprints of all certificates with this email
+//! <root>/lock                  exists while the store is being updated, if locking is enabled
+//! ```
+//!
+//! All files are replaced atomically, so readers never see partially written data.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::composed::signed_key::is_self_certified;
+use crate::composed::{Deserializable, SignedPublicKey};
+use crate::errors::Result;
+use crate::ser::Serialize;
+use crate::types::{Fingerprint, KeyTrait};
+
+const CERTS_DIR: &str = "certs";
+const EMAILS_DIR: &str = "emails";
+const LOCK_FILE: &str = "lock";
+
+/// How long to wait for another process to release the lock.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A directory-backed certificate store.
+#[derive(Debug, Clone)]
+pub struct CertStore {
+    root: PathBuf,
+    locking: bool,
+}
+
+impl CertStore {
+    /// Opens the store at `root`, creating the directory layout if needed.
+    pub fn open(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(root.join(CERTS_DIR))?;
+        fs::create_dir_all(root.join(EMAILS_DIR))?;
+
+        Ok(CertStore {
+            root,
+            locking: false,
+        })
+    }
+
+    /// Enables locking the store during updates, for stores that are shared between processes.
+    pub fn with_locking(mut self, locking: bool) -> Self {
+        self.locking = locking;
+        self
+    }
+
+    /// Stores `key`, merging it with the already stored copy, if any.
+    ///
+    /// The signatures of `key` are verified first. Only the email addresses of User IDs
+    /// that are bound by a valid self-certification are indexed.
+    ///
+    /// Returns the certificate as it is stored now.
+    pub fn insert(&self, key: SignedPublicKey) -> Result<SignedPublicKey> {
+        key.verify()?;
+        let _lock = self.lock()?;
+
+        let fingerprint = key.fingerprint();
+        let key = match self.get(&fingerprint)? {
+            Some(existing) => existing.merge(key)?,
+            None => key,
+        };
+        write_atomic(&self.cert_path(&fingerprint), &key.to_bytes()?)?;
+
+        let line = format!("{:x}", fingerprint);
+        for email in key
+            .details
+            .users
+            .iter()
+            .filter(|user| is_self_certified(user, &key.primary_key))
+            .filter_map(|user| user.id.email_normalized())
+        {
+            let path = self.email_path(&email);
+            let mut index = read_optional(&path)?
+                .map(|data| String::from_utf8_lossy(&data).into_owned())
+                .unwrap_or_default();
+            if !index.lines().any(|l| l == line) {
+                index.push_str(&line);
+                index.push('\n');
+                write_atomic(&path, index.as_bytes())?;
+            }
+        }
+
+        Ok(key)
+    }
+
+    /// Reads the certificate with the given primary key fingerprint.
+    pub fn get(&self, fingerprint: &Fingerprint) -> Result<Option<SignedPublicKey>> {
+        match read_optional(&self.cert_path(fingerprint))? {
+            Some(data) => Ok(Some(SignedPublicKey::from_bytes(&data[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads all certificates with a User ID for the given email address, ignoring case.
+    pub fn get_by_email(&self, email: &str) -> Result<Vec<SignedPublicKey>> {
+        let Some(index) = read_optional(&self.email_path(&email.to_lowercase()))? else {
+            return Ok(Vec::new());
+        };
+
+        let mut keys = Vec::new();
+        for line in String::from_utf8_lossy(&index).lines() {
+            if let Some(key) = self.get(&Fingerprint::from_hex(line)?)? {
+                keys.push(key);
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Lists the fingerprints of all stored certificates.
+    pub fn fingerprints(&self) -> Result<Vec<Fingerprint>> {
+        let mut fingerprints = Vec::new();
+        for entry in fs::read_dir(self.root.join(CERTS_DIR))? {
+            let name = entry?.file_name();
+            // skips temporary files of interrupted updates
+            if let Some(fingerprint) = name.to_str().and_then(|n| Fingerprint::from_hex(n).ok()) {
+                fingerprints.push(fingerprint);
+            }
+        }
+
+        Ok(fingerprints)
+    }
+
+    fn cert_path(&self, fingerprint: &Fingerprint) -> PathBuf {
+        self.root.join(CERTS_DIR).join(format!("{:x}", fingerprint))
+    }
+
+    fn email_path(&self, email: &str) -> PathBuf {
+        // hex encoded, as emails may contain characters that are not valid in file names
+        self.root.join(EMAILS_DIR).join(hex::encode(email))
+    }
+
+    fn lock(&self) -> Result<Option<StoreLock>> {
+        if !self.locking {
+            return Ok(None);
+        }
+
+        let path = self.root.join(LOCK_FILE);
+        let start = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(Some(StoreLock { path })),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    ensure!(
+                        start.elapsed() < LOCK_TIMEOUT,
+                        "store is locked: {}",
+                        path.display()
+                    );
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+/// Holds the lock of a store, until it is dropped.
+struct StoreLock {
+    path: PathBuf,
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            warn!("failed to release lock {}: {:?}", self.path.display(), err);
+        }
+    }
+}
+
+pub(crate) fn read_optional(path: &Path) -> Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Replaces the file at `path` with `data`, by writing to a temporary file first.
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    // unique per process and call, so concurrent writers never share a temporary file
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(
+        ".tmp-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let tmp = PathBuf::from(tmp);
+
+    let mut file = File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::packet::UserId;
+    use crate::test_util::{gen_key, gen_public_key, temp_dir};
+    use crate::types::SignedUser;
+
+    #[test]
+    fn test_store() {
+        let dir = temp_dir("store-insert");
+        let store = CertStore::open(&dir).unwrap().with_locking(true);
+
+        let alice = gen_public_key("Alice <Alice@example.com>");
+        let bob = gen_public_key("Bob <bob@example.com>");
+        assert_eq!(store.insert(alice.clone()).unwrap(), alice);
+        store.insert(bob.clone()).unwrap();
+
+        assert_eq!(
+            store.get(&alice.fingerprint()).unwrap(),
+            Some(alice.clone())
+        );
+        assert_eq!(
+            store.get_by_email("alice@EXAMPLE.com").unwrap(),
+            vec![alice]
+        );
+        assert_eq!(
+            store.get_by_email("bob@example.com").unwrap(),
+            vec![bob.clone()]
+        );
+        assert!(store.get_by_email("carol@example.com").unwrap().is_empty());
+
+        let mut fingerprints = store.fingerprints().unwrap();
+        fingerprints.sort_by_key(|fp| fp.to_vec());
+        assert_eq!(fingerprints.len(), 2);
+        assert!(fingerprints.contains(&bob.fingerprint()));
+
+        // the lock is released after each update
+        assert!(!dir.join(LOCK_FILE).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_store_merge() {
+        let dir = temp_dir("store-merge");
+        let store = CertStore::open(&dir).unwrap();
+
+        let secret = gen_key("Alice <alice@example.com>");
+        let key: SignedPublicKey = secret.clone().into();
+        store.insert(key.clone()).unwrap();
+
+        let other = secret
+            .add_user_id("Alice <alice@work.example.com>", String::new)
+            .unwrap();
+        let merged = store.insert(other.into()).unwrap();
+        assert_eq!(merged.details.users.len(), 2);
+        assert_eq!(store.get(&key.fingerprint()).unwrap(), Some(merged.clone()));
+        assert_eq!(
+            store.get_by_email("alice@work.example.com").unwrap(),
+            vec![merged]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_store_unverified() {
+        let dir = temp_dir("store-unverified");
+        let store = CertStore::open(&dir).unwrap();
+
+        let mut key = gen_public_key("Alice <alice@example.com>");
+        let bob = gen_public_key("Bob <bob@example.com>");
+
+        // a User ID without any self-certification
+        let mut forged = key.clone();
+        forged.details.users.push(SignedUser::new(
+            UserId::from_str(Default::default(), "CEO <ceo@example.com>"),
+            Vec::new(),
+        ));
+        assert!(store.insert(forged).is_err());
+
+        // a User ID with the self-certification of another key
+        key.details.users.push(bob.details.users[0].clone());
+        assert!(store.insert(key.clone()).is_err());
+
+        assert!(store.get(&key.fingerprint()).unwrap().is_none());
+        assert!(store.get_by_email("ceo@example.com").unwrap().is_empty());
+        assert!(store.get_by_email("bob@example.com").unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_store_locked() {
+        let dir = temp_dir("store-locked");
+        let store = CertStore::open(&dir).unwrap().with_locking(true);
+
+        let lock = store.lock().unwrap();
+        assert!(lock.is_some());
+        assert!(dir.join(LOCK_FILE).exists());
+        drop(lock);
+        assert!(!dir.join(LOCK_FILE).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
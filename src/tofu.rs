@@ -0,0 +1,314 @@
+//! # Trust on first use
+//!
+//! Remembers which certificates were seen for an email address, so that applications can warn
+//! when a correspondent's key changes, like messengers do.
+//!
+//! The first certificate that makes a valid signature for an email address is bound to it.
+//! Later signatures by the same certificate are [`Known`](TrustEvent::Known), signatures by a
+//! different certificate with the same email address are a [`Conflict`](TrustEvent::Conflict).
+//! What to do about a conflict, e.g. asking the user to compare fingerprints, is up to the
+//! application.
+//!
+//! The bindings are kept in a [`TrustStore`], [`FileTrustStore`] is a simple directory-backed
+//! implementation:
+//!
+//! ```text
+//! <root>/<hex(email)>   one line per bound certificate: "<fingerprint> <first seen>"
+//! ```
+
+use chrono::{DateTime, Utc};
+
+use crate::composed::{Signer, VerificationResult};
+use crate::errors::Result;
+use crate::types::{Fingerprint, KeyTrait};
+
+/// A certificate bound to an email address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    fingerprint: Fingerprint,
+    first_seen: DateTime<Utc>,
+}
+
+impl Binding {
+    pub fn new(fingerprint: Fingerprint, first_seen: DateTime<Utc>) -> Self {
+        Binding {
+            fingerprint,
+            first_seen,
+        }
+    }
+
+    /// The fingerprint of the primary key of the certificate.
+    pub fn fingerprint(&self) -> &Fingerprint {
+        &self.fingerprint
+    }
+
+    /// When the certificate was first seen for the email address.
+    pub fn first_seen(&self) -> &DateTime<Utc> {
+        &self.first_seen
+    }
+}
+
+/// The outcome of observing a certificate for an email address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrustEvent {
+    /// No certificate was bound to the email address before, this one is now.
+    FirstSeen,
+    /// The certificate was already bound to the email address.
+    Known(Binding),
+    /// Other certificates were already bound to the email address. The certificate is bound
+    /// to it as well now, so that the conflict is reported only once.
+    Conflict(Vec<Binding>),
+}
+
+impl TrustEvent {
+    pub fn is_conflict(&self) -> bool {
+        matches!(self, TrustEvent::Conflict(_))
+    }
+}
+
+/// Storage of the bindings between email addresses and certificates.
+///
+/// Email addresses are passed in lower case, as returned by
+/// [`UserId::email_normalized`](crate::packet::UserId::email_normalized).
+pub trait TrustStore {
+    /// All certificates bound to `email`, in the order they were first seen.
+    fn bindings(&self, email: &str) -> Result<Vec<Binding>>;
+
+    /// Binds the certificate of `binding` to `email`.
+    fn record(&self, email: &str, binding: Binding) -> Result<()>;
+
+    /// Records that the certificate `fingerprint` was seen for `email` at `at`, and reports
+    /// whether that is expected.
+    fn observe(
+        &self,
+        email: &str,
+        fingerprint: &Fingerprint,
+        at: DateTime<Utc>,
+    ) -> Result<TrustEvent> {
+        let bindings = self.bindings(email)?;
+        if let Some(binding) = bindings.iter().find(|b| &b.fingerprint == fingerprint) {
+            return Ok(TrustEvent::Known(binding.clone()));
+        }
+
+        self.record(email, Binding::new(fingerprint.clone(), at))?;
+        if bindings.is_empty() {
+            Ok(TrustEvent::FirstSeen)
+        } else {
+            Ok(TrustEvent::Conflict(bindings))
+        }
+    }
+}
+
+/// Observes the certificate of `signer` for each email address of its valid User IDs.
+///
+/// Returns the email addresses with their events.
+pub fn observe_signer(
+    store: &(impl TrustStore + ?Sized),
+    signer: &Signer<'_>,
+    at: DateTime<Utc>,
+) -> Result<Vec<(String, TrustEvent)>> {
+    let cert = signer.cert();
+    let fingerprint = cert.fingerprint();
+
+    let mut events: Vec<(String, TrustEvent)> = Vec::new();
+    for user in &cert.details.users {
+        // User IDs without a valid self-signature are not claimed by the key holder
+        if user.verify(&cert.primary_key).is_err() {
+            continue;
+        }
+        let Some(email) = user.id.email_normalized() else {
+            continue;
+        };
+        if events.iter().any(|(e, _)| e == &email) {
+            continue;
+        }
+
+        let event = store.observe(&email, &fingerprint, at)?;
+        events.push((email, event));
+    }
+
+    Ok(events)
+}
+
+/// Observes the signers of all valid signatures in `results`, see [`observe_signer`].
+///
+/// Invalid signatures are skipped, they do not bind anything.
+pub fn observe_results(
+    store: &(impl TrustStore + ?Sized),
+    results: &mut [VerificationResult<'_>],
+    at: DateTime<Utc>,
+) -> Result<()> {
+    for result in results.iter_mut().filter(|r| r.is_valid()) {
+        if let Some(signer) = result.signer().copied() {
+            result.set_trust(observe_signer(store, &signer, at)?);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::file::FileTrustStore;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod file {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use chrono::{DateTime, Utc};
+
+    use super::{Binding, TrustStore};
+    use crate::errors::Result;
+    use crate::store::{read_optional, write_atomic};
+    use crate::types::Fingerprint;
+
+    /// A directory-backed [`TrustStore`].
+    ///
+    /// Updates are atomic, but not locked, so concurrent updates of the same email address
+    /// may lose bindings.
+    #[derive(Debug, Clone)]
+    pub struct FileTrustStore {
+        root: PathBuf,
+    }
+
+    impl FileTrustStore {
+        /// Opens the store at `root`, creating the directory if needed.
+        pub fn open(root: impl AsRef<Path>) -> Result<Self> {
+            let root = root.as_ref().to_path_buf();
+            fs::create_dir_all(&root)?;
+
+            Ok(FileTrustStore { root })
+        }
+
+        fn path(&self, email: &str) -> PathBuf {
+            // hex encoded, as emails may contain characters that are not valid in file names
+            self.root.join(hex::encode(email))
+        }
+    }
+
+    impl TrustStore for FileTrustStore {
+        fn bindings(&self, email: &str) -> Result<Vec<Binding>> {
+            let Some(data) = read_optional(&self.path(email))? else {
+                return Ok(Vec::new());
+            };
+
+            let mut bindings = Vec::new();
+            for line in String::from_utf8_lossy(&data).lines() {
+                let (fingerprint, first_seen) = line
+                    .split_once(' ')
+                    .ok_or_else(|| format_err!("invalid trust store line {:?}", line))?;
+                let first_seen = DateTime::parse_from_rfc3339(first_seen)
+                    .map_err(|err| format_err!("invalid trust store time: {}", err))?;
+                bindings.push(Binding::new(
+                    Fingerprint::from_hex(fingerprint)?,
+                    first_seen.with_timezone(&Utc),
+                ));
+            }
+
+            Ok(bindings)
+        }
+
+        fn record(&self, email: &str, binding: Binding) -> Result<()> {
+            let path = self.path(email);
+            let mut data = read_optional(&path)?.unwrap_or_default();
+            data.extend_from_slice(
+                format!(
+                    "{:x} {}\n",
+                    binding.fingerprint,
+                    binding.first_seen.to_rfc3339()
+                )
+                .as_bytes(),
+            );
+
+            write_atomic(&path, &data)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::fs;
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::composed::{Message, SignedPublicKey};
+    use crate::crypto::hash::HashAlgorithm;
+    use crate::test_util::{gen_key, temp_dir};
+
+    #[test]
+    fn test_observe() {
+        let dir = temp_dir("tofu-observe");
+        let store = FileTrustStore::open(&dir).unwrap();
+
+        let first = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        let later = Utc.with_ymd_and_hms(2023, 6, 1, 0, 0, 0).unwrap();
+        let alice = gen_key("Alice <alice@example.com>").fingerprint();
+        let mallory = gen_key("Alice <alice@example.com>").fingerprint();
+
+        let email = "alice@example.com";
+        assert_eq!(
+            store.observe(email, &alice, first).unwrap(),
+            TrustEvent::FirstSeen
+        );
+        let known = Binding::new(alice.clone(), first);
+        assert_eq!(
+            store.observe(email, &alice, later).unwrap(),
+            TrustEvent::Known(known.clone())
+        );
+        assert_eq!(
+            store.observe(email, &mallory, later).unwrap(),
+            TrustEvent::Conflict(vec![known.clone()])
+        );
+        // reported once, afterwards both are known
+        assert_eq!(
+            store.observe(email, &mallory, later).unwrap(),
+            TrustEvent::Known(Binding::new(mallory.clone(), later))
+        );
+        assert_eq!(
+            store.bindings(email).unwrap(),
+            vec![known, Binding::new(mallory, later)]
+        );
+        assert!(store.bindings("bob@example.com").unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_observe_results() {
+        let dir = temp_dir("tofu-results");
+        let store = FileTrustStore::open(&dir).unwrap();
+
+        let alice = gen_key("Alice <Alice@example.com>");
+        let mallory = gen_key("Alice <alice@example.com>");
+        let alice_pub: SignedPublicKey = alice.clone().into();
+        let mallory_pub: SignedPublicKey = mallory.clone().into();
+        let certs = [&alice_pub, &mallory_pub];
+
+        let msg = Message::new_literal("hello.txt", "hello world\n")
+            .sign(&alice, String::new, HashAlgorithm::SHA2_256)
+            .unwrap();
+        let results = msg.verify_signatures_with_trust(&certs, &store).unwrap();
+        assert_eq!(
+            results[0].trust(),
+            &[("alice@example.com".to_string(), TrustEvent::FirstSeen)]
+        );
+
+        let msg = Message::new_literal("hello.txt", "hello world\n")
+            .sign(&mallory, String::new, HashAlgorithm::SHA2_256)
+            .unwrap();
+        let results = msg.verify_signatures_with_trust(&certs, &store).unwrap();
+        assert!(results[0].trust()[0].1.is_conflict());
+
+        // invalid signatures are not recorded
+        let results = msg
+            .verify_signatures_with_trust(&[&alice_pub], &store)
+            .unwrap();
+        assert!(!results[0].is_valid());
+        assert!(results[0].trust().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,6 +1,7 @@
 use num_enum::{FromPrimitive, IntoPrimitive};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, FromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Available compression algorithms.
 /// Ref: https://tools.ietf.org/html/rfc4880.html#section-9.3
 #[repr(u8)]
@@ -9,9 +10,59 @@ pub enum CompressionAlgorithm {
     ZIP = 1,
     ZLIB = 2,
     BZip2 = 3,
+    /// Zstandard, using a private-use ID. Experimental, and not understood by other
+    /// implementations, requires the `zstd` feature.
+    Zstd = 100,
     /// Do not use, just for compatability with GnuPG.
     Private10 = 110,
 
     #[num_enum(catch_all)]
     Other(u8),
 }
+
+/// Algorithms considered for negotiation, most preferred first.
+const NEGOTIATION_ORDER: &[CompressionAlgorithm] =
+    &[CompressionAlgorithm::ZLIB, CompressionAlgorithm::ZIP];
+
+impl CompressionAlgorithm {
+    /// Picks the first algorithm supported by all recipients, given their preferences.
+    ///
+    /// Falls back to `Uncompressed`, which every implementation must support, if there is no
+    /// common algorithm.
+    pub fn negotiate(preferences: &[&[CompressionAlgorithm]]) -> Self {
+        NEGOTIATION_ORDER
+            .iter()
+            .copied()
+            .find(|alg| preferences.iter().all(|prefs| prefs.contains(alg)))
+            .unwrap_or(CompressionAlgorithm::Uncompressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        use CompressionAlgorithm::*;
+
+        assert_eq!(CompressionAlgorithm::negotiate(&[]), ZLIB);
+        assert_eq!(
+            CompressionAlgorithm::negotiate(&[&[ZIP, ZLIB], &[BZip2, ZIP, ZLIB]]),
+            ZLIB
+        );
+        assert_eq!(
+            CompressionAlgorithm::negotiate(&[&[ZLIB, ZIP], &[ZIP]]),
+            ZIP
+        );
+        // no common algorithm, or no preferences at all
+        assert_eq!(
+            CompressionAlgorithm::negotiate(&[&[ZLIB], &[BZip2]]),
+            Uncompressed
+        );
+        assert_eq!(
+            CompressionAlgorithm::negotiate(&[&[ZLIB], &[]]),
+            Uncompressed
+        );
+    }
+}
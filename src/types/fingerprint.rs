@@ -0,0 +1,175 @@
+use std::fmt;
+
+use smallvec::SmallVec;
+
+use crate::errors::Result;
+use crate::types::{KeyId, KeyVersion};
+
+/// Represents the fingerprint of a key.
+///
+/// The length depends on the key version: 16 bytes for version 2 and 3 keys,
+/// 20 bytes for version 4 keys and 32 bytes for version 5 keys.
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct Fingerprint {
+    version: KeyVersion,
+    fingerprint: SmallVec<[u8; 32]>,
+}
+
+impl AsRef<[u8]> for Fingerprint {
+    fn as_ref(&self) -> &[u8] {
+        &self.fingerprint
+    }
+}
+
+impl Fingerprint {
+    /// Creates a fingerprint for a key of the given `version`.
+    pub fn new(version: KeyVersion, input: &[u8]) -> Result<Fingerprint> {
+        match version {
+            KeyVersion::V2 | KeyVersion::V3 => {
+                ensure_eq!(input.len(), 16, "invalid input length")
+            }
+            KeyVersion::V4 => ensure_eq!(input.len(), 20, "invalid input length"),
+            KeyVersion::V5 => ensure_eq!(input.len(), 32, "invalid input length"),
+            KeyVersion::Other(_) => ensure!(input.len() <= 32, "invalid input length"),
+        }
+
+        Ok(Fingerprint {
+            version,
+            fingerprint: SmallVec::from_slice(input),
+        })
+    }
+
+    /// Parses a hex encoded fingerprint.
+    ///
+    /// Whitespace, as used in the GnuPG style grouping, and a leading `0x` are ignored.
+    /// The key version is derived from the length.
+    pub fn from_hex(input: &str) -> Result<Fingerprint> {
+        let input: String = input.split_whitespace().collect();
+        let input = input
+            .strip_prefix("0x")
+            .or_else(|| input.strip_prefix("0X"))
+            .unwrap_or(&input);
+        let bytes = hex::decode(input)?;
+        let version = match bytes.len() {
+            16 => KeyVersion::V3,
+            20 => KeyVersion::V4,
+            32 => KeyVersion::V5,
+            len => bail!("invalid fingerprint length: {}", len),
+        };
+
+        Fingerprint::new(version, &bytes)
+    }
+
+    /// The version of the key this fingerprint belongs to.
+    pub fn version(&self) -> KeyVersion {
+        self.version
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.fingerprint
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.fingerprint.to_vec()
+    }
+
+    /// Derives the Key ID from the fingerprint.
+    ///
+    /// Returns `None` for version 2 and 3 keys, where the Key ID is not part of the fingerprint.
+    pub fn key_id(&self) -> Option<KeyId> {
+        let id = match self.version {
+            KeyVersion::V4 => &self.fingerprint[12..],
+            KeyVersion::V5 => &self.fingerprint[..8],
+            _ => return None,
+        };
+
+        KeyId::from_slice(id).ok()
+    }
+}
+
+impl fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Fingerprint({})", hex::encode(self.as_ref()))
+    }
+}
+
+/// Formats the fingerprint the way GnuPG does, in groups of four characters,
+/// with an additional space in the middle.
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let encoded = format!("{:X}", self);
+        let groups: Vec<&str> = encoded
+            .as_bytes()
+            .chunks(4)
+            .map(|c| std::str::from_utf8(c).expect("hex is ascii"))
+            .collect();
+        let (first, second) = groups.split_at(groups.len() / 2);
+
+        write!(f, "{}  {}", first.join(" "), second.join(" "))
+    }
+}
+
+impl fmt::LowerHex for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.as_ref()))
+    }
+}
+
+impl fmt::UpperHex for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut encoded = hex::encode(self.as_ref());
+        encoded.make_ascii_uppercase();
+        write!(f, "{encoded}")
+    }
+}
+
+/// Serialized as uppercase hex.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Fingerprint {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{:X}", self))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Fingerprint {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Fingerprint::from_hex(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_hex() {
+        let fp =
+            Fingerprint::from_hex("2A4F 2E6E 4B05 44C8 D6D8  2A7A 3CC2 B3DD 63C5 2D1F").unwrap();
+        assert_eq!(fp.version(), KeyVersion::V4);
+        assert_eq!(
+            format!("{:x}", fp),
+            "2a4f2e6e4b0544c8d6d82a7a3cc2b3dd63c52d1f"
+        );
+        assert_eq!(
+            fp.to_string(),
+            "2A4F 2E6E 4B05 44C8 D6D8  2A7A 3CC2 B3DD 63C5 2D1F"
+        );
+        assert_eq!(fp.key_id().unwrap().to_string(), "3CC2B3DD63C52D1F");
+        assert_eq!(
+            Fingerprint::from_hex("0x2a4f2e6e4b0544c8d6d82a7a3cc2b3dd63c52d1f").unwrap(),
+            fp
+        );
+
+        assert!(Fingerprint::from_hex("2a4f2e6e4b0544c8").is_err());
+        assert!(Fingerprint::new(KeyVersion::V5, fp.as_bytes()).is_err());
+    }
+}
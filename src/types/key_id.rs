@@ -3,7 +3,7 @@ use std::fmt;
 use crate::errors::Result;
 
 /// Represents a Key ID.
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Eq, PartialEq, Hash)]
 pub struct KeyId([u8; 8]);
 
 impl AsRef<[u8]> for KeyId {
@@ -13,6 +13,9 @@ impl AsRef<[u8]> for KeyId {
 }
 
 impl KeyId {
+    /// The wildcard Key ID, used in place of the recipient's Key ID to hide it.
+    pub const WILDCARD: KeyId = KeyId([0u8; 8]);
+
     pub fn from_slice(input: &[u8]) -> Result<KeyId> {
         ensure_eq!(input.len(), 8, "invalid input length");
         let mut r = [0u8; 8];
@@ -21,9 +24,24 @@ impl KeyId {
         Ok(KeyId(r))
     }
 
+    /// Parses a hex encoded Key ID, optionally prefixed with `0x`.
+    pub fn from_hex(input: &str) -> Result<KeyId> {
+        let input = input.trim();
+        let input = input
+            .strip_prefix("0x")
+            .or_else(|| input.strip_prefix("0X"))
+            .unwrap_or(input);
+
+        KeyId::from_slice(&hex::decode(input)?)
+    }
+
     pub fn to_vec(&self) -> Vec<u8> {
         self.0.to_vec()
     }
+
+    pub fn is_wildcard(&self) -> bool {
+        self == &Self::WILDCARD
+    }
 }
 
 impl fmt::Debug for KeyId {
@@ -32,6 +50,13 @@ impl fmt::Debug for KeyId {
     }
 }
 
+/// Formats the Key ID the way GnuPG does, as 16 uppercase hex characters.
+impl fmt::Display for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:X}", self)
+    }
+}
+
 impl fmt::LowerHex for KeyId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", hex::encode(self.as_ref()))
@@ -45,3 +70,24 @@ impl fmt::UpperHex for KeyId {
         write!(f, "{encoded}")
     }
 }
+
+/// Serialized as uppercase hex.
+#[cfg(feature = "serde")]
+impl serde::Serialize for KeyId {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{:X}", self))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for KeyId {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        KeyId::from_hex(&s).map_err(serde::de::Error::custom)
+    }
+}
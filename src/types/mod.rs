@@ -1,25 +1,33 @@
 mod compression;
+mod fingerprint;
 mod key;
 mod key_id;
 mod mpi;
 mod packet;
 mod params;
+mod passphrase;
 mod public_key;
 mod revocation_key;
 mod s2k;
+mod secret_bytes;
 mod secret_key;
 mod secret_key_repr;
+mod signer;
 mod user;
 
 pub use self::compression::*;
+pub use self::fingerprint::*;
 pub use self::key::*;
 pub use self::key_id::*;
 pub use self::mpi::*;
 pub use self::packet::*;
 pub use self::params::*;
+pub use self::passphrase::*;
 pub use self::public_key::*;
 pub use self::revocation_key::*;
 pub use self::s2k::*;
+pub use self::secret_bytes::*;
 pub use self::secret_key::*;
 pub use self::secret_key_repr::*;
+pub use self::signer::*;
 pub use self::user::*;
@@ -6,8 +6,10 @@ use nom::{self, Err, InputIter, InputTake};
 use num_bigint::BigUint;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
+use crate::crypto::checksum;
 use crate::errors::{self, Error, IResult};
 use crate::ser::Serialize;
+use crate::types::SecretBytes;
 use crate::util::{bit_size, strip_leading_zeros, strip_leading_zeros_vec};
 
 /// Number of bits we accept when reading or writing MPIs.
@@ -55,7 +57,47 @@ pub fn mpi(input: &[u8]) -> IResult<&[u8], MpiRef<'_>> {
 /// Represents an owned MPI value.
 /// The inner value is ready to be serialized, without the need to strip leading zeros.
 #[derive(Default, Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
-pub struct Mpi(Vec<u8>);
+pub struct Mpi(MpiBytes);
+
+/// The storage of an MPI, secret values are kept in [`SecretBytes`].
+#[derive(Clone)]
+enum MpiBytes {
+    Public(Vec<u8>),
+    Secret(SecretBytes),
+}
+
+impl MpiBytes {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            MpiBytes::Public(bytes) => bytes,
+            MpiBytes::Secret(bytes) => bytes,
+        }
+    }
+}
+
+impl Default for MpiBytes {
+    fn default() -> Self {
+        MpiBytes::Public(Vec::new())
+    }
+}
+
+impl PartialEq for MpiBytes {
+    /// Compares in constant time, as the value might be secret.
+    fn eq(&self, other: &Self) -> bool {
+        checksum::ct_eq(self.as_slice(), other.as_slice())
+    }
+}
+
+impl Eq for MpiBytes {}
+
+impl Zeroize for MpiBytes {
+    fn zeroize(&mut self) {
+        match self {
+            MpiBytes::Public(bytes) => bytes.zeroize(),
+            MpiBytes::Secret(bytes) => bytes.zeroize(),
+        }
+    }
+}
 
 /// Represents a borrowed MPI value.
 /// The inner value is ready to be serialized, without the need to strip leading zeros.
@@ -64,31 +106,31 @@ pub struct MpiRef<'a>(&'a [u8]);
 
 impl AsRef<[u8]> for Mpi {
     fn as_ref(&self) -> &[u8] {
-        self.0.as_ref()
+        self.0.as_slice()
     }
 }
 
 impl Mpi {
     pub fn from_raw(mut v: Vec<u8>) -> Self {
         strip_leading_zeros_vec(&mut v);
-        Mpi(v)
+        Mpi(MpiBytes::Public(v))
     }
 
     pub fn from_slice(slice: &[u8]) -> Self {
-        Mpi(slice.to_vec())
+        Mpi(MpiBytes::Public(slice.to_vec()))
     }
 
     /// Strips leading zeros.
     pub fn from_raw_slice(raw: &[u8]) -> Self {
-        Mpi(strip_leading_zeros(raw).to_vec())
+        Mpi(MpiBytes::Public(strip_leading_zeros(raw).to_vec()))
     }
 
     pub fn as_ref(&self) -> MpiRef<'_> {
-        MpiRef(&self.0)
+        MpiRef(self.0.as_slice())
     }
 
     pub fn as_bytes(&self) -> &[u8] {
-        &self.0
+        self.0.as_slice()
     }
 }
 
@@ -96,7 +138,7 @@ impl std::ops::Deref for Mpi {
     type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        self.0.as_slice()
     }
 }
 
@@ -114,7 +156,12 @@ impl<'a> MpiRef<'a> {
     }
 
     pub fn to_owned(&self) -> Mpi {
-        Mpi(self.0.to_owned())
+        Mpi::from_slice(self.0)
+    }
+
+    /// Copies the value into [`SecretBytes`], for secret key material.
+    pub(crate) fn to_secret(&self) -> Mpi {
+        Mpi(MpiBytes::Secret(SecretBytes::new(self.0)))
     }
 
     pub fn parse(slice: &'a [u8]) -> IResult<&'a [u8], MpiRef<'a>> {
@@ -141,7 +188,7 @@ impl<'a> MpiRef<'a> {
 
 impl Serialize for Mpi {
     fn to_writer<W: io::Write>(&self, w: &mut W) -> errors::Result<()> {
-        MpiRef(&self.0).to_writer(w)
+        self.as_ref().to_writer(w)
     }
 }
 
@@ -170,13 +217,13 @@ impl<'a> From<&'a [u8]> for MpiRef<'a> {
 
 impl From<Vec<u8>> for Mpi {
     fn from(other: Vec<u8>) -> Mpi {
-        Mpi(other)
+        Mpi(MpiBytes::Public(other))
     }
 }
 
 impl From<BigUint> for Mpi {
     fn from(other: BigUint) -> Self {
-        Mpi(other.to_bytes_be())
+        Mpi(MpiBytes::Public(other.to_bytes_be()))
     }
 }
 
@@ -206,7 +253,7 @@ impl<'a, 'b> From<&'b MpiRef<'a>> for BigUint {
 
 impl<'a> From<&'a BigUint> for Mpi {
     fn from(other: &'a BigUint) -> Self {
-        Mpi(other.to_bytes_be())
+        Mpi(MpiBytes::Public(other.to_bytes_be()))
     }
 }
 
@@ -33,6 +33,7 @@ impl From<usize> for PacketLength {
 
 /// Packet tag as defined in RFC 4880, Section 4.3 "Packet Tags"
 #[derive(Debug, PartialEq, Eq, Clone, Copy, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Tag {
     /// Public-Key Encrypted Session Key Packet
@@ -69,6 +70,21 @@ pub enum Tag {
     SymEncryptedProtectedData = 18,
     /// Modification Detection Code Packet
     ModDetectionCode = 19,
+    /// Private or Experimental Values
+    Private60 = 60,
+    Private61 = 61,
+    Private62 = 62,
+    Private63 = 63,
+}
+
+impl Tag {
+    /// Whether the tag is one of the values reserved for private or experimental use.
+    pub fn is_experimental(self) -> bool {
+        matches!(
+            self,
+            Tag::Private60 | Tag::Private61 | Tag::Private62 | Tag::Private63
+        )
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, TryFromPrimitive)]
@@ -83,11 +99,14 @@ pub enum Version {
 }
 
 impl Version {
+    /// Writes a packet header in this format.
+    ///
+    /// Tags above 15 can not be expressed in the old format, and are written in the new format.
     pub fn write_header(self, writer: &mut impl io::Write, tag: u8, len: usize) -> Result<()> {
         debug!("write_header {:?} {} {}", self, tag, len);
 
         match self {
-            Version::Old => {
+            Version::Old if tag < 16 => {
                 if len < 256 {
                     // one octet
                     writer.write_all(&[0b1000_0000 | tag << 2, len as u8])?;
@@ -101,7 +120,7 @@ impl Version {
                     writer.write_u32::<BigEndian>(len as u32)?;
                 }
             }
-            Version::New => {
+            Version::Old | Version::New => {
                 writer.write_all(&[0b1100_0000 | tag])?;
                 if len < 192 {
                     writer.write_all(&[len as u8])?;
@@ -122,7 +141,8 @@ impl Version {
 }
 
 // TODO: find a better place for this
-#[derive(Debug, PartialEq, Eq, Clone, Copy, FromPrimitive, IntoPrimitive)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, FromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum KeyVersion {
     V2 = 2,
@@ -168,5 +188,20 @@ mod tests {
             .unwrap();
 
         assert_eq!(hex::encode(buf), "c2c06f");
+
+        let mut buf = Vec::new();
+        Version::Old
+            .write_header(&mut buf, Tag::Signature as u8, 303)
+            .unwrap();
+
+        assert_eq!(hex::encode(buf), "89012f");
+
+        // tags above 15 fall back to the new format
+        let mut buf = Vec::new();
+        Version::Old
+            .write_header(&mut buf, Tag::UserAttribute as u8, 12875)
+            .unwrap();
+
+        assert_eq!(hex::encode(buf), "d1ff0000324b");
     }
 }
@@ -1,6 +1,6 @@
 use std::{fmt, io};
 
-use byteorder::{BigEndian, ByteOrder};
+use zeroize::Zeroizing;
 
 use crate::crypto::checksum;
 use crate::crypto::public_key::PublicKeyAlgorithm;
@@ -64,9 +64,11 @@ impl EncryptedSecretParams {
     pub fn compare_checksum(&self, other: Option<&[u8]>) -> Result<()> {
         if self.string_to_key_id < 254 {
             if let Some(other) = other {
-                ensure_eq!(
-                    BigEndian::read_u16(other),
-                    checksum::calculate_simple(self.data()),
+                ensure!(
+                    checksum::ct_eq(
+                        &other[..2],
+                        &checksum::calculate_simple(self.data()).to_be_bytes()
+                    ),
                     "Invalid checksum"
                 );
             } else {
@@ -91,21 +93,41 @@ impl EncryptedSecretParams {
         }
     }
 
+    /// Decrypts the parameters with the passphrase from `pw`.
+    ///
+    /// The provider is asked again after a wrong passphrase, as long as it wants to retry.
     pub fn unlock<F>(
         &self,
-        pw: F,
+        mut pw: F,
+        request: &PassphraseRequest,
         alg: PublicKeyAlgorithm,
         params: &PublicParams,
     ) -> Result<PlainSecretParams>
     where
-        F: FnOnce() -> String,
+        F: PassphraseProvider,
     {
+        let mut request = request.clone();
+        loop {
+            let passphrase = pw.passphrase(&request)?;
+            match self.unlock_with_passphrase(&passphrase, alg, params) {
+                Err(Error::BadPassphrase) if pw.retry(&request) => request.next_attempt(),
+                res => return res,
+            }
+        }
+    }
+
+    fn unlock_with_passphrase(
+        &self,
+        passphrase: &str,
+        alg: PublicKeyAlgorithm,
+        params: &PublicParams,
+    ) -> Result<PlainSecretParams> {
         let key = self
             .string_to_key
-            .derive_key(&pw(), self.encryption_algorithm.key_size())?;
+            .derive_key(passphrase, self.encryption_algorithm.key_size())?;
 
         // Actual decryption
-        let mut plaintext = self.data.clone();
+        let mut plaintext = Zeroizing::new(self.data.clone());
         self.encryption_algorithm
             .decrypt_with_iv_regular(&key, &self.iv, &mut plaintext)?;
 
@@ -117,14 +139,17 @@ impl EncryptedSecretParams {
             }
             let expected_sha1 = &plaintext[plaintext.len() - 20..];
             let calculated_sha1 = checksum::calculate_sha1([&plaintext[..plaintext.len() - 20]]);
-            let checksum_correct = expected_sha1 == calculated_sha1;
+            let checksum_correct = checksum::ct_eq(expected_sha1, &calculated_sha1);
             if !checksum_correct {
-                return Err(Error::InvalidInput);
+                return Err(Error::BadPassphrase);
             }
+
+            return PlainSecretParams::from_slice(&plaintext, alg, params);
         }
 
-        let res = PlainSecretParams::from_slice(&plaintext, alg, params)?;
-        Ok(res)
+        // Without the SHA-1 hash, a wrong passphrase shows up as garbage that fails to parse
+        // or does not match the simple checksum.
+        PlainSecretParams::from_slice(&plaintext, alg, params).map_err(|_| Error::BadPassphrase)
     }
 }
 
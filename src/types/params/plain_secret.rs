@@ -1,12 +1,10 @@
-use std::hash::Hasher;
 use std::{fmt, io};
 
-use byteorder::{BigEndian, ByteOrder};
 use nom::combinator::map;
 use nom::sequence::tuple;
 use rand::{CryptoRng, Rng};
 use rsa::RsaPrivateKey;
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 use crate::crypto::checksum;
 use crate::crypto::ecc_curve::ECCCurve;
@@ -43,19 +41,20 @@ pub enum PlainSecretParamsRef<'a> {
 }
 
 impl<'a> PlainSecretParamsRef<'a> {
+    /// Copies the parameters, keeping the values in [`SecretBytes`].
     pub fn to_owned(&self) -> PlainSecretParams {
         match self {
             PlainSecretParamsRef::RSA { d, p, q, u } => PlainSecretParams::RSA {
-                d: (*d).to_owned(),
-                p: (*p).to_owned(),
-                q: (*q).to_owned(),
-                u: (*u).to_owned(),
+                d: d.to_secret(),
+                p: p.to_secret(),
+                q: q.to_secret(),
+                u: u.to_secret(),
             },
-            PlainSecretParamsRef::DSA(v) => PlainSecretParams::DSA((*v).to_owned()),
-            PlainSecretParamsRef::ECDSA(v) => PlainSecretParams::ECDSA((*v).to_owned()),
-            PlainSecretParamsRef::ECDH(v) => PlainSecretParams::ECDH((*v).to_owned()),
-            PlainSecretParamsRef::Elgamal(v) => PlainSecretParams::Elgamal((*v).to_owned()),
-            PlainSecretParamsRef::EdDSA(v) => PlainSecretParams::EdDSA((*v).to_owned()),
+            PlainSecretParamsRef::DSA(v) => PlainSecretParams::DSA(v.to_secret()),
+            PlainSecretParamsRef::ECDSA(v) => PlainSecretParams::ECDSA(v.to_secret()),
+            PlainSecretParamsRef::ECDH(v) => PlainSecretParams::ECDH(v.to_secret()),
+            PlainSecretParamsRef::Elgamal(v) => PlainSecretParams::Elgamal(v.to_secret()),
+            PlainSecretParamsRef::EdDSA(v) => PlainSecretParams::EdDSA(v.to_secret()),
         }
     }
 
@@ -95,9 +94,8 @@ impl<'a> PlainSecretParamsRef<'a> {
         if let Some(other) = other {
             let mut hasher = checksum::SimpleChecksum::default();
             self.to_writer_raw(&mut hasher)?;
-            ensure_eq!(
-                BigEndian::read_u16(other),
-                hasher.finish() as u16,
+            ensure!(
+                checksum::ct_eq(&other[..2], &hasher.finalize()),
                 "Invalid checksum"
             );
             Ok(())
@@ -113,11 +111,15 @@ impl<'a> PlainSecretParamsRef<'a> {
     }
 
     pub fn checksum_sha1(&self) -> Vec<u8> {
-        let mut buf = Vec::new();
-        self.to_writer_raw(&mut buf).expect("known write target");
+        let mut buf = Zeroizing::new(Vec::new());
+        self.to_writer_raw(&mut *buf).expect("known write target");
         checksum::calculate_sha1([&buf])
     }
 
+    #[cfg_attr(
+        not(any(feature = "nist-curves", feature = "secp256k1")),
+        allow(unused_variables)
+    )]
     pub fn as_repr(&self, public_params: &PublicParams) -> Result<SecretKeyRepr> {
         match self {
             PlainSecretParamsRef::RSA { d, p, q, .. } => match public_params {
@@ -180,16 +182,19 @@ impl<'a> PlainSecretParamsRef<'a> {
             }
             PlainSecretParamsRef::ECDSA(d) => match public_params {
                 PublicParams::ECDSA(params) => match params {
+                    #[cfg(feature = "nist-curves")]
                     EcdsaPublicParams::P256 { .. } => {
                         let secret = p256::SecretKey::from_slice(d.as_bytes())?;
 
                         Ok(SecretKeyRepr::ECDSA(ECDSASecretKey::P256(secret)))
                     }
+                    #[cfg(feature = "nist-curves")]
                     EcdsaPublicParams::P384 { .. } => {
                         let secret = p384::SecretKey::from_slice(d.as_bytes())?;
 
                         Ok(SecretKeyRepr::ECDSA(ECDSASecretKey::P384(secret)))
                     }
+                    #[cfg(feature = "secp256k1")]
                     EcdsaPublicParams::Secp256k1 { .. } => {
                         let secret = k256::SecretKey::from_slice(d.as_bytes())?;
 
@@ -215,6 +220,11 @@ impl PlainSecretParams {
         Ok(repr)
     }
 
+    /// Copies the parameters into [`SecretBytes`], for freshly generated or imported keys.
+    pub(crate) fn to_secret(&self) -> Self {
+        self.as_ref().to_owned()
+    }
+
     pub fn string_to_key_id(&self) -> u8 {
         self.as_ref().string_to_key_id()
     }
@@ -260,9 +270,9 @@ impl PlainSecretParams {
             KeyVersion::V2 => unsupported_err!("Encryption for V2 keys is not available"),
             KeyVersion::V3 => unimplemented_err!("v3 encryption"),
             KeyVersion::V4 => {
-                let mut data = Vec::new();
+                let mut data = Zeroizing::new(Vec::new());
                 self.as_ref()
-                    .to_writer_raw(&mut data)
+                    .to_writer_raw(&mut *data)
                     .expect("preallocated vector");
                 match id {
                     254 => {
@@ -273,7 +283,8 @@ impl PlainSecretParams {
 
                 alg.encrypt_with_iv_regular(&key, &iv, &mut data)?;
 
-                data
+                // encrypted now, no need to zero it
+                std::mem::take(&mut *data)
             }
             KeyVersion::V5 => unimplemented_err!("v5 encryption"),
             KeyVersion::Other(v) => unimplemented_err!("encryption for key version {}", v),
@@ -315,9 +326,7 @@ impl<'a> fmt::Debug for PlainSecretParamsRef<'a> {
             PlainSecretParamsRef::RSA { .. } => write!(f, "PlainSecretParams(RSA)"),
             PlainSecretParamsRef::DSA(_) => write!(f, "PlainSecretParams(DSA)"),
             PlainSecretParamsRef::Elgamal(_) => write!(f, "PlainSecretParams(Elgamal)"),
-            PlainSecretParamsRef::ECDSA(x) => {
-                write!(f, "PlainSecretParams(ECDSA {})", hex::encode(x.as_bytes()))
-            }
+            PlainSecretParamsRef::ECDSA(_) => write!(f, "PlainSecretParams(ECDSA)"),
             PlainSecretParamsRef::ECDH(_) => write!(f, "PlainSecretParams(ECDH)"),
             PlainSecretParamsRef::EdDSA(_) => write!(f, "PlainSecretParams(EdDSA)"),
         }
@@ -331,11 +340,11 @@ fn parse_secret_params(
         PublicKeyAlgorithm::RSA | PublicKeyAlgorithm::RSAEncrypt | PublicKeyAlgorithm::RSASign => {
             rsa_secret_params(i)
         }
-        PublicKeyAlgorithm::DSA => map(mpi, |m| PlainSecretParams::DSA(m.to_owned()))(i),
-        PublicKeyAlgorithm::Elgamal => map(mpi, |m| PlainSecretParams::Elgamal(m.to_owned()))(i),
-        PublicKeyAlgorithm::ECDH => map(mpi, |m| PlainSecretParams::ECDH(m.to_owned()))(i),
-        PublicKeyAlgorithm::ECDSA => map(mpi, |m| PlainSecretParams::ECDSA(m.to_owned()))(i),
-        PublicKeyAlgorithm::EdDSA => map(mpi, |m| PlainSecretParams::EdDSA(m.to_owned()))(i),
+        PublicKeyAlgorithm::DSA => map(mpi, |m| PlainSecretParams::DSA(m.to_secret()))(i),
+        PublicKeyAlgorithm::Elgamal => map(mpi, |m| PlainSecretParams::Elgamal(m.to_secret()))(i),
+        PublicKeyAlgorithm::ECDH => map(mpi, |m| PlainSecretParams::ECDH(m.to_secret()))(i),
+        PublicKeyAlgorithm::ECDSA => map(mpi, |m| PlainSecretParams::ECDSA(m.to_secret()))(i),
+        PublicKeyAlgorithm::EdDSA => map(mpi, |m| PlainSecretParams::EdDSA(m.to_secret()))(i),
         _ => Err(nom::Err::Error(crate::errors::Error::ParsingError(
             nom::error::ErrorKind::Switch,
         ))),
@@ -346,10 +355,10 @@ fn parse_secret_params(
 fn rsa_secret_params(i: &[u8]) -> IResult<&[u8], PlainSecretParams> {
     map(tuple((mpi, mpi, mpi, mpi)), |(d, p, q, u)| {
         PlainSecretParams::RSA {
-            d: d.to_owned(),
-            p: p.to_owned(),
-            q: q.to_owned(),
-            u: u.to_owned(),
+            d: d.to_secret(),
+            p: p.to_secret(),
+            q: q.to_secret(),
+            u: u.to_secret(),
         }
     })(i)
 }
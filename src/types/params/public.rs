@@ -41,18 +41,27 @@ pub enum PublicParams {
     },
 }
 
+/// The public parameters of an ECDSA key.
+///
+/// The variants for specific curves depend on the enabled curve features, keys on other
+/// curves are kept as [`EcdsaPublicParams::Unsupported`]. Matches need a wildcard arm, so
+/// enabling a feature does not break them.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
 pub enum EcdsaPublicParams {
+    #[cfg(feature = "nist-curves")]
     P256 {
         key: p256::PublicKey,
         /// Stores the original Mpi, to ensure we keep the padding around.
         p: Mpi,
     },
+    #[cfg(feature = "nist-curves")]
     P384 {
         key: p384::PublicKey,
         /// Stores the original Mpi, to ensure we keep the padding around.
         p: Mpi,
     },
+    #[cfg(feature = "secp256k1")]
     Secp256k1 {
         key: k256::PublicKey,
         /// Stores the original Mpi, to ensure we keep the padding around.
@@ -67,6 +76,7 @@ pub enum EcdsaPublicParams {
 impl EcdsaPublicParams {
     pub fn try_from_mpi(p: MpiRef<'_>, curve: ECCCurve) -> Result<Self> {
         match curve {
+            #[cfg(feature = "nist-curves")]
             ECCCurve::P256 => {
                 ensure!(p.len() <= 65, "invalid public key length");
                 let mut key = [0u8; 65];
@@ -78,6 +88,7 @@ impl EcdsaPublicParams {
                     p: p.to_owned(),
                 })
             }
+            #[cfg(feature = "nist-curves")]
             ECCCurve::P384 => {
                 ensure!(p.len() <= 97, "invalid public key length");
                 let mut key = [0u8; 97];
@@ -89,6 +100,7 @@ impl EcdsaPublicParams {
                     p: p.to_owned(),
                 })
             }
+            #[cfg(feature = "secp256k1")]
             ECCCurve::Secp256k1 => {
                 ensure!(p.len() <= 65, "invalid public key length");
                 let mut key = [0u8; 65];
@@ -107,10 +119,37 @@ impl EcdsaPublicParams {
         }
     }
 
+    /// The curve the key is on.
+    pub fn curve(&self) -> ECCCurve {
+        match self {
+            #[cfg(feature = "nist-curves")]
+            EcdsaPublicParams::P256 { .. } => ECCCurve::P256,
+            #[cfg(feature = "nist-curves")]
+            EcdsaPublicParams::P384 { .. } => ECCCurve::P384,
+            #[cfg(feature = "secp256k1")]
+            EcdsaPublicParams::Secp256k1 { .. } => ECCCurve::Secp256k1,
+            EcdsaPublicParams::Unsupported { curve, .. } => curve.clone(),
+        }
+    }
+
+    /// The public point, in the uncompressed SEC1 encoding.
+    pub fn point(&self) -> &[u8] {
+        match self {
+            #[cfg(feature = "nist-curves")]
+            EcdsaPublicParams::P256 { p, .. } | EcdsaPublicParams::P384 { p, .. } => p.as_bytes(),
+            #[cfg(feature = "secp256k1")]
+            EcdsaPublicParams::Secp256k1 { p, .. } => p.as_bytes(),
+            EcdsaPublicParams::Unsupported { p, .. } => p.as_bytes(),
+        }
+    }
+
     pub const fn secret_key_length(&self) -> Option<usize> {
         match self {
+            #[cfg(feature = "nist-curves")]
             EcdsaPublicParams::P256 { .. } => Some(32),
+            #[cfg(feature = "nist-curves")]
             EcdsaPublicParams::P384 { .. } => Some(48),
+            #[cfg(feature = "secp256k1")]
             EcdsaPublicParams::Secp256k1 { .. } => Some(32),
             EcdsaPublicParams::Unsupported { .. } => None,
         }
@@ -120,8 +159,11 @@ impl EcdsaPublicParams {
 impl Serialize for EcdsaPublicParams {
     fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
         let oid = match self {
+            #[cfg(feature = "nist-curves")]
             EcdsaPublicParams::P256 { .. } => ECCCurve::P256.oid(),
+            #[cfg(feature = "nist-curves")]
             EcdsaPublicParams::P384 { .. } => ECCCurve::P384.oid(),
+            #[cfg(feature = "secp256k1")]
             EcdsaPublicParams::Secp256k1 { .. } => ECCCurve::Secp256k1.oid(),
             EcdsaPublicParams::Unsupported { curve, .. } => curve.oid(),
         };
@@ -130,12 +172,15 @@ impl Serialize for EcdsaPublicParams {
         writer.write_all(&oid)?;
 
         match self {
+            #[cfg(feature = "nist-curves")]
             EcdsaPublicParams::P256 { p, .. } => {
                 p.as_ref().to_writer(writer)?;
             }
+            #[cfg(feature = "nist-curves")]
             EcdsaPublicParams::P384 { p, .. } => {
                 p.as_ref().to_writer(writer)?;
             }
+            #[cfg(feature = "secp256k1")]
             EcdsaPublicParams::Secp256k1 { p, .. } => {
                 p.as_ref().to_writer(writer)?;
             }
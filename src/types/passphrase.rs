@@ -0,0 +1,223 @@
+use zeroize::Zeroizing;
+
+use crate::crypto::public_key::PublicKeyAlgorithm;
+use crate::errors::Result;
+use crate::types::{Fingerprint, KeyId, KeyTrait};
+
+/// Supplies the passphrases to unlock secret keys.
+///
+/// It is implemented for closures returning a `String`, which are asked once and never retried.
+/// Closures that can only be called once need to be wrapped in [`Once`].
+/// Implement it directly to show the user which key is being unlocked, to ask again after a
+/// wrong passphrase, or to cancel with [`Error::Cancelled`](crate::errors::Error::Cancelled).
+pub trait PassphraseProvider {
+    /// Returns the passphrase for the key described by `request`.
+    fn passphrase(&mut self, request: &PassphraseRequest) -> Result<Zeroizing<String>>;
+
+    /// Called after the passphrase returned for `request` was rejected, return `true` to be
+    /// asked again.
+    fn retry(&mut self, _request: &PassphraseRequest) -> bool {
+        false
+    }
+}
+
+impl<F> PassphraseProvider for F
+where
+    F: FnMut() -> String,
+{
+    fn passphrase(&mut self, _request: &PassphraseRequest) -> Result<Zeroizing<String>> {
+        Ok(Zeroizing::new(self()))
+    }
+}
+
+/// Adapts a closure that can only be called once, e.g. because it moves a captured passphrase
+/// out, into a [`PassphraseProvider`].
+///
+/// Asking it for a second passphrase fails.
+#[derive(Debug, Clone)]
+pub struct Once<F>(Option<F>);
+
+impl<F> Once<F>
+where
+    F: FnOnce() -> String,
+{
+    pub fn new(f: F) -> Self {
+        Once(Some(f))
+    }
+}
+
+impl<F> PassphraseProvider for Once<F>
+where
+    F: FnOnce() -> String,
+{
+    fn passphrase(&mut self, _request: &PassphraseRequest) -> Result<Zeroizing<String>> {
+        let f = self
+            .0
+            .take()
+            .ok_or_else(|| format_err!("passphrase was already requested"))?;
+
+        Ok(Zeroizing::new(f()))
+    }
+}
+
+/// The key a passphrase is requested for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassphraseRequest {
+    key_id: KeyId,
+    fingerprint: Fingerprint,
+    algorithm: PublicKeyAlgorithm,
+    user_id: Option<String>,
+    attempt: usize,
+}
+
+impl PassphraseRequest {
+    pub fn new(key: &impl KeyTrait) -> Self {
+        PassphraseRequest {
+            key_id: key.key_id(),
+            fingerprint: key.fingerprint(),
+            algorithm: key.algorithm(),
+            user_id: None,
+            attempt: 0,
+        }
+    }
+
+    /// Sets the User ID of the certificate the key belongs to.
+    pub fn with_user_id(mut self, user_id: impl Into<String>) -> Self {
+        self.user_id = Some(user_id.into());
+        self
+    }
+
+    pub fn key_id(&self) -> &KeyId {
+        &self.key_id
+    }
+
+    pub fn fingerprint(&self) -> &Fingerprint {
+        &self.fingerprint
+    }
+
+    pub fn algorithm(&self) -> PublicKeyAlgorithm {
+        self.algorithm
+    }
+
+    /// The primary User ID of the certificate, if known.
+    pub fn user_id(&self) -> Option<&str> {
+        self.user_id.as_deref()
+    }
+
+    /// The number of rejected passphrases for this key so far.
+    pub fn attempt(&self) -> usize {
+        self.attempt
+    }
+
+    pub(crate) fn next_attempt(&mut self) {
+        self.attempt += 1;
+    }
+}
+
+/// Adds the User ID to the requests of the wrapped provider, for keys that do not know the
+/// certificate they belong to.
+#[derive(Clone)]
+pub(crate) struct WithUserId<P> {
+    provider: P,
+    user_id: Option<String>,
+}
+
+impl<P> WithUserId<P> {
+    pub(crate) fn new(provider: P, user_id: Option<String>) -> Self {
+        WithUserId { provider, user_id }
+    }
+
+    fn request(&self, request: &PassphraseRequest) -> PassphraseRequest {
+        match (&self.user_id, request.user_id()) {
+            (Some(user_id), None) => request.clone().with_user_id(user_id.clone()),
+            _ => request.clone(),
+        }
+    }
+}
+
+impl<P: PassphraseProvider> PassphraseProvider for WithUserId<P> {
+    fn passphrase(&mut self, request: &PassphraseRequest) -> Result<Zeroizing<String>> {
+        let request = self.request(request);
+        self.provider.passphrase(&request)
+    }
+
+    fn retry(&mut self, request: &PassphraseRequest) -> bool {
+        let request = self.request(request);
+        self.provider.retry(&request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::composed::SignedSecretKey;
+    use crate::errors::Error;
+    use crate::test_util::key_params;
+    use crate::types::SecretKeyTrait;
+
+    /// Answers with the given passphrases in order, and cancels when they run out.
+    struct Answers<'a> {
+        answers: Vec<&'static str>,
+        requests: &'a mut Vec<PassphraseRequest>,
+    }
+
+    impl PassphraseProvider for Answers<'_> {
+        fn passphrase(&mut self, request: &PassphraseRequest) -> Result<Zeroizing<String>> {
+            self.requests.push(request.clone());
+            if self.answers.is_empty() {
+                return Err(Error::Cancelled);
+            }
+            Ok(Zeroizing::new(self.answers.remove(0).to_string()))
+        }
+
+        fn retry(&mut self, _request: &PassphraseRequest) -> bool {
+            true
+        }
+    }
+
+    fn key() -> SignedSecretKey {
+        key_params("Alice <alice@example.com>")
+            .passphrase(Some("hello".into()))
+            .build()
+            .unwrap()
+            .generate_signed()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_passphrase_retry() {
+        let key = key();
+
+        let mut requests = Vec::new();
+        let answers = Answers {
+            answers: vec!["wrong", "hello"],
+            requests: &mut requests,
+        };
+        key.unlock(answers, |_| Ok(())).unwrap();
+
+        let attempts: Vec<_> = requests.iter().map(|r| r.attempt()).collect();
+        assert_eq!(attempts, vec![0, 1]);
+        let request = &requests[0];
+        assert_eq!(request.key_id(), &key.key_id());
+        assert_eq!(request.fingerprint(), &key.fingerprint());
+        assert_eq!(request.algorithm(), PublicKeyAlgorithm::EdDSA);
+        assert_eq!(request.user_id(), Some("Alice <alice@example.com>"));
+
+        let answers = Answers {
+            answers: vec!["wrong"],
+            requests: &mut requests,
+        };
+        let err = key.unlock(answers, |_| Ok(())).unwrap_err();
+        assert!(matches!(err, Error::Cancelled), "{err:?}");
+
+        // closures are not retried
+        let err = key.unlock(|| "wrong".into(), |_| Ok(())).unwrap_err();
+        assert!(matches!(err, Error::BadPassphrase), "{err:?}");
+
+        let passphrase = String::from("hello");
+        key.unlock(Once::new(move || passphrase), |_| Ok(()))
+            .unwrap();
+    }
+}
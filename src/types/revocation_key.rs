@@ -1,13 +1,13 @@
 use num_enum::TryFromPrimitive;
-use smallvec::SmallVec;
 
 use crate::crypto::public_key::PublicKeyAlgorithm;
+use crate::types::Fingerprint;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct RevocationKey {
     pub class: RevocationKeyClass,
     pub algorithm: PublicKeyAlgorithm,
-    pub fingerprint: SmallVec<[u8; 20]>,
+    pub fingerprint: Fingerprint,
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone, TryFromPrimitive)]
@@ -21,12 +21,12 @@ impl RevocationKey {
     pub fn new(
         class: RevocationKeyClass,
         algorithm: PublicKeyAlgorithm,
-        fingerprint: &[u8],
+        fingerprint: Fingerprint,
     ) -> Self {
         RevocationKey {
             class,
             algorithm,
-            fingerprint: SmallVec::from_slice(fingerprint),
+            fingerprint,
         }
     }
 }
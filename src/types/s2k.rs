@@ -5,6 +5,7 @@ use nom::combinator::{cond, map};
 use nom::number::streaming::be_u8;
 use num_enum::{FromPrimitive, IntoPrimitive};
 use rand::{CryptoRng, Rng};
+use zeroize::Zeroizing;
 
 use crate::crypto::hash::HashAlgorithm;
 use crate::errors::{IResult, Result};
@@ -65,11 +66,11 @@ impl StringToKey {
 
     /// String-To-Key methods are used to convert a given password string into a key.
     /// Ref: https://tools.ietf.org/html/rfc4880#section-3.7
-    pub fn derive_key(&self, passphrase: &str, key_size: usize) -> Result<Vec<u8>> {
+    pub fn derive_key(&self, passphrase: &str, key_size: usize) -> Result<Zeroizing<Vec<u8>>> {
         let digest_size = self.hash.digest_size();
         let rounds = (key_size as f32 / digest_size as f32).ceil() as usize;
 
-        let mut key = Vec::with_capacity(key_size);
+        let mut key = Zeroizing::new(Vec::with_capacity(key_size));
 
         for round in 0..rounds {
             let mut hasher = self.hash.new_hasher()?;
@@ -0,0 +1,181 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use zeroize::Zeroize;
+
+use crate::crypto::checksum;
+
+/// A fixed-size buffer for secret data, like session keys and secret key material, which is
+/// zeroed when dropped.
+///
+/// With the `secure-memory` feature, the buffer is allocated on pages of its own, surrounded
+/// by guard pages, which are locked into memory and excluded from core dumps where the
+/// platform supports it. This reduces the chance of secrets ending up in swap or core dumps of
+/// long-running services. Locking is best effort, it fails silently when the limit for locked
+/// memory (`RLIMIT_MEMLOCK`) is reached.
+pub struct SecretBytes(imp::Buffer);
+
+impl SecretBytes {
+    /// Copies `data` into a new buffer.
+    pub fn new(data: &[u8]) -> Self {
+        SecretBytes(imp::Buffer::new(data))
+    }
+}
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+
+impl DerefMut for SecretBytes {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.0.as_mut_slice()
+    }
+}
+
+impl AsRef<[u8]> for SecretBytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Clone for SecretBytes {
+    fn clone(&self) -> Self {
+        SecretBytes::new(self)
+    }
+}
+
+impl PartialEq for SecretBytes {
+    /// Compares in constant time.
+    fn eq(&self, other: &Self) -> bool {
+        checksum::ct_eq(self, other)
+    }
+}
+
+impl Eq for SecretBytes {}
+
+impl Zeroize for SecretBytes {
+    fn zeroize(&mut self) {
+        self.0.as_mut_slice().zeroize();
+    }
+}
+
+impl From<&[u8]> for SecretBytes {
+    fn from(data: &[u8]) -> Self {
+        SecretBytes::new(data)
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    /// Moves `data` into a new buffer, zeroing the vector.
+    fn from(mut data: Vec<u8>) -> Self {
+        let bytes = SecretBytes::new(&data);
+        data.zeroize();
+        bytes
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes([..])")
+    }
+}
+
+#[cfg(not(feature = "secure-memory"))]
+mod imp {
+    use zeroize::Zeroizing;
+
+    pub struct Buffer(Zeroizing<Box<[u8]>>);
+
+    impl Buffer {
+        pub fn new(data: &[u8]) -> Self {
+            Buffer(Zeroizing::new(data.into()))
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            &self.0
+        }
+
+        pub fn as_mut_slice(&mut self) -> &mut [u8] {
+            &mut self.0
+        }
+    }
+}
+
+#[cfg(feature = "secure-memory")]
+#[allow(unsafe_code)]
+mod imp {
+    use std::ptr::NonNull;
+
+    /// Memory allocated with `memsec::malloc_sized`, `None` for empty buffers.
+    pub struct Buffer(Option<NonNull<[u8]>>);
+
+    // the buffer is owned, like a `Box<[u8]>`
+    unsafe impl Send for Buffer {}
+    unsafe impl Sync for Buffer {}
+
+    impl Buffer {
+        pub fn new(data: &[u8]) -> Self {
+            if data.is_empty() {
+                return Buffer(None);
+            }
+
+            // SAFETY: the allocation is `data.len()` bytes long and not aliased.
+            unsafe {
+                let mut ptr =
+                    memsec::malloc_sized(data.len()).expect("failed to allocate secure memory");
+                ptr.as_mut().copy_from_slice(data);
+                Buffer(Some(ptr))
+            }
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            match self.0 {
+                // SAFETY: the allocation is valid until dropped.
+                Some(ptr) => unsafe { ptr.as_ref() },
+                None => &[],
+            }
+        }
+
+        pub fn as_mut_slice(&mut self) -> &mut [u8] {
+            match self.0 {
+                // SAFETY: the allocation is valid until dropped, and borrowed mutably.
+                Some(mut ptr) => unsafe { ptr.as_mut() },
+                None => &mut [],
+            }
+        }
+    }
+
+    impl Drop for Buffer {
+        fn drop(&mut self) {
+            if let Some(ptr) = self.0.take() {
+                // SAFETY: allocated with `memsec::malloc_sized`, freed once. Freeing zeroes
+                // and unlocks the memory.
+                unsafe { memsec::free(ptr) }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_bytes() {
+        let mut bytes = SecretBytes::from(vec![1, 2, 3]);
+        assert_eq!(&bytes[..], &[1, 2, 3]);
+        assert_eq!(bytes.clone(), bytes);
+        assert_eq!(format!("{bytes:?}"), "SecretBytes([..])");
+
+        bytes[0] = 4;
+        assert_eq!(&bytes[..], &[4, 2, 3]);
+        bytes.zeroize();
+        assert_eq!(&bytes[..], &[0, 0, 0]);
+
+        assert!(SecretBytes::new(&[]).is_empty());
+    }
+}
@@ -1,18 +1,18 @@
 use crate::crypto::hash::HashAlgorithm;
 use crate::errors::Result;
-use crate::types::{Mpi, PublicKeyTrait, SecretKeyRepr};
+use crate::types::{Mpi, PassphraseProvider, PublicKeyTrait, SecretKeyRepr};
 
 pub trait SecretKeyTrait: PublicKeyTrait {
     type PublicKey;
 
     fn unlock<F, G>(&self, pw: F, work: G) -> Result<()>
     where
-        F: FnOnce() -> String,
+        F: PassphraseProvider,
         G: FnOnce(&SecretKeyRepr) -> Result<()>;
 
     fn create_signature<F>(&self, key_pw: F, hash: HashAlgorithm, data: &[u8]) -> Result<Vec<Mpi>>
     where
-        F: FnOnce() -> String;
+        F: PassphraseProvider;
 
     fn public_key(&self) -> Self::PublicKey;
 }
@@ -22,7 +22,7 @@ impl<'a, T: SecretKeyTrait> SecretKeyTrait for &'a T {
 
     fn unlock<F, G>(&self, pw: F, work: G) -> Result<()>
     where
-        F: FnOnce() -> String,
+        F: PassphraseProvider,
         G: FnOnce(&SecretKeyRepr) -> Result<()>,
     {
         (*self).unlock(pw, work)
@@ -30,7 +30,7 @@ impl<'a, T: SecretKeyTrait> SecretKeyTrait for &'a T {
 
     fn create_signature<F>(&self, key_pw: F, hash: HashAlgorithm, data: &[u8]) -> Result<Vec<Mpi>>
     where
-        F: FnOnce() -> String,
+        F: PassphraseProvider,
     {
         (*self).create_signature(key_pw, hash, data)
     }
@@ -39,3 +39,49 @@ impl<'a, T: SecretKeyTrait> SecretKeyTrait for &'a T {
         (*self).public_key()
     }
 }
+
+/// Async counterpart of [`SecretKeyTrait`], for keys whose secret operations are delegated to
+/// a remote service, e.g. a KMS or a network attached HSM.
+///
+/// It is implemented for all [`SecretKeyTrait`]s, which run the operations in place.
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+pub trait AsyncSecretKeyTrait: PublicKeyTrait + Sync {
+    async fn unlock<F, G>(&self, pw: F, work: G) -> Result<()>
+    where
+        F: PassphraseProvider + Send,
+        G: FnOnce(&SecretKeyRepr) -> Result<()> + Send;
+
+    async fn create_signature<F>(
+        &self,
+        key_pw: F,
+        hash: HashAlgorithm,
+        data: &[u8],
+    ) -> Result<Vec<Mpi>>
+    where
+        F: PassphraseProvider + Send;
+}
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl<T: SecretKeyTrait + Sync> AsyncSecretKeyTrait for T {
+    async fn unlock<F, G>(&self, pw: F, work: G) -> Result<()>
+    where
+        F: PassphraseProvider + Send,
+        G: FnOnce(&SecretKeyRepr) -> Result<()> + Send,
+    {
+        SecretKeyTrait::unlock(self, pw, work)
+    }
+
+    async fn create_signature<F>(
+        &self,
+        key_pw: F,
+        hash: HashAlgorithm,
+        data: &[u8],
+    ) -> Result<Vec<Mpi>>
+    where
+        F: PassphraseProvider + Send,
+    {
+        SecretKeyTrait::create_signature(self, key_pw, hash, data)
+    }
+}
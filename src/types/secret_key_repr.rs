@@ -59,10 +59,18 @@ impl fmt::Debug for EdDSASecretKey {
     }
 }
 
+/// The secret key of an ECDSA key.
+///
+/// Like [`EcdsaPublicParams`](crate::types::EcdsaPublicParams), the variants for specific
+/// curves depend on the enabled curve features, so matches need a wildcard arm.
 #[derive(Clone, PartialEq, Eq, ZeroizeOnDrop)]
+#[non_exhaustive]
 pub enum ECDSASecretKey {
+    #[cfg(feature = "nist-curves")]
     P256(p256::SecretKey),
+    #[cfg(feature = "nist-curves")]
     P384(p384::SecretKey),
+    #[cfg(feature = "secp256k1")]
     Secp256k1(k256::SecretKey),
     Unsupported {
         /// The secret point.
@@ -75,8 +83,11 @@ pub enum ECDSASecretKey {
 impl fmt::Debug for ECDSASecretKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            #[cfg(feature = "nist-curves")]
             ECDSASecretKey::P256(_) => write!(f, "ECDSASecretKey::P256([..])"),
+            #[cfg(feature = "nist-curves")]
             ECDSASecretKey::P384(_) => write!(f, "ECDSASecretKey::P384([..])"),
+            #[cfg(feature = "secp256k1")]
             ECDSASecretKey::Secp256k1(_) => write!(f, "ECDSASecretKey::Secp256k1([..])"),
             ECDSASecretKey::Unsupported { curve, .. } => f
                 .debug_struct("ECDSASecretKey::Unsupported")
@@ -0,0 +1,213 @@
+use std::io;
+
+use rand::{CryptoRng, Rng};
+use zeroize::Zeroizing;
+
+use crate::crypto::hash::HashAlgorithm;
+use crate::crypto::public_key::PublicKeyAlgorithm;
+use crate::errors::Result;
+use crate::types::{
+    Fingerprint, KeyId, KeyTrait, Mpi, PassphraseProvider, PublicKeyTrait, SecretKeyRepr,
+    SecretKeyTrait,
+};
+
+/// Signs digests without access to the secret key material, e.g. with a key on a smartcard,
+/// in a cloud KMS or held by gpg-agent.
+///
+/// Wrap it in an [`ExternalKey`] to use it wherever a [`SecretKeyTrait`] is expected, to
+/// build signatures, certifications and signed messages.
+pub trait Signer: PublicKeyTrait {
+    /// Signs `digest`, which was hashed with `hash`, returning the signature MPIs for the
+    /// algorithm of the key.
+    fn sign(&self, hash: HashAlgorithm, digest: &[u8]) -> Result<Vec<Mpi>>;
+}
+
+impl<T: Signer> Signer for &T {
+    fn sign(&self, hash: HashAlgorithm, digest: &[u8]) -> Result<Vec<Mpi>> {
+        (*self).sign(hash, digest)
+    }
+}
+
+/// Decrypts session keys without access to the secret key material, e.g. with a key on a
+/// smartcard or held by gpg-agent.
+///
+/// Use it with [`Message::decrypt_with_decryptors`](crate::Message::decrypt_with_decryptors).
+pub trait Decryptor: PublicKeyTrait {
+    /// Decrypts the session key in `mpis`, returning the symmetric algorithm, the key and its
+    /// checksum, as encrypted by the sender.
+    fn decrypt(&self, mpis: &[Mpi]) -> Result<Zeroizing<Vec<u8>>>;
+}
+
+impl<T: Decryptor> Decryptor for &T {
+    fn decrypt(&self, mpis: &[Mpi]) -> Result<Zeroizing<Vec<u8>>> {
+        (*self).decrypt(mpis)
+    }
+}
+
+/// A [`Signer`], used as a [`SecretKeyTrait`].
+///
+/// Passwords are ignored, as the signer unlocks the key itself if needed. Unlocking fails, as
+/// the secret key material is not available, so it can not be used to decrypt.
+///
+/// ```rust
+/// use pgp::crypto::hash::HashAlgorithm;
+/// use pgp::errors::Result;
+/// use pgp::types::{ExternalKey, Signer};
+/// use pgp::Message;
+///
+/// fn sign_text(signer: &impl Signer, text: &str) -> Result<Message> {
+///     Message::new_literal("", text).sign(&ExternalKey(signer), String::new, HashAlgorithm::SHA2_256)
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExternalKey<S>(pub S);
+
+impl<S: KeyTrait> KeyTrait for ExternalKey<S> {
+    fn fingerprint(&self) -> Fingerprint {
+        self.0.fingerprint()
+    }
+
+    fn key_id(&self) -> KeyId {
+        self.0.key_id()
+    }
+
+    fn algorithm(&self) -> PublicKeyAlgorithm {
+        self.0.algorithm()
+    }
+}
+
+impl<S: PublicKeyTrait> PublicKeyTrait for ExternalKey<S> {
+    fn verify_signature(&self, hash: HashAlgorithm, data: &[u8], sig: &[Mpi]) -> Result<()> {
+        self.0.verify_signature(hash, data, sig)
+    }
+
+    fn encrypt<R: CryptoRng + Rng>(&self, rng: &mut R, plain: &[u8]) -> Result<Vec<Mpi>> {
+        self.0.encrypt(rng, plain)
+    }
+
+    fn to_writer_old(&self, writer: &mut impl io::Write) -> Result<()> {
+        self.0.to_writer_old(writer)
+    }
+}
+
+impl<S: Signer + Clone> SecretKeyTrait for ExternalKey<S> {
+    type PublicKey = S;
+
+    fn unlock<F, G>(&self, _pw: F, _work: G) -> Result<()>
+    where
+        F: PassphraseProvider,
+        G: FnOnce(&SecretKeyRepr) -> Result<()>,
+    {
+        bail!("the secret key material of an external key is not available")
+    }
+
+    fn create_signature<F>(&self, _key_pw: F, hash: HashAlgorithm, data: &[u8]) -> Result<Vec<Mpi>>
+    where
+        F: PassphraseProvider,
+    {
+        self.0.sign(hash, data)
+    }
+
+    fn public_key(&self) -> Self::PublicKey {
+        self.0.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::composed::{sign_detached, Message, SignedPublicKey, SignedSecretKey};
+    use crate::packet::{PublicKey, UserId};
+    use crate::test_util::{encryption_subkey, key_params};
+
+    /// Stands in for a hardware token, which holds the secret key and counts its signatures.
+    #[derive(Debug)]
+    struct Token {
+        key: SignedSecretKey,
+        public: PublicKey,
+        signatures: Cell<usize>,
+    }
+
+    impl KeyTrait for Token {
+        fn fingerprint(&self) -> Fingerprint {
+            self.public.fingerprint()
+        }
+
+        fn key_id(&self) -> KeyId {
+            self.public.key_id()
+        }
+
+        fn algorithm(&self) -> PublicKeyAlgorithm {
+            self.public.algorithm()
+        }
+    }
+
+    impl PublicKeyTrait for Token {
+        fn verify_signature(&self, hash: HashAlgorithm, data: &[u8], sig: &[Mpi]) -> Result<()> {
+            self.public.verify_signature(hash, data, sig)
+        }
+
+        fn encrypt<R: CryptoRng + Rng>(&self, rng: &mut R, plain: &[u8]) -> Result<Vec<Mpi>> {
+            self.public.encrypt(rng, plain)
+        }
+
+        fn to_writer_old(&self, writer: &mut impl io::Write) -> Result<()> {
+            self.public.to_writer_old(writer)
+        }
+    }
+
+    impl Signer for Token {
+        fn sign(&self, hash: HashAlgorithm, digest: &[u8]) -> Result<Vec<Mpi>> {
+            self.signatures.set(self.signatures.get() + 1);
+            self.key
+                .create_signature(|| "token pin".into(), hash, digest)
+        }
+    }
+
+    #[test]
+    fn test_external_key() {
+        let key = key_params("Alice <alice@example.com>")
+            .passphrase(Some("token pin".into()))
+            .subkey(encryption_subkey())
+            .build()
+            .unwrap()
+            .generate_signed()
+            .unwrap();
+        let cert: SignedPublicKey = key.clone().into();
+        let token = Token {
+            public: key.primary_key.public_key(),
+            key,
+            signatures: Cell::new(0),
+        };
+        let external = ExternalKey(&token);
+
+        let msg = Message::new_literal("", "hello")
+            .sign(&external, String::new, HashAlgorithm::SHA2_256)
+            .unwrap();
+        msg.verify(&cert).unwrap();
+
+        let signature = sign_detached(
+            &b"hello"[..],
+            &external,
+            String::new,
+            HashAlgorithm::SHA2_512,
+        )
+        .unwrap();
+        signature.verify(&cert, b"hello").unwrap();
+        assert_eq!(token.signatures.get(), 2);
+
+        // certifications are built the same way
+        let user = UserId::from_str(Default::default(), "Alice <alice@work.com>")
+            .sign(&external, String::new)
+            .unwrap();
+        user.verify(&cert.primary_key).unwrap();
+        assert_eq!(token.signatures.get(), 3);
+
+        // the secret key material is not available for decryption
+        assert!(external.unlock(String::new, |_| Ok(())).is_err());
+    }
+}
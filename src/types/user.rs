@@ -1,7 +1,7 @@
 use std::io;
 
 use crate::errors::Result;
-use crate::packet::{write_packet, Signature, UserAttribute, UserId};
+use crate::packet::{write_packet, Experimental, Signature, UserAttribute, UserId};
 use crate::ser::Serialize;
 use crate::types::{PublicKeyTrait, Tag};
 
@@ -9,6 +9,8 @@ use crate::types::{PublicKeyTrait, Tag};
 pub struct SignedUser {
     pub id: UserId,
     pub signatures: Vec<Signature>,
+    /// Packets with private or experimental tags, that followed this component.
+    pub experimental: Vec<Experimental>,
 }
 
 impl SignedUser {
@@ -28,7 +30,11 @@ impl SignedUser {
             })
             .collect();
 
-        SignedUser { id, signatures }
+        SignedUser {
+            id,
+            signatures,
+            experimental: Vec::new(),
+        }
     }
 
     /// Verify all signatures. If signatures is empty, this fails.
@@ -54,6 +60,9 @@ impl Serialize for SignedUser {
         for sig in &self.signatures {
             write_packet(writer, sig)?;
         }
+        for packet in &self.experimental {
+            write_packet(writer, packet)?;
+        }
 
         Ok(())
     }
@@ -63,6 +72,8 @@ impl Serialize for SignedUser {
 pub struct SignedUserAttribute {
     pub attr: UserAttribute,
     pub signatures: Vec<Signature>,
+    /// Packets with private or experimental tags, that followed this component.
+    pub experimental: Vec<Experimental>,
 }
 
 impl SignedUserAttribute {
@@ -82,7 +93,11 @@ impl SignedUserAttribute {
             })
             .collect();
 
-        SignedUserAttribute { attr, signatures }
+        SignedUserAttribute {
+            attr,
+            signatures,
+            experimental: Vec::new(),
+        }
     }
 
     /// Verify all signatures. If signatures is empty, this fails.
@@ -104,6 +119,9 @@ impl Serialize for SignedUserAttribute {
         for sig in &self.signatures {
             write_packet(writer, sig)?;
         }
+        for packet in &self.experimental {
+            write_packet(writer, packet)?;
+        }
 
         Ok(())
     }
@@ -0,0 +1,340 @@
+//! # VKS
+//!
+//! A client for the REST API of verifying keyservers, such as
+//! [keys.openpgp.org](https://keys.openpgp.org/about/api).
+//!
+//! Like the [HKP client](crate::hkp), [`Vks`] only builds requests and parses responses,
+//! while [`VksClient`] sends them using an [`HttpTransport`].
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::composed::{Deserializable, SignedPublicKey};
+use crate::errors::Result;
+use crate::hkp::{url_encode, HttpMethod, HttpRequest, HttpResponse, HttpTransport};
+use crate::types::{Fingerprint, KeyId};
+
+const API_PATH: &str = "/vks/v1";
+
+/// The publication state of an email address of an uploaded key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailStatus {
+    /// Not published, verification has not been requested.
+    Unpublished,
+    /// A verification email was sent, but not yet confirmed.
+    Pending,
+    Published,
+    Revoked,
+}
+
+/// The response to an upload or a verification request.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct UploadResponse {
+    /// The fingerprint of the uploaded key.
+    pub key_fpr: String,
+    /// Identifies the upload in subsequent verification requests.
+    pub token: String,
+    pub status: BTreeMap<String, EmailStatus>,
+}
+
+#[derive(Serialize)]
+struct UploadRequest<'a> {
+    keytext: &'a str,
+}
+
+#[derive(Serialize)]
+struct VerifyRequest<'a> {
+    token: &'a str,
+    addresses: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// Builds requests for a verifying keyserver, and parses its responses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vks {
+    base_url: String,
+}
+
+impl Vks {
+    /// Creates a keyserver for `base_url`, e.g. `https://keys.openpgp.org`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        let mut base_url = base_url.into();
+        while base_url.ends_with('/') {
+            base_url.pop();
+        }
+
+        Vks { base_url }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn by_fingerprint_request(&self, fingerprint: &Fingerprint) -> HttpRequest {
+        self.get_request(&format!("by-fingerprint/{:X}", fingerprint))
+    }
+
+    pub fn by_key_id_request(&self, key_id: &KeyId) -> HttpRequest {
+        self.get_request(&format!("by-keyid/{}", key_id))
+    }
+
+    pub fn by_email_request(&self, email: &str) -> HttpRequest {
+        self.get_request(&format!("by-email/{}", url_encode(email)))
+    }
+
+    /// Builds a request uploading `key` to the keyserver.
+    ///
+    /// The server only publishes User IDs after their email address was verified,
+    /// see [`verify_request`](Self::verify_request).
+    pub fn upload_request(&self, key: &SignedPublicKey) -> Result<HttpRequest> {
+        let keytext = key.to_armored_string(None)?;
+        self.post_request("upload", &UploadRequest { keytext: &keytext })
+    }
+
+    /// Builds a request to send verification emails for `addresses` of an uploaded key.
+    pub fn verify_request(&self, token: &str, addresses: &[String]) -> Result<HttpRequest> {
+        self.post_request("request-verify", &VerifyRequest { token, addresses })
+    }
+
+    /// Parses the response to a lookup.
+    ///
+    /// Returns `None` if the server does not know the key.
+    pub fn parse_key_response(&self, response: &HttpResponse) -> Result<Option<SignedPublicKey>> {
+        if response.status == 404 {
+            return Ok(None);
+        }
+        Self::check_status(response)?;
+        let (key, _headers) = SignedPublicKey::from_string(std::str::from_utf8(&response.body)?)?;
+
+        Ok(Some(key))
+    }
+
+    /// Parses the response to an upload or a verification request.
+    pub fn parse_upload_response(&self, response: &HttpResponse) -> Result<UploadResponse> {
+        Self::check_status(response)?;
+
+        serde_json::from_slice(&response.body)
+            .map_err(|err| format_err!("invalid keyserver response: {}", err))
+    }
+
+    fn get_request(&self, path: &str) -> HttpRequest {
+        HttpRequest {
+            method: HttpMethod::Get,
+            url: format!("{}{}/{}", self.base_url, API_PATH, path),
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    fn post_request(&self, path: &str, body: &impl Serialize) -> Result<HttpRequest> {
+        let body = serde_json::to_vec(body)
+            .map_err(|err| format_err!("failed to encode request: {}", err))?;
+
+        Ok(HttpRequest {
+            method: HttpMethod::Post,
+            url: format!("{}{}/{}", self.base_url, API_PATH, path),
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body,
+        })
+    }
+
+    fn check_status(response: &HttpResponse) -> Result<()> {
+        if (200..300).contains(&response.status) {
+            return Ok(());
+        }
+
+        // errors are usually explained in a json object
+        match serde_json::from_slice::<ErrorResponse>(&response.body) {
+            Ok(err) => bail!(
+                "keyserver request failed: status {}: {}",
+                response.status,
+                err.error
+            ),
+            Err(_) => bail!("keyserver request failed: status {}", response.status),
+        }
+    }
+}
+
+/// A blocking client for a verifying keyserver.
+#[derive(Debug, Clone)]
+pub struct VksClient<T> {
+    vks: Vks,
+    transport: T,
+}
+
+impl<T: HttpTransport> VksClient<T> {
+    pub fn new(vks: Vks, transport: T) -> Self {
+        VksClient { vks, transport }
+    }
+
+    pub fn vks(&self) -> &Vks {
+        &self.vks
+    }
+
+    pub fn lookup_by_fingerprint(
+        &self,
+        fingerprint: &Fingerprint,
+    ) -> Result<Option<SignedPublicKey>> {
+        let response = self
+            .transport
+            .send(self.vks.by_fingerprint_request(fingerprint))?;
+        self.vks.parse_key_response(&response)
+    }
+
+    pub fn lookup_by_key_id(&self, key_id: &KeyId) -> Result<Option<SignedPublicKey>> {
+        let response = self.transport.send(self.vks.by_key_id_request(key_id))?;
+        self.vks.parse_key_response(&response)
+    }
+
+    /// Fetches the key with a verified User ID for `email`.
+    pub fn lookup_by_email(&self, email: &str) -> Result<Option<SignedPublicKey>> {
+        let response = self.transport.send(self.vks.by_email_request(email))?;
+        self.vks.parse_key_response(&response)
+    }
+
+    /// Uploads `key` to the keyserver.
+    pub fn upload_key(&self, key: &SignedPublicKey) -> Result<UploadResponse> {
+        let response = self.transport.send(self.vks.upload_request(key)?)?;
+        self.vks.parse_upload_response(&response)
+    }
+
+    /// Requests verification emails for `addresses` of the upload identified by `token`.
+    pub fn request_verify(&self, token: &str, addresses: &[String]) -> Result<UploadResponse> {
+        let response = self
+            .transport
+            .send(self.vks.verify_request(token, addresses)?)?;
+        self.vks.parse_upload_response(&response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::test_util::gen_public_key;
+    use crate::types::KeyTrait;
+
+    /// Records all requests, and answers them with a fixed response.
+    struct MockTransport {
+        requests: RefCell<Vec<HttpRequest>>,
+        response: HttpResponse,
+    }
+
+    impl MockTransport {
+        fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+            MockTransport {
+                requests: RefCell::new(Vec::new()),
+                response: HttpResponse {
+                    status,
+                    body: body.into(),
+                },
+            }
+        }
+    }
+
+    impl HttpTransport for &MockTransport {
+        fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+            self.requests.borrow_mut().push(request);
+            Ok(self.response.clone())
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        let key = gen_public_key("Alice <alice@example.com>");
+        let transport = MockTransport::new(200, key.to_armored_string(None).unwrap());
+        let client = VksClient::new(Vks::new("https://keys.example.com/"), &transport);
+
+        assert_eq!(
+            client.lookup_by_fingerprint(&key.fingerprint()).unwrap(),
+            Some(key.clone())
+        );
+        assert_eq!(
+            client.lookup_by_key_id(&key.key_id()).unwrap(),
+            Some(key.clone())
+        );
+        assert_eq!(
+            client.lookup_by_email("alice@example.com").unwrap(),
+            Some(key.clone())
+        );
+
+        let urls: Vec<String> = transport
+            .requests
+            .borrow()
+            .iter()
+            .map(|r| r.url.clone())
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                format!(
+                    "https://keys.example.com/vks/v1/by-fingerprint/{:X}",
+                    key.fingerprint()
+                ),
+                format!("https://keys.example.com/vks/v1/by-keyid/{}", key.key_id()),
+                "https://keys.example.com/vks/v1/by-email/alice%40example.com".to_string(),
+            ]
+        );
+
+        let transport = MockTransport::new(404, "Not found");
+        let client = VksClient::new(Vks::new("https://keys.example.com"), &transport);
+        assert_eq!(client.lookup_by_email("bob@example.com").unwrap(), None);
+    }
+
+    #[test]
+    fn test_upload() {
+        let key = gen_public_key("Alice <alice@example.com>");
+        let body = format!(
+            r#"{{"key_fpr":"{:X}","token":"secret","status":{{"alice@example.com":"unpublished"}}}}"#,
+            key.fingerprint()
+        );
+        let transport = MockTransport::new(200, body);
+        let client = VksClient::new(Vks::new("https://keys.example.com"), &transport);
+
+        let upload = client.upload_key(&key).unwrap();
+        assert_eq!(upload.token, "secret");
+        assert_eq!(
+            upload.status.get("alice@example.com"),
+            Some(&EmailStatus::Unpublished)
+        );
+        client
+            .request_verify(&upload.token, &["alice@example.com".to_string()])
+            .unwrap();
+
+        let requests = transport.requests.borrow();
+        assert_eq!(requests[0].method, HttpMethod::Post);
+        assert_eq!(requests[0].url, "https://keys.example.com/vks/v1/upload");
+        let upload_body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        let keytext = upload_body["keytext"].as_str().unwrap();
+        assert_eq!(SignedPublicKey::from_string(keytext).unwrap().0, key);
+
+        assert_eq!(
+            requests[1].url,
+            "https://keys.example.com/vks/v1/request-verify"
+        );
+        let verify_body: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+        assert_eq!(
+            verify_body,
+            serde_json::json!({"token": "secret", "addresses": ["alice@example.com"]})
+        );
+    }
+
+    #[test]
+    fn test_error_response() {
+        let key = gen_public_key("Alice <alice@example.com>");
+        let transport = MockTransport::new(400, r#"{"error":"Invalid key"}"#);
+        let client = VksClient::new(Vks::new("https://keys.example.com"), &transport);
+
+        let err = client.upload_key(&key).unwrap_err();
+        assert!(err.to_string().contains("Invalid key"));
+    }
+}
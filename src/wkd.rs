@@ -0,0 +1,275 @@
+//! # WKD
+//!
+//! Support for the [Web Key Directory](https://datatracker.ietf.org/doc/html/draft-koch-openpgp-webkey-service),
+//! which publishes keys on the web server of the domain of their email addresses.
+//!
+//! Keys are fetched using the same [`HttpTransport`] as the [HKP client](crate::hkp).
+
+use std::path::PathBuf;
+
+use sha1::{Digest, Sha1};
+
+use crate::composed::{Deserializable, SignedPublicKey};
+use crate::errors::Result;
+use crate::hkp::{url_encode, HttpMethod, HttpRequest, HttpResponse, HttpTransport};
+use crate::ser::Serialize;
+
+const ZBASE32_ALPHABET: &[u8; 32] = b"ybndrfg8ejkmcpqxot1uwisza345h769";
+
+/// The two ways a domain can publish its keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    /// Served from the `openpgpkey` subdomain.
+    Advanced,
+    /// Served from the domain itself.
+    Direct,
+}
+
+/// An email address, split into the parts used for WKD lookups.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WkdAddress {
+    local_part: String,
+    domain: String,
+}
+
+impl WkdAddress {
+    pub fn parse(email: &str) -> Result<Self> {
+        let Some((local_part, domain)) = email.trim().rsplit_once('@') else {
+            bail!("invalid email address: {}", email);
+        };
+        ensure!(
+            !local_part.is_empty() && !domain.is_empty(),
+            "invalid email address: {}",
+            email
+        );
+
+        Ok(WkdAddress {
+            local_part: local_part.to_string(),
+            domain: domain.to_lowercase(),
+        })
+    }
+
+    pub fn local_part(&self) -> &str {
+        &self.local_part
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// The z-base-32 encoded SHA-1 hash of the lowercased local part.
+    pub fn hash(&self) -> String {
+        let digest = Sha1::digest(self.local_part.to_lowercase().as_bytes());
+        zbase32_encode(&digest)
+    }
+
+    /// The URL of the key, for the given `variant`.
+    pub fn url(&self, variant: Variant) -> String {
+        let host = match variant {
+            Variant::Advanced => format!("openpgpkey.{}", self.domain),
+            Variant::Direct => self.domain.clone(),
+        };
+
+        format!(
+            "https://{}/{}?l={}",
+            host,
+            self.path(variant),
+            url_encode(&self.local_part)
+        )
+    }
+
+    /// The path of the key on the web server, relative to its document root.
+    pub fn publication_path(&self, variant: Variant) -> PathBuf {
+        PathBuf::from(self.path(variant))
+    }
+
+    fn path(&self, variant: Variant) -> String {
+        match variant {
+            Variant::Advanced => {
+                format!(".well-known/openpgpkey/{}/hu/{}", self.domain, self.hash())
+            }
+            Variant::Direct => format!(".well-known/openpgpkey/hu/{}", self.hash()),
+        }
+    }
+}
+
+/// Builds the request for the key of `email`, for the given `variant`.
+pub fn request(email: &str, variant: Variant) -> Result<HttpRequest> {
+    Ok(HttpRequest {
+        method: HttpMethod::Get,
+        url: WkdAddress::parse(email)?.url(variant),
+        headers: Vec::new(),
+        body: Vec::new(),
+    })
+}
+
+/// Parses the response to a [`request`] for `email`.
+///
+/// Returns the first key with a User ID for `email`, or `None` if the server does not publish one.
+pub fn parse_response(email: &str, response: &HttpResponse) -> Result<Option<SignedPublicKey>> {
+    if response.status == 404 {
+        return Ok(None);
+    }
+    ensure!(
+        (200..300).contains(&response.status),
+        "key lookup failed: status {}",
+        response.status
+    );
+
+    let email = email.trim().to_lowercase();
+    for key in SignedPublicKey::from_bytes_many(&response.body[..]) {
+        let key = key?;
+        if has_email(&key, &email) {
+            return Ok(Some(key));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Fetches the key for `email`, trying the advanced method first and falling back to the
+/// direct method.
+pub fn fetch<T: HttpTransport>(transport: &T, email: &str) -> Result<Option<SignedPublicKey>> {
+    let advanced = transport
+        .send(request(email, Variant::Advanced)?)
+        .and_then(|response| parse_response(email, &response));
+    match advanced {
+        Ok(Some(key)) => Ok(Some(key)),
+        // most domains do not have the subdomain, which fails the connection
+        Ok(None) | Err(_) => {
+            let response = transport.send(request(email, Variant::Direct)?)?;
+            parse_response(email, &response)
+        }
+    }
+}
+
+/// Serializes `key` for publication under `email`.
+///
+/// The key is minimized, and all other User IDs and all User Attributes are removed, as the
+/// directory must only reveal the User ID for the requested address.
+pub fn minimize(key: &SignedPublicKey, email: &str) -> Result<Vec<u8>> {
+    key.clone().filter_email(email)?.to_bytes()
+}
+
+fn has_email(key: &SignedPublicKey, email: &str) -> bool {
+    key.details
+        .users
+        .iter()
+        .any(|user| user.id.email_normalized().as_deref() == Some(email))
+}
+
+fn zbase32_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity((data.len() * 8 + 4) / 5);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+    for &b in data {
+        buffer = (buffer << 8) | u16::from(b);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(char::from(
+                ZBASE32_ALPHABET[usize::from((buffer >> bits) & 0x1f)],
+            ));
+        }
+    }
+    if bits > 0 {
+        encoded.push(char::from(
+            ZBASE32_ALPHABET[usize::from((buffer << (5 - bits)) & 0x1f)],
+        ));
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::test_util::gen_key;
+
+    /// Answers requests to a single URL with a fixed body, and all others with an error.
+    struct MockTransport {
+        requests: RefCell<Vec<String>>,
+        url: String,
+        body: Vec<u8>,
+    }
+
+    impl HttpTransport for MockTransport {
+        fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+            self.requests.borrow_mut().push(request.url.clone());
+            ensure_eq!(request.url, self.url, "connection failed");
+
+            Ok(HttpResponse {
+                status: 200,
+                body: self.body.clone(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_wkd_address() {
+        let address = WkdAddress::parse("Joe.Doe@Example.ORG").unwrap();
+        assert_eq!(address.hash(), "iy9q119eutrkn8s1mk4r39qejnbu3n5q");
+        assert_eq!(
+            address.url(Variant::Advanced),
+            "https://openpgpkey.example.org/.well-known/openpgpkey/example.org/hu/iy9q119eutrkn8s1mk4r39qejnbu3n5q?l=Joe.Doe"
+        );
+        assert_eq!(
+            address.url(Variant::Direct),
+            "https://example.org/.well-known/openpgpkey/hu/iy9q119eutrkn8s1mk4r39qejnbu3n5q?l=Joe.Doe"
+        );
+        assert_eq!(
+            address.publication_path(Variant::Direct),
+            PathBuf::from(".well-known/openpgpkey/hu/iy9q119eutrkn8s1mk4r39qejnbu3n5q")
+        );
+
+        assert!(WkdAddress::parse("example.org").is_err());
+        assert!(WkdAddress::parse("@example.org").is_err());
+    }
+
+    #[test]
+    fn test_fetch() {
+        let key = gen_key("Alice <alice@example.com>")
+            .add_user_id("Alice <alice@work.example.com>", String::new)
+            .unwrap();
+        let key: SignedPublicKey = key.into();
+        let body = minimize(&key, "Alice@Example.com").unwrap();
+
+        let published = SignedPublicKey::from_bytes(&body[..]).unwrap();
+        assert_eq!(published.details.users.len(), 1);
+        assert_eq!(
+            published.details.users[0].id.email_normalized().unwrap(),
+            "alice@example.com"
+        );
+        assert!(minimize(&key, "bob@example.com").is_err());
+
+        // only the direct method is available
+        let address = WkdAddress::parse("alice@example.com").unwrap();
+        let transport = MockTransport {
+            requests: RefCell::new(Vec::new()),
+            url: address.url(Variant::Direct),
+            body,
+        };
+        assert_eq!(
+            fetch(&transport, "alice@example.com").unwrap(),
+            Some(published)
+        );
+        assert_eq!(
+            *transport.requests.borrow(),
+            vec![address.url(Variant::Advanced), address.url(Variant::Direct)]
+        );
+
+        // the published key does not match the requested address
+        let transport = MockTransport {
+            requests: RefCell::new(Vec::new()),
+            url: WkdAddress::parse("bob@example.com")
+                .unwrap()
+                .url(Variant::Direct),
+            body: transport.body,
+        };
+        assert_eq!(fetch(&transport, "bob@example.com").unwrap(), None);
+    }
+}
@@ -0,0 +1,710 @@
+//! # Web of trust
+//!
+//! Computes the validity of User IDs from certifications, starting at a set of trust roots,
+//! similar to the trust model of GnuPG.
+//!
+//! - Keys with [`OwnerTrust::Ultimate`] are the roots, all of their User IDs are valid.
+//! - A certification of a User ID by a trusted introducer contributes the trust amount of the
+//!   introducer to its validity. The amounts of independent introducers add up, `120` is full
+//!   validity. With the amounts of RFC 4880, that is one fully trusted introducer, or two
+//!   partially trusted ones.
+//! - A certification with a Trust Signature subpacket makes the certified key a trusted
+//!   introducer, with the given depth and amount, limited by the introducer that made it.
+//!   Depth `1` is a trusted introducer, depth `2` a meta introducer, that can make trusted
+//!   introducers in turn, and so on. Regular Expression subpackets limit the User IDs the
+//!   trust applies to.
+//! - A key with a fully valid User ID and an [`OwnerTrust`] of [`Marginal`](OwnerTrust::Marginal)
+//!   or [`Full`](OwnerTrust::Full) is a trusted introducer as well, without the right to make
+//!   further introducers.
+//!
+//! Only certifications that are valid at the reference time count, by keys in the keyring
+//! that are valid themselves. A newer certification by the same key replaces an older one,
+//! and a certification revocation withdraws it.
+//!
+//! ```no_run
+//! # use pgp::composed::SignedPublicKey;
+//! # use pgp::types::KeyTrait;
+//! # use pgp::wot::{self, OwnerTrust, TrustRoots};
+//! # let keyring: Vec<SignedPublicKey> = Vec::new();
+//! # let me = &keyring[0];
+//! let roots = TrustRoots::new().with_ownertrust(me.fingerprint(), OwnerTrust::Ultimate);
+//! for validity in wot::validate(&keyring, &roots, "Alice <alice@example.com>") {
+//!     if validity.is_full() {
+//!         println!("{:x}", validity.cert().fingerprint());
+//!     }
+//! }
+//! ```
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+
+use crate::composed::{SignedPublicKey, ValidCert};
+use crate::formats::trustdb::{OwnerTrustEntry, TrustDb, TrustLevel};
+use crate::packet::{PacketTrait, Signature, SignatureType, SubpacketData};
+use crate::types::{Fingerprint, KeyId, KeyTrait, SignedUser};
+
+/// The trust amount of full trust and validity.
+pub const FULL: u8 = 120;
+
+/// The trust amount of partial trust.
+pub const PARTIAL: u8 = 60;
+
+/// The default maximum length of certification chains, as in GnuPG.
+pub const DEFAULT_MAX_DEPTH: u8 = 5;
+
+/// How much the owner of a key is trusted to certify other keys correctly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OwnerTrust {
+    /// Not decided.
+    #[default]
+    Unknown,
+    /// Certifications of the key are ignored.
+    Never,
+    /// Certifications of the key count as partial.
+    Marginal,
+    /// Certifications of the key count as full.
+    Full,
+    /// The key is a trust root.
+    Ultimate,
+}
+
+impl OwnerTrust {
+    /// The trust amount of certifications by a key with this owner trust.
+    pub fn amount(self) -> u8 {
+        match self {
+            OwnerTrust::Unknown | OwnerTrust::Never => 0,
+            OwnerTrust::Marginal => PARTIAL,
+            OwnerTrust::Full | OwnerTrust::Ultimate => FULL,
+        }
+    }
+}
+
+/// Disabled keys are never trusted.
+impl From<&OwnerTrustEntry> for OwnerTrust {
+    fn from(entry: &OwnerTrustEntry) -> Self {
+        if entry.disabled {
+            return OwnerTrust::Never;
+        }
+
+        match entry.trust {
+            TrustLevel::Never => OwnerTrust::Never,
+            TrustLevel::Marginal => OwnerTrust::Marginal,
+            TrustLevel::Full => OwnerTrust::Full,
+            TrustLevel::Ultimate => OwnerTrust::Ultimate,
+            _ => OwnerTrust::Unknown,
+        }
+    }
+}
+
+/// The trust roots and the owner trust of other keys.
+#[derive(Debug, Clone)]
+pub struct TrustRoots {
+    ownertrust: HashMap<Fingerprint, OwnerTrust>,
+    max_depth: u8,
+}
+
+impl Default for TrustRoots {
+    fn default() -> Self {
+        TrustRoots {
+            ownertrust: HashMap::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+}
+
+impl TrustRoots {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses the owner trust exported from GnuPG, see
+    /// [`parse_ownertrust`](crate::formats::trustdb::parse_ownertrust).
+    pub fn from_gnupg(entries: &[OwnerTrustEntry]) -> Self {
+        entries.iter().fold(Self::new(), |roots, entry| {
+            roots.with_ownertrust(entry.fingerprint.clone(), entry.into())
+        })
+    }
+
+    /// Uses the owner trust and the maximum certification depth of a GnuPG trust database.
+    pub fn from_trustdb(db: &TrustDb) -> Self {
+        Self::from_gnupg(&db.ownertrust()).with_max_depth(db.max_cert_depth)
+    }
+
+    /// Sets the owner trust of the key with the primary key fingerprint `fingerprint`.
+    pub fn with_ownertrust(mut self, fingerprint: Fingerprint, trust: OwnerTrust) -> Self {
+        self.ownertrust.insert(fingerprint, trust);
+        self
+    }
+
+    /// Sets the maximum length of certification chains, starting at a root.
+    pub fn with_max_depth(mut self, max_depth: u8) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// The owner trust of the key with the primary key fingerprint `fingerprint`.
+    pub fn ownertrust(&self, fingerprint: &Fingerprint) -> OwnerTrust {
+        self.ownertrust
+            .get(fingerprint)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The fingerprints of the keys with [`OwnerTrust::Ultimate`].
+    pub fn roots(&self) -> impl Iterator<Item = &Fingerprint> {
+        self.ownertrust
+            .iter()
+            .filter(|(_, trust)| **trust == OwnerTrust::Ultimate)
+            .map(|(fingerprint, _)| fingerprint)
+    }
+}
+
+/// The validity of a certificate for a User ID.
+#[derive(Debug, Clone)]
+pub struct Validity<'a> {
+    cert: &'a SignedPublicKey,
+    amount: u8,
+    introducers: Vec<Fingerprint>,
+}
+
+impl<'a> Validity<'a> {
+    pub fn cert(&self) -> &'a SignedPublicKey {
+        self.cert
+    }
+
+    /// The trust amount, from `0` to [`FULL`].
+    pub fn amount(&self) -> u8 {
+        self.amount
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.amount >= FULL
+    }
+
+    pub fn is_partial(&self) -> bool {
+        self.amount > 0 && self.amount < FULL
+    }
+
+    /// The fingerprints of the trusted introducers that certified the User ID, empty for
+    /// roots.
+    pub fn introducers(&self) -> &[Fingerprint] {
+        &self.introducers
+    }
+}
+
+/// Computes the validity of all certificates in `keyring` with the User ID `target_uid`,
+/// at the current time.
+///
+/// The result is sorted by decreasing validity and also contains certificates that are not
+/// valid at all.
+pub fn validate<'a>(
+    keyring: &'a [SignedPublicKey],
+    roots: &TrustRoots,
+    target_uid: &str,
+) -> Vec<Validity<'a>> {
+    validate_at(keyring, roots, target_uid, Utc::now())
+}
+
+/// Same as [`validate`], at the reference time `at`.
+pub fn validate_at<'a>(
+    keyring: &'a [SignedPublicKey],
+    roots: &TrustRoots,
+    target_uid: &str,
+    at: DateTime<Utc>,
+) -> Vec<Validity<'a>> {
+    let network = Network::new(keyring, roots, at);
+
+    let mut result: Vec<_> = network
+        .certs
+        .iter()
+        .enumerate()
+        .flat_map(|(i, cert)| {
+            cert.users
+                .iter()
+                .filter(|user| user.id == target_uid)
+                .map(move |user| (i, user))
+        })
+        .map(|(i, user)| {
+            let (amount, introducers) = network.validity(i, user);
+            Validity {
+                cert: network.certs[i].key,
+                amount,
+                introducers: introducers
+                    .into_iter()
+                    .map(|j| network.certs[j].key.fingerprint())
+                    .collect(),
+            }
+        })
+        .collect();
+    result.sort_by_key(|validity| Reverse(validity.amount));
+
+    result
+}
+
+/// The valid certificates of a keyring, with the certifications between them.
+struct Network<'a> {
+    certs: Vec<Node<'a>>,
+}
+
+struct Node<'a> {
+    key: &'a SignedPublicKey,
+    ownertrust: OwnerTrust,
+    users: Vec<User>,
+    grants: Vec<Grant>,
+}
+
+/// A valid User ID and the effective certifications by other keys in the keyring.
+struct User {
+    id: String,
+    certifications: Vec<Certification>,
+}
+
+struct Certification {
+    /// The index of the issuer in [`Network::certs`].
+    issuer: usize,
+    /// The depth and amount of a Trust Signature subpacket.
+    trust: Option<(u8, u8)>,
+    /// The Regular Expression subpackets, in case of a Trust Signature.
+    regexes: Vec<Regex>,
+}
+
+/// The right of a key to act as trusted introducer.
+#[derive(Debug, Clone)]
+struct Grant {
+    amount: u8,
+    /// `1` for an introducer, more for meta introducers.
+    depth: u8,
+    /// The regular expressions of all trust signatures on the path from the root, a User ID
+    /// must match one of each.
+    scopes: Vec<Vec<Regex>>,
+}
+
+impl Grant {
+    fn matches(&self, user_id: &str) -> bool {
+        self.scopes
+            .iter()
+            .all(|scope| scope.iter().any(|regex| regex.is_match(user_id)))
+    }
+}
+
+impl PartialEq for Grant {
+    fn eq(&self, other: &Self) -> bool {
+        let patterns = |grant: &Grant| -> Vec<Vec<String>> {
+            grant
+                .scopes
+                .iter()
+                .map(|scope| scope.iter().map(|r| r.as_str().to_string()).collect())
+                .collect()
+        };
+
+        self.amount == other.amount
+            && self.depth == other.depth
+            && patterns(self) == patterns(other)
+    }
+}
+
+impl<'a> Network<'a> {
+    fn new(keyring: &'a [SignedPublicKey], roots: &TrustRoots, at: DateTime<Utc>) -> Self {
+        let valid: Vec<_> = keyring
+            .iter()
+            .filter_map(|key| ValidCert::new(key, at).ok())
+            .collect();
+
+        let mut by_fingerprint = HashMap::new();
+        let mut by_key_id = HashMap::new();
+        for (i, cert) in valid.iter().enumerate() {
+            by_fingerprint.insert(cert.key().fingerprint(), i);
+            by_key_id.insert(cert.key().key_id(), i);
+        }
+        let issuer = |sig: &Signature| -> Option<usize> {
+            sig.issuer_fingerprint()
+                .into_iter()
+                .find_map(|fp| by_fingerprint.get(fp))
+                .or_else(|| {
+                    sig.issuer()
+                        .into_iter()
+                        .find_map(|key_id: &KeyId| by_key_id.get(key_id))
+                })
+                .copied()
+        };
+
+        let mut certs: Vec<_> = valid
+            .iter()
+            .enumerate()
+            .map(|(i, cert)| {
+                let key = cert.key();
+                let users = cert
+                    .user_ids()
+                    .iter()
+                    .map(|user| User {
+                        id: user.user().id.id().to_string(),
+                        certifications: certifications(&valid, i, user.user(), &issuer, &at),
+                    })
+                    .collect();
+
+                Node {
+                    key,
+                    ownertrust: roots.ownertrust(&key.fingerprint()),
+                    users,
+                    grants: Vec::new(),
+                }
+            })
+            .collect();
+
+        for node in &mut certs {
+            if node.ownertrust == OwnerTrust::Ultimate {
+                node.grants.push(Grant {
+                    amount: FULL,
+                    depth: roots.max_depth,
+                    scopes: Vec::new(),
+                });
+            }
+        }
+
+        let mut network = Network { certs };
+        network.propagate();
+        network
+    }
+
+    /// Adds grants until nothing changes anymore.
+    ///
+    /// This terminates, as every new grant from a trust signature has a smaller depth than the
+    /// one it is derived from.
+    fn propagate(&mut self) {
+        loop {
+            let mut new = Vec::new();
+            for (i, node) in self.certs.iter().enumerate() {
+                if node.ownertrust == OwnerTrust::Never {
+                    continue;
+                }
+
+                // trusted introducers by owner trust, once their key is valid
+                if matches!(node.ownertrust, OwnerTrust::Marginal | OwnerTrust::Full)
+                    && node
+                        .users
+                        .iter()
+                        .any(|user| self.validity(i, user).0 >= FULL)
+                {
+                    new.push((
+                        i,
+                        Grant {
+                            amount: node.ownertrust.amount(),
+                            depth: 1,
+                            scopes: Vec::new(),
+                        },
+                    ));
+                }
+
+                // trusted introducers by trust signatures
+                for user in &node.users {
+                    for certification in &user.certifications {
+                        let Some((depth, amount)) = certification.trust else {
+                            continue;
+                        };
+                        if depth == 0 || amount == 0 {
+                            continue;
+                        }
+
+                        for grant in &self.certs[certification.issuer].grants {
+                            if grant.depth < 2 || !grant.matches(&user.id) {
+                                continue;
+                            }
+
+                            let mut scopes = grant.scopes.clone();
+                            if !certification.regexes.is_empty() {
+                                scopes.push(certification.regexes.clone());
+                            }
+                            new.push((
+                                i,
+                                Grant {
+                                    amount: amount.min(grant.amount),
+                                    depth: depth.min(grant.depth - 1),
+                                    scopes,
+                                },
+                            ));
+                        }
+                    }
+                }
+            }
+
+            let mut changed = false;
+            for (i, grant) in new {
+                if !self.certs[i].grants.contains(&grant) {
+                    self.certs[i].grants.push(grant);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    /// The validity of the User ID `user` of the certificate at index `i`, and the trusted
+    /// introducers that certified it.
+    fn validity(&self, i: usize, user: &User) -> (u8, Vec<usize>) {
+        if self.certs[i].ownertrust == OwnerTrust::Ultimate {
+            return (FULL, Vec::new());
+        }
+
+        let mut amount: u16 = 0;
+        let mut introducers = Vec::new();
+        for certification in &user.certifications {
+            let issuer = &self.certs[certification.issuer];
+            let best = issuer
+                .grants
+                .iter()
+                .filter(|grant| grant.matches(&user.id))
+                .map(|grant| grant.amount)
+                .max();
+            if let Some(best) = best.filter(|best| *best > 0) {
+                amount += u16::from(best);
+                introducers.push(certification.issuer);
+            }
+        }
+
+        (amount.min(FULL.into()) as u8, introducers)
+    }
+}
+
+/// The effective certifications of the User ID `user` of the certificate at index `i`, by
+/// other keys in `certs`.
+fn certifications(
+    certs: &[ValidCert<'_>],
+    i: usize,
+    user: &SignedUser,
+    issuer: &impl Fn(&Signature) -> Option<usize>,
+    at: &DateTime<Utc>,
+) -> Vec<Certification> {
+    let key = &certs[i].key().primary_key;
+
+    let mut sigs: Vec<(usize, &Signature)> = user
+        .signatures
+        .iter()
+        .filter(|sig| sig.is_certification())
+        .filter(|sig| sig.created().is_some_and(|created| created <= at))
+        .filter_map(|sig| issuer(sig).filter(|j| *j != i).map(|j| (j, sig)))
+        .collect();
+    // newest first, so that the first valid signature of each issuer is the effective one
+    sigs.sort_by(|a, b| b.1.created().cmp(&a.1.created()));
+
+    let mut seen = Vec::new();
+    let mut result = Vec::new();
+    for (j, sig) in sigs {
+        if seen.contains(&j) {
+            continue;
+        }
+        let issuer_key = &certs[j].key().primary_key;
+        if sig
+            .verify_third_party_certification(issuer_key, key, user.id.tag(), &user.id)
+            .is_err()
+        {
+            continue;
+        }
+        seen.push(j);
+
+        if sig.typ() == SignatureType::CertRevocation || sig.is_expired_at(at) {
+            continue;
+        }
+        result.push(Certification {
+            issuer: j,
+            trust: sig.trust_signature(),
+            regexes: regexes(sig),
+        });
+    }
+
+    result
+}
+
+/// The Regular Expression subpackets of `sig`.
+///
+/// Invalid expressions are replaced by one that matches nothing, so the trust does not
+/// extend beyond what the issuer intended.
+fn regexes(sig: &Signature) -> Vec<Regex> {
+    sig.config
+        .hashed_subpackets()
+        .filter_map(|p| match &p.data {
+            SubpacketData::RegularExpression(regex) => Some(regex),
+            _ => None,
+        })
+        .map(|regex| {
+            // null terminated, see RFC 4880 section 5.2.3.14
+            let regex = regex.strip_suffix(b"\0").unwrap_or(regex);
+            std::str::from_utf8(regex)
+                .ok()
+                .and_then(|regex| Regex::new(regex).ok())
+                .unwrap_or_else(|| {
+                    warn!("invalid regular expression {:?}", regex);
+                    Regex::new("[^\\s\\S]").expect("static regex")
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use chrono::Duration;
+
+    use super::*;
+    use crate::composed::{CertificationTrust, SignedSecretKey};
+    use crate::formats::trustdb::parse_ownertrust;
+    use crate::packet::CertificationType;
+    use crate::test_util::gen_key;
+
+    /// Adds a certification by `signer` to the first User ID of `key`.
+    fn certify(signer: &SignedSecretKey, key: &mut SignedPublicKey, trust: Option<(u8, u8, &str)>) {
+        let trust = trust.map(|(depth, amount, regex)| CertificationTrust {
+            depth,
+            amount,
+            regexes: Some(regex)
+                .filter(|r| !r.is_empty())
+                .map(String::from)
+                .into_iter()
+                .collect(),
+        });
+        let user_id = key.details.users[0].id.id().to_string();
+        let sig = signer
+            .certify_with_trust(
+                key,
+                &user_id,
+                CertificationType::Generic,
+                trust.as_ref(),
+                String::new,
+            )
+            .unwrap();
+        key.details.users[0].signatures.push(sig);
+    }
+
+    fn amount(keyring: &[SignedPublicKey], roots: &TrustRoots, uid: &str) -> u8 {
+        // certifications are created with a second resolution
+        let at = Utc::now() + Duration::seconds(2);
+        let result = validate_at(keyring, roots, uid, at);
+        assert_eq!(result.len(), 1, "{}", uid);
+        result[0].amount()
+    }
+
+    #[test]
+    fn test_introducers() {
+        let me = gen_key("Me <me@example.com>");
+        let ca = gen_key("CA <ca@example.com>");
+        let partial1 = gen_key("Partial 1 <p1@example.org>");
+        let partial2 = gen_key("Partial 2 <p2@example.org>");
+        let mut alice: SignedPublicKey = gen_key("Alice <alice@example.com>").into();
+        let mut bob: SignedPublicKey = gen_key("Bob <bob@example.org>").into();
+        let mut carol: SignedPublicKey = gen_key("Carol <carol@example.org>").into();
+
+        // the CA is a trusted introducer for example.com only
+        let mut ca_pub: SignedPublicKey = ca.clone().into();
+        certify(
+            &me,
+            &mut ca_pub,
+            Some((1, FULL, r"<[^>]+[@.]example\.com>$")),
+        );
+        // the partially trusted introducers get their owner trust from the keyring owner
+        let mut partial1_pub: SignedPublicKey = partial1.clone().into();
+        let mut partial2_pub: SignedPublicKey = partial2.clone().into();
+        certify(&me, &mut partial1_pub, None);
+        certify(&me, &mut partial2_pub, None);
+
+        certify(&ca, &mut alice, None);
+        certify(&ca, &mut bob, None);
+        certify(&partial1, &mut carol, None);
+        certify(&partial2, &mut carol, None);
+        certify(&partial1, &mut bob, None);
+
+        let keyring = vec![
+            me.clone().into(),
+            ca_pub,
+            partial1_pub,
+            partial2_pub,
+            alice,
+            bob,
+            carol,
+        ];
+        let roots = TrustRoots::new()
+            .with_ownertrust(me.fingerprint(), OwnerTrust::Ultimate)
+            .with_ownertrust(partial1.fingerprint(), OwnerTrust::Marginal)
+            .with_ownertrust(partial2.fingerprint(), OwnerTrust::Marginal);
+
+        assert_eq!(amount(&keyring, &roots, "Me <me@example.com>"), FULL);
+        assert_eq!(amount(&keyring, &roots, "CA <ca@example.com>"), FULL);
+        assert_eq!(amount(&keyring, &roots, "Alice <alice@example.com>"), FULL);
+        // out of scope of the CA, and only one partial introducer
+        assert_eq!(amount(&keyring, &roots, "Bob <bob@example.org>"), PARTIAL);
+        // two partial introducers
+        let at = Utc::now() + Duration::seconds(2);
+        let carol = validate_at(&keyring, &roots, "Carol <carol@example.org>", at);
+        assert!(carol[0].is_full());
+        assert_eq!(carol[0].introducers().len(), 2);
+
+        // without a trust root, nothing is valid
+        assert_eq!(
+            amount(&keyring, &TrustRoots::new(), "Alice <alice@example.com>"),
+            0
+        );
+        // distrusted introducers do not count
+        let roots = roots.with_ownertrust(partial2.fingerprint(), OwnerTrust::Never);
+        assert_eq!(
+            amount(&keyring, &roots, "Carol <carol@example.org>"),
+            PARTIAL
+        );
+        assert!(validate_at(&keyring, &roots, "Unknown", at).is_empty());
+
+        // the same owner trust, exported from GnuPG, with a disabled key
+        let ownertrust = format!(
+            "{}:6:\n{}:4:\n{}:132:\n",
+            hex::encode_upper(me.fingerprint().as_bytes()),
+            hex::encode_upper(partial1.fingerprint().as_bytes()),
+            hex::encode_upper(partial2.fingerprint().as_bytes()),
+        );
+        let roots = TrustRoots::from_gnupg(&parse_ownertrust(&ownertrust).unwrap());
+        assert_eq!(amount(&keyring, &roots, "Alice <alice@example.com>"), FULL);
+        assert_eq!(
+            amount(&keyring, &roots, "Carol <carol@example.org>"),
+            PARTIAL
+        );
+    }
+
+    #[test]
+    fn test_depth() {
+        let me = gen_key("Me <me@example.com>");
+        let meta = gen_key("Meta <meta@example.com>");
+        let intro = gen_key("Intro <intro@example.com>");
+        let sub = gen_key("Sub <sub@example.com>");
+        let mut alice: SignedPublicKey = gen_key("Alice <alice@example.com>").into();
+
+        let mut meta_pub: SignedPublicKey = meta.clone().into();
+        let mut intro_pub: SignedPublicKey = intro.clone().into();
+        let mut sub_pub: SignedPublicKey = sub.clone().into();
+        // a meta introducer with partial trust, that makes a trusted introducer, that in turn
+        // tries to make another one
+        certify(&me, &mut meta_pub, Some((2, PARTIAL, "")));
+        certify(&meta, &mut intro_pub, Some((2, FULL, "")));
+        certify(&intro, &mut sub_pub, Some((1, FULL, "")));
+        certify(&sub, &mut alice, None);
+
+        let keyring = vec![me.clone().into(), meta_pub, intro_pub, sub_pub, alice];
+        let roots = TrustRoots::new().with_ownertrust(me.fingerprint(), OwnerTrust::Ultimate);
+
+        assert_eq!(amount(&keyring, &roots, "Meta <meta@example.com>"), FULL);
+        // limited by the amount of the meta introducer
+        assert_eq!(
+            amount(&keyring, &roots, "Intro <intro@example.com>"),
+            PARTIAL
+        );
+        assert_eq!(amount(&keyring, &roots, "Sub <sub@example.com>"), PARTIAL);
+        // the introducer can not make further introducers, as the meta introducer only has
+        // depth 2
+        assert_eq!(amount(&keyring, &roots, "Alice <alice@example.com>"), 0);
+
+        // certification chains are limited to the maximum depth
+        let roots = roots.with_max_depth(1);
+        assert_eq!(amount(&keyring, &roots, "Meta <meta@example.com>"), FULL);
+        assert_eq!(amount(&keyring, &roots, "Intro <intro@example.com>"), 0);
+    }
+}
@@ -0,0 +1,284 @@
+#![cfg(feature = "async")]
+
+mod common;
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{ready, Context, Poll};
+
+use bstr::BStr;
+use pgp::armor::BlockType;
+use pgp::async_io::{AsyncArmorReader, AsyncArmorWriter, AsyncSignerWriter, AsyncStreamDecrypter};
+use pgp::composed::{Deserializable, Message, SignedPublicKey};
+use pgp::crypto::hash::HashAlgorithm;
+use pgp::crypto::public_key::PublicKeyAlgorithm;
+use pgp::crypto::sym::SymmetricKeyAlgorithm;
+use pgp::errors::Result;
+use pgp::packet::DataMode;
+use pgp::ser::Serialize;
+use pgp::types::{
+    AsyncSecretKeyTrait, CompressionAlgorithm, Fingerprint, KeyId, KeyTrait, Mpi,
+    PassphraseProvider, PublicKeyTrait, SecretKeyRepr, SecretKeyTrait, StringToKey,
+};
+use rand::{CryptoRng, Rng};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, ReadBuf};
+
+use common::gen_key_with_subkey;
+
+/// Stands in for a key held by a remote service, counting the operations delegated to it.
+#[derive(Debug)]
+struct RemoteKey<K> {
+    key: K,
+    calls: AtomicUsize,
+}
+
+impl<K: KeyTrait> KeyTrait for RemoteKey<K> {
+    fn fingerprint(&self) -> Fingerprint {
+        self.key.fingerprint()
+    }
+
+    fn key_id(&self) -> KeyId {
+        self.key.key_id()
+    }
+
+    fn algorithm(&self) -> PublicKeyAlgorithm {
+        self.key.algorithm()
+    }
+}
+
+impl<K: PublicKeyTrait> PublicKeyTrait for RemoteKey<K> {
+    fn verify_signature(&self, hash: HashAlgorithm, data: &[u8], sig: &[Mpi]) -> Result<()> {
+        self.key.verify_signature(hash, data, sig)
+    }
+
+    fn encrypt<R: CryptoRng + Rng>(&self, rng: &mut R, plain: &[u8]) -> Result<Vec<Mpi>> {
+        self.key.encrypt(rng, plain)
+    }
+
+    fn to_writer_old(&self, writer: &mut impl std::io::Write) -> Result<()> {
+        self.key.to_writer_old(writer)
+    }
+}
+
+#[pgp::async_trait]
+impl<K: SecretKeyTrait + Send + Sync> AsyncSecretKeyTrait for RemoteKey<K> {
+    async fn unlock<F, G>(&self, pw: F, work: G) -> Result<()>
+    where
+        F: PassphraseProvider + Send,
+        G: FnOnce(&SecretKeyRepr) -> Result<()> + Send,
+    {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        SecretKeyTrait::unlock(&self.key, pw, work)
+    }
+
+    async fn create_signature<F>(
+        &self,
+        key_pw: F,
+        hash: HashAlgorithm,
+        data: &[u8],
+    ) -> Result<Vec<Mpi>>
+    where
+        F: PassphraseProvider + Send,
+    {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        SecretKeyTrait::create_signature(&self.key, key_pw, hash, data)
+    }
+}
+
+/// The futures can be spawned on multi-threaded executors.
+fn assert_send<T: Send>(value: T) -> T {
+    value
+}
+
+#[test]
+fn async_sign_verify() {
+    let key = gen_key_with_subkey("Alice <alice@example.com>");
+    let public: SignedPublicKey = key.clone().into();
+    let remote = RemoteKey {
+        key: key.clone(),
+        calls: AtomicUsize::new(0),
+    };
+
+    let msg = pollster::block_on(assert_send(Message::new_literal("", "hello").sign_async(
+        &remote,
+        String::new,
+        HashAlgorithm::SHA2_256,
+    )))
+    .unwrap();
+    assert_eq!(remote.calls.load(Ordering::SeqCst), 1);
+    msg.verify(&public).unwrap();
+    assert!(msg.is_one_pass_signed());
+
+    // local keys can be used directly
+    let msg =
+        pollster::block_on(msg.sign_async(&key, String::new, HashAlgorithm::SHA2_512)).unwrap();
+    msg.verify(&public).unwrap();
+}
+
+#[test]
+fn async_decrypt() {
+    let key = gen_key_with_subkey("Alice <alice@example.com>");
+    let public: SignedPublicKey = key.clone().into();
+    let remote = RemoteKey {
+        key: key.secret_subkeys[0].clone(),
+        calls: AtomicUsize::new(0),
+    };
+
+    let msg = Message::new_literal("", "hello")
+        .encrypt_to_certs(&mut rand::thread_rng(), &[&public])
+        .unwrap();
+
+    let (mut decrypter, ids) =
+        pollster::block_on(assert_send(msg.decrypt_async(String::new, &[&remote]))).unwrap();
+    assert_eq!(remote.calls.load(Ordering::SeqCst), 1);
+    assert_eq!(ids, vec![remote.key_id()]);
+    let decrypted = decrypter.next().unwrap().unwrap();
+    assert_eq!(decrypted.get_content().unwrap().unwrap(), b"hello");
+
+    // the primary key is not a recipient
+    let err = pollster::block_on(msg.decrypt_async(String::new, &[&key.primary_key]));
+    assert!(err.is_err());
+}
+
+/// Returns at most a few bytes per read, and is pending every other time.
+struct Trickle<R> {
+    inner: R,
+    pending: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Trickle<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        self.pending = !self.pending;
+        if self.pending {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+
+        let mut small = vec![0u8; buf.remaining().min(7)];
+        let mut small_buf = ReadBuf::new(&mut small);
+        ready!(Pin::new(&mut self.inner).poll_read(cx, &mut small_buf))?;
+        buf.put_slice(small_buf.filled());
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn trickle<R>(inner: R) -> Trickle<R> {
+    Trickle {
+        inner,
+        pending: false,
+    }
+}
+
+#[test]
+fn async_armor_sign_roundtrip() {
+    let key = gen_key_with_subkey("Alice <alice@example.com>");
+    let public: SignedPublicKey = key.clone().into();
+    let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+
+    let armored = pollster::block_on(async {
+        let armor = AsyncArmorWriter::new(Vec::new(), BlockType::Message, None)?;
+        let mut signer = AsyncSignerWriter::new(
+            armor,
+            &key,
+            HashAlgorithm::SHA2_256,
+            BStr::new("data.bin"),
+            DataMode::Binary,
+        )?;
+        for chunk in content.chunks(1000) {
+            signer.write_all(chunk).await?;
+        }
+        signer.finish(String::new).await?.finish().await
+    })
+    .unwrap();
+
+    let (msg, _headers) = Message::from_armor_single(std::io::Cursor::new(&armored)).unwrap();
+    msg.verify(&public).unwrap();
+    assert_eq!(msg.get_content().unwrap().unwrap(), content);
+
+    let bytes = pollster::block_on(async {
+        let mut reader = AsyncArmorReader::new(trickle(&armored[..])).await?;
+        assert_eq!(reader.typ(), BlockType::Message);
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        pgp::errors::Result::Ok(bytes)
+    })
+    .unwrap();
+    assert_eq!(Message::from_bytes(&bytes[..]).unwrap(), msg);
+}
+
+#[test]
+fn async_stream_decrypt() {
+    let key = gen_key_with_subkey("Alice <alice@example.com>");
+    let public: SignedPublicKey = key.clone().into();
+    let content: Vec<u8> = (0..300_000u32).map(|i| (i % 253) as u8).collect();
+
+    let armored = Message::new_literal_bytes("data.bin", &content)
+        .compress(CompressionAlgorithm::ZLIB)
+        .unwrap()
+        .encrypt_to_certs(&mut rand::thread_rng(), &[&public])
+        .unwrap()
+        .to_armored_bytes(None)
+        .unwrap();
+
+    let decrypted = pollster::block_on(async {
+        let reader = AsyncArmorReader::new(trickle(&armored[..])).await?;
+        let decrypter = AsyncStreamDecrypter::new(trickle(reader)).await?;
+        assert_eq!(decrypter.esk().len(), 1);
+        let (mut reader, ids) = decrypter.decrypt(String::new, &[&key]).await?;
+        assert_eq!(ids, vec![key.secret_subkeys[0].key_id()]);
+        assert_eq!(reader.file_name(), "data.bin");
+
+        let mut decrypted = Vec::new();
+        reader.read_to_end(&mut decrypted).await?;
+        pgp::errors::Result::Ok(decrypted)
+    })
+    .unwrap();
+    assert_eq!(decrypted, content);
+
+    // with a key held elsewhere
+    let remote = RemoteKey {
+        key: key.secret_subkeys[0].clone(),
+        calls: AtomicUsize::new(0),
+    };
+    let decrypted = pollster::block_on(async {
+        let reader = AsyncArmorReader::new(&armored[..]).await?;
+        let decrypter = AsyncStreamDecrypter::new(reader).await?;
+        let (mut reader, _ids) = decrypter.decrypt_async(String::new, &[&remote]).await?;
+
+        let mut decrypted = Vec::new();
+        reader.read_to_end(&mut decrypted).await?;
+        pgp::errors::Result::Ok(decrypted)
+    })
+    .unwrap();
+    assert_eq!(decrypted, content);
+    assert_eq!(remote.calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn async_stream_decrypt_tampered() {
+    let mut rng = rand::thread_rng();
+    let s2k = StringToKey::new_default(&mut rng);
+    let mut bytes = Message::new_literal("", "hello world")
+        .encrypt_with_password(&mut rng, s2k, SymmetricKeyAlgorithm::AES128, || {
+            "secret".into()
+        })
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 1;
+
+    let res = pollster::block_on(async {
+        let decrypter = AsyncStreamDecrypter::new(trickle(&bytes[..])).await?;
+        let mut reader = decrypter.decrypt_with_password(|| "secret".into()).await?;
+        let mut decrypted = Vec::new();
+        reader.read_to_end(&mut decrypted).await?;
+        pgp::errors::Result::Ok(decrypted)
+    });
+    assert!(res.is_err());
+}
@@ -0,0 +1,52 @@
+//! Fixtures shared by the integration tests.
+
+#![allow(dead_code)]
+
+use pgp::composed::{
+    KeyType, SecretKeyParamsBuilder, SignedPublicKey, SignedSecretKey, SubkeyParams,
+    SubkeyParamsBuilder,
+};
+
+/// The parameters of [`gen_key`], for tests that need to extend them.
+pub fn key_params(user_id: &str) -> SecretKeyParamsBuilder {
+    let mut params = SecretKeyParamsBuilder::default();
+    params
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .can_sign(true)
+        .primary_user_id(user_id.into());
+    params
+}
+
+/// An ECDH encryption subkey.
+pub fn encryption_subkey() -> SubkeyParams {
+    SubkeyParamsBuilder::default()
+        .key_type(KeyType::ECDH)
+        .can_encrypt(true)
+        .build()
+        .unwrap()
+}
+
+/// Generates an EdDSA key for `user_id`, that can certify and sign.
+pub fn gen_key(user_id: &str) -> SignedSecretKey {
+    key_params(user_id)
+        .build()
+        .unwrap()
+        .generate_signed()
+        .unwrap()
+}
+
+/// Same as [`gen_key`], but only returns the public key.
+pub fn gen_public_key(user_id: &str) -> SignedPublicKey {
+    gen_key(user_id).into()
+}
+
+/// Same as [`gen_key`], with an [`encryption_subkey`].
+pub fn gen_key_with_subkey(user_id: &str) -> SignedSecretKey {
+    key_params(user_id)
+        .subkey(encryption_subkey())
+        .build()
+        .unwrap()
+        .generate_signed()
+        .unwrap()
+}
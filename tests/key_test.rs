@@ -5,6 +5,8 @@ extern crate pretty_assertions;
 #[macro_use]
 extern crate smallvec;
 
+mod common;
+
 use std::fs::File;
 use std::io::{Cursor, Read};
 use std::path::Path;
@@ -943,6 +945,7 @@ openpgp_key!(
 );
 
 #[test]
+#[cfg(feature = "nist-curves")]
 fn private_ecc1_verify() {
     let f = read_file("./tests/openpgp/samplekeys/ecc-sample-1-sec.asc");
     let (sk, _headers) = SignedSecretKey::from_armor_single(f).expect("failed to parse key");
@@ -968,6 +971,7 @@ fn private_ecc1_verify() {
 }
 
 #[test]
+#[cfg(feature = "nist-curves")]
 fn private_ecc2_verify() {
     let f = read_file("./tests/openpgp/samplekeys/ecc-sample-2-sec.asc");
     let (sk, _headers) = SignedSecretKey::from_armor_single(f).expect("failed to parse key");
@@ -996,6 +1000,7 @@ fn private_ecc2_verify() {
 }
 
 #[test]
+#[cfg(feature = "secp256k1")]
 fn private_ecc3_verify() {
     let f = read_file("./tests/openpgp/samplekeys/ecc-sample-4-sec.asc");
     let (sk, _headers) = SignedSecretKey::from_armor_single(f).expect("failed to parse key");
@@ -1132,6 +1137,30 @@ autocrypt_key!(
     true,
 );
 
+#[test]
+fn test_parse_mangled_armor() {
+    let armored =
+        std::fs::read_to_string("./tests/autocrypt/alice@autocrypt.example.pub.asc").unwrap();
+    let (key, _) = SignedPublicKey::from_string(&armored).unwrap();
+
+    // as found in mails: CRLF line endings, trailing whitespace, a BOM or leading text
+    let crlf: String = armored.lines().map(|line| format!("{line} \r\n")).collect();
+    for mangled in [
+        format!("\u{feff}{crlf}"),
+        format!("Hi,\r\n\r\nmy key:\r\n{crlf}"),
+    ] {
+        let (parsed, _) = SignedPublicKey::from_string(&mangled).unwrap();
+        assert_eq!(parsed, key);
+
+        let (parsed, _) =
+            SignedPublicKey::from_armor_single(Cursor::new(mangled.as_bytes())).unwrap();
+        assert_eq!(parsed, key);
+
+        let (keys, _) = from_armor_many(Cursor::new(mangled.as_bytes())).unwrap();
+        assert_eq!(keys.count(), 1);
+    }
+}
+
 #[test]
 fn test_invalid() {
     let v = (0..64).collect::<Vec<u8>>();
@@ -1180,15 +1209,1298 @@ fn test_encrypted_key() {
     key.verify().expect("invalid key");
     let unsigned_pubkey = key.public_key();
 
-    // Incorrect password results in InvalidInput error.
+    // Incorrect password results in BadPassphrase error.
     assert!(matches!(
         unsigned_pubkey
             .clone()
             .sign(&key, || "".into())
             .err()
             .unwrap(),
-        pgp::errors::Error::InvalidInput
+        pgp::errors::Error::BadPassphrase
     ));
 
     let _signed_key = unsigned_pubkey.sign(&key, || "123".into()).unwrap();
 }
+
+#[test]
+fn test_add_user_id() {
+    let (key, _headers) =
+        SignedSecretKey::from_armor_single(read_file("./tests/key-with-password-123.asc"))
+            .expect("failed to parse key");
+    key.verify().expect("invalid key");
+    let users_before = key.details.users.len();
+    let primary_sig = key.details.users[0].signatures[0].clone();
+
+    let key = key
+        .add_user_id("Second <second@example.org>", || "123".into())
+        .expect("failed to add user id");
+    key.verify().expect("invalid key");
+
+    assert_eq!(key.details.users.len(), users_before + 1);
+    let user = key.details.users.last().unwrap();
+    assert_eq!(user.id.id(), "Second <second@example.org>");
+    assert!(!user.is_primary());
+    assert_eq!(user.signatures.len(), 1);
+
+    let sig = &user.signatures[0];
+    assert_eq!(sig.typ(), SignatureType::CertGeneric);
+    assert_eq!(sig.key_flags(), primary_sig.key_flags());
+    assert_eq!(
+        sig.preferred_symmetric_algs(),
+        primary_sig.preferred_symmetric_algs()
+    );
+    assert_eq!(sig.preferred_hash_algs(), primary_sig.preferred_hash_algs());
+
+    // adding the same id twice is rejected
+    assert!(key
+        .clone()
+        .add_user_id("Second <second@example.org>", || "123".into())
+        .is_err());
+
+    // wrong password
+    assert!(key
+        .clone()
+        .add_user_id("Third <third@example.org>", || "wrong".into())
+        .is_err());
+
+    let armor = key.to_armored_string(None).expect("failed to serialize key");
+    let (parsed, _headers) = SignedSecretKey::from_string(&armor).expect("failed to parse key");
+    assert_eq!(parsed, key);
+}
+
+#[test]
+fn test_add_subkey() {
+    use pgp::composed::{KeyType, SubkeyParamsBuilder};
+
+    let (key, _headers) =
+        SignedSecretKey::from_armor_single(read_file("./tests/key-with-password-123.asc"))
+            .expect("failed to parse key");
+    let subkeys_before = key.secret_subkeys.len();
+
+    let key = key
+        .add_subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH)
+                .can_encrypt(true)
+                .expiration(Some(std::time::Duration::from_secs(60 * 60 * 24)))
+                .build()
+                .unwrap(),
+            || "123".into(),
+        )
+        .expect("failed to add encryption subkey");
+    let key = key
+        .add_subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::EdDSA)
+                .can_sign(true)
+                .passphrase(Some("sub".into()))
+                .build()
+                .unwrap(),
+            || "123".into(),
+        )
+        .expect("failed to add signing subkey");
+    key.verify().expect("invalid key");
+    assert_eq!(key.secret_subkeys.len(), subkeys_before + 2);
+
+    let enc = &key.secret_subkeys[subkeys_before];
+    let binding = &enc.signatures[0];
+    assert!(binding.key_flags().encrypt_comms());
+    assert_eq!(
+        binding.key_expiration_time(),
+        Some(&chrono::Duration::days(1))
+    );
+    assert!(binding.embedded_signature().is_none());
+
+    let sign = &key.secret_subkeys[subkeys_before + 1];
+    let binding = &sign.signatures[0];
+    assert!(binding.key_flags().sign());
+    let backsig = binding
+        .embedded_signature()
+        .expect("missing back signature");
+    assert_eq!(backsig.typ(), SignatureType::KeyBinding);
+    backsig
+        .verify_backwards_key_binding(&sign.key, &key.primary_key)
+        .expect("invalid back signature");
+
+    // the new signing subkey is usable with its own passphrase
+    sign.key
+        .unlock(|| "sub".into(), |_| Ok(()))
+        .expect("failed to unlock subkey");
+
+    // wrong primary key password
+    assert!(key
+        .clone()
+        .add_subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH)
+                .can_encrypt(true)
+                .build()
+                .unwrap(),
+            || "wrong".into(),
+        )
+        .is_err());
+
+    let armor = key.to_armored_string(None).expect("failed to serialize key");
+    let (parsed, _headers) = SignedSecretKey::from_string(&armor).expect("failed to parse key");
+    assert_eq!(parsed, key);
+}
+
+#[test]
+fn test_set_expiration() {
+    let (key, _headers) =
+        SignedSecretKey::from_armor_single(read_file("./tests/key-with-password-123.asc"))
+            .expect("failed to parse key");
+    assert!(!key.secret_subkeys.is_empty());
+    let users_before: Vec<_> = key
+        .details
+        .users
+        .iter()
+        .map(|u| u.signatures.len())
+        .collect();
+
+    let key = key
+        .set_expiration(Some(chrono::Duration::days(365)), || "123".into())
+        .expect("failed to set expiration");
+    key.verify().expect("invalid key");
+    assert_eq!(
+        key.expires_at(),
+        Some(*key.primary_key.created_at() + chrono::Duration::days(365))
+    );
+    for (user, count) in key.details.users.iter().zip(users_before) {
+        assert_eq!(user.signatures.len(), count);
+    }
+
+    // shortening the expiration replaces the previous self-certifications
+    let key = key
+        .set_expiration(Some(chrono::Duration::days(10)), || "123".into())
+        .expect("failed to set expiration");
+    assert_eq!(
+        key.details.key_expiration_time(),
+        Some(chrono::Duration::days(10))
+    );
+
+    let key = key
+        .set_expiration(None, || "123".into())
+        .expect("failed to remove expiration");
+    key.verify().expect("invalid key");
+    assert_eq!(key.expires_at(), None);
+
+    let subkey_id = key.secret_subkeys[0].key_id();
+    let key = key
+        .set_subkey_expiration(&subkey_id, Some(chrono::Duration::days(30)), || {
+            "123".into()
+        })
+        .expect("failed to set subkey expiration");
+    key.verify().expect("invalid key");
+    let binding = &key.secret_subkeys[0].signatures[0];
+    assert_eq!(
+        binding.key_expiration_time(),
+        Some(&chrono::Duration::days(30))
+    );
+
+    assert!(key
+        .clone()
+        .set_subkey_expiration(&key.key_id(), None, || "123".into())
+        .is_err());
+    assert!(key.clone().set_expiration(None, || "wrong".into()).is_err());
+
+    let armor = key
+        .to_armored_string(None)
+        .expect("failed to serialize key");
+    let (parsed, _headers) = SignedSecretKey::from_string(&armor).expect("failed to parse key");
+    assert_eq!(parsed, key);
+}
+
+#[test]
+fn test_revocations() {
+    use pgp::packet::RevocationCode;
+    use pgp::StandaloneSignature;
+
+    let (key, _headers) =
+        SignedSecretKey::from_armor_single(read_file("./tests/key-with-password-123.asc"))
+            .expect("failed to parse key");
+
+    // standalone revocation certificate
+    let cert = key
+        .create_revocation_certificate(RevocationCode::KeyCompromised, "leaked", || "123".into())
+        .expect("failed to create revocation certificate");
+    assert!(cert.starts_with("-----BEGIN PGP PUBLIC KEY BLOCK-----"));
+    let (sig, _headers) =
+        StandaloneSignature::from_string(&cert).expect("failed to parse revocation");
+    assert_eq!(sig.signature.typ(), SignatureType::KeyRevocation);
+    assert_eq!(
+        sig.signature.revocation_reason_code(),
+        Some(&RevocationCode::KeyCompromised)
+    );
+    assert_eq!(
+        sig.signature.revocation_reason_string(),
+        Some(b"leaked"[..].into())
+    );
+    sig.signature
+        .verify_key(&key.primary_key)
+        .expect("invalid revocation");
+
+    // revoke user id, subkey and key
+    let user_id = key.details.users[0].id.id().to_string();
+    let subkey_id = key.secret_subkeys[0].key_id();
+    let key = key
+        .revoke_user_id(&user_id, RevocationCode::CertUserIdInvalid, "", || {
+            "123".into()
+        })
+        .expect("failed to revoke user id")
+        .revoke_subkey(
+            &subkey_id,
+            RevocationCode::KeySuperseded,
+            "replaced",
+            || "123".into(),
+        )
+        .expect("failed to revoke subkey")
+        .revoke(RevocationCode::KeyRetired, "retired", || "123".into())
+        .expect("failed to revoke key");
+    key.verify().expect("invalid key");
+
+    let user_sig = key.details.users[0].signatures.last().unwrap();
+    assert_eq!(user_sig.typ(), SignatureType::CertRevocation);
+    assert_eq!(
+        user_sig.revocation_reason_code(),
+        Some(&RevocationCode::CertUserIdInvalid)
+    );
+    let subkey_sig = key.secret_subkeys[0].signatures.last().unwrap();
+    assert_eq!(subkey_sig.typ(), SignatureType::SubkeyRevocation);
+    assert_eq!(key.details.revocation_signatures.len(), 1);
+    assert_eq!(
+        key.details.revocation_signatures[0].typ(),
+        SignatureType::KeyRevocation
+    );
+
+    assert!(key
+        .clone()
+        .revoke_user_id("unknown", RevocationCode::NoReason, "", || "123".into())
+        .is_err());
+    assert!(key
+        .clone()
+        .revoke(RevocationCode::NoReason, "", || "wrong".into())
+        .is_err());
+
+    let armor = key
+        .to_armored_string(None)
+        .expect("failed to serialize key");
+    let (parsed, _headers) = SignedSecretKey::from_string(&armor).expect("failed to parse key");
+    assert_eq!(parsed, key);
+}
+
+#[test]
+fn test_designated_revoker() {
+    use chrono::SubsecRound;
+    use pgp::packet::{RevocationCode, SignatureConfigBuilder};
+    use pgp::types::{RevocationKey, RevocationKeyClass};
+
+    use crate::common::key_params;
+
+    fn gen_key(id: &str, revocation_key: Option<RevocationKey>) -> SignedSecretKey {
+        key_params(id)
+            .revocation_key(revocation_key)
+            .build()
+            .unwrap()
+            .generate_signed()
+            .unwrap()
+    }
+
+    let revoker = gen_key("Revoker <revoker@mail.com>", None);
+    let other = gen_key("Other <other@mail.com>", None);
+    let key = gen_key(
+        "Me <me@mail.com>",
+        Some(RevocationKey::new(
+            RevocationKeyClass::Sensitive,
+            revoker.algorithm(),
+            revoker.fingerprint(),
+        )),
+    );
+    assert_eq!(key.details.revocation_keys(&key.primary_key).len(), 1);
+    assert!(!key.is_revoked::<SignedPublicKey>(&[]));
+
+    // revocation issued by the designated revoker
+    let mut revoked = key.clone();
+    let sig = SignatureConfigBuilder::default()
+        .typ(SignatureType::KeyRevocation)
+        .pub_alg(revoker.algorithm())
+        .hashed_subpackets(vec![
+            Subpacket::regular(SubpacketData::SignatureCreationTime(
+                chrono::Utc::now().trunc_subsecs(0),
+            )),
+            Subpacket::regular(SubpacketData::RevocationReason(
+                RevocationCode::KeyCompromised,
+                "lost".into(),
+            )),
+            Subpacket::regular(SubpacketData::IssuerFingerprint(revoker.fingerprint())),
+        ])
+        .unhashed_subpackets(vec![])
+        .build()
+        .unwrap()
+        .sign_key(&revoker.primary_key, String::new, &key.primary_key)
+        .unwrap();
+    revoked.details.revocation_signatures.push(sig.clone());
+    revoked.verify().expect("invalid key");
+
+    let revoker_pub: SignedPublicKey = revoker.into();
+    let other_pub: SignedPublicKey = other.clone().into();
+    assert!(!revoked.is_revoked::<SignedPublicKey>(&[]));
+    assert!(!revoked.is_revoked(&[&other_pub]));
+    assert!(revoked.is_revoked(&[&other_pub, &revoker_pub]));
+
+    let revoked_pub: SignedPublicKey = revoked.into();
+    assert!(revoked_pub.is_revoked(&[&revoker_pub]));
+
+    let now = chrono::Utc::now();
+    assert!(revoked_pub.valid_at(now).is_ok());
+    assert!(ValidCert::with_revokers(&revoked_pub, now, &[&other_pub]).is_ok());
+    assert!(ValidCert::with_revokers(&revoked_pub, now, &[&revoker_pub]).is_err());
+
+    // the same revocation is not accepted for a key without the designation
+    let mut not_designated = other;
+    not_designated.details.revocation_signatures.push(sig);
+    assert!(not_designated.verify().is_err());
+    assert!(!not_designated.is_revoked(&[revoker_pub]));
+
+    // self revocation
+    let self_revoked = key
+        .revoke(RevocationCode::KeyRetired, "", String::new)
+        .unwrap();
+    assert!(self_revoked.is_revoked::<SignedPublicKey>(&[]));
+}
+
+#[test]
+fn test_set_passphrase() {
+    let (key, _headers) =
+        SignedSecretKey::from_armor_single(read_file("./tests/key-with-password-123.asc"))
+            .expect("failed to parse key");
+    assert!(!key.secret_subkeys.is_empty());
+
+    // wrong old passphrase
+    assert!(key
+        .clone()
+        .set_passphrase(|| "wrong".into(), "new")
+        .is_err());
+
+    let key = key
+        .set_passphrase(|| "123".into(), "new")
+        .expect("failed to change passphrase");
+    key.verify().expect("invalid key");
+    assert!(key.primary_key.secret_params().is_encrypted());
+    key.unlock(|| "new".into(), |_| Ok(()))
+        .expect("failed to unlock with new passphrase");
+    assert!(key.unlock(|| "123".into(), |_| Ok(())).is_err());
+    for subkey in &key.secret_subkeys {
+        assert!(subkey.key.secret_params().is_encrypted());
+        subkey
+            .unlock(|| "new".into(), |_| Ok(()))
+            .expect("failed to unlock subkey with new passphrase");
+    }
+
+    let armor = key
+        .to_armored_string(None)
+        .expect("failed to serialize key");
+    let (parsed, _headers) = SignedSecretKey::from_string(&armor).expect("failed to parse key");
+    assert_eq!(parsed, key);
+
+    let key = key
+        .remove_passphrase(|| "new".into())
+        .expect("failed to remove passphrase");
+    assert!(!key.primary_key.secret_params().is_encrypted());
+    for subkey in &key.secret_subkeys {
+        assert!(!subkey.key.secret_params().is_encrypted());
+    }
+    key.unlock(String::new, |_| Ok(()))
+        .expect("failed to unlock unprotected key");
+
+    // protecting an unprotected key again
+    let key = key
+        .set_passphrase(String::new, "again")
+        .expect("failed to set passphrase");
+    key.unlock(|| "again".into(), |_| Ok(()))
+        .expect("failed to unlock with new passphrase");
+}
+
+#[test]
+fn test_merge_public_keys() {
+    use pgp::composed::{KeyType, SubkeyParamsBuilder};
+    use pgp::packet::RevocationCode;
+
+    let (key, _headers) =
+        SignedSecretKey::from_armor_single(read_file("./tests/key-with-password-123.asc"))
+            .expect("failed to parse key");
+    let subkey_id = key.secret_subkeys[0].key_id();
+
+    let with_user: SignedPublicKey = key
+        .clone()
+        .add_user_id("Other <other@mail.com>", || "123".into())
+        .expect("failed to add user id")
+        .into();
+    let with_subkey: SignedPublicKey = key
+        .clone()
+        .revoke_subkey(&subkey_id, RevocationCode::KeyRetired, "", || "123".into())
+        .expect("failed to revoke subkey")
+        .add_subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH)
+                .can_encrypt(true)
+                .build()
+                .unwrap(),
+            || "123".into(),
+        )
+        .expect("failed to add subkey")
+        .into();
+    let original: SignedPublicKey = key.into();
+
+    let merged = original
+        .clone()
+        .merge(with_user.clone())
+        .expect("failed to merge")
+        .merge(with_subkey.clone())
+        .expect("failed to merge");
+    merged.verify().expect("invalid key");
+
+    assert_eq!(merged.details.users, with_user.details.users);
+    assert_eq!(merged.public_subkeys, with_subkey.public_subkeys);
+    assert_eq!(
+        merged.public_subkeys[0].signatures.last().unwrap().typ(),
+        SignatureType::SubkeyRevocation
+    );
+
+    // merging is idempotent
+    assert_eq!(
+        merged.clone().merge(original).expect("failed to merge"),
+        merged
+    );
+    assert_eq!(
+        merged
+            .clone()
+            .merge(merged.clone())
+            .expect("failed to merge"),
+        merged
+    );
+
+    // different keys can not be merged
+    let (other, _headers) = SignedPublicKey::from_armor_single(read_file(
+        "./tests/opengpg-interop/testcases/keys/gnupg-v1-001.asc",
+    ))
+    .expect("failed to parse key");
+    assert!(merged.merge(other).is_err());
+}
+
+#[test]
+fn test_minimize_public_key() {
+    use chrono::SubsecRound;
+    use pgp::packet::{PacketTrait, SignatureConfigBuilder};
+
+    use crate::common::gen_key;
+
+    let (key, _headers) =
+        SignedSecretKey::from_armor_single(read_file("./tests/key-with-password-123.asc"))
+            .expect("failed to parse key");
+    let key = key
+        .add_user_id("Other <other@mail.com>", || "123".into())
+        .expect("failed to add user id");
+    let updated: SignedPublicKey = key
+        .clone()
+        .set_expiration(Some(chrono::Duration::days(100)), || "123".into())
+        .expect("failed to set expiration")
+        .into();
+    let mut public: SignedPublicKey = key.into();
+
+    // third-party certification
+    let signer = gen_key("Signer <signer@mail.com>");
+    let user = &mut public.details.users[0];
+    let certification = SignatureConfigBuilder::default()
+        .typ(SignatureType::CertGeneric)
+        .pub_alg(signer.algorithm())
+        .hashed_subpackets(vec![
+            Subpacket::regular(SubpacketData::SignatureCreationTime(
+                chrono::Utc::now().trunc_subsecs(0),
+            )),
+            Subpacket::regular(SubpacketData::IssuerFingerprint(signer.fingerprint())),
+        ])
+        .unhashed_subpackets(vec![])
+        .build()
+        .unwrap()
+        .sign_certification(&signer.primary_key, String::new, user.id.tag(), &user.id)
+        .unwrap();
+    user.signatures.push(certification);
+
+    // multiple self-signatures per component
+    let full = public.merge(updated).expect("failed to merge");
+    assert!(full.details.users.iter().all(|u| u.signatures.len() >= 2));
+
+    let minimal = full.clone().minimize();
+    minimal.verify().expect("invalid key");
+    assert_eq!(minimal.details.users.len(), full.details.users.len());
+    for user in &minimal.details.users {
+        assert_eq!(user.signatures.len(), 1);
+        assert_eq!(
+            user.signatures[0].key_expiration_time(),
+            Some(&chrono::Duration::days(100))
+        );
+        assert_eq!(
+            user.signatures[0].issuer_fingerprint(),
+            vec![&full.fingerprint()]
+        );
+    }
+    assert_eq!(minimal.public_subkeys.len(), full.public_subkeys.len());
+
+    let filtered = full
+        .clone()
+        .filter("Other <other@mail.com>")
+        .expect("failed to filter");
+    filtered.verify().expect("invalid key");
+    assert_eq!(filtered.details.users.len(), 1);
+    assert_eq!(filtered.details.users[0].id.id(), "Other <other@mail.com>");
+    assert!(filtered.details.user_attributes.is_empty());
+
+    assert!(full.filter("Unknown <unknown@mail.com>").is_err());
+}
+
+#[test]
+fn test_unhashed_subpackets() {
+    use pgp::packet::{Notation, PacketTrait, SubpacketAreas};
+    use pgp::types::{RevocationKey, RevocationKeyClass};
+
+    use crate::common::gen_key;
+
+    let key = gen_key("Me <me@mail.com>");
+    let attacker = gen_key("Attacker <attacker@mail.com>");
+    let mut public: SignedPublicKey = key.clone().into();
+
+    // a certification by the attacker, claiming to be a self-certification in the unhashed
+    // area, that designates the attacker as revoker
+    let user = &mut public.details.users[0];
+    let mut forged = SubpacketAreas::new()
+        .created(chrono::Utc::now() + chrono::Duration::seconds(10))
+        .revocation_key(RevocationKey::new(
+            RevocationKeyClass::Default,
+            attacker.algorithm(),
+            attacker.fingerprint(),
+        ))
+        .into_config(
+            SignatureType::CertGeneric,
+            attacker.algorithm(),
+            HashAlgorithm::SHA2_256,
+        )
+        .sign_certification(&attacker.primary_key, String::new, user.id.tag(), &user.id)
+        .unwrap();
+    forged.config.unhashed_subpackets = vec![
+        Subpacket::regular(SubpacketData::IssuerFingerprint(key.fingerprint())),
+        Subpacket::regular(SubpacketData::Issuer(key.key_id())),
+    ];
+    user.signatures.push(forged);
+
+    assert!(public
+        .details
+        .revocation_keys(&public.primary_key)
+        .is_empty());
+    let minimal = public.clone().minimize();
+    minimal.verify().expect("invalid key");
+    assert_eq!(
+        minimal.details.users[0].signatures,
+        key.details.users[0].signatures
+    );
+
+    // unhashed subpackets are kept, but not trusted
+    let mut annotated: SignedPublicKey = key.into();
+    let sig = &mut annotated.details.users[0].signatures[0];
+    sig.config
+        .unhashed_subpackets
+        .push(Notation::new("hint@example.com", "unsigned").into());
+    assert!(sig.notations().is_empty());
+    annotated.verify().expect("invalid key");
+
+    let bytes = annotated.to_bytes().unwrap();
+    let parsed = SignedPublicKey::from_bytes(&bytes[..]).unwrap();
+    assert_eq!(parsed, annotated);
+    assert_eq!(parsed.to_bytes().unwrap(), bytes);
+
+    // copies only differing in the unhashed area are merged into one
+    let merged = annotated.clone().merge(minimal).expect("failed to merge");
+    assert_eq!(merged, annotated);
+}
+
+#[test]
+fn test_certify() {
+    use pgp::composed::CertificationTrust;
+    use pgp::packet::{CertificationType, PacketTrait};
+
+    use crate::common::key_params;
+
+    let signer = key_params("Signer <signer@mail.com>")
+        .passphrase(Some("signer".into()))
+        .build()
+        .unwrap()
+        .generate_signed()
+        .unwrap();
+    let (key, _headers) =
+        SignedSecretKey::from_armor_single(read_file("./tests/key-with-password-123.asc"))
+            .expect("failed to parse key");
+    let mut key: SignedPublicKey = key.into();
+    let user_id = key.details.users[0].id.id().to_string();
+
+    let sig = signer
+        .certify(&key, &user_id, CertificationType::Casual, || {
+            "signer".into()
+        })
+        .expect("failed to certify");
+    assert_eq!(sig.typ(), SignatureType::CertCasual);
+    let user = &key.details.users[0];
+    sig.verify_third_party_certification(
+        &signer.primary_key,
+        &key.primary_key,
+        user.id.tag(),
+        &user.id,
+    )
+    .expect("invalid certification");
+    assert!(sig
+        .verify_certification(&key.primary_key, user.id.tag(), &user.id)
+        .is_err());
+
+    let trust = CertificationTrust {
+        depth: 1,
+        amount: 120,
+        regexes: vec![r"<[^>]+[@.]mail\.com>$".into()],
+    };
+    let sig = signer
+        .certify_with_trust(
+            &key,
+            &user_id,
+            CertificationType::Positive,
+            Some(&trust),
+            || "signer".into(),
+        )
+        .expect("failed to certify");
+    assert_eq!(sig.typ(), SignatureType::CertPositive);
+    let hashed: Vec<_> = sig.config.hashed_subpackets().collect();
+    assert!(hashed
+        .iter()
+        .any(|p| p.data == SubpacketData::TrustSignature(1, 120)));
+    assert!(hashed.iter().any(|p| p.is_critical
+        && p.data == SubpacketData::RegularExpression(b"<[^>]+[@.]mail\\.com>$\0"[..].into())));
+    sig.verify_third_party_certification(
+        &signer.primary_key,
+        &key.primary_key,
+        user.id.tag(),
+        &user.id,
+    )
+    .expect("invalid certification");
+
+    // the certification survives a roundtrip
+    key.details.users[0].signatures.push(sig.clone());
+    let armor = key
+        .to_armored_string(None)
+        .expect("failed to serialize key");
+    let (parsed, _headers) = SignedPublicKey::from_string(&armor).expect("failed to parse key");
+    assert_eq!(parsed.details.users[0].signatures.last(), Some(&sig));
+
+    assert!(signer
+        .certify(
+            &key,
+            "Unknown <unknown@mail.com>",
+            CertificationType::Generic,
+            || { "signer".into() }
+        )
+        .is_err());
+}
+
+#[test]
+fn test_signing_subkey_requires_back_signature() {
+    use pgp::composed::{KeyType, SubkeyParamsBuilder};
+    use pgp::packet::SignatureConfigBuilder;
+
+    let (key, _headers) =
+        SignedSecretKey::from_armor_single(read_file("./tests/key-with-password-123.asc"))
+            .expect("failed to parse key");
+    let key = key
+        .add_subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::EdDSA)
+                .can_sign(true)
+                .build()
+                .unwrap(),
+            || "123".into(),
+        )
+        .expect("failed to add signing subkey");
+    key.verify().expect("invalid key");
+    let public: SignedPublicKey = key.clone().into();
+    public.verify().expect("invalid key");
+
+    // a signing subkey bound without back signature is rejected
+    let mut forged = public;
+    let subkey = forged.public_subkeys.last_mut().unwrap();
+    let binding = &subkey.signatures[0];
+    subkey.signatures[0] = SignatureConfigBuilder::default()
+        .typ(SignatureType::SubkeyBinding)
+        .pub_alg(binding.config.pub_alg)
+        .hashed_subpackets(
+            binding
+                .config
+                .hashed_subpackets()
+                .filter(|p| !matches!(p.data, SubpacketData::EmbeddedSignature(_)))
+                .cloned()
+                .collect(),
+        )
+        .unhashed_subpackets(binding.config.unhashed_subpackets().cloned().collect())
+        .build()
+        .unwrap()
+        .sign_key_binding(&key.primary_key, || "123".into(), &subkey.key)
+        .unwrap();
+    assert!(forged.verify().is_err());
+
+    // the back signature is found after other embedded signatures
+    let bind = |embedded: Vec<Signature>| {
+        let mut public: SignedPublicKey = key.clone().into();
+        let subkey = public.public_subkeys.last_mut().unwrap();
+        let binding = &subkey.signatures[0];
+        let mut hashed_subpackets: Vec<_> = binding
+            .config
+            .hashed_subpackets()
+            .filter(|p| !matches!(p.data, SubpacketData::EmbeddedSignature(_)))
+            .cloned()
+            .collect();
+        hashed_subpackets.extend(
+            embedded
+                .into_iter()
+                .map(|sig| Subpacket::regular(SubpacketData::EmbeddedSignature(Box::new(sig)))),
+        );
+        subkey.signatures[0] = SignatureConfigBuilder::default()
+            .typ(SignatureType::SubkeyBinding)
+            .pub_alg(binding.config.pub_alg)
+            .hashed_subpackets(hashed_subpackets)
+            .unhashed_subpackets(binding.config.unhashed_subpackets().cloned().collect())
+            .build()
+            .unwrap()
+            .sign_key_binding(&key.primary_key, || "123".into(), &subkey.key)
+            .unwrap();
+        public
+    };
+    let public: SignedPublicKey = key.clone().into();
+    let subkey = public.public_subkeys.last().unwrap();
+    let backsig = subkey.signatures[0].embedded_signature().unwrap().clone();
+    bind(vec![subkey.signatures[0].clone(), backsig])
+        .verify()
+        .expect("invalid key");
+
+    // a back signature made by another subkey is rejected
+    let other_subkey = key
+        .clone()
+        .add_subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::EdDSA)
+                .can_sign(true)
+                .build()
+                .unwrap(),
+            || "123".into(),
+        )
+        .expect("failed to add signing subkey");
+    let other_backsig = other_subkey.secret_subkeys.last().unwrap().signatures[0]
+        .embedded_signature()
+        .unwrap()
+        .clone();
+    assert!(bind(vec![other_backsig]).verify().is_err());
+
+    // re-signing the public key keeps the back signature
+    let resigned = key
+        .public_key()
+        .sign(&key, || "123".into())
+        .expect("failed to sign public key");
+    resigned.verify().expect("invalid key");
+}
+
+#[test]
+fn test_valid_cert() {
+    use pgp::composed::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+    use pgp::packet::RevocationCode;
+
+    let day = std::time::Duration::from_secs(24 * 60 * 60);
+    let key = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .can_sign(true)
+        .primary_user_id("Me <me@mail.com>".into())
+        .user_id("Old <old@mail.com>")
+        .expiration(Some(day * 365))
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH)
+                .can_encrypt(true)
+                .expiration(Some(day * 30))
+                .build()
+                .unwrap(),
+        )
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::EdDSA)
+                .can_sign(true)
+                .build()
+                .unwrap(),
+        )
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH)
+                .can_encrypt(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap()
+        .generate_signed()
+        .unwrap();
+    let retired_id = key.secret_subkeys[2].key_id();
+    let key = key
+        .revoke_user_id(
+            "Old <old@mail.com>",
+            RevocationCode::CertUserIdInvalid,
+            "",
+            String::new,
+        )
+        .unwrap()
+        .revoke_subkey(&retired_id, RevocationCode::KeyRetired, "", String::new)
+        .unwrap();
+    let key: SignedPublicKey = key.into();
+    let created = *key.primary_key.created_at();
+    let now = chrono::Utc::now() + chrono::Duration::seconds(1);
+
+    let valid = key.valid_at(now).expect("key should be valid");
+    assert_eq!(
+        valid.expires_at(),
+        Some(created + chrono::Duration::days(365))
+    );
+    assert!(valid.key_flags().sign());
+    assert_eq!(valid.user_ids().len(), 1);
+    let primary = valid.primary_user_id().unwrap();
+    assert!(primary.is_primary());
+    assert_eq!(primary.user().id.id(), "Me <me@mail.com>");
+    assert_eq!(valid.subkeys().len(), 2);
+    assert!(valid.subkeys()[0].key_flags().encrypt_comms());
+    assert_eq!(
+        valid.subkeys()[0].expires_at(),
+        Some(*key.public_subkeys[0].key.created_at() + chrono::Duration::days(30))
+    );
+    assert!(valid.subkeys()[1].key_flags().sign());
+
+    // the encryption subkey expires after 30 days
+    let valid = key
+        .valid_at(created + chrono::Duration::days(31))
+        .expect("key should be valid");
+    assert_eq!(valid.subkeys().len(), 1);
+    assert!(valid.subkeys()[0].key_flags().sign());
+
+    // the primary key expires after a year
+    assert!(key.valid_at(created + chrono::Duration::days(366)).is_err());
+    // and does not exist before its creation
+    assert!(key.valid_at(created - chrono::Duration::days(1)).is_err());
+
+    // a revoked key is not valid
+    let (secret, _headers) =
+        SignedSecretKey::from_armor_single(read_file("./tests/key-with-password-123.asc"))
+            .expect("failed to parse key");
+    let revoked: SignedPublicKey = secret
+        .revoke(RevocationCode::KeyCompromised, "", || "123".into())
+        .unwrap()
+        .into();
+    assert!(revoked.valid_at(now).is_err());
+}
+
+#[test]
+fn test_subkey_selection() {
+    use pgp::composed::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+    use pgp::packet::RevocationCode;
+
+    let day = std::time::Duration::from_secs(24 * 60 * 60);
+    let key = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_certify(true)
+        .primary_user_id("Me <me@mail.com>".into())
+        .expiration(Some(day * 365))
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH)
+                .can_encrypt(true)
+                .expiration(Some(day * 30))
+                .build()
+                .unwrap(),
+        )
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::EdDSA)
+                .can_sign(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap()
+        .generate_signed()
+        .unwrap();
+    let public: SignedPublicKey = key.clone().into();
+    let created = *public.primary_key.created_at();
+    let now = chrono::Utc::now() + chrono::Duration::seconds(1);
+
+    let enc = public
+        .encryption_subkey(now)
+        .expect("missing encryption key");
+    assert_eq!(enc, &public.public_subkeys[0]);
+    let sign = public.signing_subkey(now).expect("missing signing key");
+    assert_eq!(sign, &public.public_subkeys[1]);
+
+    // the encryption subkey is expired
+    let later = created + chrono::Duration::days(31);
+    assert!(public.encryption_subkey(later).is_none());
+    assert_eq!(public.signing_subkey(later), Some(sign));
+
+    // the whole key is expired
+    let expired = created + chrono::Duration::days(366);
+    assert!(public.signing_subkey(expired).is_none());
+
+    // revoked subkeys are not used
+    let sign_id = sign.key_id();
+    let revoked: SignedPublicKey = key
+        .revoke_subkey(&sign_id, RevocationCode::KeyCompromised, "", String::new)
+        .unwrap()
+        .into();
+    assert!(revoked.signing_subkey(now).is_none());
+    assert!(revoked.encryption_subkey(now).is_some());
+}
+
+#[test]
+fn test_add_photo_id() {
+    use crate::common::gen_key;
+
+    let key = gen_key("Me <me@mail.com>");
+
+    // not a real image, but the start and end markers of one
+    let jpeg = vec![
+        0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0xFF, 0xD9,
+    ];
+    let photo = UserAttribute::new_jpeg(jpeg.clone());
+    assert_eq!(photo.jpeg(), Some(&jpeg[..]));
+
+    let key = key.add_user_attribute(photo.clone(), String::new).unwrap();
+    key.verify().unwrap();
+    assert!(key.clone().add_user_attribute(photo, String::new).is_err());
+
+    let armored = key.public_key().sign(&key, String::new).unwrap();
+    let armored = armored.to_armored_string(None).unwrap();
+    let (public, _) = SignedPublicKey::from_string(&armored).unwrap();
+    public.verify().unwrap();
+    assert_eq!(public.details.user_attributes.len(), 1);
+    assert_eq!(
+        public.details.user_attributes[0].attr.jpeg(),
+        Some(&jpeg[..])
+    );
+
+    let now = chrono::Utc::now() + chrono::Duration::seconds(1);
+    let valid = public.valid_at(now).unwrap();
+    assert_eq!(valid.user_attributes().len(), 1);
+    assert_eq!(
+        valid.user_attributes()[0].user_attribute(),
+        &public.details.user_attributes[0]
+    );
+}
+
+#[test]
+fn test_keyring() {
+    use pgp::composed::Keyring;
+
+    use crate::common::gen_key_with_subkey;
+
+    let alice = gen_key_with_subkey("Alice <Alice@Example.com>");
+    let bob: SignedPublicKey = gen_key_with_subkey("Bob <bob@example.com>").into();
+
+    // a key claiming bob's subkey and User ID, without binding them
+    let mut mallory: SignedPublicKey = gen_key_with_subkey("Mallory <mallory@example.com>").into();
+    mallory.public_subkeys.push(bob.public_subkeys[0].clone());
+    mallory.details.users.push(bob.details.users[0].clone());
+
+    let mut keyring = Keyring::new();
+    keyring.insert(mallory.clone()).unwrap();
+    assert!(keyring
+        .get_by_fingerprint(&bob.public_subkeys[0].fingerprint())
+        .is_none());
+    assert!(keyring.get_by_email("bob@example.com").is_empty());
+    keyring.insert(alice.clone().into()).unwrap();
+    keyring.insert(bob.clone()).unwrap();
+    assert_eq!(keyring.len(), 3);
+
+    let found = keyring.get_by_fingerprint(&bob.fingerprint()).unwrap();
+    assert_eq!(found, &bob);
+    let found = keyring
+        .get_by_fingerprint(&bob.public_subkeys[0].fingerprint())
+        .unwrap();
+    assert_eq!(found, &bob);
+    assert_eq!(keyring.get_by_key_id(&bob.key_id()), vec![&bob]);
+    assert_eq!(
+        keyring.get_by_key_id(&bob.public_subkeys[0].key_id()),
+        vec![&bob]
+    );
+    assert_eq!(keyring.get_by_email("BOB@example.com"), vec![&bob]);
+    assert!(keyring.get_by_email("carol@example.com").is_empty());
+
+    // inserting the same key again merges the copies
+    let alice = alice
+        .add_user_id("Alice <alice@work.example.com>", String::new)
+        .unwrap();
+    keyring.insert(alice.clone().into()).unwrap();
+    assert_eq!(keyring.len(), 3);
+    let found = keyring.get_by_email("alice@work.example.com");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].details.users.len(), 2);
+    assert_eq!(
+        keyring.get_by_email("alice@example.com")[0].fingerprint(),
+        alice.fingerprint()
+    );
+}
+
+#[test]
+fn test_parse_binary_keyring() {
+    use pgp::composed::from_keyring_many;
+    use pgp::packet::write_packet;
+
+    use crate::common::gen_key_with_subkey;
+
+    let gen_key = |id: &str| -> SignedPublicKey { gen_key_with_subkey(id).into() };
+    let alice = gen_key("Alice <alice@example.com>");
+    let bob = gen_key("Bob <bob@example.com>");
+    let carol = gen_key("Carol <carol@example.com>");
+
+    // old style trust packet, as written by GnuPG after keys and signatures
+    let trust = [0xB0, 0x02, 0x00, 0x00];
+
+    let mut keyring = Vec::new();
+    write_packet(&mut keyring, &alice.primary_key).unwrap();
+    keyring.extend_from_slice(&trust);
+    for user in &alice.details.users {
+        user.to_writer(&mut keyring).unwrap();
+        keyring.extend_from_slice(&trust);
+    }
+    for subkey in &alice.public_subkeys {
+        subkey.to_writer(&mut keyring).unwrap();
+        keyring.extend_from_slice(&trust);
+    }
+
+    // a certificate with a damaged user attribute
+    write_packet(&mut keyring, &bob.primary_key).unwrap();
+    bob.details.users[0].to_writer(&mut keyring).unwrap();
+    keyring.extend_from_slice(&[0xD1, 0x04, 0x03, 0x01, 0x00, 0x00]);
+    for subkey in &bob.public_subkeys {
+        subkey.to_writer(&mut keyring).unwrap();
+    }
+
+    carol.to_writer(&mut keyring).unwrap();
+    keyring.extend_from_slice(&trust);
+
+    let keys: Vec<_> = from_keyring_many(&keyring[..]).collect();
+    assert_eq!(keys.len(), 3);
+    match &keys[0] {
+        Ok(PublicOrSecret::Public(key)) => assert_eq!(key, &alice),
+        other => panic!("unexpected result {other:?}"),
+    }
+    assert!(keys[1].is_err());
+    match &keys[2] {
+        Ok(PublicOrSecret::Public(key)) => assert_eq!(key, &carol),
+        other => panic!("unexpected result {other:?}"),
+    }
+
+    #[cfg(feature = "rayon")]
+    {
+        let parallel = pgp::composed::from_keyring_parallel(&keyring);
+        assert_eq!(parallel.len(), 3);
+        match &parallel[0] {
+            Ok(PublicOrSecret::Public(key)) => {
+                assert_eq!(key, &alice);
+                key.verify_parallel().unwrap();
+            }
+            other => panic!("unexpected result {other:?}"),
+        }
+        assert!(parallel[1].is_err());
+        assert!(parallel[2].is_ok());
+    }
+}
+
+#[test]
+fn test_marker_and_trust_packets() {
+    use pgp::composed::Message;
+    use pgp::packet::write_packet;
+
+    use crate::common::gen_public_key;
+
+    let key = gen_public_key("Alice <alice@example.com>");
+
+    let marker = [0xA8, 0x03, b'P', b'G', b'P'];
+    // old style trust packet, as written by GnuPG after keys and signatures
+    let trust = [0xB0, 0x02, 0x00, 0x00];
+
+    let mut exported = marker.to_vec();
+    write_packet(&mut exported, &key.primary_key).unwrap();
+    exported.extend_from_slice(&trust);
+    for user in &key.details.users {
+        write_packet(&mut exported, &user.id).unwrap();
+        exported.extend_from_slice(&trust);
+        for sig in &user.signatures {
+            write_packet(&mut exported, sig).unwrap();
+            exported.extend_from_slice(&trust);
+        }
+    }
+    exported.extend_from_slice(&marker);
+
+    let parsed = SignedPublicKey::from_bytes(&exported[..]).unwrap();
+    assert_eq!(parsed, key);
+    let keys: Vec<_> = from_bytes_many(&exported[..]).collect();
+    assert_eq!(keys.len(), 1);
+    match &keys[0] {
+        Ok(PublicOrSecret::Public(parsed)) => assert_eq!(parsed, &key),
+        other => panic!("unexpected result {other:?}"),
+    }
+
+    let literal = Message::new_literal("hello.txt", "hello");
+    let mut message = marker.to_vec();
+    message.extend_from_slice(&trust);
+    literal.to_writer(&mut message).unwrap();
+    assert_eq!(Message::from_bytes(&message[..]).unwrap(), literal);
+
+    let mut reader = pgp::LiteralReader::new(&message[..]).unwrap();
+    let mut data = String::new();
+    reader.read_to_string(&mut data).unwrap();
+    assert_eq!(data, "hello");
+}
+
+#[test]
+fn test_experimental_packets() {
+    use pgp::packet::Experimental;
+    use pgp::types::Tag;
+
+    use crate::common::gen_key_with_subkey;
+
+    let mut key = gen_key_with_subkey("Alice <alice@example.com>");
+
+    let experimental =
+        |tag, body: &[u8]| Experimental::from_slice(Version::New, tag, body).unwrap();
+    key.details.experimental = vec![experimental(Tag::Private60, &[1, 2, 3])];
+    key.details.users[0].experimental = vec![
+        experimental(Tag::Private61, &[4]),
+        experimental(Tag::Private62, &[]),
+    ];
+    key.secret_subkeys[0].experimental = vec![experimental(Tag::Private63, &[5, 6])];
+
+    // each packet is kept with the component it follows
+    let bytes = key.to_bytes().unwrap();
+    let parsed = SignedSecretKey::from_bytes(&bytes[..]).unwrap();
+    assert_eq!(parsed, key);
+    assert_eq!(parsed.to_bytes().unwrap(), bytes);
+    parsed.verify().unwrap();
+
+    let public: SignedPublicKey = key.into();
+    assert_eq!(public.public_subkeys[0].experimental.len(), 1);
+    let bytes = public.to_bytes().unwrap();
+    let parsed = SignedPublicKey::from_bytes(&bytes[..]).unwrap();
+    assert_eq!(parsed, public);
+    assert_eq!(parsed.to_bytes().unwrap(), bytes);
+}
+
+#[test]
+fn test_parse_keyring_recovering() {
+    use pgp::packet::write_packet;
+    use pgp::types::Tag;
+
+    use crate::common::gen_public_key;
+
+    let alice = gen_public_key("Alice <alice@example.com>");
+    let bob = gen_public_key("Bob <bob@example.com>");
+
+    let mut keyring = alice.to_bytes().unwrap();
+    let garbage = keyring.len();
+    keyring.extend_from_slice(&[0x00, 0x01, 0x02]);
+    write_packet(&mut keyring, &bob.primary_key).unwrap();
+    bob.details.users[0].to_writer(&mut keyring).unwrap();
+    let damaged = keyring.len();
+    keyring.extend_from_slice(&[0xD1, 0x04, 0x03, 0x01, 0x00, 0x00]);
+
+    let (keys, warnings) = SignedPublicKey::from_bytes_many_recovering(&keyring[..]);
+    let keys: Vec<_> = keys
+        .into_iter()
+        .collect::<pgp::errors::Result<_>>()
+        .unwrap();
+    assert_eq!(keys, vec![alice, bob]);
+
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[0].offset(), garbage);
+    assert_eq!(warnings[0].length(), 3);
+    assert_eq!(warnings[0].tag(), None);
+    assert_eq!(warnings[1].offset(), damaged);
+    assert_eq!(warnings[1].tag(), Some(Tag::UserAttribute));
+}
+
+#[test]
+fn test_lint() {
+    use pgp::composed::{KeyType, SubkeyParamsBuilder};
+    use pgp::policy::{Policy, PolicyBuilder};
+
+    use crate::common::{encryption_subkey, key_params};
+
+    let secret = key_params("Me <me@mail.com>")
+        .subkey(encryption_subkey())
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::EdDSA)
+                .can_sign(true)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap()
+        .generate_signed()
+        .unwrap();
+    let key: SignedPublicKey = secret.clone().into();
+    let policy = Policy::default();
+    assert_eq!(key.lint(&policy), vec![]);
+
+    // self-signatures with rejected hash algorithms
+    let strict = PolicyBuilder::default()
+        .hash_algorithms(vec![HashAlgorithm::SHA3_512])
+        .build()
+        .unwrap();
+    let lints = key.lint(&strict);
+    assert!(!lints.is_empty());
+    assert!(lints
+        .iter()
+        .all(|lint| matches!(lint, Lint::WeakSelfSignature { .. })));
+
+    // a subkey with the binding signature of another one
+    let mut unbound = key.clone();
+    unbound.public_subkeys[0].signatures = key.public_subkeys[1].signatures.clone();
+    assert_eq!(
+        unbound.lint(&policy),
+        vec![Lint::UnboundSubkey {
+            key_id: key.public_subkeys[0].key_id()
+        }]
+    );
+
+    // a signing subkey without a back signature
+    let mut no_backsig = key.clone();
+    no_backsig.public_subkeys[1] = secret.secret_subkeys[1]
+        .public_key()
+        .with_backsig(None)
+        .sign(&secret, String::new)
+        .unwrap();
+    let lints = no_backsig.lint(&policy);
+    assert_eq!(
+        lints,
+        vec![Lint::MissingBackSignature {
+            key_id: key.public_subkeys[1].key_id()
+        }]
+    );
+    assert!(lints[0].to_string().contains("signing subkey"));
+
+    // a User ID without self-certification
+    let mut uncertified = key;
+    uncertified.details.users[0].signatures.clear();
+    assert!(uncertified
+        .lint(&policy)
+        .contains(&Lint::UncertifiedUserId {
+            id: "Me <me@mail.com>".into()
+        }));
+}
@@ -0,0 +1,74 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use pgp::armor;
+use pgp::packet::{Packet, PacketParser, PacketRefParser};
+use pgp::ser::Serialize;
+
+/// Collects the OpenPGP data from all test files, dearmoring armored files.
+fn corpus() -> Vec<(PathBuf, Vec<u8>)> {
+    fn walk(dir: &Path, files: &mut Vec<PathBuf>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                walk(&path, files);
+            } else if matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("asc" | "gpg" | "key" | "sig" | "pgp")
+            ) {
+                files.push(path);
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    walk(Path::new("./tests"), &mut files);
+    files.sort();
+
+    let mut corpus = Vec::new();
+    for path in files {
+        let raw = fs::read(&path).unwrap();
+        if raw.windows(10).any(|w| w == b"-----BEGIN") {
+            for block in armor::from_armor_many(&raw[..]).map_while(Result::ok) {
+                corpus.push((path.clone(), block.data));
+            }
+        } else {
+            corpus.push((path, raw));
+        }
+    }
+
+    corpus
+}
+
+#[test]
+fn test_packet_roundtrip_corpus() {
+    let mut count = 0;
+    for (path, input) in corpus() {
+        for packet in PacketRefParser::new(&input).map_while(Result::ok) {
+            // packets that can not be parsed at all are skipped
+            let Ok(parsed) = packet.to_packet() else {
+                continue;
+            };
+
+            let serialized = parsed.to_bytes().unwrap();
+            let reparsed = PacketRefParser::new(&serialized).next().unwrap().unwrap();
+            assert_eq!(
+                reparsed.body(),
+                packet.body(),
+                "{:?} in {} does not round trip",
+                packet.tag(),
+                path.display()
+            );
+            assert_eq!(reparsed.tag(), packet.tag());
+            assert_eq!(reparsed.packet_version(), packet.packet_version());
+
+            let parsed_again: Vec<Packet> = PacketParser::new(&serialized[..])
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(parsed_again.len(), 1);
+            assert_eq!(parsed_again[0].to_bytes().unwrap(), serialized);
+            count += 1;
+        }
+    }
+    assert!(count > 1000, "only {count} packets in the corpus");
+}
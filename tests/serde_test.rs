@@ -0,0 +1,98 @@
+#![cfg(feature = "serde")]
+
+use pgp::composed::{
+    Deserializable, KeyType, Message, SecretKeyParamsBuilder, SignedPublicKey, SignedSecretKey,
+};
+use pgp::crypto::hash::HashAlgorithm;
+use pgp::crypto::public_key::PublicKeyAlgorithm;
+use pgp::packet::{Subpacket, UserId, UserIdComponents};
+use pgp::types::{Fingerprint, KeyId, KeyTrait};
+use serde_json::json;
+
+const ALICE_PUB: &str = include_str!("autocrypt/alice@autocrypt.example.pub.asc");
+
+#[test]
+fn serde_fingerprint_key_id() {
+    let (key, _headers) = SignedPublicKey::from_string(ALICE_PUB).unwrap();
+
+    let fingerprint = serde_json::to_value(key.fingerprint()).unwrap();
+    assert_eq!(
+        fingerprint,
+        json!("EB85BB5FA33A75E15E944E63F231550C4F47E38E")
+    );
+    let parsed: Fingerprint = serde_json::from_value(fingerprint).unwrap();
+    assert_eq!(parsed, key.fingerprint());
+
+    let key_id = serde_json::to_value(key.key_id()).unwrap();
+    assert_eq!(key_id, json!("F231550C4F47E38E"));
+    let parsed: KeyId = serde_json::from_value(key_id).unwrap();
+    assert_eq!(parsed, key.key_id());
+
+    assert!(serde_json::from_value::<KeyId>(json!("not hex")).is_err());
+}
+
+#[test]
+fn serde_algorithms_and_user_ids() {
+    let alg = serde_json::to_string(&HashAlgorithm::SHA2_256).unwrap();
+    assert_eq!(
+        serde_json::from_str::<HashAlgorithm>(&alg).unwrap(),
+        HashAlgorithm::SHA2_256
+    );
+    let alg = serde_json::to_string(&PublicKeyAlgorithm::EdDSA).unwrap();
+    assert_eq!(
+        serde_json::from_str::<PublicKeyAlgorithm>(&alg).unwrap(),
+        PublicKeyAlgorithm::EdDSA
+    );
+
+    let components = UserId::from_str(Default::default(), "Alice <alice@example.com>").components();
+    let value = serde_json::to_value(&components).unwrap();
+    assert_eq!(
+        value,
+        json!({ "name": "Alice", "comment": null, "email": "alice@example.com" })
+    );
+    assert_eq!(
+        serde_json::from_value::<UserIdComponents>(value).unwrap(),
+        components
+    );
+}
+
+#[test]
+fn serde_subpackets() {
+    let (key, _headers) = SignedPublicKey::from_string(ALICE_PUB).unwrap();
+    let subpackets = &key.details.users[0].signatures[0].config.hashed_subpackets;
+    assert!(!subpackets.is_empty());
+
+    for subpacket in subpackets {
+        let value = serde_json::to_value(subpacket).unwrap();
+        assert!(value["body"].is_string());
+        let parsed: Subpacket = serde_json::from_value(value).unwrap();
+        assert_eq!(&parsed, subpacket);
+    }
+}
+
+#[test]
+fn serde_verification_result() {
+    let key: SignedSecretKey = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSA)
+        .can_sign(true)
+        .primary_user_id("Alice <alice@example.com>".into())
+        .build()
+        .unwrap()
+        .generate_signed()
+        .unwrap();
+    let public: SignedPublicKey = key.clone().into();
+
+    let msg = Message::new_literal("", "hello")
+        .sign(&key, String::new, HashAlgorithm::SHA2_256)
+        .unwrap();
+    let results = msg.verify_signatures(&[&public]).unwrap();
+    assert_eq!(results.len(), 1);
+
+    let value = serde_json::to_value(&results[0]).unwrap();
+    assert_eq!(value["valid"], json!(true));
+    assert_eq!(value["error"], json!(null));
+    assert_eq!(value["signer"], json!(format!("{:X}", public.key_id())));
+    assert!(value["created"].is_string());
+    assert_eq!(value["revocations"], json!([]));
+    assert_eq!(value["warnings"], json!([]));
+}
@@ -0,0 +1,76 @@
+//! Smoke tests for in-browser use, which only work on in-memory data.
+//!
+//! On `wasm32-unknown-unknown` they are run with
+//! `wasm-pack test --node -- --no-default-features --features wasm --test wasm_test`.
+
+mod common;
+
+use pgp::composed::{Deserializable, Message, SignedPublicKey, StandaloneSignature};
+use pgp::crypto::hash::HashAlgorithm;
+use pgp::crypto::sym::SymmetricKeyAlgorithm;
+use pgp::types::{KeyTrait, StringToKey};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+use common::gen_key_with_subkey;
+
+const ALICE_PUB: &str = include_str!("autocrypt/alice@autocrypt.example.pub.asc");
+
+#[test]
+fn wasm_parse_key() {
+    let (key, _headers) = SignedPublicKey::from_string(ALICE_PUB).unwrap();
+    key.verify().unwrap();
+    assert_eq!(
+        format!("{:X}", key.fingerprint()),
+        "EB85BB5FA33A75E15E944E63F231550C4F47E38E"
+    );
+}
+
+#[test]
+fn wasm_encrypt_decrypt() {
+    let key = gen_key_with_subkey("Alice <alice@example.com>");
+    let public: SignedPublicKey = key.clone().into();
+
+    let msg = Message::new_literal("", "hello from the browser")
+        .encrypt_to_certs(&mut rand::thread_rng(), &[&public])
+        .unwrap();
+    let armored = msg.to_armored_string(None).unwrap();
+
+    let (msg, _headers) = Message::from_string(&armored).unwrap();
+    let (mut decrypter, _ids) = msg.decrypt(String::new, &[&key]).unwrap();
+    let decrypted = decrypter.next().unwrap().unwrap();
+    assert_eq!(
+        decrypted.get_content().unwrap().unwrap(),
+        b"hello from the browser"
+    );
+
+    let mut rng = rand::thread_rng();
+    let s2k = StringToKey::new_default(&mut rng);
+    let msg = Message::new_literal("", "hello")
+        .encrypt_with_password(&mut rng, s2k, SymmetricKeyAlgorithm::AES128, || {
+            "secret".into()
+        })
+        .unwrap();
+    let decrypted = msg
+        .decrypt_with_password(|| "secret".into())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(decrypted.get_content().unwrap().unwrap(), b"hello");
+}
+
+#[test]
+fn wasm_sign_verify() {
+    let key = gen_key_with_subkey("Alice <alice@example.com>");
+    let public: SignedPublicKey = key.clone().into();
+
+    let signature =
+        pgp::composed::sign_detached(&b"hello"[..], &key, String::new, HashAlgorithm::SHA2_256)
+            .unwrap();
+    let armored = signature.to_armored_string(None).unwrap();
+
+    let (signature, _headers) = StandaloneSignature::from_string(&armored).unwrap();
+    signature.verify(&public, b"hello").unwrap();
+    assert!(signature.verify(&public, b"hello!").is_err());
+}